@@ -6,7 +6,8 @@
 // This file is responsible for:
 // 1. Loading Hardhat plugins and environment variables.
 // 2. Defining the Solidity compiler version and optimizer settings.
-// 3. Configuring Polygon Amoy testnet, optional Polygon mainnet, and local Hardhat.
+// 3. Configuring Polygon Amoy testnet, optional Polygon mainnet, local Hardhat,
+//    and a separately running local dev chain.
 // 4. Supplying the deployment wallet private key to configured networks.
 // 5. Configuring Polygonscan source-code verification.
 //
@@ -38,7 +39,10 @@ const {
   POLYGON_CHAIN_ID,
 
   // Polygonscan API key used by Hardhat's verification task.
-  POLYGONSCAN_API_KEY
+  POLYGONSCAN_API_KEY,
+
+  // RPC endpoint of the local dev chain used by integration tests.
+  LOCAL_RPC_HTTP
 } = process.env;
 
 module.exports = {
@@ -106,6 +110,14 @@ module.exports = {
     // Accounts are generated automatically and must never be used on public chains.
     hardhat: {
       chainId: 31337
+    },
+
+    // A local dev chain started separately (`npx hardhat node` or `anvil`),
+    // used by the x402 integration test. Its pre-funded accounts are the
+    // well-known development keys and are read from the node itself.
+    localhost: {
+      url: LOCAL_RPC_HTTP || "http://127.0.0.1:8545",
+      chainId: 31337
     }
   },
 
@@ -0,0 +1,177 @@
+/**
+ * Chain-side helper for the x402 integration test against a local dev chain
+ * (`npx hardhat node` or `anvil`). Never point it at a public network.
+ *
+ * Hardhat scripts take no arguments, so the action and its inputs come from
+ * the environment. Every action prints one JSON line as its last output line.
+ *
+ * Run examples:
+ *   X402_LOCAL_ACTION=accounts npx hardhat run --network localhost scripts/x402_local.js
+ *   X402_LOCAL_ACTION=deploy   npx hardhat run --network localhost scripts/x402_local.js
+ *   X402_LOCAL_ACTION=pay X402_INVOICE="$(cat start.json)" \
+ *     npx hardhat run --network localhost scripts/x402_local.js
+ *   X402_LOCAL_ACTION=warp X402_WARP_TO=1900000000 \
+ *     npx hardhat run --network localhost scripts/x402_local.js
+ *
+ * Actions:
+ *   accounts   admin (signer 0), payer (signer 1) and creator (signer 2) addresses
+ *   deploy     deploy X402Splitter with signer 0 (or ADMIN_WALLET) as admin
+ *   pay        pay an invoice returned by POST /api/pay/x402/start
+ *   warp       set the next block timestamp to X402_WARP_TO and mine it
+ *
+ * `pay` environment variables:
+ *   X402_INVOICE          JSON response of /api/pay/x402/start (required)
+ *   X402_PAY_WEI          amount sent (default: amount_wei of the invoice)
+ *   X402_SIGN_MIN_WEI     re-sign the invoice with this minimum using the admin
+ *                         key, e.g. to simulate an outdated authorization
+ *   X402_ADMIN_PRIVKEY    admin key for re-signing (default: signer 0)
+ *   X402_PAYER_INDEX=1    node account that pays; must be the invoice payer
+ */
+
+const hre = require("hardhat");
+
+function out(data) {
+  console.log(JSON.stringify(data));
+}
+
+function revertReason(e) {
+  return e.reason || e.shortMessage || e.message || String(e);
+}
+
+async function accounts() {
+  const [admin, payer, creator] = await hre.ethers.getSigners();
+  out({
+    ok: true,
+    admin: await admin.getAddress(),
+    payer: await payer.getAddress(),
+    creator: await creator.getAddress(),
+  });
+}
+
+async function deploy() {
+  const [signer] = await hre.ethers.getSigners();
+  const admin = process.env.ADMIN_WALLET || (await signer.getAddress());
+  const Factory = await hre.ethers.getContractFactory("X402Splitter", signer);
+  const contract = await Factory.deploy(admin);
+  await contract.waitForDeployment();
+  out({ ok: true, address: await contract.getAddress(), admin });
+}
+
+// Same payload as X402Splitter._verify and the server's PayAuthorization.
+async function signAuthorization(admin, inv, token, minWei, payer, chainId) {
+  const { ethers } = hre;
+  const digest = ethers.keccak256(
+    ethers.AbiCoder.defaultAbiCoder().encode(
+      [
+        "bytes32", "address", "uint256", "address", "uint16",
+        "bytes32", "address", "uint256", "address", "uint256",
+      ],
+      [
+        inv.invoice_uid_bytes32,
+        token,
+        minWei,
+        inv.creator_wallet,
+        inv.split_creator_bp,
+        ethers.keccak256(ethers.toUtf8Bytes(inv.video_id)),
+        payer,
+        inv.deadline,
+        inv.x402_contract,
+        chainId,
+      ]
+    )
+  );
+  return ethers.Signature.from(await admin.signMessage(ethers.getBytes(digest)));
+}
+
+async function pay() {
+  const { ethers } = hre;
+  if (!process.env.X402_INVOICE) throw new Error("X402_INVOICE not set");
+  const inv = JSON.parse(process.env.X402_INVOICE);
+  if (!inv.ok) throw new Error(`invoice is not ok: ${inv.error}`);
+
+  const signers = await ethers.getSigners();
+  const payer = signers[Number(process.env.X402_PAYER_INDEX || 1)];
+  const payerAddr = await payer.getAddress();
+  const chainId = (await ethers.provider.getNetwork()).chainId;
+  const token = inv.token_address || ethers.ZeroAddress;
+  const payWei = BigInt(process.env.X402_PAY_WEI || inv.amount_wei);
+
+  let minWei = BigInt(inv.min_amount_wei);
+  let sig = { v: inv.v, r: inv.r, s: inv.s };
+  if (process.env.X402_SIGN_MIN_WEI) {
+    const admin = process.env.X402_ADMIN_PRIVKEY
+      ? new ethers.Wallet(process.env.X402_ADMIN_PRIVKEY, ethers.provider)
+      : signers[0];
+    minWei = BigInt(process.env.X402_SIGN_MIN_WEI);
+    sig = await signAuthorization(admin, inv, token, minWei, payerAddr, chainId);
+  }
+
+  const contract = await ethers.getContractAt("X402Splitter", inv.x402_contract, payer);
+  try {
+    let tx;
+    if (token === ethers.ZeroAddress) {
+      tx = await contract.payNativeSigned(
+        inv.invoice_uid_bytes32,
+        inv.creator_wallet,
+        inv.split_creator_bp,
+        inv.video_id,
+        minWei,
+        inv.deadline,
+        sig.v,
+        sig.r,
+        sig.s,
+        { value: payWei }
+      );
+    } else {
+      const erc20 = await ethers.getContractAt(
+        ["function approve(address spender, uint256 amount) returns (bool)"],
+        token,
+        payer
+      );
+      await (await erc20.approve(inv.x402_contract, payWei)).wait();
+      tx = await contract.payERC20Signed(
+        inv.invoice_uid_bytes32,
+        token,
+        payWei,
+        inv.creator_wallet,
+        inv.split_creator_bp,
+        inv.video_id,
+        minWei,
+        inv.deadline,
+        sig.v,
+        sig.r,
+        sig.s
+      );
+    }
+    const receipt = await tx.wait();
+    out({ ok: true, tx_hash: receipt.hash, block: receipt.blockNumber });
+  } catch (e) {
+    out({ ok: false, error: revertReason(e) });
+  }
+}
+
+async function warp() {
+  const to = Number(process.env.X402_WARP_TO);
+  if (!to) throw new Error("X402_WARP_TO not set");
+  await hre.network.provider.send("evm_setNextBlockTimestamp", [to]);
+  await hre.network.provider.send("evm_mine", []);
+  const block = await hre.ethers.provider.getBlock("latest");
+  out({ ok: true, timestamp: block.timestamp });
+}
+
+async function main() {
+  if (hre.network.name !== "localhost" && hre.network.name !== "hardhat") {
+    throw new Error(`refusing to run on network ${hre.network.name}`);
+  }
+  const actions = { accounts, deploy, pay, warp };
+  const action = actions[process.env.X402_LOCAL_ACTION];
+  if (!action) {
+    throw new Error(`X402_LOCAL_ACTION must be one of: ${Object.keys(actions).join(", ")}`);
+  }
+  await action();
+}
+
+main().catch((e) => {
+  out({ ok: false, error: revertReason(e) });
+  process.exitCode = 1;
+});
@@ -0,0 +1,44 @@
+-- 036_video_imports.sql
+-- Bulk video import from remote HTTPS URLs (creators) or a server-local
+-- directory (admins). Every source file becomes one item row so the creator
+-- can see exactly which files were imported, skipped, or failed.
+
+CREATE TABLE IF NOT EXISTS video_import_jobs (
+  id TEXT PRIMARY KEY,
+  owner_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  source_kind TEXT NOT NULL,              -- url | directory
+  source TEXT NOT NULL DEFAULT '',        -- directory path, or '' for URL lists
+  price_cents BIGINT NOT NULL DEFAULT 0,
+  status TEXT NOT NULL DEFAULT 'pending', -- pending | running | completed | completed_with_errors | failed
+  total_items BIGINT NOT NULL DEFAULT 0,
+  imported_items BIGINT NOT NULL DEFAULT 0,
+  failed_items BIGINT NOT NULL DEFAULT 0,
+  skipped_items BIGINT NOT NULL DEFAULT 0,
+  last_error TEXT,
+  started_by_user_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  started_at TIMESTAMPTZ,
+  completed_at TIMESTAMPTZ,
+  CONSTRAINT video_import_jobs_source_kind_check CHECK (source_kind IN ('url', 'directory')),
+  CONSTRAINT video_import_jobs_price_check CHECK (price_cents >= 0)
+);
+
+CREATE INDEX IF NOT EXISTS idx_video_import_jobs_owner_created_at
+  ON video_import_jobs (owner_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS video_import_job_items (
+  id TEXT PRIMARY KEY,
+  job_id TEXT NOT NULL REFERENCES video_import_jobs(id) ON DELETE CASCADE,
+  position INT NOT NULL DEFAULT 0,
+  source TEXT NOT NULL,                   -- remote URL or absolute file path
+  title TEXT NOT NULL DEFAULT '',
+  status TEXT NOT NULL DEFAULT 'pending', -- pending | downloading | imported | skipped | failed
+  video_id TEXT REFERENCES videos(id) ON DELETE SET NULL,
+  bytes BIGINT NOT NULL DEFAULT 0,
+  error_message TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_video_import_job_items_job_position
+  ON video_import_job_items (job_id, position);
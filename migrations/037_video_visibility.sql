@@ -0,0 +1,34 @@
+-- 037_video_visibility.sql
+-- Per-video visibility plus an optional publish window.
+--
+--   draft     only the owner (and admins) can see or play it
+--   unlisted  playable by anyone who has the link, never listed or federated
+--   private   playable only by the owner, admins, and allowlisted users
+--   public    listed in the catalog, public profiles, and federation
+--
+-- publish_at / unpublish_at bound when a public or unlisted video is live.
+-- Existing rows stay 'public' so current catalogs do not change.
+
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS visibility TEXT NOT NULL DEFAULT 'public';
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS publish_at TIMESTAMPTZ;
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS unpublish_at TIMESTAMPTZ;
+
+DO $$
+BEGIN
+  IF NOT EXISTS (
+    SELECT 1 FROM pg_constraint WHERE conname = 'videos_visibility_check'
+  ) THEN
+    ALTER TABLE videos ADD CONSTRAINT videos_visibility_check
+      CHECK (visibility IN ('draft', 'unlisted', 'private', 'public'));
+  END IF;
+  IF NOT EXISTS (
+    SELECT 1 FROM pg_constraint WHERE conname = 'videos_publish_window_check'
+  ) THEN
+    ALTER TABLE videos ADD CONSTRAINT videos_publish_window_check
+      CHECK (publish_at IS NULL OR unpublish_at IS NULL OR unpublish_at > publish_at);
+  END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_videos_listed
+  ON videos (created_at DESC)
+  WHERE visibility = 'public' AND hls_ready = TRUE;
@@ -0,0 +1,20 @@
+-- 038_video_catalog_search.sql
+-- Catalog browsing: full-text search over title + description, and indexes
+-- backing the cursor-paginated sort orders of GET /api/videos.
+--
+-- The 'simple' text search configuration is used on purpose: titles are a mix
+-- of Indonesian and English, so no language-specific stemming is applied.
+
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS search_tsv tsvector
+  GENERATED ALWAYS AS (
+    to_tsvector('simple', coalesce(title, '') || ' ' || coalesce(description, ''))
+  ) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_videos_search_tsv
+  ON videos USING GIN (search_tsv);
+
+CREATE INDEX IF NOT EXISTS idx_videos_price_id
+  ON videos (price_cents, id);
+
+CREATE INDEX IF NOT EXISTS idx_videos_created_at_id
+  ON videos (created_at DESC, id DESC);
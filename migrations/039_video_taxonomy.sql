@@ -0,0 +1,52 @@
+-- 039_video_taxonomy.sql
+-- Grouping for the catalog:
+--   categories     admin-managed, at most one per video
+--   video_tags     free-form creator tags (normalised lowercase, no '#')
+--   series         creator-defined ordered collections / playlists
+--   series_videos  membership with an explicit position inside the series
+
+CREATE TABLE IF NOT EXISTS categories (
+  id TEXT PRIMARY KEY,
+  slug TEXT NOT NULL UNIQUE,
+  name TEXT NOT NULL,
+  description TEXT NOT NULL DEFAULT '',
+  position INT NOT NULL DEFAULT 0,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS category_id TEXT
+  REFERENCES categories(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_videos_category ON videos (category_id);
+
+CREATE TABLE IF NOT EXISTS video_tags (
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  tag TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (video_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS idx_video_tags_tag ON video_tags (tag);
+
+CREATE TABLE IF NOT EXISTS series (
+  id TEXT PRIMARY KEY,
+  owner_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  title TEXT NOT NULL,
+  description TEXT NOT NULL DEFAULT '',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_series_owner ON series (owner_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS series_videos (
+  series_id TEXT NOT NULL REFERENCES series(id) ON DELETE CASCADE,
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  position INT NOT NULL,
+  added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (series_id, video_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_series_videos_order ON series_videos (series_id, position);
+CREATE INDEX IF NOT EXISTS idx_series_videos_video ON series_videos (video_id);
@@ -0,0 +1,68 @@
+-- 041_bundles.sql
+-- Bundles: several videos (a fixed set, or a whole series) sold at one price.
+--   bundles                  creator-owned offer; `series_id` set for a series pass
+--   bundle_videos            members of a fixed-set bundle
+--   bundle_purchases         one row per buyer and bundle (the purchase is recorded once)
+--   bundle_purchase_videos   videos covered at purchase time; series passes bought
+--                            with `include_future` also cover videos added to the series later
+
+CREATE TABLE IF NOT EXISTS bundles (
+  id TEXT PRIMARY KEY,
+  owner_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  title TEXT NOT NULL,
+  description TEXT NOT NULL DEFAULT '',
+  price_cents BIGINT NOT NULL CHECK (price_cents > 0),
+  series_id TEXT REFERENCES series(id) ON DELETE SET NULL,
+  include_future BOOLEAN NOT NULL DEFAULT FALSE,
+  active BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_bundles_owner ON bundles (owner_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_bundles_series ON bundles (series_id);
+
+CREATE TABLE IF NOT EXISTS bundle_videos (
+  bundle_id TEXT NOT NULL REFERENCES bundles(id) ON DELETE CASCADE,
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  position INT NOT NULL DEFAULT 0,
+  PRIMARY KEY (bundle_id, video_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_bundle_videos_video ON bundle_videos (video_id);
+
+CREATE TABLE IF NOT EXISTS bundle_purchases (
+  id BIGSERIAL PRIMARY KEY,
+  bundle_id TEXT NOT NULL REFERENCES bundles(id) ON DELETE RESTRICT,
+  user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  amount_cents BIGINT NOT NULL,
+  source TEXT NOT NULL,          -- wallet | fiat:<provider>
+  payment_ref TEXT,              -- fiat invoice uid
+  include_future BOOLEAN NOT NULL DEFAULT FALSE,  -- copied from the bundle at purchase
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  UNIQUE (bundle_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_bundle_purchases_user ON bundle_purchases (user_id);
+
+CREATE TABLE IF NOT EXISTS bundle_purchase_videos (
+  purchase_id BIGINT NOT NULL REFERENCES bundle_purchases(id) ON DELETE CASCADE,
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  PRIMARY KEY (purchase_id, video_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_bundle_purchase_videos_video ON bundle_purchase_videos (video_id);
+
+-- Fiat checkout for bundles: the invoice references the bundle instead of a video.
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS bundle_id TEXT REFERENCES bundles(id);
+ALTER TABLE fiat_invoices ALTER COLUMN video_id DROP NOT NULL;
+
+DO $$
+BEGIN
+  IF NOT EXISTS (
+    SELECT 1 FROM pg_constraint WHERE conname = 'fiat_invoices_item_check'
+  ) THEN
+    ALTER TABLE fiat_invoices ADD CONSTRAINT fiat_invoices_item_check
+      CHECK (video_id IS NOT NULL OR bundle_id IS NOT NULL);
+  END IF;
+END $$;
@@ -0,0 +1,83 @@
+-- 042_creator_subscriptions.sql
+-- Creator subscription tiers with recurring billing.
+--   subscription_tiers       creator-defined plan; unlocks all of the creator's
+--                            videos or only videos carrying one of the tier tags
+--   subscription_tier_tags   tags unlocked by a `tagged` tier
+--   subscriptions            one buyer on one tier
+--   subscription_charges     every renewal attempt (wallet debit or plugin charge)
+--
+-- Subscription status:
+--   pending    first plugin checkout not paid yet (no access)
+--   active     paid through current_period_end
+--   past_due   renewal failed; access continues until grace_until
+--   canceled   buyer cancelled; access continues until current_period_end
+--   expired    no access
+
+CREATE TABLE IF NOT EXISTS subscription_tiers (
+  id TEXT PRIMARY KEY,
+  creator_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name TEXT NOT NULL,
+  description TEXT NOT NULL DEFAULT '',
+  price_cents BIGINT NOT NULL CHECK (price_cents > 0),
+  interval_days INT NOT NULL DEFAULT 30 CHECK (interval_days > 0),
+  access_scope TEXT NOT NULL DEFAULT 'all' CHECK (access_scope IN ('all', 'tagged')),
+  active BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscription_tiers_creator ON subscription_tiers (creator_id);
+
+CREATE TABLE IF NOT EXISTS subscription_tier_tags (
+  tier_id TEXT NOT NULL REFERENCES subscription_tiers(id) ON DELETE CASCADE,
+  tag TEXT NOT NULL,
+  PRIMARY KEY (tier_id, tag)
+);
+
+CREATE TABLE IF NOT EXISTS subscriptions (
+  id TEXT PRIMARY KEY,
+  tier_id TEXT NOT NULL REFERENCES subscription_tiers(id) ON DELETE RESTRICT,
+  user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  status TEXT NOT NULL DEFAULT 'pending'
+    CHECK (status IN ('pending', 'active', 'past_due', 'canceled', 'expired')),
+  billing_method TEXT NOT NULL,        -- wallet | plugin
+  provider TEXT,                       -- payment plugin key when billing_method = 'plugin'
+  provider_payment_ref TEXT,           -- reference used for off-session renewals
+  current_period_start TIMESTAMPTZ,
+  current_period_end TIMESTAMPTZ,
+  grace_until TIMESTAMPTZ,
+  failed_attempts INT NOT NULL DEFAULT 0,
+  last_attempt_at TIMESTAMPTZ,
+  canceled_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- At most one live subscription per buyer and tier.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_subscriptions_live
+  ON subscriptions (tier_id, user_id)
+  WHERE status IN ('pending', 'active', 'past_due', 'canceled');
+CREATE INDEX IF NOT EXISTS idx_subscriptions_user ON subscriptions (user_id, status);
+CREATE INDEX IF NOT EXISTS idx_subscriptions_due ON subscriptions (status, current_period_end);
+
+CREATE TABLE IF NOT EXISTS subscription_charges (
+  id BIGSERIAL PRIMARY KEY,
+  subscription_id TEXT NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+  amount_cents BIGINT NOT NULL,
+  method TEXT NOT NULL,                -- wallet | fiat:<provider>
+  status TEXT NOT NULL,                -- paid | failed
+  period_start TIMESTAMPTZ,
+  period_end TIMESTAMPTZ,
+  reference TEXT,
+  error TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscription_charges_sub ON subscription_charges (subscription_id, created_at DESC);
+
+-- First plugin checkout of a subscription is a fiat invoice tied to it.
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS subscription_id TEXT REFERENCES subscriptions(id);
+
+ALTER TABLE fiat_invoices DROP CONSTRAINT IF EXISTS fiat_invoices_item_check;
+ALTER TABLE fiat_invoices ADD CONSTRAINT fiat_invoices_item_check
+  CHECK (video_id IS NOT NULL OR bundle_id IS NOT NULL OR subscription_id IS NOT NULL);
@@ -0,0 +1,26 @@
+-- 043_video_rentals.sql
+-- Time-limited rentals next to permanent purchases.
+--   videos.rental_price_cents   NULL = the video cannot be rented
+--   videos.rental_hours         viewing window once the rental is started
+--
+-- A rental is a `purchases` row with `rental_hours` set. At purchase time
+-- `expires_at` is set to the last day the rental can be started; the first
+-- playback starts the window and moves `expires_at` to
+-- LEAST(expires_at, first play + rental_hours). Permanent purchases keep
+-- `expires_at` NULL.
+
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS rental_price_cents BIGINT
+  CHECK (rental_price_cents IS NULL OR rental_price_cents > 0);
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS rental_hours INT NOT NULL DEFAULT 48
+  CHECK (rental_hours BETWEEN 1 AND 720);
+
+ALTER TABLE purchases ADD COLUMN IF NOT EXISTS rental_hours INT;
+ALTER TABLE purchases ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+ALTER TABLE purchases ADD COLUMN IF NOT EXISTS rental_started_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_purchases_rentals
+  ON purchases (user_id, video_id, expires_at) WHERE rental_hours IS NOT NULL;
+
+-- Checkouts remember the rental window they were sold with.
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS rental_hours INT;
+ALTER TABLE x402_invoices ADD COLUMN IF NOT EXISTS rental_hours INT;
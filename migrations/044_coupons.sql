@@ -0,0 +1,50 @@
+-- 044_coupons.sql
+-- Coupon codes for video purchases and rentals.
+--   coupons              issued by a creator (their videos only) or by an admin
+--                        (`creator_id` NULL: any creator); `video_id` narrows a
+--                        coupon to one video
+--   coupon_redemptions   one row per checkout that used a coupon
+--
+-- Redemption status:
+--   pending    fiat / x402 checkout started; holds a use for an hour, after
+--              which an unpaid checkout no longer counts against the limits
+--   redeemed   payment completed
+
+CREATE TABLE IF NOT EXISTS coupons (
+  id TEXT PRIMARY KEY,
+  code TEXT NOT NULL,
+  issuer_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  creator_id TEXT REFERENCES users(id) ON DELETE CASCADE,
+  video_id TEXT REFERENCES videos(id) ON DELETE CASCADE,
+  kind TEXT NOT NULL CHECK (kind IN ('percent', 'fixed')),
+  value BIGINT NOT NULL CHECK (value > 0),   -- percent (1..99) or cents off
+  max_redemptions INT CHECK (max_redemptions IS NULL OR max_redemptions > 0),
+  per_user_limit INT NOT NULL DEFAULT 1 CHECK (per_user_limit > 0),
+  starts_at TIMESTAMPTZ,
+  ends_at TIMESTAMPTZ,
+  active BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  CHECK (kind <> 'percent' OR value < 100)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_coupons_code ON coupons (LOWER(code));
+CREATE INDEX IF NOT EXISTS idx_coupons_issuer ON coupons (issuer_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS coupon_redemptions (
+  id BIGSERIAL PRIMARY KEY,
+  coupon_id TEXT NOT NULL REFERENCES coupons(id) ON DELETE CASCADE,
+  user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  video_id TEXT NOT NULL,
+  payment_method TEXT NOT NULL,        -- wallet | x402 | fiat:<provider>
+  reference TEXT,                      -- invoice uid for fiat / x402
+  original_cents BIGINT NOT NULL,
+  discount_cents BIGINT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'redeemed')),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  redeemed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_coupon ON coupon_redemptions (coupon_id, status);
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_user ON coupon_redemptions (coupon_id, user_id);
+CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_ref ON coupon_redemptions (reference);
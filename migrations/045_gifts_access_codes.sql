@@ -0,0 +1,73 @@
+-- 045_gifts_access_codes.sql
+-- Gift purchases and redeemable access codes.
+--   video_gifts          a video bought by one user for another
+--   access_code_batches  one generation run of N codes for a video
+--   access_codes         single-use codes; redeeming one grants the video
+--   access_code_events   audit trail shown to the video's creator
+--
+-- Access code status:
+--   active     can be redeemed
+--   redeemed   used; redeemed_by holds access through purchase_id
+--   revoked    unusable; if it had been redeemed the grant was removed too
+
+CREATE TABLE IF NOT EXISTS video_gifts (
+  id BIGSERIAL PRIMARY KEY,
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  buyer_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  recipient_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  amount_cents BIGINT NOT NULL,
+  method TEXT NOT NULL,                -- wallet | fiat:<provider>
+  reference TEXT,                      -- fiat invoice uid
+  message TEXT NOT NULL DEFAULT '',
+  purchase_id BIGINT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_video_gifts_recipient ON video_gifts (recipient_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_video_gifts_buyer ON video_gifts (buyer_id, created_at DESC);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_video_gifts_reference
+  ON video_gifts (reference) WHERE reference IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS access_code_batches (
+  id TEXT PRIMARY KEY,
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  issuer_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  quantity INT NOT NULL CHECK (quantity > 0),
+  amount_cents BIGINT NOT NULL DEFAULT 0,  -- 0 when the creator issues their own codes
+  label TEXT NOT NULL DEFAULT '',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_access_code_batches_video ON access_code_batches (video_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS access_codes (
+  code TEXT PRIMARY KEY,
+  batch_id TEXT NOT NULL REFERENCES access_code_batches(id) ON DELETE CASCADE,
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'redeemed', 'revoked')),
+  redeemed_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+  redeemed_at TIMESTAMPTZ,
+  purchase_id BIGINT,
+  revoked_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+  revoked_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_access_codes_batch ON access_codes (batch_id);
+CREATE INDEX IF NOT EXISTS idx_access_codes_video ON access_codes (video_id, status);
+
+CREATE TABLE IF NOT EXISTS access_code_events (
+  id BIGSERIAL PRIMARY KEY,
+  code TEXT NOT NULL REFERENCES access_codes(code) ON DELETE CASCADE,
+  video_id TEXT NOT NULL,
+  actor_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+  action TEXT NOT NULL CHECK (action IN ('issued', 'redeemed', 'revoked')),
+  detail TEXT NOT NULL DEFAULT '',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_access_code_events_video ON access_code_events (video_id, created_at DESC);
+
+-- A fiat checkout can be a gift; the webhook grants the recipient instead of the buyer.
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS gift_recipient_id TEXT REFERENCES users(id);
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS gift_message TEXT;
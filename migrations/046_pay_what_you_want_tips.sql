@@ -0,0 +1,34 @@
+-- 046_pay_what_you_want_tips.sql
+-- Pay-what-you-want videos and creator tips.
+--   videos.pay_what_you_want  buyers choose the amount; price_cents is the minimum
+--   tips                      standalone tips to a creator
+--
+-- Tip status:
+--   pending   plugin checkout not paid yet
+--   paid      creator share credited to the creator's wallet
+
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS pay_what_you_want BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS tips (
+  id TEXT PRIMARY KEY,
+  creator_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  tipper_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  amount_cents BIGINT NOT NULL CHECK (amount_cents > 0),
+  creator_cents BIGINT NOT NULL DEFAULT 0,
+  method TEXT NOT NULL,                -- wallet | fiat:<provider>
+  message TEXT NOT NULL DEFAULT '',
+  status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'paid')),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  paid_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_tips_creator ON tips (creator_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_tips_tipper ON tips (tipper_id, created_at DESC);
+
+-- A plugin tip checkout is a fiat invoice tied to the tip.
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS tip_id TEXT REFERENCES tips(id);
+
+ALTER TABLE fiat_invoices DROP CONSTRAINT IF EXISTS fiat_invoices_item_check;
+ALTER TABLE fiat_invoices ADD CONSTRAINT fiat_invoices_item_check
+  CHECK (video_id IS NOT NULL OR bundle_id IS NOT NULL OR subscription_id IS NOT NULL
+         OR tip_id IS NOT NULL);
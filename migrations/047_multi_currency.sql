@@ -0,0 +1,41 @@
+-- 047_multi_currency.sql
+-- Per-video currency, exchange rates, and locked checkout quotes.
+--   videos.currency   ISO 4217 code of price_cents / rental_price_cents (minor units)
+--   exchange_rates    units of a currency per 1 USD, refreshed from the rate provider
+--   rate_quotes       a rate locked for one buyer until expires_at
+--
+-- Wallet balances, bundles, tiers, tips, and x402 stay in USD. Fiat invoices
+-- record the charged amount in their own currency plus the USD equivalent used
+-- for commissions and ledgers.
+
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS currency TEXT NOT NULL DEFAULT 'USD';
+
+CREATE TABLE IF NOT EXISTS exchange_rates (
+  currency TEXT PRIMARY KEY,
+  per_usd DOUBLE PRECISION NOT NULL CHECK (per_usd > 0),
+  source TEXT NOT NULL,
+  fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO exchange_rates (currency, per_usd, source)
+VALUES ('USD', 1, 'base')
+ON CONFLICT (currency) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS rate_quotes (
+  id TEXT PRIMARY KEY,
+  user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  base_currency TEXT NOT NULL,
+  quote_currency TEXT NOT NULL,
+  rate DOUBLE PRECISION NOT NULL CHECK (rate > 0),  -- quote units per base unit
+  expires_at TIMESTAMPTZ NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_rate_quotes_user ON rate_quotes (user_id, created_at DESC);
+
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS quote_id TEXT REFERENCES rate_quotes(id);
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS base_currency TEXT;
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS base_amount BIGINT;
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS usd_cents BIGINT;
+
+ALTER TABLE x402_invoices ADD COLUMN IF NOT EXISTS quote_id TEXT REFERENCES rate_quotes(id);
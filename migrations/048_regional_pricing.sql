@@ -0,0 +1,28 @@
+-- 048_regional_pricing.sql
+-- Per-country prices and geo-restrictions per video.
+--   video_region_prices  price override for buyers in one country (video currency)
+--   video_geo_rules      allow / deny list entries
+--
+-- Countries are ISO 3166-1 alpha-2 codes resolved from the buyer's IP with the
+-- offline GeoIP file (GEOIP_FILE). With any `allow` rule the video is only
+-- available in the listed countries; a `deny` rule blocks one country. Both
+-- apply to checkout and to playback of videos already bought.
+
+CREATE TABLE IF NOT EXISTS video_region_prices (
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  country TEXT NOT NULL CHECK (country ~ '^[A-Z]{2}$'),
+  price_cents BIGINT NOT NULL CHECK (price_cents > 0),
+  rental_price_cents BIGINT CHECK (rental_price_cents > 0),
+  PRIMARY KEY (video_id, country)
+);
+
+CREATE TABLE IF NOT EXISTS video_geo_rules (
+  video_id TEXT NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+  country TEXT NOT NULL CHECK (country ~ '^[A-Z]{2}$'),
+  rule TEXT NOT NULL CHECK (rule IN ('allow', 'deny')),
+  PRIMARY KEY (video_id, country)
+);
+
+-- Country the buyer was in when the invoice was created.
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS buyer_country TEXT;
+ALTER TABLE x402_invoices ADD COLUMN IF NOT EXISTS buyer_country TEXT;
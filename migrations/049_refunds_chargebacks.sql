@@ -0,0 +1,45 @@
+-- 049_refunds_chargebacks.sql
+-- Refunds and chargebacks of fiat plugin payments.
+--   payment_refunds  one row per provider refund or dispute (idempotent by provider_ref)
+--
+-- Refund status:
+--   pending     requested, the provider has not confirmed it yet
+--   succeeded   money returned; reversal applied once (applied_at)
+--   failed      the provider rejected it
+--
+-- A full refund or a chargeback revokes what the invoice granted; a partial
+-- refund keeps access. Either way the refunded share is taken back from the
+-- creator (tips credited to the wallet, disbursed sales) and from the
+-- affiliate commission.
+
+CREATE TABLE IF NOT EXISTS payment_refunds (
+  id TEXT PRIMARY KEY,
+  invoice_uid TEXT NOT NULL REFERENCES fiat_invoices(invoice_uid),
+  provider TEXT NOT NULL,
+  provider_ref TEXT,                   -- provider refund / dispute id
+  kind TEXT NOT NULL CHECK (kind IN ('refund', 'chargeback')),
+  amount BIGINT NOT NULL CHECK (amount > 0),  -- charge currency, minor units
+  usd_cents BIGINT NOT NULL DEFAULT 0,
+  creator_reversed_cents BIGINT NOT NULL DEFAULT 0,
+  commission_reversed_cents BIGINT NOT NULL DEFAULT 0,
+  status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'succeeded', 'failed')),
+  reason TEXT NOT NULL DEFAULT '',
+  requested_by TEXT REFERENCES users(id),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  applied_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_payment_refunds_provider_ref
+  ON payment_refunds (provider, provider_ref) WHERE provider_ref IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_payment_refunds_invoice ON payment_refunds (invoice_uid);
+
+-- Running total of refunded money; status becomes partially_refunded,
+-- refunded, or charged_back.
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS refunded_amount BIGINT NOT NULL DEFAULT 0;
+
+-- Provider reference of the settled charge (payment intent, capture, ...).
+-- `provider_ref` keeps the checkout reference, which cannot be refunded.
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS charge_ref TEXT;
+CREATE INDEX IF NOT EXISTS idx_fiat_charge_ref ON fiat_invoices (charge_ref);
+
+ALTER TABLE affiliate_commissions ADD COLUMN IF NOT EXISTS reversed_cents BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,127 @@
+-- 050_entitlements.sql
+-- One ledger of who may watch what.
+--   entitlements   a user's right to a video, a bundle purchase, or a subscription
+--
+-- Sources:
+--   wallet, x402, fiat   direct video purchases and rentals (`provider` = fiat plugin)
+--   grant                manual allowlist entries, gifts and access codes
+--   bundle               a bundle purchase; covers its video snapshot (and series
+--                        videos added later for `include_future` passes)
+--   subscription         a creator subscription; valid for the paid period or
+--                        grace period, covering the tier's videos
+--
+-- An entitlement is live while `valid_from <= NOW() < valid_until` (NULL =
+-- permanent) and `revoked_at` is NULL. Rentals carry `rental_hours`: the
+-- first playback moves `valid_until` to at most that many hours later.
+--
+-- `purchases` stays the sales record; `allowlist` and the paid-invoice check
+-- are no longer read for access. Existing rows are copied below.
+
+CREATE TABLE IF NOT EXISTS entitlements (
+  id BIGSERIAL PRIMARY KEY,
+  user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  video_id TEXT REFERENCES videos(id) ON DELETE CASCADE,
+  bundle_purchase_id BIGINT REFERENCES bundle_purchases(id) ON DELETE CASCADE,
+  subscription_id TEXT REFERENCES subscriptions(id) ON DELETE CASCADE,
+  source TEXT NOT NULL
+    CHECK (source IN ('wallet', 'x402', 'fiat', 'grant', 'bundle', 'subscription')),
+  provider TEXT,                       -- fiat plugin key
+  reference TEXT,                      -- invoice uid, tx hash, gift / code, 'allowlist'
+  purchase_id BIGINT REFERENCES purchases(id) ON DELETE SET NULL,
+  granted_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+  rental_hours INT,
+  rental_started_at TIMESTAMPTZ,
+  valid_from TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  valid_until TIMESTAMPTZ,
+  revoked_at TIMESTAMPTZ,
+  revoke_reason TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  CHECK (num_nonnulls(video_id, bundle_purchase_id, subscription_id) = 1)
+);
+
+CREATE INDEX IF NOT EXISTS idx_entitlements_user_video
+  ON entitlements (user_id, video_id) WHERE video_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_entitlements_user ON entitlements (user_id);
+CREATE INDEX IF NOT EXISTS idx_entitlements_video ON entitlements (video_id);
+CREATE INDEX IF NOT EXISTS idx_entitlements_reference ON entitlements (reference);
+CREATE INDEX IF NOT EXISTS idx_entitlements_purchase ON entitlements (purchase_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_entitlements_bundle
+  ON entitlements (bundle_purchase_id) WHERE bundle_purchase_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_entitlements_subscription
+  ON entitlements (subscription_id) WHERE subscription_id IS NOT NULL;
+
+-- Backfill, once: only while the table is still empty.
+DO $$
+BEGIN
+  IF EXISTS (SELECT 1 FROM entitlements) THEN
+    RETURN;
+  END IF;
+
+  -- Purchases and rentals, with the source of the payment that made them.
+  INSERT INTO entitlements
+    (user_id, video_id, source, provider, reference, purchase_id,
+     rental_hours, rental_started_at, valid_from, valid_until, created_at)
+  SELECT p.user_id, p.video_id,
+         CASE WHEN g.id IS NOT NULL OR ac.code IS NOT NULL THEN 'grant'
+              WHEN fi.invoice_uid IS NOT NULL THEN 'fiat'
+              WHEN xi.invoice_uid IS NOT NULL THEN 'x402'
+              ELSE 'wallet' END,
+         CASE WHEN g.id IS NULL AND ac.code IS NULL THEN fi.provider END,
+         COALESCE(g.reference, ac.code, fi.invoice_uid, xi.invoice_uid),
+         p.id, p.rental_hours, p.rental_started_at,
+         CASE WHEN p.created_at ~ '^\d{4}-\d{2}-\d{2}' THEN p.created_at::timestamptz
+              ELSE NOW() END,
+         CASE WHEN p.rental_hours IS NOT NULL THEN COALESCE(p.expires_at, NOW()) END,
+         NOW()
+  FROM purchases p
+  LEFT JOIN video_gifts g ON g.purchase_id = p.id
+  LEFT JOIN access_codes ac ON ac.purchase_id = p.id
+  LEFT JOIN LATERAL (
+    SELECT f.invoice_uid, f.provider FROM fiat_invoices f
+    WHERE f.user_id = p.user_id AND f.video_id = p.video_id
+      AND f.status IN ('paid', 'partially_refunded') AND f.gift_recipient_id IS NULL
+      AND (f.rental_hours IS NULL) = (p.rental_hours IS NULL)
+    ORDER BY f.paid_at LIMIT 1
+  ) fi ON TRUE
+  LEFT JOIN LATERAL (
+    SELECT x.invoice_uid FROM x402_invoices x
+    WHERE x.user_id = p.user_id AND x.video_id = p.video_id AND x.status = 'paid'
+      AND (x.rental_hours IS NULL) = (p.rental_hours IS NULL)
+    ORDER BY x.paid_at LIMIT 1
+  ) xi ON TRUE;
+
+  -- Paid fiat video invoices whose purchase row is missing.
+  INSERT INTO entitlements (user_id, video_id, source, provider, reference, valid_from)
+  SELECT fi.user_id, fi.video_id, 'fiat', fi.provider, fi.invoice_uid, COALESCE(fi.paid_at, NOW())
+  FROM fiat_invoices fi
+  WHERE fi.status IN ('paid', 'partially_refunded') AND fi.video_id IS NOT NULL
+    AND fi.rental_hours IS NULL AND fi.gift_recipient_id IS NULL AND fi.tip_id IS NULL
+    AND NOT EXISTS (SELECT 1 FROM entitlements e
+                    WHERE e.user_id = fi.user_id AND e.video_id = fi.video_id
+                      AND e.rental_hours IS NULL);
+
+  -- Allowlist entries not backed by a purchase are manual grants.
+  INSERT INTO entitlements (user_id, video_id, source, reference)
+  SELECT u.id, a.video_id, 'grant', 'allowlist'
+  FROM allowlist a
+  JOIN users u ON u.username = a.username
+  WHERE NOT EXISTS (SELECT 1 FROM entitlements e
+                    WHERE e.user_id = u.id AND e.video_id = a.video_id
+                      AND e.rental_hours IS NULL);
+
+  INSERT INTO entitlements
+    (user_id, bundle_purchase_id, source, provider, reference, valid_from)
+  SELECT bp.user_id, bp.id, 'bundle',
+         NULLIF(split_part(bp.source, ':', 2), ''), bp.payment_ref, bp.created_at
+  FROM bundle_purchases bp;
+
+  INSERT INTO entitlements
+    (user_id, subscription_id, source, provider, valid_from, valid_until)
+  SELECT s.user_id, s.id, 'subscription', s.provider,
+         COALESCE(s.current_period_start, s.created_at),
+         CASE WHEN s.status IN ('active', 'canceled') THEN s.current_period_end
+              WHEN s.status = 'past_due' THEN s.grace_until
+              ELSE COALESCE(s.updated_at, NOW()) END
+  FROM subscriptions s
+  WHERE s.current_period_start IS NOT NULL;
+END $$;
@@ -0,0 +1,46 @@
+-- 051_webhook_inbox.sql
+-- Inbox of fiat plugin webhooks.
+--   webhook_events  every inbound webhook, stored before it is acted on
+--
+-- The webhook endpoint verifies the event with its plugin, stores it with the
+-- normalized result, and returns; a worker applies it afterwards.
+--
+-- Status:
+--   queued      waiting for (another) attempt at `next_attempt_at`
+--   processing  claimed by the worker
+--   processed   applied; `outcome` holds what was done
+--   failed      gave up after `max_attempts`; an admin can re-drive it
+--   rejected    verification failed; never applied unless re-driven
+--
+-- Verified events are unique per provider event id (or body hash when the
+-- provider has none); redeliveries only bump `duplicates`.
+
+CREATE TABLE IF NOT EXISTS webhook_events (
+  id BIGSERIAL PRIMARY KEY,
+  provider TEXT NOT NULL,
+  event_id TEXT NOT NULL,
+  headers JSONB NOT NULL DEFAULT '{}',
+  body BYTEA NOT NULL,
+  verified BOOLEAN NOT NULL DEFAULT FALSE,
+  verify_error TEXT,
+  result JSONB,                          -- normalized PaymentResult
+  invoice_uid TEXT,
+  status TEXT NOT NULL DEFAULT 'queued'
+    CHECK (status IN ('queued', 'processing', 'processed', 'failed', 'rejected')),
+  attempts INT NOT NULL DEFAULT 0,
+  max_attempts INT NOT NULL DEFAULT 8,
+  next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  last_error TEXT,
+  outcome JSONB,
+  duplicates INT NOT NULL DEFAULT 0,
+  received_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  processed_at TIMESTAMPTZ,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_webhook_events_event
+  ON webhook_events (provider, event_id) WHERE verified;
+CREATE INDEX IF NOT EXISTS idx_webhook_events_due
+  ON webhook_events (next_attempt_at) WHERE status = 'queued';
+CREATE INDEX IF NOT EXISTS idx_webhook_events_invoice ON webhook_events (invoice_uid);
+CREATE INDEX IF NOT EXISTS idx_webhook_events_received ON webhook_events (received_at DESC);
@@ -0,0 +1,45 @@
+-- 052_payment_reconciliation.sql
+-- Reconciliation of fiat invoices whose webhook never arrived.
+--   fiat_invoices.reconciled_at   last time the provider was asked for its status
+--   payment_discrepancies         what the reconciler found that an admin should see
+--
+-- The reconciler polls the provider for `pending` invoices older than
+-- RECONCILE_AFTER_MINUTES. Paid ones go through the webhook grant path,
+-- expired / failed / cancelled ones get that status, and anything that does
+-- not add up is recorded here once per invoice and kind.
+--
+-- Kinds:
+--   missed_webhook    paid at the provider, granted by the reconciler
+--   amount_mismatch   paid at the provider for less than the invoice amount
+--   apply_failed      paid at the provider but the grant failed
+--   status_mismatch   refunded or disputed at the provider while still pending here
+--   not_found         the provider has no such payment; expired here
+--   lookup_failed     the provider could not be asked past RECONCILE_EXPIRE_HOURS
+
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS reconciled_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_fiat_pending_created
+  ON fiat_invoices (created_at) WHERE status = 'pending';
+
+CREATE TABLE IF NOT EXISTS payment_discrepancies (
+  id BIGSERIAL PRIMARY KEY,
+  invoice_uid TEXT NOT NULL REFERENCES fiat_invoices(invoice_uid) ON DELETE CASCADE,
+  provider TEXT NOT NULL,
+  kind TEXT NOT NULL
+    CHECK (kind IN ('missed_webhook', 'amount_mismatch', 'apply_failed',
+                    'status_mismatch', 'not_found', 'lookup_failed')),
+  local_status TEXT NOT NULL,
+  provider_status TEXT,
+  expected_amount BIGINT,
+  reported_amount BIGINT,
+  currency TEXT,
+  detail TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  resolved_at TIMESTAMPTZ,
+  resolved_by TEXT REFERENCES users(id),
+  note TEXT,
+  UNIQUE (invoice_uid, kind)
+);
+
+CREATE INDEX IF NOT EXISTS idx_payment_discrepancies_open
+  ON payment_discrepancies (created_at DESC) WHERE resolved_at IS NULL;
@@ -0,0 +1,52 @@
+-- 053_x402_watcher_state.sql
+-- Durable progress of the x402 watcher.
+--   x402_watcher_state  last block scanned for Paid logs, per chain and contract
+--   x402_chain_events   every Paid log seen, with the block it was seen in
+--
+-- The watcher scans `eth_getLogs` from the last scanned block (minus the
+-- reorg window) up to the head, so logs emitted while it was disconnected are
+-- picked up on reconnect. A log is applied once it has X402_CONFIRMATIONS
+-- confirmations and its block is still canonical.
+--
+-- Status:
+--   seen       recorded, waiting for confirmations
+--   applied    the invoice was paid (or already paid by this transaction)
+--   underpaid  the invoice was recorded as underpaid; nothing was granted
+--   unmatched  no invoice has this uid hash
+--   reorged    its block left the canonical chain; anything applied was rolled back
+
+CREATE TABLE IF NOT EXISTS x402_watcher_state (
+  chain_id BIGINT NOT NULL,
+  contract_address TEXT NOT NULL,        -- lowercase
+  last_block BIGINT NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (chain_id, contract_address)
+);
+
+CREATE TABLE IF NOT EXISTS x402_chain_events (
+  id BIGSERIAL PRIMARY KEY,
+  chain_id BIGINT NOT NULL,
+  contract_address TEXT NOT NULL,        -- lowercase
+  tx_hash TEXT NOT NULL,                 -- lowercase 0x-hex
+  log_index BIGINT NOT NULL,
+  block_number BIGINT NOT NULL,
+  block_hash TEXT NOT NULL,
+  invoice_uid_hash TEXT NOT NULL,        -- lowercase 0x-hex, as in x402_invoices
+  payer TEXT NOT NULL,
+  amount_wei NUMERIC(78,0) NOT NULL,
+  video_id TEXT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'seen'
+    CHECK (status IN ('seen', 'applied', 'underpaid', 'unmatched', 'reorged')),
+  seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  applied_at TIMESTAMPTZ,
+  reorged_at TIMESTAMPTZ,
+  -- an invoice can be paid only once per contract, so a transaction carries
+  -- at most one Paid log per invoice; this key survives re-inclusion
+  UNIQUE (chain_id, contract_address, tx_hash, invoice_uid_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_x402_chain_events_block
+  ON x402_chain_events (chain_id, contract_address, block_number)
+  WHERE status <> 'reorged';
+CREATE INDEX IF NOT EXISTS idx_x402_chain_events_invoice
+  ON x402_chain_events (invoice_uid_hash);
@@ -0,0 +1,20 @@
+-- 054_x402_chains.sql
+-- EVM chains x402 payments are accepted on, configured by admins.
+--
+-- Each chain has its own X402Splitter deployment, RPC endpoints and
+-- confirmation depth; the tokens offered on it are its active `pay_tokens`
+-- rows. The chain set by X402_CHAIN_ID / X402_CONTRACT_ADDRESS / X402_RPC_*
+-- still works as a default until a row with the same chain_id exists.
+
+CREATE TABLE IF NOT EXISTS x402_chains (
+  chain_id BIGINT PRIMARY KEY CHECK (chain_id > 0),
+  name TEXT NOT NULL,
+  contract_address TEXT NOT NULL CHECK (contract_address ~* '^0x[0-9a-f]{40}$'),
+  rpc_http TEXT NOT NULL,
+  rpc_wss TEXT,                          -- watcher endpoint; rpc_http when NULL
+  confirmations INT NOT NULL DEFAULT 3 CHECK (confirmations BETWEEN 1 AND 1000),
+  start_block BIGINT CHECK (start_block >= 0), -- first block the watcher scans
+  is_active BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
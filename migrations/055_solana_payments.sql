@@ -0,0 +1,14 @@
+-- 055_solana_payments.sql
+-- Solana USDC payments through the `solana` payment plugin.
+--
+-- Solana invoices are ordinary `fiat_invoices` rows in USD. The buyer pays
+-- with a Solana Pay transfer whose memo is the invoice uid; the server finds
+-- it on chain by that memo and records its signature as `charge_ref`.
+
+ALTER TABLE payment_settings
+  ADD COLUMN IF NOT EXISTS solana_enabled BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- one transaction pays one invoice, even if it carries several memos
+CREATE UNIQUE INDEX IF NOT EXISTS idx_fiat_invoices_solana_charge
+  ON fiat_invoices (charge_ref)
+  WHERE provider = 'solana' AND charge_ref IS NOT NULL;
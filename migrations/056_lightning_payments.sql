@@ -0,0 +1,14 @@
+-- 056_lightning_payments.sql
+-- Lightning Network payments through the `lightning` payment plugin.
+--
+-- Lightning invoices are ordinary `fiat_invoices` rows priced in BTC, so
+-- `amount` is in satoshis, converted at the quote locked at checkout.
+-- `provider_ref` and, once settled, `charge_ref` hold the payment hash.
+
+ALTER TABLE payment_settings
+  ADD COLUMN IF NOT EXISTS lightning_enabled BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- the settlement watcher polls open Lightning invoices
+CREATE INDEX IF NOT EXISTS idx_fiat_invoices_lightning_pending
+  ON fiat_invoices (created_at)
+  WHERE provider = 'lightning' AND status = 'pending';
@@ -0,0 +1,68 @@
+-- 057_creator_payouts.sql
+-- Automated creator payouts.
+--   creator_payout_settings   how and from what balance each creator is paid
+--   payout_batches            one scheduled (or admin-triggered) payout run
+--   payouts                   one transfer to one creator, with its status
+--   fiat_invoices.payout_id   the payout that paid the creator share of the invoice
+--
+-- A batch collects paid, undisbursed, non-tip invoices older than
+-- PAYOUT_HOLD_DAYS, takes the creator share (CREATOR_SPLIT_BP of what the
+-- buyer kept) and creates a payout for every creator whose total reaches
+-- their threshold. Payouts are sent through the payout plugin of the
+-- creator's method and retried with backoff up to PAYOUT_MAX_ATTEMPTS.
+--
+-- Payout status:
+--   pending      waiting to be sent (again) at next_attempt_at
+--   sending      claimed by the worker
+--   processing   accepted by the provider, or exported for a manual bank transfer
+--   paid         the creator has the money; its invoices are disbursed
+--   failed       gave up after PAYOUT_MAX_ATTEMPTS; an admin can retry it
+
+CREATE TABLE IF NOT EXISTS creator_payout_settings (
+  user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+  method TEXT NOT NULL CHECK (method IN ('xendit', 'paypal', 'stripe', 'manual_bank', 'mock')),
+  destination TEXT NOT NULL,
+  threshold_cents BIGINT NOT NULL CHECK (threshold_cents > 0),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS payout_batches (
+  id BIGSERIAL PRIMARY KEY,
+  triggered_by TEXT REFERENCES users(id),   -- NULL = scheduled
+  payout_count INT NOT NULL DEFAULT 0,
+  total_cents BIGINT NOT NULL DEFAULT 0,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS payouts (
+  id TEXT PRIMARY KEY,
+  batch_id BIGINT NOT NULL REFERENCES payout_batches(id),
+  creator_id TEXT NOT NULL REFERENCES users(id),
+  method TEXT NOT NULL,
+  destination TEXT NOT NULL,
+  amount_cents BIGINT NOT NULL CHECK (amount_cents > 0),   -- USD cents
+  sent_currency TEXT,
+  sent_amount BIGINT,                                      -- minor units of sent_currency
+  status TEXT NOT NULL DEFAULT 'pending'
+    CHECK (status IN ('pending', 'sending', 'processing', 'paid', 'failed')),
+  provider_ref TEXT,
+  attempts INT NOT NULL DEFAULT 0,
+  last_error TEXT,
+  next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  paid_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_payouts_creator ON payouts (creator_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_payouts_open
+  ON payouts (next_attempt_at) WHERE status IN ('pending', 'sending', 'processing');
+
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS payout_id TEXT REFERENCES payouts(id);
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS payout_cents BIGINT;
+CREATE INDEX IF NOT EXISTS idx_fiat_payout ON fiat_invoices (payout_id);
+
+-- the batch scans paid invoices nobody has paid out yet
+CREATE INDEX IF NOT EXISTS idx_fiat_unpaid_out
+  ON fiat_invoices (creator_id, paid_at)
+  WHERE disbursed_at IS NULL AND payout_id IS NULL AND tip_id IS NULL;
@@ -0,0 +1,20 @@
+-- 058_stripe_connect.sql
+-- Stripe Connect: split charges with creators at checkout.
+--   users.stripe_account_id            the creator's Express connected account
+--   users.stripe_account_ready         onboarding is done and the account can
+--                                      receive transfers (refreshed from Stripe)
+--   fiat_invoices.connect_account_id   the account a Stripe checkout was split with
+--   fiat_invoices.application_fee      platform share of that checkout, in minor
+--                                      units of the invoice currency
+--
+-- A split invoice is disbursed the moment it is paid (disburse_ref
+-- 'connect:<account id>'): Stripe moved the creator share, so neither
+-- admin_disburse nor the payout engine touch it.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS stripe_account_id TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS stripe_account_ready BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_stripe_account
+  ON users (stripe_account_id) WHERE stripe_account_id IS NOT NULL;
+
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS connect_account_id TEXT;
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS application_fee BIGINT;
@@ -0,0 +1,18 @@
+-- 059_payout_split.sql
+-- Split a scheduled payout paid the creator of each invoice with.
+--   fiat_invoices.payout_split_bp   CREATOR_SPLIT_BP when the batch took the
+--                                   invoice; a later refund takes back only the
+--                                   creator share it changes, at this split
+--
+-- Invoices paid out before this column existed get the split their
+-- payout_cents imply.
+
+ALTER TABLE fiat_invoices ADD COLUMN IF NOT EXISTS payout_split_bp INT
+  CHECK (payout_split_bp BETWEEN 0 AND 10000);
+
+UPDATE fiat_invoices
+SET payout_split_bp = LEAST(10000, ROUND(
+      payout_cents * 10000.0 * amount
+      / (COALESCE(usd_cents, amount) * (amount - refunded_amount))))
+WHERE payout_id IS NOT NULL AND payout_split_bp IS NULL
+  AND amount > refunded_amount AND COALESCE(usd_cents, amount) > 0;
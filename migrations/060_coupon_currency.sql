@@ -0,0 +1,25 @@
+-- 060_coupon_currency.sql
+-- Currency of fixed-amount coupons.
+--   coupons.currency   ISO 4217 code `value` is in, for kind = 'fixed'; NULL for
+--                      percent coupons
+--
+-- A fixed coupon bound to one video is in that video's currency. Other fixed
+-- coupons are converted into the price currency at the current rate when
+-- applied. Older fixed coupons were taken off any price as-is: video coupons
+-- keep their video's currency, the others become USD.
+
+ALTER TABLE coupons ADD COLUMN IF NOT EXISTS currency TEXT;
+
+UPDATE coupons c
+SET currency = COALESCE((SELECT v.currency FROM videos v WHERE v.id = c.video_id), 'USD')
+WHERE c.kind = 'fixed' AND c.currency IS NULL;
+
+DO $$
+BEGIN
+  IF NOT EXISTS (
+    SELECT 1 FROM pg_constraint WHERE conname = 'coupons_fixed_currency'
+  ) THEN
+    ALTER TABLE coupons ADD CONSTRAINT coupons_fixed_currency
+      CHECK (kind <> 'fixed' OR currency IS NOT NULL);
+  END IF;
+END $$;
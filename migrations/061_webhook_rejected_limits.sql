@@ -0,0 +1,26 @@
+-- 061_webhook_rejected_limits.sql
+-- Bounds on webhooks that failed verification.
+--   webhook_events.body_truncated   only the first bytes of the body were kept
+--
+-- Anyone can post to the webhook endpoint, so unverified events are stored
+-- with at most the first 8 KiB of their body, once per provider event id
+-- (redeliveries bump `duplicates`), and are deleted 7 days after their last
+-- update. A truncated event cannot be re-driven.
+
+ALTER TABLE webhook_events
+  ADD COLUMN IF NOT EXISTS body_truncated BOOLEAN NOT NULL DEFAULT FALSE;
+
+DELETE FROM webhook_events w
+USING webhook_events newer
+WHERE NOT w.verified AND NOT newer.verified
+  AND w.provider = newer.provider AND w.event_id = newer.event_id
+  AND w.id < newer.id;
+
+UPDATE webhook_events
+SET body = substring(body FROM 1 FOR 8192), body_truncated = TRUE
+WHERE NOT verified AND octet_length(body) > 8192;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_webhook_events_unverified_event
+  ON webhook_events (provider, event_id) WHERE NOT verified;
+CREATE INDEX IF NOT EXISTS idx_webhook_events_rejected
+  ON webhook_events (updated_at) WHERE status = 'rejected';
@@ -0,0 +1,35 @@
+-- 062_video_purchase_count.sql
+-- Purchase counter behind the catalog's `popular` sort.
+--   videos.purchase_count   number of `purchases` rows for the video (sales and
+--                           rentals), kept up to date by a trigger
+--
+-- The catalog used to count purchases per row with a correlated subquery on
+-- every request. Existing counts are rebuilt from `purchases` below.
+
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS purchase_count BIGINT NOT NULL DEFAULT 0;
+
+UPDATE videos v
+SET purchase_count = (SELECT COUNT(*) FROM purchases p WHERE p.video_id = v.id);
+
+CREATE OR REPLACE FUNCTION count_video_purchase()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE videos SET purchase_count = purchase_count + 1 WHERE id = NEW.video_id;
+    END IF;
+    IF TG_OP IN ('DELETE', 'UPDATE') THEN
+        UPDATE videos SET purchase_count = GREATEST(purchase_count - 1, 0)
+        WHERE id = OLD.video_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_count_video_purchase ON purchases;
+CREATE TRIGGER trg_count_video_purchase
+AFTER INSERT OR DELETE OR UPDATE OF video_id ON purchases
+FOR EACH ROW
+EXECUTE FUNCTION count_video_purchase();
+
+CREATE INDEX IF NOT EXISTS idx_videos_purchase_count_id
+  ON videos (purchase_count DESC, id DESC);
@@ -0,0 +1,16 @@
+-- 063_x402_event_failures.sql
+-- Paid logs the watcher could not apply.
+--   x402_chain_events.last_error   why applying the log failed
+--
+-- Status (adds to 053):
+--   failed     applying it returned an error; nothing was recorded for the
+--              invoice, the log needs an admin look
+--
+-- A log that fails to apply used to abort the whole pass, so every later log
+-- of the chain waited behind it. It is now marked failed and the pass goes on.
+
+ALTER TABLE x402_chain_events ADD COLUMN IF NOT EXISTS last_error TEXT;
+
+ALTER TABLE x402_chain_events DROP CONSTRAINT IF EXISTS x402_chain_events_status_check;
+ALTER TABLE x402_chain_events ADD CONSTRAINT x402_chain_events_status_check
+  CHECK (status IN ('seen', 'applied', 'underpaid', 'unmatched', 'reorged', 'failed'));
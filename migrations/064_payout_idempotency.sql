@@ -0,0 +1,12 @@
+-- 064_payout_idempotency.sql
+-- Stable idempotency keys for payout transfers.
+--   payouts.key_generation   bumped when the provider rejects a transfer;
+--                            the key is the payout id, then `<id>-<generation>`
+--
+-- Payouts used to send every attempt under a new key `<id>-<attempt>`, so an
+-- attempt that timed out after the provider made the transfer could be sent
+-- a second time. The key now only changes after a definitive rejection (or an
+-- admin retry of a failed payout); a send whose outcome is unknown stays
+-- `processing` until a status lookup or an admin settles it.
+
+ALTER TABLE payouts ADD COLUMN IF NOT EXISTS key_generation INT NOT NULL DEFAULT 0;
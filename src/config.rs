@@ -35,9 +35,34 @@ pub struct Config {
     pub max_upload_bytes: u64,
     pub allow_exts: Vec<String>, // e.g. ["mp4","mkv","mov","webm"]
 
+    // ===== Bulk import =====
+    pub import_root: String, // satu-satunya root yang boleh dipakai admin untuk import folder
+
+    // ===== Karantina upload =====
+    pub quarantine_decode_check: bool, // decode penuh dengan ffmpeg di worker sebelum transcode
+    pub quarantine_decode_timeout_secs: u64,
+
     // ===== Kurs Dollar ke Rupiah =====
     pub dollar_usd_to_rupiah: f64,
 
+    // ===== Kurs multi-mata uang =====
+    /// Rate provider: `static` (default), `file`, or `http`.
+    pub rate_provider: String,
+    /// Extra static rates per USD, e.g. `EUR=0.92,SGD=1.35` (`static` provider).
+    pub exchange_rates: String,
+    /// JSON rates file for the `file` provider.
+    pub rate_file: String,
+    /// JSON rates endpoint for the `http` provider.
+    pub rate_url: String,
+    /// Seconds between rate refreshes (default 3600).
+    pub rate_refresh_secs: u64,
+    /// Minutes a checkout quote keeps its rate (default 15).
+    pub quote_ttl_minutes: i32,
+
+    // ===== GeoIP =====
+    /// Offline IP-to-country CSV (`start_ip,end_ip,country`); empty disables lookups.
+    pub geoip_file: String,
+
     // ===== X402 =====
     pub x402_contract: String,
     #[allow(dead_code)]
@@ -45,11 +70,41 @@ pub struct Config {
     pub x402_rpc_wss: String,    // untuk watcher (WebSocket RPC)
     pub x402_chain_id: u64,      // chain default (mis: 137)
     pub x402_deadline_secs: u64, // payment window in seconds (default 900 = 15 min)
+    /// How many recent blocks the watcher re-checks for reorgs (never fewer
+    /// than a chain's confirmations).
+    #[cfg_attr(not(feature = "x402-watcher"), allow(dead_code))]
+    pub x402_reorg_depth: u64,
+    /// Seconds between watcher passes.
+    #[cfg_attr(not(feature = "x402-watcher"), allow(dead_code))]
+    pub x402_watch_interval_secs: u64,
 
     // ===== Revenue split =====
     /// Creator share in basis points (0–10000). Default 9000 = 90%.
     /// Admin share is implicitly 10000 - creator_split_bp.
     pub creator_split_bp: u16,
+
+    // ===== Langganan kreator =====
+    /// Days a subscription keeps access after a failed renewal (default 3).
+    pub subscription_grace_days: i32,
+
+    // ===== Rekonsiliasi pembayaran =====
+    /// Minutes a fiat invoice stays pending before the reconciler asks its
+    /// provider, and between later checks (default 30).
+    pub reconcile_after_minutes: i32,
+    /// Hours after which an invoice the provider still reports as pending or
+    /// unknown is marked expired (default 48).
+    pub reconcile_expire_hours: i32,
+
+    // ===== Payout kreator =====
+    /// Hours between scheduled payout batches (default 168 = weekly).
+    pub payout_interval_hours: i32,
+    /// Days a paid invoice is held before its creator share is paid out, so
+    /// early refunds and chargebacks land first (default 7).
+    pub payout_hold_days: i32,
+    /// Lowest payout threshold a creator can choose, in USD cents (default 1000).
+    pub payout_min_threshold_cents: i64,
+    /// Send attempts before a payout is marked failed (default 5).
+    pub payout_max_attempts: i32,
 }
 
 impl Config {
@@ -130,12 +185,46 @@ impl Config {
             "mp4,mkv,mov,webm".into()
         }));
 
+        // Import folder server hanya boleh di bawah root ini
+        let import_root = env::var("IMPORT_ROOT").unwrap_or_else(|_| "import".into());
+
+        // Karantina: decode penuh (ffmpeg -f null) di worker sebelum transcode
+        let quarantine_decode_check = env::var("QUARANTINE_DECODE_CHECK")
+            .map(|v| !matches!(v.to_ascii_lowercase().as_str(), "0" | "false" | "off"))
+            .unwrap_or(true);
+        let quarantine_decode_timeout_secs = env::var("QUARANTINE_DECODE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(900);
+
         // Kurs Dollar ke Rupiah (default 17000)
         let dollar_usd_to_rupiah = env::var("DOLLAR_USD_TO_RUPIAH")
             .ok()
             .and_then(|s| s.parse::<f64>().ok())
             .unwrap_or(17_000.0);
 
+        // Kurs multi-mata uang
+        let rate_provider = env::var("RATE_PROVIDER")
+            .map(|v| v.trim().to_ascii_lowercase())
+            .unwrap_or_else(|_| "static".into());
+        let exchange_rates = env::var("EXCHANGE_RATES").unwrap_or_default();
+        let rate_file = env::var("RATE_FILE").unwrap_or_else(|_| "rates.json".into());
+        let rate_url = env::var("RATE_URL").unwrap_or_default();
+        let rate_refresh_secs = env::var("RATE_REFRESH_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(3600);
+        let quote_ttl_minutes = env::var("QUOTE_TTL_MINUTES")
+            .ok()
+            .and_then(|s| s.parse::<i32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(15);
+
+        // GeoIP (opsional)
+        let geoip_file = env::var("GEOIP_FILE").unwrap_or_default();
+
         // ===== X402 =====
         let x402_contract = env::var("X402_CONTRACT_ADDRESS").unwrap_or_default();
         let x402_admin_wallet = env::var("X402_ADMIN_WALLET").unwrap_or_default();
@@ -148,6 +237,15 @@ impl Config {
             .ok()
             .and_then(|s| s.parse::<u64>().ok())
             .unwrap_or(900);
+        let x402_reorg_depth = env::var("X402_REORG_DEPTH")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(64);
+        let x402_watch_interval_secs = env::var("X402_WATCH_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(10)
+            .max(1);
 
         // ===== Revenue split =====
         let creator_split_bp = env::var("CREATOR_SPLIT_BP")
@@ -156,6 +254,47 @@ impl Config {
             .unwrap_or(9000)
             .min(10000);
 
+        // ===== Langganan kreator =====
+        let subscription_grace_days = env::var("SUBSCRIPTION_GRACE_DAYS")
+            .ok()
+            .and_then(|s| s.parse::<i32>().ok())
+            .filter(|v| *v >= 0)
+            .unwrap_or(3);
+
+        // ===== Rekonsiliasi pembayaran =====
+        let reconcile_after_minutes = env::var("RECONCILE_AFTER_MINUTES")
+            .ok()
+            .and_then(|s| s.parse::<i32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(30);
+        let reconcile_expire_hours = env::var("RECONCILE_EXPIRE_HOURS")
+            .ok()
+            .and_then(|s| s.parse::<i32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(48);
+
+        // ===== Payout kreator =====
+        let payout_interval_hours = env::var("PAYOUT_INTERVAL_HOURS")
+            .ok()
+            .and_then(|s| s.parse::<i32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(168);
+        let payout_hold_days = env::var("PAYOUT_HOLD_DAYS")
+            .ok()
+            .and_then(|s| s.parse::<i32>().ok())
+            .filter(|v| *v >= 0)
+            .unwrap_or(7);
+        let payout_min_threshold_cents = env::var("PAYOUT_MIN_THRESHOLD_CENTS")
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(1000);
+        let payout_max_attempts = env::var("PAYOUT_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|s| s.parse::<i32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(5);
+
         let cfg = Self {
             database_url,
             bind,
@@ -174,20 +313,39 @@ impl Config {
             hwaccel,
             max_upload_bytes,
             allow_exts,
+            import_root,
+            quarantine_decode_check,
+            quarantine_decode_timeout_secs,
             dollar_usd_to_rupiah,
+            rate_provider,
+            exchange_rates,
+            rate_file,
+            rate_url,
+            rate_refresh_secs,
+            quote_ttl_minutes,
+            geoip_file,
             x402_contract,
             x402_admin_wallet,
             x402_rpc_wss,
             x402_chain_id,
             x402_deadline_secs,
+            x402_reorg_depth,
+            x402_watch_interval_secs,
             creator_split_bp,
+            subscription_grace_days,
+            reconcile_after_minutes,
+            reconcile_expire_hours,
+            payout_interval_hours,
+            payout_hold_days,
+            payout_min_threshold_cents,
+            payout_max_attempts,
         };
 
         cfg.ensure_dirs();
 
         println!(
             "[config] bind={}, base_url={}, trust_proxy_headers={}, db_url={}, upload_dir={}, media_dir={}, tmp_dir={}, public_dir={}, \
-             hls_segment={}s, hwaccel={}, kurs_usd_to_idr={}, max_upload={}MB, \
+             hls_segment={}s, hwaccel={}, kurs_usd_to_idr={}, rate_provider={}, max_upload={}MB, \
              creator_split={}bp ({}%), x402_deadline={}s, \
              x402_contract={}, x402_chain_id={}, watcher_wss={}",
             cfg.bind,
@@ -201,6 +359,7 @@ impl Config {
             cfg.hls_segment_seconds,
             cfg.hwaccel,
             cfg.dollar_usd_to_rupiah,
+            cfg.rate_provider,
             cfg.max_upload_bytes / (1024 * 1024),
             cfg.creator_split_bp,
             cfg.creator_split_bp / 100,
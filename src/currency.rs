@@ -0,0 +1,406 @@
+// src/currency.rs
+//
+// Exchange rates and locked price quotes.
+//
+// Amounts are integers in a currency's minor unit (cents for USD, whole
+// rupiah for IDR, satoshis for BTC). Wallet balances, bundles, tiers, tips,
+// and x402 are in USD; a video may be priced in any currency with a known
+// rate (`videos.currency`).
+//
+// Rates are stored as units of a currency per 1 USD in `exchange_rates`. A
+// [`RateProvider`] supplies them: `static` (config only, works offline),
+// `file` (a JSON file), or `http` (a JSON endpoint). The refresh worker
+// reloads them every `RATE_REFRESH_SECS`.
+//
+// Checkouts never convert at a floating rate. [`lock_quote`] either honours a
+// quote the buyer was shown (`quote_id`) or locks the current rate, and the
+// invoice keeps the converted amount, so what was shown is what is charged.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::config::Config;
+
+/// Currency of wallet balances and USD-priced items.
+pub const BASE_CURRENCY: &str = "USD";
+
+/// Decimal places of a currency's minor unit as used by the payment providers.
+pub fn minor_exponent(currency: &str) -> u32 {
+    match currency {
+        "IDR" | "JPY" | "KRW" | "VND" | "HUF" | "TWD" | "CLP" | "ISK" => 0,
+        // Satoshis, for Lightning invoices.
+        "BTC" => 8,
+        _ => 2,
+    }
+}
+
+/// Upper-cases an ISO 4217 code and checks its shape.
+pub fn normalize_currency(raw: &str) -> Result<String, String> {
+    let code = raw.trim().to_ascii_uppercase();
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err("currency must be a 3-letter ISO 4217 code".to_string());
+    }
+    Ok(code)
+}
+
+/// Converts `amount` minor units of `from` into minor units of `to` at `rate`
+/// (units of `to` per unit of `from`), rounding up so the seller never
+/// receives less than the listed price.
+pub fn convert_minor(amount: i64, from: &str, to: &str, rate: f64) -> i64 {
+    if from == to {
+        return amount;
+    }
+    let major = amount as f64 / 10f64.powi(minor_exponent(from) as i32);
+    let converted = major * rate * 10f64.powi(minor_exponent(to) as i32);
+    // Drop float noise (e.g. 1234.0000000002) before rounding up.
+    (converted - 1e-6).ceil().max(0.0) as i64
+}
+
+/// Source of exchange rates, as units of each currency per 1 USD.
+#[async_trait::async_trait]
+pub trait RateProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn fetch(&self) -> Result<HashMap<String, f64>, String>;
+}
+
+/// Rates from configuration only; never touches the network.
+pub struct StaticRates {
+    rates: HashMap<String, f64>,
+}
+
+impl StaticRates {
+    pub fn from_config(cfg: &Config) -> Self {
+        let mut rates = parse_rate_list(&cfg.exchange_rates);
+        rates
+            .entry("IDR".to_string())
+            .or_insert(cfg.dollar_usd_to_rupiah);
+        Self { rates }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateProvider for StaticRates {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(self.rates.clone())
+    }
+}
+
+/// Rates read from a JSON file: `{"IDR": 16250, ...}` or `{"rates": {...}}`.
+pub struct FileRates {
+    path: String,
+}
+
+#[async_trait::async_trait]
+impl RateProvider for FileRates {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, f64>, String> {
+        let raw = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| format!("read {}: {e}", self.path))?;
+        let json: serde_json::Value =
+            serde_json::from_str(&raw).map_err(|e| format!("parse {}: {e}", self.path))?;
+        Ok(parse_rate_json(&json))
+    }
+}
+
+/// Rates from a JSON endpoint in the same shape as [`FileRates`].
+pub struct HttpRates {
+    url: String,
+}
+
+#[async_trait::async_trait]
+impl RateProvider for HttpRates {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, f64>, String> {
+        let resp = reqwest::Client::new()
+            .get(&self.url)
+            .timeout(std::time::Duration::from_secs(15))
+            .send()
+            .await
+            .map_err(|e| format!("fetch: {e}"))?;
+        if !resp.status().is_success() {
+            return Err(format!("rates http {}", resp.status()));
+        }
+        let json: serde_json::Value = resp.json().await.map_err(|e| format!("parse: {e}"))?;
+        Ok(parse_rate_json(&json))
+    }
+}
+
+/// Provider selected by `RATE_PROVIDER`; unknown values fall back to `static`.
+pub fn provider_from_config(cfg: &Config) -> Box<dyn RateProvider> {
+    match cfg.rate_provider.as_str() {
+        "file" => Box::new(FileRates {
+            path: cfg.rate_file.clone(),
+        }),
+        "http" if !cfg.rate_url.is_empty() => Box::new(HttpRates {
+            url: cfg.rate_url.clone(),
+        }),
+        _ => Box::new(StaticRates::from_config(cfg)),
+    }
+}
+
+fn parse_rate_list(raw: &str) -> HashMap<String, f64> {
+    raw.split(',')
+        .filter_map(|pair| {
+            let (code, rate) = pair.split_once('=')?;
+            let code = normalize_currency(code).ok()?;
+            let rate = rate.trim().parse::<f64>().ok().filter(|r| *r > 0.0)?;
+            Some((code, rate))
+        })
+        .collect()
+}
+
+fn parse_rate_json(json: &serde_json::Value) -> HashMap<String, f64> {
+    let rates = json.get("rates").unwrap_or(json);
+    rates
+        .as_object()
+        .map(|obj| {
+            obj.iter()
+                .filter_map(|(code, rate)| {
+                    let code = normalize_currency(code).ok()?;
+                    let rate = rate.as_f64().filter(|r| *r > 0.0)?;
+                    Some((code, rate))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Fetches rates from `provider` and stores them. Returns how many were saved.
+pub async fn refresh_rates(pool: &PgPool, provider: &dyn RateProvider) -> Result<usize, String> {
+    let rates = provider.fetch().await?;
+    let mut saved = 0;
+    for (currency, per_usd) in rates {
+        if currency == BASE_CURRENCY {
+            continue;
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO exchange_rates (currency, per_usd, source, fetched_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (currency) DO UPDATE
+              SET per_usd = EXCLUDED.per_usd, source = EXCLUDED.source, fetched_at = NOW()
+            "#,
+        )
+        .bind(&currency)
+        .bind(per_usd)
+        .bind(provider.name())
+        .execute(pool)
+        .await
+        .map_err(|e| format!("db: {e}"))?;
+        saved += 1;
+    }
+    Ok(saved)
+}
+
+/// Loads rates once at startup, then every `RATE_REFRESH_SECS`.
+pub fn start_rate_refresh_worker(pool: PgPool, cfg: Config) {
+    tokio::spawn(async move {
+        let provider = provider_from_config(&cfg);
+        let mut ticker =
+            tokio::time::interval(std::time::Duration::from_secs(cfg.rate_refresh_secs));
+        loop {
+            ticker.tick().await;
+            match refresh_rates(&pool, provider.as_ref()).await {
+                Ok(n) => tracing::info!("exchange rates refreshed: {n} from {}", provider.name()),
+                Err(e) => tracing::warn!("exchange rate refresh failed ({}): {e}", provider.name()),
+            }
+        }
+    });
+}
+
+/// Units of a currency per 1 USD.
+async fn per_usd(pool: &PgPool, currency: &str) -> Result<f64, String> {
+    if currency == BASE_CURRENCY {
+        return Ok(1.0);
+    }
+    sqlx::query_scalar::<_, f64>("SELECT per_usd FROM exchange_rates WHERE currency = $1")
+        .bind(currency)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("db: {e}"))?
+        .ok_or_else(|| format!("no exchange rate for {currency}"))
+}
+
+/// Current rate from `from` to `to` (units of `to` per unit of `from`).
+pub async fn current_rate(pool: &PgPool, from: &str, to: &str) -> Result<f64, String> {
+    if from == to {
+        return Ok(1.0);
+    }
+    Ok(per_usd(pool, to).await? / per_usd(pool, from).await?)
+}
+
+/// Converts at the current rate, for indicative prices that charge nothing.
+pub async fn convert_now(pool: &PgPool, amount: i64, from: &str, to: &str) -> Result<i64, String> {
+    let rate = current_rate(pool, from, to).await?;
+    Ok(convert_minor(amount, from, to, rate))
+}
+
+/// Checks that a price currency has a known rate.
+pub async fn check_supported(pool: &PgPool, currency: &str) -> Result<(), String> {
+    per_usd(pool, currency).await.map(|_| ())
+}
+
+/// A rate locked for one buyer.
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub id: String,
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: f64,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Quote {
+    /// `amount` minor units of the base currency in the quote currency.
+    pub fn convert(&self, amount: i64) -> i64 {
+        convert_minor(amount, &self.base_currency, &self.quote_currency, self.rate)
+    }
+}
+
+/// Creates a quote at the current rate, valid for `QUOTE_TTL_MINUTES`.
+pub async fn create_quote(
+    pool: &PgPool,
+    cfg: &Config,
+    user_id: &str,
+    from: &str,
+    to: &str,
+) -> Result<Quote, String> {
+    let rate = current_rate(pool, from, to).await?;
+    let id = Uuid::new_v4().to_string();
+    let expires_at: DateTime<Utc> = sqlx::query_scalar(
+        r#"
+        INSERT INTO rate_quotes (id, user_id, base_currency, quote_currency, rate, expires_at)
+        VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(mins => $6))
+        RETURNING expires_at
+        "#,
+    )
+    .bind(&id)
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .bind(rate)
+    .bind(cfg.quote_ttl_minutes)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?;
+
+    Ok(Quote {
+        id,
+        base_currency: from.to_string(),
+        quote_currency: to.to_string(),
+        rate,
+        expires_at,
+    })
+}
+
+/// The rate a checkout converts at.
+///
+/// With `quote_id`, that quote must belong to the buyer, match the currency
+/// pair, and still be valid; an expired quote is an error so the buyer sees
+/// the new amount before paying. Without one, the current rate is locked.
+pub async fn lock_quote(
+    pool: &PgPool,
+    cfg: &Config,
+    user_id: &str,
+    quote_id: Option<&str>,
+    from: &str,
+    to: &str,
+) -> Result<Quote, String> {
+    let Some(quote_id) = quote_id.map(str::trim).filter(|q| !q.is_empty()) else {
+        return create_quote(pool, cfg, user_id, from, to).await;
+    };
+
+    let row = sqlx::query(
+        r#"
+        SELECT base_currency, quote_currency, rate, expires_at, expires_at > NOW() AS valid
+        FROM rate_quotes
+        WHERE id = $1 AND user_id = $2
+        "#,
+    )
+    .bind(quote_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?
+    .ok_or_else(|| "quote not found".to_string())?;
+
+    let quote = Quote {
+        id: quote_id.to_string(),
+        base_currency: row.try_get("base_currency").unwrap_or_default(),
+        quote_currency: row.try_get("quote_currency").unwrap_or_default(),
+        rate: row.try_get("rate").unwrap_or(0.0),
+        expires_at: row.try_get("expires_at").unwrap_or_else(|_| Utc::now()),
+    };
+    if quote.base_currency != from || quote.quote_currency != to {
+        return Err(format!(
+            "quote is for {} to {}",
+            quote.base_currency, quote.quote_currency
+        ));
+    }
+    if !row.try_get::<bool, _>("valid").unwrap_or(false) {
+        return Err("quote expired; request a new one".to_string());
+    }
+    Ok(quote)
+}
+
+/// Stored rates, for `/api/kurs`.
+pub async fn list_rates(pool: &PgPool) -> Result<Vec<(String, f64, DateTime<Utc>)>, sqlx::Error> {
+    let rows =
+        sqlx::query("SELECT currency, per_usd, fetched_at FROM exchange_rates ORDER BY currency")
+            .fetch_all(pool)
+            .await?;
+    Ok(rows
+        .iter()
+        .map(|r| {
+            (
+                r.try_get("currency").unwrap_or_default(),
+                r.try_get("per_usd").unwrap_or(0.0),
+                r.try_get("fetched_at").unwrap_or_else(|_| Utc::now()),
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_respects_minor_units_and_rounds_up() {
+        // $10.00 at 16250 IDR/USD → 162500 IDR (no minor unit).
+        assert_eq!(convert_minor(1_000, "USD", "IDR", 16_250.0), 162_500);
+        // 162500 IDR back to USD cents.
+        assert_eq!(convert_minor(162_500, "IDR", "USD", 1.0 / 16_250.0), 1_000);
+        // €9.99 at 1.0869 USD/EUR → 1085.8 cents, rounded up.
+        assert_eq!(convert_minor(999, "EUR", "USD", 1.0869), 1_086);
+        assert_eq!(convert_minor(500, "USD", "USD", 3.0), 500);
+        // $5.00 at 0.0000104 BTC/USD → 5200 sats.
+        assert_eq!(convert_minor(500, "USD", "BTC", 0.000_010_4), 5_200);
+    }
+
+    #[test]
+    fn rate_sources_accept_both_shapes() {
+        let flat = serde_json::json!({"idr": 16250, "EUR": 0.92, "bad": -1});
+        let rates = parse_rate_json(&flat);
+        assert_eq!(rates.get("IDR"), Some(&16_250.0));
+        assert_eq!(rates.len(), 2);
+        let nested = serde_json::json!({"base": "USD", "rates": {"SGD": 1.35}});
+        assert_eq!(parse_rate_json(&nested).get("SGD"), Some(&1.35));
+        assert_eq!(parse_rate_list("EUR=0.92, x=1,SGD=abc").len(), 1);
+    }
+}
@@ -207,6 +207,47 @@ pub async fn send_password_changed(pool: &PgPool, to_email: &str, username: &str
     }
 }
 
+/// Tell an admin about payment discrepancies found by the reconciler.
+/// `items_html` is a pre-rendered `<li>` list.
+pub async fn send_payment_discrepancies(
+    pool: &PgPool,
+    to_email: &str,
+    count: usize,
+    items_html: &str,
+    base_url: &str,
+) {
+    let cfg = SmtpConfig::load(pool).await;
+    let admin_url = format!("{base_url}/public/admin/payments.html");
+
+    let subject = env_template(
+        "EMAIL_PAYMENT_DISCREPANCIES_SUBJECT",
+        "PPV Stream payment reconciliation found discrepancies",
+    );
+
+    let template = env_template(
+        "EMAIL_PAYMENT_DISCREPANCIES_HTML",
+        r#"
+<p>The payment reconciler found <b>{{count}}</b> new discrepancies between provider records and PPV Stream invoices:</p>
+<ul>{{items}}</ul>
+<p>Review and resolve them on the <a href="{{admin_url}}">payments page</a>.</p>
+<p>This is an automated email. Please do not reply.</p>
+"#,
+    );
+
+    let html = render_template(
+        &template,
+        &[
+            ("count", &count.to_string()),
+            ("items", items_html),
+            ("admin_url", &admin_url),
+        ],
+    );
+
+    if let Err(e) = send(&cfg, to_email, "Admin", &subject, &html).await {
+        warn!("send_payment_discrepancies failed: {e}");
+    }
+}
+
 /// Send a test email from the admin SMTP settings page.
 pub async fn send_test(cfg: &SmtpConfig, to_email: &str) -> Result<()> {
     let subject = env_template("EMAIL_TEST_SUBJECT", "PPV Stream test email");
@@ -0,0 +1,427 @@
+// src/entitlements.rs
+//
+// Entitlements: the one record of who may watch what.
+//
+// Every payment path, gift, access code, manual grant, bundle and subscription
+// writes its access through this module, and `user_has_view_access` reads it
+// back with a single query ([`video_entitlement`]). Direct video purchases
+// also keep their `purchases` row as the sales record.
+//
+// An entitlement covers one video, one bundle purchase (its video snapshot,
+// plus later series videos for `include_future` passes) or one subscription
+// (the tier's videos). It is live inside its validity window and until it is
+// revoked; revocation keeps the row for the audit trail.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgExecutor, PgPool, Row};
+
+/// Where an entitlement came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Wallet,
+    X402,
+    Fiat,
+    Grant,
+    Bundle,
+    Subscription,
+}
+
+impl Source {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::Wallet => "wallet",
+            Source::X402 => "x402",
+            Source::Fiat => "fiat",
+            Source::Grant => "grant",
+            Source::Bundle => "bundle",
+            Source::Subscription => "subscription",
+        }
+    }
+}
+
+/// Source, plugin and reference stored with a new entitlement.
+#[derive(Clone, Copy, Debug)]
+pub struct Origin<'a> {
+    pub source: Source,
+    pub provider: Option<&'a str>,
+    pub reference: Option<&'a str>,
+}
+
+impl<'a> Origin<'a> {
+    pub fn new(source: Source, reference: Option<&'a str>) -> Self {
+        Origin {
+            source,
+            provider: None,
+            reference,
+        }
+    }
+
+    /// A fiat plugin payment; `invoice_uid` is the reference.
+    pub fn fiat(provider: &'a str, invoice_uid: &'a str) -> Self {
+        Origin {
+            source: Source::Fiat,
+            provider: Some(provider),
+            reference: Some(invoice_uid),
+        }
+    }
+
+    /// Parses the `wallet` / `fiat:<provider>` method strings used by gifts
+    /// and bundle purchases.
+    pub fn from_method(method: &'a str, reference: Option<&'a str>) -> Self {
+        match method.split_once(':') {
+            Some(("fiat", provider)) => Origin {
+                source: Source::Fiat,
+                provider: Some(provider),
+                reference,
+            },
+            _ if method == "x402" => Origin::new(Source::X402, reference),
+            _ => Origin::new(Source::Wallet, reference),
+        }
+    }
+}
+
+/// Reference of manual grants made from the video's allowlist form.
+pub const ALLOWLIST_REFERENCE: &str = "allowlist";
+
+/// What a live entitlement allows for one video.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoEntitlement {
+    /// Purchase, grant, bundle or subscription.
+    Full,
+    /// Rental running until the given time.
+    Rental(DateTime<Utc>),
+}
+
+const LIVE: &str = "e.revoked_at IS NULL AND e.valid_from <= NOW() \
+                    AND (e.valid_until IS NULL OR e.valid_until > NOW())";
+
+/// The user's strongest live entitlement to a video.
+///
+/// With `direct_only`, bundles and subscriptions are ignored (private videos
+/// are only open to entitlements for the video itself).
+pub(crate) async fn video_entitlement(
+    pool: &PgPool,
+    video_id: &str,
+    user_id: &str,
+    direct_only: bool,
+) -> Result<Option<VideoEntitlement>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        r#"
+        SELECT
+          COALESCE(BOOL_OR(e.rental_hours IS NULL), FALSE) AS full,
+          MAX(e.valid_until) FILTER (WHERE e.rental_hours IS NOT NULL) AS rental_until
+        FROM entitlements e
+        LEFT JOIN bundle_purchases bp ON bp.id = e.bundle_purchase_id
+        LEFT JOIN bundles b ON b.id = bp.bundle_id
+        LEFT JOIN subscriptions s ON s.id = e.subscription_id
+        LEFT JOIN subscription_tiers t ON t.id = s.tier_id
+        WHERE e.user_id = $2 AND {LIVE}
+          AND (
+            e.video_id = $1
+            OR (NOT $3 AND bp.id IS NOT NULL AND (
+                  EXISTS (SELECT 1 FROM bundle_purchase_videos bpv
+                          WHERE bpv.purchase_id = bp.id AND bpv.video_id = $1)
+                  OR (bp.include_future AND EXISTS (
+                          SELECT 1 FROM series_videos sv
+                          WHERE sv.series_id = b.series_id AND sv.video_id = $1))))
+            OR (NOT $3 AND t.id IS NOT NULL
+                AND EXISTS (SELECT 1 FROM videos v WHERE v.id = $1 AND v.owner_id = t.creator_id)
+                AND (t.access_scope = 'all'
+                     OR EXISTS (SELECT 1 FROM subscription_tier_tags tt
+                                JOIN video_tags vt ON vt.tag = tt.tag
+                                WHERE tt.tier_id = t.id AND vt.video_id = $1)))
+          )
+        "#
+    ))
+    .bind(video_id)
+    .bind(user_id)
+    .bind(direct_only)
+    .fetch_one(pool)
+    .await?;
+
+    if row.try_get::<bool, _>("full").unwrap_or(false) {
+        return Ok(Some(VideoEntitlement::Full));
+    }
+    Ok(row
+        .try_get::<Option<DateTime<Utc>>, _>("rental_until")?
+        .map(VideoEntitlement::Rental))
+}
+
+/// True when the user holds a live permanent entitlement to the video itself
+/// ("already purchased").
+pub(crate) async fn owns_video(
+    executor: impl PgExecutor<'_>,
+    video_id: &str,
+    user_id: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(&format!(
+        "SELECT EXISTS(SELECT 1 FROM entitlements e \
+         WHERE e.user_id = $2 AND e.video_id = $1 AND e.rental_hours IS NULL AND {LIVE})"
+    ))
+    .bind(video_id)
+    .bind(user_id)
+    .fetch_one(executor)
+    .await
+}
+
+/// Grants permanent access to a video and records the sale in `purchases`.
+///
+/// Returns the new purchase id, or `None` when the user already held a live
+/// permanent entitlement (nothing is written then).
+pub(crate) async fn grant_video(
+    conn: &mut PgConnection,
+    user_id: &str,
+    video_id: &str,
+    origin: Origin<'_>,
+) -> Result<Option<i64>, sqlx::Error> {
+    if owns_video(&mut *conn, video_id, user_id).await? {
+        return Ok(None);
+    }
+    let purchase_id: i64 = sqlx::query_scalar(
+        "INSERT INTO purchases (user_id, video_id, created_at) VALUES ($1, $2, NOW()) RETURNING id",
+    )
+    .bind(user_id)
+    .bind(video_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO entitlements (user_id, video_id, source, provider, reference, purchase_id)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(user_id)
+    .bind(video_id)
+    .bind(origin.source.as_str())
+    .bind(origin.provider)
+    .bind(origin.reference)
+    .bind(purchase_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(Some(purchase_id))
+}
+
+/// Manual grant from the creator's allowlist form; no sale is recorded.
+/// Returns `false` when the user already had a live manual grant. Removing
+/// the user from the allowlist revokes it ([`Revoke::Allowlist`]).
+pub(crate) async fn grant_manual(
+    conn: &mut PgConnection,
+    user_id: &str,
+    video_id: &str,
+    granted_by: &str,
+) -> Result<bool, sqlx::Error> {
+    let inserted = sqlx::query(&format!(
+        r#"
+        INSERT INTO entitlements (user_id, video_id, source, reference, granted_by)
+        SELECT $1, $2, 'grant', $3, $4
+        WHERE NOT EXISTS (
+          SELECT 1 FROM entitlements e
+          WHERE e.user_id = $1 AND e.video_id = $2 AND e.reference = $3 AND {LIVE})
+        "#
+    ))
+    .bind(user_id)
+    .bind(video_id)
+    .bind(ALLOWLIST_REFERENCE)
+    .bind(granted_by)
+    .execute(conn)
+    .await?;
+    Ok(inserted.rows_affected() > 0)
+}
+
+/// Grants a rental that must be started within `start_within_days`, records
+/// the sale, and returns that deadline.
+///
+/// Returns `None` when the user already holds a live rental of the video, so
+/// a replayed payment confirmation does not stack a second rental.
+pub(crate) async fn grant_rental(
+    conn: &mut PgConnection,
+    user_id: &str,
+    video_id: &str,
+    rental_hours: i32,
+    start_within_days: i32,
+    origin: Origin<'_>,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let renting: bool = sqlx::query_scalar(&format!(
+        "SELECT EXISTS(SELECT 1 FROM entitlements e \
+         WHERE e.user_id = $1 AND e.video_id = $2 AND e.rental_hours IS NOT NULL AND {LIVE})"
+    ))
+    .bind(user_id)
+    .bind(video_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if renting {
+        return Ok(None);
+    }
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO purchases (user_id, video_id, created_at, rental_hours, expires_at)
+        VALUES ($1, $2, NOW(), $3, NOW() + make_interval(days => $4))
+        RETURNING id, expires_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(video_id)
+    .bind(rental_hours)
+    .bind(start_within_days)
+    .fetch_one(&mut *conn)
+    .await?;
+    let purchase_id: i64 = row.try_get("id")?;
+    let start_by: DateTime<Utc> = row.try_get("expires_at")?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO entitlements
+          (user_id, video_id, source, provider, reference, purchase_id, rental_hours, valid_until)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(user_id)
+    .bind(video_id)
+    .bind(origin.source.as_str())
+    .bind(origin.provider)
+    .bind(origin.reference)
+    .bind(purchase_id)
+    .bind(rental_hours)
+    .bind(start_by)
+    .execute(&mut *conn)
+    .await?;
+    Ok(Some(start_by))
+}
+
+/// Starts the viewing window of the user's not-yet-started rental of a video
+/// (first playback).
+pub(crate) async fn start_rental(
+    pool: &PgPool,
+    video_id: &str,
+    user_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!(
+        r#"
+        UPDATE entitlements e
+        SET rental_started_at = NOW(),
+            valid_until = LEAST(e.valid_until, NOW() + make_interval(hours => e.rental_hours))
+        WHERE e.user_id = $2 AND e.video_id = $1
+          AND e.rental_hours IS NOT NULL AND e.rental_started_at IS NULL AND {LIVE}
+        "#
+    ))
+    .bind(video_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Grants the videos of a bundle purchase. Buying a bundle again after a
+/// refund reinstates its revoked entitlement.
+pub(crate) async fn grant_bundle(
+    conn: &mut PgConnection,
+    user_id: &str,
+    bundle_purchase_id: i64,
+    origin: Origin<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO entitlements (user_id, bundle_purchase_id, source, provider, reference)
+        VALUES ($1, $2, $5, $3, $4)
+        ON CONFLICT (bundle_purchase_id) WHERE bundle_purchase_id IS NOT NULL
+        DO UPDATE SET provider = EXCLUDED.provider, reference = EXCLUDED.reference,
+                      valid_from = NOW(), revoked_at = NULL, revoke_reason = NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(bundle_purchase_id)
+    .bind(origin.provider)
+    .bind(origin.reference)
+    .bind(Source::Bundle.as_str())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Brings a subscription's entitlement in line with its status: the paid
+/// period while active or canceled, the grace period while past due, and
+/// nothing once expired. A checkout that was never paid gets no row. Call
+/// after every subscription change.
+pub(crate) async fn sync_subscription(
+    conn: &mut PgConnection,
+    subscription_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO entitlements
+          (user_id, subscription_id, source, provider, valid_from, valid_until)
+        SELECT s.user_id, s.id, $2, s.provider,
+               COALESCE(s.current_period_start, s.created_at),
+               CASE WHEN s.status IN ('active', 'canceled') THEN s.current_period_end
+                    WHEN s.status = 'past_due' THEN s.grace_until
+                    ELSE NOW() END
+        FROM subscriptions s
+        WHERE s.id = $1 AND s.current_period_start IS NOT NULL
+        ON CONFLICT (subscription_id) WHERE subscription_id IS NOT NULL
+        DO UPDATE SET valid_from = EXCLUDED.valid_from, valid_until = EXCLUDED.valid_until
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(Source::Subscription.as_str())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// What to revoke.
+#[derive(Clone, Copy, Debug)]
+pub enum Revoke<'a> {
+    /// Everything granted under a reference (fiat invoice uid, ...).
+    Reference(&'a str),
+    /// The grant made with one sale (`purchases.id`).
+    Purchase(i64),
+    /// A subscription's entitlement.
+    Subscription(&'a str),
+    /// One user's manual grant to a video (see [`grant_manual`]).
+    Allowlist { user_id: &'a str, video_id: &'a str },
+}
+
+/// Revokes live entitlements and returns how many were revoked.
+pub(crate) async fn revoke(
+    conn: &mut PgConnection,
+    target: Revoke<'_>,
+    reason: &str,
+) -> Result<u64, sqlx::Error> {
+    let filter = match target {
+        Revoke::Reference(_) => "reference = $2",
+        Revoke::Purchase(_) => "purchase_id = $2",
+        Revoke::Subscription(_) => "subscription_id = $2",
+        Revoke::Allowlist { .. } => "user_id = $2 AND video_id = $3 AND reference = $4",
+    };
+    let sql = format!(
+        "UPDATE entitlements SET revoked_at = NOW(), revoke_reason = $1 \
+         WHERE {filter} AND revoked_at IS NULL"
+    );
+    let query = sqlx::query(&sql).bind(reason);
+    let query = match target {
+        Revoke::Reference(key) | Revoke::Subscription(key) => query.bind(key),
+        Revoke::Purchase(id) => query.bind(id),
+        Revoke::Allowlist { user_id, video_id } => {
+            query.bind(user_id).bind(video_id).bind(ALLOWLIST_REFERENCE)
+        }
+    };
+    Ok(query.execute(conn).await?.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn method_strings_map_to_sources() {
+        let fiat = Origin::from_method("fiat:stripe", Some("inv-1"));
+        assert_eq!(fiat.source, Source::Fiat);
+        assert_eq!(fiat.provider, Some("stripe"));
+        assert_eq!(fiat.reference, Some("inv-1"));
+        assert_eq!(Origin::from_method("wallet", None).source, Source::Wallet);
+        assert_eq!(Origin::from_method("x402", None).source, Source::X402);
+        assert_eq!(Source::Subscription.as_str(), "subscription");
+    }
+}
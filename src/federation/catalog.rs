@@ -74,13 +74,14 @@ pub async fn catalog(
             String,         // description
             i64,            // price_cents
             Option<String>, // object_uri
-        )> = sqlx::query_as(
+        )> = sqlx::query_as(&format!(
             "SELECT v.id, v.title, v.description, v.price_cents, v.object_uri \
              FROM videos v \
-             WHERE v.federation_visibility = 'public' \
+             WHERE v.federation_visibility = 'public' AND {} \
              ORDER BY v.created_at DESC \
              LIMIT $1 OFFSET $2",
-        )
+            crate::handlers::video::LISTED_VIDEO_SQL
+        ))
         .bind(limit)
         .bind(offset)
         .fetch_all(&state.pool)
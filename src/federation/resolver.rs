@@ -1,4 +1,4 @@
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 
 /// Maximum allowed response body size when fetching remote ActivityPub objects.
 const MAX_BODY_BYTES: usize = 128 * 1024; // 128 KB
@@ -47,12 +47,20 @@ fn extract_host_port(url: &str) -> Option<(String, u16)> {
 /// When `FEDERATION_DEV_HTTP_BYPASS=1` all checks are skipped so that two
 /// Docker containers (with private 172.x IPs) can federate during integration
 /// testing.  Never set this in production.
-async fn assert_safe_url(url: &str) -> Result<(), String> {
+pub(crate) async fn assert_safe_url(url: &str) -> Result<(), String> {
+    resolve_safe_url(url).await.map(|_| ())
+}
+
+/// Like [`assert_safe_url`], but also returns the host and the addresses that
+/// were checked. Connecting to exactly those addresses
+/// (`reqwest::ClientBuilder::resolve_to_addrs`) leaves no second DNS lookup
+/// for a rebinding attack. The list is empty under the dev bypass.
+pub(crate) async fn resolve_safe_url(url: &str) -> Result<(String, Vec<SocketAddr>), String> {
     if dev_bypass_enabled() {
         if url.is_empty() {
             return Err("URL is empty".into());
         }
-        return Ok(());
+        return Ok((String::new(), Vec::new()));
     }
 
     if !url.starts_with("https://") {
@@ -66,11 +74,12 @@ async fn assert_safe_url(url: &str) -> Result<(), String> {
         return Err("remote URL must not target localhost".into());
     }
 
-    let lookup = tokio::net::lookup_host(format!("{}:{}", host, port))
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host(format!("{}:{}", host, port))
         .await
-        .map_err(|e| format!("DNS resolution failed for {}: {}", host, e))?;
+        .map_err(|e| format!("DNS resolution failed for {}: {}", host, e))?
+        .collect();
 
-    for addr in lookup {
+    for addr in &addrs {
         if !is_safe_ip(addr.ip()) {
             return Err(format!(
                 "URL {} resolves to private/reserved address {}",
@@ -79,8 +88,11 @@ async fn assert_safe_url(url: &str) -> Result<(), String> {
             ));
         }
     }
+    if addrs.is_empty() {
+        return Err(format!("DNS resolution failed for {}: no addresses", host));
+    }
 
-    Ok(())
+    Ok((host, addrs))
 }
 
 /// Fetch an ActivityPub JSON-LD document from a remote URL.
@@ -211,6 +223,19 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn resolved_addresses_are_the_checked_ones() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (host, addrs) = rt
+            .block_on(resolve_safe_url("https://8.8.8.8:8443/video.mp4"))
+            .unwrap();
+        assert_eq!(host, "8.8.8.8");
+        assert_eq!(addrs, vec!["8.8.8.8:8443".parse::<SocketAddr>().unwrap()]);
+        assert!(rt
+            .block_on(resolve_safe_url("https://10.0.0.7/video.mp4"))
+            .is_err());
+    }
+
     #[test]
     fn host_port_extraction() {
         assert_eq!(
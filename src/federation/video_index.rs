@@ -4,43 +4,61 @@ use serde_json::{json, Value};
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::handlers::taxonomy;
+use crate::handlers::video::LISTED_VIDEO_SQL;
+
 /// The ActivityPub `as:Public` audience URI.
 const AP_PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
 
 // ── AP object building ─────────────────────────────────────────────────────
 
+type LocalVideoRow = (
+    String,         // title
+    String,         // description
+    i64,            // price_cents
+    String,         // currency
+    String,         // owner_id
+    String,         // federation_visibility
+    Option<String>, // object_uri
+    bool,           // listed
+);
+
 /// Build the ActivityPub Video object for a local video.
 ///
 /// Returns `None` when the video is not eligible for federation
-/// (e.g., `federation_visibility` is not `public`).
+/// (e.g., `federation_visibility` is not `public`, or the video is not
+/// publicly listed — see [`is_federation_eligible`]).
 pub async fn build_video_object(
     pool: &PgPool,
     video_id: &str,
     base_url: &str,
 ) -> anyhow::Result<Option<Value>> {
-    let row: Option<(
-        String,         // title
-        String,         // description
-        i64,            // price_cents
-        String,         // owner_id
-        String,         // federation_visibility
-        Option<String>, // object_uri
-    )> = sqlx::query_as(
-        "SELECT title, description, price_cents, owner_id, \
-                federation_visibility, object_uri \
-         FROM videos WHERE id = $1 LIMIT 1",
-    )
-    .bind(video_id)
-    .fetch_optional(pool)
-    .await
-    .context("video lookup failed")?;
-
-    let Some((title, description, price_cents, owner_id, visibility, existing_object_uri)) = row
+    let sql = format!(
+        "SELECT v.title, v.description, v.price_cents, v.currency, v.owner_id, \
+                v.federation_visibility, v.object_uri, ({LISTED_VIDEO_SQL}) AS listed \
+         FROM videos v WHERE v.id = $1 LIMIT 1"
+    );
+    let row: Option<LocalVideoRow> = sqlx::query_as(&sql)
+        .bind(video_id)
+        .fetch_optional(pool)
+        .await
+        .context("video lookup failed")?;
+
+    let Some((
+        title,
+        description,
+        price_cents,
+        price_currency,
+        owner_id,
+        visibility,
+        existing_object_uri,
+        listed,
+    )) = row
     else {
         return Ok(None);
     };
 
-    if visibility != "public" {
+    if visibility != "public" || !listed {
         return Ok(None);
     }
 
@@ -59,13 +77,28 @@ pub async fn build_video_object(
     let object_uri =
         existing_object_uri.unwrap_or_else(|| format!("{}/videos/{}", base_url, video_id));
 
-    // Price in major units (e.g. cents → dollars)
-    let price_major = (price_cents as f64) / 100.0;
-    let price_str = format!("{:.2}", price_major);
+    // Price in major units (e.g. cents → dollars; rupiah has no minor unit)
+    let decimals = crate::currency::minor_exponent(&price_currency);
+    let price_major = (price_cents as f64) / 10f64.powi(decimals as i32);
+    let price_str = format!("{:.*}", decimals as usize, price_major);
 
     let checkout_url = format!("{}/checkout/{}", base_url, video_id);
     let watch_url = format!("{}/watch/{}", base_url, video_id);
 
+    // Tags as ActivityStreams `Hashtag` objects linking to the local browse page.
+    let tags: Vec<Value> = taxonomy::video_tags(pool, video_id)
+        .await
+        .context("video tags lookup failed")?
+        .into_iter()
+        .map(|tag| {
+            json!({
+                "type": "Hashtag",
+                "name": format!("#{}", tag),
+                "href": format!("{}/public/browse.html?tag={}", base_url, tag)
+            })
+        })
+        .collect();
+
     Ok(Some(json!({
         "@context": [
             "https://www.w3.org/ns/activitystreams",
@@ -89,8 +122,9 @@ pub async fn build_video_object(
             "mediaType": "text/html",
             "name":      "Watch on origin"
         }],
+        "tag":         tags,
         "priceAmount":  price_str,
-        "priceCurrency": "USD",
+        "priceCurrency": price_currency,
         "checkoutUrl":  checkout_url
     })))
 }
@@ -107,8 +141,14 @@ pub async fn publish_create(
     video_id: &str,
     base_url: &str,
 ) -> anyhow::Result<Option<String>> {
-    // Ensure object_uri is set
-    let object_uri = format!("{}/videos/{}", base_url, video_id);
+    let video_obj = build_video_object(pool, video_id, base_url).await?;
+    let Some(video_obj) = video_obj else {
+        return Ok(None);
+    };
+
+    // Ensure object_uri is set (only once the video is eligible, so a draft or
+    // scheduled video is announced by the visibility worker when it goes live)
+    let object_uri = video_obj["id"].as_str().unwrap_or_default().to_string();
     sqlx::query(
         "UPDATE videos SET object_uri = $2, federated_at = NOW(), \
                           federation_updated_at = NOW() \
@@ -120,11 +160,6 @@ pub async fn publish_create(
     .await
     .context("video object_uri assignment failed")?;
 
-    let video_obj = build_video_object(pool, video_id, base_url).await?;
-    let Some(video_obj) = video_obj else {
-        return Ok(None);
-    };
-
     let actor_uri = video_obj["attributedTo"]
         .as_str()
         .unwrap_or_default()
@@ -209,7 +244,94 @@ pub async fn publish_delete(pool: &PgPool, video_id: &str, base_url: &str) -> an
         "object": object_uri
     });
 
-    broadcast_to_followers(pool, &actor_uri, &activity, base_url).await
+    broadcast_to_followers(pool, &actor_uri, &activity, base_url).await?;
+
+    // Forget the object URI so a later re-publish broadcasts a fresh Create.
+    sqlx::query(
+        "UPDATE videos SET object_uri = NULL, federation_updated_at = NOW() \
+         WHERE id = $1",
+    )
+    .bind(video_id)
+    .execute(pool)
+    .await
+    .context("video object_uri reset failed")?;
+    Ok(())
+}
+
+/// Whether a local video should currently be federated: `federation_visibility`
+/// is `public` and the video is publicly listed (public, transcoded, and inside
+/// its publish window).
+pub async fn is_federation_eligible(pool: &PgPool, video_id: &str) -> anyhow::Result<bool> {
+    let sql = format!(
+        "SELECT EXISTS(SELECT 1 FROM videos v \
+         WHERE v.id = $1 AND v.federation_visibility = 'public' AND {LISTED_VIDEO_SQL})"
+    );
+    sqlx::query_scalar(&sql)
+        .bind(video_id)
+        .fetch_one(pool)
+        .await
+        .context("federation eligibility lookup failed")
+}
+
+// ── Scheduled visibility ───────────────────────────────────────────────────
+
+/// How often the visibility worker reconciles federated state.
+const VISIBILITY_POLL_SECS: u64 = 60;
+/// Maximum videos announced or withdrawn per tick.
+const VISIBILITY_BATCH: i64 = 50;
+
+/// Spawn the worker that keeps federation in step with visibility changes
+/// that happen without a request: `publish_at` / `unpublish_at` passing and
+/// transcoding finishing.
+pub fn start_visibility_worker(pool: PgPool, base_url: String) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(VISIBILITY_POLL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = sync_scheduled_visibility(&pool, &base_url).await {
+                tracing::error!("federation visibility worker: {}", e);
+            }
+        }
+    });
+}
+
+/// Broadcast `Create` for eligible videos that were never announced and
+/// `Delete` for announced videos that are no longer eligible.
+async fn sync_scheduled_visibility(pool: &PgPool, base_url: &str) -> anyhow::Result<()> {
+    let eligible = format!("v.federation_visibility = 'public' AND {LISTED_VIDEO_SQL}");
+
+    let to_create: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT v.id FROM videos v WHERE v.object_uri IS NULL AND {eligible} \
+         ORDER BY v.created_at ASC LIMIT $1"
+    ))
+    .bind(VISIBILITY_BATCH)
+    .fetch_all(pool)
+    .await
+    .context("scheduled publish query failed")?;
+
+    for video_id in to_create {
+        if let Err(e) = publish_create(pool, &video_id, base_url).await {
+            tracing::warn!(%video_id, "scheduled federation Create failed: {}", e);
+        }
+    }
+
+    let to_delete: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT v.id FROM videos v WHERE v.object_uri IS NOT NULL AND NOT ({eligible}) \
+         ORDER BY v.created_at ASC LIMIT $1"
+    ))
+    .bind(VISIBILITY_BATCH)
+    .fetch_all(pool)
+    .await
+    .context("scheduled unpublish query failed")?;
+
+    for video_id in to_delete {
+        if let Err(e) = publish_delete(pool, &video_id, base_url).await {
+            tracing::warn!(%video_id, "scheduled federation Delete failed: {}", e);
+        }
+    }
+
+    Ok(())
 }
 
 // ── Processing inbound remote video activities ─────────────────────────────
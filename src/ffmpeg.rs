@@ -10,6 +10,7 @@
 // 5. Detecting source resolution and audio availability.
 // 6. Producing adaptive bitrate HLS output for video streaming.
 // 7. Selecting CPU or hardware accelerated H.264 encoders.
+// 8. Probing and fully decoding quarantined uploads before they are accepted.
 
 use anyhow::{anyhow, Context, Result};
 use std::{path::Path, process::Stdio};
@@ -204,6 +205,121 @@ pub async fn ffprobe_has_audio(input: &str) -> bool {
     }
 }
 
+/// Container and stream summary reported by FFprobe for a quarantined upload.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProbeSummary {
+    /// Comma separated demuxer names, e.g. `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub format_name: String,
+    pub duration_secs: Option<f64>,
+    pub video_streams: usize,
+    pub audio_streams: usize,
+}
+
+/// Runs FFprobe with JSON output and summarises the container.
+///
+/// Unlike the `ffprobe_*` helpers above, failures are reported instead of
+/// swallowed: the error carries FFprobe's stderr so the caller can explain
+/// why a file was rejected.
+pub async fn ffprobe_summary(input: &str) -> Result<ProbeSummary> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=format_name,duration:stream=codec_type",
+            "-of",
+            "json",
+            input,
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("spawn ffprobe")?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !output.status.success() {
+        return Err(anyhow!(if stderr.is_empty() {
+            format!("ffprobe exited with code {:?}", output.status.code())
+        } else {
+            stderr
+        }));
+    }
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("parse ffprobe json")?;
+    let format = &json["format"];
+    let mut summary = ProbeSummary {
+        format_name: format["format_name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        duration_secs: format["duration"]
+            .as_str()
+            .and_then(|d| d.parse::<f64>().ok()),
+        ..ProbeSummary::default()
+    };
+    for stream in json["streams"].as_array().into_iter().flatten() {
+        match stream["codec_type"].as_str() {
+            Some("video") => summary.video_streams += 1,
+            Some("audio") => summary.audio_streams += 1,
+            _ => {}
+        }
+    }
+    Ok(summary)
+}
+
+/// Decodes every frame of the default video and audio streams into the null
+/// muxer (`ffmpeg -xerror -i <input> -f null -`).
+///
+/// FFmpeg can exit with status 0 while still logging decode errors for a
+/// damaged file, so any `-v error` output is treated as a failure. The
+/// returned error holds the first few diagnostic lines. The child process is
+/// killed when `timeout` elapses.
+pub async fn ffmpeg_decode_check(input: &str, timeout: std::time::Duration) -> Result<()> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args([
+        "-hide_banner",
+        "-nostdin",
+        "-v",
+        "error",
+        "-xerror",
+        "-i",
+        input,
+        "-f",
+        "null",
+        "-",
+    ])
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true);
+
+    let output = tokio::time::timeout(timeout, cmd.output())
+        .await
+        .map_err(|_| anyhow!("full decode did not finish within {}s", timeout.as_secs()))?
+        .context("spawn ffmpeg")?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let diagnostics: Vec<&str> = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .take(5)
+        .collect();
+
+    if !output.status.success() || !diagnostics.is_empty() {
+        return Err(anyhow!(if diagnostics.is_empty() {
+            format!("ffmpeg exited with code {:?}", output.status.code())
+        } else {
+            diagnostics.join("; ")
+        }));
+    }
+    Ok(())
+}
+
 #[allow(dead_code)]
 pub async fn encode_hls_abr(
     input_mp4: &str,
@@ -0,0 +1,202 @@
+// src/geo.rs
+//
+// Offline IP-to-country resolution.
+//
+// The database is a CSV file (`GEOIP_FILE`) with one range per line:
+// `start_ip,end_ip,country`. Addresses may be written as IPs or as decimal
+// integers, and quoted, so the free DB-IP "IP to Country Lite" and
+// IP2Location LITE DB1 downloads load unchanged. Extra columns are ignored.
+// The file is read once at startup; without it every country is unknown.
+//
+// `middleware::resolve_client_country` stores the result for each request as
+// a [`ClientCountry`] extension, which handlers take as an extractor.
+
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap},
+};
+use once_cell::sync::OnceCell;
+
+use crate::config::Config;
+
+static GEO_DB: OnceCell<GeoDb> = OnceCell::new();
+
+/// Sorted, non-overlapping address ranges with their country code.
+pub struct GeoDb {
+    ranges: Vec<(u128, u128, [u8; 2])>,
+}
+
+impl GeoDb {
+    /// Parses the CSV described in the module docs. Lines that cannot be read
+    /// are skipped; an empty result is an error.
+    pub fn parse(text: &str) -> Result<GeoDb, String> {
+        let mut ranges = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split(',').map(|f| f.trim().trim_matches('"'));
+            let (Some(start), Some(end), Some(country)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let (Some(start), Some(end)) = (parse_addr(start), parse_addr(end)) else {
+                continue;
+            };
+            let Some(country) = normalize_country(country).ok() else {
+                continue;
+            };
+            if start > end || country == "ZZ" {
+                continue;
+            }
+            let code = country.as_bytes();
+            ranges.push((start, end, [code[0], code[1]]));
+        }
+        if ranges.is_empty() {
+            return Err("no usable ranges".to_string());
+        }
+        ranges.sort_by_key(|r| r.0);
+        Ok(GeoDb { ranges })
+    }
+
+    /// Country code of `ip`, if a range covers it.
+    pub fn lookup(&self, ip: IpAddr) -> Option<String> {
+        let key = addr_key(ip);
+        let idx = self.ranges.partition_point(|r| r.0 <= key);
+        let (_, end, code) = self.ranges.get(idx.checked_sub(1)?)?;
+        (key <= *end).then(|| String::from_utf8_lossy(code).into_owned())
+    }
+
+    pub fn range_count(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
+/// IPv4 addresses share the IPv6 space as `::ffff:a.b.c.d`.
+fn addr_key(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u128::from(v4.to_ipv6_mapped()),
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+fn parse_addr(raw: &str) -> Option<u128> {
+    if let Ok(ip) = raw.parse::<IpAddr>() {
+        return Some(addr_key(ip));
+    }
+    // Decimal form: IPv4 databases number addresses 0..=u32::MAX.
+    let n = raw.parse::<u128>().ok()?;
+    Some(match u32::try_from(n) {
+        Ok(v4) => addr_key(IpAddr::V4(Ipv4Addr::from(v4))),
+        Err(_) => addr_key(IpAddr::V6(Ipv6Addr::from(n))),
+    })
+}
+
+/// Upper-cases an ISO 3166-1 alpha-2 code and checks its shape.
+pub fn normalize_country(raw: &str) -> Result<String, String> {
+    let code = raw.trim().to_ascii_uppercase();
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("invalid country code: {raw}"));
+    }
+    Ok(code)
+}
+
+/// Loads `GEOIP_FILE` once at startup.
+pub fn load(cfg: &Config) {
+    if cfg.geoip_file.trim().is_empty() {
+        tracing::info!("geoip: GEOIP_FILE not set, client countries are unknown");
+        return;
+    }
+    let db = std::fs::read_to_string(&cfg.geoip_file)
+        .map_err(|e| e.to_string())
+        .and_then(|text| GeoDb::parse(&text));
+    match db {
+        Ok(db) => {
+            tracing::info!(
+                "geoip: loaded {} ranges from {}",
+                db.range_count(),
+                cfg.geoip_file
+            );
+            let _ = GEO_DB.set(db);
+        }
+        Err(e) => tracing::warn!("geoip: cannot load {}: {e}", cfg.geoip_file),
+    }
+}
+
+/// Country of `ip` from the loaded database.
+pub fn country_for_ip(ip: IpAddr) -> Option<String> {
+    GEO_DB.get()?.lookup(ip)
+}
+
+/// Client address: the first forwarded hop when proxy headers are trusted,
+/// otherwise the socket peer.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trust_proxy: bool) -> Option<IpAddr> {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .or_else(|| headers.get("x-real-ip").and_then(|v| v.to_str().ok()));
+        if let Some(ip) = forwarded.and_then(|v| v.trim().parse().ok()) {
+            return Some(ip);
+        }
+    }
+    peer
+}
+
+/// Country the current request comes from, `None` when unknown.
+#[derive(Clone, Debug, Default)]
+pub struct ClientCountry(pub Option<String>);
+
+impl ClientCountry {
+    pub fn code(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientCountry {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ClientCountry>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_ranges_in_ip_and_decimal_form() {
+        let db = GeoDb::parse(
+            "# test ranges\n\
+             1.0.0.0,1.0.0.255,AU\n\
+             \"16777472\",\"16778239\",\"cn\",\"China\"\n\
+             2001:db8::,2001:db8::ffff,DE\n\
+             bad,line,XX\n",
+        )
+        .unwrap();
+        assert_eq!(db.range_count(), 3);
+        assert_eq!(db.lookup("1.0.0.7".parse().unwrap()).as_deref(), Some("AU"));
+        assert_eq!(db.lookup("1.0.1.9".parse().unwrap()).as_deref(), Some("CN"));
+        assert_eq!(
+            db.lookup("2001:db8::1".parse().unwrap()).as_deref(),
+            Some("DE")
+        );
+        assert_eq!(db.lookup("1.0.4.0".parse().unwrap()), None);
+        assert_eq!(db.lookup("0.255.255.255".parse().unwrap()), None);
+    }
+}
@@ -113,11 +113,16 @@ pub async fn admin_data(
     .await
     .unwrap_or_default();
 
-    // allowlist
-    let allowlist = sqlx::query(
-        r#"SELECT video_id, username
-           FROM allowlist
-           ORDER BY video_id, username
+    // entitlements
+    let entitlements = sqlx::query(
+        r#"SELECT id, user_id,
+                  COALESCE(video_id, 'bundle:' || bundle_purchase_id::text,
+                           'subscription:' || subscription_id) AS target,
+                  source, COALESCE(reference, '') AS reference,
+                  COALESCE(valid_until::text, '') AS valid_until,
+                  COALESCE(revoked_at::text, '') AS revoked_at
+           FROM entitlements
+           ORDER BY id DESC
            LIMIT $1"#,
     )
     .bind(limit as i64)
@@ -162,7 +167,7 @@ pub async fn admin_data(
         .fetch_one(&st.pool)
         .await
         .unwrap_or(0);
-    let total_allowlist: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM allowlist")
+    let total_entitlements: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM entitlements")
         .fetch_one(&st.pool)
         .await
         .unwrap_or(0);
@@ -270,24 +275,34 @@ pub async fn admin_data(
     }
     html.push_str("</tbody></table></div>");
 
-    // allowlist
+    // entitlements
     html.push_str(&format!(
         r#"
-<div class="card"><h2>allowlist <span class="caps">(total: {})</span></h2>
+<div class="card"><h2>entitlements <span class="caps">(total: {})</span></h2>
 <table>
-  <thead><tr><th>video_id</th><th>username</th></tr></thead>
+  <thead><tr><th>id</th><th>user_id</th><th>target</th><th>source</th><th>reference</th><th>valid_until</th><th>revoked_at</th></tr></thead>
   <tbody>
 "#,
-        total_allowlist
+        total_entitlements
     ));
 
-    for r in &allowlist {
-        let video_id: String = r.get("video_id");
-        let username: String = r.get("username");
+    for r in &entitlements {
+        let id: i64 = r.get("id");
+        let user_id: String = r.get("user_id");
+        let target: String = r.get("target");
+        let source: String = r.get("source");
+        let reference: String = r.get("reference");
+        let valid_until: String = r.get("valid_until");
+        let revoked_at: String = r.get("revoked_at");
         html.push_str(&format!(
-            "<tr><td class='mono'>{}</td><td>{}</td></tr>",
-            esc(&video_id),
-            esc(&username)
+            "<tr><td class='mono'>{}</td><td class='mono'>{}</td><td class='mono'>{}</td><td>{}</td><td class='mono'>{}</td><td class='mono'>{}</td><td class='mono'>{}</td></tr>",
+            id,
+            esc(&user_id),
+            esc(&target),
+            esc(&source),
+            esc(&reference),
+            esc(&valid_until),
+            esc(&revoked_at)
         ));
     }
     html.push_str("</tbody></table></div>");
@@ -374,19 +389,23 @@ pub async fn admin_payments(
             fi.provider,
             fi.status,
             fi.amount,
+            fi.refunded_amount,
             fi.currency,
             fi.payment_url,
             fi.created_at::TEXT    AS created_at,
             fi.paid_at::TEXT       AS paid_at,
             fi.disbursed_at::TEXT  AS disbursed_at,
             fi.disburse_ref,
+            fi.payout_id,
             buyer.username         AS buyer_username,
-            v.title                AS video_title,
+            COALESCE(v.title, b.title) AS video_title,
+            fi.bundle_id,
             creator.username       AS creator_username,
             creator.bank_account   AS creator_bank
         FROM fiat_invoices fi
         JOIN users  buyer   ON buyer.id   = fi.user_id
-        JOIN videos v       ON v.id       = fi.video_id
+        LEFT JOIN videos v  ON v.id       = fi.video_id
+        LEFT JOIN bundles b ON b.id       = fi.bundle_id
         JOIN users  creator ON creator.id = fi.creator_id
         "#,
     );
@@ -419,14 +438,17 @@ pub async fn admin_payments(
                 "provider":         r.try_get::<String, _>("provider").unwrap_or_default(),
                 "status":           r.try_get::<String, _>("status").unwrap_or_default(),
                 "amount":           r.try_get::<i64,   _>("amount").unwrap_or(0),
+                "refunded_amount":  r.try_get::<i64,   _>("refunded_amount").unwrap_or(0),
                 "currency":         r.try_get::<String, _>("currency").unwrap_or_default(),
                 "payment_url":      r.try_get::<Option<String>, _>("payment_url").unwrap_or(None),
                 "created_at":       r.try_get::<Option<String>, _>("created_at").unwrap_or(None),
                 "paid_at":          r.try_get::<Option<String>, _>("paid_at").unwrap_or(None),
                 "disbursed_at":     r.try_get::<Option<String>, _>("disbursed_at").unwrap_or(None),
                 "disburse_ref":     r.try_get::<Option<String>, _>("disburse_ref").unwrap_or(None),
+                "payout_id":        r.try_get::<Option<String>, _>("payout_id").unwrap_or(None),
                 "buyer_username":   r.try_get::<String, _>("buyer_username").unwrap_or_default(),
                 "video_title":      r.try_get::<Option<String>, _>("video_title").unwrap_or(None),
+                "bundle_id":        r.try_get::<Option<String>, _>("bundle_id").unwrap_or(None),
                 "creator_username": r.try_get::<String, _>("creator_username").unwrap_or_default(),
                 "creator_bank":     r.try_get::<Option<String>, _>("creator_bank").unwrap_or(None),
             })
@@ -487,7 +509,8 @@ pub async fn admin_disburse(
 
     // Fetch invoice + creator bank_account in one query
     let row = sqlx::query(
-        r#"SELECT fi.provider, fi.amount, fi.currency, fi.status, fi.disbursed_at,
+        r#"SELECT fi.provider, fi.amount - fi.refunded_amount AS amount, fi.currency,
+                  fi.status, fi.disbursed_at IS NOT NULL AS disbursed, fi.payout_id,
                   creator.bank_account AS creator_bank
            FROM fiat_invoices fi
            JOIN users creator ON creator.id = fi.creator_id
@@ -504,16 +527,24 @@ pub async fn admin_disburse(
     };
 
     let status: String = row.try_get("status").unwrap_or_default();
-    let disbursed_at: Option<String> = row.try_get("disbursed_at").unwrap_or(None);
+    let disbursed: bool = row.try_get("disbursed").unwrap_or(false);
+    let payout_id: Option<String> = row.try_get("payout_id").unwrap_or(None);
     let provider: String = row.try_get("provider").unwrap_or_default();
     let amount: i64 = row.try_get("amount").unwrap_or(0);
 
-    if status != "paid" {
+    // Partially refunded invoices pay out what the buyer kept.
+    if status != "paid" && status != "partially_refunded" {
         return Json(json!({"ok": false, "error": "invoice is not paid yet"}));
     }
-    if disbursed_at.is_some() {
+    if disbursed {
         return Json(json!({"ok": false, "error": "already disbursed"}));
     }
+    // The payout engine owns it now; see /admin/payouts.
+    if let Some(payout_id) = payout_id {
+        return Json(
+            json!({"ok": false, "error": format!("invoice is part of payout {payout_id}")}),
+        );
+    }
 
     if provider == "xendit" {
         // Try actual Xendit disbursement
@@ -624,9 +655,28 @@ pub struct PaymentSettingsSavePayload {
     pub xendit_enabled: bool,
     pub midtrans_enabled: bool,
     pub x402_enabled: bool,
+    #[serde(default)]
+    pub solana_enabled: bool,
+    #[serde(default)]
+    pub lightning_enabled: bool,
     pub default_provider: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct X402ChainSavePayload {
+    pub chain_id: i64,
+    pub name: String,
+    pub contract_address: String,
+    pub rpc_http: String,
+    pub rpc_wss: Option<String>,
+    pub confirmations: Option<i32>,
+    pub start_block: Option<i64>,
+    pub is_active: Option<bool>,
+    /// Symbols of the chain's `pay_tokens` to offer; the others are
+    /// deactivated. Omitted: tokens are left as they are.
+    pub tokens: Option<Vec<String>>,
+}
+
 #[derive(Deserialize)]
 pub struct StorageSettingsSavePayload {
     pub backend: String,
@@ -1699,6 +1749,8 @@ pub async fn admin_payment_settings_save(
         ("xendit", p.xendit_enabled),
         ("midtrans", p.midtrans_enabled),
         ("x402", p.x402_enabled),
+        ("solana", p.solana_enabled),
+        ("lightning", p.lightning_enabled),
     ];
 
     for (provider, enabled) in requested {
@@ -1723,6 +1775,8 @@ pub async fn admin_payment_settings_save(
             "xendit" => p.xendit_enabled,
             "midtrans" => p.midtrans_enabled,
             "x402" => p.x402_enabled,
+            "solana" => p.solana_enabled,
+            "lightning" => p.lightning_enabled,
             _ => false,
         };
         if !enabled {
@@ -1737,9 +1791,9 @@ pub async fn admin_payment_settings_save(
         r#"INSERT INTO payment_settings
               (id, wallet_payment_enabled, wallet_transfer_enabled, paypal_enabled,
                stripe_enabled, xendit_enabled, midtrans_enabled, x402_enabled,
-               default_provider, updated_at)
+               solana_enabled, lightning_enabled, default_provider, updated_at)
            VALUES
-              (TRUE, $1, $2, $3, $4, $5, $6, $7, $8, NOW())
+              (TRUE, $1, $2, $3, $4, $5, $6, $7, $9, $10, $8, NOW())
            ON CONFLICT (id) DO UPDATE
              SET wallet_payment_enabled = $1,
                  wallet_transfer_enabled = $2,
@@ -1748,6 +1802,8 @@ pub async fn admin_payment_settings_save(
                  xendit_enabled = $5,
                  midtrans_enabled = $6,
                  x402_enabled = $7,
+                 solana_enabled = $9,
+                 lightning_enabled = $10,
                  default_provider = $8,
                  updated_at = NOW()"#,
     )
@@ -1759,6 +1815,8 @@ pub async fn admin_payment_settings_save(
     .bind(p.midtrans_enabled)
     .bind(p.x402_enabled)
     .bind(default_provider.as_deref())
+    .bind(p.solana_enabled)
+    .bind(p.lightning_enabled)
     .execute(&st.pool)
     .await;
 
@@ -1774,6 +1832,8 @@ pub async fn admin_payment_settings_save(
                 xendit_enabled = p.xendit_enabled,
                 midtrans_enabled = p.midtrans_enabled,
                 x402_enabled = p.x402_enabled,
+                solana_enabled = p.solana_enabled,
+                lightning_enabled = p.lightning_enabled,
                 default_provider = ?default_provider,
                 "payment settings saved"
             );
@@ -1783,6 +1843,235 @@ pub async fn admin_payment_settings_save(
     }
 }
 
+/// GET /admin/x402_chains
+///
+/// Configured chains (inactive ones included) with all their `pay_tokens`,
+/// and the default chain from the X402_* environment variables.
+pub async fn admin_x402_chains_get(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+) -> impl IntoResponse {
+    if let Err(resp) = ensure_admin_session(&st, &cookies).await {
+        return resp;
+    }
+    let chains = sqlx::query(
+        r#"SELECT chain_id, name, contract_address, rpc_http, rpc_wss, confirmations,
+                  start_block, is_active, updated_at
+           FROM x402_chains ORDER BY chain_id"#,
+    )
+    .fetch_all(&st.pool)
+    .await;
+    let tokens = sqlx::query(
+        r#"SELECT chain_id, symbol, decimals, COALESCE(erc20_address, erc20) AS erc20_address,
+                  is_active
+           FROM pay_tokens ORDER BY chain_id, symbol"#,
+    )
+    .fetch_all(&st.pool)
+    .await;
+    let (chains, tokens) = match (chains, tokens) {
+        (Ok(c), Ok(t)) => (c, t),
+        (Err(e), _) | (_, Err(e)) => {
+            return Json(json!({"ok": false, "error": format!("db: {e}")}))
+        }
+    };
+
+    let tokens_of = |chain_id: i64| {
+        tokens
+            .iter()
+            .filter(|t| t.try_get::<i64, _>("chain_id").ok() == Some(chain_id))
+            .map(|t| {
+                json!({
+                    "symbol": t.try_get::<String, _>("symbol").unwrap_or_default(),
+                    "decimals": t.try_get::<i32, _>("decimals").unwrap_or(18),
+                    "erc20": t.try_get::<Option<String>, _>("erc20_address").ok().flatten(),
+                    "is_active": t.try_get::<bool, _>("is_active").unwrap_or(false),
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+    let items: Vec<serde_json::Value> = chains
+        .iter()
+        .map(|c| {
+            let chain_id = c.try_get::<i64, _>("chain_id").unwrap_or(0);
+            json!({
+                "chain_id": chain_id,
+                "name": c.try_get::<String, _>("name").unwrap_or_default(),
+                "contract_address": c.try_get::<String, _>("contract_address").unwrap_or_default(),
+                "rpc_http": c.try_get::<String, _>("rpc_http").unwrap_or_default(),
+                "rpc_wss": c.try_get::<Option<String>, _>("rpc_wss").ok().flatten(),
+                "confirmations": c.try_get::<i32, _>("confirmations").unwrap_or(0),
+                "start_block": c.try_get::<Option<i64>, _>("start_block").ok().flatten(),
+                "is_active": c.try_get::<bool, _>("is_active").unwrap_or(false),
+                "updated_at": c
+                    .try_get::<chrono::DateTime<chrono::Utc>, _>("updated_at")
+                    .ok(),
+                "tokens": tokens_of(chain_id),
+            })
+        })
+        .collect();
+    let env_chain = crate::x402_chains::X402Chain::from_env().map(|chain| {
+        json!({
+            "chain_id": chain.chain_id,
+            "contract_address": chain.contract,
+            "confirmations": chain.confirmations,
+            "overridden": items.iter().any(|c| c["chain_id"] == chain.chain_id),
+            "tokens": tokens_of(chain.chain_id),
+        })
+    });
+
+    Json(json!({"ok": true, "chains": items, "env_chain": env_chain}))
+}
+
+/// POST /admin/x402_chains — adds or updates a chain (keyed by `chain_id`).
+pub async fn admin_x402_chain_save(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Json(p): Json<X402ChainSavePayload>,
+) -> impl IntoResponse {
+    let admin_user_id = match ensure_admin_session(&st, &cookies).await {
+        Ok(user_id) => user_id,
+        Err(resp) => return resp,
+    };
+
+    let name = p.name.trim();
+    let contract = p.contract_address.trim().to_ascii_lowercase();
+    let rpc_http = p.rpc_http.trim();
+    let rpc_wss = p
+        .rpc_wss
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    let confirmations = p.confirmations.unwrap_or(3);
+    let error = if p.chain_id <= 0 {
+        Some("chain_id must be positive")
+    } else if name.is_empty() {
+        Some("name required")
+    } else if !crate::x402_chains::is_contract_address(&contract) {
+        Some("contract_address must be a non-zero 0x address")
+    } else if !(rpc_http.starts_with("http://") || rpc_http.starts_with("https://")) {
+        Some("rpc_http must be an http(s) URL")
+    } else if rpc_wss.is_some_and(|url| {
+        !["ws://", "wss://", "http://", "https://"]
+            .iter()
+            .any(|scheme| url.starts_with(scheme))
+    }) {
+        Some("rpc_wss must be a ws(s) or http(s) URL")
+    } else if !(1..=1000).contains(&confirmations) {
+        Some("confirmations must be between 1 and 1000")
+    } else if p.start_block.is_some_and(|b| b < 0) {
+        Some("start_block must not be negative")
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        return Json(json!({"ok": false, "error": error}));
+    }
+
+    let mut tx = match st.pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let saved = sqlx::query(
+        r#"INSERT INTO x402_chains
+              (chain_id, name, contract_address, rpc_http, rpc_wss, confirmations,
+               start_block, is_active)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+           ON CONFLICT (chain_id) DO UPDATE
+             SET name = $2, contract_address = $3, rpc_http = $4, rpc_wss = $5,
+                 confirmations = $6, start_block = $7, is_active = $8, updated_at = NOW()"#,
+    )
+    .bind(p.chain_id)
+    .bind(name)
+    .bind(&contract)
+    .bind(rpc_http)
+    .bind(rpc_wss)
+    .bind(confirmations)
+    .bind(p.start_block)
+    .bind(p.is_active.unwrap_or(true))
+    .execute(&mut *tx)
+    .await;
+    if let Err(e) = saved {
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+
+    if let Some(symbols) = &p.tokens {
+        let known: Vec<String> =
+            match sqlx::query_scalar("SELECT symbol FROM pay_tokens WHERE chain_id = $1")
+                .bind(p.chain_id)
+                .fetch_all(&mut *tx)
+                .await
+            {
+                Ok(k) => k,
+                Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+            };
+        if let Some(unknown) = symbols.iter().find(|s| !known.contains(s)) {
+            return Json(json!({
+                "ok": false,
+                "error": format!("{unknown} is not a pay_tokens row of chain {}", p.chain_id)
+            }));
+        }
+        if let Err(e) =
+            sqlx::query("UPDATE pay_tokens SET is_active = (symbol = ANY($2)) WHERE chain_id = $1")
+                .bind(p.chain_id)
+                .bind(symbols)
+                .execute(&mut *tx)
+                .await
+        {
+            return Json(json!({"ok": false, "error": format!("db: {e}")}));
+        }
+    }
+    if let Err(e) = tx.commit().await {
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+
+    info!(
+        admin_user_id = %admin_user_id,
+        action = "admin_x402_chain_save",
+        chain_id = p.chain_id,
+        contract = %contract,
+        confirmations,
+        tokens = ?p.tokens,
+        "x402 chain saved"
+    );
+    Json(json!({
+        "ok": true,
+        "message": "Chain saved. Its watcher picks up the change within a minute."
+    }))
+}
+
+/// POST /admin/x402_chains/:chain_id/delete
+///
+/// Removes a configured chain. A chain still set in the X402_* environment
+/// variables falls back to that configuration; deactivate it instead to turn
+/// it off.
+pub async fn admin_x402_chain_delete(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Path(chain_id): Path<i64>,
+) -> impl IntoResponse {
+    let admin_user_id = match ensure_admin_session(&st, &cookies).await {
+        Ok(user_id) => user_id,
+        Err(resp) => return resp,
+    };
+    match sqlx::query("DELETE FROM x402_chains WHERE chain_id = $1")
+        .bind(chain_id)
+        .execute(&st.pool)
+        .await
+    {
+        Ok(r) if r.rows_affected() > 0 => {
+            info!(
+                admin_user_id = %admin_user_id,
+                action = "admin_x402_chain_delete",
+                chain_id,
+                "x402 chain deleted"
+            );
+            Json(json!({"ok": true}))
+        }
+        Ok(_) => Json(json!({"ok": false, "error": "chain not found"})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
 pub async fn admin_smtp_save(
     State(st): State<AdminState>,
     cookies: Cookies,
@@ -0,0 +1,625 @@
+// src/handlers/bundles.rs
+//
+// Bundles: several videos sold as one purchase.
+//
+// A bundle is either a fixed set of the creator's videos (`bundle_videos`) or
+// a pass for one of the creator's series. Buying it writes a single
+// `bundle_purchases` row plus a snapshot of the videos covered at that moment,
+// so later edits to the bundle or series never take access away. Series
+// passes sold with `include_future` additionally cover every video added to
+// the series afterwards. Access itself is a `bundle` entitlement on the
+// purchase (`crate::entitlements`).
+//
+// Checkout goes through `/api/wallet/pay_bundle` or the fiat payment plugins
+// (`bundle_id` on the invoice payload).
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgConnection, PgPool, Row};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::entitlements::{self, Origin};
+use crate::handlers::series::check_owned_videos;
+use crate::handlers::video::{
+    video_item_from_row, VideoItem, VideoState, LISTED_VIDEO_SQL, VIDEO_ITEM_COLUMNS,
+};
+use crate::sessions;
+
+/// Maximum number of videos in a fixed-set bundle.
+pub const MAX_BUNDLE_VIDEOS: usize = 200;
+const MAX_BUNDLE_TITLE_CHARS: usize = 200;
+
+/// Purchase-relevant fields of a bundle.
+pub(crate) struct BundleOffer {
+    pub id: String,
+    pub owner_id: String,
+    pub title: String,
+    pub price_cents: i64,
+    pub active: bool,
+}
+
+pub(crate) async fn load_bundle(
+    pool: &PgPool,
+    bundle_id: &str,
+) -> Result<Option<BundleOffer>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT id, owner_id, title, price_cents, active FROM bundles WHERE id = $1 LIMIT 1",
+    )
+    .bind(bundle_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| BundleOffer {
+        id: r.try_get("id").unwrap_or_default(),
+        owner_id: r.try_get("owner_id").unwrap_or_default(),
+        title: r.try_get("title").unwrap_or_default(),
+        price_cents: r.try_get("price_cents").unwrap_or(0),
+        active: r.try_get("active").unwrap_or(false),
+    }))
+}
+
+/// Checks that `buyer_id` may start a purchase of `bundle`.
+pub(crate) async fn check_bundle_purchasable(
+    pool: &PgPool,
+    bundle: &BundleOffer,
+    buyer_id: &str,
+) -> Result<(), String> {
+    if !bundle.active {
+        return Err("bundle is not on sale".to_string());
+    }
+    if bundle.owner_id == buyer_id {
+        return Err("you own this bundle".to_string());
+    }
+    if bundle.price_cents <= 0 {
+        return Err("bundle has no price set".to_string());
+    }
+    let already: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS(
+          SELECT 1 FROM bundle_purchases bp
+          JOIN entitlements e ON e.bundle_purchase_id = bp.id
+          WHERE bp.bundle_id = $1 AND bp.user_id = $2 AND e.revoked_at IS NULL)
+        "#,
+    )
+    .bind(&bundle.id)
+    .bind(buyer_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?;
+    if already {
+        return Err("already purchased".to_string());
+    }
+    Ok(())
+}
+
+/// Records a bundle purchase, snapshots the videos it covers, and grants it.
+///
+/// Idempotent per buyer and bundle: returns `false` when the buyer already
+/// holds the bundle (e.g. a replayed webhook). A purchase whose entitlement
+/// was revoked by a refund is bought anew.
+pub(crate) async fn record_bundle_purchase(
+    conn: &mut PgConnection,
+    bundle_id: &str,
+    user_id: &str,
+    amount_cents: i64,
+    source: &str,
+    payment_ref: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let purchase_id: Option<i64> = sqlx::query_scalar(
+        r#"
+        INSERT INTO bundle_purchases
+          (bundle_id, user_id, amount_cents, source, payment_ref, include_future)
+        SELECT $1, $2, $3, $4, $5, b.include_future FROM bundles b WHERE b.id = $1
+        ON CONFLICT (bundle_id, user_id) DO UPDATE
+          SET amount_cents = EXCLUDED.amount_cents, source = EXCLUDED.source,
+              payment_ref = EXCLUDED.payment_ref, created_at = NOW()
+          WHERE NOT EXISTS (SELECT 1 FROM entitlements e
+                            WHERE e.bundle_purchase_id = bundle_purchases.id
+                              AND e.revoked_at IS NULL)
+        RETURNING id
+        "#,
+    )
+    .bind(bundle_id)
+    .bind(user_id)
+    .bind(amount_cents)
+    .bind(source)
+    .bind(payment_ref)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(purchase_id) = purchase_id else {
+        return Ok(false);
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO bundle_purchase_videos (purchase_id, video_id)
+        SELECT $1, bv.video_id FROM bundle_videos bv WHERE bv.bundle_id = $2
+        UNION
+        SELECT $1, sv.video_id
+        FROM bundles b
+        JOIN series_videos sv ON sv.series_id = b.series_id
+        WHERE b.id = $2
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(purchase_id)
+    .bind(bundle_id)
+    .execute(&mut *conn)
+    .await?;
+
+    entitlements::grant_bundle(
+        conn,
+        user_id,
+        purchase_id,
+        Origin::from_method(source, payment_ref),
+    )
+    .await?;
+    Ok(true)
+}
+
+#[derive(Deserialize)]
+pub struct BundleListQs {
+    /// Creator username or user id.
+    pub creator: Option<String>,
+    /// Only bundles that currently include this video.
+    pub video_id: Option<String>,
+}
+
+/// GET /api/bundles?creator=...&video_id=...
+///
+/// Active bundles, optionally filtered by creator or by a contained video.
+pub async fn list_bundles(
+    State(st): State<VideoState>,
+    Query(qs): Query<BundleListQs>,
+) -> impl IntoResponse {
+    let creator = qs
+        .creator
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    let video_id = qs
+        .video_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT b.id, b.owner_id, COALESCE(u.username, '') AS owner_name,
+               b.title, b.description, b.price_cents, b.series_id, b.include_future,
+               b.created_at::text AS created_at,
+               (SELECT COUNT(*) FROM bundle_videos bv WHERE bv.bundle_id = b.id)
+             + (SELECT COUNT(*) FROM series_videos sv WHERE sv.series_id = b.series_id)
+               AS video_count
+        FROM bundles b
+        JOIN users u ON u.id = b.owner_id
+        WHERE b.active
+          AND ($1::text IS NULL OR u.username = $1 OR u.id = $1)
+          AND ($2::text IS NULL
+               OR EXISTS (SELECT 1 FROM bundle_videos bv WHERE bv.bundle_id = b.id AND bv.video_id = $2)
+               OR EXISTS (SELECT 1 FROM series_videos sv WHERE sv.series_id = b.series_id AND sv.video_id = $2))
+        ORDER BY b.created_at DESC
+        LIMIT 100
+        "#,
+    )
+    .bind(creator)
+    .bind(video_id)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let bundles: Vec<serde_json::Value> = rows.iter().map(bundle_json).collect();
+    Json(json!({"ok": true, "bundles": bundles}))
+}
+
+fn bundle_json(r: &sqlx::postgres::PgRow) -> serde_json::Value {
+    json!({
+        "id": r.try_get::<String, _>("id").unwrap_or_default(),
+        "owner_id": r.try_get::<String, _>("owner_id").unwrap_or_default(),
+        "owner_name": r.try_get::<String, _>("owner_name").unwrap_or_default(),
+        "title": r.try_get::<String, _>("title").unwrap_or_default(),
+        "description": r.try_get::<String, _>("description").unwrap_or_default(),
+        "price_cents": r.try_get::<i64, _>("price_cents").unwrap_or(0),
+        "series_id": r.try_get::<Option<String>, _>("series_id").ok().flatten(),
+        "include_future": r.try_get::<bool, _>("include_future").unwrap_or(false),
+        "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+        "video_count": r.try_get::<i64, _>("video_count").unwrap_or(0),
+    })
+}
+
+/// GET /api/bundles/:id
+///
+/// Bundle details, its publicly listed videos, and whether the current user
+/// already owns it.
+pub async fn get_bundle(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let row = match sqlx::query(
+        r#"
+        SELECT b.id, b.owner_id, COALESCE(u.username, '') AS owner_name,
+               b.title, b.description, b.price_cents, b.series_id, b.include_future,
+               b.active, b.created_at::text AS created_at,
+               (SELECT COUNT(*) FROM bundle_videos bv WHERE bv.bundle_id = b.id)
+             + (SELECT COUNT(*) FROM series_videos sv WHERE sv.series_id = b.series_id)
+               AS video_count
+        FROM bundles b
+        LEFT JOIN users u ON u.id = b.owner_id
+        WHERE b.id = $1
+        "#,
+    )
+    .bind(&id)
+    .fetch_optional(&st.pool)
+    .await
+    {
+        Ok(Some(row)) => row,
+        Ok(None) => return Json(json!({"ok": false, "error": "not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let viewer = sessions::current_user_id(&st.pool, &st.cfg, &cookies).await;
+    let owner_id: String = row.try_get("owner_id").unwrap_or_default();
+    let is_owner = viewer.as_ref().is_some_and(|(uid, _)| *uid == owner_id);
+    if !row.try_get::<bool, _>("active").unwrap_or(false) && !is_owner {
+        return Json(json!({"ok": false, "error": "not found"}));
+    }
+
+    let purchased = match &viewer {
+        Some((uid, _)) => sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+              SELECT 1 FROM bundle_purchases bp
+              JOIN entitlements e ON e.bundle_purchase_id = bp.id
+              WHERE bp.bundle_id = $1 AND bp.user_id = $2 AND e.revoked_at IS NULL)
+            "#,
+        )
+        .bind(&id)
+        .bind(uid)
+        .fetch_one(&st.pool)
+        .await
+        .unwrap_or(false),
+        None => false,
+    };
+
+    let sql = format!(
+        r#"
+        SELECT {VIDEO_ITEM_COLUMNS}
+        FROM (
+          SELECT bv.video_id, bv.position FROM bundle_videos bv WHERE bv.bundle_id = $1
+          UNION ALL
+          SELECT sv.video_id, sv.position
+          FROM bundles b JOIN series_videos sv ON sv.series_id = b.series_id
+          WHERE b.id = $1
+        ) m
+        JOIN videos v ON v.id = m.video_id
+        LEFT JOIN users u ON u.id = v.owner_id
+        WHERE {LISTED_VIDEO_SQL}
+        ORDER BY m.position ASC
+        "#
+    );
+    let videos: Vec<VideoItem> = match sqlx::query(&sql).bind(&id).fetch_all(&st.pool).await {
+        Ok(rows) => rows.iter().map(video_item_from_row).collect(),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let mut bundle = bundle_json(&row);
+    bundle["active"] = json!(row.try_get::<bool, _>("active").unwrap_or(false));
+    Json(json!({
+        "ok": true,
+        "bundle": bundle,
+        "purchased": purchased,
+        "videos": videos
+    }))
+}
+
+/// GET /api/my_bundles
+///
+/// Bundles created by the current user, including inactive ones, with sales.
+pub async fn my_bundles(State(st): State<VideoState>, cookies: Cookies) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT b.id, b.owner_id, '' AS owner_name, b.title, b.description,
+               b.price_cents, b.series_id, b.include_future, b.active,
+               b.created_at::text AS created_at,
+               (SELECT COUNT(*) FROM bundle_videos bv WHERE bv.bundle_id = b.id)
+             + (SELECT COUNT(*) FROM series_videos sv WHERE sv.series_id = b.series_id)
+               AS video_count,
+               ARRAY(SELECT bv.video_id FROM bundle_videos bv
+                     WHERE bv.bundle_id = b.id ORDER BY bv.position) AS video_ids,
+               (SELECT COUNT(*) FROM bundle_purchases bp WHERE bp.bundle_id = b.id) AS sales
+        FROM bundles b
+        WHERE b.owner_id = $1
+        ORDER BY b.created_at DESC
+        "#,
+    )
+    .bind(&uid)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let bundles: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            let mut b = bundle_json(r);
+            b["active"] = json!(r.try_get::<bool, _>("active").unwrap_or(false));
+            b["video_ids"] = json!(r.try_get::<Vec<String>, _>("video_ids").unwrap_or_default());
+            b["sales"] = json!(r.try_get::<i64, _>("sales").unwrap_or(0));
+            b
+        })
+        .collect();
+
+    Json(json!({"ok": true, "bundles": bundles}))
+}
+
+#[derive(Deserialize)]
+pub struct BundlePayload {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub price_cents: Option<i64>,
+    /// Fixed-set bundle: ordered video ids. Mutually exclusive with `series_id`.
+    pub video_ids: Option<Vec<String>>,
+    /// Series pass: the series to sell. Only accepted on creation.
+    pub series_id: Option<String>,
+    /// Series passes only: also grant videos added to the series later.
+    pub include_future: Option<bool>,
+    /// Update only: take the bundle off sale (existing buyers keep access).
+    pub active: Option<bool>,
+}
+
+fn validate_bundle_fields(title: &str, price_cents: i64) -> Result<(), String> {
+    if title.is_empty() || title.chars().count() > MAX_BUNDLE_TITLE_CHARS {
+        return Err(format!(
+            "title is required (max {MAX_BUNDLE_TITLE_CHARS} characters)"
+        ));
+    }
+    if price_cents <= 0 {
+        return Err("price_cents must be positive".to_string());
+    }
+    Ok(())
+}
+
+async fn replace_bundle_videos(
+    conn: &mut PgConnection,
+    bundle_id: &str,
+    video_ids: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM bundle_videos WHERE bundle_id = $1")
+        .bind(bundle_id)
+        .execute(&mut *conn)
+        .await?;
+    for (position, video_id) in video_ids.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO bundle_videos (bundle_id, video_id, position) VALUES ($1, $2, $3)",
+        )
+        .bind(bundle_id)
+        .bind(video_id)
+        .bind(position as i32)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// POST /api/bundles
+///
+/// Creates a fixed-set bundle (`video_ids`) or a series pass (`series_id`).
+pub async fn create_bundle(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<BundlePayload>,
+) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let title = p.title.as_deref().unwrap_or_default().trim().to_string();
+    let price_cents = p.price_cents.unwrap_or(0);
+    if let Err(e) = validate_bundle_fields(&title, price_cents) {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let series_id = p
+        .series_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let video_ids = p.video_ids.unwrap_or_default();
+    let include_future = p.include_future.unwrap_or(false);
+
+    match series_id {
+        Some(series_id) => {
+            if !video_ids.is_empty() {
+                return Json(json!({
+                    "ok": false,
+                    "error": "use either video_ids or series_id, not both"
+                }));
+            }
+            let owns_series: bool = sqlx::query_scalar(
+                "SELECT EXISTS(SELECT 1 FROM series WHERE id = $1 AND owner_id = $2)",
+            )
+            .bind(series_id)
+            .bind(&uid)
+            .fetch_one(&st.pool)
+            .await
+            .unwrap_or(false);
+            if !owns_series {
+                return Json(json!({"ok": false, "error": "series not found"}));
+            }
+        }
+        None => {
+            if video_ids.is_empty() {
+                return Json(json!({"ok": false, "error": "video_ids or series_id is required"}));
+            }
+            if include_future {
+                return Json(json!({
+                    "ok": false,
+                    "error": "include_future is only available for series passes"
+                }));
+            }
+            if let Err(e) = check_owned_videos(&st.pool, &uid, &video_ids, MAX_BUNDLE_VIDEOS).await
+            {
+                return Json(json!({"ok": false, "error": e}));
+            }
+        }
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = st.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO bundles (id, owner_id, title, description, price_cents, series_id, include_future)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(&id)
+        .bind(&uid)
+        .bind(&title)
+        .bind(p.description.as_deref().unwrap_or_default().trim())
+        .bind(price_cents)
+        .bind(series_id)
+        .bind(include_future)
+        .execute(&mut *tx)
+        .await?;
+        replace_bundle_videos(&mut tx, &id, &video_ids).await?;
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => Json(json!({"ok": true, "id": id})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// POST /api/bundles/:id
+///
+/// Absent fields are left unchanged. Changes apply to future buyers only;
+/// existing purchases keep the videos they were sold with.
+pub async fn update_bundle(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+    Json(p): Json<BundlePayload>,
+) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let current = match sqlx::query(
+        "SELECT title, price_cents, series_id FROM bundles WHERE id = $1 AND owner_id = $2",
+    )
+    .bind(&id)
+    .bind(&uid)
+    .fetch_optional(&st.pool)
+    .await
+    {
+        Ok(Some(row)) => row,
+        Ok(None) => return Json(json!({"ok": false, "error": "not owner / not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let is_series_pass = current
+        .try_get::<Option<String>, _>("series_id")
+        .ok()
+        .flatten()
+        .is_some();
+
+    let title = p
+        .title
+        .as_deref()
+        .map(|t| t.trim().to_string())
+        .unwrap_or_else(|| current.try_get("title").unwrap_or_default());
+    let price_cents = p
+        .price_cents
+        .unwrap_or_else(|| current.try_get("price_cents").unwrap_or(0));
+    if let Err(e) = validate_bundle_fields(&title, price_cents) {
+        return Json(json!({"ok": false, "error": e}));
+    }
+    if p.series_id.is_some() {
+        return Json(json!({"ok": false, "error": "series_id cannot be changed"}));
+    }
+    if is_series_pass && p.video_ids.is_some() {
+        return Json(json!({"ok": false, "error": "series passes follow their series"}));
+    }
+    if !is_series_pass && p.include_future == Some(true) {
+        return Json(json!({
+            "ok": false,
+            "error": "include_future is only available for series passes"
+        }));
+    }
+    if let Some(video_ids) = &p.video_ids {
+        if video_ids.is_empty() {
+            return Json(json!({"ok": false, "error": "a bundle needs at least one video"}));
+        }
+        if let Err(e) = check_owned_videos(&st.pool, &uid, video_ids, MAX_BUNDLE_VIDEOS).await {
+            return Json(json!({"ok": false, "error": e}));
+        }
+    }
+
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = st.pool.begin().await?;
+        sqlx::query(
+            r#"
+            UPDATE bundles
+            SET title = $2, price_cents = $3,
+                description = COALESCE($4, description),
+                include_future = COALESCE($5, include_future),
+                active = COALESCE($6, active),
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(&id)
+        .bind(&title)
+        .bind(price_cents)
+        .bind(p.description.as_deref().map(str::trim))
+        .bind(p.include_future)
+        .bind(p.active)
+        .execute(&mut *tx)
+        .await?;
+        if let Some(video_ids) = &p.video_ids {
+            replace_bundle_videos(&mut tx, &id, video_ids).await?;
+        }
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => Json(json!({"ok": true})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_fields_require_title_and_positive_price() {
+        assert!(validate_bundle_fields("Season one", 500).is_ok());
+        assert!(validate_bundle_fields("", 500).is_err());
+        assert!(validate_bundle_fields("Season one", 0).is_err());
+        assert!(validate_bundle_fields(&"x".repeat(MAX_BUNDLE_TITLE_CHARS + 1), 500).is_err());
+    }
+}
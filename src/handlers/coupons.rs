@@ -0,0 +1,710 @@
+// src/handlers/coupons.rs
+//
+// Coupon codes for video purchases and rentals.
+//
+// Creators issue coupons for their own videos (one video, or all of them when
+// `video_id` is empty); admins may also issue coupons valid for any creator.
+// A coupon takes a percentage (1-99) or a fixed amount off the price, and
+// always leaves at least one cent to pay. A fixed amount is in the coupon's
+// `currency` (the video's, for a one-video coupon) and is converted at the
+// current rate when the price is in another currency. Limits: an optional total
+// `max_redemptions`, a `per_user_limit`, and an optional validity window.
+//
+// Checkout (`wallet_pay_video`, `x402_start`, `create_invoice_with_provider`)
+// calls [`apply_coupon`] to price the sale and [`redeem`] to record the use,
+// so the creator split and affiliate commission are computed from the
+// discounted amount. Fiat and x402 checkouts hold their use as `pending`
+// until [`confirm_redemption`] runs on payment; an unpaid hold stops counting
+// after `PENDING_HOLD_MINUTES`.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgConnection, PgExecutor, PgPool, Row};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::currency;
+use crate::handlers::rentals;
+use crate::handlers::video::{parse_schedule_field, VideoState};
+use crate::sessions;
+
+/// Accepted coupon kinds.
+pub const COUPON_KINDS: [&str; 2] = ["percent", "fixed"];
+const MIN_CODE_CHARS: usize = 3;
+const MAX_CODE_CHARS: usize = 32;
+/// How long an unpaid fiat / x402 checkout holds a coupon use.
+const PENDING_HOLD_MINUTES: i32 = 60;
+
+/// A coupon priced against one sale.
+#[derive(Debug, Clone)]
+pub(crate) struct AppliedCoupon {
+    pub coupon_id: String,
+    pub code: String,
+    pub original_cents: i64,
+    pub discount_cents: i64,
+}
+
+impl AppliedCoupon {
+    pub fn final_cents(&self) -> i64 {
+        self.original_cents - self.discount_cents
+    }
+}
+
+/// Amount taken off `price_cents`; never more than `price_cents - 1`.
+pub(crate) fn discount_cents(kind: &str, value: i64, price_cents: i64) -> i64 {
+    let raw = match kind {
+        "percent" => ((price_cents as i128) * (value as i128) / 100) as i64,
+        _ => value,
+    };
+    raw.clamp(0, (price_cents - 1).max(0))
+}
+
+/// Upper-cases a code and checks its length and characters.
+pub(crate) fn normalize_code(raw: &str) -> Result<String, String> {
+    let code = raw.trim().to_ascii_uppercase();
+    let len = code.chars().count();
+    if !(MIN_CODE_CHARS..=MAX_CODE_CHARS).contains(&len)
+        || !code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "code must be {MIN_CODE_CHARS}-{MAX_CODE_CHARS} letters, digits, '-' or '_'"
+        ));
+    }
+    Ok(code)
+}
+
+/// Uses of a coupon that count against its limits: `(total, by this user)`.
+async fn usage(
+    conn: &mut PgConnection,
+    coupon_id: &str,
+    user_id: &str,
+) -> Result<(i64, i64), sqlx::Error> {
+    let row = sqlx::query(
+        r#"
+        SELECT COUNT(*) AS total,
+               COUNT(*) FILTER (WHERE user_id = $2) AS by_user
+        FROM coupon_redemptions
+        WHERE coupon_id = $1
+          AND (status = 'redeemed'
+               OR (status = 'pending' AND created_at > NOW() - make_interval(mins => $3)))
+        "#,
+    )
+    .bind(coupon_id)
+    .bind(user_id)
+    .bind(PENDING_HOLD_MINUTES)
+    .fetch_one(conn)
+    .await?;
+    Ok((
+        row.try_get("total").unwrap_or(0),
+        row.try_get("by_user").unwrap_or(0),
+    ))
+}
+
+fn check_limits(
+    max_redemptions: Option<i32>,
+    per_user_limit: i32,
+    (total, by_user): (i64, i64),
+) -> Result<(), String> {
+    if max_redemptions.is_some_and(|max| total >= max as i64) {
+        return Err("coupon has been fully redeemed".to_string());
+    }
+    if by_user >= per_user_limit as i64 {
+        return Err("you have already used this coupon".to_string());
+    }
+    Ok(())
+}
+
+/// Prices a sale of `video_id` at `price_cents` with `code`.
+pub(crate) async fn apply_coupon(
+    pool: &PgPool,
+    code: &str,
+    video_id: &str,
+    user_id: &str,
+    price_cents: i64,
+) -> Result<AppliedCoupon, String> {
+    let row = sqlx::query(
+        r#"
+        SELECT c.id, c.code, c.kind, c.value, c.currency, v.currency AS price_currency,
+               c.max_redemptions, c.per_user_limit,
+               (c.active
+                AND (c.starts_at IS NULL OR c.starts_at <= NOW())
+                AND (c.ends_at IS NULL OR c.ends_at > NOW())) AS live,
+               ((c.video_id IS NULL OR c.video_id = v.id)
+                AND (c.creator_id IS NULL OR c.creator_id = v.owner_id)) AS applies
+        FROM coupons c
+        JOIN videos v ON v.id = $2
+        WHERE LOWER(c.code) = LOWER($1)
+        LIMIT 1
+        "#,
+    )
+    .bind(code.trim())
+    .bind(video_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?
+    .ok_or_else(|| "invalid coupon code".to_string())?;
+
+    if !row.try_get::<bool, _>("live").unwrap_or(false) {
+        return Err("coupon is not valid right now".to_string());
+    }
+    if !row.try_get::<bool, _>("applies").unwrap_or(false) {
+        return Err("coupon does not apply to this video".to_string());
+    }
+
+    let coupon_id: String = row.try_get("id").unwrap_or_default();
+    let mut conn = pool.acquire().await.map_err(|e| format!("db: {e}"))?;
+    let used = usage(&mut conn, &coupon_id, user_id)
+        .await
+        .map_err(|e| format!("db: {e}"))?;
+    check_limits(
+        row.try_get("max_redemptions").ok().flatten(),
+        row.try_get("per_user_limit").unwrap_or(1),
+        used,
+    )?;
+
+    let kind: String = row.try_get("kind").unwrap_or_default();
+    let mut value: i64 = row.try_get("value").unwrap_or(0);
+    if kind == "fixed" {
+        let coupon_currency: String = row
+            .try_get::<Option<String>, _>("currency")
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| currency::BASE_CURRENCY.into());
+        let price_currency: String = row
+            .try_get("price_currency")
+            .unwrap_or_else(|_| currency::BASE_CURRENCY.into());
+        value = currency::convert_now(pool, value, &coupon_currency, &price_currency).await?;
+    }
+    Ok(AppliedCoupon {
+        coupon_id,
+        code: row.try_get("code").unwrap_or_default(),
+        original_cents: price_cents,
+        discount_cents: discount_cents(&kind, value, price_cents),
+    })
+}
+
+/// Records one use of an applied coupon, re-checking the limits under a row
+/// lock on the coupon. Call inside the checkout transaction.
+///
+/// `reference` is the invoice uid of a fiat / x402 checkout; those uses stay
+/// `pending` until [`confirm_redemption`]. Wallet sales pass `None` and are
+/// redeemed immediately.
+pub(crate) async fn redeem(
+    conn: &mut PgConnection,
+    applied: &AppliedCoupon,
+    user_id: &str,
+    video_id: &str,
+    payment_method: &str,
+    reference: Option<&str>,
+) -> Result<(), String> {
+    let row =
+        sqlx::query("SELECT max_redemptions, per_user_limit FROM coupons WHERE id = $1 FOR UPDATE")
+            .bind(&applied.coupon_id)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| format!("db: {e}"))?;
+
+    let used = usage(conn, &applied.coupon_id, user_id)
+        .await
+        .map_err(|e| format!("db: {e}"))?;
+    check_limits(
+        row.try_get("max_redemptions").ok().flatten(),
+        row.try_get("per_user_limit").unwrap_or(1),
+        used,
+    )?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO coupon_redemptions
+          (coupon_id, user_id, video_id, payment_method, reference,
+           original_cents, discount_cents, status, redeemed_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7,
+                CASE WHEN $5::text IS NULL THEN 'redeemed' ELSE 'pending' END,
+                CASE WHEN $5::text IS NULL THEN NOW() END)
+        "#,
+    )
+    .bind(&applied.coupon_id)
+    .bind(user_id)
+    .bind(video_id)
+    .bind(payment_method)
+    .bind(reference)
+    .bind(applied.original_cents)
+    .bind(applied.discount_cents)
+    .execute(conn)
+    .await
+    .map_err(|e| format!("db: {e}"))?;
+    Ok(())
+}
+
+/// Marks the coupon use held by a paid fiat / x402 invoice as redeemed.
+pub(crate) async fn confirm_redemption(
+    executor: impl PgExecutor<'_>,
+    reference: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE coupon_redemptions SET status = 'redeemed', redeemed_at = NOW() \
+         WHERE reference = $1 AND status = 'pending'",
+    )
+    .bind(reference)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Gives back the use a checkout held when it fails before payment, so the
+/// coupon does not stay consumed for `PENDING_HOLD_MINUTES`.
+pub(crate) async fn release_hold(pool: &PgPool, reference: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM coupon_redemptions WHERE reference = $1 AND status = 'pending'")
+        .bind(reference)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Applies `code` and records a pending use for a fiat / x402 checkout in one
+/// transaction. Returns `None` when no code was given.
+pub(crate) async fn hold_for_checkout(
+    pool: &PgPool,
+    code: Option<&str>,
+    video_id: &str,
+    user_id: &str,
+    price_cents: i64,
+    payment_method: &str,
+    reference: &str,
+) -> Result<Option<AppliedCoupon>, String> {
+    let Some(code) = code.map(str::trim).filter(|c| !c.is_empty()) else {
+        return Ok(None);
+    };
+    let applied = apply_coupon(pool, code, video_id, user_id, price_cents).await?;
+    let mut tx = pool.begin().await.map_err(|e| format!("db: {e}"))?;
+    redeem(
+        &mut tx,
+        &applied,
+        user_id,
+        video_id,
+        payment_method,
+        Some(reference),
+    )
+    .await?;
+    tx.commit().await.map_err(|e| format!("db: {e}"))?;
+    Ok(Some(applied))
+}
+
+// ─── Handlers ────────────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct CouponPayload {
+    pub code: Option<String>,
+    /// `percent` or `fixed`.
+    pub kind: Option<String>,
+    /// Percent off (1-99) or minor units of `currency` off.
+    pub value: Option<i64>,
+    /// Fixed coupons valid for more than one video; defaults to USD. A
+    /// one-video coupon is in the video's currency.
+    pub currency: Option<String>,
+    /// Limit the coupon to one video.
+    pub video_id: Option<String>,
+    /// Admin only: valid for every creator's videos.
+    #[serde(default)]
+    pub store_wide: bool,
+    /// Empty clears the cap.
+    pub max_redemptions: Option<String>,
+    pub per_user_limit: Option<i32>,
+    /// RFC 3339; empty clears.
+    pub starts_at: Option<String>,
+    /// RFC 3339; empty clears.
+    pub ends_at: Option<String>,
+    pub active: Option<bool>,
+}
+
+fn validate_value(kind: &str, value: i64) -> Result<(), String> {
+    match kind {
+        "percent" if (1..=99).contains(&value) => Ok(()),
+        "percent" => Err("percent coupons take 1 to 99 percent off".to_string()),
+        "fixed" if value > 0 => Ok(()),
+        "fixed" => Err("value must be positive".to_string()),
+        _ => Err(format!("kind must be one of: {}", COUPON_KINDS.join(", "))),
+    }
+}
+
+/// `None` → unchanged, `Some(None)` → no cap, `Some(Some(n))` → cap at `n`.
+fn parse_max_redemptions(raw: Option<&str>) -> Result<Option<Option<i32>>, String> {
+    match raw.map(str::trim) {
+        None => Ok(None),
+        Some("") => Ok(Some(None)),
+        Some(v) => match v.parse::<i32>() {
+            Ok(n) if n > 0 => Ok(Some(Some(n))),
+            _ => Err("max_redemptions must be a positive number".to_string()),
+        },
+    }
+}
+
+/// POST /api/coupons
+pub async fn create_coupon(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<CouponPayload>,
+) -> impl IntoResponse {
+    let (uid, is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let code = match normalize_code(p.code.as_deref().unwrap_or_default()) {
+        Ok(c) => c,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let kind = p
+        .kind
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let value = p.value.unwrap_or(0);
+    if let Err(e) = validate_value(&kind, value) {
+        return Json(json!({"ok": false, "error": e}));
+    }
+    let per_user_limit = p.per_user_limit.unwrap_or(1);
+    if per_user_limit <= 0 {
+        return Json(json!({"ok": false, "error": "per_user_limit must be positive"}));
+    }
+    let max_redemptions = match parse_max_redemptions(p.max_redemptions.as_deref()) {
+        Ok(v) => v.flatten(),
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let starts_at = match parse_schedule_field("starts_at", p.starts_at.as_deref()) {
+        Ok(v) => v.flatten(),
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let ends_at = match parse_schedule_field("ends_at", p.ends_at.as_deref()) {
+        Ok(v) => v.flatten(),
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    if let (Some(from), Some(until)) = (starts_at, ends_at) {
+        if until <= from {
+            return Json(json!({"ok": false, "error": "ends_at must be later than starts_at"}));
+        }
+    }
+
+    let video_id = p
+        .video_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string);
+    if p.store_wide && !is_admin {
+        return Json(json!({"ok": false, "error": "admin only"}));
+    }
+
+    // Creator coupons cover the issuer's own videos; a video-specific admin
+    // coupon is bound to that video's creator.
+    let mut video_currency: Option<String> = None;
+    let creator_id: Option<String> = match &video_id {
+        Some(video_id) => {
+            match sqlx::query("SELECT owner_id, currency FROM videos WHERE id = $1")
+                .bind(video_id)
+                .fetch_optional(&st.pool)
+                .await
+            {
+                Ok(Some(row)) => {
+                    let owner: String = row.try_get("owner_id").unwrap_or_default();
+                    if owner != uid && !is_admin {
+                        return Json(json!({"ok": false, "error": "not owner / not found"}));
+                    }
+                    video_currency = row.try_get("currency").ok();
+                    Some(owner)
+                }
+                Ok(None) => return Json(json!({"ok": false, "error": "video not found"})),
+                Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+            }
+        }
+        None if p.store_wide => None,
+        None => Some(uid.clone()),
+    };
+
+    let coupon_currency = if kind == "fixed" {
+        let requested = match p
+            .currency
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+        {
+            Some(raw) => match currency::normalize_currency(raw) {
+                Ok(c) => Some(c),
+                Err(e) => return Json(json!({"ok": false, "error": e})),
+            },
+            None => None,
+        };
+        match (video_currency, requested) {
+            (Some(video), Some(requested)) if video != requested => {
+                return Json(json!({
+                    "ok": false,
+                    "error": format!("this video is priced in {video}")
+                }));
+            }
+            (Some(video), _) => Some(video),
+            (None, requested) => {
+                let c = requested.unwrap_or_else(|| currency::BASE_CURRENCY.into());
+                if let Err(e) = currency::check_supported(&st.pool, &c).await {
+                    return Json(json!({"ok": false, "error": e}));
+                }
+                Some(c)
+            }
+        }
+    } else {
+        None
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let result = sqlx::query(
+        r#"
+        INSERT INTO coupons
+          (id, code, issuer_id, creator_id, video_id, kind, value, currency,
+           max_redemptions, per_user_limit, starts_at, ends_at, active)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        "#,
+    )
+    .bind(&id)
+    .bind(&code)
+    .bind(&uid)
+    .bind(&creator_id)
+    .bind(&video_id)
+    .bind(&kind)
+    .bind(value)
+    .bind(&coupon_currency)
+    .bind(max_redemptions)
+    .bind(per_user_limit)
+    .bind(starts_at)
+    .bind(ends_at)
+    .bind(p.active.unwrap_or(true))
+    .execute(&st.pool)
+    .await;
+
+    match result {
+        Ok(_) => {
+            if is_admin {
+                tracing::info!(
+                    admin_user_id = %uid,
+                    action = "create_coupon",
+                    coupon_id = %id,
+                    code = %code,
+                    "admin action"
+                );
+            }
+            Json(json!({"ok": true, "id": id, "code": code}))
+        }
+        Err(sqlx::Error::Database(db)) if db.is_unique_violation() => {
+            Json(json!({"ok": false, "error": "code already in use"}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// POST /api/coupons/:id
+///
+/// Updates the limits, window, or `active` flag. Code, kind, value, and scope
+/// are fixed once issued; issue a new coupon instead.
+pub async fn update_coupon(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+    Json(p): Json<CouponPayload>,
+) -> impl IntoResponse {
+    let (uid, is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let max_redemptions = match parse_max_redemptions(p.max_redemptions.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    if p.per_user_limit.is_some_and(|n| n <= 0) {
+        return Json(json!({"ok": false, "error": "per_user_limit must be positive"}));
+    }
+    let starts_at = match parse_schedule_field("starts_at", p.starts_at.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let ends_at = match parse_schedule_field("ends_at", p.ends_at.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+
+    let res = sqlx::query(
+        r#"
+        UPDATE coupons
+        SET max_redemptions = CASE WHEN $3 THEN $4 ELSE max_redemptions END,
+            per_user_limit = COALESCE($5, per_user_limit),
+            starts_at = CASE WHEN $6 THEN $7 ELSE starts_at END,
+            ends_at = CASE WHEN $8 THEN $9 ELSE ends_at END,
+            active = COALESCE($10, active),
+            updated_at = NOW()
+        WHERE id = $1 AND (issuer_id = $2 OR $11)
+        "#,
+    )
+    .bind(&id)
+    .bind(&uid)
+    .bind(max_redemptions.is_some())
+    .bind(max_redemptions.flatten())
+    .bind(p.per_user_limit)
+    .bind(starts_at.is_some())
+    .bind(starts_at.flatten())
+    .bind(ends_at.is_some())
+    .bind(ends_at.flatten())
+    .bind(p.active)
+    .bind(is_admin)
+    .execute(&st.pool)
+    .await;
+
+    match res {
+        Ok(r) if r.rows_affected() > 0 => Json(json!({"ok": true})),
+        Ok(_) => Json(json!({"ok": false, "error": "not owner / not found"})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// GET /api/my_coupons
+///
+/// Coupons issued by the current user with their redemption counts.
+pub async fn my_coupons(State(st): State<VideoState>, cookies: Cookies) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT c.id, c.code, c.kind, c.value, c.currency, c.video_id,
+               (c.creator_id IS NULL) AS store_wide,
+               c.max_redemptions, c.per_user_limit, c.active,
+               c.starts_at::text AS starts_at, c.ends_at::text AS ends_at,
+               (SELECT COUNT(*) FROM coupon_redemptions r
+                 WHERE r.coupon_id = c.id AND r.status = 'redeemed') AS redeemed,
+               (SELECT COALESCE(SUM(r.discount_cents), 0)::BIGINT FROM coupon_redemptions r
+                 WHERE r.coupon_id = c.id AND r.status = 'redeemed') AS discount_total_cents
+        FROM coupons c
+        WHERE c.issuer_id = $1
+        ORDER BY c.created_at DESC
+        "#,
+    )
+    .bind(&uid)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let coupons: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<String, _>("id").unwrap_or_default(),
+                "code": r.try_get::<String, _>("code").unwrap_or_default(),
+                "kind": r.try_get::<String, _>("kind").unwrap_or_default(),
+                "value": r.try_get::<i64, _>("value").unwrap_or(0),
+                "currency": r.try_get::<Option<String>, _>("currency").ok().flatten(),
+                "video_id": r.try_get::<Option<String>, _>("video_id").ok().flatten(),
+                "store_wide": r.try_get::<bool, _>("store_wide").unwrap_or(false),
+                "max_redemptions": r.try_get::<Option<i32>, _>("max_redemptions").ok().flatten(),
+                "per_user_limit": r.try_get::<i32, _>("per_user_limit").unwrap_or(1),
+                "starts_at": r.try_get::<Option<String>, _>("starts_at").ok().flatten(),
+                "ends_at": r.try_get::<Option<String>, _>("ends_at").ok().flatten(),
+                "active": r.try_get::<bool, _>("active").unwrap_or(false),
+                "redeemed": r.try_get::<i64, _>("redeemed").unwrap_or(0),
+                "discount_total_cents": r.try_get::<i64, _>("discount_total_cents").unwrap_or(0),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "coupons": coupons}))
+}
+
+#[derive(Deserialize)]
+pub struct CouponCheckQs {
+    pub code: String,
+    pub video_id: String,
+    /// Price the rental instead of the purchase.
+    #[serde(default)]
+    pub rental: bool,
+}
+
+/// GET /api/coupons/check?code=&video_id=
+///
+/// Previews the discounted price for the current user without using the coupon.
+pub async fn check_coupon(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Query(qs): Query<CouponCheckQs>,
+) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let price_cents: Option<i64> = if qs.rental {
+        match rentals::load_rental_offer(&st.pool, &qs.video_id).await {
+            Ok(offer) => offer.map(|o| o.price_cents),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+        }
+    } else {
+        match sqlx::query_scalar::<_, i64>("SELECT price_cents FROM videos WHERE id = $1")
+            .bind(&qs.video_id)
+            .fetch_optional(&st.pool)
+            .await
+        {
+            Ok(price) => price.filter(|p| *p > 0),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+        }
+    };
+    let Some(price_cents) = price_cents else {
+        return Json(json!({"ok": false, "error": "video is not for sale"}));
+    };
+
+    match apply_coupon(&st.pool, &qs.code, &qs.video_id, &uid, price_cents).await {
+        Ok(applied) => Json(json!({
+            "ok": true,
+            "code": applied.code,
+            "original_cents": applied.original_cents,
+            "discount_cents": applied.discount_cents,
+            "final_cents": applied.final_cents(),
+        })),
+        Err(e) => Json(json!({"ok": false, "error": e})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discounts_leave_at_least_one_cent() {
+        assert_eq!(discount_cents("percent", 25, 1000), 250);
+        assert_eq!(discount_cents("percent", 99, 1), 0);
+        assert_eq!(discount_cents("fixed", 300, 1000), 300);
+        assert_eq!(discount_cents("fixed", 5000, 1000), 999);
+    }
+
+    #[test]
+    fn codes_are_normalized() {
+        assert_eq!(normalize_code(" spring-25 ").as_deref(), Ok("SPRING-25"));
+        assert!(normalize_code("ab").is_err());
+        assert!(normalize_code("no spaces").is_err());
+    }
+
+    #[test]
+    fn limits_count_total_and_per_user_uses() {
+        assert!(check_limits(None, 1, (50, 0)).is_ok());
+        assert!(check_limits(Some(10), 1, (10, 0)).is_err());
+        assert!(check_limits(Some(10), 2, (3, 1)).is_ok());
+        assert!(check_limits(Some(10), 2, (3, 2)).is_err());
+    }
+}
@@ -0,0 +1,898 @@
+// src/handlers/gifts.rs
+//
+// Gift purchases and redeemable access codes.
+//
+// A gift is a permanent purchase paid by one user and granted to another,
+// named by username or email. Gifts are paid from the wallet
+// (`POST /api/gifts`) or through a fiat plugin (`gift_to` on the invoice
+// payload, granted by the webhook); both end in [`record_gift`].
+//
+// Access codes are single-use codes for one video, generated in batches for
+// giveaways. Creators issue codes for their own videos for free; anyone else
+// pays the video price per code from their wallet, split with the creator
+// like a normal sale. Redeeming a code grants the same entitlement as a
+// purchase. Revoking a redeemed code revokes that entitlement again;
+// revocation never refunds. Every issue, redemption, and
+// revocation is logged to `access_code_events` for the creator.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgConnection, PgPool, Row};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::currency;
+use crate::entitlements::{self, Origin, Revoke, Source};
+use crate::geo::ClientCountry;
+use crate::handlers::regions;
+use crate::handlers::video::VideoState;
+use crate::handlers::wallet::settle_wallet_sale;
+use crate::payment_settings::load_payment_settings;
+use crate::sessions;
+
+/// Maximum number of codes generated in one batch.
+pub const MAX_CODES_PER_BATCH: i32 = 500;
+const MAX_GIFT_MESSAGE_CHARS: usize = 500;
+/// Code alphabet without look-alike characters (0/O, 1/I/L).
+const CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+const CODE_GROUPS: usize = 3;
+const CODE_GROUP_CHARS: usize = 4;
+
+/// A user receiving a gift or redeeming a code.
+pub(crate) struct Recipient {
+    pub id: String,
+    pub username: String,
+}
+
+/// Finds a user by username or, when `raw` contains `@`, by email.
+pub(crate) async fn resolve_recipient(pool: &PgPool, raw: &str) -> Result<Recipient, String> {
+    let needle = raw.trim();
+    if needle.is_empty() {
+        return Err("recipient required".to_string());
+    }
+    let sql = if needle.contains('@') {
+        "SELECT id, username FROM users WHERE LOWER(email) = LOWER($1) LIMIT 1"
+    } else {
+        "SELECT id, username FROM users WHERE username = $1 LIMIT 1"
+    };
+    match sqlx::query(sql).bind(needle).fetch_optional(pool).await {
+        Ok(Some(r)) => Ok(Recipient {
+            id: r.try_get("id").unwrap_or_default(),
+            username: r.try_get("username").unwrap_or_default(),
+        }),
+        Ok(None) => Err("recipient not found".to_string()),
+        Err(e) => Err(format!("db: {e}")),
+    }
+}
+
+/// Checks that `recipient_id` can be given the video: not its owner and not
+/// already holding a permanent purchase.
+pub(crate) async fn check_giftable(
+    pool: &PgPool,
+    video_id: &str,
+    owner_id: &str,
+    recipient_id: &str,
+) -> Result<(), String> {
+    if recipient_id == owner_id {
+        return Err("recipient owns this video".to_string());
+    }
+    let owned = entitlements::owns_video(pool, video_id, recipient_id)
+        .await
+        .map_err(|e| format!("db: {e}"))?;
+    if owned {
+        return Err("recipient already owns this video".to_string());
+    }
+    Ok(())
+}
+
+/// Grants a paid gift to the recipient and records it.
+///
+/// `reference` is the fiat invoice uid; a replayed webhook for the same
+/// invoice records nothing. Returns `false` when no gift was recorded.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn record_gift(
+    conn: &mut PgConnection,
+    video_id: &str,
+    buyer_id: &str,
+    recipient_id: &str,
+    amount_cents: i64,
+    method: &str,
+    reference: Option<&str>,
+    message: &str,
+) -> Result<bool, sqlx::Error> {
+    if let Some(reference) = reference {
+        let seen: bool =
+            sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM video_gifts WHERE reference = $1)")
+                .bind(reference)
+                .fetch_one(&mut *conn)
+                .await?;
+        if seen {
+            return Ok(false);
+        }
+    }
+
+    let origin = Origin::new(Source::Grant, reference);
+    let purchase_id = entitlements::grant_video(conn, recipient_id, video_id, origin).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO video_gifts
+          (video_id, buyer_id, recipient_id, amount_cents, method, reference, message, purchase_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(video_id)
+    .bind(buyer_id)
+    .bind(recipient_id)
+    .bind(amount_cents)
+    .bind(method)
+    .bind(reference)
+    .bind(message)
+    .bind(purchase_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(true)
+}
+
+/// Trims a gift message and checks its length.
+pub(crate) fn clean_gift_message(raw: Option<&str>) -> Result<String, String> {
+    let message = raw.unwrap_or_default().trim().to_string();
+    if message.chars().count() > MAX_GIFT_MESSAGE_CHARS {
+        return Err(format!(
+            "message must be at most {MAX_GIFT_MESSAGE_CHARS} characters"
+        ));
+    }
+    Ok(message)
+}
+
+/// A fresh random code, e.g. `7KQM-X2PD-R9TB`.
+fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_GROUPS)
+        .map(|_| {
+            (0..CODE_GROUP_CHARS)
+                .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Canonical form of a code typed by a user: upper-case, dashes optional.
+fn normalize_code(raw: &str) -> Option<String> {
+    let chars: String = raw
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if chars.len() != CODE_GROUPS * CODE_GROUP_CHARS
+        || !chars.bytes().all(|b| CODE_ALPHABET.contains(&b))
+    {
+        return None;
+    }
+    let groups: Vec<&str> = (0..CODE_GROUPS)
+        .map(|i| &chars[i * CODE_GROUP_CHARS..(i + 1) * CODE_GROUP_CHARS])
+        .collect();
+    Some(groups.join("-"))
+}
+
+async fn log_code_event(
+    conn: &mut PgConnection,
+    code: &str,
+    video_id: &str,
+    actor_id: &str,
+    action: &str,
+    detail: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO access_code_events (code, video_id, actor_id, action, detail) \
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(code)
+    .bind(video_id)
+    .bind(actor_id)
+    .bind(action)
+    .bind(detail)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+// ─── Gifts ───────────────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct GiftPayload {
+    pub video_id: String,
+    /// Username or email of the recipient.
+    pub recipient: String,
+    pub message: Option<String>,
+    /// Quote from `/api/pay/quote` locking the video-currency → USD rate.
+    pub quote_id: Option<String>,
+}
+
+/// POST /api/gifts
+///
+/// Buys a video for another user with wallet balance.
+pub async fn send_gift(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    country: ClientCountry,
+    Json(p): Json<GiftPayload>,
+) -> impl IntoResponse {
+    if !load_payment_settings(&st.pool).await.wallet_payment_enabled {
+        return Json(
+            json!({"ok": false, "error": "wallet payment is currently disabled by admin"}),
+        );
+    }
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let message = match clean_gift_message(p.message.as_deref()) {
+        Ok(m) => m,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let recipient = match resolve_recipient(&st.pool, &p.recipient).await {
+        Ok(r) => r,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    if recipient.id == uid {
+        return Json(json!({"ok": false, "error": "use /api/wallet/pay to buy for yourself"}));
+    }
+
+    let video = match sqlx::query(
+        "SELECT price_cents, currency, owner_id FROM videos WHERE id = $1 LIMIT 1",
+    )
+    .bind(&p.video_id)
+    .fetch_optional(&st.pool)
+    .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return Json(json!({"ok": false, "error": "video not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    // The buyer's region prices the gift; playback is checked for the recipient.
+    let listed_cents: i64 =
+        match regions::regional_terms(&st.pool, &p.video_id, country.code()).await {
+            Ok(t) => t.price(video.try_get("price_cents").unwrap_or(0)),
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        };
+    let video_currency: String = video
+        .try_get("currency")
+        .unwrap_or_else(|_| currency::BASE_CURRENCY.into());
+    let owner_id: String = video.try_get("owner_id").unwrap_or_default();
+    if listed_cents <= 0 {
+        return Json(json!({"ok": false, "error": "video has no price set"}));
+    }
+    if let Err(e) = check_giftable(&st.pool, &p.video_id, &owner_id, &recipient.id).await {
+        return Json(json!({"ok": false, "error": e}));
+    }
+    let price_cents = match currency::lock_quote(
+        &st.pool,
+        &st.cfg,
+        &uid,
+        p.quote_id.as_deref(),
+        &video_currency,
+        currency::BASE_CURRENCY,
+    )
+    .await
+    {
+        Ok(q) => q.convert(listed_cents),
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+
+    let creator_cut =
+        ((price_cents as i128).saturating_mul(st.cfg.creator_split_bp as i128) / 10_000) as i64;
+
+    let mut tx = match st.pool.begin().await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": format!("begin tx: {e}")})),
+    };
+    let balance = match settle_wallet_sale(
+        &mut tx,
+        &uid,
+        &owner_id,
+        price_cents,
+        creator_cut,
+        &format!("Video gift for {}: {}", recipient.username, p.video_id),
+        &format!("Video gift sale: {}", p.video_id),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": e}));
+        }
+    };
+    if let Err(e) = record_gift(
+        &mut tx,
+        &p.video_id,
+        &uid,
+        &recipient.id,
+        price_cents,
+        "wallet",
+        None,
+        &message,
+    )
+    .await
+    {
+        let _ = tx.rollback().await;
+        return Json(json!({"ok": false, "error": format!("gift: {e}")}));
+    }
+
+    match tx.commit().await {
+        Ok(_) => Json(json!({
+            "ok": true,
+            "video_id": p.video_id,
+            "recipient": recipient.username,
+            "price_cents": price_cents,
+            "balance_cents": balance,
+        })),
+        Err(e) => Json(json!({"ok": false, "error": format!("commit: {e}")})),
+    }
+}
+
+/// GET /api/my_gifts
+///
+/// Gifts the current user sent and received.
+pub async fn my_gifts(State(st): State<VideoState>, cookies: Cookies) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT g.video_id, v.title, g.amount_cents, g.method, g.message,
+               g.buyer_id, b.username AS buyer_username,
+               r.username AS recipient_username, g.created_at::text AS created_at
+        FROM video_gifts g
+        JOIN videos v ON v.id = g.video_id
+        JOIN users b ON b.id = g.buyer_id
+        JOIN users r ON r.id = g.recipient_id
+        WHERE g.buyer_id = $1 OR g.recipient_id = $1
+        ORDER BY g.created_at DESC
+        LIMIT 200
+        "#,
+    )
+    .bind(&uid)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let (mut sent, mut received) = (Vec::new(), Vec::new());
+    for r in &rows {
+        let item = json!({
+            "video_id": r.try_get::<String, _>("video_id").unwrap_or_default(),
+            "title": r.try_get::<String, _>("title").unwrap_or_default(),
+            "amount_cents": r.try_get::<i64, _>("amount_cents").unwrap_or(0),
+            "method": r.try_get::<String, _>("method").unwrap_or_default(),
+            "message": r.try_get::<String, _>("message").unwrap_or_default(),
+            "from": r.try_get::<String, _>("buyer_username").unwrap_or_default(),
+            "to": r.try_get::<String, _>("recipient_username").unwrap_or_default(),
+            "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+        });
+        if r.try_get::<String, _>("buyer_id").unwrap_or_default() == uid {
+            sent.push(item);
+        } else {
+            received.push(item);
+        }
+    }
+    Json(json!({"ok": true, "sent": sent, "received": received}))
+}
+
+// ─── Access codes ────────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct AccessCodesPayload {
+    pub video_id: String,
+    pub quantity: i32,
+    /// Free-form note shown in the creator's code list, e.g. the giveaway name.
+    pub label: Option<String>,
+    /// Quote from `/api/pay/quote` locking the video-currency → USD rate.
+    pub quote_id: Option<String>,
+}
+
+/// POST /api/access_codes
+///
+/// Generates `quantity` single-use codes for a video. Free for the video's
+/// creator; other users pay `price_cents * quantity` from their wallet.
+pub async fn create_access_codes(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<AccessCodesPayload>,
+) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    if !(1..=MAX_CODES_PER_BATCH).contains(&p.quantity) {
+        return Json(json!({
+            "ok": false,
+            "error": format!("quantity must be between 1 and {MAX_CODES_PER_BATCH}")
+        }));
+    }
+    let label = p.label.as_deref().unwrap_or_default().trim().to_string();
+
+    let video = match sqlx::query(
+        "SELECT price_cents, currency, owner_id FROM videos WHERE id = $1 LIMIT 1",
+    )
+    .bind(&p.video_id)
+    .fetch_optional(&st.pool)
+    .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return Json(json!({"ok": false, "error": "video not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let price_cents: i64 = video.try_get("price_cents").unwrap_or(0);
+    let video_currency: String = video
+        .try_get("currency")
+        .unwrap_or_else(|_| currency::BASE_CURRENCY.into());
+    let owner_id: String = video.try_get("owner_id").unwrap_or_default();
+
+    let amount_cents = if owner_id == uid {
+        0
+    } else {
+        if price_cents <= 0 {
+            return Json(json!({"ok": false, "error": "video has no price set"}));
+        }
+        if !load_payment_settings(&st.pool).await.wallet_payment_enabled {
+            return Json(
+                json!({"ok": false, "error": "wallet payment is currently disabled by admin"}),
+            );
+        }
+        match currency::lock_quote(
+            &st.pool,
+            &st.cfg,
+            &uid,
+            p.quote_id.as_deref(),
+            &video_currency,
+            currency::BASE_CURRENCY,
+        )
+        .await
+        {
+            Ok(q) => q.convert(price_cents.saturating_mul(p.quantity as i64)),
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        }
+    };
+
+    let mut tx = match st.pool.begin().await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": format!("begin tx: {e}")})),
+    };
+
+    let mut balance = None;
+    if amount_cents > 0 {
+        let creator_cut = ((amount_cents as i128).saturating_mul(st.cfg.creator_split_bp as i128)
+            / 10_000) as i64;
+        match settle_wallet_sale(
+            &mut tx,
+            &uid,
+            &owner_id,
+            amount_cents,
+            creator_cut,
+            &format!("Access codes x{}: {}", p.quantity, p.video_id),
+            &format!("Access code sale x{}: {}", p.quantity, p.video_id),
+        )
+        .await
+        {
+            Ok(v) => balance = Some(v),
+            Err(e) => {
+                let _ = tx.rollback().await;
+                return Json(json!({"ok": false, "error": e}));
+            }
+        }
+    }
+
+    let batch_id = Uuid::new_v4().to_string();
+    if let Err(e) = sqlx::query(
+        "INSERT INTO access_code_batches (id, video_id, issuer_id, quantity, amount_cents, label) \
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(&batch_id)
+    .bind(&p.video_id)
+    .bind(&uid)
+    .bind(p.quantity)
+    .bind(amount_cents)
+    .bind(&label)
+    .execute(&mut *tx)
+    .await
+    {
+        let _ = tx.rollback().await;
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+
+    let mut codes = Vec::with_capacity(p.quantity as usize);
+    while codes.len() < p.quantity as usize {
+        let code = generate_code();
+        let inserted = sqlx::query(
+            "INSERT INTO access_codes (code, batch_id, video_id) VALUES ($1, $2, $3) \
+             ON CONFLICT (code) DO NOTHING",
+        )
+        .bind(&code)
+        .bind(&batch_id)
+        .bind(&p.video_id)
+        .execute(&mut *tx)
+        .await;
+        match inserted {
+            // A collision with an existing code: draw again.
+            Ok(r) if r.rows_affected() == 0 => continue,
+            Ok(_) => {}
+            Err(e) => {
+                let _ = tx.rollback().await;
+                return Json(json!({"ok": false, "error": format!("db: {e}")}));
+            }
+        }
+        if let Err(e) = log_code_event(&mut tx, &code, &p.video_id, &uid, "issued", &label).await {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": format!("db: {e}")}));
+        }
+        codes.push(code);
+    }
+
+    match tx.commit().await {
+        Ok(_) => Json(json!({
+            "ok": true,
+            "batch_id": batch_id,
+            "video_id": p.video_id,
+            "amount_cents": amount_cents,
+            "balance_cents": balance,
+            "codes": codes,
+        })),
+        Err(e) => Json(json!({"ok": false, "error": format!("commit: {e}")})),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RedeemPayload {
+    pub code: String,
+}
+
+/// POST /api/access_codes/redeem
+///
+/// Redeems a code for the current user. A code is consumed only when it
+/// actually grants access, so users who already own the video keep it.
+pub async fn redeem_access_code(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<RedeemPayload>,
+) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let Some(code) = normalize_code(&p.code) else {
+        return Json(json!({"ok": false, "error": "invalid code"}));
+    };
+
+    let mut tx = match st.pool.begin().await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": format!("begin tx: {e}")})),
+    };
+
+    let row = match sqlx::query(
+        r#"
+        SELECT c.video_id, c.status, v.owner_id
+        FROM access_codes c
+        JOIN videos v ON v.id = c.video_id
+        WHERE c.code = $1
+        FOR UPDATE OF c
+        "#,
+    )
+    .bind(&code)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return Json(json!({"ok": false, "error": "invalid code"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let video_id: String = row.try_get("video_id").unwrap_or_default();
+    let status: String = row.try_get("status").unwrap_or_default();
+    let owner_id: String = row.try_get("owner_id").unwrap_or_default();
+
+    match status.as_str() {
+        "active" => {}
+        "redeemed" => return Json(json!({"ok": false, "error": "code already redeemed"})),
+        _ => return Json(json!({"ok": false, "error": "code revoked"})),
+    }
+    if owner_id == uid {
+        return Json(json!({"ok": false, "error": "you own this video"}));
+    }
+
+    let origin = Origin::new(Source::Grant, Some(&code));
+    let purchase_id = match entitlements::grant_video(&mut tx, &uid, &video_id, origin).await {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return Json(json!({"ok": false, "error": "you already have access to this video"}))
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": format!("purchase: {e}")}));
+        }
+    };
+
+    let updated = sqlx::query(
+        "UPDATE access_codes SET status = 'redeemed', redeemed_by = $2, redeemed_at = NOW(), \
+         purchase_id = $3 WHERE code = $1",
+    )
+    .bind(&code)
+    .bind(&uid)
+    .bind(purchase_id)
+    .execute(&mut *tx)
+    .await;
+    let logged = match updated {
+        Ok(_) => log_code_event(&mut tx, &code, &video_id, &uid, "redeemed", "").await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = logged {
+        let _ = tx.rollback().await;
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+
+    match tx.commit().await {
+        Ok(_) => Json(json!({"ok": true, "video_id": video_id})),
+        Err(e) => Json(json!({"ok": false, "error": format!("commit: {e}")})),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AccessCodesQs {
+    pub video_id: Option<String>,
+    pub batch_id: Option<String>,
+}
+
+/// GET /api/access_codes?video_id=&batch_id=
+///
+/// Codes the current user issued, plus every code for videos they created.
+pub async fn list_access_codes(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Query(q): Query<AccessCodesQs>,
+) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let video_id = q.video_id.as_deref().filter(|v| !v.is_empty());
+    let batch_id = q.batch_id.as_deref().filter(|b| !b.is_empty());
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT c.code, c.video_id, c.batch_id, c.status, b.label,
+               issuer.username AS issued_by, redeemer.username AS redeemed_by,
+               c.redeemed_at::text AS redeemed_at, c.revoked_at::text AS revoked_at,
+               c.created_at::text AS created_at
+        FROM access_codes c
+        JOIN access_code_batches b ON b.id = c.batch_id
+        JOIN videos v ON v.id = c.video_id
+        JOIN users issuer ON issuer.id = b.issuer_id
+        LEFT JOIN users redeemer ON redeemer.id = c.redeemed_by
+        WHERE (b.issuer_id = $1 OR v.owner_id = $1)
+          AND ($2::text IS NULL OR c.video_id = $2)
+          AND ($3::text IS NULL OR c.batch_id = $3)
+        ORDER BY c.created_at DESC, c.code
+        LIMIT 2000
+        "#,
+    )
+    .bind(&uid)
+    .bind(video_id)
+    .bind(batch_id)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let codes: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "code": r.try_get::<String, _>("code").unwrap_or_default(),
+                "video_id": r.try_get::<String, _>("video_id").unwrap_or_default(),
+                "batch_id": r.try_get::<String, _>("batch_id").unwrap_or_default(),
+                "label": r.try_get::<String, _>("label").unwrap_or_default(),
+                "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                "issued_by": r.try_get::<String, _>("issued_by").unwrap_or_default(),
+                "redeemed_by": r.try_get::<Option<String>, _>("redeemed_by").ok().flatten(),
+                "redeemed_at": r.try_get::<Option<String>, _>("redeemed_at").ok().flatten(),
+                "revoked_at": r.try_get::<Option<String>, _>("revoked_at").ok().flatten(),
+                "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "codes": codes}))
+}
+
+/// POST /api/access_codes/:code/revoke
+///
+/// Revokes a code. Allowed for the issuer, the video's creator, and admins.
+/// If the code was already redeemed, the access it granted is removed.
+pub async fn revoke_access_code(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(raw_code): Path<String>,
+) -> impl IntoResponse {
+    let (uid, is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let Some(code) = normalize_code(&raw_code) else {
+        return Json(json!({"ok": false, "error": "invalid code"}));
+    };
+
+    let mut tx = match st.pool.begin().await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": format!("begin tx: {e}")})),
+    };
+
+    let row = match sqlx::query(
+        r#"
+        SELECT c.video_id, c.status, c.purchase_id, b.issuer_id, v.owner_id
+        FROM access_codes c
+        JOIN access_code_batches b ON b.id = c.batch_id
+        JOIN videos v ON v.id = c.video_id
+        WHERE c.code = $1
+        FOR UPDATE OF c
+        "#,
+    )
+    .bind(&code)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return Json(json!({"ok": false, "error": "code not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let video_id: String = row.try_get("video_id").unwrap_or_default();
+    let status: String = row.try_get("status").unwrap_or_default();
+    let purchase_id: Option<i64> = row.try_get("purchase_id").ok().flatten();
+    let issuer_id: String = row.try_get("issuer_id").unwrap_or_default();
+    let owner_id: String = row.try_get("owner_id").unwrap_or_default();
+
+    if uid != issuer_id && uid != owner_id && !is_admin {
+        return Json(json!({"ok": false, "error": "not issuer / not found"}));
+    }
+    if status == "revoked" {
+        return Json(json!({"ok": false, "error": "code already revoked"}));
+    }
+
+    let revoked = match purchase_id {
+        Some(id) => entitlements::revoke(&mut tx, Revoke::Purchase(id), "access code revoked")
+            .await
+            .map(|_| ()),
+        None => Ok(()),
+    };
+    let revoked = match revoked {
+        Ok(_) => sqlx::query(
+            "UPDATE access_codes SET status = 'revoked', revoked_by = $2, revoked_at = NOW() \
+             WHERE code = $1",
+        )
+        .bind(&code)
+        .bind(&uid)
+        .execute(&mut *tx)
+        .await
+        .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    let detail = if status == "redeemed" {
+        "access removed"
+    } else {
+        ""
+    };
+    let logged = match revoked {
+        Ok(_) => log_code_event(&mut tx, &code, &video_id, &uid, "revoked", detail).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = logged {
+        let _ = tx.rollback().await;
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+
+    match tx.commit().await {
+        Ok(_) => {
+            if is_admin && uid != issuer_id && uid != owner_id {
+                tracing::info!(
+                    admin_user_id = %uid,
+                    action = "revoke_access_code",
+                    code = %code,
+                    video_id = %video_id,
+                    "admin action"
+                );
+            }
+            Json(json!({"ok": true, "code": code, "access_removed": status == "redeemed"}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": format!("commit: {e}")})),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CodeEventsQs {
+    pub video_id: String,
+}
+
+/// GET /api/access_codes/events?video_id=
+///
+/// Audit trail of a video's codes for its creator (or an admin).
+pub async fn access_code_events(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Query(q): Query<CodeEventsQs>,
+) -> impl IntoResponse {
+    let (uid, is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    match sqlx::query_scalar::<_, String>("SELECT owner_id FROM videos WHERE id = $1")
+        .bind(&q.video_id)
+        .fetch_optional(&st.pool)
+        .await
+    {
+        Ok(Some(owner)) if owner == uid || is_admin => {}
+        Ok(_) => return Json(json!({"ok": false, "error": "not owner / not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT e.code, e.action, e.detail, u.username AS actor, e.created_at::text AS created_at
+        FROM access_code_events e
+        LEFT JOIN users u ON u.id = e.actor_id
+        WHERE e.video_id = $1
+        ORDER BY e.created_at DESC, e.id DESC
+        LIMIT 2000
+        "#,
+    )
+    .bind(&q.video_id)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let events: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "code": r.try_get::<String, _>("code").unwrap_or_default(),
+                "action": r.try_get::<String, _>("action").unwrap_or_default(),
+                "detail": r.try_get::<String, _>("detail").unwrap_or_default(),
+                "actor": r.try_get::<Option<String>, _>("actor").ok().flatten(),
+                "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "events": events}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_codes_round_trip_through_normalization() {
+        let code = generate_code();
+        assert_eq!(code.len(), 14);
+        assert_eq!(normalize_code(&code).as_deref(), Some(code.as_str()));
+        let typed = code.replace('-', "").to_ascii_lowercase();
+        assert_eq!(normalize_code(&typed).as_deref(), Some(code.as_str()));
+        assert_eq!(normalize_code("0000-1111-IIII"), None);
+        assert_eq!(normalize_code("ABCD-EFGH"), None);
+    }
+}
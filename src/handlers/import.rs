@@ -0,0 +1,901 @@
+// src/handlers/import.rs
+//
+// Bulk video import for creators migrating from other platforms.
+//
+// Creators submit a list of remote HTTPS URLs; admins may additionally import
+// every allowed video file found below a directory on the server, restricted
+// to `IMPORT_ROOT`. Each source becomes one `video_import_job_items` row and is
+// pushed through the same validation, quarantine, and insert/enqueue handoff
+// as `/api/upload`, so imported videos are checked and transcoded exactly
+// like uploads.
+//
+// Remote URLs are checked with `federation::resolver::resolve_safe_url` before
+// every request, including each redirect hop, and the request connects only
+// to the addresses that check resolved, so the importer cannot be used to
+// reach private or loopback addresses (not even by DNS rebinding).
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::Row;
+use std::path::{Path as FsPath, PathBuf};
+use std::time::Duration;
+use tokio::{
+    fs,
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+};
+use tower_cookies::Cookies;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::federation::resolver::resolve_safe_url;
+use crate::handlers::upload::{
+    allowed_extensions, register_video, resolve_upload_dir, NewVideo, UploadState,
+};
+use crate::sessions;
+use crate::storage_settings::collect_local_files;
+
+const MAX_IMPORT_ITEMS: usize = 500;
+const MAX_IMPORT_REDIRECTS: usize = 3;
+const MAX_TITLE_CHARS: usize = 200;
+const IMPORT_CONNECT_TIMEOUT_SECS: u64 = 10;
+const IMPORT_READ_TIMEOUT_SECS: u64 = 60;
+
+#[derive(Deserialize)]
+pub struct ImportUrlsPayload {
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub price_cents: i64,
+}
+
+#[derive(Deserialize)]
+pub struct ImportDirectoryPayload {
+    pub path: String,
+    /// Username that will own the imported videos. Defaults to the admin.
+    pub owner_username: Option<String>,
+    #[serde(default)]
+    pub price_cents: i64,
+}
+
+// ---------------------------------------------------------------------------
+// POST /api/import/url
+// ---------------------------------------------------------------------------
+
+pub async fn import_from_urls(
+    State(st): State<UploadState>,
+    cookies: Cookies,
+    Json(p): Json<ImportUrlsPayload>,
+) -> impl IntoResponse {
+    let Some((user_id, _)) = sessions::current_user_id(&st.pool, &st.cfg, &cookies).await else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({"ok": false, "error": "not logged in"})),
+        );
+    };
+
+    let urls: Vec<String> = p
+        .urls
+        .iter()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+
+    if let Err(message) = validate_item_count(urls.len()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"ok": false, "error": message})),
+        );
+    }
+    if p.price_cents < 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"ok": false, "error": "price_cents must be zero or greater"})),
+        );
+    }
+    if let Some(bad) = urls.iter().find(|url| !url.starts_with("https://")) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"ok": false, "error": format!("remote URL must use HTTPS: {bad}")})),
+        );
+    }
+
+    match create_import_job(&st, &user_id, &user_id, "url", "", p.price_cents, &urls).await {
+        Ok(job_id) => {
+            info!(
+                user_id = %user_id,
+                action = "video_import_url_start",
+                job_id = %job_id,
+                items = urls.len(),
+                "video import started"
+            );
+            (
+                StatusCode::ACCEPTED,
+                Json(json!({
+                    "ok": true,
+                    "job_id": job_id,
+                    "total_items": urls.len(),
+                    "message": "Import started in the background."
+                })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"ok": false, "error": format!("db: {e}")})),
+        ),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/import/directory
+// ---------------------------------------------------------------------------
+
+pub async fn admin_import_directory(
+    State(st): State<UploadState>,
+    cookies: Cookies,
+    Json(p): Json<ImportDirectoryPayload>,
+) -> impl IntoResponse {
+    let admin_user_id = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some((user_id, true)) => user_id,
+        Some(_) => return Json(json!({"ok": false, "error": "admin only"})),
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    if p.price_cents < 0 {
+        return Json(json!({"ok": false, "error": "price_cents must be zero or greater"}));
+    }
+
+    let directory = match resolve_import_directory(&st.cfg.import_root, &p.path).await {
+        Ok(directory) => directory,
+        Err(message) => return Json(json!({"ok": false, "error": message})),
+    };
+
+    let owner_id = match p
+        .owner_username
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        Some(username) => {
+            match sqlx::query_scalar::<_, String>("SELECT id FROM users WHERE username = $1")
+                .bind(username)
+                .fetch_optional(&st.pool)
+                .await
+            {
+                Ok(Some(id)) => id,
+                Ok(None) => return Json(json!({"ok": false, "error": "owner not found"})),
+                Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+            }
+        }
+        None => admin_user_id.clone(),
+    };
+
+    let files = match collect_local_files(&directory).await {
+        Ok(files) => files,
+        Err(e) => return Json(json!({"ok": false, "error": e.to_string()})),
+    };
+    let allowed = allowed_extensions(&st.cfg);
+    let sources: Vec<String> = files
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| allowed.contains(&ext.to_ascii_lowercase()))
+                .unwrap_or(false)
+        })
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+
+    if sources.is_empty() {
+        return Json(json!({
+            "ok": false,
+            "error": "no files with an allowed extension were found in this directory"
+        }));
+    }
+    if let Err(message) = validate_item_count(sources.len()) {
+        return Json(json!({"ok": false, "error": message}));
+    }
+
+    let directory_label = directory.to_string_lossy().to_string();
+    match create_import_job(
+        &st,
+        &owner_id,
+        &admin_user_id,
+        "directory",
+        &directory_label,
+        p.price_cents,
+        &sources,
+    )
+    .await
+    {
+        Ok(job_id) => {
+            info!(
+                admin_user_id = %admin_user_id,
+                action = "admin_video_import_directory_start",
+                job_id = %job_id,
+                owner_id = %owner_id,
+                directory = %directory_label,
+                items = sources.len(),
+                "directory import started"
+            );
+            Json(json!({
+                "ok": true,
+                "job_id": job_id,
+                "total_items": sources.len(),
+                "message": "Import started in the background."
+            }))
+        }
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// GET /api/import/jobs  and  GET /api/import/jobs/:id/items
+// ---------------------------------------------------------------------------
+
+pub async fn list_import_jobs(
+    State(st): State<UploadState>,
+    cookies: Cookies,
+) -> impl IntoResponse {
+    let Some((user_id, _)) = sessions::current_user_id(&st.pool, &st.cfg, &cookies).await else {
+        return Json(json!({"ok": false, "error": "not logged in"}));
+    };
+
+    let rows = match sqlx::query(
+        r#"SELECT id, owner_id, source_kind, source, price_cents, status,
+                  total_items, imported_items, failed_items, skipped_items, last_error,
+                  created_at::TEXT AS created_at, started_at::TEXT AS started_at,
+                  completed_at::TEXT AS completed_at
+           FROM video_import_jobs
+           WHERE owner_id = $1 OR started_by_user_id = $1
+           ORDER BY created_at DESC
+           LIMIT 50"#,
+    )
+    .bind(&user_id)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let jobs: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<String, _>("id").unwrap_or_default(),
+                "owner_id": r.try_get::<String, _>("owner_id").unwrap_or_default(),
+                "source_kind": r.try_get::<String, _>("source_kind").unwrap_or_default(),
+                "source": r.try_get::<String, _>("source").unwrap_or_default(),
+                "price_cents": r.try_get::<i64, _>("price_cents").unwrap_or(0),
+                "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                "total_items": r.try_get::<i64, _>("total_items").unwrap_or(0),
+                "imported_items": r.try_get::<i64, _>("imported_items").unwrap_or(0),
+                "failed_items": r.try_get::<i64, _>("failed_items").unwrap_or(0),
+                "skipped_items": r.try_get::<i64, _>("skipped_items").unwrap_or(0),
+                "last_error": r.try_get::<Option<String>, _>("last_error").unwrap_or(None),
+                "created_at": r.try_get::<Option<String>, _>("created_at").unwrap_or(None),
+                "started_at": r.try_get::<Option<String>, _>("started_at").unwrap_or(None),
+                "completed_at": r.try_get::<Option<String>, _>("completed_at").unwrap_or(None),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "jobs": jobs}))
+}
+
+pub async fn import_job_items(
+    State(st): State<UploadState>,
+    cookies: Cookies,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    let Some((user_id, is_admin)) = sessions::current_user_id(&st.pool, &st.cfg, &cookies).await
+    else {
+        return Json(json!({"ok": false, "error": "not logged in"}));
+    };
+
+    let visible: bool = sqlx::query_scalar(
+        r#"SELECT EXISTS(
+               SELECT 1 FROM video_import_jobs
+               WHERE id = $1 AND ($2 OR owner_id = $3 OR started_by_user_id = $3)
+           )"#,
+    )
+    .bind(&job_id)
+    .bind(is_admin)
+    .bind(&user_id)
+    .fetch_one(&st.pool)
+    .await
+    .unwrap_or(false);
+    if !visible {
+        return Json(json!({"ok": false, "error": "import job not found"}));
+    }
+
+    let rows = match sqlx::query(
+        r#"SELECT id, position, source, title, status, video_id, bytes, error_message,
+                  created_at::TEXT AS created_at, updated_at::TEXT AS updated_at
+           FROM video_import_job_items
+           WHERE job_id = $1
+           ORDER BY position"#,
+    )
+    .bind(&job_id)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let items: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<String, _>("id").unwrap_or_default(),
+                "position": r.try_get::<i32, _>("position").unwrap_or(0),
+                "source": r.try_get::<String, _>("source").unwrap_or_default(),
+                "title": r.try_get::<String, _>("title").unwrap_or_default(),
+                "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                "video_id": r.try_get::<Option<String>, _>("video_id").unwrap_or(None),
+                "bytes": r.try_get::<i64, _>("bytes").unwrap_or(0),
+                "error_message": r.try_get::<Option<String>, _>("error_message").unwrap_or(None),
+                "created_at": r.try_get::<Option<String>, _>("created_at").unwrap_or(None),
+                "updated_at": r.try_get::<Option<String>, _>("updated_at").unwrap_or(None),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "job_id": job_id, "items": items}))
+}
+
+// ---------------------------------------------------------------------------
+// Job creation and background runner
+// ---------------------------------------------------------------------------
+
+fn validate_item_count(count: usize) -> Result<(), String> {
+    if count == 0 {
+        return Err("at least one source is required".to_string());
+    }
+    if count > MAX_IMPORT_ITEMS {
+        return Err(format!(
+            "a single import is limited to {MAX_IMPORT_ITEMS} items ({count} given)"
+        ));
+    }
+    Ok(())
+}
+
+async fn create_import_job(
+    st: &UploadState,
+    owner_id: &str,
+    started_by: &str,
+    source_kind: &str,
+    source: &str,
+    price_cents: i64,
+    sources: &[String],
+) -> anyhow::Result<String> {
+    let job_id = Uuid::new_v4().to_string();
+    let mut tx = st.pool.begin().await?;
+
+    sqlx::query(
+        r#"INSERT INTO video_import_jobs
+              (id, owner_id, source_kind, source, price_cents, status, total_items, started_by_user_id, created_at)
+           VALUES
+              ($1, $2, $3, $4, $5, 'pending', $6, $7, NOW())"#,
+    )
+    .bind(&job_id)
+    .bind(owner_id)
+    .bind(source_kind)
+    .bind(source)
+    .bind(price_cents)
+    .bind(sources.len() as i64)
+    .bind(started_by)
+    .execute(&mut *tx)
+    .await?;
+
+    for (position, item_source) in sources.iter().enumerate() {
+        let (title, _) = title_and_extension(item_source);
+        sqlx::query(
+            r#"INSERT INTO video_import_job_items (id, job_id, position, source, title, status)
+               VALUES ($1, $2, $3, $4, $5, 'pending')"#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&job_id)
+        .bind(position as i32)
+        .bind(item_source)
+        .bind(&title)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    let st = st.clone();
+    let job_id_clone = job_id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = run_import_job(&st, &job_id_clone).await {
+            let _ = sqlx::query(
+                r#"UPDATE video_import_jobs
+                   SET status = 'failed', last_error = $2, completed_at = NOW()
+                   WHERE id = $1"#,
+            )
+            .bind(&job_id_clone)
+            .bind(e.to_string())
+            .execute(&st.pool)
+            .await;
+            warn!(
+                action = "video_import_failed",
+                job_id = %job_id_clone,
+                error = %e,
+                "video import failed"
+            );
+        }
+    });
+
+    Ok(job_id)
+}
+
+async fn run_import_job(st: &UploadState, job_id: &str) -> anyhow::Result<()> {
+    let job = sqlx::query(
+        r#"UPDATE video_import_jobs
+           SET status = 'running', started_at = NOW(), last_error = NULL
+           WHERE id = $1
+           RETURNING owner_id, source_kind, price_cents"#,
+    )
+    .bind(job_id)
+    .fetch_one(&st.pool)
+    .await?;
+
+    let owner_id: String = job.try_get("owner_id")?;
+    let source_kind: String = job.try_get("source_kind")?;
+    let price_cents: i64 = job.try_get("price_cents")?;
+
+    let items = sqlx::query(
+        r#"SELECT id, source, title
+           FROM video_import_job_items
+           WHERE job_id = $1 AND status = 'pending'
+           ORDER BY position"#,
+    )
+    .bind(job_id)
+    .fetch_all(&st.pool)
+    .await?;
+
+    let mut imported = 0_i64;
+    let mut failed = 0_i64;
+    let mut skipped = 0_i64;
+    let mut last_error: Option<String> = None;
+
+    for item in items {
+        let item_id: String = item.try_get("id")?;
+        let source: String = item.try_get("source")?;
+        let title: String = item.try_get("title")?;
+
+        update_item(&st.pool, &item_id, "downloading", None, 0, None).await;
+
+        match import_one(st, &source_kind, &source, &title, &owner_id, price_cents).await {
+            Ok((video_id, bytes)) => {
+                imported += 1;
+                update_item(&st.pool, &item_id, "imported", Some(&video_id), bytes, None).await;
+            }
+            Err(ItemError::Skipped(reason)) => {
+                skipped += 1;
+                update_item(&st.pool, &item_id, "skipped", None, 0, Some(&reason)).await;
+            }
+            Err(ItemError::Failed(reason)) => {
+                failed += 1;
+                warn!(job_id = %job_id, source = %source, error = %reason, "import item failed");
+                update_item(&st.pool, &item_id, "failed", None, 0, Some(&reason)).await;
+                last_error = Some(reason);
+            }
+        }
+
+        let _ = sqlx::query(
+            r#"UPDATE video_import_jobs
+               SET imported_items = $2, failed_items = $3, skipped_items = $4,
+                   last_error = COALESCE($5, last_error)
+               WHERE id = $1"#,
+        )
+        .bind(job_id)
+        .bind(imported)
+        .bind(failed)
+        .bind(skipped)
+        .bind(last_error.as_deref())
+        .execute(&st.pool)
+        .await;
+    }
+
+    let final_status = if failed > 0 {
+        "completed_with_errors"
+    } else {
+        "completed"
+    };
+    sqlx::query(
+        r#"UPDATE video_import_jobs
+           SET status = $2, completed_at = NOW()
+           WHERE id = $1"#,
+    )
+    .bind(job_id)
+    .bind(final_status)
+    .execute(&st.pool)
+    .await?;
+
+    info!(
+        action = "video_import_completed",
+        job_id = %job_id,
+        imported,
+        failed,
+        skipped,
+        "video import finished"
+    );
+    Ok(())
+}
+
+enum ItemError {
+    /// The source is not something we import (e.g. extension not allowed).
+    Skipped(String),
+    Failed(String),
+}
+
+async fn update_item(
+    pool: &sqlx::PgPool,
+    item_id: &str,
+    status: &str,
+    video_id: Option<&str>,
+    bytes: i64,
+    error_message: Option<&str>,
+) {
+    let _ = sqlx::query(
+        r#"UPDATE video_import_job_items
+           SET status = $2, video_id = COALESCE($3, video_id), bytes = $4,
+               error_message = $5, updated_at = NOW()
+           WHERE id = $1"#,
+    )
+    .bind(item_id)
+    .bind(status)
+    .bind(video_id)
+    .bind(bytes)
+    .bind(error_message)
+    .execute(pool)
+    .await;
+}
+
+/// Fetches or copies one source into the upload directory, validates it, and
+/// hands it to the transcoding pipeline. Returns the new video id and size.
+async fn import_one(
+    st: &UploadState,
+    source_kind: &str,
+    source: &str,
+    title: &str,
+    owner_id: &str,
+    price_cents: i64,
+) -> Result<(String, i64), ItemError> {
+    let (_, extension) = title_and_extension(source);
+    if !allowed_extensions(&st.cfg).contains(&extension) {
+        return Err(ItemError::Skipped(format!(
+            "file extension not allowed: .{extension}"
+        )));
+    }
+
+    let upload_dir = resolve_upload_dir(&st.cfg);
+    fs::create_dir_all(&upload_dir)
+        .await
+        .map_err(|e| ItemError::Failed(format!("mkdir_upload: {e}")))?;
+
+    let video_id = Uuid::new_v4().to_string();
+    let filename = format!("{video_id}.{extension}");
+    let full_path = FsPath::new(&upload_dir).join(&filename);
+    let temporary_path = full_path.with_extension(format!("{extension}.part"));
+
+    let fetched = match source_kind {
+        "url" => download_remote_file(source, &temporary_path, st.cfg.max_upload_bytes).await,
+        _ => {
+            copy_local_file(
+                &st.cfg.import_root,
+                source,
+                &temporary_path,
+                st.cfg.max_upload_bytes,
+            )
+            .await
+        }
+    };
+    let bytes = match fetched {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = fs::remove_file(&temporary_path).await;
+            return Err(ItemError::Failed(e));
+        }
+    };
+
+    if let Err(rejection) = st.quarantine.inspect(&temporary_path, &extension).await {
+        let _ = fs::remove_file(&temporary_path).await;
+        return Err(ItemError::Failed(format!("quarantine: {rejection}")));
+    }
+
+    if let Err(e) = fs::rename(&temporary_path, &full_path).await {
+        let _ = fs::remove_file(&temporary_path).await;
+        return Err(ItemError::Failed(format!("rename: {e}")));
+    }
+
+    register_video(
+        st,
+        NewVideo {
+            video_id: &video_id,
+            owner_id,
+            title,
+            price_cents,
+            filename: &filename,
+            path: &full_path,
+            visibility: "public",
+            publish_at: None,
+            unpublish_at: None,
+        },
+    )
+    .await
+    .map_err(|(stage, message)| ItemError::Failed(format!("{stage}: {message}")))?;
+
+    Ok((video_id, bytes as i64))
+}
+
+/// Streams a remote HTTPS file to `dest`, following at most
+/// `MAX_IMPORT_REDIRECTS` redirects and re-checking every hop for SSRF. Every
+/// hop is pinned to the addresses its check resolved.
+async fn download_remote_file(url: &str, dest: &FsPath, max_bytes: u64) -> Result<u64, String> {
+    let mut current = url.to_string();
+    let mut response = None;
+    for _ in 0..=MAX_IMPORT_REDIRECTS {
+        // Each hop connects only to the addresses that were just checked.
+        let (host, addrs) = resolve_safe_url(&current).await?;
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(IMPORT_CONNECT_TIMEOUT_SECS))
+            .read_timeout(Duration::from_secs(IMPORT_READ_TIMEOUT_SECS))
+            .redirect(reqwest::redirect::Policy::none());
+        if !addrs.is_empty() {
+            let parsed =
+                reqwest::Url::parse(&current).map_err(|e| format!("invalid URL {current}: {e}"))?;
+            if parsed.host_str() != Some(host.as_str()) {
+                return Err(format!("unsupported URL host in {current}"));
+            }
+            builder = builder.resolve_to_addrs(&host, &addrs);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("HTTP client build error: {e}"))?;
+        let resp = client
+            .get(&current)
+            .send()
+            .await
+            .map_err(|e| format!("request to {current} failed: {e}"))?;
+
+        if resp.status().is_redirection() {
+            let location = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| format!("redirect from {current} has no Location header"))?;
+            current = resp
+                .url()
+                .join(location)
+                .map_err(|e| format!("invalid redirect target {location}: {e}"))?
+                .to_string();
+            continue;
+        }
+
+        response = Some(resp);
+        break;
+    }
+
+    let Some(mut response) = response else {
+        return Err(format!("too many redirects (limit {MAX_IMPORT_REDIRECTS})"));
+    };
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "remote {current} returned HTTP {}",
+            response.status()
+        ));
+    }
+    if let Some(length) = response.content_length() {
+        if length > max_bytes {
+            return Err(format!("file too large: {length} > {max_bytes} bytes"));
+        }
+    }
+
+    let output_file = File::create(dest)
+        .await
+        .map_err(|e| format!("create_file: {e}"))?;
+    let mut output = BufWriter::with_capacity(1024 * 1024, output_file);
+    let mut total_bytes: u64 = 0;
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("read_chunk: {e}"))?
+    {
+        total_bytes += chunk.len() as u64;
+        if total_bytes > max_bytes {
+            return Err(format!("file too large: exceeds {max_bytes} bytes"));
+        }
+        output
+            .write_all(&chunk)
+            .await
+            .map_err(|e| format!("write_file: {e}"))?;
+    }
+
+    output.flush().await.map_err(|e| format!("flush: {e}"))?;
+    Ok(total_bytes)
+}
+
+/// Copies a file listed from a server folder, once more checking that it
+/// still lies inside `import_root` after following any link.
+async fn copy_local_file(
+    import_root: &str,
+    source: &str,
+    dest: &FsPath,
+    max_bytes: u64,
+) -> Result<u64, String> {
+    let root = fs::canonicalize(import_root)
+        .await
+        .map_err(|e| format!("IMPORT_ROOT {import_root} is not accessible: {e}"))?;
+    let path = fs::canonicalize(source)
+        .await
+        .map_err(|e| format!("stat {source}: {e}"))?;
+    if !path.starts_with(&root) {
+        return Err(format!(
+            "{source} is outside IMPORT_ROOT ({})",
+            root.display()
+        ));
+    }
+    let meta = fs::metadata(&path)
+        .await
+        .map_err(|e| format!("stat {source}: {e}"))?;
+    if !meta.is_file() {
+        return Err(format!("{source} is not a regular file"));
+    }
+    if meta.len() > max_bytes {
+        return Err(format!(
+            "file too large: {} > {max_bytes} bytes",
+            meta.len()
+        ));
+    }
+    // Copy rather than move so the admin's source folder stays untouched.
+    fs::copy(&path, dest)
+        .await
+        .map_err(|e| format!("copy {source}: {e}"))
+}
+
+/// Canonicalises `requested` and checks that it lies inside `import_root`.
+async fn resolve_import_directory(import_root: &str, requested: &str) -> Result<PathBuf, String> {
+    let requested = requested.trim();
+    if requested.is_empty() {
+        return Err("path is required".to_string());
+    }
+
+    let root = fs::canonicalize(import_root)
+        .await
+        .map_err(|e| format!("IMPORT_ROOT {import_root} is not accessible: {e}"))?;
+    let candidate = if FsPath::new(requested).is_absolute() {
+        PathBuf::from(requested)
+    } else {
+        root.join(requested)
+    };
+    let directory = fs::canonicalize(&candidate)
+        .await
+        .map_err(|e| format!("directory {} is not accessible: {e}", candidate.display()))?;
+
+    if !directory.starts_with(&root) {
+        return Err(format!(
+            "directory must be inside IMPORT_ROOT ({})",
+            root.display()
+        ));
+    }
+    if !directory.is_dir() {
+        return Err(format!("{} is not a directory", directory.display()));
+    }
+    Ok(directory)
+}
+
+/// Derives a default title (file stem) and lower-cased extension from a URL or
+/// file path. Query strings and fragments are ignored; a missing extension
+/// falls back to `mp4`, matching the multipart upload handler.
+fn title_and_extension(source: &str) -> (String, String) {
+    let without_query = source
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(source)
+        .trim_end_matches('/');
+    let file_name = without_query
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(without_query);
+
+    let path = FsPath::new(file_name);
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .unwrap_or_else(|| "mp4".to_string());
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(str::trim)
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("Untitled");
+
+    (stem.chars().take(MAX_TITLE_CHARS).collect(), extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_and_extension_from_url() {
+        assert_eq!(
+            title_and_extension("https://cdn.example.com/videos/My%20Clip.MOV?token=abc#t=1"),
+            ("My%20Clip".to_string(), "mov".to_string())
+        );
+    }
+
+    #[test]
+    fn title_and_extension_from_path() {
+        assert_eq!(
+            title_and_extension("/srv/import/course/lesson-01.mkv"),
+            ("lesson-01".to_string(), "mkv".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_extension_defaults_to_mp4() {
+        assert_eq!(
+            title_and_extension("https://example.com/stream/"),
+            ("stream".to_string(), "mp4".to_string())
+        );
+    }
+
+    #[test]
+    fn item_count_is_bounded() {
+        assert!(validate_item_count(0).is_err());
+        assert!(validate_item_count(1).is_ok());
+        assert!(validate_item_count(MAX_IMPORT_ITEMS).is_ok());
+        assert!(validate_item_count(MAX_IMPORT_ITEMS + 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn links_out_of_the_import_root_are_not_imported() {
+        let base = std::env::temp_dir().join(format!("import-test-{}", std::process::id()));
+        let root = base.join("root");
+        let outside = base.join("secret.mp4");
+        fs::create_dir_all(root.join("nested")).await.unwrap();
+        fs::write(root.join("nested/clip.mp4"), b"frames")
+            .await
+            .unwrap();
+        fs::write(&outside, b"host file").await.unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link.mp4")).unwrap();
+        std::os::unix::fs::symlink(&base, root.join("up")).unwrap();
+
+        let listed = collect_local_files(&root).await.unwrap();
+        let root_str = root.to_string_lossy();
+        let copied = copy_local_file(
+            &root_str,
+            &root.join("link.mp4").to_string_lossy(),
+            &base.join("copy.part"),
+            1024,
+        )
+        .await;
+        let nested = copy_local_file(
+            &root_str,
+            &root.join("nested/clip.mp4").to_string_lossy(),
+            &base.join("clip.part"),
+            1024,
+        )
+        .await;
+        let _ = fs::remove_dir_all(&base).await;
+
+        assert_eq!(listed, vec![root.join("nested/clip.mp4")]);
+        assert!(copied.unwrap_err().contains("outside IMPORT_ROOT"));
+        assert_eq!(nested, Ok(6));
+    }
+}
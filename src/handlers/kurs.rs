@@ -1,25 +1,54 @@
 // src/handlers/kurs.rs
 use axum::{extract::State, routing::get, Json, Router};
 use serde::Serialize;
+use sqlx::PgPool;
 
 use crate::config::Config;
+use crate::currency;
 
 #[derive(Clone)]
 pub struct KursState {
+    pub pool: PgPool,
     pub cfg: Config,
 }
 
+#[derive(Serialize)]
+struct RateItem {
+    currency: String,
+    per_usd: f64,
+    fetched_at: String,
+}
+
 #[derive(Serialize)]
 struct KursResp {
     ok: bool,
     usd_to_idr: f64,
+    /// Every stored rate, as units per 1 USD.
+    rates: Vec<RateItem>,
 }
 
-// GET /api/kurs -> { ok: true, usd_to_idr: <f64> }
+// GET /api/kurs -> { ok: true, usd_to_idr: <f64>, rates: [...] }
 async fn get_kurs(State(state): State<KursState>) -> Json<KursResp> {
+    let rates: Vec<RateItem> = currency::list_rates(&state.pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(currency, per_usd, fetched_at)| RateItem {
+            currency,
+            per_usd,
+            fetched_at: fetched_at.to_rfc3339(),
+        })
+        .collect();
+    // Fall back to the configured rate until the first refresh has run.
+    let usd_to_idr = rates
+        .iter()
+        .find(|r| r.currency == "IDR")
+        .map(|r| r.per_usd)
+        .unwrap_or(state.cfg.dollar_usd_to_rupiah);
     Json(KursResp {
         ok: true,
-        usd_to_idr: state.cfg.dollar_usd_to_rupiah,
+        usd_to_idr,
+        rates,
     })
 }
 
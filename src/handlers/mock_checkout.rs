@@ -0,0 +1,225 @@
+// src/handlers/mock_checkout.rs
+//
+// Hosted checkout page of the mock payment provider.
+//
+// The mock plugin's payment URL points here instead of an external provider.
+// The page shows the invoice and lets the buyer (or a test script) pick an
+// outcome; the plugin then sends its signed webhook to
+// `/api/pay/mock/webhook`, so the purchase goes through the same inbox and
+// grant path as a real provider.
+//
+// Both routes answer 404 unless MOCK_PAYMENT_ENABLED is true.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use serde::Deserialize;
+use sqlx::Row;
+
+use crate::handlers::payment_plugins::PaymentPluginState;
+use crate::plugins::payment::providers::mock::{MockCheckout, MockOutcome, MockPaymentPlugin};
+
+#[derive(Deserialize)]
+pub struct CheckoutQuery {
+    pub success_url: Option<String>,
+    pub cancel_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CheckoutForm {
+    pub outcome: String,
+    pub success_url: Option<String>,
+    pub cancel_url: Option<String>,
+}
+
+fn not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Html("<h1>Not found</h1>".to_string()),
+    )
+        .into_response()
+}
+
+fn esc(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Only redirect back into this site.
+fn local_redirect(base_url: &str, url: Option<&str>) -> Option<String> {
+    let url = url?.trim();
+    let base = base_url.trim_end_matches('/');
+    let local = (url.starts_with('/') && !url.starts_with("//"))
+        || url == base
+        || url.starts_with(&format!("{base}/"));
+    local.then(|| url.to_string())
+}
+
+async fn pending_checkout(st: &PaymentPluginState, invoice_uid: &str) -> Option<MockCheckout> {
+    let row = sqlx::query(
+        "SELECT amount, currency FROM fiat_invoices
+         WHERE invoice_uid = $1 AND provider = 'mock' AND status = 'pending'",
+    )
+    .bind(invoice_uid)
+    .fetch_optional(&st.pool)
+    .await
+    .ok()??;
+    Some(MockCheckout {
+        invoice_uid: invoice_uid.to_string(),
+        amount_cents: row.try_get("amount").ok()?,
+        currency: row.try_get("currency").ok()?,
+    })
+}
+
+/// GET /api/pay/mock/checkout/:invoice_uid
+pub async fn checkout_page(
+    State(st): State<PaymentPluginState>,
+    Path(invoice_uid): Path<String>,
+    Query(q): Query<CheckoutQuery>,
+) -> Response {
+    if !MockPaymentPlugin::enabled() {
+        return not_found();
+    }
+    let Some(checkout) = pending_checkout(&st, &invoice_uid).await else {
+        return Html(
+            "<h1>Checkout unavailable</h1><p>This invoice does not exist or is no longer pending.</p>"
+                .to_string(),
+        )
+        .into_response();
+    };
+
+    let options: String = MockOutcome::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, o)| {
+            format!(
+                r#"<div class="form-check"><input class="form-check-input" type="radio" name="outcome" id="o-{0}" value="{0}"{1}><label class="form-check-label" for="o-{0}">{0}</label></div>"#,
+                o.as_str(),
+                if i == 0 { " checked" } else { "" }
+            )
+        })
+        .collect();
+    let hidden = |name: &str, value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|v| format!(r#"<input type="hidden" name="{name}" value="{}">"#, esc(v)))
+            .unwrap_or_default()
+    };
+
+    Html(format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Mock checkout</title>
+  <link href="https://cdn.jsdelivr.net/npm/bootstrap@5.3.3/dist/css/bootstrap.min.css" rel="stylesheet">
+</head>
+<body class="bg-body-tertiary">
+<div class="container py-5" style="max-width: 480px">
+  <div class="card shadow-sm">
+    <div class="card-body">
+      <span class="badge text-bg-warning mb-2">Test provider</span>
+      <h1 class="fs-4 fw-bold">Mock checkout</h1>
+      <p class="text-body-secondary small mb-3">Invoice <code>{uid}</code></p>
+      <p class="fs-5 mb-4">{amount} {currency}</p>
+      <form method="post">
+        {options}
+        {success}{cancel}
+        <button class="btn btn-primary w-100 mt-3" type="submit">Complete payment</button>
+      </form>
+    </div>
+  </div>
+</div>
+</body>
+</html>"#,
+        uid = esc(&checkout.invoice_uid),
+        amount = checkout.amount_cents,
+        currency = esc(&checkout.currency),
+        success = hidden("success_url", &q.success_url),
+        cancel = hidden("cancel_url", &q.cancel_url),
+    ))
+    .into_response()
+}
+
+/// POST /api/pay/mock/checkout/:invoice_uid
+///
+/// Completes the checkout with the chosen outcome, then returns to the
+/// success (or, for `failed`, cancel) URL when one was given.
+pub async fn complete_checkout(
+    State(st): State<PaymentPluginState>,
+    Path(invoice_uid): Path<String>,
+    Form(form): Form<CheckoutForm>,
+) -> Response {
+    if !MockPaymentPlugin::enabled() {
+        return not_found();
+    }
+    let Some(outcome) = MockOutcome::parse(&form.outcome) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Html("<h1>Unknown outcome</h1>".to_string()),
+        )
+            .into_response();
+    };
+    let Some(checkout) = pending_checkout(&st, &invoice_uid).await else {
+        return (
+            StatusCode::CONFLICT,
+            Html("<h1>Checkout unavailable</h1>".to_string()),
+        )
+            .into_response();
+    };
+
+    let plugin = MockPaymentPlugin::from_env();
+    if let Err(e) = plugin.complete_checkout(checkout, outcome).await {
+        tracing::warn!("mock checkout {invoice_uid} ({}): {e}", outcome.as_str());
+        return (
+            StatusCode::BAD_GATEWAY,
+            Html(format!(
+                "<h1>Webhook failed</h1><p>{}</p>",
+                esc(&e.to_string())
+            )),
+        )
+            .into_response();
+    }
+    tracing::info!(
+        "mock checkout {invoice_uid} completed: {}",
+        outcome.as_str()
+    );
+
+    let back = if outcome == MockOutcome::Failed {
+        form.cancel_url.as_deref()
+    } else {
+        form.success_url.as_deref()
+    };
+    match local_redirect(&st.cfg.base_url, back) {
+        Some(url) => Redirect::to(&url).into_response(),
+        None => Html(format!(
+            "<h1>Checkout completed</h1><p>Outcome: <code>{}</code></p>",
+            outcome.as_str()
+        ))
+        .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirects_stay_on_this_site() {
+        let base = "http://localhost:8080";
+        assert_eq!(
+            local_redirect(base, Some("/public/watch.html?v=1")).as_deref(),
+            Some("/public/watch.html?v=1")
+        );
+        assert!(local_redirect(base, Some("http://localhost:8080/public/watch.html")).is_some());
+        assert!(local_redirect(base, Some("//evil.example/")).is_none());
+        assert!(local_redirect(base, Some("http://localhost:8080.evil.example/")).is_none());
+        assert!(local_redirect(base, None).is_none());
+    }
+}
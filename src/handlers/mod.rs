@@ -3,15 +3,31 @@ pub mod admin;
 pub mod affiliate;
 pub mod auth_admin;
 pub mod auth_user;
+pub mod bundles;
 pub mod chat;
+pub mod coupons;
 pub mod creator_block;
+pub mod gifts;
+pub mod import;
 pub mod kurs; // <-- WAJIB: expose router /api/kurs
 pub mod me;
+pub mod mock_checkout;
 pub mod pay;
 pub mod payment_plugins;
+pub mod payouts;
+pub mod reconciliation;
+pub mod refunds;
+pub mod regions;
+pub mod rentals;
+pub mod series;
 pub mod setup;
 pub mod stream;
+pub mod stripe_connect;
+pub mod subscriptions;
+pub mod taxonomy;
+pub mod tips;
 pub mod upload;
 pub mod users; // <-- TAMBAHKAN BARIS INI
 pub mod video;
 pub mod wallet;
+pub mod webhook_inbox;
@@ -8,7 +8,7 @@
 // 3. Fetching and caching cryptocurrency market prices.
 // 4. Verifying an EVM transaction receipt and decoding the Paid smart contract event.
 // 5. Marking invoices as paid or underpaid.
-// 6. Unlocking purchased video access through purchases and allowlist records.
+// 6. Unlocking purchased video access through the entitlements ledger.
 
 use axum::{
     extract::{Query, State},
@@ -34,9 +34,21 @@ use tower_cookies::Cookies;
 use uuid::Uuid;
 
 use crate::commission;
+use crate::currency;
+use crate::entitlements::{self, Origin, Source};
+use crate::geo::ClientCountry;
 use crate::handlers::video::VideoState;
+use crate::handlers::{coupons, regions, rentals, tips};
 use crate::payment_settings::load_payment_settings;
+use crate::plugins::payment::{
+    providers::{
+        lightning::{LightningPaymentPlugin, LIGHTNING_CURRENCY},
+        solana::SolanaPaymentPlugin,
+    },
+    traits::PaymentPlugin,
+};
 use crate::sessions;
+use crate::x402_chains;
 
 /// Check whether `video_id` belongs to a remote federated video.
 ///
@@ -64,9 +76,10 @@ async fn remote_video_checkout_url(pool: &sqlx::PgPool, video_id: &str) -> Optio
 /// Route: `GET /api/pay/options?video_id=<id>`
 ///
 /// The response contains the video price, creator wallet information, preferred
-/// creator chain, and all active token configurations from `pay_tokens`.
+/// creator chain, and the x402 chains with their active `pay_tokens` rows.
 pub async fn pay_options(
     State(st): State<VideoState>,
+    country: ClientCountry,
     Query(query): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
     // Read the required video ID from the query string.
@@ -106,26 +119,21 @@ pub async fn pay_options(
         return Json(json!({"ok": false, "error": "video not found"}));
     };
 
-    // Load every active payment token. COALESCE supports both the current
-    // `erc20_address` column and the legacy `erc20` column.
-    let tokens = sqlx::query(
-        r#"
-        SELECT chain, chain_id, symbol, decimals,
-               COALESCE(erc20_address, erc20) AS erc20_address
-        FROM pay_tokens
-        WHERE is_active = TRUE
-        ORDER BY chain_id, symbol
-        "#,
-    )
-    .fetch_all(&st.pool)
-    .await
-    .unwrap_or_default();
+    // Geo-restricted videos offer no payment options outside their regions.
+    let regional = match regions::regional_terms(&st.pool, &video_id, country.code()).await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": e, "country": country.0})),
+    };
+
+    // Chains x402 is configured on, each with its active tokens.
+    let (chains, tokens) = x402_chain_options(&st.pool).await;
 
     // Convert database rows into the frontend payment option response.
     Json(json!({
         "ok": true,
         "video_id": video_id,
-        "price_cents": video_metadata.try_get::<i64, _>("price_cents").unwrap_or(0),
+        "price_cents": regional.price(video_metadata.try_get::<i64, _>("price_cents").unwrap_or(0)),
+        "country": country.0,
         "creator_id": video_metadata
             .try_get::<String, _>("owner_id")
             .unwrap_or_default(),
@@ -137,22 +145,63 @@ pub async fn pay_options(
             .try_get::<Option<i64>, _>("wallet_chain_id")
             .ok()
             .flatten(),
-        "tokens": tokens
+        "chains": chains,
+        "tokens": tokens,
+    }))
+}
+
+/// x402 chains for the buyer to choose from, each with its active
+/// `pay_tokens` rows, and the same tokens as one flat list.
+///
+/// Chains without an active token are left out.
+async fn x402_chain_options(
+    pool: &sqlx::PgPool,
+) -> (Vec<serde_json::Value>, Vec<serde_json::Value>) {
+    let chains = x402_chains::load_chains(pool).await.unwrap_or_default();
+    // COALESCE supports both the current `erc20_address` column and the
+    // legacy `erc20` column.
+    let rows = sqlx::query(
+        r#"
+        SELECT chain, chain_id, symbol, decimals,
+               COALESCE(erc20_address, erc20) AS erc20_address
+        FROM pay_tokens
+        WHERE is_active = TRUE
+        ORDER BY chain_id, symbol
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap_or_default();
+
+    let mut chain_list = Vec::new();
+    let mut token_list = Vec::new();
+    for chain in chains {
+        let tokens: Vec<serde_json::Value> = rows
             .iter()
-            .map(|token| {
+            .filter(|t| t.try_get::<i64, _>("chain_id").ok() == Some(chain.chain_id))
+            .map(|t| {
                 json!({
-                    "chain": token.try_get::<String, _>("chain").unwrap_or_default(),
-                    "chain_id": token.try_get::<i64, _>("chain_id").unwrap_or_default(),
-                    "symbol": token.try_get::<String, _>("symbol").unwrap_or_default(),
-                    "decimals": token.try_get::<i32, _>("decimals").unwrap_or_default(),
-                    "erc20": token
-                        .try_get::<Option<String>, _>("erc20_address")
-                        .ok()
-                        .flatten(),
+                    "chain":    t.try_get::<String, _>("chain").unwrap_or_default(),
+                    "chain_id": chain.chain_id,
+                    "symbol":   t.try_get::<String, _>("symbol").unwrap_or_default(),
+                    "decimals": t.try_get::<i32, _>("decimals").unwrap_or(18),
+                    "erc20":    t.try_get::<Option<String>, _>("erc20_address").unwrap_or(None),
                 })
             })
-            .collect::<Vec<_>>()
-    }))
+            .collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        token_list.extend(tokens.iter().cloned());
+        chain_list.push(json!({
+            "chain_id":      chain.chain_id,
+            "name":          chain.name,
+            "contract":      chain.contract,
+            "confirmations": chain.confirmations,
+            "tokens":        tokens,
+        }));
+    }
+    (chain_list, token_list)
 }
 
 /// JSON request body accepted by `POST /api/pay/x402/start`.
@@ -164,6 +213,16 @@ pub struct StartPayReq {
     pub token_address: Option<String>,
     pub payer_address: String,
     pub ref_code: Option<String>, // affiliate referral username
+    /// Rent the video for its rental window instead of buying it.
+    #[serde(default)]
+    pub rental: bool,
+    /// Coupon code to apply.
+    pub coupon: Option<String>,
+    /// Amount to pay for a pay-what-you-want video, in the video's currency;
+    /// defaults to its minimum.
+    pub amount_cents: Option<i64>,
+    /// Quote from `/api/pay/quote` locking the video-currency → USD rate.
+    pub quote_id: Option<String>,
 }
 
 /// JSON response returned after an x402 payment invoice is created and signed.
@@ -204,6 +263,7 @@ pub struct StartPayResp {
 pub async fn x402_start(
     State(st): State<VideoState>,
     cookies: Cookies,
+    country: ClientCountry,
     Json(body): Json<StartPayReq>,
 ) -> impl IntoResponse {
     let payment_settings = load_payment_settings(&st.pool).await;
@@ -232,7 +292,7 @@ pub async fn x402_start(
     // Load the video price, creator ID, and creator wallet.
     let video_metadata = sqlx::query(
         r#"
-        SELECT v.price_cents, v.owner_id, u.wallet_account
+        SELECT v.price_cents, v.currency, v.owner_id, v.pay_what_you_want, u.wallet_account
         FROM videos v
         JOIN users u ON u.id = v.owner_id
         WHERE v.id = $1
@@ -268,6 +328,18 @@ pub async fn x402_start(
         Err(_) => return Json(json!({"ok": false, "error": "invalid creator wallet"})),
     };
 
+    // The buyer's chain must be configured for x402; its contract is signed
+    // into the authorization.
+    let chain = match x402_chains::find_chain(&st.pool, body.chain_id).await {
+        Ok(Some(chain)) => chain,
+        Ok(None) => return Json(json!({"ok": false, "error": "chain not supported"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let contract_address = match Address::from_str(&chain.contract) {
+        Ok(address) => address,
+        Err(_) => return Json(json!({"ok": false, "error": "invalid x402 contract"})),
+    };
+
     // Validate that the requested token configuration is active and matches
     // the selected chain, symbol, and optional token contract address.
     let token_info = sqlx::query(
@@ -293,7 +365,101 @@ pub async fn x402_start(
     };
 
     let decimals = token_info.try_get::<i32, _>("decimals").unwrap_or(18) as u32;
-    let price_cents: i64 = video_metadata.try_get::<i64, _>("price_cents").unwrap_or(0);
+    let regional = match regions::regional_terms(&st.pool, &body.video_id, country.code()).await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let mut price_cents: i64 =
+        regional.price(video_metadata.try_get::<i64, _>("price_cents").unwrap_or(0));
+
+    let mut rental_hours: Option<i32> = None;
+    if body.rental {
+        let offer = match rentals::load_rental_offer(&st.pool, &body.video_id).await {
+            Ok(Some(o)) => o,
+            Ok(None) => return Json(json!({"ok": false, "error": "video is not for rent"})),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+        };
+        if let Err(e) = rentals::check_rentable(&st.pool, &body.video_id, &buyer_id).await {
+            return Json(json!({"ok": false, "error": e}));
+        }
+        price_cents = regional.rental_price(offer.price_cents);
+        rental_hours = Some(offer.hours);
+    } else if video_metadata
+        .try_get::<bool, _>("pay_what_you_want")
+        .unwrap_or(false)
+    {
+        if body.coupon.as_deref().is_some_and(|c| !c.trim().is_empty()) {
+            return Json(json!({
+                "ok": false,
+                "error": "coupons do not apply to pay-what-you-want videos"
+            }));
+        }
+        price_cents = match tips::pwyw_amount(price_cents, body.amount_cents) {
+            Ok(a) => a,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        };
+    }
+
+    // The administrative private key signs the authorization payload. The key
+    // must only be supplied through secure runtime configuration. It is
+    // checked before a coupon use is held for the checkout.
+    let admin_private_key = std::env::var("X402_ADMIN_PRIVKEY").unwrap_or_default();
+    if admin_private_key.is_empty() {
+        return Json(json!({
+            "ok": false,
+            "error": "X402_ADMIN_PRIVKEY not set"
+        }));
+    }
+
+    let admin_wallet: LocalWallet = match admin_private_key.parse() {
+        Ok(wallet) => wallet,
+        Err(_) => return Json(json!({"ok": false, "error": "bad admin privkey"})),
+    };
+
+    // Generate an application invoice ID and its bytes32 Keccak hash. The hash
+    // is used as the indexed invoice UID in the smart contract Paid event.
+    let invoice_uid = Uuid::new_v4().to_string();
+
+    // The signed amount is the discounted price, so the contract splits what
+    // the buyer actually pays. The coupon use is held until confirmation.
+    if price_cents > 0 {
+        match coupons::hold_for_checkout(
+            &st.pool,
+            body.coupon.as_deref(),
+            &body.video_id,
+            &buyer_id,
+            price_cents,
+            "x402",
+            &invoice_uid,
+        )
+        .await
+        {
+            Ok(Some(applied)) => price_cents = applied.final_cents(),
+            Ok(None) => {}
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        }
+    }
+
+    // Tokens are USD-pegged: convert the listed price at a locked rate first.
+    let quote = match currency::lock_quote(
+        &st.pool,
+        &st.cfg,
+        &buyer_id,
+        body.quote_id.as_deref(),
+        &video_metadata
+            .try_get::<String, _>("currency")
+            .unwrap_or_else(|_| currency::BASE_CURRENCY.into()),
+        currency::BASE_CURRENCY,
+    )
+    .await
+    {
+        Ok(q) => q,
+        Err(e) => {
+            let _ = coupons::release_hold(&st.pool, &invoice_uid).await;
+            return Json(json!({"ok": false, "error": e}));
+        }
+    };
+    let price_cents = quote.convert(price_cents);
 
     // Convert price cents into token base units and round upward. For example,
     // an 18-decimal token uses 10^18 base units per whole token.
@@ -305,15 +471,13 @@ pub async fn x402_start(
     };
 
     if token_amount_wei == 0 {
+        let _ = coupons::release_hold(&st.pool, &invoice_uid).await;
         return Json(json!({
             "ok": false,
             "error": "calculated amount is zero"
         }));
     }
 
-    // Generate an application invoice ID and its bytes32 Keccak hash. The hash
-    // is used as the indexed invoice UID in the smart contract Paid event.
-    let invoice_uid = Uuid::new_v4().to_string();
     let invoice_uid_bytes32 = H256::from_slice(&keccak256(invoice_uid.as_bytes()));
     let invoice_uid_hash_hex = format!("{:#066x}", invoice_uid_bytes32);
 
@@ -330,12 +494,12 @@ pub async fn x402_start(
           (invoice_uid, invoice_uid_hash, user_id, video_id, creator_id,
            chain_id, token_symbol, token_address,
            price_cents, token_amount, required_amount_wei,
-           status, expires_at)
+           rental_hours, quote_id, buyer_country, status, expires_at)
         VALUES
           ($1,$2,$3,$4,$5,
            $6,$7,$8,
            $9,$10,$11,
-           'pending', NOW() + INTERVAL '10 minutes')
+           $12, $13, $14, 'pending', NOW() + INTERVAL '10 minutes')
         "#,
     )
     .bind(&invoice_uid)
@@ -353,6 +517,9 @@ pub async fn x402_start(
     .bind(price_cents)
     .bind(&token_amount_decimal)
     .bind(&token_amount_decimal)
+    .bind(rental_hours)
+    .bind(&quote.id)
+    .bind(country.code())
     .execute(&st.pool)
     .await;
 
@@ -365,11 +532,6 @@ pub async fn x402_start(
             .await;
     }
 
-    // Load the target smart contract address. The frontend is expected to
-    // perform an additional deployed-code validation before submitting payment.
-    let x402_contract = std::env::var("X402_CONTRACT_ADDRESS").unwrap_or_default();
-    let _contract_address = Address::from_str(&x402_contract).unwrap_or(Address::zero());
-
     // A missing token address represents payment with the native chain asset.
     let token_address = body
         .token_address
@@ -380,47 +542,27 @@ pub async fn x402_start(
     let creator_basis_points = st.cfg.creator_split_bp;
     let minimum_amount_wei = U256::from_dec_str(&token_amount_wei.to_string()).unwrap();
     let deadline: u64 = (chrono::Utc::now().timestamp() as u64) + st.cfg.x402_deadline_secs;
-    let video_hash = H256::from_slice(&keccak256(body.video_id.as_bytes()));
-
-    // ABI encode values in the exact order expected by the smart contract
-    // verification function.
-    let encoded_payload = abi_encode(&[
-        AbiToken::FixedBytes(invoice_uid_bytes32.as_bytes().to_vec()),
-        AbiToken::Address(token_address),
-        AbiToken::Uint(minimum_amount_wei),
-        AbiToken::Address(creator_address),
-        AbiToken::Uint(U256::from(creator_basis_points)),
-        AbiToken::FixedBytes(video_hash.as_bytes().to_vec()),
-        AbiToken::Address(payer_address),
-        AbiToken::Uint(U256::from(deadline)),
-    ]);
-
-    // Hash the ABI payload and then apply the EIP-191 personal-sign prefix for
-    // a 32-byte message.
-    let message_hash = H256::from_slice(&keccak256(&encoded_payload));
-    let ethereum_signed_hash = H256::from_slice(&keccak256(
-        [b"\x19Ethereum Signed Message:\n32", message_hash.as_bytes()].concat(),
-    ));
-
-    // The administrative private key signs the authorization payload. The key
-    // must only be supplied through secure runtime configuration.
-    let admin_private_key = std::env::var("X402_ADMIN_PRIVKEY").unwrap_or_default();
-    if admin_private_key.is_empty() {
-        return Json(json!({
-            "ok": false,
-            "error": "X402_ADMIN_PRIVKEY not set"
-        }));
+    let ethereum_signed_hash = PayAuthorization {
+        invoice_uid: invoice_uid_bytes32,
+        token: token_address,
+        min_amount_wei: minimum_amount_wei,
+        creator: creator_address,
+        creator_bp: creator_basis_points,
+        video_id: &body.video_id,
+        payer: payer_address,
+        deadline,
+        contract: contract_address,
+        chain_id: body.chain_id as u64,
     }
-
-    let admin_wallet: LocalWallet = match admin_private_key.parse() {
-        Ok(wallet) => wallet,
-        Err(_) => return Json(json!({"ok": false, "error": "bad admin privkey"})),
-    };
+    .signed_hash();
 
     // `sign_hash` is synchronous because the private key is available locally.
     let signature: Signature = match admin_wallet.sign_hash(ethereum_signed_hash) {
         Ok(signature) => signature,
-        Err(e) => return Json(json!({"ok": false, "error": format!("sign: {e}")})),
+        Err(e) => {
+            let _ = coupons::release_hold(&st.pool, &invoice_uid).await;
+            return Json(json!({"ok": false, "error": format!("sign: {e}")}));
+        }
     };
 
     // Return all values required by the frontend to call the smart contract.
@@ -440,13 +582,58 @@ pub async fn x402_start(
         s: format!("{:#066x}", signature.s),
         split_creator_bp: st.cfg.creator_split_bp as i32,
         split_admin_bp: (10000u16 - st.cfg.creator_split_bp) as i32,
-        x402_contract,
+        // The frontend is expected to check the contract's deployed code
+        // before submitting payment.
+        x402_contract: chain.contract,
         creator_wallet: creator_wallet_string,
     };
 
     Json(json!(response))
 }
 
+/// The payment authorization the admin key signs for `payNativeSigned` /
+/// `payERC20Signed`.
+struct PayAuthorization<'a> {
+    invoice_uid: H256,
+    token: Address,
+    min_amount_wei: U256,
+    creator: Address,
+    creator_bp: u16,
+    video_id: &'a str,
+    payer: Address,
+    deadline: u64,
+    contract: Address,
+    chain_id: u64,
+}
+
+impl PayAuthorization<'_> {
+    /// EIP-191 hash of the payload `X402Splitter._verify` rebuilds: the
+    /// ABI-encoded fields in contract order, bound to the contract address and
+    /// chain id so a signature cannot be replayed on another deployment.
+    fn signed_hash(&self) -> H256 {
+        let encoded_payload = abi_encode(&[
+            AbiToken::FixedBytes(self.invoice_uid.as_bytes().to_vec()),
+            AbiToken::Address(self.token),
+            AbiToken::Uint(self.min_amount_wei),
+            AbiToken::Address(self.creator),
+            AbiToken::Uint(U256::from(self.creator_bp)),
+            AbiToken::FixedBytes(keccak256(self.video_id.as_bytes()).to_vec()),
+            AbiToken::Address(self.payer),
+            AbiToken::Uint(U256::from(self.deadline)),
+            AbiToken::Address(self.contract),
+            AbiToken::Uint(U256::from(self.chain_id)),
+        ]);
+        let message_hash = keccak256(&encoded_payload);
+        H256::from_slice(&keccak256(
+            [
+                b"\x19Ethereum Signed Message:\n32".as_slice(),
+                &message_hash,
+            ]
+            .concat(),
+        ))
+    }
+}
+
 /// One in-memory cryptocurrency price cache entry.
 #[derive(Clone, Debug)]
 struct CacheEntry {
@@ -577,19 +764,39 @@ fn paid_event_abi() -> Event {
     }
 }
 
+/// Current block number from `eth_blockNumber`.
+async fn rpc_block_number(rpc_url: &str) -> Result<u64, String> {
+    let payload = json!({"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber", "params": []});
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(rpc_url)
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| format!("rpc: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("rpc parse: {e}"))?;
+    response
+        .pointer("/result")
+        .and_then(|value| value.as_str())
+        .and_then(|value| u64::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+        .ok_or_else(|| "rpc: no block number".to_string())
+}
+
 /// Confirms an x402 payment by verifying the on-chain transaction receipt.
 ///
 /// Route: `POST /api/pay/x402/confirm`
 ///
 /// Verification flow:
 /// 1. Load the expected invoice from PostgreSQL.
-/// 2. Fetch the transaction receipt from the configured EVM RPC endpoint.
-/// 3. Require a successful transaction status.
+/// 2. Fetch the transaction receipt from the RPC endpoint of the invoice's chain.
+/// 3. Require a successful transaction status and the chain's number of
+///    confirmations.
 /// 4. Locate a Paid event emitted by the configured x402 contract.
 /// 5. Match the indexed invoice UID and decode amount and video ID.
 /// 6. Compare the paid amount against the required amount.
 /// 7. Update invoice status.
-/// 8. Create purchase and allowlist records when fully paid.
+/// 8. Grant the video entitlement when fully paid.
 pub async fn x402_confirm(
     State(st): State<VideoState>,
     Json(body): Json<ConfirmReq>,
@@ -603,7 +810,7 @@ pub async fn x402_confirm(
     let invoice = sqlx::query!(
         r#"
         SELECT id, user_id, video_id, invoice_uid, invoice_uid_hash, required_amount_wei
-             , status, tx_hash
+             , status, tx_hash, rental_hours, chain_id
         FROM x402_invoices
         WHERE invoice_uid=$1
         LIMIT 1
@@ -632,10 +839,32 @@ pub async fn x402_confirm(
         }));
     }
 
-    // The HTTP RPC endpoint is required to fetch the transaction receipt.
-    let rpc_url = std::env::var("X402_RPC_HTTP").unwrap_or_default();
+    // A transaction is counted once: confirming an underpaid transaction
+    // again (or after the watcher saw it) must not add its amount twice.
+    if invoice.status == "underpaid"
+        && invoice
+            .tx_hash
+            .as_deref()
+            .is_some_and(|value| value.eq_ignore_ascii_case(&body.tx_hash))
+    {
+        return Json(json!({
+            "ok": false,
+            "underpaid": true,
+            "replayed": true,
+            "message": "This transaction was already counted. Please top up the remainder to unlock."
+        }));
+    }
+
+    // The invoice's chain supplies the RPC endpoint, the contract and the
+    // confirmation depth.
+    let chain = match x402_chains::find_chain(&st.pool, invoice.chain_id).await {
+        Ok(Some(chain)) => chain,
+        Ok(None) => return Json(json!({"ok": false, "error": "chain not supported"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let rpc_url = chain.rpc_http.clone();
     if rpc_url.is_empty() {
-        return Json(json!({"ok": false, "error": "X402_RPC_HTTP not set"}));
+        return Json(json!({"ok": false, "error": "no RPC endpoint for this chain"}));
     }
 
     // Build an Ethereum JSON-RPC request for `eth_getTransactionReceipt`.
@@ -676,15 +905,34 @@ pub async fn x402_confirm(
         return Json(json!({"ok": false, "error": "tx failed"}));
     }
 
+    // Same confirmation depth as the chain's watcher; nothing is recorded
+    // before it.
+    let tx_block = receipt
+        .pointer("/result/blockNumber")
+        .and_then(|value| value.as_str())
+        .and_then(|value| u64::from_str_radix(value.trim_start_matches("0x"), 16).ok());
+    let head = match rpc_block_number(&rpc_url).await {
+        Ok(head) => head,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let confirmations = tx_block.map_or(0, |block| (head + 1).saturating_sub(block));
+    if confirmations < chain.confirmations {
+        return Json(json!({
+            "ok": false,
+            "pending": true,
+            "confirmations": confirmations,
+            "required": chain.confirmations,
+            "error": "waiting for confirmations"
+        }));
+    }
+
     // Compute the expected event signature topic from the Paid ABI.
     let paid_event = paid_event_abi();
     let paid_signature =
         format!("0x{}", hex::encode(paid_event.signature().to_fixed_bytes())).to_lowercase();
 
-    // Only events emitted by the configured x402 contract are accepted.
-    let x402_contract = std::env::var("X402_CONTRACT_ADDRESS")
-        .unwrap_or_default()
-        .to_lowercase();
+    // Only events emitted by the chain's x402 contract are accepted.
+    let x402_contract = chain.contract.to_lowercase();
 
     let logs = receipt
         .pointer("/result/logs")
@@ -811,9 +1059,50 @@ pub async fn x402_confirm(
         .unwrap_or_else(|| BigDecimal::from(0));
     let is_fully_paid = paid_amount >= required_amount;
 
+    // Status, coupon and grant are written in one transaction against the
+    // locked invoice, so a concurrent confirmation or the chain watcher
+    // cannot apply the same payment twice.
+    let mut tx = match st.pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let locked = match sqlx::query!(
+        "SELECT status, tx_hash FROM x402_invoices WHERE id = $1 FOR UPDATE",
+        invoice.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    {
+        Ok(locked) => locked,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    // Re-check the replay cases now that the row is locked.
+    let locked_same_tx = locked
+        .tx_hash
+        .as_deref()
+        .is_some_and(|value| value.eq_ignore_ascii_case(&body.tx_hash));
+    if locked.status == "paid" {
+        return Json(json!({
+            "ok": true,
+            "status": "paid",
+            "replayed": true,
+            "same_tx": locked_same_tx
+        }));
+    }
+    if locked.status == "underpaid" && locked_same_tx {
+        return Json(json!({
+            "ok": false,
+            "underpaid": true,
+            "replayed": true,
+            "message": "This transaction was already counted. Please top up the remainder to unlock."
+        }));
+    }
+
     // Persist the transaction hash, accumulated paid amount, and final invoice
     // status. Accumulation allows a future top-up confirmation flow.
-    let _ = sqlx::query!(
+    if let Err(e) = sqlx::query!(
         r#"
         UPDATE x402_invoices
            SET status = $1,
@@ -827,11 +1116,17 @@ pub async fn x402_confirm(
         &paid_amount,
         invoice.id
     )
-    .execute(&st.pool)
-    .await;
+    .execute(&mut *tx)
+    .await
+    {
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
 
     // Return the missing amount when payment is below the invoice requirement.
     if !is_fully_paid {
+        if let Err(e) = tx.commit().await {
+            return Json(json!({"ok": false, "error": format!("db: {e}")}));
+        }
         let missing_amount = (&required_amount - paid_amount).max(BigDecimal::from(0));
         return Json(json!({
             "ok": false,
@@ -841,44 +1136,33 @@ pub async fn x402_confirm(
         }));
     }
 
-    // Record the purchase idempotently so repeated confirmations do not create
-    // duplicate purchase rows.
-    let _ = sqlx::query!(
-        r#"
-        INSERT INTO purchases (user_id, video_id, created_at)
-        VALUES ($1,$2,NOW())
-        ON CONFLICT DO NOTHING
-        "#,
-        invoice.user_id,
-        invoice.video_id
-    )
-    .execute(&st.pool)
-    .await;
+    if let Err(e) = coupons::confirm_redemption(&mut *tx, &body.invoice_uid).await {
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
 
-    // Resolve the buyer username because playback authorization currently uses
-    // the `(video_id, username)` allowlist rather than a direct user ID relation.
-    let username =
-        sqlx::query_scalar::<_, Option<String>>(r#"SELECT username FROM users WHERE id=$1"#)
-            .bind(&invoice.user_id)
-            .fetch_one(&st.pool)
+    // Rentals expire, so they get a time-limited entitlement instead of a
+    // permanent one. Both are idempotent for repeated confirmations.
+    let origin = Origin::new(Source::X402, Some(&body.invoice_uid));
+    let granted = match invoice.rental_hours {
+        Some(hours) => {
+            rentals::record_rental(&mut tx, &invoice.user_id, &invoice.video_id, hours, origin)
+                .await
+                .map(|_| ())
+        }
+        None => entitlements::grant_video(&mut tx, &invoice.user_id, &invoice.video_id, origin)
             .await
-            .unwrap_or(None)
-            .unwrap_or_default();
+            .map(|_| ()),
+    };
+    if let Err(e) = granted {
+        tracing::error!("x402 grant failed for {}: {e}", body.invoice_uid);
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+    if let Err(e) = tx.commit().await {
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
 
-    // Insert the allowlist entry idempotently. `user_has_view_access` later uses
-    // this record to authorize the buyer's playback request.
-    if !username.is_empty() {
-        let _ = sqlx::query!(
-            r#"
-            INSERT INTO allowlist (video_id, username)
-            VALUES ($1,$2)
-            ON CONFLICT (video_id, username) DO NOTHING
-            "#,
-            invoice.video_id,
-            username
-        )
-        .execute(&st.pool)
-        .await;
+    if let Some(hours) = invoice.rental_hours {
+        return Json(json!({"ok": true, "status": "paid", "rental_hours": hours}));
     }
 
     // Best-effort affiliate commission after x402 payment
@@ -941,6 +1225,7 @@ pub async fn x402_confirm(
 pub async fn all_options(
     State(st): State<VideoState>,
     cookies: Cookies,
+    country: ClientCountry,
     Query(q): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
     let payment_settings = load_payment_settings(&st.pool).await;
@@ -950,11 +1235,13 @@ pub async fn all_options(
     }
 
     // Load video price and owner
-    let video_row =
-        sqlx::query("SELECT v.price_cents, v.owner_id FROM videos v WHERE v.id = $1 LIMIT 1")
-            .bind(&video_id)
-            .fetch_optional(&st.pool)
-            .await;
+    let video_row = sqlx::query(
+        "SELECT v.price_cents, v.currency, v.owner_id, v.rental_price_cents, v.rental_hours, \
+         v.pay_what_you_want FROM videos v WHERE v.id = $1 LIMIT 1",
+    )
+    .bind(&video_id)
+    .fetch_optional(&st.pool)
+    .await;
 
     let video_row = match video_row {
         Ok(Some(r)) => r,
@@ -971,8 +1258,39 @@ pub async fn all_options(
         Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
     };
 
-    let price_cents: i64 = video_row.try_get("price_cents").unwrap_or(0);
+    // Regional price for the buyer's country; blocked regions get no options.
+    let regional = match regions::regional_terms(&st.pool, &video_id, country.code()).await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": e, "country": country.0})),
+    };
+    let price_cents: i64 = regional.price(video_row.try_get("price_cents").unwrap_or(0));
     let owner_id: String = video_row.try_get("owner_id").unwrap_or_default();
+    let rental_price_cents: Option<i64> = video_row
+        .try_get::<Option<i64>, _>("rental_price_cents")
+        .ok()
+        .flatten()
+        .map(|c| regional.rental_price(c));
+    let rental_hours: i32 = video_row.try_get("rental_hours").unwrap_or(48);
+    let pay_what_you_want: bool = video_row.try_get("pay_what_you_want").unwrap_or(false);
+    // Buyers of a pay-what-you-want video pay at least the minimum.
+    let price_cents = if pay_what_you_want {
+        tips::pwyw_minimum(price_cents)
+    } else {
+        price_cents
+    };
+    // Prices are listed in the video's currency; wallet and x402 pay the USD
+    // equivalent (indicative here, locked by `/api/pay/quote` or at checkout).
+    let video_currency: String = video_row
+        .try_get("currency")
+        .unwrap_or_else(|_| currency::BASE_CURRENCY.into());
+    let usd_cents = currency::convert_now(
+        &st.pool,
+        price_cents,
+        &video_currency,
+        currency::BASE_CURRENCY,
+    )
+    .await
+    .ok();
 
     // Wallet balance (null if not logged in)
     let current_user = sessions::current_user_id(&st.pool, &st.cfg, &cookies).await;
@@ -985,42 +1303,21 @@ pub async fn all_options(
             .flatten()
             .unwrap_or(0);
 
-        let purchased: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM purchases WHERE user_id = $1 AND video_id = $2",
-        )
-        .bind(uid)
-        .bind(&video_id)
-        .fetch_one(&st.pool)
-        .await
-        .unwrap_or(0);
+        let purchased = entitlements::owns_video(&st.pool, &video_id, uid)
+            .await
+            .unwrap_or(false);
 
-        (Some(bal), uid == &owner_id, purchased > 0)
+        (Some(bal), uid == &owner_id, purchased)
     } else {
         (None, false, false)
     };
+    let active_rental = match &current_user {
+        Some((uid, _)) => rentals::rental_status(&st.pool, &video_id, uid).await,
+        None => None,
+    };
 
-    // X402 tokens
-    let tokens = sqlx::query(
-        r#"SELECT chain, chain_id, symbol, decimals,
-                  COALESCE(erc20_address, erc20) AS erc20_address
-           FROM pay_tokens WHERE is_active = TRUE ORDER BY chain_id, symbol"#,
-    )
-    .fetch_all(&st.pool)
-    .await
-    .unwrap_or_default();
-
-    let token_list: Vec<serde_json::Value> = tokens
-        .iter()
-        .map(|t| {
-            json!({
-                "chain":     t.try_get::<String, _>("chain").unwrap_or_default(),
-                "chain_id":  t.try_get::<i64,    _>("chain_id").unwrap_or(0),
-                "symbol":    t.try_get::<String, _>("symbol").unwrap_or_default(),
-                "decimals":  t.try_get::<i32,    _>("decimals").unwrap_or(18),
-                "erc20":     t.try_get::<Option<String>, _>("erc20_address").unwrap_or(None),
-            })
-        })
-        .collect();
+    // X402 chains and their tokens
+    let (chain_list, token_list) = x402_chain_options(&st.pool).await;
 
     // Fiat providers from env (admin-configured)
     let mut fiat_providers: Vec<String> = Vec::new();
@@ -1036,6 +1333,15 @@ pub async fn all_options(
     if payment_settings.xendit_enabled {
         fiat_providers.push("xendit".to_string());
     }
+    if payment_settings.is_provider_enabled("mock") {
+        fiat_providers.push("mock".to_string());
+    }
+    let solana = SolanaPaymentPlugin::from_env().capability();
+    let lightning = LightningPaymentPlugin::from_env().capability();
+    // Without a BTC rate there is nothing to price the invoice in.
+    let sats = currency::convert_now(&st.pool, price_cents, &video_currency, LIGHTNING_CURRENCY)
+        .await
+        .ok();
 
     let cents_display =
         |c: i64| -> String { format!("${}.{:02}", c / 100, (c % 100).unsigned_abs()) };
@@ -1044,22 +1350,207 @@ pub async fn all_options(
         "ok": true,
         "video_id":        video_id,
         "price_cents":     price_cents,
-        "price_display":   cents_display(price_cents),
+        "currency":        video_currency,
+        "usd_cents":       usd_cents,
+        "country":         country.0,
+        "price_display":   if video_currency == currency::BASE_CURRENCY {
+            cents_display(price_cents)
+        } else {
+            format!("{} {}", video_currency, format_minor(price_cents, &video_currency))
+        },
         "is_owner":        is_owner,
         "already_purchased": already_purchased,
+        "pay_what_you_want": {
+            "enabled":       pay_what_you_want,
+            "min_cents":     pay_what_you_want.then_some(price_cents),
+            "max_cents":     pay_what_you_want.then_some(tips::MAX_CHOSEN_CENTS),
+        },
+        "rental": {
+            "available":     rental_price_cents.is_some() && !already_purchased && !is_owner,
+            "price_cents":   rental_price_cents,
+            "price_display": rental_price_cents.map(|c| format_minor(c, &video_currency)),
+            "rental_hours":  rental_hours,
+            "active":        active_rental,
+        },
         "wallet": {
             "available":       payment_settings.wallet_payment_enabled,
             "balance_cents":   wallet_balance,
             "balance_display": wallet_balance.map(cents_display),
-            "can_afford":      wallet_balance.map(|b| payment_settings.wallet_payment_enabled && b >= price_cents && !is_owner),
+            "can_afford":      wallet_balance.map(|b| payment_settings.wallet_payment_enabled && usd_cents.is_some_and(|usd| b >= usd) && !is_owner),
         },
         "x402": {
-            "available": payment_settings.x402_enabled && !token_list.is_empty(),
+            "available": payment_settings.x402_enabled && !chain_list.is_empty(),
+            "chains":    chain_list,
             "tokens":    token_list,
         },
+        "solana": {
+            "available":   payment_settings.solana_enabled && solana.configured,
+            "token":       "USDC",
+            "environment": solana.environment,
+        },
+        "lightning": {
+            "available": payment_settings.lightning_enabled && lightning.configured && sats.is_some(),
+            "sats":      sats,
+            "network":   lightning.environment,
+        },
         "fiat": {
             "available": !fiat_providers.is_empty(),
             "providers": fiat_providers,
         }
     }))
 }
+
+/// Formats minor units as a plain major amount, e.g. `12.50` or `162500`.
+fn format_minor(amount: i64, currency_code: &str) -> String {
+    match currency::minor_exponent(currency_code) {
+        0 => amount.to_string(),
+        _ => format!("{}.{:02}", amount / 100, (amount % 100).unsigned_abs()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct QuoteReq {
+    /// Currency to pay in; USD for wallet and x402.
+    pub currency: String,
+    /// Video to price; without it the quote converts from USD (bundles,
+    /// tiers, tips).
+    pub video_id: Option<String>,
+}
+
+/// Locks an exchange rate for the current user.
+///
+/// Route: `POST /api/pay/quote`
+///
+/// Pass the returned `quote_id` to the wallet, fiat, or x402 checkout within
+/// `expires_at` to be charged exactly the quoted conversion.
+pub async fn create_quote(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    country: ClientCountry,
+    Json(body): Json<QuoteReq>,
+) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let target = match currency::normalize_currency(&body.currency) {
+        Ok(c) => c,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+
+    let video_id = body.video_id.as_deref().filter(|v| !v.is_empty());
+    let video = match video_id {
+        Some(video_id) => match sqlx::query(
+            "SELECT price_cents, currency, rental_price_cents, pay_what_you_want \
+             FROM videos WHERE id = $1 LIMIT 1",
+        )
+        .bind(video_id)
+        .fetch_optional(&st.pool)
+        .await
+        {
+            Ok(Some(r)) => Some(r),
+            Ok(None) => return Json(json!({"ok": false, "error": "video not found"})),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+        },
+        None => None,
+    };
+    let base = video
+        .as_ref()
+        .and_then(|v| v.try_get::<String, _>("currency").ok())
+        .unwrap_or_else(|| currency::BASE_CURRENCY.into());
+
+    let quote = match currency::create_quote(&st.pool, &st.cfg, &uid, &base, &target).await {
+        Ok(q) => q,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+
+    let mut resp = json!({
+        "ok": true,
+        "quote_id": quote.id,
+        "base_currency": quote.base_currency,
+        "currency": quote.quote_currency,
+        "rate": quote.rate,
+        "expires_at": quote.expires_at.to_rfc3339(),
+    });
+    if let (Some(v), Some(video_id)) = (&video, video_id) {
+        let regional = match regions::regional_terms(&st.pool, video_id, country.code()).await {
+            Ok(t) => t,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        };
+        let price_cents: i64 = regional.price(v.try_get("price_cents").unwrap_or(0));
+        let price_cents = if v.try_get::<bool, _>("pay_what_you_want").unwrap_or(false) {
+            tips::pwyw_minimum(price_cents)
+        } else {
+            price_cents
+        };
+        resp["price"] = json!(quote.convert(price_cents));
+        resp["rental_price"] = json!(v
+            .try_get::<Option<i64>, _>("rental_price_cents")
+            .ok()
+            .flatten()
+            .map(|c| quote.convert(regional.rental_price(c))));
+    }
+    Json(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::Signer;
+
+    fn authorization(contract: Address, chain_id: u64) -> PayAuthorization<'static> {
+        PayAuthorization {
+            invoice_uid: H256::from_slice(&keccak256(b"inv-1")),
+            token: Address::zero(),
+            min_amount_wei: U256::from(1_000_000_000_000_000u64),
+            creator: Address::repeat_byte(0x22),
+            creator_bp: 9000,
+            video_id: "video-1",
+            payer: Address::repeat_byte(0x11),
+            deadline: 1_900_000_000,
+            contract,
+            chain_id,
+        }
+    }
+
+    #[test]
+    fn authorization_is_bound_to_contract_and_chain() {
+        let contract = Address::repeat_byte(0x33);
+        let auth = authorization(contract, 31337);
+
+        // X402Splitter._verify: keccak256(abi.encode(..., address(this), block.chainid))
+        let fields = [
+            AbiToken::FixedBytes(auth.invoice_uid.as_bytes().to_vec()),
+            AbiToken::Address(auth.token),
+            AbiToken::Uint(auth.min_amount_wei),
+            AbiToken::Address(auth.creator),
+            AbiToken::Uint(U256::from(9000)),
+            AbiToken::FixedBytes(keccak256(b"video-1").to_vec()),
+            AbiToken::Address(auth.payer),
+            AbiToken::Uint(U256::from(auth.deadline)),
+            AbiToken::Address(contract),
+            AbiToken::Uint(U256::from(31337)),
+        ];
+        let expected = ethers::utils::hash_message(keccak256(abi_encode(&fields)));
+        assert_eq!(auth.signed_hash(), expected);
+
+        assert_ne!(
+            auth.signed_hash(),
+            authorization(contract, 137).signed_hash()
+        );
+        assert_ne!(
+            auth.signed_hash(),
+            authorization(Address::repeat_byte(0x44), 31337).signed_hash()
+        );
+
+        let admin: LocalWallet =
+            "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+                .parse()
+                .unwrap();
+        let signature = admin.sign_hash(auth.signed_hash()).unwrap();
+        assert_eq!(
+            signature.recover(auth.signed_hash()).unwrap(),
+            admin.address()
+        );
+    }
+}
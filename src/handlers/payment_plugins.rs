@@ -7,17 +7,29 @@ use axum::{
     response::IntoResponse,
     Json,
 };
-use base64::{engine::general_purpose::STANDARD as B64, Engine};
 use serde::Deserialize;
 use serde_json::json;
+use sqlx::{PgPool, Row};
 use tower_cookies::Cookies;
 use uuid::Uuid;
 
 use crate::commission;
 use crate::config::Config;
+use crate::currency;
+use crate::entitlements::{self, Origin};
+use crate::geo::ClientCountry;
+use crate::handlers::webhook_inbox::{self, Received};
+use crate::handlers::{
+    bundles, coupons, gifts, refunds, regions, rentals, stripe_connect, subscriptions, tips,
+};
 use crate::payment_settings::load_payment_settings;
 use crate::plugins::payment::{
-    models::{ConfirmPaymentRequest, CreateInvoiceRequest, PaymentStatus},
+    models::{
+        ConfirmPaymentRequest, CreateInvoiceRequest, PaymentResult, PaymentStatus,
+        PaymentStatusRequest,
+    },
+    providers::lightning::{LightningPaymentPlugin, LIGHTNING_CURRENCY},
+    traits::PaymentPlugin,
     PaymentPluginRegistry,
 };
 use crate::sessions;
@@ -32,10 +44,39 @@ pub struct PaymentPluginState {
 pub struct CreateInvoicePayload {
     #[allow(dead_code)]
     pub user_id: String,
+    #[serde(default)]
     pub video_id: String,
-    #[allow(dead_code)]
+    /// Buy a bundle instead of a single video.
+    #[serde(default)]
+    pub bundle_id: Option<String>,
+    /// Subscribe to a creator tier; the provider must support recurring charges.
+    #[serde(default)]
+    pub tier_id: Option<String>,
+    /// Rent `video_id` for its rental window instead of buying it.
+    #[serde(default)]
+    pub rental: bool,
+    /// Coupon code; single-video purchases and rentals only.
+    #[serde(default)]
+    pub coupon: Option<String>,
+    /// Buy `video_id` for another user, named by username or email.
+    #[serde(default)]
+    pub gift_to: Option<String>,
+    /// Note shown to the gift recipient.
+    #[serde(default)]
+    pub gift_message: Option<String>,
+    /// Tip this creator (by username) `amount_cents` instead of buying an item.
+    #[serde(default)]
+    pub tip_to: Option<String>,
+    /// Only honoured for tips (USD) and pay-what-you-want videos (video
+    /// currency); every other price comes from the database.
+    #[serde(default)]
     pub amount_cents: i64,
+    /// Currency to charge in; the price is converted at a locked rate.
+    #[serde(default)]
     pub currency: String,
+    /// Quote from `/api/pay/quote` for the item currency → `currency` rate.
+    #[serde(default)]
+    pub quote_id: Option<String>,
     pub buyer_email: Option<String>,
     pub buyer_name: Option<String>,
     pub success_url: Option<String>,
@@ -83,6 +124,7 @@ pub async fn list_payment_plugins(State(state): State<PaymentPluginState>) -> im
                     "supports_redirect_checkout": capability.supports_redirect_checkout,
                     "supports_webhook_confirmation": capability.supports_webhook_confirmation,
                     "supports_manual_confirmation": capability.supports_manual_confirmation,
+                    "supports_recurring": capability.supports_recurring,
                     "supported_currencies": capability.supported_currencies,
                     "required_env": capability.required_env,
                     "missing_env": capability.missing_env,
@@ -105,23 +147,25 @@ pub async fn list_payment_plugins(State(state): State<PaymentPluginState>) -> im
 pub async fn create_default_payment_invoice(
     State(state): State<PaymentPluginState>,
     cookies: Cookies,
+    country: ClientCountry,
     Json(payload): Json<CreateInvoicePayload>,
 ) -> impl IntoResponse {
     let registry = runtime_registry(&state).await;
     let Some(provider) = registry.default_provider_name() else {
         return Json(json!({"ok": false, "error": "default payment provider is not configured"}));
     };
-    create_invoice_with_provider(state, registry, provider, payload, cookies).await
+    create_invoice_with_provider(state, registry, provider, payload, cookies, country).await
 }
 
 pub async fn create_payment_invoice(
     State(state): State<PaymentPluginState>,
     cookies: Cookies,
+    country: ClientCountry,
     Path(provider): Path<String>,
     Json(payload): Json<CreateInvoicePayload>,
 ) -> impl IntoResponse {
     let registry = runtime_registry(&state).await;
-    create_invoice_with_provider(state, registry, provider, payload, cookies).await
+    create_invoice_with_provider(state, registry, provider, payload, cookies, country).await
 }
 
 async fn create_invoice_with_provider(
@@ -130,6 +174,7 @@ async fn create_invoice_with_provider(
     provider: String,
     payload: CreateInvoicePayload,
     cookies: Cookies,
+    country: ClientCountry,
 ) -> Json<serde_json::Value> {
     let Some(plugin) = registry.get(&provider) else {
         return Json(
@@ -145,28 +190,240 @@ async fn create_invoice_with_provider(
         return Json(json!({"ok": false, "error": "not logged in"}));
     };
 
-    // Load the authoritative price and ownership data from the database so the
-    // client cannot tamper with invoice totals or buy its own content.
-    let video_row = sqlx::query!(
-        "SELECT title, owner_id AS creator_id, price_cents FROM videos WHERE id = $1",
-        payload.video_id
-    )
-    .fetch_optional(&state.pool)
-    .await;
-
-    let (video_title, creator_id, video_price_cents) = match video_row {
-        Ok(Some(r)) => (r.title, r.creator_id, r.price_cents),
-        Ok(None) => return Json(json!({"ok": false, "error": "video not found"})),
-        Err(e) => return Json(json!({"ok": false, "error": format!("db error: {e}")})),
+    // x402 settles in USD-pegged tokens and Lightning in satoshis; other
+    // providers charge `currency`.
+    let charge_currency = if provider == "lightning" {
+        LIGHTNING_CURRENCY.to_string()
+    } else if provider == "x402" || payload.currency.trim().is_empty() {
+        currency::BASE_CURRENCY.to_string()
+    } else {
+        match currency::normalize_currency(&payload.currency) {
+            Ok(c) => c,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        }
     };
+    let accepted = plugin.capability().supported_currencies;
+    if provider != "x402"
+        && !accepted.is_empty()
+        && !accepted
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(&charge_currency))
+    {
+        return Json(json!({
+            "ok": false,
+            "error": format!("{provider} does not accept {charge_currency}")
+        }));
+    }
+    // Bundles, tiers, and tips are priced in USD; videos in their own currency.
+    let mut base_currency = currency::BASE_CURRENCY.to_string();
 
-    if creator_id == buyer_id {
-        return Json(json!({"ok": false, "error": "owners cannot buy their own video"}));
+    let bundle_id = payload
+        .bundle_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(str::to_string);
+    let tier_id = payload
+        .tier_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string);
+    let mut subscription_id: Option<String> = None;
+    let mut tip_id: Option<String> = None;
+    let mut rental_hours: Option<i32> = None;
+    let tip_to = payload
+        .tip_to
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty());
+    let gift_to = payload
+        .gift_to
+        .as_deref()
+        .map(str::trim)
+        .filter(|g| !g.is_empty());
+    let mut gift_recipient_id: Option<String> = None;
+    let gift_message = match gift_to {
+        Some(_) => match gifts::clean_gift_message(payload.gift_message.as_deref()) {
+            Ok(m) => Some(m),
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        },
+        None => None,
+    };
+    if gift_to.is_some()
+        && (bundle_id.is_some() || tier_id.is_some() || tip_to.is_some() || payload.rental)
+    {
+        return Json(json!({"ok": false, "error": "gifts are permanent single-video purchases"}));
     }
-    if video_price_cents < 0 {
-        return Json(json!({"ok": false, "error": "invalid video price"}));
+    if gift_to.is_some()
+        && payload
+            .coupon
+            .as_deref()
+            .is_some_and(|c| !c.trim().is_empty())
+    {
+        return Json(json!({"ok": false, "error": "coupons cannot be applied to gifts"}));
     }
 
+    // Load the authoritative price and ownership data from the database so the
+    // client cannot tamper with invoice totals or buy its own content.
+    let (item_title, creator_id, mut video_price_cents, video_id) = if let Some(bundle_id) =
+        &bundle_id
+    {
+        // The x402 plugin settles against a single on-chain video id.
+        if provider == "x402" {
+            return Json(json!({"ok": false, "error": "bundles cannot be paid with x402"}));
+        }
+        let bundle = match bundles::load_bundle(&state.pool, bundle_id).await {
+            Ok(Some(b)) => b,
+            Ok(None) => return Json(json!({"ok": false, "error": "bundle not found"})),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db error: {e}")})),
+        };
+        if let Err(e) = bundles::check_bundle_purchasable(&state.pool, &bundle, &buyer_id).await {
+            return Json(json!({"ok": false, "error": e}));
+        }
+        (bundle.title, bundle.owner_id, bundle.price_cents, None)
+    } else if let Some(tier_id) = &tier_id {
+        if !plugin.capability().supports_recurring {
+            return Json(json!({
+                "ok": false,
+                "error": format!("{provider} does not support recurring charges")
+            }));
+        }
+        let tier = match subscriptions::load_tier(&state.pool, tier_id).await {
+            Ok(Some(t)) => t,
+            Ok(None) => return Json(json!({"ok": false, "error": "tier not found"})),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db error: {e}")})),
+        };
+        if let Err(e) = subscriptions::check_tier_subscribable(&state.pool, &tier, &buyer_id).await
+        {
+            return Json(json!({"ok": false, "error": e}));
+        }
+        match subscriptions::create_pending_subscription(&state.pool, &tier, &buyer_id, &provider)
+            .await
+        {
+            Ok(id) => subscription_id = Some(id),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db error: {e}")})),
+        }
+        (tier.name, tier.creator_id, tier.price_cents, None)
+    } else if let Some(tip_to) = tip_to {
+        if provider == "x402" {
+            return Json(json!({"ok": false, "error": "tips cannot be paid with x402"}));
+        }
+        let amount_cents = match tips::tip_amount(payload.amount_cents) {
+            Ok(a) => a,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        };
+        let message = match gifts::clean_gift_message(payload.gift_message.as_deref()) {
+            Ok(m) => m,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        };
+        let target = match tips::load_tip_target(&state.pool, tip_to, &buyer_id).await {
+            Ok(t) => t,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        };
+        match tips::create_pending_tip(
+            &state.pool,
+            &target,
+            &buyer_id,
+            amount_cents,
+            state.cfg.creator_split_bp,
+            &provider,
+            &message,
+        )
+        .await
+        {
+            Ok(id) => tip_id = Some(id),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db error: {e}")})),
+        }
+        (
+            format!("Tip for @{}", target.username),
+            target.creator_id,
+            amount_cents,
+            None,
+        )
+    } else {
+        let video_row = sqlx::query!(
+            "SELECT title, owner_id AS creator_id, price_cents, currency, pay_what_you_want \
+             FROM videos WHERE id = $1",
+            payload.video_id
+        )
+        .fetch_optional(&state.pool)
+        .await;
+
+        let (video_title, creator_id, video_price_cents, pay_what_you_want) = match video_row {
+            Ok(Some(r)) => {
+                base_currency = r.currency;
+                (r.title, r.creator_id, r.price_cents, r.pay_what_you_want)
+            }
+            Ok(None) => return Json(json!({"ok": false, "error": "video not found"})),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db error: {e}")})),
+        };
+
+        if creator_id == buyer_id {
+            return Json(json!({"ok": false, "error": "owners cannot buy their own video"}));
+        }
+        let regional =
+            match regions::regional_terms(&state.pool, &payload.video_id, country.code()).await {
+                Ok(t) => t,
+                Err(e) => return Json(json!({"ok": false, "error": e})),
+            };
+        let mut video_price_cents = regional.price(video_price_cents);
+        if payload.rental {
+            let offer = match rentals::load_rental_offer(&state.pool, &payload.video_id).await {
+                Ok(Some(o)) => o,
+                Ok(None) => return Json(json!({"ok": false, "error": "video is not for rent"})),
+                Err(e) => return Json(json!({"ok": false, "error": format!("db error: {e}")})),
+            };
+            if let Err(e) = rentals::check_rentable(&state.pool, &payload.video_id, &buyer_id).await
+            {
+                return Json(json!({"ok": false, "error": e}));
+            }
+            video_price_cents = regional.rental_price(offer.price_cents);
+            rental_hours = Some(offer.hours);
+        } else if pay_what_you_want {
+            if payload
+                .coupon
+                .as_deref()
+                .is_some_and(|c| !c.trim().is_empty())
+            {
+                return Json(json!({
+                    "ok": false,
+                    "error": "coupons do not apply to pay-what-you-want videos"
+                }));
+            }
+            video_price_cents =
+                match tips::pwyw_amount(video_price_cents, Some(payload.amount_cents)) {
+                    Ok(a) => a,
+                    Err(e) => return Json(json!({"ok": false, "error": e})),
+                };
+        }
+        if let Some(gift_to) = gift_to {
+            let recipient = match gifts::resolve_recipient(&state.pool, gift_to).await {
+                Ok(r) => r,
+                Err(e) => return Json(json!({"ok": false, "error": e})),
+            };
+            if recipient.id == buyer_id {
+                return Json(json!({"ok": false, "error": "cannot gift a video to yourself"}));
+            }
+            if let Err(e) =
+                gifts::check_giftable(&state.pool, &payload.video_id, &creator_id, &recipient.id)
+                    .await
+            {
+                return Json(json!({"ok": false, "error": e}));
+            }
+            gift_recipient_id = Some(recipient.id);
+        }
+        if video_price_cents < 0 {
+            return Json(json!({"ok": false, "error": "invalid video price"}));
+        }
+        (
+            video_title,
+            creator_id,
+            video_price_cents,
+            Some(payload.video_id.clone()),
+        )
+    };
+
     let buyer_email_from_db: Option<String> =
         sqlx::query_scalar("SELECT email FROM users WHERE id = $1 LIMIT 1")
             .bind(&buyer_id)
@@ -179,23 +436,152 @@ async fn create_invoice_with_provider(
     // always have an internal identifier, even before the provider responds.
     let invoice_uid = Uuid::new_v4().to_string();
 
+    // A coupon use is held against this invoice until the webhook confirms it.
+    let coupon_code = payload.coupon.as_deref().filter(|c| !c.trim().is_empty());
+    let coupon = match (&video_id, coupon_code) {
+        (_, None) => None,
+        (None, Some(_)) => {
+            abandon_checkout(
+                &state.pool,
+                &invoice_uid,
+                subscription_id.as_deref(),
+                tip_id.as_deref(),
+            )
+            .await;
+            return Json(json!({"ok": false, "error": "coupons apply to single videos only"}));
+        }
+        (Some(video_id), Some(code)) => match coupons::hold_for_checkout(
+            &state.pool,
+            Some(code),
+            video_id,
+            &buyer_id,
+            video_price_cents,
+            &format!("fiat:{provider}"),
+            &invoice_uid,
+        )
+        .await
+        {
+            Ok(applied) => applied,
+            Err(e) => {
+                abandon_checkout(
+                    &state.pool,
+                    &invoice_uid,
+                    subscription_id.as_deref(),
+                    tip_id.as_deref(),
+                )
+                .await;
+                return Json(json!({"ok": false, "error": e}));
+            }
+        },
+    };
+    if let Some(applied) = &coupon {
+        video_price_cents = applied.final_cents();
+    }
+
+    // Lock the rate now: the invoice amount is what the provider charges, and
+    // the USD equivalent feeds commissions and ledgers on payment.
+    let base_amount = video_price_cents;
+    let quote = match currency::lock_quote(
+        &state.pool,
+        &state.cfg,
+        &buyer_id,
+        payload.quote_id.as_deref(),
+        &base_currency,
+        &charge_currency,
+    )
+    .await
+    {
+        Ok(q) => q,
+        Err(e) => {
+            abandon_checkout(
+                &state.pool,
+                &invoice_uid,
+                subscription_id.as_deref(),
+                tip_id.as_deref(),
+            )
+            .await;
+            return Json(json!({"ok": false, "error": e}));
+        }
+    };
+    let charge_amount = quote.convert(base_amount);
+    let usd_cents = match currency::convert_now(
+        &state.pool,
+        base_amount,
+        &base_currency,
+        currency::BASE_CURRENCY,
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(e) => {
+            abandon_checkout(
+                &state.pool,
+                &invoice_uid,
+                subscription_id.as_deref(),
+                tip_id.as_deref(),
+            )
+            .await;
+            return Json(json!({"ok": false, "error": e}));
+        }
+    };
+
+    // Stripe Connect: a creator with a ready connected account gets their share
+    // at charge time and the platform keeps only the application fee. Tips are
+    // credited to the creator's wallet instead.
+    let split = if provider == "stripe" && tip_id.is_none() {
+        stripe_connect::charge_destination(&state.pool, &creator_id)
+            .await
+            .map(|account| {
+                let fee =
+                    stripe_connect::application_fee(charge_amount, state.cfg.creator_split_bp);
+                (account, fee)
+            })
+    } else {
+        None
+    };
+
     let insert_result = sqlx::query!(
         r#"INSERT INTO fiat_invoices
-           (invoice_uid, provider, user_id, video_id, creator_id, amount, currency, buyer_email)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+           (invoice_uid, provider, user_id, video_id, bundle_id, subscription_id,
+            rental_hours, creator_id, amount, currency, buyer_email,
+            gift_recipient_id, gift_message, tip_id,
+            quote_id, base_currency, base_amount, usd_cents, buyer_country,
+            connect_account_id, application_fee)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14,
+                   $15, $16, $17, $18, $19, $20, $21)"#,
         invoice_uid,
         provider,
         buyer_id,
-        payload.video_id,
+        video_id.as_deref(),
+        bundle_id.as_deref(),
+        subscription_id.as_deref(),
+        rental_hours,
         creator_id,
-        video_price_cents,
-        payload.currency,
+        charge_amount,
+        charge_currency,
         buyer_email_from_db.as_deref().unwrap_or(""),
+        gift_recipient_id.as_deref(),
+        gift_message.as_deref(),
+        tip_id.as_deref(),
+        quote.id,
+        base_currency,
+        base_amount,
+        usd_cents,
+        country.code(),
+        split.as_ref().map(|(account, _)| account.as_str()),
+        split.as_ref().map(|(_, fee)| *fee),
     )
     .execute(&state.pool)
     .await;
 
     if let Err(e) = insert_result {
+        abandon_checkout(
+            &state.pool,
+            &invoice_uid,
+            subscription_id.as_deref(),
+            tip_id.as_deref(),
+        )
+        .await;
         return Json(json!({"ok": false, "error": format!("db insert error: {e}")}));
     }
 
@@ -211,13 +597,50 @@ async fn create_invoice_with_provider(
     // reconciliation tied to our own invoice and video records.
     let mut metadata = payload.metadata;
     metadata.insert("invoice_uid".into(), invoice_uid.clone());
-    metadata.insert("video_title".into(), video_title);
+    stripe_connect::set_split_metadata(
+        &mut metadata,
+        split
+            .as_ref()
+            .map(|(account, fee)| (account.as_str(), *fee)),
+    );
+    match (&bundle_id, &subscription_id, &tip_id) {
+        (Some(bundle_id), _, _) => {
+            metadata.insert("bundle_id".into(), bundle_id.clone());
+            metadata.insert("bundle_title".into(), item_title);
+        }
+        (None, Some(subscription_id), _) => {
+            metadata.insert("subscription_id".into(), subscription_id.clone());
+            metadata.insert("tier_title".into(), item_title);
+            metadata.insert("recurring".into(), "true".into());
+        }
+        (None, None, Some(tip_id)) => {
+            metadata.insert("tip_id".into(), tip_id.clone());
+            metadata.insert("tip_title".into(), item_title);
+        }
+        (None, None, None) => {
+            metadata.insert("video_title".into(), item_title);
+            if let Some(hours) = rental_hours {
+                metadata.insert("rental_hours".into(), hours.to_string());
+            }
+            if let Some(applied) = &coupon {
+                metadata.insert("coupon".into(), applied.code.clone());
+            }
+            if gift_recipient_id.is_some() {
+                metadata.insert("gift".into(), "true".into());
+            }
+        }
+    }
 
     let request = CreateInvoiceRequest {
         user_id: buyer_id,
-        video_id: payload.video_id,
-        amount_cents: video_price_cents,
-        currency: payload.currency,
+        // Providers use this as the line item id; bundles, tiers, and tips pass their own id.
+        video_id: video_id
+            .or(bundle_id)
+            .or(tier_id)
+            .or(tip_id.clone())
+            .unwrap_or_default(),
+        amount_cents: charge_amount,
+        currency: charge_currency.clone(),
         buyer_email: buyer_email_from_db.or(payload.buyer_email),
         buyer_name: payload.buyer_name,
         success_url: payload.success_url,
@@ -231,6 +654,7 @@ async fn create_invoice_with_provider(
                 .as_str()
                 .or_else(|| invoice.raw["xendit_invoice_id"].as_str())
                 .or_else(|| invoice.raw["session_id"].as_str())
+                .or_else(|| invoice.raw["payment_hash"].as_str())
                 .unwrap_or(&invoice_uid);
 
             let _ = sqlx::query!(
@@ -242,20 +666,67 @@ async fn create_invoice_with_provider(
             .execute(&state.pool)
             .await;
 
-            Json(json!({"ok": true, "provider": provider, "invoice": invoice}))
+            Json(json!({
+                "ok": true,
+                "provider": provider,
+                "invoice": invoice,
+                "quote": {
+                    "id": quote.id,
+                    "base_currency": quote.base_currency,
+                    "base_amount": base_amount,
+                    "currency": charge_currency,
+                    "amount": charge_amount,
+                    "rate": quote.rate,
+                    "expires_at": quote.expires_at.to_rfc3339(),
+                },
+            }))
         }
         Err(e) => {
-            let _ = sqlx::query!(
-                "DELETE FROM fiat_invoices WHERE invoice_uid = $1",
-                invoice_uid
+            abandon_checkout(
+                &state.pool,
+                &invoice_uid,
+                subscription_id.as_deref(),
+                tip_id.as_deref(),
             )
-            .execute(&state.pool)
             .await;
             Json(json!({"ok": false, "provider": provider, "error": e.to_string()}))
         }
     }
 }
 
+/// Undoes what a checkout set up before it failed: its local invoice, the
+/// pending subscription or tip it started, and the coupon use it held.
+async fn abandon_checkout(
+    pool: &PgPool,
+    invoice_uid: &str,
+    subscription_id: Option<&str>,
+    tip_id: Option<&str>,
+) {
+    if let Err(e) = sqlx::query!(
+        "DELETE FROM fiat_invoices WHERE invoice_uid = $1",
+        invoice_uid
+    )
+    .execute(pool)
+    .await
+    {
+        tracing::warn!("checkout {invoice_uid}: invoice cleanup failed: {e}");
+        return;
+    }
+    if let Some(id) = subscription_id {
+        if let Err(e) = subscriptions::discard_pending_subscription(pool, id).await {
+            tracing::warn!("checkout {invoice_uid}: pending subscription {id} left: {e}");
+        }
+    }
+    if let Some(id) = tip_id {
+        if let Err(e) = tips::discard_pending_tip(pool, id).await {
+            tracing::warn!("checkout {invoice_uid}: pending tip {id} left: {e}");
+        }
+    }
+    if let Err(e) = coupons::release_hold(pool, invoice_uid).await {
+        tracing::warn!("checkout {invoice_uid}: coupon hold left: {e}");
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Manual confirm
 // ---------------------------------------------------------------------------
@@ -306,10 +777,253 @@ async fn confirm_payment_with_provider(
     }
 }
 
+/// A pending invoice of a provider without webhooks.
+struct OpenInvoice {
+    amount: i64,
+    currency: String,
+    provider_ref: Option<String>,
+}
+
+/// Loads a `provider` invoice that is still waiting for payment; anything
+/// else is answered directly (already paid, closed, or unknown).
+async fn load_open_invoice(
+    pool: &PgPool,
+    provider: &str,
+    invoice_uid: &str,
+) -> Result<OpenInvoice, Json<serde_json::Value>> {
+    let invoice = sqlx::query(
+        "SELECT amount, currency, provider_ref, status, paid_at IS NOT NULL AS paid
+         FROM fiat_invoices WHERE invoice_uid = $1 AND provider = $2",
+    )
+    .bind(invoice_uid)
+    .bind(provider)
+    .fetch_optional(pool)
+    .await;
+    let r = match invoice {
+        Ok(Some(r)) => r,
+        Ok(None) => return Err(Json(json!({"ok": false, "error": "invoice not found"}))),
+        Err(e) => {
+            return Err(Json(
+                json!({"ok": false, "error": format!("db error: {e}")}),
+            ))
+        }
+    };
+    if r.try_get::<bool, _>("paid").unwrap_or(false) {
+        return Err(Json(json!({
+            "ok": true,
+            "status": "paid",
+            "invoice_id": invoice_uid,
+            "replayed": true
+        })));
+    }
+    let status = r.try_get::<String, _>("status").unwrap_or_default();
+    if status != "pending" {
+        return Err(Json(
+            json!({"ok": false, "error": format!("invoice is {status}")}),
+        ));
+    }
+    Ok(OpenInvoice {
+        amount: r.try_get("amount").unwrap_or(0),
+        currency: r.try_get("currency").unwrap_or_default(),
+        provider_ref: r.try_get("provider_ref").unwrap_or(None),
+    })
+}
+
+/// Grants an invoice the provider reports as paid in full, like a webhook.
+/// A short payment is left pending for the reconciler to report; an expired
+/// one is closed.
+async fn grant_confirmed(
+    pool: &PgPool,
+    provider: &str,
+    invoice: &OpenInvoice,
+    result: PaymentResult,
+) -> Json<serde_json::Value> {
+    let invoice_uid = result.invoice_id.as_str();
+    if matches!(
+        result.status,
+        PaymentStatus::Expired | PaymentStatus::Cancelled
+    ) {
+        let _ = sqlx::query(
+            "UPDATE fiat_invoices SET status = 'expired'
+             WHERE invoice_uid = $1 AND status = 'pending' AND paid_at IS NULL",
+        )
+        .bind(invoice_uid)
+        .execute(pool)
+        .await;
+        return Json(json!({"ok": false, "error": "invoice expired"}));
+    }
+    if result.status != PaymentStatus::Paid {
+        return Json(json!({
+            "ok": false,
+            "pending": true,
+            "error": "payment not found yet"
+        }));
+    }
+    if result.paid_amount_cents < invoice.amount {
+        return Json(json!({
+            "ok": false,
+            "error": "underpaid",
+            "paid_amount_cents": result.paid_amount_cents,
+            "required_amount_cents": invoice.amount,
+            "transaction_id": result.transaction_id,
+        }));
+    }
+    match apply_webhook_result(pool, provider, &result).await {
+        Ok(outcome) => Json(json!({
+            "ok": true,
+            "status": "paid",
+            "invoice_id": invoice_uid,
+            "transaction_id": result.transaction_id,
+            "result": outcome,
+        })),
+        Err(e) => {
+            tracing::warn!("{provider} confirm: grant failed for {invoice_uid}: {e}");
+            Json(json!({"ok": false, "error": e}))
+        }
+    }
+}
+
+/// POST /api/pay/solana/confirm
+///
+/// Solana payments have no webhook: the buyer's page asks the server to look
+/// for the transfer (`transaction_id`, or any transaction with the invoice
+/// memo) and it is granted like a webhook once paid in full. A short payment
+/// is left pending for the reconciler to report.
+pub async fn confirm_solana_payment(
+    State(state): State<PaymentPluginState>,
+    Json(payload): Json<ConfirmPaymentPayload>,
+) -> impl IntoResponse {
+    let invoice = match load_open_invoice(&state.pool, "solana", &payload.invoice_id).await {
+        Ok(invoice) => invoice,
+        Err(answer) => return answer,
+    };
+
+    let registry = registry_for_existing_invoices(&state);
+    let Some(plugin) = registry.get("solana") else {
+        return Json(json!({"ok": false, "error": "payment provider not found: solana"}));
+    };
+    let request = ConfirmPaymentRequest {
+        provider: "solana".into(),
+        invoice_id: payload.invoice_id.clone(),
+        transaction_id: payload.transaction_id,
+        webhook_payload: None,
+        signature_headers: Default::default(),
+    };
+    match plugin.confirm_payment(request).await {
+        Ok(result) => grant_confirmed(&state.pool, "solana", &invoice, result).await,
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// POST /api/pay/lightning/confirm
+///
+/// Asks the node about one invoice right away instead of waiting for the
+/// settlement watcher. The invoice is looked up by the payment hash saved at
+/// checkout; a hash sent by the buyer is ignored.
+pub async fn confirm_lightning_payment(
+    State(state): State<PaymentPluginState>,
+    Json(payload): Json<ConfirmPaymentPayload>,
+) -> impl IntoResponse {
+    let invoice = match load_open_invoice(&state.pool, "lightning", &payload.invoice_id).await {
+        Ok(invoice) => invoice,
+        Err(answer) => return answer,
+    };
+    let plugin = LightningPaymentPlugin::from_env();
+    match settle_lightning_invoice(&state.pool, &plugin, &payload.invoice_id, &invoice).await {
+        Ok(answer) => answer,
+        Err(e) => Json(json!({"ok": false, "error": e})),
+    }
+}
+
+async fn settle_lightning_invoice(
+    pool: &PgPool,
+    plugin: &LightningPaymentPlugin,
+    invoice_uid: &str,
+    invoice: &OpenInvoice,
+) -> Result<Json<serde_json::Value>, String> {
+    let request = PaymentStatusRequest {
+        invoice_id: invoice_uid.to_string(),
+        payment_ref: invoice.provider_ref.clone(),
+        currency: invoice.currency.clone(),
+    };
+    let mut result = plugin
+        .payment_status(request)
+        .await
+        .map_err(|e| e.to_string())?;
+    result.invoice_id = invoice_uid.to_string();
+    Ok(grant_confirmed(pool, "lightning", invoice, result).await)
+}
+
+/// Spawns the Lightning settlement watcher: every `LIGHTNING_POLL_SECS`
+/// (default 5) it asks the node about each open invoice and grants settled
+/// ones. Invoices are watched until shortly after their BOLT11 expiry; the
+/// reconciler covers any it misses.
+pub fn start_lightning_watcher(pool: PgPool) {
+    let plugin = LightningPaymentPlugin::from_env();
+    if !plugin.is_configured() {
+        return;
+    }
+    let poll_secs = std::env::var("LIGHTNING_POLL_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(5);
+    // Keep watching a little past expiry for payments settling at the edge.
+    let watch_secs = plugin.invoice_expiry_secs() + 600;
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(poll_secs));
+        loop {
+            ticker.tick().await;
+            let rows = sqlx::query(
+                "SELECT invoice_uid, amount, currency, provider_ref
+                 FROM fiat_invoices
+                 WHERE provider = 'lightning' AND status = 'pending' AND paid_at IS NULL
+                   AND provider_ref IS NOT NULL
+                   AND created_at > NOW() - make_interval(secs => $1)
+                 ORDER BY created_at",
+            )
+            .bind(watch_secs as f64)
+            .fetch_all(&pool)
+            .await;
+            let rows = match rows {
+                Ok(rows) => rows,
+                Err(e) => {
+                    tracing::warn!("lightning watcher: {e}");
+                    continue;
+                }
+            };
+            for r in rows {
+                let invoice_uid: String = r.try_get("invoice_uid").unwrap_or_default();
+                let invoice = OpenInvoice {
+                    amount: r.try_get("amount").unwrap_or(0),
+                    currency: r.try_get("currency").unwrap_or_default(),
+                    provider_ref: r.try_get("provider_ref").unwrap_or(None),
+                };
+                match settle_lightning_invoice(&pool, &plugin, &invoice_uid, &invoice).await {
+                    Ok(Json(answer)) if answer["ok"] == true => {
+                        tracing::info!("lightning watcher: uid={invoice_uid} settled");
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        // Most likely the node is unreachable; try again next tick.
+                        tracing::warn!("lightning watcher: uid={invoice_uid}: {e}");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
 // ---------------------------------------------------------------------------
 // Webhook handler
 // ---------------------------------------------------------------------------
 
+/// POST /api/pay/:provider/webhook
+///
+/// Verifies the event with the provider plugin and stores it in the webhook
+/// inbox; the inbox worker applies it (see [`apply_webhook_result`]).
 pub async fn handle_webhook(
     State(state): State<PaymentPluginState>,
     Path(provider): Path<String>,
@@ -323,11 +1037,6 @@ pub async fn handle_webhook(
         );
     };
 
-    let raw_b64 = B64.encode(&body);
-    let parsed: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
-    let mut payload = parsed;
-    payload["__raw__"] = json!(raw_b64);
-
     let mut sig_headers = std::collections::HashMap::<String, String>::new();
     for (k, v) in headers.iter() {
         if let Ok(val) = v.to_str() {
@@ -335,28 +1044,60 @@ pub async fn handle_webhook(
         }
     }
 
-    let request = ConfirmPaymentRequest {
-        provider: provider.clone(),
-        invoice_id: String::new(),
-        transaction_id: None,
-        webhook_payload: Some(payload),
-        signature_headers: sig_headers,
-    };
-
-    // Delegate signature verification and payload interpretation to the
-    // provider plugin, then apply our own local idempotency and entitlement
-    // rules to the normalized result.
-    let result = match plugin.confirm_payment(request).await {
-        Ok(r) => r,
+    match webhook_inbox::receive(&state.pool, plugin.as_ref(), &provider, sig_headers, &body).await
+    {
+        Ok(Received::Queued(id)) => {
+            webhook_inbox::spawn_process(state.pool.clone(), id);
+            Json(json!({"ok": true, "queued": true, "event": id}))
+        }
+        Ok(Received::Duplicate(id)) => {
+            tracing::info!("fiat webhook duplicate ignored: provider={provider} event={id}");
+            Json(json!({"ok": true, "duplicate": true, "event": id}))
+        }
+        Ok(Received::Rejected(error)) => {
+            tracing::warn!("webhook confirm_payment error ({provider}): {error}");
+            Json(json!({"ok": false, "error": error}))
+        }
         Err(e) => {
-            tracing::warn!("webhook confirm_payment error ({provider}): {e}");
-            return Json(json!({"ok": false, "error": e.to_string()}));
+            tracing::error!("webhook: inbox insert failed ({provider}): {e}");
+            Json(json!({"ok": false, "error": "db error"}))
         }
-    };
+    }
+}
+
+/// Applies a verified webhook result: a refund or dispute, or the grant of a
+/// paid invoice. Returns what was done; an `Err` is retried by the inbox.
+///
+/// The invoice becomes `paid` in the same transaction as its grant, and only
+/// once (`paid_at IS NULL`), so redelivered or re-driven events change nothing.
+pub(crate) async fn apply_webhook_result(
+    pool: &PgPool,
+    provider: &str,
+    result: &PaymentResult,
+) -> Result<serde_json::Value, String> {
+    if matches!(
+        result.status,
+        PaymentStatus::Refunded | PaymentStatus::Chargeback
+    ) {
+        if let Some(notice) = result.refund.as_ref() {
+            let applied = refunds::record_provider_refund(
+                pool,
+                provider,
+                &result.invoice_id,
+                &result.status,
+                notice,
+            )
+            .await?;
+            return Ok(json!({
+                "status": format!("{:?}", result.status),
+                "refund_id": notice.refund_id,
+                "applied": applied,
+            }));
+        }
+    }
 
     if result.status != PaymentStatus::Paid {
-        return Json(json!({
-            "ok": true,
+        return Ok(json!({
             "status": format!("{:?}", result.status),
             "invoice_id": result.invoice_id
         }));
@@ -364,120 +1105,200 @@ pub async fn handle_webhook(
 
     let invoice_uid = &result.invoice_id;
 
+    // A webhook can arrive before the checkout's invoice row is visible;
+    // a missing invoice is an error so the event is retried.
     let inv = sqlx::query!(
-        r#"SELECT fi.user_id, fi.video_id, fi.creator_id, fi.amount, fi.currency,
+        r#"SELECT fi.user_id, fi.video_id, fi.bundle_id, fi.subscription_id, fi.rental_hours,
+                  fi.creator_id, fi.gift_recipient_id, fi.gift_message, fi.tip_id,
+                  fi.amount, fi.currency, fi.usd_cents,
                   fi.status, fi.paid_at, fi.disbursed_at,
-                  buyer.username  AS buyer_username,
-                  creator.bank_account AS creator_bank
+                  creator.bank_account AS creator_bank,
+                  EXISTS (SELECT 1 FROM creator_payout_settings s WHERE s.user_id = fi.creator_id)
+                    AS "scheduled_payouts!"
            FROM fiat_invoices fi
            JOIN users buyer   ON buyer.id   = fi.user_id
            JOIN users creator ON creator.id = fi.creator_id
            WHERE fi.invoice_uid = $1"#,
         invoice_uid
     )
-    .fetch_optional(&state.pool)
-    .await;
-
-    let inv = match inv {
-        Ok(Some(r)) => r,
-        Ok(None) => {
-            tracing::warn!("webhook: no fiat_invoice row for uid={invoice_uid}");
-            return Json(json!({"ok": false, "error": "invoice not found"}));
-        }
-        Err(e) => {
-            tracing::error!("webhook: db fetch error: {e}");
-            return Json(json!({"ok": false, "error": "db error"}));
-        }
-    };
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?
+    .ok_or_else(|| format!("invoice not found: {invoice_uid}"))?;
 
-    // Ignore replayed webhooks once the invoice has already been finalized as
-    // paid. This prevents duplicate entitlement grants and disbursement logic.
-    if inv.status == "paid" && inv.paid_at.is_some() {
+    let replayed = json!({
+        "status": inv.status,
+        "invoice_id": invoice_uid,
+        "video_id": inv.video_id,
+        "bundle_id": inv.bundle_id,
+        "replayed": true
+    });
+    if inv.paid_at.is_some() {
         tracing::info!(
             "fiat webhook replay ignored: provider={provider} uid={invoice_uid} already paid"
         );
-        return Json(json!({
-            "ok": true,
-            "status": "paid",
-            "invoice_id": invoice_uid,
-            "video_id": inv.video_id,
-            "replayed": true
-        }));
+        return Ok(replayed);
     }
 
-    let username = inv.buyer_username.clone();
+    // Ledgers and commissions are in USD; older invoices were always USD.
+    let settled_cents = inv.usd_cents.unwrap_or(inv.amount);
+    let method = format!("fiat:{provider}");
+    let origin = Origin::fiat(provider, invoice_uid);
 
-    let _ = sqlx::query!(
+    let mut tx = pool.begin().await.map_err(|e| format!("begin tx: {e}"))?;
+    // A Stripe Connect charge already moved the creator share, so it is
+    // disbursed with the payment.
+    let claimed = sqlx::query!(
         r#"UPDATE fiat_invoices
            SET status = 'paid', paid_at = now(),
-               provider_ref = COALESCE(provider_ref, $2)
-           WHERE invoice_uid = $1"#,
+               provider_ref = COALESCE(provider_ref, $2),
+               charge_ref = COALESCE(charge_ref, NULLIF($2, '')),
+               disbursed_at = CASE WHEN connect_account_id IS NULL
+                                   THEN disbursed_at ELSE now() END,
+               disburse_ref = CASE WHEN connect_account_id IS NULL
+                                   THEN disburse_ref ELSE 'connect:' || connect_account_id END
+           WHERE invoice_uid = $1 AND paid_at IS NULL"#,
         invoice_uid,
         result.transaction_id.as_deref().unwrap_or(""),
     )
-    .execute(&state.pool)
-    .await;
-
-    let _ = sqlx::query!(
-        r#"INSERT INTO purchases (user_id, video_id, created_at)
-           VALUES ($1, $2, NOW())
-           ON CONFLICT DO NOTHING"#,
-        inv.user_id,
-        inv.video_id,
-    )
-    .execute(&state.pool)
-    .await;
-
-    let _ = sqlx::query!(
-        r#"INSERT INTO allowlist (video_id, username)
-           VALUES ($1, $2)
-           ON CONFLICT (video_id, username) DO NOTHING"#,
-        inv.video_id,
-        username,
-    )
-    .execute(&state.pool)
-    .await;
-
-    tracing::info!(
-        "fiat payment granted: provider={provider} uid={invoice_uid} user={} video={}",
-        inv.user_id,
-        inv.video_id
-    );
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("db: {e}"))?
+    .rows_affected();
+    if claimed == 0 {
+        return Ok(replayed);
+    }
 
-    let aff_ref: Option<String> =
-        sqlx::query("SELECT affiliate_ref FROM fiat_invoices WHERE invoice_uid = $1 LIMIT 1")
-            .bind(invoice_uid)
-            .fetch_optional(&state.pool)
-            .await
-            .ok()
-            .flatten()
-            .and_then(|r: sqlx::postgres::PgRow| {
-                use sqlx::Row as _;
-                r.try_get::<Option<String>, _>("affiliate_ref")
-                    .ok()
-                    .flatten()
-            });
-
-    if let Some(ref_username) = aff_ref.as_deref().filter(|s| !s.is_empty()) {
-        if let Err(e) = commission::process_affiliate_commission(
-            &state.pool,
-            &inv.video_id,
+    let granted: Result<(), String> = if let Some(bundle_id) = inv.bundle_id.as_deref() {
+        bundles::record_bundle_purchase(
+            &mut tx,
+            bundle_id,
             &inv.user_id,
-            &inv.creator_id,
-            inv.amount,
-            ref_username,
-            &provider,
+            settled_cents,
+            &method,
             Some(invoice_uid.as_str()),
         )
         .await
-        {
-            tracing::warn!("fiat affiliate commission skipped: {e}");
+        .map(|_| ())
+        .map_err(|e| format!("bundle purchase: {e}"))
+    } else if let Some(subscription_id) = inv.subscription_id.as_deref() {
+        subscriptions::activate_from_checkout(
+            &mut tx,
+            subscription_id,
+            settled_cents,
+            provider,
+            result.transaction_id.as_deref(),
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("subscription activation: {e}"))
+    } else if let Some(tip_id) = inv.tip_id.as_deref() {
+        tips::credit_plugin_tip(&mut tx, tip_id)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("tip credit: {e}"))
+    } else if let Some(video_id) = inv.video_id.as_deref() {
+        if let Some(hours) = inv.rental_hours {
+            // Rentals expire, so they get a time-limited entitlement.
+            rentals::record_rental(&mut tx, &inv.user_id, video_id, hours, origin)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("rental: {e}"))
+        } else if let Some(recipient_id) = inv.gift_recipient_id.as_deref() {
+            gifts::record_gift(
+                &mut tx,
+                video_id,
+                &inv.user_id,
+                recipient_id,
+                settled_cents,
+                &method,
+                Some(invoice_uid.as_str()),
+                inv.gift_message.as_deref().unwrap_or_default(),
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("gift: {e}"))
+        } else {
+            entitlements::grant_video(&mut tx, &inv.user_id, video_id, origin)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("purchase: {e}"))
+        }
+    } else {
+        Ok(())
+    };
+    granted?;
+    tx.commit().await.map_err(|e| format!("commit: {e}"))?;
+
+    if let Err(e) = coupons::confirm_redemption(pool, invoice_uid).await {
+        tracing::warn!("webhook: coupon redemption not confirmed for uid={invoice_uid}: {e}");
+    }
+
+    if let Some(bundle_id) = inv.bundle_id.as_deref() {
+        tracing::info!(
+            "fiat bundle payment granted: provider={provider} uid={invoice_uid} user={} bundle={bundle_id}",
+            inv.user_id
+        );
+    } else if let Some(subscription_id) = inv.subscription_id.as_deref() {
+        tracing::info!(
+            "fiat subscription started: provider={provider} uid={invoice_uid} user={} subscription={subscription_id}",
+            inv.user_id
+        );
+    } else if let Some(tip_id) = inv.tip_id.as_deref() {
+        tracing::info!(
+            "fiat tip paid: provider={provider} uid={invoice_uid} user={} creator={} tip={tip_id}",
+            inv.user_id,
+            inv.creator_id
+        );
+    } else if let Some(video_id) = inv.video_id.as_deref() {
+        tracing::info!(
+            "fiat payment granted: provider={provider} uid={invoice_uid} user={} video={} rental_hours={:?}",
+            inv.user_id,
+            video_id,
+            inv.rental_hours
+        );
+
+        let aff_ref: Option<String> =
+            sqlx::query("SELECT affiliate_ref FROM fiat_invoices WHERE invoice_uid = $1 LIMIT 1")
+                .bind(invoice_uid)
+                .fetch_optional(pool)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|r: sqlx::postgres::PgRow| {
+                    use sqlx::Row as _;
+                    r.try_get::<Option<String>, _>("affiliate_ref")
+                        .ok()
+                        .flatten()
+                });
+
+        if let Some(ref_username) = aff_ref.as_deref().filter(|s| !s.is_empty()) {
+            if let Err(e) = commission::process_affiliate_commission(
+                pool,
+                video_id,
+                &inv.user_id,
+                &inv.creator_id,
+                settled_cents,
+                ref_username,
+                provider,
+                Some(invoice_uid.as_str()),
+            )
+            .await
+            {
+                tracing::warn!("fiat affiliate commission skipped: {e}");
+            }
         }
     }
 
     // Auto-disburse only once for providers that support it natively. A replay
     // or repeated callback should not produce multiple creator payouts.
-    if provider == "xendit" && inv.disbursed_at.is_none() {
+    // Tips are credited to the creator's wallet instead, and creators with
+    // payout settings are paid by the payout engine (handlers/payouts.rs).
+    if provider == "xendit"
+        && inv.disbursed_at.is_none()
+        && inv.tip_id.is_none()
+        && !inv.scheduled_payouts
+    {
         use crate::plugins::payment::providers::xendit::XenditPaymentPlugin;
 
         if let Some(ba) = inv.creator_bank {
@@ -491,7 +1312,7 @@ pub async fn handle_webhook(
                             disburse_ref,
                             invoice_uid,
                         )
-                        .execute(&state.pool)
+                        .execute(pool)
                         .await;
                         tracing::info!("xendit: disbursed {disburse_ref} for uid={invoice_uid}");
                     }
@@ -503,10 +1324,10 @@ pub async fn handle_webhook(
         }
     }
 
-    Json(json!({
-        "ok": true,
+    Ok(json!({
         "status": "paid",
         "invoice_id": invoice_uid,
-        "video_id": inv.video_id
+        "video_id": inv.video_id,
+        "bundle_id": inv.bundle_id
     }))
 }
@@ -0,0 +1,1112 @@
+// src/handlers/payouts.rs
+//
+// Automated creator payouts.
+//
+// Creators choose a payout method, a destination and a threshold
+// (`creator_payout_settings`). Every `PAYOUT_INTERVAL_HOURS` — or when an admin
+// runs it — a batch collects the paid fiat invoices nobody has paid out yet
+// (not disbursed, not part of a payout, not tips, paid more than
+// `PAYOUT_HOLD_DAYS` ago) and takes the creator share: CREATOR_SPLIT_BP of the
+// USD value the buyer kept after refunds. Creators whose total reaches their
+// threshold get one `payouts` row; its invoices point at it.
+//
+// The worker sends pending payouts through the `PayoutPlugin` of the method,
+// converted from USD to the plugin's currency:
+//
+//   paid         its invoices are marked disbursed (`disburse_ref = payout:<id>`)
+//   processing   provider lookups until it settles; manual bank transfers wait
+//                for an admin to mark them paid or failed
+//   rejected     retried with exponential backoff, then `failed` after
+//                PAYOUT_MAX_ATTEMPTS; an admin retry sends it once more with
+//                the creator's current method and destination
+//   unknown      the send errored after it may have reached the provider:
+//                `processing` until a status lookup or an admin settles it
+//
+// Every attempt of a payout goes out under the same idempotency key; the key
+// only changes after a rejection or an admin retry (`key_generation`), so a
+// transfer the provider already made is never made again.
+//
+// Refunds treat invoices in a payout as disbursed and take the creator share
+// back from the wallet (see refunds.rs), so a payout never changes amount.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::collections::BTreeMap;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::currency;
+use crate::plugins::payment::{
+    models::{PayoutRequest, PayoutResult, PayoutStatus},
+    registry::{payout_plugin, payout_plugins},
+};
+use crate::sessions;
+
+/// How often the worker wakes.
+const POLL_INTERVAL_SECS: u64 = 60;
+/// How many payouts are sent or looked up per pass.
+const BATCH_SIZE: i64 = 20;
+/// A payout still `sending` after this long was interrupted mid-send.
+const STALE_SENDING_MINUTES: i32 = 15;
+/// Transfers are looked up no sooner than this after they were sent or last
+/// checked.
+const STATUS_LOOKUP_AFTER_SECS: i32 = 30;
+/// Longest wait between two attempts.
+const MAX_RETRY_DELAY_MINUTES: i32 = 24 * 60;
+/// Serializes batch runs between the worker and admins.
+const BATCH_LOCK_KEY: i64 = 0x7061_796f_7574;
+
+type Ts = chrono::DateTime<chrono::Utc>;
+
+#[derive(Clone)]
+pub struct PayoutState {
+    pub pool: PgPool,
+    pub cfg: Config,
+}
+
+/// Creator share of an invoice in USD cents: `split_bp` of the part of
+/// `settled_cents` the buyer kept after `refunded` of `amount` came back.
+pub(crate) fn creator_share(settled_cents: i64, amount: i64, refunded: i64, split_bp: u16) -> i64 {
+    if amount <= 0 {
+        return 0;
+    }
+    let kept = (amount - refunded.clamp(0, amount)) as i128;
+    (settled_cents as i128 * kept * split_bp as i128 / (amount as i128 * 10_000)) as i64
+}
+
+/// Minutes to wait after the `attempt`-th failed send: 5, 10, 20, … up to a day.
+fn retry_delay_minutes(attempt: i32) -> i32 {
+    let doublings = attempt.clamp(1, 16) - 1;
+    (5i32 << doublings).min(MAX_RETRY_DELAY_MINUTES)
+}
+
+/// One CSV cell; quoted when needed, and never read as a formula.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn format_minor(amount: i64, currency: &str) -> String {
+    let exp = currency::minor_exponent(currency);
+    if exp == 0 {
+        return amount.to_string();
+    }
+    let unit = 10i64.pow(exp);
+    format!(
+        "{}.{:0width$}",
+        amount / unit,
+        amount % unit,
+        width = exp as usize
+    )
+}
+
+// ─── Worker ──────────────────────────────────────────────────────────────────
+
+/// Spawns the background task that runs scheduled batches and sends payouts.
+pub fn start_payout_worker(pool: PgPool, cfg: Config) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(POLL_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match batch_due(&pool, &cfg).await {
+                Ok(true) => {
+                    if let Err(e) = run_batch(&pool, &cfg, None).await {
+                        tracing::error!("payout batch: {}", e);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => tracing::error!("payout schedule: {}", e),
+            }
+            if let Err(e) = send_due(&pool, &cfg).await {
+                tracing::error!("payout send: {}", e);
+            }
+            if let Err(e) = poll_processing(&pool, &cfg).await {
+                tracing::error!("payout status: {}", e);
+            }
+        }
+    });
+}
+
+/// Scheduled batches run `PAYOUT_INTERVAL_HOURS` apart; admin runs do not
+/// move the schedule.
+async fn batch_due(pool: &PgPool, cfg: &Config) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COALESCE(MAX(created_at) < NOW() - make_interval(hours => $1), TRUE)
+         FROM payout_batches WHERE triggered_by IS NULL",
+    )
+    .bind(cfg.payout_interval_hours)
+    .fetch_one(pool)
+    .await
+}
+
+/// Totals of one batch, returned by the admin "run now" endpoint.
+#[derive(Default, Serialize)]
+pub(crate) struct BatchSummary {
+    batch_id: i64,
+    payouts: usize,
+    total_cents: i64,
+    /// Creators with earnings below their threshold.
+    below_threshold: usize,
+    /// Creators whose payout method is not available right now.
+    unavailable: usize,
+}
+
+struct Earnings {
+    method: String,
+    destination: String,
+    threshold_cents: i64,
+    invoices: Vec<String>,
+    shares: Vec<i64>,
+}
+
+/// Creates the payouts of one batch. See the module docs.
+pub(crate) async fn run_batch(
+    pool: &PgPool,
+    cfg: &Config,
+    triggered_by: Option<&str>,
+) -> Result<BatchSummary, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(BATCH_LOCK_KEY)
+        .execute(&mut *tx)
+        .await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT fi.invoice_uid, fi.creator_id, fi.amount, fi.refunded_amount, fi.usd_cents,
+               s.method, s.destination, s.threshold_cents
+        FROM fiat_invoices fi
+        JOIN creator_payout_settings s ON s.user_id = fi.creator_id
+        WHERE fi.status IN ('paid', 'partially_refunded')
+          AND fi.paid_at < NOW() - make_interval(days => $1)
+          AND fi.disbursed_at IS NULL AND fi.payout_id IS NULL AND fi.tip_id IS NULL
+        ORDER BY fi.creator_id, fi.paid_at
+        FOR UPDATE OF fi SKIP LOCKED
+        "#,
+    )
+    .bind(cfg.payout_hold_days)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut by_creator: BTreeMap<String, Earnings> = BTreeMap::new();
+    for r in &rows {
+        let amount: i64 = r.try_get("amount").unwrap_or(0);
+        // Ledgers are in USD; older invoices were always USD.
+        let settled = r
+            .try_get::<Option<i64>, _>("usd_cents")
+            .ok()
+            .flatten()
+            .unwrap_or(amount);
+        let share = creator_share(
+            settled,
+            amount,
+            r.try_get("refunded_amount").unwrap_or(0),
+            cfg.creator_split_bp,
+        );
+        if share <= 0 {
+            continue;
+        }
+        let creator_id: String = r.try_get("creator_id").unwrap_or_default();
+        let entry = by_creator.entry(creator_id).or_insert_with(|| Earnings {
+            method: r.try_get("method").unwrap_or_default(),
+            destination: r.try_get("destination").unwrap_or_default(),
+            threshold_cents: r.try_get("threshold_cents").unwrap_or(0),
+            invoices: Vec::new(),
+            shares: Vec::new(),
+        });
+        entry
+            .invoices
+            .push(r.try_get("invoice_uid").unwrap_or_default());
+        entry.shares.push(share);
+    }
+
+    let batch_id: i64 =
+        sqlx::query_scalar("INSERT INTO payout_batches (triggered_by) VALUES ($1) RETURNING id")
+            .bind(triggered_by)
+            .fetch_one(&mut *tx)
+            .await?;
+    let mut summary = BatchSummary {
+        batch_id,
+        ..Default::default()
+    };
+
+    for (creator_id, earnings) in by_creator {
+        let total: i64 = earnings.shares.iter().sum();
+        if total < earnings.threshold_cents.max(cfg.payout_min_threshold_cents) {
+            summary.below_threshold += 1;
+            continue;
+        }
+        if !payout_plugin(&earnings.method).is_some_and(|p| p.payout_configured()) {
+            tracing::warn!(
+                "payout batch {batch_id}: method {} unavailable for creator {creator_id}",
+                earnings.method
+            );
+            summary.unavailable += 1;
+            continue;
+        }
+        let payout_id = Uuid::new_v4().to_string();
+        sqlx::query(
+            "INSERT INTO payouts (id, batch_id, creator_id, method, destination, amount_cents)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&payout_id)
+        .bind(batch_id)
+        .bind(&creator_id)
+        .bind(&earnings.method)
+        .bind(&earnings.destination)
+        .bind(total)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE fiat_invoices fi
+             SET payout_id = $1, payout_cents = u.cents, payout_split_bp = $4
+             FROM UNNEST($2::text[], $3::bigint[]) AS u(uid, cents)
+             WHERE fi.invoice_uid = u.uid",
+        )
+        .bind(&payout_id)
+        .bind(&earnings.invoices)
+        .bind(&earnings.shares)
+        .bind(cfg.creator_split_bp as i32)
+        .execute(&mut *tx)
+        .await?;
+        summary.payouts += 1;
+        summary.total_cents += total;
+    }
+
+    sqlx::query("UPDATE payout_batches SET payout_count = $2, total_cents = $3 WHERE id = $1")
+        .bind(batch_id)
+        .bind(summary.payouts as i32)
+        .bind(summary.total_cents)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "payout batch {batch_id}: payouts={} total_cents={} below_threshold={} unavailable={}",
+        summary.payouts,
+        summary.total_cents,
+        summary.below_threshold,
+        summary.unavailable
+    );
+    Ok(summary)
+}
+
+/// Sends the pending payouts whose next attempt is due.
+pub(crate) async fn send_due(pool: &PgPool, cfg: &Config) -> Result<usize, sqlx::Error> {
+    // Interrupted sends go again under the same key, so providers that
+    // already accepted them answer with the same transfer.
+    sqlx::query(
+        "UPDATE payouts SET status = 'pending', attempts = GREATEST(attempts - 1, 0),
+                updated_at = NOW()
+         WHERE status = 'sending' AND updated_at < NOW() - make_interval(mins => $1)",
+    )
+    .bind(STALE_SENDING_MINUTES)
+    .execute(pool)
+    .await?;
+
+    let rows = sqlx::query(
+        r#"
+        UPDATE payouts SET status = 'sending', attempts = attempts + 1, updated_at = NOW()
+        WHERE id IN (
+            SELECT id FROM payouts
+            WHERE status = 'pending' AND next_attempt_at <= NOW()
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, method, destination, amount_cents, attempts, key_generation
+        "#,
+    )
+    .bind(BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    for r in &rows {
+        let id: String = r.try_get("id").unwrap_or_default();
+        let attempt: i32 = r.try_get("attempts").unwrap_or(1);
+        let outcome = send_one(
+            pool,
+            &id,
+            &r.try_get::<String, _>("method").unwrap_or_default(),
+            &r.try_get::<String, _>("destination").unwrap_or_default(),
+            r.try_get("amount_cents").unwrap_or(0),
+            attempt,
+            r.try_get("key_generation").unwrap_or(0),
+        )
+        .await;
+        match outcome {
+            Ok(result) => record_result(pool, cfg, &id, attempt, result).await?,
+            Err(SendError::NotSent(e)) => {
+                record_failure(pool, cfg, &id, attempt, &e, false).await?
+            }
+            Err(SendError::Unknown(e)) => record_unknown(pool, &id, &e).await?,
+        }
+    }
+    Ok(rows.len())
+}
+
+/// Why a send produced no result.
+enum SendError {
+    /// Failed before the provider was called; safe to send again.
+    NotSent(String),
+    /// The provider call errored; the transfer may have been made.
+    Unknown(String),
+}
+
+/// Idempotency key of a payout: its id until the first rejection.
+fn idempotency_key(payout_id: &str, key_generation: i32) -> String {
+    if key_generation == 0 {
+        payout_id.to_string()
+    } else {
+        format!("{payout_id}-{key_generation}")
+    }
+}
+
+async fn send_one(
+    pool: &PgPool,
+    payout_id: &str,
+    method: &str,
+    destination: &str,
+    amount_cents: i64,
+    attempt: i32,
+    key_generation: i32,
+) -> Result<PayoutResult, SendError> {
+    let plugin = payout_plugin(method)
+        .filter(|p| p.payout_configured())
+        .ok_or_else(|| SendError::NotSent(format!("payout method {method} is not available")))?;
+    let currency = plugin.payout_currency();
+    let amount = currency::convert_now(pool, amount_cents, "USD", &currency)
+        .await
+        .map_err(SendError::NotSent)?;
+    sqlx::query("UPDATE payouts SET sent_currency = $2, sent_amount = $3 WHERE id = $1")
+        .bind(payout_id)
+        .bind(&currency)
+        .bind(amount)
+        .execute(pool)
+        .await
+        .map_err(|e| SendError::NotSent(format!("db: {e}")))?;
+    plugin
+        .send_payout(PayoutRequest {
+            payout_id: payout_id.to_string(),
+            destination: destination.to_string(),
+            amount,
+            currency,
+            description: format!("PPV creator payout {payout_id}"),
+            attempt,
+            idempotency_key: idempotency_key(payout_id, key_generation),
+        })
+        .await
+        .map_err(|e| SendError::Unknown(e.to_string()))
+}
+
+async fn record_result(
+    pool: &PgPool,
+    cfg: &Config,
+    payout_id: &str,
+    attempt: i32,
+    result: PayoutResult,
+) -> Result<(), sqlx::Error> {
+    match result.status {
+        PayoutStatus::Paid => {
+            mark_paid(pool, payout_id, result.provider_ref.as_deref()).await?;
+        }
+        PayoutStatus::Processing => {
+            sqlx::query(
+                "UPDATE payouts SET status = 'processing', updated_at = NOW(),
+                        provider_ref = COALESCE($2, provider_ref), last_error = NULL
+                 WHERE id = $1 AND status IN ('sending', 'processing')",
+            )
+            .bind(payout_id)
+            .bind(result.provider_ref.as_deref())
+            .execute(pool)
+            .await?;
+        }
+        PayoutStatus::Failed => {
+            let reason = result
+                .failure_reason
+                .unwrap_or_else(|| "rejected by provider".to_string());
+            record_failure(pool, cfg, payout_id, attempt, &reason, true).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Parks a send with an unknown outcome as `processing`: a status lookup
+/// settles it when the provider returned a reference, an admin otherwise.
+async fn record_unknown(pool: &PgPool, payout_id: &str, error: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE payouts SET status = 'processing', updated_at = NOW(),
+                last_error = $2
+         WHERE id = $1 AND status = 'sending'",
+    )
+    .bind(payout_id)
+    .bind(format!("outcome unknown: {error}"))
+    .execute(pool)
+    .await?;
+    tracing::warn!("payout {payout_id} outcome unknown, not sending again: {error}");
+    Ok(())
+}
+
+/// Schedules the next attempt, or gives up after `PAYOUT_MAX_ATTEMPTS`.
+/// A transfer the provider `rejected` goes again under a new key.
+async fn record_failure(
+    pool: &PgPool,
+    cfg: &Config,
+    payout_id: &str,
+    attempt: i32,
+    error: &str,
+    rejected: bool,
+) -> Result<(), sqlx::Error> {
+    let give_up = attempt >= cfg.payout_max_attempts;
+    sqlx::query(
+        "UPDATE payouts
+         SET status = CASE WHEN $3 THEN 'failed' ELSE 'pending' END,
+             next_attempt_at = NOW() + make_interval(mins => $4),
+             key_generation = key_generation + CASE WHEN $5 THEN 1 ELSE 0 END,
+             last_error = $2, updated_at = NOW()
+         WHERE id = $1 AND status IN ('sending', 'processing')",
+    )
+    .bind(payout_id)
+    .bind(error)
+    .bind(give_up)
+    .bind(retry_delay_minutes(attempt))
+    .bind(rejected)
+    .execute(pool)
+    .await?;
+    if give_up {
+        tracing::warn!("payout {payout_id} failed after {attempt} attempts: {error}");
+    } else {
+        tracing::info!("payout {payout_id} attempt {attempt} failed, will retry: {error}");
+    }
+    Ok(())
+}
+
+/// Settles a payout and marks its invoices disbursed. Returns false when the
+/// payout was not open.
+async fn mark_paid(
+    pool: &PgPool,
+    payout_id: &str,
+    provider_ref: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let paid = sqlx::query(
+        "UPDATE payouts SET status = 'paid', paid_at = NOW(), updated_at = NOW(),
+                provider_ref = COALESCE($2, provider_ref), last_error = NULL
+         WHERE id = $1 AND status IN ('sending', 'processing')",
+    )
+    .bind(payout_id)
+    .bind(provider_ref)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if paid == 0 {
+        return Ok(false);
+    }
+    sqlx::query(
+        "UPDATE fiat_invoices SET disbursed_at = NOW(), disburse_ref = 'payout:' || $1
+         WHERE payout_id = $1 AND disbursed_at IS NULL",
+    )
+    .bind(payout_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    tracing::info!("payout {payout_id} paid");
+    Ok(true)
+}
+
+/// Asks providers about payouts they accepted but have not settled.
+async fn poll_processing(pool: &PgPool, cfg: &Config) -> Result<(), sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT id, method, provider_ref, attempts FROM payouts
+         WHERE status = 'processing' AND provider_ref IS NOT NULL
+           AND updated_at < NOW() - make_interval(secs => $2)
+         ORDER BY updated_at
+         LIMIT $1",
+    )
+    .bind(BATCH_SIZE)
+    .bind(STATUS_LOOKUP_AFTER_SECS as f64)
+    .fetch_all(pool)
+    .await?;
+
+    for r in &rows {
+        let id: String = r.try_get("id").unwrap_or_default();
+        let method: String = r.try_get("method").unwrap_or_default();
+        let provider_ref: String = r.try_get("provider_ref").unwrap_or_default();
+        let Some(plugin) = payout_plugin(&method) else {
+            continue;
+        };
+        match plugin.payout_status(&provider_ref).await {
+            Ok(result) if result.status == PayoutStatus::Processing => {
+                sqlx::query("UPDATE payouts SET updated_at = NOW() WHERE id = $1")
+                    .bind(&id)
+                    .execute(pool)
+                    .await?;
+            }
+            Ok(result) => {
+                record_result(pool, cfg, &id, r.try_get("attempts").unwrap_or(1), result).await?
+            }
+            Err(e) => {
+                // Provider unreachable: ask again next pass.
+                tracing::warn!("payout {id} status lookup failed: {e}");
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+// ─── Creator ─────────────────────────────────────────────────────────────────
+
+/// GET /api/payouts/settings
+///
+/// The creator's payout settings, the methods they can pick, and what they
+/// have earned since their last payout.
+pub async fn payout_settings_get(
+    State(st): State<PayoutState>,
+    cookies: Cookies,
+) -> impl IntoResponse {
+    let (user_id, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let settings = sqlx::query(
+        "SELECT method, destination, threshold_cents, updated_at
+         FROM creator_payout_settings WHERE user_id = $1",
+    )
+    .bind(&user_id)
+    .fetch_optional(&st.pool)
+    .await;
+    let settings = match settings {
+        Ok(row) => row.map(|r| {
+            json!({
+                "method": r.try_get::<String, _>("method").unwrap_or_default(),
+                "destination": r.try_get::<String, _>("destination").unwrap_or_default(),
+                "threshold_cents": r.try_get::<i64, _>("threshold_cents").unwrap_or(0),
+                "updated_at": r.try_get::<Ts, _>("updated_at").ok(),
+            })
+        }),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let rows = sqlx::query(
+        "SELECT amount, refunded_amount, usd_cents,
+                paid_at < NOW() - make_interval(days => $2) AS cleared
+         FROM fiat_invoices
+         WHERE creator_id = $1 AND status IN ('paid', 'partially_refunded')
+           AND disbursed_at IS NULL AND payout_id IS NULL AND tip_id IS NULL",
+    )
+    .bind(&user_id)
+    .bind(st.cfg.payout_hold_days)
+    .fetch_all(&st.pool)
+    .await
+    .unwrap_or_default();
+    let (mut available, mut held) = (0i64, 0i64);
+    for r in &rows {
+        let amount: i64 = r.try_get("amount").unwrap_or(0);
+        let settled = r
+            .try_get::<Option<i64>, _>("usd_cents")
+            .ok()
+            .flatten()
+            .unwrap_or(amount);
+        let share = creator_share(
+            settled,
+            amount,
+            r.try_get("refunded_amount").unwrap_or(0),
+            st.cfg.creator_split_bp,
+        );
+        if r.try_get::<bool, _>("cleared").unwrap_or(false) {
+            available += share;
+        } else {
+            held += share;
+        }
+    }
+
+    let methods: Vec<Value> = payout_plugins()
+        .iter()
+        .map(|p| {
+            json!({
+                "method": p.payout_method(),
+                "label": p.payout_label(),
+                "configured": p.payout_configured(),
+                "currency": p.payout_currency(),
+                "destination_hint": p.destination_hint(),
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "ok": true,
+        "settings": settings,
+        "methods": methods,
+        "min_threshold_cents": st.cfg.payout_min_threshold_cents,
+        "hold_days": st.cfg.payout_hold_days,
+        "interval_hours": st.cfg.payout_interval_hours,
+        "earnings": {
+            "available_cents": available,
+            "held_cents": held,
+        },
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct PayoutSettingsPayload {
+    pub method: String,
+    pub destination: String,
+    pub threshold_cents: i64,
+}
+
+/// POST /api/payouts/settings
+pub async fn payout_settings_save(
+    State(st): State<PayoutState>,
+    cookies: Cookies,
+    Json(body): Json<PayoutSettingsPayload>,
+) -> impl IntoResponse {
+    let (user_id, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let method = body.method.trim().to_ascii_lowercase();
+    let destination = body.destination.trim();
+    let Some(plugin) = payout_plugin(&method) else {
+        return Json(json!({"ok": false, "error": "unknown payout method"}));
+    };
+    if !plugin.payout_configured() {
+        return Json(json!({"ok": false, "error": "payout method is not available"}));
+    }
+    if let Err(e) = plugin.validate_destination(destination) {
+        return Json(json!({"ok": false, "error": e.to_string()}));
+    }
+    if body.threshold_cents < st.cfg.payout_min_threshold_cents {
+        return Json(json!({
+            "ok": false,
+            "error": format!(
+                "threshold must be at least {} cents",
+                st.cfg.payout_min_threshold_cents
+            ),
+        }));
+    }
+
+    let saved = sqlx::query(
+        "INSERT INTO creator_payout_settings (user_id, method, destination, threshold_cents)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (user_id) DO UPDATE
+         SET method = EXCLUDED.method, destination = EXCLUDED.destination,
+             threshold_cents = EXCLUDED.threshold_cents, updated_at = NOW()",
+    )
+    .bind(&user_id)
+    .bind(&method)
+    .bind(destination)
+    .bind(body.threshold_cents)
+    .execute(&st.pool)
+    .await;
+    match saved {
+        Ok(_) => Json(json!({"ok": true})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PayoutListQuery {
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+fn payout_json(r: &sqlx::postgres::PgRow) -> Value {
+    json!({
+        "id": r.try_get::<String, _>("id").unwrap_or_default(),
+        "batch_id": r.try_get::<i64, _>("batch_id").unwrap_or(0),
+        "method": r.try_get::<String, _>("method").unwrap_or_default(),
+        "destination": r.try_get::<String, _>("destination").unwrap_or_default(),
+        "amount_cents": r.try_get::<i64, _>("amount_cents").unwrap_or(0),
+        "sent_currency": r.try_get::<Option<String>, _>("sent_currency").ok().flatten(),
+        "sent_amount": r.try_get::<Option<i64>, _>("sent_amount").ok().flatten(),
+        "status": r.try_get::<String, _>("status").unwrap_or_default(),
+        "provider_ref": r.try_get::<Option<String>, _>("provider_ref").ok().flatten(),
+        "attempts": r.try_get::<i32, _>("attempts").unwrap_or(0),
+        "last_error": r.try_get::<Option<String>, _>("last_error").ok().flatten(),
+        "next_attempt_at": r.try_get::<Ts, _>("next_attempt_at").ok(),
+        "invoice_count": r.try_get::<i64, _>("invoice_count").unwrap_or(0),
+        "created_at": r.try_get::<Ts, _>("created_at").ok(),
+        "paid_at": r.try_get::<Option<Ts>, _>("paid_at").ok().flatten(),
+    })
+}
+
+/// GET /api/payouts?limit=
+pub async fn my_payouts(
+    State(st): State<PayoutState>,
+    cookies: Cookies,
+    Query(q): Query<PayoutListQuery>,
+) -> impl IntoResponse {
+    let (user_id, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let rows = sqlx::query(
+        r#"
+        SELECT p.*, (SELECT COUNT(*) FROM fiat_invoices fi WHERE fi.payout_id = p.id) AS invoice_count
+        FROM payouts p
+        WHERE p.creator_id = $1
+        ORDER BY p.created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(&user_id)
+    .bind(q.limit.unwrap_or(50).clamp(1, 200))
+    .fetch_all(&st.pool)
+    .await;
+    match rows {
+        Ok(rows) => {
+            Json(json!({"ok": true, "items": rows.iter().map(payout_json).collect::<Vec<_>>()}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+// ─── Admin ───────────────────────────────────────────────────────────────────
+
+async fn admin_user(st: &PayoutState, cookies: &Cookies) -> Result<String, Json<Value>> {
+    match sessions::current_user_id(&st.pool, &st.cfg, cookies).await {
+        Some((user_id, true)) => Ok(user_id),
+        Some(_) => Err(Json(json!({"ok": false, "error": "admin only"}))),
+        None => Err(Json(json!({"ok": false, "error": "not logged in"}))),
+    }
+}
+
+/// GET /admin/payouts?status=&limit=
+pub async fn admin_payouts(
+    State(st): State<PayoutState>,
+    cookies: Cookies,
+    Query(q): Query<PayoutListQuery>,
+) -> impl IntoResponse {
+    if let Err(resp) = admin_user(&st, &cookies).await {
+        return resp;
+    }
+    let status = q.status.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let rows = sqlx::query(
+        r#"
+        SELECT p.*, u.username AS creator,
+               (SELECT COUNT(*) FROM fiat_invoices fi WHERE fi.payout_id = p.id) AS invoice_count
+        FROM payouts p
+        JOIN users u ON u.id = p.creator_id
+        WHERE ($1::text IS NULL OR p.status = $1)
+        ORDER BY p.created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(status)
+    .bind(q.limit.unwrap_or(100).clamp(1, 500))
+    .fetch_all(&st.pool)
+    .await;
+    let rows = match rows {
+        Ok(r) => r,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let items: Vec<Value> = rows
+        .iter()
+        .map(|r| {
+            let mut item = payout_json(r);
+            item["creator"] = json!(r.try_get::<String, _>("creator").unwrap_or_default());
+            item
+        })
+        .collect();
+
+    let batches: Vec<Value> = sqlx::query(
+        "SELECT b.id, b.payout_count, b.total_cents, b.created_at, u.username AS triggered_by
+         FROM payout_batches b LEFT JOIN users u ON u.id = b.triggered_by
+         ORDER BY b.created_at DESC LIMIT 10",
+    )
+    .fetch_all(&st.pool)
+    .await
+    .unwrap_or_default()
+    .iter()
+    .map(|r| {
+        json!({
+            "id": r.try_get::<i64, _>("id").unwrap_or(0),
+            "payout_count": r.try_get::<i32, _>("payout_count").unwrap_or(0),
+            "total_cents": r.try_get::<i64, _>("total_cents").unwrap_or(0),
+            "created_at": r.try_get::<Ts, _>("created_at").ok(),
+            "triggered_by": r.try_get::<Option<String>, _>("triggered_by").ok().flatten(),
+        })
+    })
+    .collect();
+
+    Json(json!({
+        "ok": true,
+        "items": items,
+        "batches": batches,
+        "interval_hours": st.cfg.payout_interval_hours,
+        "hold_days": st.cfg.payout_hold_days,
+    }))
+}
+
+/// POST /admin/payouts/run
+///
+/// Runs a batch now, sends what is due and looks up unsettled transfers
+/// instead of waiting for the worker.
+pub async fn admin_run_payouts(
+    State(st): State<PayoutState>,
+    cookies: Cookies,
+) -> impl IntoResponse {
+    let admin_user_id = match admin_user(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+    tracing::info!(
+        admin_user_id = %admin_user_id,
+        action = "run_payouts",
+        "admin action"
+    );
+    let summary = match run_batch(&st.pool, &st.cfg, Some(&admin_user_id)).await {
+        Ok(s) => s,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let sent = match send_due(&st.pool, &st.cfg).await {
+        Ok(n) => n,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    match poll_processing(&st.pool, &st.cfg).await {
+        Ok(()) => Json(json!({"ok": true, "summary": summary, "sent": sent})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+/// POST /admin/payouts/:id/retry
+///
+/// Sends a failed payout once more, to the creator's current method and
+/// destination, under a new idempotency key.
+pub async fn admin_retry_payout(
+    State(st): State<PayoutState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let admin_user_id = match admin_user(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+    let retried = sqlx::query(
+        "UPDATE payouts p
+         SET status = 'pending', next_attempt_at = NOW(), updated_at = NOW(),
+             provider_ref = NULL, key_generation = p.key_generation + 1,
+             method = COALESCE(s.method, p.method),
+             destination = COALESCE(s.destination, p.destination)
+         FROM payouts cur
+         LEFT JOIN creator_payout_settings s ON s.user_id = cur.creator_id
+         WHERE cur.id = $1 AND p.id = cur.id AND p.status = 'failed'",
+    )
+    .bind(&id)
+    .execute(&st.pool)
+    .await;
+    match retried {
+        Ok(r) if r.rows_affected() > 0 => {
+            tracing::info!(
+                admin_user_id = %admin_user_id,
+                action = "retry_payout",
+                payout = %id,
+                "admin action"
+            );
+            match send_due(&st.pool, &st.cfg).await {
+                Ok(_) => Json(json!({"ok": true, "id": id})),
+                Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+            }
+        }
+        Ok(_) => Json(json!({"ok": false, "error": "payout not found or not failed"})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SettlePayload {
+    /// Bank reference of the transfer, or why it failed.
+    #[serde(default)]
+    pub note: String,
+}
+
+/// POST /admin/payouts/:id/mark_paid
+///
+/// For transfers made outside a provider API (manual bank export).
+pub async fn admin_mark_payout_paid(
+    State(st): State<PayoutState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+    Json(body): Json<SettlePayload>,
+) -> impl IntoResponse {
+    let admin_user_id = match admin_user(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+    let reference = Some(body.note.trim()).filter(|s| !s.is_empty());
+    match mark_paid(&st.pool, &id, reference).await {
+        Ok(true) => {
+            tracing::info!(
+                admin_user_id = %admin_user_id,
+                action = "mark_payout_paid",
+                payout = %id,
+                "admin action"
+            );
+            Json(json!({"ok": true, "id": id}))
+        }
+        Ok(false) => Json(json!({"ok": false, "error": "payout not found or not in progress"})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+/// POST /admin/payouts/:id/mark_failed
+pub async fn admin_mark_payout_failed(
+    State(st): State<PayoutState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+    Json(body): Json<SettlePayload>,
+) -> impl IntoResponse {
+    let admin_user_id = match admin_user(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+    let reason = match body.note.trim() {
+        "" => "marked failed by admin",
+        note => note,
+    };
+    let failed = sqlx::query(
+        "UPDATE payouts SET status = 'failed', last_error = $2, updated_at = NOW()
+         WHERE id = $1 AND status = 'processing'",
+    )
+    .bind(&id)
+    .bind(reason)
+    .execute(&st.pool)
+    .await;
+    match failed {
+        Ok(r) if r.rows_affected() > 0 => {
+            tracing::info!(
+                admin_user_id = %admin_user_id,
+                action = "mark_payout_failed",
+                payout = %id,
+                "admin action"
+            );
+            Json(json!({"ok": true, "id": id}))
+        }
+        Ok(_) => Json(json!({"ok": false, "error": "payout not found or not in progress"})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+/// GET /admin/payouts/export.csv
+///
+/// Manual bank payouts waiting for a transfer, for the admin's banking app.
+pub async fn admin_export_payouts(State(st): State<PayoutState>, cookies: Cookies) -> Response {
+    if let Err(resp) = admin_user(&st, &cookies).await {
+        return resp.into_response();
+    }
+    let rows = sqlx::query(
+        "SELECT p.id, u.username, u.email, p.destination, p.sent_currency, p.sent_amount,
+                p.amount_cents, p.created_at
+         FROM payouts p JOIN users u ON u.id = p.creator_id
+         WHERE p.method = 'manual_bank' AND p.status = 'processing'
+         ORDER BY p.created_at",
+    )
+    .fetch_all(&st.pool)
+    .await;
+    let rows = match rows {
+        Ok(r) => r,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})).into_response(),
+    };
+
+    let mut csv =
+        String::from("payout_id,creator,email,destination,currency,amount,amount_usd,created_at\n");
+    for r in &rows {
+        let currency = r
+            .try_get::<Option<String>, _>("sent_currency")
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| "USD".to_string());
+        let amount_cents: i64 = r.try_get("amount_cents").unwrap_or(0);
+        let amount = r
+            .try_get::<Option<i64>, _>("sent_amount")
+            .ok()
+            .flatten()
+            .unwrap_or(amount_cents);
+        let amount = format_minor(amount, &currency);
+        let line = [
+            r.try_get::<String, _>("id").unwrap_or_default(),
+            r.try_get::<String, _>("username").unwrap_or_default(),
+            r.try_get::<Option<String>, _>("email")
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
+            r.try_get::<String, _>("destination").unwrap_or_default(),
+            currency,
+            amount,
+            format_minor(amount_cents, "USD"),
+            r.try_get::<Ts, _>("created_at")
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+        ]
+        .iter()
+        .map(|v| csv_field(v))
+        .collect::<Vec<_>>()
+        .join(",");
+        csv.push_str(&line);
+        csv.push('\n');
+    }
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"payouts.csv\"",
+            ),
+        ],
+        csv,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creator_share_follows_the_split_of_what_the_buyer_kept() {
+        // $1.99 at 90%.
+        assert_eq!(creator_share(199, 199, 0, 9000), 179);
+        // IDR invoice settled as $5.00, half refunded.
+        assert_eq!(creator_share(500, 80_000, 40_000, 9000), 225);
+        assert_eq!(creator_share(500, 80_000, 80_000, 9000), 0);
+        assert_eq!(creator_share(500, 0, 0, 9000), 0);
+    }
+
+    #[test]
+    fn retries_back_off_and_csv_cells_stay_inert() {
+        assert_eq!(retry_delay_minutes(1), 5);
+        assert_eq!(retry_delay_minutes(3), 20);
+        assert_eq!(retry_delay_minutes(30), MAX_RETRY_DELAY_MINUTES);
+        assert_eq!(csv_field("BCA 123 a/n Budi"), "BCA 123 a/n Budi");
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+        assert_eq!(csv_field("=HYPERLINK(1)"), "'=HYPERLINK(1)");
+    }
+
+    #[test]
+    fn idempotency_key_is_the_payout_id_until_a_rejection() {
+        assert_eq!(idempotency_key("po_1", 0), "po_1");
+        assert_eq!(idempotency_key("po_1", 2), "po_1-2");
+    }
+}
@@ -0,0 +1,587 @@
+// src/handlers/reconciliation.rs
+//
+// Reconciliation of pending fiat invoices.
+//
+// A lost webhook leaves a `fiat_invoices` row `pending` although the buyer
+// paid. The reconciler asks the provider (`PaymentPlugin::payment_status`)
+// about invoices pending longer than `RECONCILE_AFTER_MINUTES` and:
+//
+//   paid                      → granted through `apply_webhook_result`, as if
+//                               the webhook had arrived
+//   expired/failed/cancelled  → the invoice gets that status
+//   still pending or unknown  → left alone until `RECONCILE_EXPIRE_HOURS`,
+//                               then expired
+//
+// Anything an admin should look at — the lost webhook itself, an amount that
+// does not match, a grant that failed — is recorded in
+// `payment_discrepancies` once per invoice and kind, logged, and mailed to
+// admins. Admins list and resolve them under `/admin/reconciliation`.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use tower_cookies::Cookies;
+
+use crate::config::Config;
+use crate::email;
+use crate::handlers::admin::AdminState;
+use crate::handlers::payment_plugins::apply_webhook_result;
+use crate::plugins::payment::{
+    models::{PaymentStatus, PaymentStatusRequest},
+    PaymentPluginRegistry,
+};
+use crate::sessions;
+
+/// How often the reconciler wakes.
+const POLL_INTERVAL_SECS: u64 = 600;
+/// How many invoices are checked per pass.
+const BATCH_SIZE: i64 = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    MissedWebhook,
+    AmountMismatch,
+    ApplyFailed,
+    StatusMismatch,
+    NotFound,
+    LookupFailed,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::MissedWebhook => "missed_webhook",
+            Kind::AmountMismatch => "amount_mismatch",
+            Kind::ApplyFailed => "apply_failed",
+            Kind::StatusMismatch => "status_mismatch",
+            Kind::NotFound => "not_found",
+            Kind::LookupFailed => "lookup_failed",
+        }
+    }
+}
+
+/// What to do with a pending invoice given the provider's answer.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    /// Paid in full: grant it.
+    Grant,
+    /// Set this status on the invoice and, if given, report why.
+    Close(&'static str, Option<Kind>),
+    /// Keep it pending but report it.
+    Report(Kind),
+    /// Ask again on a later pass.
+    Wait,
+}
+
+/// `overdue` is true once the invoice is older than `RECONCILE_EXPIRE_HOURS`.
+/// Amounts are only compared when the provider reports one in the invoice
+/// currency.
+fn decide(
+    status: &PaymentStatus,
+    paid_amount: i64,
+    paid_currency: &str,
+    amount: i64,
+    currency: &str,
+    overdue: bool,
+) -> Action {
+    match status {
+        PaymentStatus::Paid => {
+            let short = paid_amount > 0
+                && paid_currency.eq_ignore_ascii_case(currency)
+                && paid_amount < amount;
+            if short {
+                Action::Close("underpaid", Some(Kind::AmountMismatch))
+            } else {
+                Action::Grant
+            }
+        }
+        PaymentStatus::Underpaid => Action::Close("underpaid", Some(Kind::AmountMismatch)),
+        PaymentStatus::Expired => Action::Close("expired", None),
+        PaymentStatus::Failed => Action::Close("failed", None),
+        PaymentStatus::Cancelled => Action::Close("cancelled", None),
+        PaymentStatus::Refunded | PaymentStatus::Chargeback => Action::Report(Kind::StatusMismatch),
+        PaymentStatus::Unknown if overdue => Action::Close("expired", Some(Kind::NotFound)),
+        PaymentStatus::Pending if overdue => Action::Close("expired", None),
+        PaymentStatus::Unknown | PaymentStatus::Pending => Action::Wait,
+    }
+}
+
+fn status_name(status: &PaymentStatus) -> String {
+    serde_json::to_value(status)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+struct PendingInvoice {
+    invoice_uid: String,
+    provider: String,
+    provider_ref: Option<String>,
+    amount: i64,
+    currency: String,
+    overdue: bool,
+}
+
+struct Finding<'a> {
+    kind: Kind,
+    provider_status: Option<String>,
+    reported_amount: Option<i64>,
+    detail: Option<&'a str>,
+}
+
+/// Totals of one pass, returned by the admin "run now" endpoint.
+#[derive(Default, serde::Serialize)]
+pub(crate) struct PassSummary {
+    checked: usize,
+    granted: usize,
+    closed: usize,
+    errors: usize,
+    discrepancies: Vec<Value>,
+}
+
+/// Spawns the background task that reconciles pending fiat invoices.
+pub fn start_reconcile_worker(pool: PgPool, cfg: Config) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(POLL_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_pass(&pool, &cfg).await {
+                tracing::error!("payment reconciler: {}", e);
+            }
+        }
+    });
+}
+
+/// Checks one batch of pending invoices, oldest unchecked first, and
+/// notifies admins of new discrepancies.
+pub(crate) async fn run_pass(pool: &PgPool, cfg: &Config) -> Result<PassSummary, sqlx::Error> {
+    let registry = PaymentPluginRegistry::from_all_env_known_with_pool(Some(pool.clone()));
+    let providers: Vec<String> = registry
+        .names()
+        .into_iter()
+        .filter(|name| {
+            registry
+                .get(name)
+                .is_some_and(|p| p.capability().supports_status_lookup)
+        })
+        .collect();
+
+    let rows = sqlx::query(
+        r#"
+        UPDATE fiat_invoices SET reconciled_at = NOW()
+        WHERE id IN (
+            SELECT id FROM fiat_invoices
+            WHERE status = 'pending' AND paid_at IS NULL
+              AND provider = ANY($1)
+              AND created_at < NOW() - make_interval(mins => $2)
+              AND (reconciled_at IS NULL OR reconciled_at < NOW() - make_interval(mins => $2))
+            ORDER BY reconciled_at ASC NULLS FIRST, created_at ASC
+            LIMIT $3
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING invoice_uid, provider, provider_ref, amount, currency,
+                  created_at < NOW() - make_interval(hours => $4) AS overdue
+        "#,
+    )
+    .bind(&providers)
+    .bind(cfg.reconcile_after_minutes)
+    .bind(BATCH_SIZE)
+    .bind(cfg.reconcile_expire_hours)
+    .fetch_all(pool)
+    .await?;
+
+    let mut summary = PassSummary::default();
+    for r in rows {
+        let inv = PendingInvoice {
+            invoice_uid: r.try_get("invoice_uid")?,
+            provider: r.try_get("provider")?,
+            provider_ref: r.try_get("provider_ref")?,
+            amount: r.try_get("amount")?,
+            currency: r.try_get("currency")?,
+            overdue: r.try_get("overdue")?,
+        };
+        summary.checked += 1;
+        reconcile_invoice(pool, &registry, &inv, &mut summary).await?;
+    }
+
+    if !summary.discrepancies.is_empty() {
+        notify_admins(pool, cfg, &summary.discrepancies).await;
+    }
+    Ok(summary)
+}
+
+async fn reconcile_invoice(
+    pool: &PgPool,
+    registry: &PaymentPluginRegistry,
+    inv: &PendingInvoice,
+    summary: &mut PassSummary,
+) -> Result<(), sqlx::Error> {
+    let Some(plugin) = registry.get(&inv.provider) else {
+        return Ok(());
+    };
+    let request = PaymentStatusRequest {
+        invoice_id: inv.invoice_uid.clone(),
+        payment_ref: inv.provider_ref.clone(),
+        currency: inv.currency.clone(),
+    };
+    let mut result = match plugin.payment_status(request).await {
+        Ok(result) => result,
+        Err(e) => {
+            summary.errors += 1;
+            tracing::warn!(
+                "reconciler: status lookup failed for uid={} ({}): {e}",
+                inv.invoice_uid,
+                inv.provider
+            );
+            if inv.overdue {
+                let detail = e.to_string();
+                let finding = Finding {
+                    kind: Kind::LookupFailed,
+                    provider_status: None,
+                    reported_amount: None,
+                    detail: Some(&detail),
+                };
+                report(pool, inv, finding, summary).await?;
+            }
+            return Ok(());
+        }
+    };
+    // The lookup is keyed by our invoice; providers may not echo it back.
+    result.invoice_id = inv.invoice_uid.clone();
+    let provider_status = status_name(&result.status);
+
+    let action = decide(
+        &result.status,
+        result.paid_amount_cents,
+        &result.currency,
+        inv.amount,
+        &inv.currency,
+        inv.overdue,
+    );
+    match action {
+        Action::Grant => match apply_webhook_result(pool, &inv.provider, &result).await {
+            Ok(outcome) => {
+                summary.granted += 1;
+                tracing::info!(
+                    "reconciler: uid={} paid at {} without a webhook",
+                    inv.invoice_uid,
+                    inv.provider
+                );
+                let detail = outcome.to_string();
+                let finding = Finding {
+                    kind: Kind::MissedWebhook,
+                    provider_status: Some(provider_status),
+                    reported_amount: Some(result.paid_amount_cents),
+                    detail: Some(&detail),
+                };
+                report(pool, inv, finding, summary).await?;
+            }
+            Err(e) => {
+                summary.errors += 1;
+                let finding = Finding {
+                    kind: Kind::ApplyFailed,
+                    provider_status: Some(provider_status),
+                    reported_amount: Some(result.paid_amount_cents),
+                    detail: Some(&e),
+                };
+                report(pool, inv, finding, summary).await?;
+            }
+        },
+        Action::Close(status, kind) => {
+            let closed = sqlx::query(
+                "UPDATE fiat_invoices SET status = $2
+                 WHERE invoice_uid = $1 AND status = 'pending' AND paid_at IS NULL",
+            )
+            .bind(&inv.invoice_uid)
+            .bind(status)
+            .execute(pool)
+            .await?
+            .rows_affected();
+            if closed > 0 {
+                summary.closed += 1;
+                tracing::info!(
+                    "reconciler: uid={} marked {status} ({} reports {provider_status})",
+                    inv.invoice_uid,
+                    inv.provider
+                );
+            }
+            if let Some(kind) = kind {
+                let finding = Finding {
+                    kind,
+                    provider_status: Some(provider_status),
+                    reported_amount: Some(result.paid_amount_cents),
+                    detail: None,
+                };
+                report(pool, inv, finding, summary).await?;
+            }
+        }
+        Action::Report(kind) => {
+            let finding = Finding {
+                kind,
+                provider_status: Some(provider_status),
+                reported_amount: Some(result.paid_amount_cents),
+                detail: None,
+            };
+            report(pool, inv, finding, summary).await?;
+        }
+        Action::Wait => {}
+    }
+    Ok(())
+}
+
+/// Records a discrepancy unless this invoice already has one of this kind.
+async fn report(
+    pool: &PgPool,
+    inv: &PendingInvoice,
+    finding: Finding<'_>,
+    summary: &mut PassSummary,
+) -> Result<(), sqlx::Error> {
+    let inserted: Option<i64> = sqlx::query_scalar(
+        r#"
+        INSERT INTO payment_discrepancies
+          (invoice_uid, provider, kind, local_status, provider_status,
+           expected_amount, reported_amount, currency, detail)
+        SELECT invoice_uid, provider, $2, status, $3, amount, $4, currency, $5
+        FROM fiat_invoices WHERE invoice_uid = $1
+        ON CONFLICT (invoice_uid, kind) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(&inv.invoice_uid)
+    .bind(finding.kind.as_str())
+    .bind(finding.provider_status.as_deref())
+    .bind(finding.reported_amount)
+    .bind(finding.detail)
+    .fetch_optional(pool)
+    .await?;
+    if let Some(id) = inserted {
+        tracing::warn!(
+            "reconciler: discrepancy {} for uid={} ({})",
+            finding.kind.as_str(),
+            inv.invoice_uid,
+            inv.provider
+        );
+        summary.discrepancies.push(json!({
+            "id": id,
+            "invoice_uid": inv.invoice_uid,
+            "provider": inv.provider,
+            "kind": finding.kind.as_str(),
+            "provider_status": finding.provider_status,
+            "expected_amount": inv.amount,
+            "reported_amount": finding.reported_amount,
+            "currency": inv.currency,
+        }));
+    }
+    Ok(())
+}
+
+async fn notify_admins(pool: &PgPool, cfg: &Config, discrepancies: &[Value]) {
+    let admins: Vec<String> = sqlx::query_scalar(
+        "SELECT email FROM users WHERE is_admin = 1 AND email IS NOT NULL AND email <> ''",
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap_or_default();
+    if admins.is_empty() {
+        return;
+    }
+    let items: String = discrepancies
+        .iter()
+        .map(|d| {
+            format!(
+                "<li>{} — invoice <code>{}</code> ({}): provider reports {}</li>",
+                d["kind"].as_str().unwrap_or_default(),
+                d["invoice_uid"].as_str().unwrap_or_default(),
+                d["provider"].as_str().unwrap_or_default(),
+                d["provider_status"].as_str().unwrap_or("no status"),
+            )
+        })
+        .collect();
+    for to in admins {
+        email::send_payment_discrepancies(pool, &to, discrepancies.len(), &items, &cfg.base_url)
+            .await;
+    }
+}
+
+// ─── Admin ───────────────────────────────────────────────────────────────────
+
+async fn admin_user(st: &AdminState, cookies: &Cookies) -> Result<String, Json<Value>> {
+    match sessions::current_user_id(&st.pool, &st.cfg, cookies).await {
+        Some((user_id, true)) => Ok(user_id),
+        Some(_) => Err(Json(json!({"ok": false, "error": "admin only"}))),
+        None => Err(Json(json!({"ok": false, "error": "not logged in"}))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DiscrepancyQuery {
+    /// Only unresolved discrepancies (default true).
+    pub open: Option<bool>,
+    pub limit: Option<i64>,
+}
+
+/// GET /admin/reconciliation?open=&limit=
+pub async fn admin_discrepancies(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Query(q): Query<DiscrepancyQuery>,
+) -> impl IntoResponse {
+    if let Err(resp) = admin_user(&st, &cookies).await {
+        return resp;
+    }
+    let rows = sqlx::query(
+        r#"
+        SELECT d.id, d.invoice_uid, d.provider, d.kind, d.local_status, d.provider_status,
+               d.expected_amount, d.reported_amount, d.currency, d.detail,
+               d.created_at, d.resolved_at, d.note, u.username AS resolved_by,
+               fi.status AS invoice_status
+        FROM payment_discrepancies d
+        JOIN fiat_invoices fi ON fi.invoice_uid = d.invoice_uid
+        LEFT JOIN users u ON u.id = d.resolved_by
+        WHERE (NOT $1 OR d.resolved_at IS NULL)
+        ORDER BY d.created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(q.open.unwrap_or(true))
+    .bind(q.limit.unwrap_or(100).clamp(1, 500))
+    .fetch_all(&st.pool)
+    .await;
+    let rows = match rows {
+        Ok(r) => r,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    type Ts = chrono::DateTime<chrono::Utc>;
+    let items: Vec<Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<i64, _>("id").unwrap_or(0),
+                "invoice_uid": r.try_get::<String, _>("invoice_uid").unwrap_or_default(),
+                "provider": r.try_get::<String, _>("provider").unwrap_or_default(),
+                "kind": r.try_get::<String, _>("kind").unwrap_or_default(),
+                "local_status": r.try_get::<String, _>("local_status").unwrap_or_default(),
+                "invoice_status": r.try_get::<String, _>("invoice_status").unwrap_or_default(),
+                "provider_status": r.try_get::<Option<String>, _>("provider_status").ok().flatten(),
+                "expected_amount": r.try_get::<Option<i64>, _>("expected_amount").ok().flatten(),
+                "reported_amount": r.try_get::<Option<i64>, _>("reported_amount").ok().flatten(),
+                "currency": r.try_get::<Option<String>, _>("currency").ok().flatten(),
+                "detail": r.try_get::<Option<String>, _>("detail").ok().flatten(),
+                "created_at": r.try_get::<Ts, _>("created_at").ok(),
+                "resolved_at": r.try_get::<Option<Ts>, _>("resolved_at").ok().flatten(),
+                "resolved_by": r.try_get::<Option<String>, _>("resolved_by").ok().flatten(),
+                "note": r.try_get::<Option<String>, _>("note").ok().flatten(),
+            })
+        })
+        .collect();
+    Json(json!({"ok": true, "items": items}))
+}
+
+/// POST /admin/reconciliation/run
+///
+/// Runs a reconciliation pass now instead of waiting for the worker.
+pub async fn admin_run_reconciliation(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+) -> impl IntoResponse {
+    let admin_user_id = match admin_user(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+    tracing::info!(
+        admin_user_id = %admin_user_id,
+        action = "run_reconciliation",
+        "admin action"
+    );
+    match run_pass(&st.pool, &st.cfg).await {
+        Ok(summary) => Json(json!({"ok": true, "summary": summary})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ResolvePayload {
+    #[serde(default)]
+    pub note: String,
+}
+
+/// POST /admin/reconciliation/:id/resolve
+pub async fn admin_resolve_discrepancy(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Path(id): Path<i64>,
+    Json(body): Json<ResolvePayload>,
+) -> impl IntoResponse {
+    let admin_user_id = match admin_user(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+    let note = body.note.trim();
+    let resolved = sqlx::query(
+        "UPDATE payment_discrepancies
+         SET resolved_at = NOW(), resolved_by = $2, note = NULLIF($3, '')
+         WHERE id = $1 AND resolved_at IS NULL",
+    )
+    .bind(id)
+    .bind(&admin_user_id)
+    .bind(note)
+    .execute(&st.pool)
+    .await;
+    match resolved {
+        Ok(r) if r.rows_affected() > 0 => {
+            tracing::info!(
+                admin_user_id = %admin_user_id,
+                action = "resolve_payment_discrepancy",
+                discrepancy = id,
+                "admin action"
+            );
+            Json(json!({"ok": true, "id": id}))
+        }
+        Ok(_) => Json(json!({"ok": false, "error": "discrepancy not found or already resolved"})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_payments_are_reported_instead_of_granted() {
+        let paid = PaymentStatus::Paid;
+        assert_eq!(decide(&paid, 199, "USD", 199, "USD", false), Action::Grant);
+        // No amount reported, or in another currency: trust the provider.
+        assert_eq!(decide(&paid, 0, "USD", 199, "USD", false), Action::Grant);
+        assert_eq!(decide(&paid, 150, "EUR", 199, "USD", false), Action::Grant);
+        assert_eq!(
+            decide(&paid, 150, "usd", 199, "USD", false),
+            Action::Close("underpaid", Some(Kind::AmountMismatch))
+        );
+    }
+
+    #[test]
+    fn unanswered_invoices_expire_only_when_overdue() {
+        for status in [PaymentStatus::Pending, PaymentStatus::Unknown] {
+            assert_eq!(decide(&status, 0, "USD", 199, "USD", false), Action::Wait);
+        }
+        assert_eq!(
+            decide(&PaymentStatus::Pending, 0, "USD", 199, "USD", true),
+            Action::Close("expired", None)
+        );
+        assert_eq!(
+            decide(&PaymentStatus::Unknown, 0, "USD", 199, "USD", true),
+            Action::Close("expired", Some(Kind::NotFound))
+        );
+        assert_eq!(
+            decide(&PaymentStatus::Chargeback, 199, "USD", 199, "USD", true),
+            Action::Report(Kind::StatusMismatch)
+        );
+    }
+}
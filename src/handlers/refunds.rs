@@ -0,0 +1,770 @@
+// src/handlers/refunds.rs
+//
+// Refunds and chargebacks of fiat plugin payments.
+//
+// A refund is recorded in `payment_refunds` either by an admin
+// (`POST /admin/payments/:uid/refund`, which calls the provider's `refund`) or
+// by a provider webhook reporting a refund or dispute. Provider refund ids make
+// the rows idempotent, so the admin call and the webhook that follows it end up
+// in the same row.
+//
+// Once a refund has succeeded it is applied exactly once (`applied_at`):
+//   * the invoice's `refunded_amount` grows and its status becomes
+//     `partially_refunded`, `refunded` or `charged_back`;
+//   * a full refund or a chargeback revokes the entitlements the invoice
+//     granted (purchase, rental, gift, bundle, subscription);
+//   * the refunded share is taken back from the creator — tips were credited to
+//     the wallet and disbursed sales were paid out in full, so both are
+//     debited; a scheduled payout only paid the creator share, so only the
+//     part of it the refund removes is debited; sales not disbursed yet
+//     simply pay out less (`admin_disburse`, handlers/payouts.rs). Refunds of a
+//     Stripe Connect charge reverse its transfer at Stripe instead; a
+//     chargeback of one debits the wallet for the part of the transfer the
+//     creator still holds, not the platform's application fee;
+//   * the same share of any affiliate commission goes back to the creator.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool, Row};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::entitlements::{self, Revoke};
+use crate::handlers::admin::AdminState;
+use crate::handlers::payouts;
+use crate::plugins::payment::{
+    models::{PaymentStatus, RefundNotice, RefundRequest, RefundStatus},
+    PaymentPluginRegistry,
+};
+use crate::sessions;
+
+/// Part of `total` that corresponds to `refunded` out of `amount`.
+///
+/// Each refund takes `refunded_share(after) - refunded_share(before)`, so
+/// partial refunds add up to exactly `total` once the whole amount is back.
+pub(crate) fn refunded_share(total: i64, refunded: i64, amount: i64) -> i64 {
+    if amount <= 0 {
+        return 0;
+    }
+    let refunded = refunded.clamp(0, amount);
+    ((total as i128) * (refunded as i128) / (amount as i128)) as i64
+}
+
+/// Creator share, in USD cents, that a refund from `before` to `after` takes
+/// out of an invoice a scheduled payout paid at `split_bp`.
+pub(crate) fn payout_reversal(
+    settled_cents: i64,
+    amount: i64,
+    before: i64,
+    after: i64,
+    split_bp: u16,
+) -> i64 {
+    payouts::creator_share(settled_cents, amount, before, split_bp)
+        - payouts::creator_share(settled_cents, amount, after, split_bp)
+}
+
+/// Part of a Connect transfer, in USD cents, that a chargeback from `before`
+/// to `after` leaves the platform owing. Stripe sent the creator `amount`
+/// minus `application_fee`; earlier refunds reversed their part of it.
+pub(crate) fn connect_reversal(
+    settled_cents: i64,
+    amount: i64,
+    application_fee: i64,
+    before: i64,
+    after: i64,
+) -> i64 {
+    if amount <= 0 {
+        return 0;
+    }
+    let transferred = (amount - application_fee).clamp(0, amount);
+    let transferred_cents =
+        ((settled_cents as i128) * (transferred as i128) / (amount as i128)) as i64;
+    refunded_share(transferred_cents, after, amount)
+        - refunded_share(transferred_cents, before, amount)
+}
+
+/// Result of applying one refund to its invoice.
+#[derive(Debug, Serialize)]
+pub(crate) struct AppliedRefund {
+    pub refund_id: String,
+    pub invoice_uid: String,
+    pub amount: i64,
+    pub usd_cents: i64,
+    pub invoice_status: String,
+    pub access_revoked: bool,
+    pub creator_reversed_cents: i64,
+    pub commission_reversed_cents: i64,
+}
+
+/// Records a refund or dispute reported by a provider webhook and applies it.
+///
+/// The invoice is found by our uid or, for events that only carry the
+/// provider's charge reference, by `charge_ref` / `provider_ref`. Returns
+/// `None` when the event was already applied or there is nothing left to
+/// refund.
+pub(crate) async fn record_provider_refund(
+    pool: &PgPool,
+    provider: &str,
+    invoice_uid: &str,
+    status: &PaymentStatus,
+    notice: &RefundNotice,
+) -> Result<Option<AppliedRefund>, String> {
+    if notice.refund_id.is_empty() {
+        return Err("refund event without id".to_string());
+    }
+    let kind = if *status == PaymentStatus::Chargeback {
+        "chargeback"
+    } else {
+        "refund"
+    };
+    let charge_ref = notice.payment_ref.as_deref().unwrap_or_default();
+
+    let invoice = sqlx::query(
+        r#"
+        SELECT invoice_uid, amount - refunded_amount AS remaining
+        FROM fiat_invoices
+        WHERE provider = $1
+          AND (invoice_uid = $2
+               OR ($3 <> '' AND (charge_ref = $3 OR provider_ref = $3)))
+        ORDER BY (invoice_uid = $2) DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(provider)
+    .bind(invoice_uid)
+    .bind(charge_ref)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?
+    .ok_or_else(|| "invoice not found".to_string())?;
+    let uid: String = invoice.try_get("invoice_uid").unwrap_or_default();
+    let remaining: i64 = invoice.try_get("remaining").unwrap_or(0);
+
+    // Providers that omit the amount refund whatever is left.
+    let amount = if notice.amount_cents > 0 {
+        notice.amount_cents
+    } else {
+        remaining
+    };
+    if amount <= 0 {
+        return Ok(None);
+    }
+
+    let refund_id: String = sqlx::query_scalar(
+        r#"
+        INSERT INTO payment_refunds
+          (id, invoice_uid, provider, provider_ref, kind, amount, status, reason)
+        VALUES ($1, $2, $3, $4, $5, $6, 'succeeded', $7)
+        ON CONFLICT (provider, provider_ref) WHERE provider_ref IS NOT NULL
+        DO UPDATE SET status = 'succeeded'
+        RETURNING id
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&uid)
+    .bind(provider)
+    .bind(&notice.refund_id)
+    .bind(kind)
+    .bind(amount)
+    .bind(notice.reason.as_deref().unwrap_or_default())
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?;
+
+    apply_refund(pool, &refund_id).await
+}
+
+/// Applies a succeeded refund to its invoice, once. See the module docs.
+pub(crate) async fn apply_refund(
+    pool: &PgPool,
+    refund_id: &str,
+) -> Result<Option<AppliedRefund>, String> {
+    let mut tx = pool.begin().await.map_err(|e| format!("begin tx: {e}"))?;
+
+    let Some(inv) = sqlx::query(
+        r#"
+        SELECT fi.invoice_uid, fi.provider, fi.user_id, fi.creator_id, fi.video_id,
+               fi.bundle_id, fi.subscription_id, fi.rental_hours, fi.gift_recipient_id,
+               fi.tip_id, fi.amount, fi.usd_cents, fi.refunded_amount,
+               fi.paid_at IS NOT NULL AS paid,
+               fi.disbursed_at IS NOT NULL AS disbursed, fi.payout_id IS NOT NULL AS paid_out,
+               fi.payout_split_bp,
+               fi.connect_account_id IS NOT NULL AS connected, fi.application_fee
+        FROM fiat_invoices fi
+        JOIN payment_refunds r ON r.invoice_uid = fi.invoice_uid
+        WHERE r.id = $1
+        FOR UPDATE OF fi
+        "#,
+    )
+    .bind(refund_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| format!("db: {e}"))?
+    else {
+        return Ok(None);
+    };
+
+    let Some(refund) = sqlx::query(
+        r#"
+        UPDATE payment_refunds SET applied_at = NOW()
+        WHERE id = $1 AND status = 'succeeded' AND applied_at IS NULL
+        RETURNING kind, amount
+        "#,
+    )
+    .bind(refund_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| format!("db: {e}"))?
+    else {
+        return Ok(None);
+    };
+
+    let uid: String = inv.try_get("invoice_uid").unwrap_or_default();
+    let provider: String = inv.try_get("provider").unwrap_or_default();
+    let buyer_id: String = inv.try_get("user_id").unwrap_or_default();
+    let creator_id: String = inv.try_get("creator_id").unwrap_or_default();
+    let amount: i64 = inv.try_get("amount").unwrap_or(0);
+    let before: i64 = inv.try_get("refunded_amount").unwrap_or(0);
+    let kind: String = refund.try_get("kind").unwrap_or_default();
+    let requested: i64 = refund.try_get("amount").unwrap_or(0);
+
+    let applied = requested.min(amount - before);
+    if !inv.try_get::<bool, _>("paid").unwrap_or(false) || applied <= 0 {
+        tx.commit().await.map_err(|e| format!("commit: {e}"))?;
+        return Ok(None);
+    }
+    let after = before + applied;
+    // Ledgers are in USD; older invoices were always USD.
+    let settled = inv
+        .try_get::<Option<i64>, _>("usd_cents")
+        .ok()
+        .flatten()
+        .unwrap_or(amount);
+    let usd_cents =
+        refunded_share(settled, after, amount) - refunded_share(settled, before, amount);
+
+    let full = kind == "chargeback" || after >= amount;
+    let invoice_status = if kind == "chargeback" {
+        "charged_back"
+    } else if after >= amount {
+        "refunded"
+    } else {
+        "partially_refunded"
+    };
+    sqlx::query(
+        "UPDATE fiat_invoices SET refunded_amount = $2, status = $3 WHERE invoice_uid = $1",
+    )
+    .bind(&uid)
+    .bind(after)
+    .bind(invoice_status)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("db invoice: {e}"))?;
+
+    if full {
+        revoke_invoice_grant(&mut tx, &inv, &kind)
+            .await
+            .map_err(|e| format!("revoke: {e}"))?;
+    }
+
+    // Creator share: tips were credited to the wallet, other items were paid
+    // out by disbursement or a payout. Undisbursed sales pay out less instead,
+    // a Connect refund already took the share back from the creator's
+    // connected account, and a Connect chargeback takes back what is left of
+    // the transfer.
+    let tip_id: Option<String> = inv.try_get("tip_id").ok().flatten();
+    let creator_reversed = match tip_id.as_deref() {
+        Some(tip_id) => {
+            let creator_cents: i64 =
+                sqlx::query_scalar("SELECT creator_cents FROM tips WHERE id = $1")
+                    .bind(tip_id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(|e| format!("db tip: {e}"))?
+                    .unwrap_or(0);
+            refunded_share(creator_cents, after, amount)
+                - refunded_share(creator_cents, before, amount)
+        }
+        None if kind == "refund" && inv.try_get::<bool, _>("connected").unwrap_or(false) => 0,
+        None if inv.try_get::<bool, _>("connected").unwrap_or(false) => {
+            let fee: Option<i64> = inv.try_get("application_fee").ok().flatten();
+            connect_reversal(settled, amount, fee.unwrap_or(0), before, after)
+        }
+        None if inv.try_get::<bool, _>("paid_out").unwrap_or(false) => {
+            let split_bp: Option<i32> = inv.try_get("payout_split_bp").ok().flatten();
+            payout_reversal(
+                settled,
+                amount,
+                before,
+                after,
+                split_bp.unwrap_or(10_000).clamp(0, 10_000) as u16,
+            )
+        }
+        None if inv.try_get::<bool, _>("disbursed").unwrap_or(false) => usd_cents,
+        None => 0,
+    };
+    if creator_reversed > 0 {
+        wallet_move(
+            &mut tx,
+            &creator_id,
+            -creator_reversed,
+            &buyer_id,
+            &format!("Refund reversal ({provider}): {uid}"),
+        )
+        .await?;
+    }
+
+    let commission_reversed = reverse_commissions(&mut tx, &uid, after, amount).await?;
+
+    sqlx::query(
+        "UPDATE payment_refunds SET usd_cents = $2, creator_reversed_cents = $3, \
+         commission_reversed_cents = $4 WHERE id = $1",
+    )
+    .bind(refund_id)
+    .bind(usd_cents)
+    .bind(creator_reversed)
+    .bind(commission_reversed)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("db: {e}"))?;
+
+    tx.commit().await.map_err(|e| format!("commit: {e}"))?;
+
+    tracing::info!(
+        "payment refund applied: provider={provider} uid={uid} refund={refund_id} kind={kind} \
+         amount={applied} usd_cents={usd_cents} status={invoice_status}"
+    );
+    Ok(Some(AppliedRefund {
+        refund_id: refund_id.to_string(),
+        invoice_uid: uid,
+        amount: applied,
+        usd_cents,
+        invoice_status: invoice_status.to_string(),
+        access_revoked: full,
+        creator_reversed_cents: creator_reversed,
+        commission_reversed_cents: commission_reversed,
+    }))
+}
+
+/// Takes back what the invoice granted. Everything paid through a plugin is
+/// granted under the invoice uid; a subscription also ends.
+async fn revoke_invoice_grant(
+    conn: &mut PgConnection,
+    inv: &sqlx::postgres::PgRow,
+    kind: &str,
+) -> Result<(), sqlx::Error> {
+    let uid: String = inv.try_get("invoice_uid")?;
+    entitlements::revoke(conn, Revoke::Reference(&uid), kind).await?;
+
+    if let Some(subscription_id) = inv.try_get::<Option<String>, _>("subscription_id")? {
+        sqlx::query(
+            r#"
+            UPDATE subscriptions
+            SET status = 'expired', current_period_end = LEAST(current_period_end, NOW()),
+                grace_until = NULL, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(&subscription_id)
+        .execute(&mut *conn)
+        .await?;
+        entitlements::revoke(conn, Revoke::Subscription(&subscription_id), kind).await?;
+    }
+    Ok(())
+}
+
+/// Returns the refunded share of the invoice's affiliate commissions to the
+/// creator. Returns the total moved.
+pub(crate) async fn reverse_commissions(
+    conn: &mut PgConnection,
+    uid: &str,
+    refunded: i64,
+    amount: i64,
+) -> Result<i64, String> {
+    let rows = sqlx::query(
+        "SELECT id, affiliate_id, owner_id, commission_cents, reversed_cents \
+         FROM affiliate_commissions WHERE ref_invoice_uid = $1 FOR UPDATE",
+    )
+    .bind(uid)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("db commissions: {e}"))?;
+
+    let mut total = 0;
+    for row in rows {
+        let id: i64 = row.try_get("id").unwrap_or_default();
+        let affiliate_id: String = row.try_get("affiliate_id").unwrap_or_default();
+        let owner_id: String = row.try_get("owner_id").unwrap_or_default();
+        let commission: i64 = row.try_get("commission_cents").unwrap_or(0);
+        let reversed: i64 = row.try_get("reversed_cents").unwrap_or(0);
+
+        let back = (refunded_share(commission, refunded, amount) - reversed)
+            .clamp(0, commission - reversed);
+        if back == 0 {
+            continue;
+        }
+        let note = format!("Affiliate commission reversed (refund): {uid}");
+        wallet_move(conn, &affiliate_id, -back, &owner_id, &note).await?;
+        wallet_move(conn, &owner_id, back, &affiliate_id, &note).await?;
+        sqlx::query(
+            "UPDATE affiliate_commissions SET reversed_cents = reversed_cents + $2 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(back)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("db commission: {e}"))?;
+        total += back;
+    }
+    Ok(total)
+}
+
+/// Adds `delta_cents` to a wallet (negative debits it, possibly below zero)
+/// and appends the ledger row.
+async fn wallet_move(
+    conn: &mut PgConnection,
+    user_id: &str,
+    delta_cents: i64,
+    ref_user_id: &str,
+    note: &str,
+) -> Result<(), String> {
+    let balance: i64 = sqlx::query_scalar(
+        "UPDATE users SET balance_cents = balance_cents + $1 WHERE id = $2 RETURNING balance_cents",
+    )
+    .bind(delta_cents)
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| format!("db wallet: {e}"))?;
+
+    let txn_type = if delta_cents < 0 {
+        "transfer_out"
+    } else {
+        "transfer_in"
+    };
+    sqlx::query(
+        "INSERT INTO wallet_transactions (user_id,txn_type,amount_cents,balance_after,status,ref_user_id,note) \
+         VALUES ($1,$2,$3,$4,'completed',$5,$6)",
+    )
+    .bind(user_id)
+    .bind(txn_type)
+    .bind(delta_cents.abs())
+    .bind(balance)
+    .bind(ref_user_id)
+    .bind(note)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| format!("ledger: {e}"))?;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Admin
+// ---------------------------------------------------------------------------
+
+async fn admin_user(st: &AdminState, cookies: &Cookies) -> Result<String, Json<serde_json::Value>> {
+    match sessions::current_user_id(&st.pool, &st.cfg, cookies).await {
+        Some((user_id, true)) => Ok(user_id),
+        Some(_) => Err(Json(json!({"ok": false, "error": "admin only"}))),
+        None => Err(Json(json!({"ok": false, "error": "not logged in"}))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RefundPayload {
+    /// Charge-currency minor units; defaults to everything not yet refunded.
+    pub amount: Option<i64>,
+    pub reason: Option<String>,
+}
+
+/// POST /admin/payments/:uid/refund
+///
+/// Refunds all or part of a paid invoice through its provider.
+pub async fn admin_refund(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Path(uid): Path<String>,
+    Json(p): Json<RefundPayload>,
+) -> impl IntoResponse {
+    let admin_user_id = match admin_user(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    let inv = sqlx::query(
+        r#"
+        SELECT fi.provider, fi.currency, fi.status, fi.paid_at IS NOT NULL AS paid,
+               COALESCE(fi.charge_ref, fi.provider_ref) AS charge_ref,
+               fi.connect_account_id IS NOT NULL AS connected,
+               fi.amount - fi.refunded_amount - COALESCE(
+                 (SELECT SUM(r.amount) FROM payment_refunds r
+                  WHERE r.invoice_uid = fi.invoice_uid AND r.status = 'pending'), 0)::BIGINT
+                 AS refundable
+        FROM fiat_invoices fi
+        WHERE fi.invoice_uid = $1
+        "#,
+    )
+    .bind(&uid)
+    .fetch_optional(&st.pool)
+    .await;
+    let inv = match inv {
+        Ok(Some(r)) => r,
+        Ok(None) => return Json(json!({"ok": false, "error": "invoice not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let provider: String = inv.try_get("provider").unwrap_or_default();
+    let status: String = inv.try_get("status").unwrap_or_default();
+    let refundable: i64 = inv.try_get("refundable").unwrap_or(0);
+    if !inv.try_get::<bool, _>("paid").unwrap_or(false)
+        || !matches!(status.as_str(), "paid" | "partially_refunded")
+    {
+        return Json(json!({"ok": false, "error": "invoice is not refundable"}));
+    }
+    let amount = p.amount.unwrap_or(refundable);
+    if amount <= 0 || amount > refundable {
+        return Json(json!({
+            "ok": false,
+            "error": format!("amount must be between 1 and {refundable}")
+        }));
+    }
+    let Some(charge_ref) = inv
+        .try_get::<Option<String>, _>("charge_ref")
+        .ok()
+        .flatten()
+        .filter(|r| !r.is_empty())
+    else {
+        return Json(json!({"ok": false, "error": "invoice has no provider reference"}));
+    };
+
+    let registry = PaymentPluginRegistry::from_all_env_known_with_pool(Some(st.pool.clone()));
+    let Some(plugin) = registry.get(&provider) else {
+        return Json(
+            json!({"ok": false, "error": format!("payment provider not found: {provider}")}),
+        );
+    };
+    if !plugin.capability().supports_refunds {
+        return Json(json!({"ok": false, "error": format!("{provider} does not support refunds")}));
+    }
+
+    let reason = p.reason.unwrap_or_default().trim().to_string();
+    let refund_id = Uuid::new_v4().to_string();
+    if let Err(e) = sqlx::query(
+        "INSERT INTO payment_refunds (id, invoice_uid, provider, kind, amount, reason, requested_by) \
+         VALUES ($1, $2, $3, 'refund', $4, $5, $6)",
+    )
+    .bind(&refund_id)
+    .bind(&uid)
+    .bind(&provider)
+    .bind(amount)
+    .bind(&reason)
+    .bind(&admin_user_id)
+    .execute(&st.pool)
+    .await
+    {
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+
+    let result = plugin
+        .refund(RefundRequest {
+            invoice_id: uid.clone(),
+            payment_ref: charge_ref,
+            amount_cents: amount,
+            currency: inv.try_get("currency").unwrap_or_else(|_| "USD".into()),
+            reason: (!reason.is_empty()).then_some(reason),
+            reverse_transfer: inv.try_get("connected").unwrap_or(false),
+        })
+        .await;
+    let result = match result {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = sqlx::query("UPDATE payment_refunds SET status = 'failed' WHERE id = $1")
+                .bind(&refund_id)
+                .execute(&st.pool)
+                .await;
+            tracing::warn!(admin_user_id = %admin_user_id, action = "admin_refund_failed", provider = %provider, invoice_uid = %uid, amount, error = %e, "admin refund failed");
+            return Json(json!({"ok": false, "error": e.to_string()}));
+        }
+    };
+
+    // The provider's webhook may have recorded this refund already; keep
+    // that row and drop ours.
+    let updated = sqlx::query(
+        r#"
+        UPDATE payment_refunds SET provider_ref = $2, status = $3
+        WHERE id = $1
+          AND NOT EXISTS (SELECT 1 FROM payment_refunds
+                          WHERE provider = $4 AND provider_ref = $2)
+        "#,
+    )
+    .bind(&refund_id)
+    .bind(result.refund_id.as_deref())
+    .bind(result.status.as_str())
+    .bind(&provider)
+    .execute(&st.pool)
+    .await
+    .map(|r| r.rows_affected())
+    .unwrap_or(0);
+    let refund_id = if updated == 0 {
+        let _ = sqlx::query("DELETE FROM payment_refunds WHERE id = $1")
+            .bind(&refund_id)
+            .execute(&st.pool)
+            .await;
+        sqlx::query_scalar::<_, String>(
+            "SELECT id FROM payment_refunds WHERE provider = $1 AND provider_ref = $2",
+        )
+        .bind(&provider)
+        .bind(result.refund_id.as_deref())
+        .fetch_optional(&st.pool)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(refund_id)
+    } else {
+        refund_id
+    };
+
+    let applied = if result.status == RefundStatus::Succeeded {
+        match apply_refund(&st.pool, &refund_id).await {
+            Ok(a) => a,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        }
+    } else {
+        None
+    };
+
+    tracing::info!(
+        admin_user_id = %admin_user_id,
+        action = "admin_refund",
+        provider = %provider,
+        invoice_uid = %uid,
+        amount,
+        refund_status = result.status.as_str(),
+        "admin action"
+    );
+
+    Json(json!({
+        "ok": true,
+        "refund_id": refund_id,
+        "provider_refund_id": result.refund_id,
+        "status": result.status.as_str(),
+        "applied": applied,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct RefundListQuery {
+    pub invoice_uid: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// GET /admin/refunds?invoice_uid=&limit=
+pub async fn admin_refunds(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Query(q): Query<RefundListQuery>,
+) -> impl IntoResponse {
+    if let Err(resp) = admin_user(&st, &cookies).await {
+        return resp;
+    }
+    let rows = sqlx::query(
+        r#"
+        SELECT id, invoice_uid, provider, provider_ref, kind, amount, usd_cents,
+               creator_reversed_cents, commission_reversed_cents, status, reason,
+               requested_by, created_at, applied_at
+        FROM payment_refunds
+        WHERE ($1::TEXT IS NULL OR invoice_uid = $1)
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(q.invoice_uid.as_deref())
+    .bind(q.limit.unwrap_or(100).clamp(1, 500))
+    .fetch_all(&st.pool)
+    .await;
+    let rows = match rows {
+        Ok(r) => r,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let items: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<String, _>("id").unwrap_or_default(),
+                "invoice_uid": r.try_get::<String, _>("invoice_uid").unwrap_or_default(),
+                "provider": r.try_get::<String, _>("provider").unwrap_or_default(),
+                "provider_ref": r.try_get::<Option<String>, _>("provider_ref").ok().flatten(),
+                "kind": r.try_get::<String, _>("kind").unwrap_or_default(),
+                "amount": r.try_get::<i64, _>("amount").unwrap_or(0),
+                "usd_cents": r.try_get::<i64, _>("usd_cents").unwrap_or(0),
+                "creator_reversed_cents": r.try_get::<i64, _>("creator_reversed_cents").unwrap_or(0),
+                "commission_reversed_cents": r.try_get::<i64, _>("commission_reversed_cents").unwrap_or(0),
+                "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                "reason": r.try_get::<String, _>("reason").unwrap_or_default(),
+                "requested_by": r.try_get::<Option<String>, _>("requested_by").ok().flatten(),
+                "created_at": r.try_get::<chrono::DateTime<chrono::Utc>, _>("created_at").ok(),
+                "applied_at": r.try_get::<Option<chrono::DateTime<chrono::Utc>>, _>("applied_at").ok().flatten(),
+            })
+        })
+        .collect();
+    Json(json!({"ok": true, "items": items}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_refunds_add_up_to_the_whole_share() {
+        // 3 refunds of a 1000-unit charge settled as 333 USD cents.
+        let mut taken = 0;
+        let mut before = 0;
+        for after in [100, 450, 1000] {
+            taken += refunded_share(333, after, 1000) - refunded_share(333, before, 1000);
+            before = after;
+        }
+        assert_eq!(taken, 333);
+        assert_eq!(refunded_share(333, 450, 1000), 149);
+        assert_eq!(refunded_share(500, 2000, 1000), 500);
+        assert_eq!(refunded_share(500, 10, 0), 0);
+    }
+
+    #[test]
+    fn paid_out_refunds_take_back_only_the_creator_share() {
+        // $10.00 paid out at 90%: a full refund takes back the $9.00 sent.
+        assert_eq!(payout_reversal(1000, 1000, 0, 1000, 9000), 900);
+        // Partial refunds of an IDR invoice settled as $5.00 add up to the
+        // share the payout sent, never to the platform fee.
+        let sent = payouts::creator_share(500, 80_000, 0, 9000);
+        let mut taken = 0;
+        let mut before = 0;
+        for after in [10_000, 30_001, 80_000] {
+            taken += payout_reversal(500, 80_000, before, after, 9000);
+            before = after;
+        }
+        assert_eq!(taken, sent);
+        assert_eq!(payout_reversal(500, 80_000, 0, 40_000, 9000), 225);
+        // a payout taken after a partial refund only sent what was kept
+        let sent = payouts::creator_share(500, 80_000, 40_000, 9000);
+        assert_eq!(payout_reversal(500, 80_000, 40_000, 80_000, 9000), sent);
+    }
+
+    #[test]
+    fn connect_chargebacks_take_back_only_the_transfer() {
+        // $10.00 with a $1.00 application fee: Stripe sent the creator $9.00.
+        assert_eq!(connect_reversal(1000, 1000, 100, 0, 1000), 900);
+        // Half was refunded with its transfer reversed; the dispute of the rest
+        // leaves the other half of the transfer.
+        assert_eq!(connect_reversal(1000, 1000, 100, 500, 1000), 450);
+        // JPY 1500 settled as $10.00, fee JPY 150.
+        assert_eq!(connect_reversal(1000, 1500, 150, 0, 1500), 900);
+        assert_eq!(connect_reversal(1000, 1000, 0, 0, 1000), 1000);
+        assert_eq!(connect_reversal(1000, 0, 0, 0, 0), 0);
+    }
+}
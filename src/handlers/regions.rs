@@ -0,0 +1,351 @@
+// src/handlers/regions.rs
+//
+// Regional prices and geo-restrictions per video.
+//
+// A creator may override the purchase and rental price for single countries
+// (`video_region_prices`, in the video's currency) and restrict distribution
+// with allow / deny lists (`video_geo_rules`). With any `allow` entry the video
+// is only available in the listed countries, and buyers whose country is
+// unknown are refused; a `deny` entry blocks one country.
+//
+// The buyer's country comes from the offline GeoIP database (`crate::geo`).
+// Checkout (`wallet_pay_video`, `x402_start`, `create_invoice_with_provider`,
+// `send_gift`) and the pay options call [`regional_terms`] before pricing;
+// `request_play` calls it too, so a purchase made elsewhere does not play in a
+// blocked country.
+
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgPool, Row};
+use tower_cookies::Cookies;
+
+use crate::geo::{self, ClientCountry};
+use crate::handlers::video::VideoState;
+use crate::sessions;
+
+/// Error returned when the buyer's country may not buy or play a video.
+pub const REGION_BLOCKED: &str = "this video is not available in your region";
+const MAX_REGION_ENTRIES: usize = 250;
+
+/// Prices that apply to one buyer's country.
+#[derive(Debug, Default)]
+pub(crate) struct RegionalTerms {
+    pub price_cents: Option<i64>,
+    pub rental_price_cents: Option<i64>,
+}
+
+impl RegionalTerms {
+    /// Purchase price for this country, or the listed one.
+    pub fn price(&self, listed_cents: i64) -> i64 {
+        self.price_cents.unwrap_or(listed_cents)
+    }
+
+    /// Rental price for this country, or the listed one.
+    pub fn rental_price(&self, listed_cents: i64) -> i64 {
+        self.rental_price_cents.unwrap_or(listed_cents)
+    }
+}
+
+/// Whether the allow / deny lists admit `country`.
+pub(crate) fn region_permits(allow: &[String], deny: &[String], country: Option<&str>) -> bool {
+    match country {
+        Some(c) => {
+            !deny.iter().any(|d| d == c) && (allow.is_empty() || allow.iter().any(|a| a == c))
+        }
+        None => allow.is_empty(),
+    }
+}
+
+/// Regional terms of a video for `country`, or [`REGION_BLOCKED`].
+pub(crate) async fn regional_terms(
+    pool: &PgPool,
+    video_id: &str,
+    country: Option<&str>,
+) -> Result<RegionalTerms, String> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+          COALESCE(array_agg(country) FILTER (WHERE rule = 'allow'), '{}') AS allow,
+          COALESCE(array_agg(country) FILTER (WHERE rule = 'deny'), '{}') AS deny
+        FROM video_geo_rules
+        WHERE video_id = $1
+        "#,
+    )
+    .bind(video_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?;
+
+    let allow: Vec<String> = row.try_get("allow").unwrap_or_default();
+    let deny: Vec<String> = row.try_get("deny").unwrap_or_default();
+    if !region_permits(&allow, &deny, country) {
+        return Err(REGION_BLOCKED.to_string());
+    }
+
+    let Some(country) = country else {
+        return Ok(RegionalTerms::default());
+    };
+    let row = sqlx::query(
+        "SELECT price_cents, rental_price_cents FROM video_region_prices \
+         WHERE video_id = $1 AND country = $2",
+    )
+    .bind(video_id)
+    .bind(country)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?;
+
+    Ok(row
+        .map(|r| RegionalTerms {
+            price_cents: r.try_get("price_cents").ok(),
+            rental_price_cents: r.try_get("rental_price_cents").ok().flatten(),
+        })
+        .unwrap_or_default())
+}
+
+/// Checks that `uid` may edit the video's regions and returns its currency.
+async fn owned_video(
+    pool: &PgPool,
+    video_id: &str,
+    uid: &str,
+    is_admin: bool,
+) -> Result<String, String> {
+    let row = sqlx::query("SELECT owner_id, currency FROM videos WHERE id = $1")
+        .bind(video_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("db: {e}"))?
+        .ok_or_else(|| "video not found".to_string())?;
+    let owner_id: String = row.try_get("owner_id").unwrap_or_default();
+    if owner_id != uid && !is_admin {
+        return Err("not your video".to_string());
+    }
+    Ok(row.try_get("currency").unwrap_or_else(|_| "USD".into()))
+}
+
+#[derive(Deserialize)]
+pub struct RegionsQuery {
+    pub video_id: String,
+}
+
+/// `GET /api/video_regions?video_id=` — regional prices and geo rules of a
+/// video (its creator or an admin).
+pub async fn get_video_regions(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    country: ClientCountry,
+    Query(q): Query<RegionsQuery>,
+) -> impl IntoResponse {
+    let (uid, is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let currency = match owned_video(&st.pool, &q.video_id, &uid, is_admin).await {
+        Ok(c) => c,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+
+    let prices = sqlx::query(
+        "SELECT country, price_cents, rental_price_cents FROM video_region_prices \
+         WHERE video_id = $1 ORDER BY country",
+    )
+    .bind(&q.video_id)
+    .fetch_all(&st.pool)
+    .await
+    .unwrap_or_default();
+    let rules = sqlx::query(
+        "SELECT country, rule FROM video_geo_rules WHERE video_id = $1 ORDER BY country",
+    )
+    .bind(&q.video_id)
+    .fetch_all(&st.pool)
+    .await
+    .unwrap_or_default();
+
+    let list = |rule: &str| -> Vec<String> {
+        rules
+            .iter()
+            .filter(|r| r.try_get::<String, _>("rule").is_ok_and(|v| v == rule))
+            .filter_map(|r| r.try_get("country").ok())
+            .collect()
+    };
+
+    Json(json!({
+        "ok": true,
+        "video_id": q.video_id,
+        "currency": currency,
+        "prices": prices.iter().map(|r| json!({
+            "country": r.try_get::<String, _>("country").unwrap_or_default(),
+            "price_cents": r.try_get::<i64, _>("price_cents").unwrap_or(0),
+            "rental_price_cents": r.try_get::<Option<i64>, _>("rental_price_cents").ok().flatten(),
+        })).collect::<Vec<_>>(),
+        "allow": list("allow"),
+        "deny": list("deny"),
+        "your_country": country.0,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct RegionPricePayload {
+    pub country: String,
+    pub price_cents: i64,
+    #[serde(default)]
+    pub rental_price_cents: Option<i64>,
+}
+
+/// Full replacement of a video's regional settings.
+#[derive(Deserialize)]
+pub struct RegionsPayload {
+    pub video_id: String,
+    #[serde(default)]
+    pub prices: Vec<RegionPricePayload>,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+fn country_list(raw: &[String]) -> Result<Vec<String>, String> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for c in raw {
+        let code = geo::normalize_country(c)?;
+        if seen.insert(code.clone()) {
+            out.push(code);
+        }
+    }
+    Ok(out)
+}
+
+/// `POST /api/video_regions` — replaces the regional prices and allow / deny
+/// lists of a video.
+pub async fn set_video_regions(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<RegionsPayload>,
+) -> impl IntoResponse {
+    let (uid, is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    if let Err(e) = owned_video(&st.pool, &p.video_id, &uid, is_admin).await {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    if p.prices.len() + p.allow.len() + p.deny.len() > MAX_REGION_ENTRIES {
+        return Json(json!({
+            "ok": false,
+            "error": format!("at most {MAX_REGION_ENTRIES} regional entries per video")
+        }));
+    }
+    let (allow, deny) = match (country_list(&p.allow), country_list(&p.deny)) {
+        (Ok(a), Ok(d)) => (a, d),
+        (Err(e), _) | (_, Err(e)) => return Json(json!({"ok": false, "error": e})),
+    };
+    if let Some(both) = allow.iter().find(|c| deny.contains(c)) {
+        return Json(json!({"ok": false, "error": format!("{both} is both allowed and denied")}));
+    }
+
+    let mut prices = Vec::new();
+    let mut seen = HashSet::new();
+    for entry in &p.prices {
+        let country = match geo::normalize_country(&entry.country) {
+            Ok(c) => c,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        };
+        if !seen.insert(country.clone()) {
+            return Json(json!({"ok": false, "error": format!("duplicate price for {country}")}));
+        }
+        if entry.price_cents <= 0 || entry.rental_price_cents.is_some_and(|c| c <= 0) {
+            return Json(json!({"ok": false, "error": "regional prices must be positive"}));
+        }
+        prices.push((country, entry.price_cents, entry.rental_price_cents));
+    }
+
+    let mut tx = match st.pool.begin().await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": format!("begin tx: {e}")})),
+    };
+    let result: Result<(), sqlx::Error> = async {
+        sqlx::query("DELETE FROM video_region_prices WHERE video_id = $1")
+            .bind(&p.video_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM video_geo_rules WHERE video_id = $1")
+            .bind(&p.video_id)
+            .execute(&mut *tx)
+            .await?;
+        for (country, price_cents, rental_price_cents) in &prices {
+            sqlx::query(
+                "INSERT INTO video_region_prices (video_id, country, price_cents, rental_price_cents) \
+                 VALUES ($1, $2, $3, $4)",
+            )
+            .bind(&p.video_id)
+            .bind(country)
+            .bind(price_cents)
+            .bind(rental_price_cents)
+            .execute(&mut *tx)
+            .await?;
+        }
+        for (rule, countries) in [("allow", &allow), ("deny", &deny)] {
+            for country in countries {
+                sqlx::query(
+                    "INSERT INTO video_geo_rules (video_id, country, rule) VALUES ($1, $2, $3)",
+                )
+                .bind(&p.video_id)
+                .bind(country)
+                .bind(rule)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+    if let Err(e) = tx.commit().await {
+        return Json(json!({"ok": false, "error": format!("commit: {e}")}));
+    }
+
+    if is_admin {
+        tracing::info!(
+            admin_user_id = %uid,
+            action = "set_video_regions",
+            video_id = %p.video_id,
+            "admin action"
+        );
+    }
+
+    Json(json!({
+        "ok": true,
+        "video_id": p.video_id,
+        "prices": prices.len(),
+        "allow": allow,
+        "deny": deny,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_list_excludes_unknown_countries_and_deny_wins() {
+        let allow = vec!["ID".to_string(), "MY".to_string()];
+        let deny = vec!["US".to_string()];
+        assert!(region_permits(&allow, &[], Some("ID")));
+        assert!(!region_permits(&allow, &[], Some("SG")));
+        assert!(!region_permits(&allow, &[], None));
+        assert!(region_permits(&[], &deny, None));
+        assert!(region_permits(&[], &deny, Some("SG")));
+        assert!(!region_permits(&[], &deny, Some("US")));
+    }
+}
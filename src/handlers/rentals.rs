@@ -0,0 +1,222 @@
+// src/handlers/rentals.rs
+//
+// Time-limited rentals next to permanent purchases.
+//
+// Creators opt in per video with `rental_price_cents` and a viewing window
+// (`rental_hours`, 48 by default). A rental is an entitlement with
+// `rental_hours` set: at purchase time its `valid_until` is the last moment
+// the rental can be started (`RENTAL_START_DAYS` out), and the first playback
+// starts the window, moving `valid_until` to at most `rental_hours` later.
+// The sale is also recorded in `purchases` with the same `rental_hours`.
+//
+// `user_has_view_access` lets any permanent entitlement win over a rental,
+// and `request_play` caps playback sessions at the rental expiry.
+
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool, Row};
+use tower_cookies::Cookies;
+
+use crate::entitlements::{self, Origin};
+use crate::handlers::video::VideoState;
+use crate::sessions;
+
+/// Days a buyer has to start watching a rental after paying for it.
+pub const RENTAL_START_DAYS: i32 = 30;
+/// Accepted range for a video's rental window.
+pub const RENTAL_HOURS_RANGE: std::ops::RangeInclusive<i32> = 1..=720;
+
+/// Rental terms of a video.
+pub(crate) struct RentalOffer {
+    pub price_cents: i64,
+    pub hours: i32,
+}
+
+/// Rental terms, or `None` when the video cannot be rented.
+pub(crate) async fn load_rental_offer(
+    pool: &PgPool,
+    video_id: &str,
+) -> Result<Option<RentalOffer>, sqlx::Error> {
+    let row =
+        sqlx::query("SELECT rental_price_cents, rental_hours FROM videos WHERE id = $1 LIMIT 1")
+            .bind(video_id)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(row.and_then(|r| {
+        let price_cents: Option<i64> = r.try_get("rental_price_cents").ok().flatten();
+        price_cents.map(|price_cents| RentalOffer {
+            price_cents,
+            hours: r.try_get("rental_hours").unwrap_or(48),
+        })
+    }))
+}
+
+/// Checks that `user_id` may rent the video: not already owned outright and
+/// no rental still running.
+pub(crate) async fn check_rentable(
+    pool: &PgPool,
+    video_id: &str,
+    user_id: &str,
+) -> Result<(), String> {
+    if entitlements::owns_video(pool, video_id, user_id)
+        .await
+        .map_err(|e| format!("db: {e}"))?
+    {
+        return Err("already purchased".to_string());
+    }
+    if rental_access_until(pool, video_id, user_id)
+        .await
+        .map_err(|e| format!("db: {e}"))?
+        .is_some()
+    {
+        return Err("rental still active".to_string());
+    }
+    Ok(())
+}
+
+/// Records a rental and returns the deadline for starting it.
+///
+/// Returns `None` when the user already holds a live rental of the video, so
+/// a replayed payment confirmation does not stack a second rental.
+pub(crate) async fn record_rental(
+    conn: &mut PgConnection,
+    user_id: &str,
+    video_id: &str,
+    rental_hours: i32,
+    origin: Origin<'_>,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    entitlements::grant_rental(
+        conn,
+        user_id,
+        video_id,
+        rental_hours,
+        RENTAL_START_DAYS,
+        origin,
+    )
+    .await
+}
+
+/// Latest expiry among the user's live rentals of the video.
+pub(crate) async fn rental_access_until(
+    pool: &PgPool,
+    video_id: &str,
+    user_id: &str,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT MAX(valid_until) FROM entitlements
+        WHERE video_id = $1 AND user_id = $2 AND rental_hours IS NOT NULL
+          AND revoked_at IS NULL AND valid_until > NOW()
+        "#,
+    )
+    .bind(video_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Starts the viewing window of a not-yet-started rental on first playback
+/// and returns the resulting expiry.
+pub(crate) async fn start_rental_clock(
+    pool: &PgPool,
+    video_id: &str,
+    user_id: &str,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    entitlements::start_rental(pool, video_id, user_id).await?;
+    rental_access_until(pool, video_id, user_id).await
+}
+
+fn rental_json(r: &sqlx::postgres::PgRow) -> serde_json::Value {
+    let expires_at: Option<DateTime<Utc>> = r.try_get("valid_until").ok().flatten();
+    let started_at: Option<DateTime<Utc>> = r.try_get("rental_started_at").ok().flatten();
+    json!({
+        "video_id": r.try_get::<String, _>("video_id").unwrap_or_default(),
+        "rental_hours": r.try_get::<Option<i32>, _>("rental_hours").ok().flatten(),
+        "started": started_at.is_some(),
+        "started_at": started_at.map(|t| t.to_rfc3339()),
+        "expires_at": expires_at.map(|t| t.to_rfc3339()),
+        "remaining_seconds": expires_at.map(remaining_seconds),
+    })
+}
+
+fn remaining_seconds(expires_at: DateTime<Utc>) -> i64 {
+    (expires_at - Utc::now()).num_seconds().max(0)
+}
+
+/// The user's live rental of a video, if any, for API responses.
+pub(crate) async fn rental_status(
+    pool: &PgPool,
+    video_id: &str,
+    user_id: &str,
+) -> Option<serde_json::Value> {
+    sqlx::query(
+        r#"
+        SELECT video_id, rental_hours, rental_started_at, valid_until
+        FROM entitlements
+        WHERE video_id = $1 AND user_id = $2 AND rental_hours IS NOT NULL
+          AND revoked_at IS NULL AND valid_until > NOW()
+        ORDER BY valid_until DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(video_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .ok()
+    .flatten()
+    .map(|r| rental_json(&r))
+}
+
+/// GET /api/my_rentals
+///
+/// Live rentals of the current user with their remaining time.
+pub async fn my_rentals(State(st): State<VideoState>, cookies: Cookies) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    match sqlx::query(
+        r#"
+        SELECT e.video_id, e.rental_hours, e.rental_started_at, e.valid_until, v.title
+        FROM entitlements e
+        JOIN videos v ON v.id = e.video_id
+        WHERE e.user_id = $1 AND e.rental_hours IS NOT NULL
+          AND e.revoked_at IS NULL AND e.valid_until > NOW()
+        ORDER BY e.valid_until ASC
+        "#,
+    )
+    .bind(&uid)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => {
+            let rentals: Vec<serde_json::Value> = rows
+                .iter()
+                .map(|r| {
+                    let mut item = rental_json(r);
+                    item["title"] = json!(r.try_get::<String, _>("title").unwrap_or_default());
+                    item
+                })
+                .collect();
+            Json(json!({"ok": true, "rentals": rentals}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_time_never_goes_negative() {
+        let past = Utc::now() - chrono::Duration::hours(1);
+        assert_eq!(remaining_seconds(past), 0);
+        let future = Utc::now() + chrono::Duration::hours(2);
+        assert!((7190..=7200).contains(&remaining_seconds(future)));
+    }
+}
@@ -0,0 +1,388 @@
+// src/handlers/series.rs
+//
+// Creator-defined series (ordered collections / playlists).
+//
+// A series belongs to one creator and holds that creator's own videos in an
+// explicit order. Public endpoints only show videos that are publicly listed
+// (see `handlers::video::LISTED_VIDEO_SQL`); the owner sees every member via
+// `/api/my_series`.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::Row;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::handlers::video::{
+    video_item_from_row, VideoItem, VideoState, LISTED_VIDEO_SQL, VIDEO_ITEM_COLUMNS,
+};
+use crate::sessions;
+
+const MAX_SERIES_TITLE_CHARS: usize = 200;
+/// Maximum number of videos in one series.
+pub const MAX_SERIES_VIDEOS: usize = 500;
+
+#[derive(Deserialize)]
+pub struct SeriesListQs {
+    /// Creator username or user id.
+    pub creator: String,
+}
+
+/// GET /api/series?creator=...
+///
+/// A creator's series, each with the number of publicly listed videos.
+/// Series without any listed video are omitted.
+pub async fn list_series(
+    State(st): State<VideoState>,
+    Query(qs): Query<SeriesListQs>,
+) -> impl IntoResponse {
+    let sql = format!(
+        r#"
+        SELECT s.id, s.title, s.description, s.created_at::text AS created_at,
+               COUNT(v.id) AS video_count
+        FROM series s
+        JOIN users u ON u.id = s.owner_id
+        JOIN series_videos sv ON sv.series_id = s.id
+        JOIN videos v ON v.id = sv.video_id AND {LISTED_VIDEO_SQL}
+        WHERE u.username = $1 OR u.id = $1
+        GROUP BY s.id
+        ORDER BY s.created_at DESC
+        "#
+    );
+    let rows = match sqlx::query(&sql)
+        .bind(qs.creator.trim())
+        .fetch_all(&st.pool)
+        .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let series: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<String, _>("id").unwrap_or_default(),
+                "title": r.try_get::<String, _>("title").unwrap_or_default(),
+                "description": r.try_get::<String, _>("description").unwrap_or_default(),
+                "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+                "video_count": r.try_get::<i64, _>("video_count").unwrap_or(0),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "series": series}))
+}
+
+/// GET /api/series/:id
+///
+/// Series details and its publicly listed videos in series order.
+pub async fn get_series(State(st): State<VideoState>, Path(id): Path<String>) -> impl IntoResponse {
+    let series = match sqlx::query(
+        r#"
+        SELECT s.id, s.owner_id, COALESCE(u.username, '') AS owner_name,
+               s.title, s.description, s.created_at::text AS created_at
+        FROM series s
+        LEFT JOIN users u ON u.id = s.owner_id
+        WHERE s.id = $1
+        "#,
+    )
+    .bind(&id)
+    .fetch_optional(&st.pool)
+    .await
+    {
+        Ok(Some(row)) => row,
+        Ok(None) => return Json(json!({"ok": false, "error": "not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let sql = format!(
+        r#"
+        SELECT {VIDEO_ITEM_COLUMNS}, sv.position
+        FROM series_videos sv
+        JOIN videos v ON v.id = sv.video_id
+        LEFT JOIN users u ON u.id = v.owner_id
+        WHERE sv.series_id = $1 AND {LISTED_VIDEO_SQL}
+        ORDER BY sv.position ASC
+        "#
+    );
+    let videos: Vec<VideoItem> = match sqlx::query(&sql).bind(&id).fetch_all(&st.pool).await {
+        Ok(rows) => rows.iter().map(video_item_from_row).collect(),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    Json(json!({
+        "ok": true,
+        "series": {
+            "id": series.try_get::<String, _>("id").unwrap_or_default(),
+            "owner_id": series.try_get::<String, _>("owner_id").unwrap_or_default(),
+            "owner_name": series.try_get::<String, _>("owner_name").unwrap_or_default(),
+            "title": series.try_get::<String, _>("title").unwrap_or_default(),
+            "description": series.try_get::<String, _>("description").unwrap_or_default(),
+            "created_at": series.try_get::<String, _>("created_at").unwrap_or_default(),
+        },
+        "videos": videos
+    }))
+}
+
+/// GET /api/my_series
+///
+/// The current user's series with every member video id in order.
+pub async fn my_series(State(st): State<VideoState>, cookies: Cookies) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT s.id, s.title, s.description, s.created_at::text AS created_at,
+               ARRAY(
+                 SELECT sv.video_id FROM series_videos sv
+                 WHERE sv.series_id = s.id ORDER BY sv.position
+               ) AS video_ids
+        FROM series s
+        WHERE s.owner_id = $1
+        ORDER BY s.created_at DESC
+        "#,
+    )
+    .bind(&uid)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let series: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<String, _>("id").unwrap_or_default(),
+                "title": r.try_get::<String, _>("title").unwrap_or_default(),
+                "description": r.try_get::<String, _>("description").unwrap_or_default(),
+                "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+                "video_ids": r.try_get::<Vec<String>, _>("video_ids").unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "series": series}))
+}
+
+#[derive(Deserialize)]
+pub struct SeriesPayload {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+fn validate_title(title: &str) -> Result<(), String> {
+    if title.is_empty() || title.chars().count() > MAX_SERIES_TITLE_CHARS {
+        return Err(format!(
+            "title is required (max {MAX_SERIES_TITLE_CHARS} characters)"
+        ));
+    }
+    Ok(())
+}
+
+/// POST /api/series
+pub async fn create_series(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<SeriesPayload>,
+) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let title = p.title.as_deref().unwrap_or_default().trim().to_string();
+    if let Err(e) = validate_title(&title) {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    match sqlx::query(
+        "INSERT INTO series (id, owner_id, title, description) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(&id)
+    .bind(&uid)
+    .bind(&title)
+    .bind(p.description.as_deref().unwrap_or_default().trim())
+    .execute(&st.pool)
+    .await
+    {
+        Ok(_) => Json(json!({"ok": true, "id": id})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// POST /api/series/:id
+///
+/// Absent fields are left unchanged.
+pub async fn update_series(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+    Json(p): Json<SeriesPayload>,
+) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let title = p.title.as_deref().map(str::trim);
+    if let Some(title) = title {
+        if let Err(e) = validate_title(title) {
+            return Json(json!({"ok": false, "error": e}));
+        }
+    }
+
+    match sqlx::query(
+        r#"
+        UPDATE series
+        SET title = COALESCE($3, title),
+            description = COALESCE($4, description),
+            updated_at = NOW()
+        WHERE id = $1 AND owner_id = $2
+        "#,
+    )
+    .bind(&id)
+    .bind(&uid)
+    .bind(title)
+    .bind(p.description.as_deref().map(str::trim))
+    .execute(&st.pool)
+    .await
+    {
+        Ok(r) if r.rows_affected() > 0 => Json(json!({"ok": true})),
+        Ok(_) => Json(json!({"ok": false, "error": "not owner / not found"})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// POST /api/series/:id/delete
+///
+/// Deletes the series only; its videos are untouched.
+pub async fn delete_series(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    match sqlx::query("DELETE FROM series WHERE id = $1 AND owner_id = $2")
+        .bind(&id)
+        .bind(&uid)
+        .execute(&st.pool)
+        .await
+    {
+        Ok(r) if r.rows_affected() > 0 => Json(json!({"ok": true})),
+        Ok(_) => Json(json!({"ok": false, "error": "not owner / not found"})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// Validates an ordered video list for a series or bundle: at most `max`
+/// entries, no duplicates, and every video owned by `owner_id`.
+pub(crate) async fn check_owned_videos(
+    pool: &sqlx::PgPool,
+    owner_id: &str,
+    video_ids: &[String],
+    max: usize,
+) -> Result<(), String> {
+    if video_ids.len() > max {
+        return Err(format!("at most {max} videos allowed"));
+    }
+    let mut unique = video_ids.to_vec();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != video_ids.len() {
+        return Err("video_ids contains duplicates".to_string());
+    }
+
+    let owned: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM videos WHERE id = ANY($1) AND owner_id = $2")
+            .bind(video_ids)
+            .bind(owner_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("db: {e}"))?;
+    if owned as usize != video_ids.len() {
+        return Err("every video must exist and belong to you".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct SeriesVideosPayload {
+    /// Complete, ordered member list. Replaces the current membership.
+    pub video_ids: Vec<String>,
+}
+
+/// POST /api/series/:id/videos
+///
+/// Sets the series members and their order in one call. Every video must be
+/// owned by the series owner; duplicates are rejected.
+pub async fn set_series_videos(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+    Json(p): Json<SeriesVideosPayload>,
+) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    if let Err(e) = check_owned_videos(&st.pool, &uid, &p.video_ids, MAX_SERIES_VIDEOS).await {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let is_owner: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM series WHERE id = $1 AND owner_id = $2)")
+            .bind(&id)
+            .bind(&uid)
+            .fetch_one(&st.pool)
+            .await
+            .unwrap_or(false);
+    if !is_owner {
+        return Json(json!({"ok": false, "error": "not owner / not found"}));
+    }
+
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = st.pool.begin().await?;
+        sqlx::query("DELETE FROM series_videos WHERE series_id = $1")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+        for (position, video_id) in p.video_ids.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO series_videos (series_id, video_id, position) VALUES ($1, $2, $3)",
+            )
+            .bind(&id)
+            .bind(video_id)
+            .bind(position as i32)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query("UPDATE series SET updated_at = NOW() WHERE id = $1")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => Json(json!({"ok": true, "count": p.video_ids.len()})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
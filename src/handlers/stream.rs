@@ -23,7 +23,9 @@ use uuid::Uuid;
 
 use crate::config::Config;
 use crate::ffmpeg::run_ffmpeg;
-use crate::handlers::video::user_has_view_access;
+use crate::geo::ClientCountry;
+use crate::handlers::video::{view_access, ViewAccess};
+use crate::handlers::{regions, rentals};
 use crate::sessions;
 
 const PLAYBACK_SESSION_TTL_SECONDS: i32 = 60 * 60;
@@ -44,6 +46,7 @@ pub struct RequestPlayQuery {
 pub async fn request_play(
     State(st): State<StreamState>,
     cookies: Cookies,
+    country: ClientCountry,
     Query(q): Query<RequestPlayQuery>,
 ) -> impl IntoResponse {
     let (user_id, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
@@ -87,9 +90,39 @@ pub async fn request_play(
         }
     }
 
-    match user_has_view_access(&st.pool, &q.video_id, &user_id).await {
-        Ok(true) => {}
-        Ok(false) => {
+    // Geo-restrictions apply to playback too, whatever was bought elsewhere.
+    if let Err(e) = regions::regional_terms(&st.pool, &q.video_id, country.code()).await {
+        let status = if e == regions::REGION_BLOCKED {
+            StatusCode::FORBIDDEN
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+        return (status, Json(json!({"ok": false, "error": e}))).into_response();
+    }
+
+    // Rentals start their window on first play; sessions never outlive them.
+    let rental_expires_at = match view_access(&st.pool, &q.video_id, &user_id).await {
+        Ok(ViewAccess::Granted) => None,
+        Ok(ViewAccess::Rental(_)) => {
+            match rentals::start_rental_clock(&st.pool, &q.video_id, &user_id).await {
+                Ok(Some(until)) => Some(until),
+                Ok(None) => {
+                    return (
+                        StatusCode::FORBIDDEN,
+                        Json(json!({"ok": false, "error": "rental expired"})),
+                    )
+                        .into_response()
+                }
+                Err(e) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(json!({"ok": false, "error": format!("rental start: {e}")})),
+                    )
+                        .into_response()
+                }
+            }
+        }
+        Ok(ViewAccess::Denied) => {
             return (
                 StatusCode::FORBIDDEN,
                 Json(json!({"ok": false, "error": "no access"})),
@@ -103,7 +136,7 @@ pub async fn request_play(
             )
                 .into_response()
         }
-    }
+    };
 
     let row = match sqlx::query!(
         r#"
@@ -198,13 +231,15 @@ pub async fn request_play(
         INSERT INTO playback_sessions
             (session_id, user_id, video_id, session_dir, status, expires_at)
         VALUES
-            ($1, $2, $3, $4, 'starting', NOW() + make_interval(secs => $5))
+            ($1, $2, $3, $4, 'starting',
+             LEAST(NOW() + make_interval(secs => $5), $6))
         "#,
         session,
         user_id,
         video.id,
         session_dir_string,
-        PLAYBACK_SESSION_TTL_SECONDS as f64
+        PLAYBACK_SESSION_TTL_SECONDS as f64,
+        rental_expires_at as _
     )
     .execute(&st.pool)
     .await
@@ -417,6 +452,12 @@ pub async fn request_play(
     }
 
     let playlist = format!("/hls/{}/master.m3u8", session);
+    let expires_in_seconds = match rental_expires_at {
+        Some(until) => (until - chrono::Utc::now())
+            .num_seconds()
+            .clamp(0, PLAYBACK_SESSION_TTL_SECONDS as i64),
+        None => PLAYBACK_SESSION_TTL_SECONDS as i64,
+    };
     (
         StatusCode::OK,
         Json(json!({
@@ -427,7 +468,8 @@ pub async fn request_play(
             "title": video.title,
             "price_cents": video.price_cents,
             "segment_seconds": segment_seconds,
-            "expires_in_seconds": PLAYBACK_SESSION_TTL_SECONDS
+            "expires_in_seconds": expires_in_seconds,
+            "rental_expires_at": rental_expires_at.map(|t| t.to_rfc3339())
         })),
     )
         .into_response()
@@ -0,0 +1,228 @@
+// src/handlers/stripe_connect.rs
+//
+// Stripe Connect onboarding and charge splitting.
+//
+// With STRIPE_CONNECT_ENABLED a creator links an Express connected account
+// from the dashboard: `POST /api/stripe/connect/onboard` creates the account
+// once (`users.stripe_account_id`) and returns a Stripe onboarding link, and
+// `GET /api/stripe/connect/status` asks Stripe whether the account can receive
+// transfers yet (`users.stripe_account_ready`).
+//
+// Stripe checkouts for a ready creator are destination charges: the creator
+// share goes straight to their account and the platform keeps
+// `application_fee`, the price minus CREATOR_SPLIT_BP of it. Such invoices are
+// disbursed as soon as they are paid (payment_plugins.rs), an admin refund
+// reverses the transfer instead of debiting the creator's wallet (refunds.rs),
+// and renewals of a split subscription are split the same way. Tips are
+// credited to the wallet and are never split.
+
+use axum::{extract::State, response::IntoResponse, Json};
+use serde_json::json;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use tower_cookies::Cookies;
+
+use crate::handlers::payouts::PayoutState;
+use crate::handlers::tips;
+use crate::plugins::payment::providers::stripe::{
+    StripePaymentPlugin, APPLICATION_FEE_KEY, CONNECT_ACCOUNT_KEY,
+};
+use crate::sessions;
+
+/// Platform share of a split charge of `amount`, in the same minor units.
+pub(crate) fn application_fee(amount: i64, creator_split_bp: u16) -> i64 {
+    amount - tips::creator_share(amount, creator_split_bp)
+}
+
+/// Connected account a Stripe charge for `creator_id` is split with, if
+/// Connect is on and the creator finished onboarding.
+pub(crate) async fn charge_destination(pool: &PgPool, creator_id: &str) -> Option<String> {
+    if !StripePaymentPlugin::from_env().connect_enabled() {
+        return None;
+    }
+    sqlx::query_scalar::<_, Option<String>>(
+        "SELECT stripe_account_id FROM users WHERE id = $1 AND stripe_account_ready",
+    )
+    .bind(creator_id)
+    .fetch_optional(pool)
+    .await
+    .unwrap_or_else(|e| {
+        tracing::warn!("stripe connect: account lookup failed for {creator_id}: {e}");
+        None
+    })
+    .flatten()
+}
+
+/// Puts the split of a charge into provider metadata. Whatever the client
+/// sent under the same keys is dropped first.
+pub(crate) fn set_split_metadata(
+    metadata: &mut HashMap<String, String>,
+    split: Option<(&str, i64)>,
+) {
+    metadata.remove(CONNECT_ACCOUNT_KEY);
+    metadata.remove(APPLICATION_FEE_KEY);
+    if let Some((account, fee)) = split {
+        metadata.insert(CONNECT_ACCOUNT_KEY.into(), account.to_string());
+        metadata.insert(APPLICATION_FEE_KEY.into(), fee.to_string());
+    }
+}
+
+/// POST /api/stripe/connect/onboard
+///
+/// Creates the creator's connected account on first use and returns a fresh
+/// onboarding link for it.
+pub async fn connect_onboard(State(st): State<PayoutState>, cookies: Cookies) -> impl IntoResponse {
+    let (user_id, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let stripe = StripePaymentPlugin::from_env();
+    if !stripe.connect_enabled() {
+        return Json(json!({"ok": false, "error": "Stripe Connect is not enabled"}));
+    }
+
+    let row = match sqlx::query("SELECT email, stripe_account_id FROM users WHERE id = $1")
+        .bind(&user_id)
+        .fetch_one(&st.pool)
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let account_id = match row
+        .try_get::<Option<String>, _>("stripe_account_id")
+        .ok()
+        .flatten()
+    {
+        Some(id) => id,
+        None => {
+            let email: String = row
+                .try_get::<Option<String>, _>("email")
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+            let created = match stripe.create_connected_account(&user_id, &email).await {
+                Ok(id) => id,
+                Err(e) => return Json(json!({"ok": false, "error": e.to_string()})),
+            };
+            // A concurrent request may have saved an account first; keep it.
+            match sqlx::query_scalar::<_, String>(
+                "UPDATE users SET stripe_account_id = COALESCE(stripe_account_id, $2)
+                 WHERE id = $1 RETURNING stripe_account_id",
+            )
+            .bind(&user_id)
+            .bind(&created)
+            .fetch_one(&st.pool)
+            .await
+            {
+                Ok(id) => id,
+                Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+            }
+        }
+    };
+
+    let dashboard = format!(
+        "{}/public/dashboard.html",
+        st.cfg.base_url.trim_end_matches('/')
+    );
+    match stripe
+        .onboarding_link(
+            &account_id,
+            &format!("{dashboard}?stripe_connect=refresh"),
+            &format!("{dashboard}?stripe_connect=return"),
+        )
+        .await
+    {
+        Ok(url) => {
+            tracing::info!(%user_id, %account_id, "stripe connect onboarding link created");
+            Json(json!({"ok": true, "account_id": account_id, "url": url}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// GET /api/stripe/connect/status
+///
+/// Refreshes whether the creator's connected account can take split charges.
+pub async fn connect_status(State(st): State<PayoutState>, cookies: Cookies) -> impl IntoResponse {
+    let (user_id, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let stripe = StripePaymentPlugin::from_env();
+
+    let row = match sqlx::query(
+        "SELECT stripe_account_id, stripe_account_ready FROM users WHERE id = $1",
+    )
+    .bind(&user_id)
+    .fetch_one(&st.pool)
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let account_id: Option<String> = row.try_get("stripe_account_id").ok().flatten();
+    let mut ready: bool = row.try_get("stripe_account_ready").unwrap_or(false);
+
+    if let Some(account_id) = account_id.as_deref().filter(|_| stripe.connect_enabled()) {
+        match stripe.connected_account_ready(account_id).await {
+            Ok(now_ready) if now_ready != ready => {
+                if let Err(e) =
+                    sqlx::query("UPDATE users SET stripe_account_ready = $2 WHERE id = $1")
+                        .bind(&user_id)
+                        .bind(now_ready)
+                        .execute(&st.pool)
+                        .await
+                {
+                    return Json(json!({"ok": false, "error": format!("db: {e}")}));
+                }
+                tracing::info!(%user_id, %account_id, ready = now_ready, "stripe connect account updated");
+                ready = now_ready;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!("stripe connect: status of {account_id} unavailable: {e}");
+            }
+        }
+    }
+
+    Json(json!({
+        "ok": true,
+        "enabled": stripe.connect_enabled(),
+        "account_id": account_id,
+        "ready": ready,
+        "creator_split_bp": st.cfg.creator_split_bp,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn application_fee_is_what_the_creator_does_not_get() {
+        assert_eq!(application_fee(1000, 9000), 100);
+        // the creator share rounds down, so the odd cent stays with the platform
+        assert_eq!(application_fee(999, 9000), 100);
+        assert_eq!(application_fee(150_000, 7000), 45_000);
+        assert_eq!(application_fee(500, 10_000), 0);
+    }
+
+    #[test]
+    fn client_metadata_cannot_choose_the_split() {
+        let mut metadata: HashMap<String, String> = [
+            (CONNECT_ACCOUNT_KEY.to_string(), "acct_attacker".to_string()),
+            (APPLICATION_FEE_KEY.to_string(), "0".to_string()),
+            ("note".to_string(), "kept".to_string()),
+        ]
+        .into();
+        set_split_metadata(&mut metadata, None);
+        assert!(!metadata.contains_key(CONNECT_ACCOUNT_KEY));
+        assert!(!metadata.contains_key(APPLICATION_FEE_KEY));
+        assert_eq!(metadata["note"], "kept");
+
+        set_split_metadata(&mut metadata, Some(("acct_creator", 100)));
+        assert_eq!(metadata[CONNECT_ACCOUNT_KEY], "acct_creator");
+        assert_eq!(metadata[APPLICATION_FEE_KEY], "100");
+    }
+}
@@ -0,0 +1,1074 @@
+// src/handlers/subscriptions.rs
+//
+// Creator subscription tiers with recurring billing.
+//
+// A tier unlocks either every video of its creator (`access_scope = 'all'`)
+// or only videos carrying one of the tier tags (`'tagged'`). Buyers pay per
+// period of `interval_days`:
+//
+//   wallet   charged immediately on subscribe, then auto-debited by the
+//            renewal worker (same creator split as a video sale)
+//   plugin   first period through `/api/pay/:provider/start` with `tier_id`
+//            (providers with `supports_recurring` only); renewals call
+//            `PaymentPlugin::charge_recurring` with the saved payment ref,
+//            split with the creator's account under Stripe Connect
+//
+// A failed renewal moves the subscription to `past_due`; access continues
+// until `grace_until` (`SUBSCRIPTION_GRACE_DAYS` after the period end) while
+// the worker retries daily, then it expires. Cancelling keeps access until
+// the end of the paid period. Every status change is mirrored into the
+// subscription's entitlement with [`entitlements::sync_subscription`].
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{Acquire, PgPool, Row};
+use std::collections::HashMap;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::entitlements;
+use crate::handlers::video::VideoState;
+use crate::handlers::wallet::settle_wallet_sale;
+use crate::handlers::{stripe_connect, taxonomy};
+use crate::payment_settings::load_payment_settings;
+use crate::plugins::payment::{
+    models::{PaymentStatus, RecurringChargeRequest},
+    PaymentPluginRegistry,
+};
+use crate::sessions;
+
+/// Accepted `access_scope` values.
+pub const TIER_SCOPES: [&str; 2] = ["all", "tagged"];
+const MAX_TIER_NAME_CHARS: usize = 80;
+const RENEWAL_POLL_SECS: u64 = 300;
+const RENEWAL_BATCH: i64 = 50;
+/// Minimum time between renewal attempts of a past-due subscription.
+const RETRY_INTERVAL_HOURS: i32 = 24;
+/// Subscriptions the renewal worker charges; `$1` is RETRY_INTERVAL_HOURS.
+const RENEWAL_DUE: &str = "((status = 'active' AND current_period_end <= NOW()) \
+     OR (status = 'past_due' \
+         AND (last_attempt_at IS NULL \
+              OR last_attempt_at <= NOW() - make_interval(hours => $1))))";
+/// Abandoned plugin checkouts are expired after this many hours.
+const PENDING_TTL_HOURS: i32 = 24;
+
+/// Billing-relevant fields of a tier.
+pub(crate) struct TierOffer {
+    pub id: String,
+    pub creator_id: String,
+    pub name: String,
+    pub price_cents: i64,
+    pub active: bool,
+}
+
+pub(crate) async fn load_tier(
+    pool: &PgPool,
+    tier_id: &str,
+) -> Result<Option<TierOffer>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT id, creator_id, name, price_cents, active FROM subscription_tiers WHERE id = $1",
+    )
+    .bind(tier_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| TierOffer {
+        id: r.try_get("id").unwrap_or_default(),
+        creator_id: r.try_get("creator_id").unwrap_or_default(),
+        name: r.try_get("name").unwrap_or_default(),
+        price_cents: r.try_get("price_cents").unwrap_or(0),
+        active: r.try_get("active").unwrap_or(false),
+    }))
+}
+
+/// Checks that `user_id` may start a subscription to `tier`.
+pub(crate) async fn check_tier_subscribable(
+    pool: &PgPool,
+    tier: &TierOffer,
+    user_id: &str,
+) -> Result<(), String> {
+    if !tier.active {
+        return Err("tier is not available".to_string());
+    }
+    if tier.creator_id == user_id {
+        return Err("you cannot subscribe to your own tier".to_string());
+    }
+    let live: bool = sqlx::query_scalar(
+        r#"SELECT EXISTS(
+             SELECT 1 FROM subscriptions
+             WHERE tier_id = $1 AND user_id = $2
+               AND status IN ('active', 'past_due', 'canceled'))"#,
+    )
+    .bind(&tier.id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("db: {e}"))?;
+    if live {
+        return Err("already subscribed".to_string());
+    }
+    Ok(())
+}
+
+/// Creates a `pending` subscription for a plugin checkout, replacing any
+/// earlier abandoned checkout for the same tier.
+pub(crate) async fn create_pending_subscription(
+    pool: &PgPool,
+    tier: &TierOffer,
+    user_id: &str,
+    provider: &str,
+) -> Result<String, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE subscriptions SET status = 'expired', updated_at = NOW() \
+         WHERE tier_id = $1 AND user_id = $2 AND status = 'pending'",
+    )
+    .bind(&tier.id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO subscriptions (id, tier_id, user_id, status, billing_method, provider) \
+         VALUES ($1, $2, $3, 'pending', 'plugin', $4)",
+    )
+    .bind(&id)
+    .bind(&tier.id)
+    .bind(user_id)
+    .bind(provider)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Drops the `pending` subscription of a plugin checkout that failed before
+/// the buyer reached the provider.
+pub(crate) async fn discard_pending_subscription(
+    pool: &PgPool,
+    subscription_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM subscriptions WHERE id = $1 AND status = 'pending'")
+        .bind(subscription_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Starts the first period of a plugin subscription once its checkout is paid,
+/// on the caller's transaction. Replays are ignored (only `pending`
+/// subscriptions are activated).
+pub(crate) async fn activate_from_checkout(
+    conn: &mut sqlx::PgConnection,
+    subscription_id: &str,
+    amount_cents: i64,
+    provider: &str,
+    payment_ref: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let period: Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> =
+        sqlx::query_as(
+            r#"
+            UPDATE subscriptions s
+            SET status = 'active',
+                provider_payment_ref = COALESCE($2, s.provider_payment_ref),
+                current_period_start = NOW(),
+                current_period_end = NOW() + make_interval(days => t.interval_days),
+                grace_until = NULL, failed_attempts = 0, updated_at = NOW()
+            FROM subscription_tiers t
+            WHERE s.id = $1 AND t.id = s.tier_id AND s.status = 'pending'
+            RETURNING s.current_period_start, s.current_period_end
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(payment_ref)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let Some((start, end)) = period else {
+        return Ok(false);
+    };
+    record_charge(
+        conn,
+        subscription_id,
+        amount_cents,
+        &format!("fiat:{provider}"),
+        Ok((start, end)),
+        payment_ref,
+    )
+    .await?;
+    entitlements::sync_subscription(conn, subscription_id).await?;
+    Ok(true)
+}
+
+type Period = (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>);
+
+async fn record_charge(
+    conn: &mut sqlx::PgConnection,
+    subscription_id: &str,
+    amount_cents: i64,
+    method: &str,
+    outcome: Result<Period, &str>,
+    reference: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let (status, period, error) = match outcome {
+        Ok(period) => ("paid", Some(period), None),
+        Err(e) => ("failed", None, Some(e)),
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO subscription_charges
+          (subscription_id, amount_cents, method, status, period_start, period_end, reference, error)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(amount_cents)
+    .bind(method)
+    .bind(status)
+    .bind(period.map(|p| p.0))
+    .bind(period.map(|p| p.1))
+    .bind(reference)
+    .bind(error)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+// ─── Tiers ───────────────────────────────────────────────────────────────────
+
+fn tier_json(r: &sqlx::postgres::PgRow) -> serde_json::Value {
+    json!({
+        "id": r.try_get::<String, _>("id").unwrap_or_default(),
+        "creator_id": r.try_get::<String, _>("creator_id").unwrap_or_default(),
+        "name": r.try_get::<String, _>("name").unwrap_or_default(),
+        "description": r.try_get::<String, _>("description").unwrap_or_default(),
+        "price_cents": r.try_get::<i64, _>("price_cents").unwrap_or(0),
+        "interval_days": r.try_get::<i32, _>("interval_days").unwrap_or(30),
+        "access_scope": r.try_get::<String, _>("access_scope").unwrap_or_default(),
+        "tags": r.try_get::<Vec<String>, _>("tags").unwrap_or_default(),
+        "active": r.try_get::<bool, _>("active").unwrap_or(false),
+    })
+}
+
+const TIER_COLUMNS: &str = r#"
+    t.id, t.creator_id, t.name, t.description, t.price_cents, t.interval_days,
+    t.access_scope, t.active,
+    ARRAY(SELECT tt.tag FROM subscription_tier_tags tt WHERE tt.tier_id = t.id ORDER BY tt.tag) AS tags"#;
+
+#[derive(Deserialize)]
+pub struct TierListQs {
+    /// Creator username or user id.
+    pub creator: String,
+}
+
+/// GET /api/tiers?creator=...
+pub async fn list_tiers(
+    State(st): State<VideoState>,
+    Query(qs): Query<TierListQs>,
+) -> impl IntoResponse {
+    let sql = format!(
+        r#"
+        SELECT {TIER_COLUMNS}
+        FROM subscription_tiers t
+        JOIN users u ON u.id = t.creator_id
+        WHERE t.active AND (u.username = $1 OR u.id = $1)
+        ORDER BY t.price_cents ASC, t.created_at ASC
+        "#
+    );
+    match sqlx::query(&sql)
+        .bind(qs.creator.trim())
+        .fetch_all(&st.pool)
+        .await
+    {
+        Ok(rows) => {
+            let tiers: Vec<serde_json::Value> = rows.iter().map(tier_json).collect();
+            Json(json!({"ok": true, "tiers": tiers}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+/// GET /api/my_tiers
+///
+/// The current creator's tiers, including inactive ones, with subscriber counts.
+pub async fn my_tiers(State(st): State<VideoState>, cookies: Cookies) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let sql = format!(
+        r#"
+        SELECT {TIER_COLUMNS},
+          (SELECT COUNT(*) FROM subscriptions s
+            WHERE s.tier_id = t.id AND s.status IN ('active', 'past_due', 'canceled')) AS subscribers
+        FROM subscription_tiers t
+        WHERE t.creator_id = $1
+        ORDER BY t.created_at DESC
+        "#
+    );
+    match sqlx::query(&sql).bind(&uid).fetch_all(&st.pool).await {
+        Ok(rows) => {
+            let tiers: Vec<serde_json::Value> = rows
+                .iter()
+                .map(|r| {
+                    let mut t = tier_json(r);
+                    t["subscribers"] = json!(r.try_get::<i64, _>("subscribers").unwrap_or(0));
+                    t
+                })
+                .collect();
+            Json(json!({"ok": true, "tiers": tiers}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TierPayload {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub price_cents: Option<i64>,
+    pub interval_days: Option<i32>,
+    /// `all` or `tagged`.
+    pub access_scope: Option<String>,
+    /// Tags unlocked by a `tagged` tier. Replaces the current list when present.
+    pub tags: Option<Vec<String>>,
+    pub active: Option<bool>,
+}
+
+struct TierFields {
+    name: String,
+    price_cents: i64,
+    interval_days: i32,
+    access_scope: String,
+    tags: Vec<String>,
+}
+
+fn validate_tier(fields: &TierFields) -> Result<(), String> {
+    if fields.name.is_empty() || fields.name.chars().count() > MAX_TIER_NAME_CHARS {
+        return Err(format!(
+            "name is required (max {MAX_TIER_NAME_CHARS} characters)"
+        ));
+    }
+    if fields.price_cents <= 0 {
+        return Err("price_cents must be positive".to_string());
+    }
+    if !(1..=366).contains(&fields.interval_days) {
+        return Err("interval_days must be between 1 and 366".to_string());
+    }
+    if !TIER_SCOPES.contains(&fields.access_scope.as_str()) {
+        return Err(format!(
+            "access_scope must be one of: {}",
+            TIER_SCOPES.join(", ")
+        ));
+    }
+    if fields.access_scope == "tagged" && fields.tags.is_empty() {
+        return Err("a tagged tier needs at least one tag".to_string());
+    }
+    Ok(())
+}
+
+fn normalize_tier_tags(raw: &[String]) -> Result<Vec<String>, String> {
+    taxonomy::parse_tags(&raw.join(","))
+}
+
+async fn replace_tier_tags(
+    conn: &mut sqlx::PgConnection,
+    tier_id: &str,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM subscription_tier_tags WHERE tier_id = $1")
+        .bind(tier_id)
+        .execute(&mut *conn)
+        .await?;
+    for tag in tags {
+        sqlx::query("INSERT INTO subscription_tier_tags (tier_id, tag) VALUES ($1, $2)")
+            .bind(tier_id)
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// POST /api/tiers
+pub async fn create_tier(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<TierPayload>,
+) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let tags = match normalize_tier_tags(p.tags.as_deref().unwrap_or_default()) {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let fields = TierFields {
+        name: p.name.as_deref().unwrap_or_default().trim().to_string(),
+        price_cents: p.price_cents.unwrap_or(0),
+        interval_days: p.interval_days.unwrap_or(30),
+        access_scope: p
+            .access_scope
+            .as_deref()
+            .unwrap_or("all")
+            .trim()
+            .to_ascii_lowercase(),
+        tags,
+    };
+    if let Err(e) = validate_tier(&fields) {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = st.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO subscription_tiers
+              (id, creator_id, name, description, price_cents, interval_days, access_scope, active)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(&id)
+        .bind(&uid)
+        .bind(&fields.name)
+        .bind(p.description.as_deref().unwrap_or_default().trim())
+        .bind(fields.price_cents)
+        .bind(fields.interval_days)
+        .bind(&fields.access_scope)
+        .bind(p.active.unwrap_or(true))
+        .execute(&mut *tx)
+        .await?;
+        replace_tier_tags(&mut tx, &id, &fields.tags).await?;
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => Json(json!({"ok": true, "id": id})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// POST /api/tiers/:id
+///
+/// Absent fields are left unchanged. A new price applies from the next
+/// renewal; deactivating a tier stops renewals (subscribers keep access until
+/// their paid period ends).
+pub async fn update_tier(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+    Json(p): Json<TierPayload>,
+) -> impl IntoResponse {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let sql = format!(
+        "SELECT {TIER_COLUMNS} FROM subscription_tiers t WHERE t.id = $1 AND t.creator_id = $2"
+    );
+    let current = match sqlx::query(&sql)
+        .bind(&id)
+        .bind(&uid)
+        .fetch_optional(&st.pool)
+        .await
+    {
+        Ok(Some(row)) => row,
+        Ok(None) => return Json(json!({"ok": false, "error": "not owner / not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let tags = match &p.tags {
+        Some(raw) => match normalize_tier_tags(raw) {
+            Ok(t) => t,
+            Err(e) => return Json(json!({"ok": false, "error": e})),
+        },
+        None => current.try_get("tags").unwrap_or_default(),
+    };
+    let fields = TierFields {
+        name: p
+            .name
+            .as_deref()
+            .map(|n| n.trim().to_string())
+            .unwrap_or_else(|| current.try_get("name").unwrap_or_default()),
+        price_cents: p
+            .price_cents
+            .unwrap_or_else(|| current.try_get("price_cents").unwrap_or(0)),
+        interval_days: p
+            .interval_days
+            .unwrap_or_else(|| current.try_get("interval_days").unwrap_or(30)),
+        access_scope: p
+            .access_scope
+            .as_deref()
+            .map(|s| s.trim().to_ascii_lowercase())
+            .unwrap_or_else(|| current.try_get("access_scope").unwrap_or_default()),
+        tags,
+    };
+    if let Err(e) = validate_tier(&fields) {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = st.pool.begin().await?;
+        sqlx::query(
+            r#"
+            UPDATE subscription_tiers
+            SET name = $2, price_cents = $3, interval_days = $4, access_scope = $5,
+                description = COALESCE($6, description),
+                active = COALESCE($7, active),
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(&id)
+        .bind(&fields.name)
+        .bind(fields.price_cents)
+        .bind(fields.interval_days)
+        .bind(&fields.access_scope)
+        .bind(p.description.as_deref().map(str::trim))
+        .bind(p.active)
+        .execute(&mut *tx)
+        .await?;
+        if p.tags.is_some() {
+            replace_tier_tags(&mut tx, &id, &fields.tags).await?;
+        }
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => Json(json!({"ok": true})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+// ─── Subscriptions ───────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct SubscribePayload {
+    pub tier_id: String,
+}
+
+/// POST /api/subscriptions
+///
+/// Subscribes with the wallet: the first period is charged immediately and
+/// later periods are auto-debited. Plugin billing starts at
+/// `/api/pay/:provider/start` with `tier_id` instead.
+pub async fn subscribe_wallet(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<SubscribePayload>,
+) -> impl IntoResponse {
+    let payment_settings = load_payment_settings(&st.pool).await;
+    if !payment_settings.wallet_payment_enabled {
+        return Json(
+            json!({"ok": false, "error": "wallet payment is currently disabled by admin"}),
+        );
+    }
+
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let tier = match load_tier(&st.pool, &p.tier_id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return Json(json!({"ok": false, "error": "tier not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    if let Err(e) = check_tier_subscribable(&st.pool, &tier, &uid).await {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let result: Result<Period, String> = async {
+        let mut tx = st
+            .pool
+            .begin()
+            .await
+            .map_err(|e| format!("begin tx: {e}"))?;
+        settle_wallet_sale(
+            &mut tx,
+            &uid,
+            &tier.creator_id,
+            tier.price_cents,
+            creator_cut(&st.cfg, tier.price_cents),
+            &format!("Subscription: {}", tier.name),
+            &format!("Subscription sale: {}", tier.id),
+        )
+        .await?;
+
+        let period: Period = sqlx::query_as(
+            r#"
+            INSERT INTO subscriptions
+              (id, tier_id, user_id, status, billing_method,
+               current_period_start, current_period_end)
+            SELECT $1, t.id, $3, 'active', 'wallet',
+                   NOW(), NOW() + make_interval(days => t.interval_days)
+            FROM subscription_tiers t WHERE t.id = $2
+            RETURNING current_period_start, current_period_end
+            "#,
+        )
+        .bind(&id)
+        .bind(&tier.id)
+        .bind(&uid)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                "already subscribed".to_string()
+            }
+            e => format!("db: {e}"),
+        })?;
+
+        record_charge(&mut tx, &id, tier.price_cents, "wallet", Ok(period), None)
+            .await
+            .map_err(|e| format!("db: {e}"))?;
+        entitlements::sync_subscription(&mut tx, &id)
+            .await
+            .map_err(|e| format!("db: {e}"))?;
+        tx.commit().await.map_err(|e| format!("commit: {e}"))?;
+        Ok(period)
+    }
+    .await;
+
+    match result {
+        Ok((_, period_end)) => Json(json!({
+            "ok": true,
+            "subscription_id": id,
+            "status": "active",
+            "current_period_end": period_end.to_rfc3339()
+        })),
+        Err(e) => Json(json!({"ok": false, "error": e})),
+    }
+}
+
+fn creator_cut(cfg: &Config, price_cents: i64) -> i64 {
+    ((price_cents as i128).saturating_mul(cfg.creator_split_bp as i128) / 10_000) as i64
+}
+
+/// GET /api/my_subscriptions
+pub async fn my_subscriptions(State(st): State<VideoState>, cookies: Cookies) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT s.id, s.tier_id, t.name AS tier_name, t.price_cents, t.interval_days,
+               COALESCE(u.username, '') AS creator_name,
+               s.status, s.billing_method, s.provider,
+               s.current_period_end::text AS current_period_end,
+               s.grace_until::text AS grace_until,
+               s.created_at::text AS created_at
+        FROM subscriptions s
+        JOIN subscription_tiers t ON t.id = s.tier_id
+        LEFT JOIN users u ON u.id = t.creator_id
+        WHERE s.user_id = $1 AND s.status <> 'pending'
+        ORDER BY s.created_at DESC
+        "#,
+    )
+    .bind(&uid)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let subscriptions: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<String, _>("id").unwrap_or_default(),
+                "tier_id": r.try_get::<String, _>("tier_id").unwrap_or_default(),
+                "tier_name": r.try_get::<String, _>("tier_name").unwrap_or_default(),
+                "creator_name": r.try_get::<String, _>("creator_name").unwrap_or_default(),
+                "price_cents": r.try_get::<i64, _>("price_cents").unwrap_or(0),
+                "interval_days": r.try_get::<i32, _>("interval_days").unwrap_or(30),
+                "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                "billing_method": r.try_get::<String, _>("billing_method").unwrap_or_default(),
+                "provider": r.try_get::<Option<String>, _>("provider").ok().flatten(),
+                "current_period_end": r.try_get::<Option<String>, _>("current_period_end").ok().flatten(),
+                "grace_until": r.try_get::<Option<String>, _>("grace_until").ok().flatten(),
+                "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "subscriptions": subscriptions}))
+}
+
+/// POST /api/subscriptions/:id/cancel
+///
+/// Stops renewals. A paid period keeps access until it ends; a past-due
+/// subscription ends immediately.
+pub async fn cancel_subscription(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let result: Result<Option<String>, sqlx::Error> = async {
+        let mut tx = st.pool.begin().await?;
+        let status: Option<String> = sqlx::query_scalar(
+            r#"
+            UPDATE subscriptions
+            SET status = CASE WHEN status = 'active' THEN 'canceled' ELSE 'expired' END,
+                canceled_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND status IN ('active', 'past_due', 'pending')
+            RETURNING status
+            "#,
+        )
+        .bind(&id)
+        .bind(&uid)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if status.is_some() {
+            entitlements::sync_subscription(&mut tx, &id).await?;
+        }
+        tx.commit().await?;
+        Ok(status)
+    }
+    .await;
+    let status = match result {
+        Ok(s) => s,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    match status {
+        Some(status) => Json(json!({"ok": true, "status": status})),
+        None => Json(json!({"ok": false, "error": "no active subscription found"})),
+    }
+}
+
+/// POST /api/subscriptions/:id/resume
+///
+/// Undoes a cancellation while the paid period is still running.
+pub async fn resume_subscription(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let result: Result<bool, sqlx::Error> = async {
+        let mut tx = st.pool.begin().await?;
+        let resumed = sqlx::query(
+            r#"
+            UPDATE subscriptions s
+            SET status = 'active', canceled_at = NULL, updated_at = NOW()
+            FROM subscription_tiers t
+            WHERE s.id = $1 AND s.user_id = $2 AND t.id = s.tier_id AND t.active
+              AND s.status = 'canceled' AND s.current_period_end > NOW()
+            "#,
+        )
+        .bind(&id)
+        .bind(&uid)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        if resumed {
+            entitlements::sync_subscription(&mut tx, &id).await?;
+        }
+        tx.commit().await?;
+        Ok(resumed)
+    }
+    .await;
+
+    match result {
+        Ok(true) => Json(json!({"ok": true, "status": "active"})),
+        Ok(false) => Json(json!({"ok": false, "error": "subscription cannot be resumed"})),
+        Err(e) => Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+}
+
+// ─── Renewal worker ──────────────────────────────────────────────────────────
+
+/// Spawns the background task that renews due subscriptions, retries past-due
+/// ones, and expires subscriptions whose paid period or grace period ended.
+pub fn start_renewal_worker(pool: PgPool, cfg: Config) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(RENEWAL_POLL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_renewals(&pool, &cfg).await {
+                tracing::error!("subscription renewal worker: {}", e);
+            }
+        }
+    });
+}
+
+async fn run_renewals(pool: &PgPool, cfg: &Config) -> Result<(), sqlx::Error> {
+    let expired: Vec<String> = sqlx::query_scalar(
+        r#"
+        UPDATE subscriptions
+        SET status = 'expired', updated_at = NOW()
+        WHERE (status = 'canceled' AND current_period_end <= NOW())
+           OR (status = 'past_due' AND grace_until <= NOW())
+           OR (status = 'pending' AND created_at <= NOW() - make_interval(hours => $1))
+        RETURNING id
+        "#,
+    )
+    .bind(PENDING_TTL_HOURS)
+    .fetch_all(pool)
+    .await?;
+    if !expired.is_empty() {
+        let mut conn = pool.acquire().await?;
+        for id in &expired {
+            entitlements::sync_subscription(&mut conn, id).await?;
+        }
+        tracing::info!(expired = expired.len(), "subscriptions expired");
+    }
+
+    let due: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT id FROM subscriptions WHERE {RENEWAL_DUE} \
+         ORDER BY current_period_end ASC LIMIT $2"
+    ))
+    .bind(RETRY_INTERVAL_HOURS)
+    .bind(RENEWAL_BATCH)
+    .fetch_all(pool)
+    .await?;
+
+    if due.is_empty() {
+        return Ok(());
+    }
+    let registry = PaymentPluginRegistry::from_all_env_known_with_pool(Some(pool.clone()));
+    for id in due {
+        if let Err(e) = renew_subscription(pool, cfg, &registry, &id).await {
+            tracing::warn!(subscription_id = %id, "subscription renewal failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Charges one due subscription and moves it to the next period, or records
+/// the failure and starts / continues the grace period.
+///
+/// The subscription stays locked until the outcome is committed; one that
+/// another worker holds, or that is no longer due, is left alone.
+async fn renew_subscription(
+    pool: &PgPool,
+    cfg: &Config,
+    registry: &PaymentPluginRegistry,
+    subscription_id: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let Some(row) = sqlx::query(&format!(
+        "SELECT s.user_id, s.billing_method, s.provider, s.provider_payment_ref,
+                s.current_period_end, s.failed_attempts,
+                t.id AS tier_id, t.creator_id, t.name, t.price_cents, t.active
+         FROM subscriptions s
+         JOIN subscription_tiers t ON t.id = s.tier_id
+         WHERE s.id = $2 AND {RENEWAL_DUE}
+         FOR UPDATE OF s SKIP LOCKED"
+    ))
+    .bind(RETRY_INTERVAL_HOURS)
+    .bind(subscription_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let user_id: String = row.try_get("user_id").unwrap_or_default();
+    let billing_method: String = row.try_get("billing_method").unwrap_or_default();
+    let tier_id: String = row.try_get("tier_id").unwrap_or_default();
+    let creator_id: String = row.try_get("creator_id").unwrap_or_default();
+    let tier_name: String = row.try_get("name").unwrap_or_default();
+    let price_cents: i64 = row.try_get("price_cents").unwrap_or(0);
+
+    // A deactivated tier no longer renews; access runs out with the period.
+    if !row.try_get::<bool, _>("active").unwrap_or(false) {
+        sqlx::query(
+            "UPDATE subscriptions SET status = 'expired', updated_at = NOW() WHERE id = $1",
+        )
+        .bind(subscription_id)
+        .execute(&mut *tx)
+        .await?;
+        entitlements::sync_subscription(&mut tx, subscription_id).await?;
+        tx.commit().await?;
+        return Ok(());
+    }
+
+    let (method, outcome): (String, Result<Option<String>, String>) = if billing_method == "wallet"
+    {
+        // A declined debit is undone alone; the failure is recorded in `tx`.
+        let mut debit = (&mut tx).begin().await?;
+        let charged = settle_wallet_sale(
+            &mut debit,
+            &user_id,
+            &creator_id,
+            price_cents,
+            creator_cut(cfg, price_cents),
+            &format!("Subscription renewal: {tier_name}"),
+            &format!("Subscription sale: {tier_id}"),
+        )
+        .await
+        .map(|_| None);
+        if charged.is_ok() {
+            debit.commit().await?;
+        } else {
+            debit.rollback().await?;
+        }
+        ("wallet".to_string(), charged)
+    } else {
+        let provider: String = row
+            .try_get::<Option<String>, _>("provider")
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let payment_ref: Option<String> = row
+            .try_get::<Option<String>, _>("provider_payment_ref")
+            .ok()
+            .flatten();
+        // Renewals of a Stripe Connect creator are split like the checkout.
+        let mut metadata: HashMap<String, String> =
+            [("tier_id".to_string(), tier_id.clone())].into();
+        if provider == "stripe" {
+            if let Some(account) = stripe_connect::charge_destination(pool, &creator_id).await {
+                let fee = stripe_connect::application_fee(price_cents, cfg.creator_split_bp);
+                stripe_connect::set_split_metadata(&mut metadata, Some((&account, fee)));
+            }
+        }
+        // One charge per period and attempt: a renewal that is run again
+        // (crash before commit, second worker) reuses the key.
+        let period_end: chrono::DateTime<chrono::Utc> =
+            row.try_get("current_period_end").unwrap_or_default();
+        let failed_attempts: i32 = row.try_get("failed_attempts").unwrap_or(0);
+        let idempotency_key = format!(
+            "sub-{subscription_id}-{}-{failed_attempts}",
+            period_end.timestamp()
+        );
+        let charged = match (registry.get(&provider), payment_ref) {
+            (Some(plugin), Some(payment_ref)) if plugin.capability().supports_recurring => plugin
+                .charge_recurring(RecurringChargeRequest {
+                    user_id: user_id.clone(),
+                    subscription_id: subscription_id.to_string(),
+                    amount_cents: price_cents,
+                    currency: "USD".into(),
+                    payment_ref,
+                    idempotency_key,
+                    metadata,
+                })
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|r| match r.status {
+                    PaymentStatus::Paid => Ok(r.transaction_id),
+                    other => Err(format!("charge status {other:?}")),
+                }),
+            (Some(_), None) => Err("no saved payment reference".to_string()),
+            _ => Err(format!("provider {provider} cannot renew subscriptions")),
+        };
+        (format!("fiat:{provider}"), charged)
+    };
+
+    match outcome {
+        Ok(reference) => {
+            // A lapsed subscription (past due, or renewed late) restarts from now
+            // rather than charging again for time already missed.
+            let period: Period = sqlx::query_as(
+                r#"
+                UPDATE subscriptions s
+                SET current_period_start = GREATEST(s.current_period_end, NOW()),
+                    current_period_end = GREATEST(s.current_period_end, NOW())
+                      + make_interval(days => t.interval_days),
+                    status = 'active', grace_until = NULL, failed_attempts = 0,
+                    last_attempt_at = NOW(), updated_at = NOW()
+                FROM subscription_tiers t
+                WHERE s.id = $1 AND t.id = s.tier_id
+                RETURNING s.current_period_start, s.current_period_end
+                "#,
+            )
+            .bind(subscription_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            record_charge(
+                &mut tx,
+                subscription_id,
+                price_cents,
+                &method,
+                Ok(period),
+                reference.as_deref(),
+            )
+            .await?;
+            entitlements::sync_subscription(&mut tx, subscription_id).await?;
+            tx.commit().await?;
+            tracing::info!(%subscription_id, %method, "subscription renewed");
+        }
+        Err(error) => {
+            sqlx::query(
+                r#"
+                UPDATE subscriptions
+                SET status = 'past_due',
+                    grace_until = COALESCE(grace_until,
+                                           current_period_end + make_interval(days => $2)),
+                    failed_attempts = failed_attempts + 1,
+                    last_attempt_at = NOW(), updated_at = NOW()
+                WHERE id = $1
+                "#,
+            )
+            .bind(subscription_id)
+            .bind(cfg.subscription_grace_days)
+            .execute(&mut *tx)
+            .await?;
+            entitlements::sync_subscription(&mut tx, subscription_id).await?;
+            record_charge(
+                &mut tx,
+                subscription_id,
+                price_cents,
+                &method,
+                Err(&error),
+                None,
+            )
+            .await?;
+            tx.commit().await?;
+            tracing::warn!(%subscription_id, %method, %error, "subscription renewal declined");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(scope: &str, tags: &[&str]) -> TierFields {
+        TierFields {
+            name: "Supporter".into(),
+            price_cents: 500,
+            interval_days: 30,
+            access_scope: scope.into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn tagged_tiers_need_tags() {
+        assert!(validate_tier(&fields("all", &[])).is_ok());
+        assert!(validate_tier(&fields("tagged", &["behind-the-scenes"])).is_ok());
+        assert!(validate_tier(&fields("tagged", &[])).is_err());
+        assert!(validate_tier(&fields("everything", &[])).is_err());
+    }
+}
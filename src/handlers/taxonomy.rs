@@ -0,0 +1,383 @@
+// src/handlers/taxonomy.rs
+//
+// Categories and tags.
+//
+// Categories are curated by admins; each video belongs to at most one.
+// Tags are free-form labels chosen by the creator through `/api/video_update`.
+// Both can be used as filters on `GET /api/videos` (`category=<slug>`,
+// `tag=<tag>`), and tags are published on federated `Video` objects.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgPool, Row};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::handlers::video::{VideoState, LISTED_VIDEO_SQL};
+use crate::sessions;
+
+/// Maximum number of tags on one video.
+pub const MAX_TAGS_PER_VIDEO: usize = 20;
+const MAX_TAG_CHARS: usize = 40;
+const MAX_CATEGORY_NAME_CHARS: usize = 80;
+
+/// Normalises one tag: trims, drops a leading `#`, lower-cases, and maps
+/// spaces to `-`. Returns `None` for empty or invalid tags.
+pub fn normalize_tag(raw: &str) -> Option<String> {
+    let tag: String = raw
+        .trim()
+        .trim_start_matches('#')
+        .trim()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-");
+    let valid = !tag.is_empty()
+        && tag.chars().count() <= MAX_TAG_CHARS
+        && tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(tag)
+}
+
+/// Parses a comma separated tag list into unique, normalised tags.
+pub fn parse_tags(csv: &str) -> Result<Vec<String>, String> {
+    let mut tags: Vec<String> = Vec::new();
+    for raw in csv.split(',').filter(|t| !t.trim().is_empty()) {
+        let tag = normalize_tag(raw).ok_or_else(|| {
+            format!(
+                "invalid tag '{}': use letters, digits, '-' or '_' (max {MAX_TAG_CHARS} chars)",
+                raw.trim()
+            )
+        })?;
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    if tags.len() > MAX_TAGS_PER_VIDEO {
+        return Err(format!("at most {MAX_TAGS_PER_VIDEO} tags per video"));
+    }
+    Ok(tags)
+}
+
+/// URL slug for a category name: lower-case ASCII letters and digits joined
+/// by single dashes.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Replaces every tag on a video.
+pub(crate) async fn replace_video_tags(
+    pool: &PgPool,
+    video_id: &str,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM video_tags WHERE video_id = $1")
+        .bind(video_id)
+        .execute(&mut *tx)
+        .await?;
+    for tag in tags {
+        sqlx::query(
+            "INSERT INTO video_tags (video_id, tag) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(video_id)
+        .bind(tag)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Tags of one video, alphabetically.
+pub(crate) async fn video_tags(pool: &PgPool, video_id: &str) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT tag FROM video_tags WHERE video_id = $1 ORDER BY tag")
+        .bind(video_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// GET /api/categories
+///
+/// Every category in display order with the number of publicly listed videos.
+pub async fn list_categories(State(st): State<VideoState>) -> impl IntoResponse {
+    let sql = format!(
+        r#"
+        SELECT c.id, c.slug, c.name, c.description, c.position,
+               (SELECT COUNT(*) FROM videos v
+                 WHERE v.category_id = c.id AND {LISTED_VIDEO_SQL}) AS video_count
+        FROM categories c
+        ORDER BY c.position ASC, c.name ASC
+        "#
+    );
+    let rows = match sqlx::query(&sql).fetch_all(&st.pool).await {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let categories: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|r| {
+            json!({
+                "id": r.try_get::<String, _>("id").unwrap_or_default(),
+                "slug": r.try_get::<String, _>("slug").unwrap_or_default(),
+                "name": r.try_get::<String, _>("name").unwrap_or_default(),
+                "description": r.try_get::<String, _>("description").unwrap_or_default(),
+                "position": r.try_get::<i32, _>("position").unwrap_or(0),
+                "video_count": r.try_get::<i64, _>("video_count").unwrap_or(0),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "categories": categories}))
+}
+
+#[derive(Deserialize)]
+pub struct TagListQs {
+    pub limit: Option<i64>,
+}
+
+/// GET /api/tags?limit=50
+///
+/// Most used tags across publicly listed videos.
+pub async fn list_tags(
+    State(st): State<VideoState>,
+    Query(qs): Query<TagListQs>,
+) -> impl IntoResponse {
+    let limit = qs.limit.unwrap_or(50).clamp(1, 200);
+    let sql = format!(
+        r#"
+        SELECT vt.tag, COUNT(*) AS video_count
+        FROM video_tags vt
+        JOIN videos v ON v.id = vt.video_id
+        WHERE {LISTED_VIDEO_SQL}
+        GROUP BY vt.tag
+        ORDER BY video_count DESC, vt.tag ASC
+        LIMIT $1
+        "#
+    );
+    let rows = match sqlx::query(&sql).bind(limit).fetch_all(&st.pool).await {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let tags: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|r| {
+            json!({
+                "tag": r.try_get::<String, _>("tag").unwrap_or_default(),
+                "video_count": r.try_get::<i64, _>("video_count").unwrap_or(0),
+            })
+        })
+        .collect();
+
+    Json(json!({"ok": true, "tags": tags}))
+}
+
+#[derive(Deserialize)]
+pub struct CategoryPayload {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub position: Option<i32>,
+}
+
+async fn require_admin(
+    st: &VideoState,
+    cookies: &Cookies,
+) -> Result<String, Json<serde_json::Value>> {
+    match sessions::current_user_id(&st.pool, &st.cfg, cookies).await {
+        Some((user_id, true)) => Ok(user_id),
+        Some(_) => Err(Json(json!({"ok": false, "error": "admin only"}))),
+        None => Err(Json(json!({"ok": false, "error": "not logged in"}))),
+    }
+}
+
+fn validate_category(name: &str, slug: &str) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > MAX_CATEGORY_NAME_CHARS {
+        return Err(format!(
+            "name is required (max {MAX_CATEGORY_NAME_CHARS} characters)"
+        ));
+    }
+    if slug.is_empty() || slug != slugify(slug) {
+        return Err("slug must contain lower-case letters, digits, and dashes".to_string());
+    }
+    Ok(())
+}
+
+/// POST /admin/categories
+pub async fn admin_create_category(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Json(p): Json<CategoryPayload>,
+) -> impl IntoResponse {
+    let admin_user_id = match require_admin(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    let name = p.name.as_deref().unwrap_or_default().trim().to_string();
+    let slug = p
+        .slug
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| slugify(&name));
+    if let Err(e) = validate_category(&name, &slug) {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let res = sqlx::query(
+        "INSERT INTO categories (id, slug, name, description, position) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(&id)
+    .bind(&slug)
+    .bind(&name)
+    .bind(p.description.as_deref().unwrap_or_default().trim())
+    .bind(p.position.unwrap_or(0))
+    .execute(&st.pool)
+    .await;
+
+    match res {
+        Ok(_) => {
+            tracing::info!(admin_user_id = %admin_user_id, action = "category_create", category_id = %id, slug = %slug);
+            Json(json!({"ok": true, "id": id, "slug": slug}))
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Json(json!({"ok": false, "error": "slug already exists"}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// POST /admin/categories/:id
+///
+/// Absent fields are left unchanged.
+pub async fn admin_update_category(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+    Json(p): Json<CategoryPayload>,
+) -> impl IntoResponse {
+    let admin_user_id = match require_admin(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    let current = match sqlx::query("SELECT name, slug FROM categories WHERE id = $1")
+        .bind(&id)
+        .fetch_optional(&st.pool)
+        .await
+    {
+        Ok(Some(row)) => row,
+        Ok(None) => return Json(json!({"ok": false, "error": "not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let name = p
+        .name
+        .as_deref()
+        .map(|n| n.trim().to_string())
+        .unwrap_or_else(|| current.try_get("name").unwrap_or_default());
+    let slug = p
+        .slug
+        .as_deref()
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|| current.try_get("slug").unwrap_or_default());
+    if let Err(e) = validate_category(&name, &slug) {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let res = sqlx::query(
+        r#"
+        UPDATE categories
+        SET name = $2, slug = $3,
+            description = COALESCE($4, description),
+            position = COALESCE($5, position),
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(&id)
+    .bind(&name)
+    .bind(&slug)
+    .bind(p.description.as_deref().map(str::trim))
+    .bind(p.position)
+    .execute(&st.pool)
+    .await;
+
+    match res {
+        Ok(_) => {
+            tracing::info!(admin_user_id = %admin_user_id, action = "category_update", category_id = %id);
+            Json(json!({"ok": true}))
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Json(json!({"ok": false, "error": "slug already exists"}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// POST /admin/categories/:id/delete
+///
+/// Videos in the category become uncategorised.
+pub async fn admin_delete_category(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let admin_user_id = match require_admin(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+
+    match sqlx::query("DELETE FROM categories WHERE id = $1")
+        .bind(&id)
+        .execute(&st.pool)
+        .await
+    {
+        Ok(r) if r.rows_affected() > 0 => {
+            tracing::info!(admin_user_id = %admin_user_id, action = "category_delete", category_id = %id);
+            Json(json!({"ok": true}))
+        }
+        Ok(_) => Json(json!({"ok": false, "error": "not found"})),
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_normalised_and_deduplicated() {
+        assert_eq!(normalize_tag(" #Rust "), Some("rust".into()));
+        assert_eq!(normalize_tag("Live Coding"), Some("live-coding".into()));
+        assert_eq!(normalize_tag("<script>"), None);
+        assert_eq!(
+            parse_tags("Rust, #rust, belajar_rust,,").unwrap(),
+            vec!["rust".to_string(), "belajar_rust".to_string()]
+        );
+        assert!(parse_tags("a/b").is_err());
+    }
+
+    #[test]
+    fn slugs_are_url_safe() {
+        assert_eq!(slugify("  Music & Concerts "), "music-concerts");
+        assert_eq!(slugify("Olahraga"), "olahraga");
+    }
+}
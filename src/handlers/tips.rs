@@ -0,0 +1,364 @@
+// src/handlers/tips.rs
+//
+// Pay-what-you-want pricing and creator tips.
+//
+// A video with `pay_what_you_want` set treats `price_cents` as a minimum (never
+// below `MIN_PWYW_CENTS`); buyers send `amount_cents` with the wallet, plugin,
+// or x402 checkout and the sale is split and commissioned on that amount.
+// Rentals keep their fixed price.
+//
+// Tips go straight to a creator from their profile. Wallet tips settle at once
+// through `settle_wallet_sale`; plugin tips (`tip_to` on the invoice payload)
+// stay `pending` until the webhook calls [`credit_plugin_tip`], which credits
+// the creator's wallet. Either way the creator receives `creator_split_bp` of
+// the tip and a `transfer_in` row in their wallet ledger.
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgConnection, PgPool, Row};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::handlers::gifts::clean_gift_message;
+use crate::handlers::video::VideoState;
+use crate::handlers::wallet::settle_wallet_sale;
+use crate::payment_settings::load_payment_settings;
+use crate::sessions;
+
+/// Lowest amount a pay-what-you-want video sells for.
+pub const MIN_PWYW_CENTS: i64 = 100; // $1
+/// Lowest tip.
+pub const MIN_TIP_CENTS: i64 = 100; // $1
+/// Highest buyer-chosen amount, for tips and pay-what-you-want alike.
+pub const MAX_CHOSEN_CENTS: i64 = 1_000_000; // $10,000
+
+/// Creator share of `amount_cents` under `creator_split_bp`.
+pub(crate) fn creator_share(amount_cents: i64, creator_split_bp: u16) -> i64 {
+    ((amount_cents as i128).saturating_mul(creator_split_bp as i128) / 10_000) as i64
+}
+
+/// Minimum price of a pay-what-you-want video.
+pub(crate) fn pwyw_minimum(price_cents: i64) -> i64 {
+    price_cents.max(MIN_PWYW_CENTS)
+}
+
+/// Amount a buyer pays for a pay-what-you-want video.
+///
+/// A missing or non-positive offer pays the minimum.
+pub(crate) fn pwyw_amount(price_cents: i64, offered: Option<i64>) -> Result<i64, String> {
+    let minimum = pwyw_minimum(price_cents);
+    match offered.filter(|a| *a > 0) {
+        None => Ok(minimum),
+        Some(a) => check_chosen_amount(a, minimum),
+    }
+}
+
+fn check_chosen_amount(amount_cents: i64, minimum: i64) -> Result<i64, String> {
+    if amount_cents < minimum {
+        return Err(format!("amount_cents must be at least {minimum}"));
+    }
+    if amount_cents > MAX_CHOSEN_CENTS {
+        return Err(format!("amount_cents must be at most {MAX_CHOSEN_CENTS}"));
+    }
+    Ok(amount_cents)
+}
+
+/// Validates a tip amount.
+pub(crate) fn tip_amount(amount_cents: i64) -> Result<i64, String> {
+    check_chosen_amount(amount_cents, MIN_TIP_CENTS)
+}
+
+/// A creator that can be tipped, looked up by username.
+pub(crate) struct TipTarget {
+    pub creator_id: String,
+    pub username: String,
+}
+
+/// Looks up the creator to tip; users cannot tip themselves.
+pub(crate) async fn load_tip_target(
+    pool: &PgPool,
+    username: &str,
+    tipper_id: &str,
+) -> Result<TipTarget, String> {
+    let row = sqlx::query("SELECT id, username FROM users WHERE username = $1 LIMIT 1")
+        .bind(username.trim())
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("db: {e}"))?
+        .ok_or_else(|| "creator not found".to_string())?;
+    let target = TipTarget {
+        creator_id: row.try_get("id").unwrap_or_default(),
+        username: row.try_get("username").unwrap_or_default(),
+    };
+    if target.creator_id == tipper_id {
+        return Err("you cannot tip yourself".to_string());
+    }
+    Ok(target)
+}
+
+/// Records a tip awaiting plugin payment and returns its id.
+pub(crate) async fn create_pending_tip(
+    pool: &PgPool,
+    target: &TipTarget,
+    tipper_id: &str,
+    amount_cents: i64,
+    creator_split_bp: u16,
+    provider: &str,
+    message: &str,
+) -> Result<String, sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO tips (id, creator_id, tipper_id, amount_cents, creator_cents, method, message)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(&id)
+    .bind(&target.creator_id)
+    .bind(tipper_id)
+    .bind(amount_cents)
+    .bind(creator_share(amount_cents, creator_split_bp))
+    .bind(format!("fiat:{provider}"))
+    .bind(message)
+    .execute(pool)
+    .await?;
+    Ok(id)
+}
+
+/// Drops a pending tip whose plugin checkout failed before the tipper reached
+/// the provider.
+pub(crate) async fn discard_pending_tip(pool: &PgPool, tip_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM tips WHERE id = $1 AND status = 'pending'")
+        .bind(tip_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Marks a plugin tip paid and credits the creator's share to their wallet,
+/// on the caller's transaction.
+///
+/// Returns `false` when the tip was already paid (replayed webhook).
+pub(crate) async fn credit_plugin_tip(
+    conn: &mut PgConnection,
+    tip_id: &str,
+) -> Result<bool, String> {
+    let Some(tip) = sqlx::query(
+        r#"
+        UPDATE tips SET status = 'paid', paid_at = NOW()
+        WHERE id = $1 AND status = 'pending'
+        RETURNING creator_id, tipper_id, creator_cents, method
+        "#,
+    )
+    .bind(tip_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| format!("db: {e}"))?
+    else {
+        return Ok(false);
+    };
+    let creator_id: String = tip.try_get("creator_id").unwrap_or_default();
+    let tipper_id: String = tip.try_get("tipper_id").unwrap_or_default();
+    let creator_cents: i64 = tip.try_get("creator_cents").unwrap_or(0);
+    let method: String = tip.try_get("method").unwrap_or_default();
+
+    if creator_cents > 0 {
+        let balance: i64 = sqlx::query_scalar(
+            "UPDATE users SET balance_cents = balance_cents + $1 WHERE id = $2 \
+             RETURNING balance_cents",
+        )
+        .bind(creator_cents)
+        .bind(&creator_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("db creator: {e}"))?;
+
+        sqlx::query(
+            "INSERT INTO wallet_transactions (user_id,txn_type,amount_cents,balance_after,status,ref_user_id,note) \
+             VALUES ($1,'transfer_in',$2,$3,'completed',$4,$5)",
+        )
+        .bind(&creator_id)
+        .bind(creator_cents)
+        .bind(balance)
+        .bind(&tipper_id)
+        .bind(format!("Tip received ({method}): {tip_id}"))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("ledger creator: {e}"))?;
+    }
+
+    Ok(true)
+}
+
+#[derive(Deserialize)]
+pub struct TipPayload {
+    pub amount_cents: i64,
+    pub message: Option<String>,
+}
+
+/// POST /api/creators/:username/tip
+///
+/// Tips a creator from the wallet.
+pub async fn send_tip(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Path(username): Path<String>,
+    Json(p): Json<TipPayload>,
+) -> impl IntoResponse {
+    if !load_payment_settings(&st.pool).await.wallet_payment_enabled {
+        return Json(
+            json!({"ok": false, "error": "wallet payment is currently disabled by admin"}),
+        );
+    }
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+    let amount_cents = match tip_amount(p.amount_cents) {
+        Ok(a) => a,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let message = match clean_gift_message(p.message.as_deref()) {
+        Ok(m) => m,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let target = match load_tip_target(&st.pool, &username, &uid).await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    let creator_cents = creator_share(amount_cents, st.cfg.creator_split_bp);
+
+    let mut tx = match st.pool.begin().await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": format!("begin tx: {e}")})),
+    };
+    let tip_id = Uuid::new_v4().to_string();
+    let balance = match settle_wallet_sale(
+        &mut tx,
+        &uid,
+        &target.creator_id,
+        amount_cents,
+        creator_cents,
+        &format!("Tip to {}: {tip_id}", target.username),
+        &format!("Tip received (wallet): {tip_id}"),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": e}));
+        }
+    };
+
+    if let Err(e) = sqlx::query(
+        r#"
+        INSERT INTO tips
+          (id, creator_id, tipper_id, amount_cents, creator_cents, method, message, status, paid_at)
+        VALUES ($1, $2, $3, $4, $5, 'wallet', $6, 'paid', NOW())
+        "#,
+    )
+    .bind(&tip_id)
+    .bind(&target.creator_id)
+    .bind(&uid)
+    .bind(amount_cents)
+    .bind(creator_cents)
+    .bind(&message)
+    .execute(&mut *tx)
+    .await
+    {
+        let _ = tx.rollback().await;
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+
+    match tx.commit().await {
+        Ok(_) => Json(json!({
+            "ok": true,
+            "tip_id": tip_id,
+            "creator": target.username,
+            "amount_cents": amount_cents,
+            "balance_cents": balance,
+        })),
+        Err(e) => Json(json!({"ok": false, "error": format!("commit: {e}")})),
+    }
+}
+
+/// GET /api/my_tips
+///
+/// Paid tips the current user sent and received, with the received total.
+pub async fn my_tips(State(st): State<VideoState>, cookies: Cookies) -> impl IntoResponse {
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let rows = match sqlx::query(
+        r#"
+        SELECT t.id, t.creator_id, c.username AS creator, f.username AS tipper,
+               t.amount_cents, t.creator_cents, t.method, t.message,
+               t.paid_at::text AS paid_at
+        FROM tips t
+        JOIN users c ON c.id = t.creator_id
+        JOIN users f ON f.id = t.tipper_id
+        WHERE t.status = 'paid' AND (t.creator_id = $1 OR t.tipper_id = $1)
+        ORDER BY t.paid_at DESC
+        LIMIT 200
+        "#,
+    )
+    .bind(&uid)
+    .fetch_all(&st.pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let (mut sent, mut received) = (Vec::new(), Vec::new());
+    let mut received_cents = 0i64;
+    for r in &rows {
+        let mut item = json!({
+            "id": r.try_get::<String, _>("id").unwrap_or_default(),
+            "to": r.try_get::<String, _>("creator").unwrap_or_default(),
+            "from": r.try_get::<String, _>("tipper").unwrap_or_default(),
+            "amount_cents": r.try_get::<i64, _>("amount_cents").unwrap_or(0),
+            "method": r.try_get::<String, _>("method").unwrap_or_default(),
+            "message": r.try_get::<String, _>("message").unwrap_or_default(),
+            "paid_at": r.try_get::<Option<String>, _>("paid_at").ok().flatten(),
+        });
+        if r.try_get::<String, _>("creator_id").unwrap_or_default() == uid {
+            let creator_cents = r.try_get::<i64, _>("creator_cents").unwrap_or(0);
+            received_cents += creator_cents;
+            item["creator_cents"] = json!(creator_cents);
+            received.push(item);
+        } else {
+            sent.push(item);
+        }
+    }
+    Json(json!({
+        "ok": true,
+        "sent": sent,
+        "received": received,
+        "received_creator_cents": received_cents,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pay_what_you_want_respects_the_minimum() {
+        assert_eq!(pwyw_amount(0, None), Ok(MIN_PWYW_CENTS));
+        assert_eq!(pwyw_amount(500, Some(0)), Ok(500));
+        assert_eq!(pwyw_amount(500, Some(750)), Ok(750));
+        assert!(pwyw_amount(500, Some(499)).is_err());
+        assert!(pwyw_amount(500, Some(MAX_CHOSEN_CENTS + 1)).is_err());
+        assert!(tip_amount(MIN_TIP_CENTS - 1).is_err());
+        assert_eq!(creator_share(1_000, 9_000), 900);
+    }
+}
@@ -5,11 +5,15 @@
 // This module is responsible for:
 // 1. Authenticating the uploader.
 // 2. Reading multipart form fields without loading the whole video into memory.
-// 3. Validating file extension, size, MIME type, title, and price.
+// 3. Validating file extension, size, MIME type, title, price, visibility,
+//    and the optional publish/unpublish schedule.
 // 4. Rejecting requests that contain more than one video file.
 // 5. Writing the upload safely through a temporary `.part` file.
 // 6. Inserting the video metadata into PostgreSQL.
 // 7. Enqueuing a background transcoding job.
+// 8. Sharing the validation and insert/enqueue handoff with `handlers::import`.
+// 9. Holding the `.part` file in quarantine (see `crate::quarantine`) until it
+//    has been fully decoded and, optionally, cleared by the content scanner.
 
 use axum::{
     extract::{Multipart, State},
@@ -18,6 +22,7 @@ use axum::{
     Json,
 };
 use bytesize::ByteSize;
+use chrono::{DateTime, Utc};
 use serde_json::json;
 use sqlx::PgPool;
 use std::path::{Path, PathBuf};
@@ -30,11 +35,10 @@ use tower_cookies::Cookies;
 use tracing::info;
 use uuid::Uuid;
 
-use std::sync::Arc;
-
 use crate::{
     config::Config,
-    plugins::storage::StoragePlugin,
+    handlers::video::{parse_schedule_field, parse_visibility},
+    quarantine::{Quarantine, Rejection},
     sessions,
     worker::{TranscodeJob, Worker},
 };
@@ -46,7 +50,7 @@ pub struct UploadState {
     pub cfg: Config,
     pub pool: PgPool,
     pub worker: Worker,
-    pub storage: Arc<dyn StoragePlugin>,
+    pub quarantine: Quarantine,
 }
 
 pub async fn upload_video(
@@ -65,11 +69,7 @@ pub async fn upload_video(
         }
     };
 
-    let upload_dir = if !st.cfg.upload_dir.is_empty() {
-        st.cfg.upload_dir.clone()
-    } else {
-        st.cfg.storage_dir.clone()
-    };
+    let upload_dir = resolve_upload_dir(&st.cfg);
 
     if let Err(e) = fs::create_dir_all(&upload_dir).await {
         return (
@@ -81,18 +81,16 @@ pub async fn upload_video(
 
     let mut title = String::from("Untitled");
     let mut price_cents: i64 = 0;
+    let mut visibility_raw: Option<String> = None;
+    let mut publish_at_raw: Option<String> = None;
+    let mut unpublish_at_raw: Option<String> = None;
     let mut file_count: u32 = 0;
     let video_id = Uuid::new_v4().to_string();
     let mut saved_path: Option<PathBuf> = None;
     let mut saved_filename_only: Option<String> = None;
     let mut total_bytes: u64 = 0;
     let max_bytes = st.cfg.max_upload_bytes;
-    let allowed_extensions: Vec<String> = st
-        .cfg
-        .allow_exts
-        .iter()
-        .map(|value| value.to_ascii_lowercase())
-        .collect();
+    let allowed_extensions = allowed_extensions(&st.cfg);
 
     while let Some(field) = match multipart.next_field().await {
         Ok(field) => field,
@@ -124,6 +122,14 @@ pub async fn upload_video(
                     .parse()
                     .unwrap_or(0);
             }
+            "visibility" | "publish_at" | "unpublish_at" => {
+                let value = field.text().await.unwrap_or_default();
+                match field_name.as_str() {
+                    "visibility" => visibility_raw = Some(value),
+                    "publish_at" => publish_at_raw = Some(value),
+                    _ => unpublish_at_raw = Some(value),
+                }
+            }
             "file" => {
                 file_count += 1;
                 if file_count > 1 {
@@ -187,7 +193,6 @@ pub async fn upload_video(
                 };
 
                 let mut output = BufWriter::with_capacity(1024 * 1024, output_file);
-                let mut first_sniff: Option<Vec<u8>> = None;
 
                 while let Some(chunk_result) = file_field.chunk().await.transpose() {
                     match chunk_result {
@@ -210,10 +215,6 @@ pub async fn upload_video(
                                     .into_response();
                             }
 
-                            if first_sniff.is_none() {
-                                first_sniff = Some(bytes[..bytes.len().min(8192)].to_vec());
-                            }
-
                             if let Err(e) = output.write_all(&bytes).await {
                                 let _ = fs::remove_file(&temporary_path).await;
                                 return (
@@ -243,40 +244,25 @@ pub async fn upload_video(
                         .into_response();
                 }
 
-                let Some(buffer) = first_sniff else {
+                if total_bytes == 0 {
                     let _ = fs::remove_file(&temporary_path).await;
                     return (
                         StatusCode::BAD_REQUEST,
                         Json(json!({"ok": false, "where": "validation", "error": "empty file"})),
                     )
                         .into_response();
-                };
-
-                let Some(kind) = infer::get(&buffer) else {
-                    let _ = fs::remove_file(&temporary_path).await;
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        Json(json!({
-                            "ok": false,
-                            "where": "validation",
-                            "error": "unable to detect a supported video MIME type"
-                        })),
-                    )
-                        .into_response();
-                };
+                }
 
-                if !kind.mime_type().starts_with("video/") {
-                    let mime_type = kind.mime_type().to_string();
+                if let Err(rejection) = st.quarantine.inspect(&temporary_path, &extension).await {
                     let _ = fs::remove_file(&temporary_path).await;
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        Json(json!({
-                            "ok": false,
-                            "where": "validation",
-                            "error": format!("uploaded content is not a video: {}", mime_type)
-                        })),
-                    )
-                        .into_response();
+                    tracing::warn!(
+                        video_id = %video_id,
+                        owner_id = %user_id,
+                        code = rejection.code,
+                        "upload rejected in quarantine: {}",
+                        rejection.reason
+                    );
+                    return quarantine_rejection_response(&rejection);
                 }
 
                 if let Err(e) = fs::rename(&temporary_path, &full_path).await {
@@ -337,6 +323,31 @@ pub async fn upload_video(
             .into_response();
     }
 
+    let schedule = parse_visibility(visibility_raw.as_deref()).and_then(|visibility| {
+        let publish_at = parse_schedule_field("publish_at", publish_at_raw.as_deref())?.flatten();
+        let unpublish_at =
+            parse_schedule_field("unpublish_at", unpublish_at_raw.as_deref())?.flatten();
+        if let (Some(from), Some(until)) = (publish_at, unpublish_at) {
+            if until <= from {
+                return Err("unpublish_at must be later than publish_at".to_string());
+            }
+        }
+        Ok((visibility.unwrap_or("public"), publish_at, unpublish_at))
+    });
+    let (visibility, publish_at, unpublish_at) = match schedule {
+        Ok(v) => v,
+        Err(message) => {
+            if let Some(path) = saved_path.as_ref() {
+                let _ = fs::remove_file(path).await;
+            }
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"ok": false, "where": "validation", "error": message})),
+            )
+                .into_response();
+        }
+    };
+
     let (saved_path, saved_filename_only) = match (saved_path, saved_filename_only) {
         (Some(path), Some(filename)) => (path, filename),
         _ => {
@@ -352,87 +363,160 @@ pub async fn upload_video(
         }
     };
 
+    if let Err((stage, message)) = register_video(
+        &st,
+        NewVideo {
+            video_id: &video_id,
+            owner_id: &user_id,
+            title: &title,
+            price_cents,
+            filename: &saved_filename_only,
+            path: &saved_path,
+            visibility,
+            publish_at,
+            unpublish_at,
+        },
+    )
+    .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"ok": false, "where": stage, "error": message})),
+        )
+            .into_response();
+    }
+
+    info!(
+        "upload ok: video_id={}, size={}",
+        video_id,
+        ByteSize(total_bytes)
+    );
+
+    (
+        StatusCode::CREATED,
+        Json(json!({
+            "ok": true,
+            "video_id": video_id,
+            "owner_id": user_id,
+            "filename": saved_filename_only,
+            "status": "quarantined",
+            "visibility": visibility,
+            "message": "Upload received. The video is being checked and will then be processed into HLS."
+        })),
+    )
+        .into_response()
+}
+
+/// Directory that receives original uploads before transcoding.
+pub(crate) fn resolve_upload_dir(cfg: &Config) -> String {
+    if !cfg.upload_dir.is_empty() {
+        cfg.upload_dir.clone()
+    } else {
+        cfg.storage_dir.clone()
+    }
+}
+
+/// Lower-cased `ALLOW_EXTS` whitelist.
+pub(crate) fn allowed_extensions(cfg: &Config) -> Vec<String> {
+    cfg.allow_exts
+        .iter()
+        .map(|value| value.to_ascii_lowercase())
+        .collect()
+}
+
+/// Maps a quarantine rejection to the upload error response. Scanner outages
+/// are reported as 503 so clients can retry; everything else is a verdict on
+/// the file itself.
+pub(crate) fn quarantine_rejection_response(rejection: &Rejection) -> axum::response::Response {
+    let status = if rejection.is_scanner_failure() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    (
+        status,
+        Json(json!({
+            "ok": false,
+            "where": "quarantine",
+            "code": rejection.code,
+            "error": rejection.reason
+        })),
+    )
+        .into_response()
+}
+
+/// A validated source file that already sits in the upload directory under
+/// its final `<video_id>.<ext>` name.
+pub(crate) struct NewVideo<'a> {
+    pub video_id: &'a str,
+    pub owner_id: &'a str,
+    pub title: &'a str,
+    pub price_cents: i64,
+    pub filename: &'a str,
+    pub path: &'a Path,
+    /// One of `handlers::video::VISIBILITY_STATES`.
+    pub visibility: &'a str,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub unpublish_at: Option<DateTime<Utc>>,
+}
+
+/// Inserts the `videos` row as `quarantined` and enqueues the transcoding
+/// job, which runs the full decode check before pushing the original to
+/// remote storage and transcoding it.
+///
+/// Shared by the multipart upload endpoint and the bulk importer. On failure
+/// the returned tuple carries the `where` stage and the error message; the
+/// source file is removed when the row could not be inserted.
+pub(crate) async fn register_video(
+    st: &UploadState,
+    video: NewVideo<'_>,
+) -> Result<(), (&'static str, String)> {
     let created_at = chrono::Utc::now().to_rfc3339();
     if let Err(e) = sqlx::query!(
         r#"
         INSERT INTO videos
-            (id, owner_id, title, price_cents, filename, created_at, hls_ready, processing_state)
+            (id, owner_id, title, price_cents, filename, created_at, hls_ready, processing_state,
+             visibility, publish_at, unpublish_at)
         VALUES
-            ($1, $2, $3, $4, $5, $6, FALSE, 'queued')
+            ($1, $2, $3, $4, $5, $6, FALSE, 'quarantined', $7, $8, $9)
         "#,
-        video_id,
-        &user_id,
-        title,
-        price_cents,
-        saved_filename_only,
-        created_at
+        video.video_id,
+        video.owner_id,
+        video.title,
+        video.price_cents,
+        video.filename,
+        created_at,
+        video.visibility,
+        video.publish_at as _,
+        video.unpublish_at as _
     )
     .execute(&st.pool)
     .await
     {
-        let _ = fs::remove_file(&saved_path).await;
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"ok": false, "where": "db_insert_videos", "error": e.to_string()})),
-        )
-            .into_response();
+        let _ = fs::remove_file(video.path).await;
+        return Err(("db_insert_videos", e.to_string()));
     }
 
-    let output_dir = st.cfg.video_hls_dir(&video_id);
+    let output_dir = st.cfg.video_hls_dir(video.video_id);
     if let Err(e) = st
         .worker
         .enqueue(TranscodeJob {
-            video_id: video_id.clone(),
-            input_path: saved_path.to_string_lossy().to_string(),
+            video_id: video.video_id.to_string(),
+            input_path: video.path.to_string_lossy().to_string(),
             out_dir: output_dir,
         })
         .await
     {
         let _ = sqlx::query!(
             r#"UPDATE videos SET processing_state='error', last_error=$2 WHERE id=$1"#,
-            video_id,
+            video.video_id,
             format!("enqueue: {e}")
         )
         .execute(&st.pool)
         .await;
 
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"ok": false, "where": "enqueue", "error": e.to_string()})),
-        )
-            .into_response();
-    }
-
-    // Push original to remote storage backend (fire-and-forget, non-fatal).
-    // No-op when STORAGE_BACKEND=local.
-    if !st.storage.is_local() {
-        let storage = st.storage.clone();
-        let key = format!("uploads/{saved_filename_only}");
-        let path = saved_path.clone();
-        tokio::spawn(async move {
-            match storage.put_file(&key, &path).await {
-                Ok(_) => tracing::info!("storage: pushed original {key}"),
-                Err(e) => tracing::warn!("storage: original push {key} non-fatal: {e}"),
-            }
-        });
+        return Err(("enqueue", e.to_string()));
     }
 
-    info!(
-        "upload ok: video_id={}, size={}",
-        video_id,
-        ByteSize(total_bytes)
-    );
-
-    (
-        StatusCode::CREATED,
-        Json(json!({
-            "ok": true,
-            "video_id": video_id,
-            "owner_id": user_id,
-            "filename": saved_filename_only,
-            "status": "queued",
-            "message": "Upload succeeded. The video is being processed into HLS."
-        })),
-    )
-        .into_response()
+    Ok(())
 }
@@ -7,7 +7,7 @@ use axum::{
     Form, Json,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Row};
 use tower_cookies::Cookies;
 
 use crate::config::Config;
@@ -30,6 +30,16 @@ pub struct PublicProfile {
     pub profile_desc: String,
 }
 
+/// A video shown on a public profile; only publicly listed videos qualify.
+#[derive(Serialize)]
+pub struct PublicProfileVideo {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub price_cents: i64,
+    pub created_at: String,
+}
+
 #[derive(Serialize)]
 pub struct MeProfile {
     pub id: String,
@@ -199,13 +209,41 @@ pub async fn public_profile(
     };
 
     if let Some(u) = row {
+        let sql = format!(
+            r#"
+            SELECT v.id, v.title, COALESCE(v.description, '') AS description,
+                   v.price_cents, v.created_at::text AS created_at
+            FROM videos v
+            WHERE v.owner_id = $1 AND {}
+            ORDER BY v.created_at DESC
+            "#,
+            crate::handlers::video::LISTED_VIDEO_SQL
+        );
+        let videos: Vec<PublicProfileVideo> =
+            match sqlx::query(&sql).bind(&u.id).fetch_all(&st.pool).await {
+                Ok(rows) => rows
+                    .into_iter()
+                    .map(|r| PublicProfileVideo {
+                        id: r.try_get("id").unwrap_or_default(),
+                        title: r.try_get("title").unwrap_or_default(),
+                        description: r.try_get("description").unwrap_or_default(),
+                        price_cents: r.try_get("price_cents").unwrap_or(0),
+                        created_at: r.try_get("created_at").unwrap_or_default(),
+                    })
+                    .collect(),
+                Err(e) => {
+                    return Json(serde_json::json!({"ok": false, "error": format!("db: {e}")}))
+                }
+            };
+
         Json(serde_json::json!({
             "ok": true,
             "profile": PublicProfile{
                 id: u.id,
                 username: u.username,
                 profile_desc: u.profile_desc.unwrap_or_default(),
-            }
+            },
+            "videos": videos
         }))
     } else {
         Json(serde_json::json!({ "ok": false, "error": "not found" }))
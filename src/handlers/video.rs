@@ -5,13 +5,33 @@ use axum::{
     response::IntoResponse,
     Form, Json,
 };
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use tower_cookies::Cookies;
 
 use crate::config::Config;
+use crate::entitlements::{self, VideoEntitlement};
+use crate::handlers::{rentals, taxonomy};
 use crate::sessions;
 
+/// Visibility states a creator can assign to a video.
+///
+/// * `draft` — only the owner and admins can see or play it.
+/// * `unlisted` — playable by anyone with the link; never listed or federated.
+/// * `private` — playable only by the owner, admins, and users entitled to the
+///   video itself (a purchase or an allowlist grant; rentals do not count).
+/// * `public` — listed in the catalog, on public profiles, and via federation.
+pub const VISIBILITY_STATES: [&str; 4] = ["draft", "unlisted", "private", "public"];
+
+/// SQL predicate over the `videos v` alias selecting videos that may appear in
+/// public listings: public, finished transcoding, and inside the optional
+/// `publish_at` / `unpublish_at` window.
+pub(crate) const LISTED_VIDEO_SQL: &str = "v.visibility = 'public' \
+     AND v.hls_ready = TRUE \
+     AND (v.publish_at IS NULL OR v.publish_at <= NOW()) \
+     AND (v.unpublish_at IS NULL OR v.unpublish_at > NOW())";
+
 #[derive(Clone)]
 pub struct VideoState {
     pub pool: PgPool,
@@ -29,12 +49,194 @@ pub struct VideoItem {
     pub price_cents: i64,
     pub filename: String,
     pub created_at: String,
+    pub duration_sec: Option<i32>,
+    /// Number of purchases and rentals; drives the `popular` sort.
+    pub popularity: i64,
+    pub tags: Vec<String>,
+    /// Category slug.
+    pub category: Option<String>,
+    /// Rental price; `None` when the video cannot be rented.
+    pub rental_price_cents: Option<i64>,
+    /// Rental viewing window in hours, counted from first play.
+    pub rental_hours: i32,
+    /// Buyers choose the amount; `price_cents` is the minimum.
+    pub pay_what_you_want: bool,
+    /// ISO 4217 currency of `price_cents` and `rental_price_cents`.
+    pub currency: String,
 }
 
-pub async fn list_videos(State(st): State<VideoState>) -> impl IntoResponse {
-    let rows = match sqlx::query(
-        r#"
-        SELECT
+const CATALOG_DEFAULT_LIMIT: i64 = 24;
+const CATALOG_MAX_LIMIT: i64 = 100;
+
+/// Query string for `GET /api/videos`. Every filter is optional.
+#[derive(Deserialize, Default)]
+pub struct CatalogQs {
+    /// Full-text search over title and description.
+    pub q: Option<String>,
+    /// `newest` (default), `oldest`, `price_asc`, `price_desc`, `popular`,
+    /// or `relevance` (default when `q` is set).
+    pub sort: Option<String>,
+    /// Creator username or user id.
+    pub creator: Option<String>,
+    pub min_price: Option<i64>,
+    pub max_price: Option<i64>,
+    /// `free` or `paid`.
+    pub price: Option<String>,
+    pub min_duration: Option<i32>,
+    pub max_duration: Option<i32>,
+    /// Category slug.
+    pub category: Option<String>,
+    pub tag: Option<String>,
+    pub limit: Option<i64>,
+    /// Opaque `next_cursor` from the previous page.
+    pub cursor: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CatalogSort {
+    Newest,
+    Oldest,
+    PriceAsc,
+    PriceDesc,
+    Popular,
+    Relevance,
+}
+
+impl CatalogSort {
+    fn parse(raw: Option<&str>, has_query: bool) -> Result<Self, String> {
+        Ok(match raw.map(str::trim).unwrap_or("") {
+            "" if has_query => Self::Relevance,
+            "" | "newest" => Self::Newest,
+            "oldest" => Self::Oldest,
+            "price_asc" | "cheapest" => Self::PriceAsc,
+            "price_desc" | "expensive" => Self::PriceDesc,
+            "popular" | "popularity" => Self::Popular,
+            "relevance" if has_query => Self::Relevance,
+            "relevance" => return Err("sort=relevance requires q".to_string()),
+            other => return Err(format!("unknown sort: {other}")),
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Oldest => "oldest",
+            Self::PriceAsc => "price_asc",
+            Self::PriceDesc => "price_desc",
+            Self::Popular => "popular",
+            Self::Relevance => "relevance",
+        }
+    }
+
+    /// `(keyset predicate, ORDER BY)` over the `c` alias. `$8` is the cursor
+    /// key (bound as text and cast here) and `$9` the cursor video id.
+    /// Dates compare as timestamps: `created_at` is stored as text in more
+    /// than one format.
+    fn sql(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Newest => (
+                "(c.created_ts, c.id) < ($8::timestamptz, $9)",
+                "c.created_ts DESC, c.id DESC",
+            ),
+            Self::Oldest => (
+                "(c.created_ts, c.id) > ($8::timestamptz, $9)",
+                "c.created_ts ASC, c.id ASC",
+            ),
+            Self::PriceAsc => (
+                "(c.price_cents, c.id) > ($8::bigint, $9)",
+                "c.price_cents ASC, c.id ASC",
+            ),
+            Self::PriceDesc => (
+                "(c.price_cents, c.id) < ($8::bigint, $9)",
+                "c.price_cents DESC, c.id DESC",
+            ),
+            Self::Popular => (
+                "(c.popularity, c.id) < ($8::bigint, $9)",
+                "c.popularity DESC, c.id DESC",
+            ),
+            Self::Relevance => ("(c.rank, c.id) < ($8::real, $9)", "c.rank DESC, c.id DESC"),
+        }
+    }
+
+    /// The sort key of a row, as stored in the cursor.
+    fn key_of(self, row: &sqlx::postgres::PgRow) -> String {
+        match self {
+            Self::Newest | Self::Oldest => row
+                .try_get::<DateTime<Utc>, _>("created_ts")
+                .map(|t| t.to_rfc3339_opts(SecondsFormat::Micros, true))
+                .unwrap_or_default(),
+            Self::PriceAsc | Self::PriceDesc => row
+                .try_get::<i64, _>("price_cents")
+                .unwrap_or(0)
+                .to_string(),
+            Self::Popular => row.try_get::<i64, _>("popularity").unwrap_or(0).to_string(),
+            Self::Relevance => row.try_get::<f32, _>("rank").unwrap_or(0.0).to_string(),
+        }
+    }
+}
+
+/// Keyset position: the sort it belongs to, the last row's sort key, and its id.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct CatalogCursor {
+    s: String,
+    k: String,
+    id: String,
+}
+
+fn encode_cursor(cursor: &CatalogCursor) -> String {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default())
+}
+
+fn decode_cursor(raw: &str, sort: CatalogSort) -> Result<CatalogCursor, String> {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    let cursor: CatalogCursor = URL_SAFE_NO_PAD
+        .decode(raw.trim())
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| "invalid cursor".to_string())?;
+    if cursor.s != sort.name() {
+        return Err("cursor belongs to a different sort order".to_string());
+    }
+    Ok(cursor)
+}
+
+pub(crate) fn video_item_from_row(r: &sqlx::postgres::PgRow) -> VideoItem {
+    VideoItem {
+        id: r.try_get::<String, _>("id").unwrap_or_default(),
+        owner_id: r.try_get::<String, _>("owner_id").unwrap_or_default(),
+        owner_name: r
+            .try_get::<String, _>("owner_name")
+            .unwrap_or_else(|_| "(tidak diketahui)".to_string()),
+        owner_profile_desc: r
+            .try_get::<Option<String>, _>("owner_profile_desc")
+            .ok()
+            .flatten()
+            .unwrap_or_default(),
+        title: r.try_get::<String, _>("title").unwrap_or_default(),
+        description: r.try_get::<String, _>("description").unwrap_or_default(),
+        price_cents: r.try_get::<i64, _>("price_cents").unwrap_or(0),
+        filename: r.try_get::<String, _>("filename").unwrap_or_default(),
+        created_at: r.try_get::<String, _>("created_at").unwrap_or_default(),
+        duration_sec: r.try_get::<Option<i32>, _>("duration_sec").ok().flatten(),
+        popularity: r.try_get::<i64, _>("popularity").unwrap_or(0),
+        tags: r.try_get::<Vec<String>, _>("tags").unwrap_or_default(),
+        category: r.try_get::<Option<String>, _>("category").ok().flatten(),
+        rental_price_cents: r
+            .try_get::<Option<i64>, _>("rental_price_cents")
+            .ok()
+            .flatten(),
+        rental_hours: r.try_get::<i32, _>("rental_hours").unwrap_or(48),
+        pay_what_you_want: r.try_get::<bool, _>("pay_what_you_want").unwrap_or(false),
+        currency: r
+            .try_get::<String, _>("currency")
+            .unwrap_or_else(|_| "USD".into()),
+    }
+}
+
+/// Columns shared by the catalog and single-video queries (over `videos v`
+/// joined to `users u`).
+pub(crate) const VIDEO_ITEM_COLUMNS: &str = r#"
           v.id,
           v.owner_id,
           COALESCE(u.username, '(tidak diketahui)') AS owner_name,
@@ -43,41 +245,223 @@ pub async fn list_videos(State(st): State<VideoState>) -> impl IntoResponse {
           COALESCE(v.description, '')  AS description,
           v.price_cents,
           v.filename,
-          v.created_at::text           AS created_at
+          v.created_at::text           AS created_at,
+          v.duration_sec,
+          v.purchase_count             AS popularity,
+          ARRAY(SELECT vt.tag FROM video_tags vt WHERE vt.video_id = v.id ORDER BY vt.tag) AS tags,
+          (SELECT cat.slug FROM categories cat WHERE cat.id = v.category_id) AS category,
+          v.rental_price_cents,
+          v.rental_hours,
+          v.pay_what_you_want,
+          v.currency"#;
+
+/// GET /api/videos
+///
+/// Publicly listed videos with cursor pagination, sorting, filters, and
+/// full-text search. `total` counts every match, not just the current page.
+pub async fn list_videos(
+    State(st): State<VideoState>,
+    Query(qs): Query<CatalogQs>,
+) -> impl IntoResponse {
+    let search =
+        qs.q.as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .map(str::to_string);
+    let sort = match CatalogSort::parse(qs.sort.as_deref(), search.is_some()) {
+        Ok(sort) => sort,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e})),
+    };
+    let free_only: Option<bool> = match qs.price.as_deref().map(str::trim) {
+        None | Some("") | Some("all") => None,
+        Some("free") => Some(true),
+        Some("paid") => Some(false),
+        Some(_) => {
+            return Json(serde_json::json!({"ok": false, "error": "price must be free or paid"}))
+        }
+    };
+    let cursor = match qs.cursor.as_deref().filter(|c| !c.trim().is_empty()) {
+        Some(raw) => match decode_cursor(raw, sort) {
+            Ok(c) => Some(c),
+            Err(e) => return Json(serde_json::json!({"ok": false, "error": e})),
+        },
+        None => None,
+    };
+    let creator = qs
+        .creator
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string);
+    let category = qs
+        .category
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string);
+    let tag = match qs.tag.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        Some(raw) => match taxonomy::normalize_tag(raw) {
+            Some(t) => Some(t),
+            None => return Json(serde_json::json!({"ok": false, "error": "invalid tag"})),
+        },
+        None => None,
+    };
+    let limit = qs
+        .limit
+        .unwrap_or(CATALOG_DEFAULT_LIMIT)
+        .clamp(1, CATALOG_MAX_LIMIT);
+
+    let (keyset, order_by) = sort.sql();
+    let sql = format!(
+        r#"
+        WITH catalog AS (
+          SELECT {VIDEO_ITEM_COLUMNS},
+            v.created_at::timestamptz AS created_ts,
+            CASE WHEN $1::text IS NULL THEN 0::real
+                 ELSE ts_rank(v.search_tsv, websearch_to_tsquery('simple', $1)) END AS rank
+          FROM videos v
+          LEFT JOIN users u ON u.id = v.owner_id
+          WHERE {LISTED_VIDEO_SQL}
+            AND ($1::text IS NULL OR v.search_tsv @@ websearch_to_tsquery('simple', $1))
+            AND ($2::text IS NULL OR u.username = $2 OR v.owner_id = $2)
+            AND ($3::bigint IS NULL OR v.price_cents >= $3)
+            AND ($4::bigint IS NULL OR v.price_cents <= $4)
+            AND ($5::bool IS NULL OR (v.price_cents <= 0) = $5)
+            AND ($6::int IS NULL OR v.duration_sec >= $6)
+            AND ($7::int IS NULL OR v.duration_sec <= $7)
+            AND ($11::text IS NULL OR EXISTS (
+                  SELECT 1 FROM categories cat WHERE cat.id = v.category_id AND cat.slug = $11))
+            AND ($12::text IS NULL OR EXISTS (
+                  SELECT 1 FROM video_tags vt WHERE vt.video_id = v.id AND vt.tag = $12))
+        )
+        SELECT t.total_count, page.*
+        FROM (SELECT COUNT(*) AS total_count FROM catalog) t
+        LEFT JOIN LATERAL (
+          SELECT c.* FROM catalog c
+          WHERE ($9::text IS NULL OR {keyset})
+          ORDER BY {order_by}
+          LIMIT $10
+        ) page ON TRUE
+        "#
+    );
+
+    let rows = match sqlx::query(&sql)
+        .bind(&search)
+        .bind(&creator)
+        .bind(qs.min_price)
+        .bind(qs.max_price)
+        .bind(free_only)
+        .bind(qs.min_duration)
+        .bind(qs.max_duration)
+        .bind(cursor.as_ref().map(|c| c.k.clone()))
+        .bind(cursor.as_ref().map(|c| c.id.clone()))
+        .bind(limit + 1)
+        .bind(&category)
+        .bind(&tag)
+        .fetch_all(&st.pool)
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": format!("db: {e}")})),
+    };
+
+    let total: i64 = rows
+        .first()
+        .and_then(|r| r.try_get::<i64, _>("total_count").ok())
+        .unwrap_or(0);
+    let mut page: Vec<&sqlx::postgres::PgRow> = rows
+        .iter()
+        .filter(|r| matches!(r.try_get::<Option<String>, _>("id"), Ok(Some(_))))
+        .collect();
+
+    let next_cursor = if page.len() as i64 > limit {
+        page.truncate(limit as usize);
+        page.last().map(|last| {
+            encode_cursor(&CatalogCursor {
+                s: sort.name().to_string(),
+                k: sort.key_of(last),
+                id: last.try_get::<String, _>("id").unwrap_or_default(),
+            })
+        })
+    } else {
+        None
+    };
+
+    let list: Vec<VideoItem> = page.into_iter().map(video_item_from_row).collect();
+
+    Json(serde_json::json!({
+        "ok": true,
+        "videos": list,
+        "total": total,
+        "sort": sort.name(),
+        "limit": limit,
+        "next_cursor": next_cursor
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct VideoQs {
+    pub id: String,
+}
+
+/// GET /api/video?id=...
+///
+/// Details for one video. Public and unlisted videos inside their publish
+/// window are returned to anyone with the link; drafts, private videos, and
+/// videos outside their window only to viewers `user_has_view_access` admits.
+pub async fn get_video(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Query(qs): Query<VideoQs>,
+) -> impl IntoResponse {
+    let sql = format!(
+        r#"
+        SELECT {VIDEO_ITEM_COLUMNS},
+          (v.visibility IN ('public', 'unlisted')
+             AND (v.publish_at IS NULL OR v.publish_at <= NOW())
+             AND (v.unpublish_at IS NULL OR v.unpublish_at > NOW())) AS linkable
         FROM videos v
         LEFT JOIN users u ON u.id = v.owner_id
-        ORDER BY v.created_at DESC
-        "#,
-    )
-    .fetch_all(&st.pool)
-    .await
+        WHERE v.id = $1
+        LIMIT 1
+        "#
+    );
+    let row = match sqlx::query(&sql)
+        .bind(&qs.id)
+        .fetch_optional(&st.pool)
+        .await
     {
         Ok(r) => r,
         Err(e) => return Json(serde_json::json!({"ok": false, "error": format!("db: {e}")})),
     };
+    let Some(row) = row else {
+        return Json(serde_json::json!({"ok": false, "error": "not found"}));
+    };
 
-    let list: Vec<VideoItem> = rows
-        .into_iter()
-        .map(|r| VideoItem {
-            id: r.try_get::<String, _>("id").unwrap_or_default(),
-            owner_id: r.try_get::<String, _>("owner_id").unwrap_or_default(),
-            owner_name: r
-                .try_get::<String, _>("owner_name")
-                .unwrap_or_else(|_| "(tidak diketahui)".to_string()),
-            owner_profile_desc: r
-                .try_get::<Option<String>, _>("owner_profile_desc")
-                .ok()
-                .flatten()
-                .unwrap_or_default(),
-            title: r.try_get::<String, _>("title").unwrap_or_default(),
-            description: r.try_get::<String, _>("description").unwrap_or_default(),
-            price_cents: r.try_get::<i64, _>("price_cents").unwrap_or(0),
-            filename: r.try_get::<String, _>("filename").unwrap_or_default(),
-            created_at: r.try_get::<String, _>("created_at").unwrap_or_default(),
-        })
-        .collect();
+    let viewer = sessions::current_user_id(&st.pool, &st.cfg, &cookies).await;
+    if !row.try_get::<bool, _>("linkable").unwrap_or(false) {
+        let allowed = match &viewer {
+            Some((uid, _)) => user_has_view_access(&st.pool, &qs.id, uid)
+                .await
+                .unwrap_or(false),
+            None => false,
+        };
+        if !allowed {
+            return Json(serde_json::json!({"ok": false, "error": "not found"}));
+        }
+    }
+
+    // The viewer's live rental, with its remaining time.
+    let rental = match &viewer {
+        Some((uid, _)) => rentals::rental_status(&st.pool, &qs.id, uid).await,
+        None => None,
+    };
 
-    Json(serde_json::json!({ "ok": true, "videos": list }))
+    Json(serde_json::json!({
+        "ok": true,
+        "video": video_item_from_row(&row),
+        "rental": rental
+    }))
 }
 
 #[derive(Serialize)]
@@ -87,6 +471,16 @@ struct MyVideo {
     description: String,
     price_cents: i64,
     created_at: String,
+    visibility: String,
+    publish_at: Option<String>,
+    unpublish_at: Option<String>,
+    processing_state: String,
+    tags: Vec<String>,
+    category: Option<String>,
+    rental_price_cents: Option<i64>,
+    rental_hours: i32,
+    pay_what_you_want: bool,
+    currency: String,
     allow_count: usize,
     allow_users: Vec<String>,
 }
@@ -100,7 +494,12 @@ pub async fn my_videos(State(st): State<VideoState>, cookies: Cookies) -> impl I
     let vids = match sqlx::query(
         r#"
         SELECT id, title, COALESCE(description,'') AS description,
-               price_cents, created_at::text AS created_at
+               price_cents, created_at::text AS created_at,
+               visibility, publish_at::text AS publish_at, unpublish_at::text AS unpublish_at,
+               COALESCE(processing_state, '') AS processing_state,
+               ARRAY(SELECT vt.tag FROM video_tags vt WHERE vt.video_id = videos.id ORDER BY vt.tag) AS tags,
+               (SELECT cat.slug FROM categories cat WHERE cat.id = videos.category_id) AS category,
+               rental_price_cents, rental_hours, pay_what_you_want, currency
         FROM videos
         WHERE owner_id = $1
         ORDER BY created_at DESC
@@ -117,12 +516,19 @@ pub async fn my_videos(State(st): State<VideoState>, cookies: Cookies) -> impl I
     let mut out: Vec<MyVideo> = Vec::with_capacity(vids.len());
     for v in vids {
         let vid = v.try_get::<String, _>("id").unwrap_or_default();
-        let allow_rows =
-            sqlx::query(r#"SELECT username FROM allowlist WHERE video_id = $1 ORDER BY username"#)
-                .bind(&vid)
-                .fetch_all(&st.pool)
-                .await
-                .unwrap_or_default();
+        let allow_rows = sqlx::query(
+            r#"SELECT DISTINCT u.username
+               FROM entitlements e
+               JOIN users u ON u.id = e.user_id
+               WHERE e.video_id = $1 AND e.reference = $2 AND e.revoked_at IS NULL
+                 AND (e.valid_until IS NULL OR e.valid_until > NOW())
+               ORDER BY u.username"#,
+        )
+        .bind(&vid)
+        .bind(entitlements::ALLOWLIST_REFERENCE)
+        .fetch_all(&st.pool)
+        .await
+        .unwrap_or_default();
 
         let allow_users: Vec<String> = allow_rows
             .into_iter()
@@ -135,6 +541,28 @@ pub async fn my_videos(State(st): State<VideoState>, cookies: Cookies) -> impl I
             description: v.try_get::<String, _>("description").unwrap_or_default(),
             price_cents: v.try_get::<i64, _>("price_cents").unwrap_or(0),
             created_at: v.try_get::<String, _>("created_at").unwrap_or_default(),
+            visibility: v
+                .try_get::<String, _>("visibility")
+                .unwrap_or_else(|_| "public".into()),
+            publish_at: v.try_get::<Option<String>, _>("publish_at").ok().flatten(),
+            unpublish_at: v
+                .try_get::<Option<String>, _>("unpublish_at")
+                .ok()
+                .flatten(),
+            processing_state: v
+                .try_get::<String, _>("processing_state")
+                .unwrap_or_default(),
+            tags: v.try_get::<Vec<String>, _>("tags").unwrap_or_default(),
+            category: v.try_get::<Option<String>, _>("category").ok().flatten(),
+            rental_price_cents: v
+                .try_get::<Option<i64>, _>("rental_price_cents")
+                .ok()
+                .flatten(),
+            rental_hours: v.try_get::<i32, _>("rental_hours").unwrap_or(48),
+            pay_what_you_want: v.try_get::<bool, _>("pay_what_you_want").unwrap_or(false),
+            currency: v
+                .try_get::<String, _>("currency")
+                .unwrap_or_else(|_| "USD".into()),
             allow_count: allow_users.len(),
             allow_users,
         });
@@ -215,7 +643,7 @@ pub async fn user_lookup(
 }
 
 #[derive(Deserialize)]
-pub struct AddAllowForm {
+pub struct AllowForm {
     pub video_id: String,
     pub user_id: Option<String>,
     pub username_or_email: Option<String>,
@@ -223,14 +651,20 @@ pub struct AddAllowForm {
     pub email: Option<String>,
 }
 
-pub async fn add_allow(
-    State(st): State<VideoState>,
-    cookies: Cookies,
-    Form(f): Form<AddAllowForm>,
-) -> impl IntoResponse {
-    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+/// Checks that the caller owns the form's video and resolves the user it
+/// names. Returns the caller's id and the user's id and username.
+async fn allow_target(
+    st: &VideoState,
+    cookies: &Cookies,
+    f: &AllowForm,
+) -> Result<(String, String, String), Json<serde_json::Value>> {
+    let (uid, _is_admin) = match sessions::current_user_id(&st.pool, &st.cfg, cookies).await {
         Some(v) => v,
-        None => return Json(serde_json::json!({"ok": false, "error": "not logged in"})),
+        None => {
+            return Err(Json(
+                serde_json::json!({"ok": false, "error": "not logged in"}),
+            ))
+        }
     };
 
     let is_owner: bool = sqlx::query_scalar(
@@ -242,64 +676,104 @@ pub async fn add_allow(
     .await
     .unwrap_or(false);
     if !is_owner {
-        return Json(serde_json::json!({"ok": false, "error": "not owner"}));
+        return Err(Json(serde_json::json!({"ok": false, "error": "not owner"})));
     }
 
-    let target_uname = if let Some(u) = &f.username {
-        u.trim().to_string()
+    let (lookup, key) = if let Some(u) = &f.username {
+        ("username = $1", u)
     } else if let Some(e) = &f.email {
-        match sqlx::query(r#"SELECT username FROM users WHERE email = $1 LIMIT 1"#)
-            .bind(e.trim())
-            .fetch_optional(&st.pool)
-            .await
-        {
-            Ok(Some(r)) => r.try_get::<String, _>("username").unwrap_or_default(),
-            _ => String::new(),
-        }
+        ("email = $1", e)
     } else if let Some(id) = &f.user_id {
-        match sqlx::query(r#"SELECT username FROM users WHERE id = $1 LIMIT 1"#)
-            .bind(id.trim())
-            .fetch_optional(&st.pool)
-            .await
-        {
-            Ok(Some(r)) => r.try_get::<String, _>("username").unwrap_or_default(),
-            _ => String::new(),
-        }
+        ("id = $1", id)
     } else if let Some(k) = &f.username_or_email {
-        match sqlx::query(r#"SELECT username FROM users WHERE username = $1 OR email = $1 LIMIT 1"#)
-            .bind(k.trim())
-            .fetch_optional(&st.pool)
-            .await
-        {
-            Ok(Some(r)) => r.try_get::<String, _>("username").unwrap_or_default(),
-            _ => String::new(),
-        }
+        ("username = $1 OR email = $1", k)
     } else {
-        String::new()
+        return Err(Json(
+            serde_json::json!({"ok": false, "error": "user not found"}),
+        ));
     };
+    match sqlx::query(&format!(
+        "SELECT id, username FROM users WHERE {lookup} LIMIT 1"
+    ))
+    .bind(key.trim())
+    .fetch_optional(&st.pool)
+    .await
+    {
+        Ok(Some(r)) => Ok((
+            uid,
+            r.try_get::<String, _>("id").unwrap_or_default(),
+            r.try_get::<String, _>("username").unwrap_or_default(),
+        )),
+        Ok(None) => Err(Json(
+            serde_json::json!({"ok": false, "error": "user not found"}),
+        )),
+        Err(e) => Err(Json(
+            serde_json::json!({"ok": false, "error": e.to_string()}),
+        )),
+    }
+}
 
-    if target_uname.is_empty() {
-        return Json(serde_json::json!({"ok": false, "error": "user not found"}));
+pub async fn add_allow(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Form(f): Form<AllowForm>,
+) -> impl IntoResponse {
+    let (uid, target_id, target_uname) = match allow_target(&st, &cookies, &f).await {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+
+    let res = match st.pool.acquire().await {
+        Ok(mut conn) => entitlements::grant_manual(&mut conn, &target_id, &f.video_id, &uid).await,
+        Err(e) => Err(e),
+    };
+
+    match res {
+        Ok(granted) => Json(serde_json::json!({
+            "ok": true,
+            "video_id": f.video_id,
+            "username": target_uname,
+            "rows_affected": u64::from(granted)
+        })),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
     }
+}
 
-    let res = sqlx::query(
-        r#"
-        INSERT INTO allowlist (video_id, username)
-        VALUES ($1, $2)
-        ON CONFLICT (video_id, username) DO NOTHING
-        "#,
-    )
-    .bind(&f.video_id)
-    .bind(&target_uname)
-    .execute(&st.pool)
-    .await;
+/// POST /api/allow/remove
+///
+/// Takes a user off a video's allowlist: their manual grant is revoked.
+/// Purchases and other grants of the same video are left alone.
+pub async fn remove_allow(
+    State(st): State<VideoState>,
+    cookies: Cookies,
+    Form(f): Form<AllowForm>,
+) -> impl IntoResponse {
+    let (_uid, target_id, target_uname) = match allow_target(&st, &cookies, &f).await {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+
+    let res = match st.pool.acquire().await {
+        Ok(mut conn) => {
+            entitlements::revoke(
+                &mut conn,
+                entitlements::Revoke::Allowlist {
+                    user_id: &target_id,
+                    video_id: &f.video_id,
+                },
+                "removed from allowlist",
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
 
     match res {
-        Ok(r) => Json(serde_json::json!({
+        Ok(revoked) => Json(serde_json::json!({
             "ok": true,
             "video_id": f.video_id,
             "username": target_uname,
-            "rows_affected": r.rows_affected()
+            "rows_affected": revoked
         })),
         Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
     }
@@ -315,6 +789,33 @@ pub struct UpdateVideoForm {
     /// Omitting the field leaves the current value unchanged.
     #[serde(default)]
     pub federation_visibility: Option<String>,
+    /// Optional visibility state, one of [`VISIBILITY_STATES`].
+    #[serde(default)]
+    pub visibility: Option<String>,
+    /// Optional RFC 3339 publish time. An empty value clears the schedule.
+    #[serde(default)]
+    pub publish_at: Option<String>,
+    /// Optional RFC 3339 unpublish time. An empty value clears the schedule.
+    #[serde(default)]
+    pub unpublish_at: Option<String>,
+    /// Optional comma separated tag list. An empty value removes every tag.
+    #[serde(default)]
+    pub tags: Option<String>,
+    /// Optional category slug. An empty value clears the category.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Optional rental price in cents. An empty value disables rentals.
+    #[serde(default)]
+    pub rental_price_cents: Option<String>,
+    /// Optional rental window in hours.
+    #[serde(default)]
+    pub rental_hours: Option<i32>,
+    /// Optional pay-what-you-want switch; `price_cents` becomes the minimum.
+    #[serde(default)]
+    pub pay_what_you_want: Option<bool>,
+    /// Optional ISO 4217 price currency; needs a known exchange rate.
+    #[serde(default)]
+    pub currency: Option<String>,
 }
 
 pub async fn update_video(
@@ -329,7 +830,7 @@ pub async fn update_video(
 
     // Validate and normalise the requested visibility change.
     // `None` (field absent) → leave unchanged; `Some("")` → also leave unchanged.
-    let new_vis: Option<&str> = match f.federation_visibility.as_deref() {
+    let new_fed_vis: Option<&str> = match f.federation_visibility.as_deref() {
         Some("public") => Some("public"),
         Some("local_only") => Some("local_only"),
         None | Some("") => None,
@@ -341,28 +842,119 @@ pub async fn update_video(
         }
     };
 
-    // When federation is active, snapshot the video's current visibility before
-    // the update so we know which AP activity to broadcast afterwards.
-    let prev_vis: String = if federation_enabled() {
-        sqlx::query_scalar::<_, String>(
-            "SELECT federation_visibility FROM videos \
-             WHERE id = $1 AND owner_id = $2 LIMIT 1",
-        )
-        .bind(&f.id)
-        .bind(&uid)
-        .fetch_optional(&st.pool)
-        .await
-        .unwrap_or(None)
-        .unwrap_or_else(|| "local_only".to_string())
-    } else {
-        String::new()
+    let new_visibility = match parse_visibility(f.visibility.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e})),
+    };
+    let publish_at = match parse_schedule_field("publish_at", f.publish_at.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e})),
+    };
+    let unpublish_at = match parse_schedule_field("unpublish_at", f.unpublish_at.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e})),
+    };
+    if let (Some(Some(from)), Some(Some(until))) = (publish_at, unpublish_at) {
+        if until <= from {
+            return Json(serde_json::json!({
+                "ok": false,
+                "error": "unpublish_at must be later than publish_at"
+            }));
+        }
+    }
+
+    let tags = match f.tags.as_deref().map(taxonomy::parse_tags).transpose() {
+        Ok(v) => v,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e})),
+    };
+    // `None` → unchanged, `Some(None)` → clear, `Some(Some(id))` → set.
+    let category_id: Option<Option<String>> = match f.category.as_deref().map(str::trim) {
+        None => None,
+        Some("") => Some(None),
+        Some(slug) => {
+            match sqlx::query_scalar::<_, String>("SELECT id FROM categories WHERE slug = $1")
+                .bind(slug)
+                .fetch_optional(&st.pool)
+                .await
+            {
+                Ok(Some(id)) => Some(Some(id)),
+                Ok(None) => {
+                    return Json(serde_json::json!({"ok": false, "error": "unknown category"}))
+                }
+                Err(e) => {
+                    return Json(serde_json::json!({"ok": false, "error": format!("db: {e}")}))
+                }
+            }
+        }
+    };
+
+    // `None` → unchanged, `Some(None)` → no rentals, `Some(Some(p))` → rent for `p`.
+    let rental_price_cents: Option<Option<i64>> =
+        match f.rental_price_cents.as_deref().map(str::trim) {
+            None => None,
+            Some("") => Some(None),
+            Some(raw) => match raw.parse::<i64>() {
+                Ok(price) if price > 0 => Some(Some(price)),
+                _ => {
+                    return Json(serde_json::json!({
+                        "ok": false,
+                        "error": "rental_price_cents must be a positive number"
+                    }))
+                }
+            },
+        };
+    if let Some(hours) = f.rental_hours {
+        if !rentals::RENTAL_HOURS_RANGE.contains(&hours) {
+            return Json(serde_json::json!({
+                "ok": false,
+                "error": format!(
+                    "rental_hours must be between {} and {}",
+                    rentals::RENTAL_HOURS_RANGE.start(),
+                    rentals::RENTAL_HOURS_RANGE.end()
+                )
+            }));
+        }
+    }
+
+    let price_currency = match f
+        .currency
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+    {
+        None => None,
+        Some(raw) => {
+            let code = match crate::currency::normalize_currency(raw) {
+                Ok(c) => c,
+                Err(e) => return Json(serde_json::json!({"ok": false, "error": e})),
+            };
+            if let Err(e) = crate::currency::check_supported(&st.pool, &code).await {
+                return Json(serde_json::json!({"ok": false, "error": e}));
+            }
+            Some(code)
+        }
     };
 
+    // When federation is active, snapshot whether the video is currently
+    // federated so we know which AP activity to broadcast afterwards.
+    let was_public = federation_enabled()
+        && crate::federation::video_index::is_federation_eligible(&st.pool, &f.id)
+            .await
+            .unwrap_or(false);
+
     let res = sqlx::query(
         r#"
         UPDATE videos
         SET title = $2, description = $3, price_cents = $4,
-            federation_visibility = COALESCE($5, federation_visibility)
+            federation_visibility = COALESCE($5, federation_visibility),
+            visibility = COALESCE($7, visibility),
+            publish_at = CASE WHEN $8 THEN $9 ELSE publish_at END,
+            unpublish_at = CASE WHEN $10 THEN $11 ELSE unpublish_at END,
+            category_id = CASE WHEN $12 THEN $13 ELSE category_id END,
+            rental_price_cents = CASE WHEN $14 THEN $15 ELSE rental_price_cents END,
+            rental_hours = COALESCE($16, rental_hours),
+            pay_what_you_want = COALESCE($17, pay_what_you_want),
+            currency = COALESCE($18, currency)
         WHERE id = $1 AND owner_id = $6
         "#,
     )
@@ -370,23 +962,40 @@ pub async fn update_video(
     .bind(f.title.trim())
     .bind(f.description.trim())
     .bind(f.price_cents)
-    .bind(new_vis)
+    .bind(new_fed_vis)
     .bind(&uid)
+    .bind(new_visibility)
+    .bind(publish_at.is_some())
+    .bind(publish_at.flatten())
+    .bind(unpublish_at.is_some())
+    .bind(unpublish_at.flatten())
+    .bind(category_id.is_some())
+    .bind(category_id.flatten())
+    .bind(rental_price_cents.is_some())
+    .bind(rental_price_cents.flatten())
+    .bind(f.rental_hours)
+    .bind(f.pay_what_you_want)
+    .bind(price_currency)
     .execute(&st.pool)
     .await;
 
     match res {
         Ok(r) if r.rows_affected() > 0 => {
-            // Determine effective new visibility (fall back to unchanged prev).
-            let eff_new = new_vis.unwrap_or(prev_vis.as_str());
-            let was_public = prev_vis == "public";
-            let now_public = eff_new == "public";
+            if let Some(tags) = &tags {
+                if let Err(e) = taxonomy::replace_video_tags(&st.pool, &f.id, tags).await {
+                    return Json(serde_json::json!({"ok": false, "error": format!("tags: {e}")}));
+                }
+            }
 
             if federation_enabled() {
                 let video_id = f.id.clone();
                 let pool = st.pool.clone();
                 let base_url = federation_base_url();
                 tokio::spawn(async move {
+                    let now_public =
+                        crate::federation::video_index::is_federation_eligible(&pool, &video_id)
+                            .await
+                            .unwrap_or(false);
                     let result = if !was_public && now_public {
                         crate::federation::video_index::publish_create(&pool, &video_id, &base_url)
                             .await
@@ -413,6 +1022,43 @@ pub async fn update_video(
     }
 }
 
+/// Validates an optional `visibility` form value. Absent or empty → `None`
+/// (leave unchanged).
+pub(crate) fn parse_visibility(raw: Option<&str>) -> Result<Option<&'static str>, String> {
+    match raw.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => VISIBILITY_STATES
+            .iter()
+            .find(|state| state.eq_ignore_ascii_case(value))
+            .map(|state| Some(*state))
+            .ok_or_else(|| {
+                format!(
+                    "visibility must be one of: {}",
+                    VISIBILITY_STATES.join(", ")
+                )
+            }),
+    }
+}
+
+/// Parses an optional schedule form value.
+///
+/// Absent → `None` (leave unchanged); empty → `Some(None)` (clear);
+/// otherwise an RFC 3339 timestamp → `Some(Some(t))`.
+pub(crate) fn parse_schedule_field(
+    name: &str,
+    raw: Option<&str>,
+) -> Result<Option<Option<DateTime<Utc>>>, String> {
+    match raw.map(str::trim) {
+        None => Ok(None),
+        Some("") => Ok(Some(None)),
+        Some(value) => DateTime::parse_from_rfc3339(value)
+            .map(|t| Some(Some(t.with_timezone(&Utc))))
+            .map_err(|_| {
+                format!("{name} must be an RFC 3339 timestamp, e.g. 2026-01-31T09:00:00Z")
+            }),
+    }
+}
+
 fn federation_enabled() -> bool {
     std::env::var("FEDERATION_ENABLED")
         .map(|v| {
@@ -424,104 +1070,165 @@ fn federation_enabled() -> bool {
         .unwrap_or(false)
 }
 
-fn federation_base_url() -> String {
+pub(crate) fn federation_base_url() -> String {
     std::env::var("FEDERATION_BASE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string())
 }
 
-/// AuthZ helper.
-/// Grants access when any of these are true:
-///   1. Video is free (price_cents = 0)
-///   2. User is the video owner
-///   3. User is an admin (is_admin session flag)
-///   4. User is in the allowlist
-///   5. User has a completed payment for the video
+/// Outcome of [`view_access`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ViewAccess {
+    Denied,
+    /// Owner, admin, free video, or a permanent grant.
+    Granted,
+    /// Only a rental grants access, until the given time.
+    Rental(DateTime<Utc>),
+}
+
+/// AuthZ helper: true unless [`view_access`] denies.
 pub async fn user_has_view_access(
     pool: &PgPool,
     video_id: &str,
     user_id: &str,
 ) -> anyhow::Result<bool> {
-    // 1. Free video — open to all authenticated users
-    let price_cents: i64 =
-        sqlx::query_scalar(r#"SELECT price_cents FROM videos WHERE id = $1 LIMIT 1"#)
-            .bind(video_id)
-            .fetch_one(pool)
-            .await
-            .unwrap_or(1); // default non-zero so we don't accidentally open paid videos
-    if price_cents <= 0 {
-        return Ok(true);
-    }
+    Ok(view_access(pool, video_id, user_id).await? != ViewAccess::Denied)
+}
 
-    // 2. Owner
-    let is_owner: bool = sqlx::query_scalar(
-        r#"SELECT EXISTS(SELECT 1 FROM videos WHERE id = $1 AND owner_id = $2)"#,
+/// Decides whether a user may watch a video.
+///
+/// Visibility is checked first:
+///   * owners and admins always have access;
+///   * `draft` videos and videos whose `publish_at` is still in the future
+///     are closed to everyone else;
+///   * `private` videos are open only to permanent entitlements for the
+///     video itself.
+///
+/// Otherwise access is granted when the video is free (price_cents = 0) and
+/// has not passed `unpublish_at`, or when the user holds a live entitlement
+/// covering it (purchase, grant, bundle, subscription, or rental).
+///
+/// Buyers keep access after `unpublish_at`; only free viewing ends. A
+/// permanent entitlement always wins over a rental.
+pub(crate) async fn view_access(
+    pool: &PgPool,
+    video_id: &str,
+    user_id: &str,
+) -> anyhow::Result<ViewAccess> {
+    let video = sqlx::query(
+        r#"
+        SELECT price_cents, owner_id, visibility,
+               (publish_at IS NOT NULL AND publish_at > NOW()) AS scheduled,
+               (unpublish_at IS NOT NULL AND unpublish_at <= NOW()) AS unpublished
+        FROM videos
+        WHERE id = $1
+        LIMIT 1
+        "#,
     )
     .bind(video_id)
-    .bind(user_id)
-    .fetch_one(pool)
-    .await
-    .unwrap_or(false);
-    if is_owner {
-        return Ok(true);
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(video) = video else {
+        return Ok(ViewAccess::Denied);
+    };
+
+    // Owner
+    let owner_id: String = video.try_get("owner_id").unwrap_or_default();
+    if owner_id == user_id {
+        return Ok(ViewAccess::Granted);
     }
 
-    // Fetch username + is_admin in one query for remaining checks
-    let row = sqlx::query(r#"SELECT username, is_admin FROM users WHERE id = $1 LIMIT 1"#)
+    let row = sqlx::query(r#"SELECT is_admin FROM users WHERE id = $1 LIMIT 1"#)
         .bind(user_id)
         .fetch_optional(pool)
         .await
         .unwrap_or(None);
 
     let Some(row) = row else {
-        return Ok(false);
+        return Ok(ViewAccess::Denied);
     };
 
-    use sqlx::Row;
-    let username: String = row.try_get("username").unwrap_or_default();
     // is_admin is stored as INTEGER 0/1
     let is_admin: bool = row.try_get::<i32, _>("is_admin").unwrap_or(0) != 0;
 
-    // 3. Admin bypasses all access control
+    // Admin bypasses all access control
     if is_admin {
-        return Ok(true);
+        return Ok(ViewAccess::Granted);
     }
 
-    // 4. Manual allowlist grant
-    let is_allowed: bool = sqlx::query_scalar(
-        r#"SELECT EXISTS(SELECT 1 FROM allowlist WHERE video_id = $1 AND username = $2)"#,
-    )
-    .bind(video_id)
-    .bind(&username)
-    .fetch_one(pool)
-    .await
-    .unwrap_or(false);
-    if is_allowed {
-        return Ok(true);
+    let visibility: String = video
+        .try_get("visibility")
+        .unwrap_or_else(|_| "draft".to_string());
+    let scheduled: bool = video.try_get("scheduled").unwrap_or(true);
+    let unpublished: bool = video.try_get("unpublished").unwrap_or(true);
+    if visibility == "draft" || scheduled {
+        return Ok(ViewAccess::Denied);
     }
 
-    // 5. Completed payment: purchases table (x402 / wallet) or fiat_invoices (paid)
-    let has_purchase: bool = sqlx::query_scalar(
-        r#"SELECT EXISTS(SELECT 1 FROM purchases WHERE video_id = $1 AND user_id = $2)"#,
-    )
-    .bind(video_id)
-    .bind(user_id)
-    .fetch_one(pool)
-    .await
-    .unwrap_or(false);
-    if has_purchase {
-        return Ok(true);
+    // Private videos: direct permanent entitlements only
+    if visibility == "private" {
+        let direct = entitlements::video_entitlement(pool, video_id, user_id, true).await?;
+        return Ok(if direct == Some(VideoEntitlement::Full) {
+            ViewAccess::Granted
+        } else {
+            ViewAccess::Denied
+        });
+    }
+
+    // Free video — open to all authenticated users while published
+    // (default non-zero price so we don't accidentally open paid videos)
+    let price_cents: i64 = video.try_get("price_cents").unwrap_or(1);
+    if price_cents <= 0 && !unpublished {
+        return Ok(ViewAccess::Granted);
     }
 
-    let has_fiat_paid: bool = sqlx::query_scalar(
-        r#"SELECT EXISTS(
-            SELECT 1 FROM fiat_invoices
-            WHERE video_id = $1 AND user_id = $2 AND status = 'paid'
-        )"#,
+    Ok(
+        match entitlements::video_entitlement(pool, video_id, user_id, false).await? {
+            Some(VideoEntitlement::Full) => ViewAccess::Granted,
+            Some(VideoEntitlement::Rental(until)) => ViewAccess::Rental(until),
+            None => ViewAccess::Denied,
+        },
     )
-    .bind(video_id)
-    .bind(user_id)
-    .fetch_one(pool)
-    .await
-    .unwrap_or(false);
+}
 
-    Ok(has_fiat_paid)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_defaults_follow_search() {
+        assert_eq!(CatalogSort::parse(None, false), Ok(CatalogSort::Newest));
+        assert_eq!(CatalogSort::parse(None, true), Ok(CatalogSort::Relevance));
+        assert_eq!(
+            CatalogSort::parse(Some("cheapest"), false),
+            Ok(CatalogSort::PriceAsc)
+        );
+        assert!(CatalogSort::parse(Some("relevance"), false).is_err());
+        assert!(CatalogSort::parse(Some("title"), false).is_err());
+    }
+
+    #[test]
+    fn cursor_round_trips_and_is_bound_to_its_sort() {
+        let cursor = CatalogCursor {
+            s: "popular".into(),
+            k: "12".into(),
+            id: "video-1".into(),
+        };
+        let encoded = encode_cursor(&cursor);
+        assert_eq!(decode_cursor(&encoded, CatalogSort::Popular), Ok(cursor));
+        assert!(decode_cursor(&encoded, CatalogSort::Newest).is_err());
+        assert!(decode_cursor("not-a-cursor", CatalogSort::Popular).is_err());
+    }
+
+    #[test]
+    fn schedule_fields_distinguish_absent_and_cleared() {
+        assert_eq!(parse_schedule_field("publish_at", None), Ok(None));
+        assert_eq!(parse_schedule_field("publish_at", Some("")), Ok(Some(None)));
+        assert!(matches!(
+            parse_schedule_field("publish_at", Some("2026-01-31T09:00:00Z")),
+            Ok(Some(Some(_)))
+        ));
+        assert!(parse_schedule_field("publish_at", Some("tomorrow")).is_err());
+        assert_eq!(parse_visibility(Some("Unlisted")), Ok(Some("unlisted")));
+        assert!(parse_visibility(Some("hidden")).is_err());
+    }
 }
@@ -1,7 +1,8 @@
 // src/handlers/wallet.rs
 // Mini wallet — pure database ledger, no blockchain.
 //
-// Also houses the wallet-pay-video endpoint: buy a video with internal balance.
+// Also houses the wallet-pay-video and wallet-pay-bundle endpoints: buy a video
+// or a bundle with internal balance.
 // Split: creator receives `creator_split_bp/10000` of price; platform retains the rest.
 //
 // Balance lives in `users.balance_cents` (integer USD cents).
@@ -24,6 +25,10 @@ use tower_cookies::Cookies;
 
 use crate::commission;
 use crate::config::Config;
+use crate::currency;
+use crate::entitlements::{self, Origin, Source};
+use crate::geo::ClientCountry;
+use crate::handlers::{bundles, coupons, regions, rentals, tips};
 use crate::payment_settings::load_payment_settings;
 use crate::sessions;
 
@@ -436,6 +441,108 @@ pub async fn wallet_transfer(
     }
 }
 
+/// Moves `price_cents` from the buyer to the creator inside `tx`: locks both
+/// rows, checks the buyer balance, credits `creator_cut`, and appends the
+/// `payment` / `transfer_in` ledger rows. Returns the buyer's new balance.
+pub(crate) async fn settle_wallet_sale(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    buyer_id: &str,
+    creator_id: &str,
+    price_cents: i64,
+    creator_cut: i64,
+    buyer_note: &str,
+    creator_note: &str,
+) -> Result<i64, String> {
+    // Lock buyer + creator rows in deterministic order
+    let (id_a, id_b) = if buyer_id < creator_id {
+        (buyer_id.to_string(), creator_id.to_string())
+    } else {
+        (creator_id.to_string(), buyer_id.to_string())
+    };
+    let _ = sqlx::query("SELECT id FROM users WHERE id IN ($1, $2) ORDER BY id FOR UPDATE")
+        .bind(&id_a)
+        .bind(&id_b)
+        .fetch_all(&mut **tx)
+        .await;
+
+    // Check buyer balance
+    let buyer_bal: i64 = sqlx::query("SELECT balance_cents FROM users WHERE id = $1")
+        .bind(buyer_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .ok()
+        .flatten()
+        .map(|r: sqlx::postgres::PgRow| r.try_get("balance_cents").unwrap_or(0))
+        .unwrap_or(0);
+
+    if buyer_bal < price_cents {
+        return Err(format!(
+            "insufficient balance (have {}, need {})",
+            cents_to_display(buyer_bal),
+            cents_to_display(price_cents)
+        ));
+    }
+
+    // Read creator balance
+    let creator_bal: i64 = sqlx::query("SELECT balance_cents FROM users WHERE id = $1")
+        .bind(creator_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .ok()
+        .flatten()
+        .map(|r: sqlx::postgres::PgRow| r.try_get("balance_cents").unwrap_or(0))
+        .unwrap_or(0);
+
+    let buyer_new = buyer_bal - price_cents;
+    let creator_new = creator_bal + creator_cut;
+
+    // Deduct buyer
+    sqlx::query("UPDATE users SET balance_cents = $1 WHERE id = $2")
+        .bind(buyer_new)
+        .bind(buyer_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| format!("db buyer: {e}"))?;
+
+    // Credit creator
+    sqlx::query("UPDATE users SET balance_cents = $1 WHERE id = $2")
+        .bind(creator_new)
+        .bind(creator_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| format!("db creator: {e}"))?;
+
+    // Ledger: buyer side (payment)
+    sqlx::query(
+        "INSERT INTO wallet_transactions (user_id,txn_type,amount_cents,balance_after,status,ref_user_id,note) \
+         VALUES ($1,'payment',$2,$3,'completed',$4,$5)",
+    )
+    .bind(buyer_id)
+    .bind(price_cents)
+    .bind(buyer_new)
+    .bind(creator_id)
+    .bind(buyer_note)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| format!("ledger buyer: {e}"))?;
+
+    // Ledger: creator side (transfer_in from the sale)
+    sqlx::query(
+        "INSERT INTO wallet_transactions (user_id,txn_type,amount_cents,balance_after,status,ref_user_id,note) \
+         VALUES ($1,'transfer_in',$2,$3,'completed',$4,$5)",
+    )
+    .bind(creator_id)
+    .bind(creator_cut)
+    .bind(creator_new)
+    .bind(buyer_id)
+    .bind(creator_note)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| format!("ledger creator: {e}"))?;
+
+    Ok(buyer_new)
+}
+
 // ─── POST /api/wallet/pay ─────────────────────────────────────────────────────
 // Buy a video using internal wallet balance.
 // Creator receives `creator_split_bp / 10000` of the price into their balance.
@@ -445,11 +552,22 @@ pub async fn wallet_transfer(
 pub struct WalletPayPayload {
     pub video_id: String,
     pub ref_code: Option<String>, // affiliate referral username
+    /// Rent the video for its rental window instead of buying it.
+    #[serde(default)]
+    pub rental: bool,
+    /// Coupon code to apply.
+    pub coupon: Option<String>,
+    /// Amount to pay for a pay-what-you-want video, in the video's currency;
+    /// defaults to its minimum.
+    pub amount_cents: Option<i64>,
+    /// Quote from `/api/pay/quote` locking the video-currency → USD rate.
+    pub quote_id: Option<String>,
 }
 
 pub async fn wallet_pay_video(
     State(st): State<WalletState>,
     cookies: Cookies,
+    country: ClientCountry,
     Json(p): Json<WalletPayPayload>,
 ) -> impl IntoResponse {
     let payment_settings = load_payment_settings(&st.pool).await;
@@ -470,7 +588,8 @@ pub async fn wallet_pay_video(
 
     // Load video and creator info
     let video_row = sqlx::query(
-        "SELECT v.price_cents, v.owner_id, u.username AS owner_username \
+        "SELECT v.price_cents, v.currency, v.owner_id, v.pay_what_you_want, \
+                u.username AS owner_username \
          FROM videos v JOIN users u ON u.id = v.owner_id WHERE v.id = $1 LIMIT 1",
     )
     .bind(&p.video_id)
@@ -483,40 +602,95 @@ pub async fn wallet_pay_video(
         Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
     };
 
-    let price_cents: i64 = video_row.try_get("price_cents").unwrap_or(0);
+    let mut price_cents: i64 = video_row.try_get("price_cents").unwrap_or(0);
     let owner_id: String = video_row.try_get("owner_id").unwrap_or_default();
     let owner_username: String = video_row.try_get("owner_username").unwrap_or_default();
+    let pay_what_you_want: bool = video_row.try_get("pay_what_you_want").unwrap_or(false);
+    let video_currency: String = video_row
+        .try_get("currency")
+        .unwrap_or_else(|_| currency::BASE_CURRENCY.into());
 
     if owner_id == uid {
         return Json(json!({"ok": false, "error": "you own this video"}));
     }
-    if price_cents <= 0 {
-        return Json(json!({"ok": false, "error": "video has no price set"}));
-    }
 
-    // Check already purchased
-    let already: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM purchases WHERE user_id = $1 AND video_id = $2")
-            .bind(&uid)
-            .bind(&p.video_id)
-            .fetch_one(&st.pool)
+    // Regional price override and geo-restriction for the buyer's country.
+    let regional = match regions::regional_terms(&st.pool, &p.video_id, country.code()).await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    price_cents = regional.price(price_cents);
+
+    let rental_hours = if p.rental {
+        let offer = match rentals::load_rental_offer(&st.pool, &p.video_id).await {
+            Ok(Some(o)) => o,
+            Ok(None) => return Json(json!({"ok": false, "error": "video is not for rent"})),
+            Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+        };
+        if let Err(e) = rentals::check_rentable(&st.pool, &p.video_id, &uid).await {
+            return Json(json!({"ok": false, "error": e}));
+        }
+        price_cents = regional.rental_price(offer.price_cents);
+        Some(offer.hours)
+    } else {
+        if pay_what_you_want {
+            if p.coupon.as_deref().is_some_and(|c| !c.trim().is_empty()) {
+                return Json(json!({
+                    "ok": false,
+                    "error": "coupons do not apply to pay-what-you-want videos"
+                }));
+            }
+            price_cents = match tips::pwyw_amount(price_cents, p.amount_cents) {
+                Ok(a) => a,
+                Err(e) => return Json(json!({"ok": false, "error": e})),
+            };
+        }
+        if price_cents <= 0 {
+            return Json(json!({"ok": false, "error": "video has no price set"}));
+        }
+
+        // Check already purchased (a rental does not count)
+        let already = entitlements::owns_video(&st.pool, &p.video_id, &uid)
             .await
-            .unwrap_or(0);
+            .unwrap_or(false);
 
-    if already > 0 {
-        return Json(json!({"ok": false, "error": "already purchased"}));
+        if already {
+            return Json(json!({"ok": false, "error": "already purchased"}));
+        }
+        None
+    };
+
+    let coupon = match p.coupon.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        Some(code) => {
+            match coupons::apply_coupon(&st.pool, code, &p.video_id, &uid, price_cents).await {
+                Ok(applied) => Some(applied),
+                Err(e) => return Json(json!({"ok": false, "error": e})),
+            }
+        }
+        None => None,
+    };
+    if let Some(applied) = &coupon {
+        price_cents = applied.final_cents();
     }
 
-    // Resolve buyer username for allowlist
-    let buyer_username: String = sqlx::query_scalar("SELECT username FROM users WHERE id = $1")
-        .bind(&uid)
-        .fetch_optional(&st.pool)
-        .await
-        .ok()
-        .flatten()
-        .unwrap_or_default();
+    // Balances are USD: convert the listed price at a locked rate.
+    let listed_amount = price_cents;
+    let quote = match currency::lock_quote(
+        &st.pool,
+        &st.cfg,
+        &uid,
+        p.quote_id.as_deref(),
+        &video_currency,
+        currency::BASE_CURRENCY,
+    )
+    .await
+    {
+        Ok(q) => q,
+        Err(e) => return Json(json!({"ok": false, "error": e})),
+    };
+    price_cents = quote.convert(listed_amount);
 
-    // Creator split (basis points, e.g. 9000 = 90%)
+    // Creator split (basis points, e.g. 9000 = 90%), on the discounted price
     let creator_cut =
         (price_cents as i128).saturating_mul(st.cfg.creator_split_bp as i128) / 10_000;
     let creator_cut = creator_cut as i64;
@@ -526,106 +700,66 @@ pub async fn wallet_pay_video(
         Err(e) => return Json(json!({"ok": false, "error": format!("begin tx: {e}")})),
     };
 
-    // Lock buyer + creator rows in deterministic order
-    let (id_a, id_b) = if uid < owner_id {
-        (uid.clone(), owner_id.clone())
-    } else {
-        (owner_id.clone(), uid.clone())
-    };
-    let _ = sqlx::query("SELECT id FROM users WHERE id IN ($1, $2) ORDER BY id FOR UPDATE")
-        .bind(&id_a)
-        .bind(&id_b)
-        .fetch_all(&mut *tx)
-        .await;
-
-    // Check buyer balance
-    let buyer_bal: i64 = sqlx::query("SELECT balance_cents FROM users WHERE id = $1")
-        .bind(&uid)
-        .fetch_optional(&mut *tx)
-        .await
-        .ok()
-        .flatten()
-        .map(|r: sqlx::postgres::PgRow| r.try_get("balance_cents").unwrap_or(0))
-        .unwrap_or(0);
-
-    if buyer_bal < price_cents {
-        let _ = tx.rollback().await;
-        return Json(json!({
-            "ok": false,
-            "error": format!("insufficient balance (have {}, need {})",
-                cents_to_display(buyer_bal), cents_to_display(price_cents))
-        }));
-    }
-
-    // Read creator balance
-    let creator_bal: i64 = sqlx::query("SELECT balance_cents FROM users WHERE id = $1")
-        .bind(&owner_id)
-        .fetch_optional(&mut *tx)
-        .await
-        .ok()
-        .flatten()
-        .map(|r: sqlx::postgres::PgRow| r.try_get("balance_cents").unwrap_or(0))
-        .unwrap_or(0);
-
-    let buyer_new = buyer_bal - price_cents;
-    let creator_new = creator_bal + creator_cut;
-
-    // Deduct buyer
-    if let Err(e) = sqlx::query("UPDATE users SET balance_cents = $1 WHERE id = $2")
-        .bind(buyer_new)
-        .bind(&uid)
-        .execute(&mut *tx)
-        .await
+    let buyer_new = match settle_wallet_sale(
+        &mut tx,
+        &uid,
+        &owner_id,
+        price_cents,
+        creator_cut,
+        &format!(
+            "Video {}: {}",
+            if p.rental { "rental" } else { "purchase" },
+            p.video_id
+        ),
+        &format!(
+            "Video {}: {}",
+            if p.rental { "rental sale" } else { "sale" },
+            p.video_id
+        ),
+    )
+    .await
     {
-        let _ = tx.rollback().await;
-        return Json(json!({"ok": false, "error": format!("db buyer: {e}")}));
-    }
+        Ok(v) => v,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": e}));
+        }
+    };
 
-    // Credit creator
-    if let Err(e) = sqlx::query("UPDATE users SET balance_cents = $1 WHERE id = $2")
-        .bind(creator_new)
-        .bind(&owner_id)
-        .execute(&mut *tx)
-        .await
-    {
-        let _ = tx.rollback().await;
-        return Json(json!({"ok": false, "error": format!("db creator: {e}")}));
+    if let Some(applied) = &coupon {
+        if let Err(e) = coupons::redeem(&mut tx, applied, &uid, &p.video_id, "wallet", None).await {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": e}));
+        }
     }
 
-    // Ledger: buyer side (payment)
-    if let Err(e) = sqlx::query(
-        "INSERT INTO wallet_transactions (user_id,txn_type,amount_cents,balance_after,status,ref_user_id,note) \
-         VALUES ($1,'payment',$2,$3,'completed',$4,$5)"
-    )
-    .bind(&uid).bind(price_cents).bind(buyer_new).bind(&owner_id)
-    .bind(format!("Video purchase: {}", p.video_id))
-    .execute(&mut *tx).await
-    { let _ = tx.rollback().await; return Json(json!({"ok": false, "error": format!("ledger buyer: {e}")})); }
-
-    // Ledger: creator side (transfer_in from video sale)
-    if let Err(e) = sqlx::query(
-        "INSERT INTO wallet_transactions (user_id,txn_type,amount_cents,balance_after,status,ref_user_id,note) \
-         VALUES ($1,'transfer_in',$2,$3,'completed',$4,$5)"
-    )
-    .bind(&owner_id).bind(creator_cut).bind(creator_new).bind(&uid)
-    .bind(format!("Video sale: {}", p.video_id))
-    .execute(&mut *tx).await
-    { let _ = tx.rollback().await; return Json(json!({"ok": false, "error": format!("ledger creator: {e}")})); }
-
-    // Purchase record
-    if let Err(e) = sqlx::query(
-        "INSERT INTO purchases (user_id, video_id, created_at) VALUES ($1,$2,NOW()) ON CONFLICT DO NOTHING"
-    )
-    .bind(&uid).bind(&p.video_id).execute(&mut *tx).await
-    { let _ = tx.rollback().await; return Json(json!({"ok": false, "error": format!("purchase: {e}")})); }
-
-    // Allowlist
-    if !buyer_username.is_empty() {
-        if let Err(e) = sqlx::query(
-            "INSERT INTO allowlist (video_id, username) VALUES ($1,$2) ON CONFLICT (video_id,username) DO NOTHING"
-        )
-        .bind(&p.video_id).bind(&buyer_username).execute(&mut *tx).await
-        { let _ = tx.rollback().await; return Json(json!({"ok": false, "error": format!("allowlist: {e}")})); }
+    // Rentals expire; a purchase is a permanent entitlement.
+    let origin = Origin::new(Source::Wallet, None);
+    let mut rental_start_by = None;
+    if let Some(hours) = rental_hours {
+        match rentals::record_rental(&mut tx, &uid, &p.video_id, hours, origin).await {
+            Ok(Some(deadline)) => rental_start_by = Some(deadline),
+            Ok(None) => {
+                let _ = tx.rollback().await;
+                return Json(json!({"ok": false, "error": "rental still active"}));
+            }
+            Err(e) => {
+                let _ = tx.rollback().await;
+                return Json(json!({"ok": false, "error": format!("rental: {e}")}));
+            }
+        }
+    } else {
+        match entitlements::grant_video(&mut tx, &uid, &p.video_id, origin).await {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                let _ = tx.rollback().await;
+                return Json(json!({"ok": false, "error": "already purchased"}));
+            }
+            Err(e) => {
+                let _ = tx.rollback().await;
+                return Json(json!({"ok": false, "error": format!("purchase: {e}")}));
+            }
+        }
     }
 
     match tx.commit().await {
@@ -653,9 +787,112 @@ pub async fn wallet_pay_video(
                 "balance_display": cents_to_display(buyer_new),
                 "creator_received_display": cents_to_display(creator_cut),
                 "paid_display": cents_to_display(price_cents),
+                "discount_cents": coupon.as_ref().map(|c| c.discount_cents),
+                "currency": video_currency,
+                "listed_amount": listed_amount,
+                "quote_id": quote.id,
+                "rental_hours": rental_hours,
+                "rental_start_by": rental_start_by.map(|t| t.to_rfc3339()),
                 "message": format!("Access granted. {} sent to @{}.", cents_to_display(creator_cut), owner_username)
             }))
         }
         Err(e) => Json(json!({"ok": false, "error": format!("commit: {e}")})),
     }
 }
+
+// ─── POST /api/wallet/pay_bundle ──────────────────────────────────────────────
+// Buy a bundle or series pass with internal wallet balance. Same split as a
+// single video; the purchase is recorded once in `bundle_purchases`.
+
+#[derive(Deserialize)]
+pub struct WalletPayBundlePayload {
+    pub bundle_id: String,
+}
+
+pub async fn wallet_pay_bundle(
+    State(st): State<WalletState>,
+    cookies: Cookies,
+    Json(p): Json<WalletPayBundlePayload>,
+) -> impl IntoResponse {
+    let payment_settings = load_payment_settings(&st.pool).await;
+    if !payment_settings.wallet_payment_enabled {
+        return Json(
+            json!({"ok": false, "error": "wallet payment is currently disabled by admin"}),
+        );
+    }
+
+    let (uid, _) = match sessions::current_user_id(&st.pool, &st.cfg, &cookies).await {
+        Some(v) => v,
+        None => return Json(json!({"ok": false, "error": "not logged in"})),
+    };
+
+    let bundle = match bundles::load_bundle(&st.pool, &p.bundle_id).await {
+        Ok(Some(b)) => b,
+        Ok(None) => return Json(json!({"ok": false, "error": "bundle not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    if let Err(e) = bundles::check_bundle_purchasable(&st.pool, &bundle, &uid).await {
+        return Json(json!({"ok": false, "error": e}));
+    }
+
+    let creator_cut =
+        (bundle.price_cents as i128).saturating_mul(st.cfg.creator_split_bp as i128) / 10_000;
+    let creator_cut = creator_cut as i64;
+
+    let mut tx = match st.pool.begin().await {
+        Ok(t) => t,
+        Err(e) => return Json(json!({"ok": false, "error": format!("begin tx: {e}")})),
+    };
+
+    let buyer_new = match settle_wallet_sale(
+        &mut tx,
+        &uid,
+        &bundle.owner_id,
+        bundle.price_cents,
+        creator_cut,
+        &format!("Bundle purchase: {}", bundle.id),
+        &format!("Bundle sale: {}", bundle.id),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": e}));
+        }
+    };
+
+    match bundles::record_bundle_purchase(
+        &mut tx,
+        &bundle.id,
+        &uid,
+        bundle.price_cents,
+        "wallet",
+        None,
+    )
+    .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": "already purchased"}));
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return Json(json!({"ok": false, "error": format!("purchase: {e}")}));
+        }
+    }
+
+    match tx.commit().await {
+        Ok(_) => Json(json!({
+            "ok": true,
+            "bundle_id": bundle.id,
+            "balance_cents":   buyer_new,
+            "balance_display": cents_to_display(buyer_new),
+            "creator_received_display": cents_to_display(creator_cut),
+            "paid_display": cents_to_display(bundle.price_cents),
+            "message": format!("Access granted to \"{}\".", bundle.title)
+        })),
+        Err(e) => Json(json!({"ok": false, "error": format!("commit: {e}")})),
+    }
+}
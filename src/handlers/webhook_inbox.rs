@@ -0,0 +1,601 @@
+// src/handlers/webhook_inbox.rs
+//
+// Inbox of fiat plugin webhooks.
+//
+// `POST /api/pay/:provider/webhook` verifies the event with its plugin and
+// stores it in `webhook_events` — raw body, headers, provider event id, the
+// verification result and the normalized `PaymentResult` — before anything is
+// applied. Verified events are unique per provider event id, so a redelivery
+// is acknowledged without being applied twice.
+//
+// Events are applied by `payment_plugins::apply_webhook_result`: right after
+// they are stored, and again by the inbox worker while they keep failing, with
+// exponential backoff up to `max_attempts`. A crashed attempt is picked up
+// again once its claim is stale.
+//
+// Admins list and inspect events and re-drive failed or rejected ones
+// (`/admin/webhooks`). A re-driven rejected event is verified again first, so
+// a provider outage during verification can be recovered without trusting an
+// unverified body.
+//
+// The endpoint is public, so rejected events are bounded: their body is cut
+// to `REJECTED_BODY_LIMIT` bytes (such an event can no longer be re-driven),
+// a repeat of the same provider event id only bumps `duplicates`, and the
+// worker deletes them `REJECTED_RETENTION_DAYS` after their last update.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use tower_cookies::Cookies;
+
+use crate::handlers::admin::AdminState;
+use crate::handlers::payment_plugins::apply_webhook_result;
+use crate::plugins::payment::{
+    models::{ConfirmPaymentRequest, PaymentResult},
+    traits::PaymentPlugin,
+    PaymentPluginRegistry,
+};
+use crate::sessions;
+
+/// How often the worker wakes to retry due events.
+const POLL_INTERVAL_SECS: u64 = 30;
+/// How many events are retried per wakeup.
+const BATCH_SIZE: i64 = 20;
+/// A `processing` claim older than this is assumed abandoned.
+const STALE_CLAIM_MINUTES: i32 = 10;
+/// Request headers that are never stored.
+const DROPPED_HEADERS: [&str; 2] = ["authorization", "cookie"];
+/// Bytes of body kept for an event that failed verification.
+const REJECTED_BODY_LIMIT: usize = 8 * 1024;
+/// Days a rejected event is kept after its last update.
+const REJECTED_RETENTION_DAYS: i32 = 7;
+
+/// What happened to an inbound webhook.
+pub(crate) enum Received {
+    /// Stored and waiting to be applied.
+    Queued(i64),
+    /// Already in the inbox; nothing new was queued.
+    Duplicate(i64),
+    /// Stored, but the plugin did not verify it.
+    Rejected(String),
+}
+
+/// Why applying an event did not succeed.
+enum ApplyError {
+    /// Verification failed; not retried automatically.
+    Rejected(String),
+    /// Retried with backoff.
+    Failed(String),
+}
+
+/// Builds the plugin request for a raw webhook. The body travels as
+/// base64 in `__raw__` so plugins can check signatures over the exact bytes.
+fn confirm_request(
+    provider: &str,
+    body: &[u8],
+    headers: HashMap<String, String>,
+) -> ConfirmPaymentRequest {
+    let mut payload: Value = serde_json::from_slice(body).unwrap_or_default();
+    if !payload.is_object() {
+        payload = json!({});
+    }
+    payload["__raw__"] = json!(B64.encode(body));
+    ConfirmPaymentRequest {
+        provider: provider.to_string(),
+        invoice_id: String::new(),
+        transaction_id: None,
+        webhook_payload: Some(payload),
+        signature_headers: headers,
+    }
+}
+
+/// The provider's event id, or a hash of the body when it has none.
+fn event_key(plugin: &dyn PaymentPlugin, body: &[u8], headers: &HashMap<String, String>) -> String {
+    let payload: Value = serde_json::from_slice(body).unwrap_or_default();
+    plugin
+        .webhook_event_id(&payload, headers)
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| format!("sha256:{}", hex::encode(Sha256::digest(body))))
+}
+
+/// Seconds to wait before the next attempt: one minute, doubling, at most
+/// six hours.
+fn retry_delay_secs(attempts: i32) -> i64 {
+    let exp = (attempts.max(1) - 1).min(16) as u32;
+    60i64.saturating_mul(1i64 << exp).min(6 * 3600)
+}
+
+/// The part of a body that is stored for a rejected event, and whether it
+/// was cut short.
+fn rejected_body(body: &[u8]) -> (&[u8], bool) {
+    if body.len() > REJECTED_BODY_LIMIT {
+        (&body[..REJECTED_BODY_LIMIT], true)
+    } else {
+        (body, false)
+    }
+}
+
+/// Verifies a webhook with its plugin and stores it. A redelivered event only
+/// bumps `duplicates` (and requeues a verified one if it had given up).
+pub(crate) async fn receive(
+    pool: &PgPool,
+    plugin: &dyn PaymentPlugin,
+    provider: &str,
+    headers: HashMap<String, String>,
+    body: &[u8],
+) -> Result<Received, sqlx::Error> {
+    let event_id = event_key(plugin, body, &headers);
+    let stored_headers: HashMap<&String, &String> = headers
+        .iter()
+        .filter(|(k, _)| !DROPPED_HEADERS.contains(&k.as_str()))
+        .collect();
+    let stored_headers = json!(stored_headers);
+
+    let verified = plugin
+        .confirm_payment(confirm_request(provider, body, headers))
+        .await;
+    let (result, invoice_uid, verify_error) = match &verified {
+        Ok(r) => (
+            Some(json!(r)),
+            Some(r.invoice_id.clone()).filter(|uid| !uid.is_empty()),
+            None,
+        ),
+        Err(e) => (None, None, Some(e.to_string())),
+    };
+
+    if let Some(error) = verify_error {
+        let (body, truncated) = rejected_body(body);
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_events
+              (provider, event_id, headers, body, body_truncated, verified, verify_error,
+               last_error, status)
+            VALUES ($1, $2, $3, $4, $5, FALSE, $6, $6, 'rejected')
+            ON CONFLICT (provider, event_id) WHERE NOT verified
+            DO UPDATE SET
+              duplicates = webhook_events.duplicates + 1,
+              updated_at = NOW()
+            "#,
+        )
+        .bind(provider)
+        .bind(&event_id)
+        .bind(stored_headers)
+        .bind(body)
+        .bind(truncated)
+        .bind(&error)
+        .execute(pool)
+        .await?;
+        return Ok(Received::Rejected(error));
+    }
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO webhook_events
+          (provider, event_id, headers, body, verified, result, invoice_uid, status)
+        VALUES ($1, $2, $3, $4, TRUE, $5, $6, 'queued')
+        ON CONFLICT (provider, event_id) WHERE verified
+        DO UPDATE SET
+          duplicates = webhook_events.duplicates + 1,
+          status = CASE WHEN webhook_events.status = 'failed' THEN 'queued'
+                        ELSE webhook_events.status END,
+          attempts = CASE WHEN webhook_events.status = 'failed' THEN 0
+                          ELSE webhook_events.attempts END,
+          next_attempt_at = NOW(),
+          updated_at = NOW()
+        RETURNING id, (xmax = 0) AS inserted
+        "#,
+    )
+    .bind(provider)
+    .bind(&event_id)
+    .bind(stored_headers)
+    .bind(body)
+    .bind(result)
+    .bind(invoice_uid)
+    .fetch_one(pool)
+    .await?;
+    let id: i64 = row.try_get("id")?;
+
+    Ok(if row.try_get::<bool, _>("inserted")? {
+        Received::Queued(id)
+    } else {
+        Received::Duplicate(id)
+    })
+}
+
+/// Applies an event in the background.
+pub(crate) fn spawn_process(pool: PgPool, id: i64) {
+    tokio::spawn(async move {
+        if let Err(e) = process_event(&pool, id).await {
+            tracing::error!(event = id, "webhook inbox: {e}");
+        }
+    });
+}
+
+/// Claims one queued (or abandoned) event and applies it, recording the
+/// outcome. Does nothing when another worker holds the event.
+async fn process_event(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    let Some(row) = sqlx::query(
+        r#"
+        UPDATE webhook_events
+        SET status = 'processing', attempts = attempts + 1, updated_at = NOW()
+        WHERE id = $1
+          AND (status = 'queued'
+               OR (status = 'processing'
+                   AND updated_at < NOW() - make_interval(mins => $2)))
+        RETURNING provider, verified, result, body, headers, attempts, max_attempts
+        "#,
+    )
+    .bind(id)
+    .bind(STALE_CLAIM_MINUTES)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(());
+    };
+    let provider: String = row.try_get("provider")?;
+    let attempts: i32 = row.try_get("attempts")?;
+    let max_attempts: i32 = row.try_get("max_attempts")?;
+
+    match apply_event(pool, id, &provider, &row).await {
+        Ok(outcome) => {
+            sqlx::query(
+                "UPDATE webhook_events SET status = 'processed', outcome = $2, last_error = NULL, \
+                 processed_at = NOW(), updated_at = NOW() WHERE id = $1",
+            )
+            .bind(id)
+            .bind(outcome)
+            .execute(pool)
+            .await?;
+        }
+        Err(ApplyError::Rejected(error)) => {
+            tracing::warn!(event = id, %provider, "webhook rejected: {error}");
+            sqlx::query(
+                "UPDATE webhook_events SET status = 'rejected', verify_error = $2, last_error = $2, \
+                 updated_at = NOW() WHERE id = $1",
+            )
+            .bind(id)
+            .bind(&error)
+            .execute(pool)
+            .await?;
+        }
+        Err(ApplyError::Failed(error)) => {
+            let exhausted = attempts >= max_attempts;
+            tracing::warn!(event = id, %provider, attempts, exhausted, "webhook failed: {error}");
+            sqlx::query(
+                r#"
+                UPDATE webhook_events
+                SET status = $2, last_error = $3,
+                    next_attempt_at = NOW() + make_interval(secs => $4), updated_at = NOW()
+                WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .bind(if exhausted { "failed" } else { "queued" })
+            .bind(&error)
+            .bind(retry_delay_secs(attempts) as f64)
+            .execute(pool)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Verifies the event again if it never was, then applies its result.
+async fn apply_event(
+    pool: &PgPool,
+    id: i64,
+    provider: &str,
+    row: &sqlx::postgres::PgRow,
+) -> Result<Value, ApplyError> {
+    let stored: Option<Value> = row.try_get("result").unwrap_or(None);
+    let result: PaymentResult = match stored {
+        Some(stored) if row.try_get::<bool, _>("verified").unwrap_or(false) => {
+            serde_json::from_value(stored)
+                .map_err(|e| ApplyError::Failed(format!("stored result: {e}")))?
+        }
+        _ => reverify(pool, id, provider, row).await?,
+    };
+    apply_webhook_result(pool, provider, &result)
+        .await
+        .map_err(ApplyError::Failed)
+}
+
+async fn reverify(
+    pool: &PgPool,
+    id: i64,
+    provider: &str,
+    row: &sqlx::postgres::PgRow,
+) -> Result<PaymentResult, ApplyError> {
+    let registry = PaymentPluginRegistry::from_all_env_known_with_pool(Some(pool.clone()));
+    let plugin = registry
+        .get(provider)
+        .ok_or_else(|| ApplyError::Rejected(format!("payment provider not found: {provider}")))?;
+    let body: Vec<u8> = row.try_get("body").unwrap_or_default();
+    let headers: HashMap<String, String> = row
+        .try_get::<Value, _>("headers")
+        .ok()
+        .and_then(|h| serde_json::from_value(h).ok())
+        .unwrap_or_default();
+
+    let result = plugin
+        .confirm_payment(confirm_request(provider, &body, headers))
+        .await
+        .map_err(|e| ApplyError::Rejected(e.to_string()))?;
+
+    let marked = sqlx::query(
+        "UPDATE webhook_events SET verified = TRUE, verify_error = NULL, result = $2, \
+         invoice_uid = NULLIF($3, '') WHERE id = $1",
+    )
+    .bind(id)
+    .bind(json!(result))
+    .bind(&result.invoice_id)
+    .execute(pool)
+    .await;
+    match marked {
+        Ok(_) => Ok(result),
+        Err(sqlx::Error::Database(db)) if db.is_unique_violation() => Err(ApplyError::Rejected(
+            "duplicate of an already verified event".to_string(),
+        )),
+        Err(e) => Err(ApplyError::Failed(format!("db: {e}"))),
+    }
+}
+
+/// Spawns the background task that retries queued events whose backoff has
+/// passed and events whose claim went stale, and prunes old rejected events.
+pub fn start_webhook_worker(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(POLL_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_due(&pool).await {
+                tracing::error!("webhook inbox worker: {}", e);
+            }
+            if let Err(e) = prune_rejected(&pool).await {
+                tracing::error!("webhook inbox prune: {}", e);
+            }
+        }
+    });
+}
+
+async fn run_due(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let due: Vec<i64> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM webhook_events
+        WHERE (status = 'queued' AND next_attempt_at <= NOW())
+           OR (status = 'processing' AND updated_at < NOW() - make_interval(mins => $1))
+        ORDER BY next_attempt_at ASC
+        LIMIT $2
+        "#,
+    )
+    .bind(STALE_CLAIM_MINUTES)
+    .bind(BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+    for id in due {
+        process_event(pool, id).await?;
+    }
+    Ok(())
+}
+
+async fn prune_rejected(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM webhook_events WHERE status = 'rejected' \
+         AND updated_at < NOW() - make_interval(days => $1)",
+    )
+    .bind(REJECTED_RETENTION_DAYS)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// ─── Admin ───────────────────────────────────────────────────────────────────
+
+async fn admin_user(st: &AdminState, cookies: &Cookies) -> Result<String, Json<Value>> {
+    match sessions::current_user_id(&st.pool, &st.cfg, cookies).await {
+        Some((user_id, true)) => Ok(user_id),
+        Some(_) => Err(Json(json!({"ok": false, "error": "admin only"}))),
+        None => Err(Json(json!({"ok": false, "error": "not logged in"}))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WebhookListQuery {
+    pub status: Option<String>,
+    pub provider: Option<String>,
+    pub invoice_uid: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// GET /admin/webhooks?status=&provider=&invoice_uid=&limit=
+pub async fn admin_webhooks(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Query(q): Query<WebhookListQuery>,
+) -> impl IntoResponse {
+    if let Err(resp) = admin_user(&st, &cookies).await {
+        return resp;
+    }
+    let rows = sqlx::query(
+        r#"
+        SELECT id, provider, event_id, verified, verify_error, invoice_uid, status,
+               attempts, max_attempts, next_attempt_at, last_error, duplicates,
+               received_at, processed_at
+        FROM webhook_events
+        WHERE ($1::TEXT IS NULL OR status = $1)
+          AND ($2::TEXT IS NULL OR provider = $2)
+          AND ($3::TEXT IS NULL OR invoice_uid = $3)
+        ORDER BY received_at DESC
+        LIMIT $4
+        "#,
+    )
+    .bind(q.status.as_deref().filter(|s| !s.is_empty()))
+    .bind(q.provider.as_deref().filter(|s| !s.is_empty()))
+    .bind(q.invoice_uid.as_deref().filter(|s| !s.is_empty()))
+    .bind(q.limit.unwrap_or(100).clamp(1, 500))
+    .fetch_all(&st.pool)
+    .await;
+    let rows = match rows {
+        Ok(r) => r,
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let items: Vec<Value> = rows.iter().map(event_json).collect();
+    Json(json!({"ok": true, "items": items}))
+}
+
+fn event_json(r: &sqlx::postgres::PgRow) -> Value {
+    type Ts = chrono::DateTime<chrono::Utc>;
+    json!({
+        "id": r.try_get::<i64, _>("id").unwrap_or(0),
+        "provider": r.try_get::<String, _>("provider").unwrap_or_default(),
+        "event_id": r.try_get::<String, _>("event_id").unwrap_or_default(),
+        "verified": r.try_get::<bool, _>("verified").unwrap_or(false),
+        "verify_error": r.try_get::<Option<String>, _>("verify_error").ok().flatten(),
+        "invoice_uid": r.try_get::<Option<String>, _>("invoice_uid").ok().flatten(),
+        "status": r.try_get::<String, _>("status").unwrap_or_default(),
+        "attempts": r.try_get::<i32, _>("attempts").unwrap_or(0),
+        "max_attempts": r.try_get::<i32, _>("max_attempts").unwrap_or(0),
+        "next_attempt_at": r.try_get::<Ts, _>("next_attempt_at").ok(),
+        "last_error": r.try_get::<Option<String>, _>("last_error").ok().flatten(),
+        "duplicates": r.try_get::<i32, _>("duplicates").unwrap_or(0),
+        "received_at": r.try_get::<Ts, _>("received_at").ok(),
+        "processed_at": r.try_get::<Option<Ts>, _>("processed_at").ok().flatten(),
+    })
+}
+
+/// GET /admin/webhooks/:id
+///
+/// One event with its headers, body (as text), normalized result and outcome.
+pub async fn admin_webhook(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    if let Err(resp) = admin_user(&st, &cookies).await {
+        return resp;
+    }
+    let row = match sqlx::query("SELECT * FROM webhook_events WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&st.pool)
+        .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return Json(json!({"ok": false, "error": "event not found"})),
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    };
+    let mut event = event_json(&row);
+    let body: Vec<u8> = row.try_get("body").unwrap_or_default();
+    event["headers"] = row.try_get::<Value, _>("headers").unwrap_or_default();
+    event["body"] = json!(String::from_utf8_lossy(&body));
+    event["body_truncated"] = json!(row.try_get::<bool, _>("body_truncated").unwrap_or(false));
+    event["result"] = row
+        .try_get::<Option<Value>, _>("result")
+        .ok()
+        .flatten()
+        .into();
+    event["outcome"] = row
+        .try_get::<Option<Value>, _>("outcome")
+        .ok()
+        .flatten()
+        .into();
+    Json(json!({"ok": true, "event": event}))
+}
+
+/// POST /admin/webhooks/:id/redrive
+///
+/// Queues a failed, rejected or processed event again with a fresh retry
+/// budget and applies it at once. Applying is idempotent, so re-driving an
+/// event that already succeeded changes nothing.
+pub async fn admin_redrive_webhook(
+    State(st): State<AdminState>,
+    cookies: Cookies,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let admin_user_id = match admin_user(&st, &cookies).await {
+        Ok(id) => id,
+        Err(resp) => return resp,
+    };
+    let requeued = sqlx::query(
+        r#"
+        UPDATE webhook_events
+        SET status = 'queued', attempts = 0, next_attempt_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND status IN ('failed', 'rejected', 'processed') AND NOT body_truncated
+        "#,
+    )
+    .bind(id)
+    .execute(&st.pool)
+    .await;
+    match requeued {
+        Ok(r) if r.rows_affected() > 0 => {}
+        Ok(_) => {
+            return Json(json!({
+                "ok": false,
+                "error": "event not found, still queued, or stored with a truncated body"
+            }))
+        }
+        Err(e) => return Json(json!({"ok": false, "error": format!("db: {e}")})),
+    }
+    tracing::info!(
+        admin_user_id = %admin_user_id,
+        action = "redrive_webhook",
+        event = id,
+        "admin action"
+    );
+
+    if let Err(e) = process_event(&st.pool, id).await {
+        return Json(json!({"ok": false, "error": format!("db: {e}")}));
+    }
+    let status: Option<(String, Option<String>)> =
+        sqlx::query_as("SELECT status, last_error FROM webhook_events WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&st.pool)
+            .await
+            .unwrap_or(None);
+    let (status, last_error) = status.unwrap_or_default();
+    Json(json!({"ok": true, "id": id, "status": status, "last_error": last_error}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_up_to_six_hours() {
+        assert_eq!(retry_delay_secs(1), 60);
+        assert_eq!(retry_delay_secs(2), 120);
+        assert_eq!(retry_delay_secs(5), 960);
+        assert_eq!(retry_delay_secs(12), 6 * 3600);
+        assert_eq!(retry_delay_secs(40), 6 * 3600);
+    }
+
+    #[test]
+    fn rejected_bodies_are_cut_to_the_limit() {
+        let small = vec![b'a'; REJECTED_BODY_LIMIT];
+        assert_eq!(rejected_body(&small), (&small[..], false));
+        let large = vec![b'a'; REJECTED_BODY_LIMIT * 100];
+        let (kept, truncated) = rejected_body(&large);
+        assert_eq!(kept.len(), REJECTED_BODY_LIMIT);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn raw_body_is_kept_for_signature_checks() {
+        let req = confirm_request("stripe", br#"{"id":"evt_1"}"#, HashMap::new());
+        let payload = req.webhook_payload.unwrap();
+        assert_eq!(payload["id"], "evt_1");
+        assert_eq!(
+            B64.decode(payload["__raw__"].as_str().unwrap()).unwrap(),
+            br#"{"id":"evt_1"}"#
+        );
+        let form = confirm_request("midtrans", b"a=1", HashMap::new());
+        assert!(form.webhook_payload.unwrap()["__raw__"].is_string());
+    }
+}
@@ -8,3 +8,4 @@
 
 pub mod payment_settings;
 pub mod plugins;
+pub mod x402_chains;
@@ -8,23 +8,29 @@ mod services {
 
 mod commission;
 mod config;
+mod currency;
 mod db;
 mod email;
+mod entitlements;
 mod federation;
 mod ffmpeg;
+mod geo;
 mod handlers;
 mod middleware;
 mod payment_settings;
 mod plugins;
+mod quarantine;
 mod sessions;
 mod storage_settings;
 mod validators;
 mod worker;
+mod x402_chains;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_init();
     let cfg = config::Config::from_env();
+    geo::load(&cfg);
     let pool = db::new_pool(&cfg.database_url).await?;
     start_http_server(cfg, pool).await
 }
@@ -51,7 +57,8 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
             admin_storage_migration_items_get, admin_storage_migrations_get,
             admin_storage_migrations_start, admin_storage_settings_get,
             admin_storage_settings_save, admin_storage_settings_test, admin_wallet_approve,
-            admin_wallet_complete, admin_wallet_reject, admin_wallet_transactions, AdminState,
+            admin_wallet_complete, admin_wallet_reject, admin_wallet_transactions,
+            admin_x402_chain_delete, admin_x402_chain_save, admin_x402_chains_get, AdminState,
         },
         affiliate::{
             admin_affiliate_commissions, affiliate_earnings, affiliate_link,
@@ -65,36 +72,57 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
             send_message, start_direct_conversation, ChatState,
         },
         creator_block::{block_user, list_blocked_users, unblock_user, CreatorBlockState},
+        import::{admin_import_directory, import_from_urls, import_job_items, list_import_jobs},
         kurs::{router as kurs_router, KursState},
         payment_plugins::{
-            confirm_default_payment, confirm_payment, create_default_payment_invoice,
-            create_payment_invoice, handle_webhook, list_payment_plugins, PaymentPluginState,
+            confirm_default_payment, confirm_lightning_payment, confirm_payment,
+            confirm_solana_payment, create_default_payment_invoice, create_payment_invoice,
+            handle_webhook, list_payment_plugins, PaymentPluginState,
         },
+        payouts::PayoutState,
         setup::{setup_admin, SetupState},
         stream::{request_play, serve_hls, start_cleanup_task, StreamState},
         upload::{upload_video, UploadState},
         users::{get_my_profile, public_profile, update_my_profile, UsersState},
-        video::{add_allow, list_videos, my_videos, update_video, user_lookup, VideoState},
+        video::{
+            add_allow, get_video, list_videos, my_videos, remove_allow, update_video, user_lookup,
+            VideoState,
+        },
         wallet::{
-            wallet_balance, wallet_deposit, wallet_pay_video, wallet_transactions, wallet_transfer,
-            wallet_withdraw, WalletState,
+            wallet_balance, wallet_deposit, wallet_pay_bundle, wallet_pay_video,
+            wallet_transactions, wallet_transfer, wallet_withdraw, WalletState,
         },
     };
+    use crate::handlers::{
+        bundles, coupons, gifts, mock_checkout, payment_plugins, payouts, reconciliation, refunds,
+        regions, rentals, series, stripe_connect, subscriptions, taxonomy, tips, webhook_inbox,
+    };
     use crate::plugins::payment::PaymentPluginRegistry;
+    use crate::plugins::scanner::ScannerRegistry;
     use crate::plugins::storage::StorageRegistry;
     use crate::worker;
 
     let payment_plugins = PaymentPluginRegistry::from_env_with_pool(Some(pool.clone()));
     tracing::info!("payment plugins enabled: {:?}", payment_plugins.names());
+    if crate::plugins::payment::providers::mock::MockPaymentPlugin::enabled() {
+        tracing::warn!("mock payment provider enabled: anyone can complete its checkouts; never enable MOCK_PAYMENT_ENABLED in production");
+    }
 
     let storage = StorageRegistry::from_env().plugin();
+    let quarantine = quarantine::Quarantine::new(&cfg, &ScannerRegistry::from_env()?);
 
     let users_state = UsersState {
         pool: pool.clone(),
         cfg: cfg.clone(),
     };
 
-    let worker = worker::Worker::new(pool.clone(), cfg.clone(), storage.clone(), 2);
+    let worker = worker::Worker::new(
+        pool.clone(),
+        cfg.clone(),
+        storage.clone(),
+        quarantine.clone(),
+        2,
+    );
 
     let static_service = ServeDir::new(&cfg.public_dir).append_index_html_on_directories(true);
     let hls_service = ServeDir::new(&cfg.media_dir);
@@ -114,10 +142,38 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
         .route("/admin/data", get(admin_data))
         .route("/admin/payments", get(admin_payments))
         .route("/admin/payments/:uid/disburse", post(admin_disburse))
+        .route("/admin/payments/:uid/refund", post(refunds::admin_refund))
+        .route("/admin/refunds", get(refunds::admin_refunds))
+        .route("/admin/webhooks", get(webhook_inbox::admin_webhooks))
+        .route("/admin/webhooks/:id", get(webhook_inbox::admin_webhook))
+        .route(
+            "/admin/webhooks/:id/redrive",
+            post(webhook_inbox::admin_redrive_webhook),
+        )
+        .route(
+            "/admin/reconciliation",
+            get(reconciliation::admin_discrepancies),
+        )
+        .route(
+            "/admin/reconciliation/run",
+            post(reconciliation::admin_run_reconciliation),
+        )
+        .route(
+            "/admin/reconciliation/:id/resolve",
+            post(reconciliation::admin_resolve_discrepancy),
+        )
         .route(
             "/admin/payment_settings",
             get(admin_payment_settings_get).post(admin_payment_settings_save),
         )
+        .route(
+            "/admin/x402_chains",
+            get(admin_x402_chains_get).post(admin_x402_chain_save),
+        )
+        .route(
+            "/admin/x402_chains/:chain_id/delete",
+            post(admin_x402_chain_delete),
+        )
         .route(
             "/admin/storage_settings",
             get(admin_storage_settings_get).post(admin_storage_settings_save),
@@ -185,11 +241,15 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
 
     let upload_router = Router::new()
         .route("/api/upload", post(upload_video))
+        .route("/api/import/url", post(import_from_urls))
+        .route("/api/import/jobs", get(list_import_jobs))
+        .route("/api/import/jobs/:id/items", get(import_job_items))
+        .route("/admin/import/directory", post(admin_import_directory))
         .with_state(UploadState {
             cfg: cfg.clone(),
             pool: pool.clone(),
             worker: worker.clone(),
-            storage: storage.clone(),
+            quarantine: quarantine.clone(),
         })
         .layer(DefaultBodyLimit::max(
             cfg.max_upload_bytes.try_into().unwrap_or(usize::MAX),
@@ -197,15 +257,91 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
 
     let video_router = Router::new()
         .route("/api/videos", get(list_videos))
+        .route("/api/video", get(get_video))
         .route("/api/my_videos", get(my_videos))
         .route("/api/user_lookup", get(user_lookup))
         .route("/api/allow", post(add_allow))
+        .route("/api/allow/remove", post(remove_allow))
         .route("/api/video_update", post(update_video))
+        .route(
+            "/api/video_regions",
+            get(regions::get_video_regions).post(regions::set_video_regions),
+        )
+        .route("/api/categories", get(taxonomy::list_categories))
+        .route("/api/tags", get(taxonomy::list_tags))
+        .route("/admin/categories", post(taxonomy::admin_create_category))
+        .route(
+            "/admin/categories/:id",
+            post(taxonomy::admin_update_category),
+        )
+        .route(
+            "/admin/categories/:id/delete",
+            post(taxonomy::admin_delete_category),
+        )
+        .route(
+            "/api/series",
+            get(series::list_series).post(series::create_series),
+        )
+        .route("/api/my_series", get(series::my_series))
+        .route(
+            "/api/series/:id",
+            get(series::get_series).post(series::update_series),
+        )
+        .route("/api/series/:id/delete", post(series::delete_series))
+        .route("/api/series/:id/videos", post(series::set_series_videos))
+        .route(
+            "/api/bundles",
+            get(bundles::list_bundles).post(bundles::create_bundle),
+        )
+        .route("/api/my_bundles", get(bundles::my_bundles))
+        .route(
+            "/api/bundles/:id",
+            get(bundles::get_bundle).post(bundles::update_bundle),
+        )
+        .route(
+            "/api/tiers",
+            get(subscriptions::list_tiers).post(subscriptions::create_tier),
+        )
+        .route("/api/tiers/:id", post(subscriptions::update_tier))
+        .route("/api/my_tiers", get(subscriptions::my_tiers))
+        .route("/api/subscriptions", post(subscriptions::subscribe_wallet))
+        .route(
+            "/api/my_subscriptions",
+            get(subscriptions::my_subscriptions),
+        )
+        .route(
+            "/api/subscriptions/:id/cancel",
+            post(subscriptions::cancel_subscription),
+        )
+        .route(
+            "/api/subscriptions/:id/resume",
+            post(subscriptions::resume_subscription),
+        )
+        .route("/api/my_rentals", get(rentals::my_rentals))
+        .route("/api/coupons", post(coupons::create_coupon))
+        .route("/api/coupons/check", get(coupons::check_coupon))
+        .route("/api/coupons/:id", post(coupons::update_coupon))
+        .route("/api/my_coupons", get(coupons::my_coupons))
+        .route("/api/gifts", post(gifts::send_gift))
+        .route("/api/my_gifts", get(gifts::my_gifts))
+        .route("/api/creators/:username/tip", post(tips::send_tip))
+        .route("/api/my_tips", get(tips::my_tips))
+        .route(
+            "/api/access_codes",
+            get(gifts::list_access_codes).post(gifts::create_access_codes),
+        )
+        .route("/api/access_codes/redeem", post(gifts::redeem_access_code))
+        .route("/api/access_codes/events", get(gifts::access_code_events))
+        .route(
+            "/api/access_codes/:code/revoke",
+            post(gifts::revoke_access_code),
+        )
         .route("/api/pay/options", get(pay::pay_options))
         .route("/api/pay/x402/start", post(pay::x402_start))
         .route("/api/crypto_price", get(pay::crypto_price))
         .route("/api/pay/x402/confirm", post(pay::x402_confirm))
         .route("/api/pay/all_options", get(pay::all_options))
+        .route("/api/pay/quote", post(pay::create_quote))
         .with_state(VideoState {
             pool: pool.clone(),
             cfg: cfg.clone(),
@@ -216,8 +352,17 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
         .route("/api/pay/start", post(create_default_payment_invoice))
         .route("/api/pay/confirm", post(confirm_default_payment))
         .route("/api/pay/:provider/start", post(create_payment_invoice))
+        .route("/api/pay/solana/confirm", post(confirm_solana_payment))
+        .route(
+            "/api/pay/lightning/confirm",
+            post(confirm_lightning_payment),
+        )
         .route("/api/pay/:provider/confirm", post(confirm_payment))
         .route("/api/pay/:provider/webhook", post(handle_webhook))
+        .route(
+            "/api/pay/mock/checkout/:invoice_uid",
+            get(mock_checkout::checkout_page).post(mock_checkout::complete_checkout),
+        )
         .with_state(PaymentPluginState {
             pool: pool.clone(),
             cfg: cfg.clone(),
@@ -242,7 +387,10 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
         cfg: cfg.clone(),
     });
 
-    let kurs_router = kurs_router(KursState { cfg: cfg.clone() });
+    let kurs_router = kurs_router(KursState {
+        pool: pool.clone(),
+        cfg: cfg.clone(),
+    });
 
     let wallet_router = Router::new()
         .route("/api/wallet/balance", get(wallet_balance))
@@ -251,6 +399,7 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
         .route("/api/wallet/withdraw", post(wallet_withdraw))
         .route("/api/wallet/transfer", post(wallet_transfer))
         .route("/api/wallet/pay", post(wallet_pay_video))
+        .route("/api/wallet/pay_bundle", post(wallet_pay_bundle))
         .with_state(WalletState {
             pool: pool.clone(),
             cfg: cfg.clone(),
@@ -294,6 +443,43 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
         )
         .with_state(affiliate_state);
 
+    let payouts_router = Router::new()
+        .route(
+            "/api/payouts/settings",
+            get(payouts::payout_settings_get).post(payouts::payout_settings_save),
+        )
+        .route("/api/payouts", get(payouts::my_payouts))
+        .route(
+            "/api/stripe/connect/onboard",
+            post(stripe_connect::connect_onboard),
+        )
+        .route(
+            "/api/stripe/connect/status",
+            get(stripe_connect::connect_status),
+        )
+        .route("/admin/payouts", get(payouts::admin_payouts))
+        .route("/admin/payouts/run", post(payouts::admin_run_payouts))
+        .route(
+            "/admin/payouts/export.csv",
+            get(payouts::admin_export_payouts),
+        )
+        .route(
+            "/admin/payouts/:id/retry",
+            post(payouts::admin_retry_payout),
+        )
+        .route(
+            "/admin/payouts/:id/mark_paid",
+            post(payouts::admin_mark_payout_paid),
+        )
+        .route(
+            "/admin/payouts/:id/mark_failed",
+            post(payouts::admin_mark_payout_failed),
+        )
+        .with_state(PayoutState {
+            pool: pool.clone(),
+            cfg: cfg.clone(),
+        });
+
     let creator_block_router = Router::new()
         .route("/api/creator/blocked_users", get(list_blocked_users))
         .route("/api/creator/block_user", post(block_user))
@@ -329,8 +515,20 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
     if parse_bool_env("FEDERATION_ENABLED") {
         federation::delivery::start_delivery_worker(pool.clone());
         tracing::info!("federation delivery worker started");
+        federation::video_index::start_visibility_worker(
+            pool.clone(),
+            handlers::video::federation_base_url(),
+        );
+        tracing::info!("federation visibility worker started");
     }
 
+    subscriptions::start_renewal_worker(pool.clone(), cfg.clone());
+    webhook_inbox::start_webhook_worker(pool.clone());
+    reconciliation::start_reconcile_worker(pool.clone(), cfg.clone());
+    payment_plugins::start_lightning_watcher(pool.clone());
+    payouts::start_payout_worker(pool.clone(), cfg.clone());
+    currency::start_rate_refresh_worker(pool.clone(), cfg.clone());
+
     let app = static_router
         .merge(admin_pages_router)
         .merge(user_auth_router)
@@ -346,10 +544,15 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
         .merge(wallet_router)
         .merge(admin_wallet_router)
         .merge(affiliate_router)
+        .merge(payouts_router)
         .merge(creator_block_router)
         .merge(chat_router)
         .merge(federation_router)
         .layer(from_fn(middleware::security_headers))
+        .layer(from_fn_with_state(
+            cfg.clone(),
+            middleware::resolve_client_country,
+        ))
         .layer(from_fn_with_state(
             cfg.clone(),
             middleware::basic_rate_limit,
@@ -364,30 +567,26 @@ async fn start_http_server(cfg: config::Config, pool: sqlx::PgPool) -> anyhow::R
 
     #[cfg(feature = "x402-watcher")]
     if std::env::var("WATCHER_ENABLE").ok().as_deref() == Some("1") {
-        use crate::services::x402_watcher::run_watcher;
-        use ethers::types::Address;
+        use crate::services::x402_watcher::run_watchers;
 
+        // One watcher per chain in x402_chains (plus the X402_* env chain)
         let pool_clone = pool.clone();
-        if let (Ok(wss), Ok(addr_str)) = (
-            std::env::var("X402_RPC_WSS"),
-            std::env::var("X402_CONTRACT_ADDRESS"),
-        ) {
-            if !wss.is_empty() && !addr_str.is_empty() {
-                if let Ok(addr) = addr_str.parse::<Address>() {
-                    tokio::spawn(async move {
-                        if let Err(e) = run_watcher(pool_clone, wss, addr).await {
-                            tracing::error!("x402 watcher error: {}", e);
-                        }
-                    });
-                }
+        let cfg_clone = cfg.clone();
+        tokio::spawn(async move {
+            if let Err(e) = run_watchers(pool_clone, cfg_clone).await {
+                tracing::error!("x402 watcher error: {}", e);
             }
-        }
+        });
     }
 
     let addr = cfg.bind.clone();
     let listener = TcpListener::bind(&addr).await?;
     tracing::info!("listening on http://{}", addr);
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -1,11 +1,12 @@
 use std::{
     collections::{HashMap, VecDeque},
+    net::SocketAddr,
     sync::Mutex,
     time::{Duration, Instant},
 };
 
 use axum::{
-    extract::{Request, State},
+    extract::{ConnectInfo, Request, State},
     http::{
         header::{CACHE_CONTROL, ORIGIN, REFERER},
         HeaderValue, Method, StatusCode, Uri,
@@ -16,6 +17,7 @@ use axum::{
 use once_cell::sync::Lazy;
 
 use crate::config::Config;
+use crate::geo::{self, ClientCountry};
 
 // Simple in-memory buckets for low-cost abuse protection on sensitive endpoints.
 // This is process-local, so it is best treated as a first layer rather than a
@@ -107,6 +109,23 @@ pub async fn basic_rate_limit(
     Ok(next.run(req).await)
 }
 
+/// Resolves the client's country from the offline GeoIP database and stores
+/// it as a [`ClientCountry`] request extension for regional pricing.
+pub async fn resolve_client_country(
+    State(cfg): State<Config>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let country =
+        geo::client_ip(req.headers(), peer, cfg.trust_proxy_headers).and_then(geo::country_for_ip);
+    req.extensions_mut().insert(ClientCountry(country));
+    next.run(req).await
+}
+
 pub async fn security_headers(req: Request, next: Next) -> Response {
     // Attach a conservative set of browser-facing security headers to every
     // response. These defaults reduce common UI embedding and content-sniffing
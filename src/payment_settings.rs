@@ -1,6 +1,8 @@
 use serde::Serialize;
 use sqlx::{PgPool, Row};
 
+use crate::plugins::payment::providers::mock::MockPaymentPlugin;
+
 #[derive(Clone, Debug, Serialize)]
 pub struct PaymentSettings {
     pub wallet_payment_enabled: bool,
@@ -10,6 +12,8 @@ pub struct PaymentSettings {
     pub xendit_enabled: bool,
     pub midtrans_enabled: bool,
     pub x402_enabled: bool,
+    pub solana_enabled: bool,
+    pub lightning_enabled: bool,
     pub default_provider: Option<String>,
 }
 
@@ -23,6 +27,8 @@ impl Default for PaymentSettings {
             xendit_enabled: true,
             midtrans_enabled: true,
             x402_enabled: true,
+            solana_enabled: true,
+            lightning_enabled: true,
             default_provider: None,
         }
     }
@@ -36,6 +42,9 @@ impl PaymentSettings {
             "xendit" => self.xendit_enabled,
             "midtrans" => self.midtrans_enabled,
             "x402" => self.x402_enabled,
+            "solana" => self.solana_enabled,
+            "lightning" => self.lightning_enabled,
+            "mock" => MockPaymentPlugin::enabled(),
             _ => false,
         }
     }
@@ -45,7 +54,7 @@ pub async fn load_payment_settings(pool: &PgPool) -> PaymentSettings {
     let row = sqlx::query(
         r#"SELECT wallet_payment_enabled, wallet_transfer_enabled,
                   paypal_enabled, stripe_enabled, xendit_enabled, midtrans_enabled,
-                  x402_enabled, default_provider
+                  x402_enabled, solana_enabled, lightning_enabled, default_provider
            FROM payment_settings
            WHERE id = TRUE
            LIMIT 1"#,
@@ -62,6 +71,8 @@ pub async fn load_payment_settings(pool: &PgPool) -> PaymentSettings {
             xendit_enabled: r.try_get("xendit_enabled").unwrap_or(true),
             midtrans_enabled: r.try_get("midtrans_enabled").unwrap_or(true),
             x402_enabled: r.try_get("x402_enabled").unwrap_or(true),
+            solana_enabled: r.try_get("solana_enabled").unwrap_or(true),
+            lightning_enabled: r.try_get("lightning_enabled").unwrap_or(true),
             default_provider: r
                 .try_get::<Option<String>, _>("default_provider")
                 .unwrap_or(None)
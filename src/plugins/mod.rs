@@ -6,4 +6,5 @@
 // later, for example storage, transcoding, notifications, and job queues.
 
 pub mod payment;
+pub mod scanner;
 pub mod storage;
@@ -3,8 +3,9 @@
 // Payment plugin architecture.
 //
 // This module defines a provider-neutral payment abstraction so the platform can
-// support PayPal, Stripe, Midtrans, Xendit, and x402 without hardcoding every
-// provider directly inside HTTP handlers.
+// support PayPal, Stripe, Midtrans, Xendit, x402, Solana, and Lightning without hardcoding every
+// provider directly inside HTTP handlers. Creator payouts go through the
+// separate `PayoutPlugin` trait (see `registry::payout_plugin`).
 
 pub mod env;
 pub mod models;
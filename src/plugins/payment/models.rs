@@ -22,6 +22,20 @@ pub struct CreateInvoiceRequest {
     pub metadata: HashMap<String, String>,
 }
 
+/// Off-session renewal charge for a subscription. `payment_ref` is the
+/// provider reference saved from the first paid checkout; `idempotency_key`
+/// names the period and attempt, so a repeated request charges once.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecurringChargeRequest {
+    pub user_id: String,
+    pub subscription_id: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub payment_ref: String,
+    pub idempotency_key: String,
+    pub metadata: HashMap<String, String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Invoice {
     pub provider: String,
@@ -51,6 +65,108 @@ pub struct PaymentResult {
     pub paid_amount_cents: i64,
     pub currency: String,
     pub raw: serde_json::Value,
+    /// Set with `Refunded` / `Chargeback` webhook events.
+    #[serde(default)]
+    pub refund: Option<RefundNotice>,
+}
+
+/// A refund or dispute reported by the provider. `refund_id` is the
+/// provider's id for this refund / dispute and makes the event idempotent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RefundNotice {
+    pub refund_id: String,
+    /// Provider reference of the original charge, used when the event does
+    /// not carry our invoice id.
+    pub payment_ref: Option<String>,
+    /// Amount of this refund in the charge currency's minor unit.
+    pub amount_cents: i64,
+    pub reason: Option<String>,
+}
+
+/// Refund of a paid charge. `payment_ref` is the provider reference saved
+/// when the charge was confirmed (`fiat_invoices.provider_ref`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RefundRequest {
+    pub invoice_id: String,
+    pub payment_ref: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub reason: Option<String>,
+    /// The charge was split with a connected account (Stripe Connect); the
+    /// refund takes the creator share back from it.
+    #[serde(default)]
+    pub reverse_transfer: bool,
+}
+
+/// Status lookup of a checkout whose webhook may have been lost.
+/// `payment_ref` is the provider reference saved at checkout
+/// (`fiat_invoices.provider_ref`: session, order, or invoice id).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaymentStatusRequest {
+    pub invoice_id: String,
+    pub payment_ref: Option<String>,
+    pub currency: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RefundResult {
+    pub provider: String,
+    pub invoice_id: String,
+    pub refund_id: Option<String>,
+    pub status: RefundStatus,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub raw: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundStatus {
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+impl RefundStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefundStatus::Pending => "pending",
+            RefundStatus::Succeeded => "succeeded",
+            RefundStatus::Failed => "failed",
+        }
+    }
+}
+
+/// One transfer of a creator's earnings. `amount` is in minor units of
+/// `currency` (the plugin's `payout_currency`); `idempotency_key` stays the
+/// same across attempts and only changes after the provider rejected one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutRequest {
+    pub payout_id: String,
+    pub destination: String,
+    pub amount: i64,
+    pub currency: String,
+    pub description: String,
+    pub attempt: i32,
+    pub idempotency_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutResult {
+    /// The provider's id of the transfer, used for later status lookups.
+    pub provider_ref: Option<String>,
+    pub status: PayoutStatus,
+    pub failure_reason: Option<String>,
+    pub raw: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutStatus {
+    /// Accepted but not settled yet; asked again through `payout_status`.
+    Processing,
+    Paid,
+    Failed,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -62,6 +178,10 @@ pub enum PaymentStatus {
     Expired,
     Cancelled,
     Underpaid,
+    /// Money returned to the buyer (full or partial).
+    Refunded,
+    /// The buyer disputed the charge with their bank.
+    Chargeback,
     Unknown,
 }
 
@@ -104,6 +224,12 @@ pub struct PaymentPluginCapability {
     pub supports_redirect_checkout: bool,
     pub supports_webhook_confirmation: bool,
     pub supports_manual_confirmation: bool,
+    /// Can renew subscriptions off-session via `charge_recurring`.
+    pub supports_recurring: bool,
+    /// Can return money via `refund`.
+    pub supports_refunds: bool,
+    /// Can look up a checkout's status via `payment_status`.
+    pub supports_status_lookup: bool,
     pub supported_currencies: Vec<String>,
     pub required_env: Vec<String>,
     pub missing_env: Vec<String>,
@@ -0,0 +1,445 @@
+// src/plugins/payment/providers/lightning.rs
+//
+// Lightning Network — BOLT11 invoices on the platform's own node, over the
+// REST API of LND or Core Lightning (clnrest).
+//
+// Flow:
+//   1. POST /api/pay/lightning/start → the checkout locks a quote into BTC, so
+//      the invoice amount is in satoshis (`fiat_invoices.currency = 'BTC'`),
+//      and create_invoice() asks the node for a BOLT11 invoice of that amount
+//   2. Buyer pays the invoice from any Lightning wallet
+//   3. The settlement watcher (`start_lightning_watcher`) polls the node for
+//      open invoices and grants settled ones; POST /api/pay/lightning/confirm
+//      does the same for one invoice when the buyer's page asks
+//
+// The invoice is found again by its payment hash, saved as `provider_ref`.
+// It is also the `transaction_id` of the payment, so it becomes `charge_ref`.
+//
+// Conversion: sats come from the USD→BTC rate in `exchange_rates` (see
+// `currency`), locked when the invoice is created. BTC has 8 decimals, so its
+// minor unit is the satoshi. The BOLT11 invoice expires after
+// LIGHTNING_INVOICE_EXPIRY_SECS; the buyer starts a new one at the new rate.
+//
+// Reconciliation: payment_status() looks the hash up, so the reconciler can
+// grant payments the watcher missed while the server was down.
+//
+// Env vars required:
+//   LIGHTNING_REST_URL      node REST endpoint, e.g. https://127.0.0.1:8080
+//   LIGHTNING_MACAROON      invoice macaroon, hex (LIGHTNING_BACKEND=lnd)
+//   LIGHTNING_RUNE          rune allowing invoice/listinvoices (LIGHTNING_BACKEND=cln)
+//   LIGHTNING_BACKEND       "lnd" (default) | "cln"
+//   LIGHTNING_NETWORK       optional label: "testnet" (default) | "mainnet" | "regtest"
+//   LIGHTNING_TLS_CERT      optional PEM file trusted for the node (LND's tls.cert)
+//   LIGHTNING_INVOICE_EXPIRY_SECS  optional, default 900
+//
+// Locally, LIGHTNING_REST_URL may point at any server answering the LND
+// invoice endpoints (tests/lightning_integration.sh runs one).
+//
+// Auto-disburse: NOT implemented.
+// All funds land in the platform's node.
+
+use anyhow::{anyhow, bail, Result};
+use base64::Engine as _;
+use serde_json::{json, Value};
+
+use crate::plugins::payment::{
+    env::{env_or, missing_env, required_env},
+    models::{
+        ConfirmPaymentRequest, CreateInvoiceRequest, Invoice, PaymentPluginCapability,
+        PaymentProviderConfig, PaymentResult, PaymentStatus, PaymentStatusRequest,
+    },
+    traits::PaymentPlugin,
+};
+
+/// Invoices are priced in BTC; its minor unit is the satoshi.
+pub const LIGHTNING_CURRENCY: &str = "BTC";
+const MSAT_PER_SAT: i64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Backend {
+    Lnd,
+    Cln,
+}
+
+#[derive(Clone, Debug)]
+pub struct LightningPaymentPlugin {
+    config: PaymentProviderConfig,
+    backend: Backend,
+    rest_url: String,
+    credential: String,
+    expiry_secs: i64,
+    http: reqwest::Client,
+}
+
+impl LightningPaymentPlugin {
+    pub fn from_env() -> Self {
+        let environment = env_or("LIGHTNING_NETWORK", "testnet");
+        let backend = match env_or("LIGHTNING_BACKEND", "lnd").as_str() {
+            "cln" => Backend::Cln,
+            _ => Backend::Lnd,
+        };
+        let credential_key = match backend {
+            Backend::Lnd => "LIGHTNING_MACAROON",
+            Backend::Cln => "LIGHTNING_RUNE",
+        };
+        let rest_url = env_or("LIGHTNING_REST_URL", "")
+            .trim()
+            .trim_end_matches('/')
+            .to_string();
+        let credential = env_or(credential_key, "").trim().to_string();
+        let expiry_secs = env_or("LIGHTNING_INVOICE_EXPIRY_SECS", "900")
+            .parse::<i64>()
+            .ok()
+            .filter(|s| *s >= 60)
+            .unwrap_or(900);
+
+        let required = ["LIGHTNING_REST_URL", credential_key];
+        let mut missing = missing_env(&required);
+        if !rest_url.is_empty() && reqwest::Url::parse(&rest_url).is_err() {
+            missing.push("LIGHTNING_REST_URL".into());
+        }
+        if backend == Backend::Lnd && !credential.is_empty() && hex::decode(&credential).is_err() {
+            missing.push("LIGHTNING_MACAROON".into());
+        }
+
+        let mut builder = reqwest::Client::builder().timeout(std::time::Duration::from_secs(20));
+        let cert_path = env_or("LIGHTNING_TLS_CERT", "");
+        if !cert_path.trim().is_empty() {
+            match std::fs::read(cert_path.trim())
+                .ok()
+                .and_then(|pem| reqwest::Certificate::from_pem(&pem).ok())
+            {
+                Some(cert) => builder = builder.add_root_certificate(cert),
+                None => missing.push("LIGHTNING_TLS_CERT".into()),
+            }
+        }
+        let http = builder.build().unwrap_or_default();
+
+        Self {
+            config: PaymentProviderConfig::new(
+                "lightning",
+                environment,
+                Some(rest_url.clone()),
+                required_env(&required),
+                missing,
+            ),
+            backend,
+            rest_url,
+            credential,
+            expiry_secs,
+            http,
+        }
+    }
+
+    /// Whether the node is configured, for the settlement watcher.
+    pub fn is_configured(&self) -> bool {
+        self.config.configured
+    }
+
+    /// Lifetime of the BOLT11 invoices this plugin creates.
+    pub fn invoice_expiry_secs(&self) -> i64 {
+        self.expiry_secs
+    }
+
+    fn ensure_configured(&self) -> Result<()> {
+        if !self.config.configured {
+            bail!(
+                "Lightning plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+        Ok(())
+    }
+
+    /// Sends a request to the node with its credential header and returns the
+    /// JSON body, or `None` when the node has no such invoice.
+    async fn call(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Option<Value>> {
+        let mut req = self
+            .http
+            .request(method, format!("{}{path}", self.rest_url));
+        req = match self.backend {
+            Backend::Lnd => req.header("Grpc-Metadata-macaroon", &self.credential),
+            Backend::Cln => req.header("Rune", &self.credential),
+        };
+        if let Some(body) = body {
+            req = req.json(&body);
+        }
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| anyhow!("lightning: node request failed: {e}"))?;
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        if status == reqwest::StatusCode::NOT_FOUND
+            || (!status.is_success() && text.contains("unable to locate invoice"))
+        {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            bail!("lightning: node returned {status}: {text}");
+        }
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| anyhow!("lightning: node response: {e}"))
+    }
+
+    /// Looks an invoice up by payment hash (hex).
+    async fn lookup(&self, invoice_uid: String, payment_hash: &str) -> Result<PaymentResult> {
+        if payment_hash.len() != 64 || hex::decode(payment_hash).is_err() {
+            bail!("lightning: invalid payment hash");
+        }
+        let found = match self.backend {
+            Backend::Lnd => {
+                self.call(
+                    reqwest::Method::GET,
+                    &format!("/v1/invoice/{payment_hash}"),
+                    None,
+                )
+                .await?
+            }
+            Backend::Cln => self
+                .call(
+                    reqwest::Method::POST,
+                    "/v1/listinvoices",
+                    Some(json!({"payment_hash": payment_hash})),
+                )
+                .await?
+                .and_then(|v| v["invoices"].as_array()?.first().cloned()),
+        };
+        let Some(raw) = found else {
+            return Ok(self.result(
+                invoice_uid,
+                payment_hash,
+                PaymentStatus::Unknown,
+                0,
+                Value::Null,
+            ));
+        };
+        let (status, paid_sats) = match self.backend {
+            Backend::Lnd => lnd_settlement(&raw),
+            Backend::Cln => cln_settlement(&raw),
+        };
+        Ok(self.result(invoice_uid, payment_hash, status, paid_sats, raw))
+    }
+
+    fn result(
+        &self,
+        invoice_id: String,
+        payment_hash: &str,
+        status: PaymentStatus,
+        paid_sats: i64,
+        raw: Value,
+    ) -> PaymentResult {
+        PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id,
+            transaction_id: Some(payment_hash.to_string()),
+            status,
+            paid_amount_cents: paid_sats,
+            currency: LIGHTNING_CURRENCY.into(),
+            raw,
+            refund: None,
+        }
+    }
+}
+
+impl Default for LightningPaymentPlugin {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+#[async_trait::async_trait]
+impl PaymentPlugin for LightningPaymentPlugin {
+    fn provider_key(&self) -> &'static str {
+        "lightning"
+    }
+    fn display_name(&self) -> &'static str {
+        "Lightning"
+    }
+
+    fn capability(&self) -> PaymentPluginCapability {
+        PaymentPluginCapability {
+            provider: self.provider_key().into(),
+            display_name: self.display_name().into(),
+            configured: self.config.configured,
+            environment: self.config.environment.clone(),
+            api_base_url: self.config.api_base_url.clone(),
+            supports_redirect_checkout: false,
+            supports_webhook_confirmation: false,
+            supports_manual_confirmation: true,
+            supports_recurring: false,
+            supports_refunds: false,
+            supports_status_lookup: true,
+            supported_currencies: vec![LIGHTNING_CURRENCY.into()],
+            required_env: self.config.required_env.clone(),
+            missing_env: self.config.missing_env.clone(),
+        }
+    }
+
+    async fn create_invoice(&self, request: CreateInvoiceRequest) -> Result<Invoice> {
+        self.ensure_configured()?;
+        if !request.currency.eq_ignore_ascii_case(LIGHTNING_CURRENCY) {
+            bail!("lightning: invoices must be in {LIGHTNING_CURRENCY} (satoshis)");
+        }
+        let sats = request.amount_cents;
+        if sats <= 0 {
+            bail!("lightning: amount must be positive");
+        }
+        let invoice_uid = request
+            .metadata
+            .get("invoice_uid")
+            .cloned()
+            .ok_or_else(|| anyhow!("lightning: missing invoice_uid"))?;
+        let title = request
+            .metadata
+            .get("video_title")
+            .or_else(|| request.metadata.get("bundle_title"))
+            .or_else(|| request.metadata.get("tip_title"))
+            .cloned()
+            .unwrap_or_else(|| request.video_id.clone());
+        let description = format!("PPV Stream: {title} ({invoice_uid})");
+
+        let (payment_request, payment_hash) = match self.backend {
+            Backend::Lnd => {
+                let resp = self
+                    .call(
+                        reqwest::Method::POST,
+                        "/v1/invoices",
+                        Some(json!({
+                            "value": sats.to_string(),
+                            "memo": description,
+                            "expiry": self.expiry_secs.to_string(),
+                        })),
+                    )
+                    .await?
+                    .unwrap_or(Value::Null);
+                // LND returns the hash base64-encoded; lookups take hex.
+                let hash = resp["r_hash"]
+                    .as_str()
+                    .and_then(|h| base64::engine::general_purpose::STANDARD.decode(h).ok())
+                    .map(hex::encode);
+                (resp["payment_request"].as_str().map(String::from), hash)
+            }
+            Backend::Cln => {
+                let resp = self
+                    .call(
+                        reqwest::Method::POST,
+                        "/v1/invoice",
+                        Some(json!({
+                            "amount_msat": sats * MSAT_PER_SAT,
+                            "label": invoice_uid,
+                            "description": description,
+                            "expiry": self.expiry_secs,
+                        })),
+                    )
+                    .await?
+                    .unwrap_or(Value::Null);
+                (
+                    resp["bolt11"].as_str().map(String::from),
+                    resp["payment_hash"].as_str().map(String::from),
+                )
+            }
+        };
+        let (Some(payment_request), Some(payment_hash)) = (payment_request, payment_hash) else {
+            bail!("lightning: node did not return an invoice");
+        };
+        let expires_at = chrono::Utc::now() + chrono::Duration::seconds(self.expiry_secs);
+
+        Ok(Invoice {
+            provider: self.provider_key().into(),
+            invoice_id: invoice_uid,
+            payment_url: Some(format!("lightning:{payment_request}")),
+            amount_cents: sats,
+            currency: LIGHTNING_CURRENCY.into(),
+            status: PaymentStatus::Pending,
+            raw: json!({
+                "payment_request": payment_request,
+                "payment_hash": payment_hash,
+                "amount_sats": sats,
+                "expires_at": expires_at.to_rfc3339(),
+                "network": self.config.environment,
+            }),
+        })
+    }
+
+    /// Looks up the invoice by the payment hash in `transaction_id`.
+    async fn confirm_payment(&self, request: ConfirmPaymentRequest) -> Result<PaymentResult> {
+        self.ensure_configured()?;
+        let hash = request
+            .transaction_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| anyhow!("lightning: payment hash required"))?
+            .to_ascii_lowercase();
+        self.lookup(request.invoice_id, &hash).await
+    }
+
+    async fn payment_status(&self, request: PaymentStatusRequest) -> Result<PaymentResult> {
+        self.ensure_configured()?;
+        let hash = request
+            .payment_ref
+            .ok_or_else(|| anyhow!("lightning: invoice has no payment hash"))?;
+        self.lookup(request.invoice_id, &hash).await
+    }
+}
+
+/// An amount sent as a number or a string (LND int64s, older CLN "…msat").
+fn amount_field(value: &Value) -> i64 {
+    value
+        .as_i64()
+        .or_else(|| value.as_str()?.trim_end_matches("msat").parse().ok())
+        .unwrap_or(0)
+}
+
+/// Status and paid satoshis of an LND `Invoice`.
+fn lnd_settlement(invoice: &Value) -> (PaymentStatus, i64) {
+    match invoice["state"].as_str() {
+        Some("SETTLED") => (PaymentStatus::Paid, amount_field(&invoice["amt_paid_sat"])),
+        // LND cancels invoices that expire unpaid.
+        Some("CANCELED") => (PaymentStatus::Expired, 0),
+        Some("OPEN") | Some("ACCEPTED") => (PaymentStatus::Pending, 0),
+        _ => (PaymentStatus::Unknown, 0),
+    }
+}
+
+/// Status and paid satoshis of a Core Lightning `listinvoices` entry.
+fn cln_settlement(invoice: &Value) -> (PaymentStatus, i64) {
+    match invoice["status"].as_str() {
+        Some("paid") => (
+            PaymentStatus::Paid,
+            amount_field(&invoice["amount_received_msat"]) / MSAT_PER_SAT,
+        ),
+        Some("expired") => (PaymentStatus::Expired, 0),
+        Some("unpaid") => (PaymentStatus::Pending, 0),
+        _ => (PaymentStatus::Unknown, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settled_invoices_report_the_sats_received() {
+        let lnd = json!({"state": "SETTLED", "amt_paid_sat": "5210", "value": "5210"});
+        assert_eq!(lnd_settlement(&lnd), (PaymentStatus::Paid, 5210));
+        assert_eq!(
+            lnd_settlement(&json!({"state": "CANCELED"})),
+            (PaymentStatus::Expired, 0)
+        );
+
+        let cln = json!({"status": "paid", "amount_received_msat": 5_210_000});
+        assert_eq!(cln_settlement(&cln), (PaymentStatus::Paid, 5210));
+        assert_eq!(
+            cln_settlement(&json!({"status": "unpaid"})),
+            (PaymentStatus::Pending, 0)
+        );
+    }
+}
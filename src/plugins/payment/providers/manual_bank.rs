@@ -0,0 +1,90 @@
+// src/plugins/payment/providers/manual_bank.rs
+//
+// Manual bank transfer — a payout method without a provider API.
+//
+// Flow:
+//   1. The payout engine "sends" the payout: it becomes `processing`
+//   2. Admin downloads GET /admin/payouts/export.csv and makes the transfers
+//      in their banking app
+//   3. Admin marks each payout paid (POST /admin/payouts/:id/mark_paid) with
+//      the bank reference, or failed if the bank rejected it
+//
+// Env vars (optional):
+//   PAYOUT_MANUAL_CURRENCY   currency of the exported amounts, default USD
+//
+// Only payouts use this plugin; it takes no payments.
+
+use anyhow::{bail, Result};
+use serde_json::json;
+
+use crate::plugins::payment::{
+    env::env_or,
+    models::{PayoutRequest, PayoutResult, PayoutStatus},
+    traits::PayoutPlugin,
+};
+
+/// Longest destination accepted; it ends up in a CSV cell.
+const MAX_DESTINATION_LEN: usize = 200;
+
+#[derive(Clone, Debug)]
+pub struct ManualBankPayoutPlugin {
+    currency: String,
+}
+
+impl ManualBankPayoutPlugin {
+    pub fn from_env() -> Self {
+        Self {
+            currency: env_or("PAYOUT_MANUAL_CURRENCY", "USD")
+                .trim()
+                .to_ascii_uppercase(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PayoutPlugin for ManualBankPayoutPlugin {
+    fn payout_method(&self) -> &'static str {
+        "manual_bank"
+    }
+
+    fn payout_label(&self) -> &'static str {
+        "Bank transfer (manual)"
+    }
+
+    fn payout_configured(&self) -> bool {
+        true
+    }
+
+    fn payout_currency(&self) -> String {
+        self.currency.clone()
+    }
+
+    fn validate_destination(&self, destination: &str) -> Result<()> {
+        let destination = destination.trim();
+        if destination.is_empty() {
+            bail!("bank account is required");
+        }
+        if destination.len() > MAX_DESTINATION_LEN || destination.contains(['\n', '\r']) {
+            bail!("bank account must be one line of at most {MAX_DESTINATION_LEN} characters");
+        }
+        Ok(())
+    }
+
+    fn destination_hint(&self) -> &'static str {
+        "Bank, account number, account holder"
+    }
+
+    /// Nothing is sent: the payout waits for an admin to transfer it by hand.
+    async fn send_payout(&self, request: PayoutRequest) -> Result<PayoutResult> {
+        Ok(PayoutResult {
+            provider_ref: None,
+            status: PayoutStatus::Processing,
+            failure_reason: None,
+            raw: json!({
+                "export": true,
+                "amount": request.amount,
+                "currency": request.currency,
+            }),
+        })
+    }
+}
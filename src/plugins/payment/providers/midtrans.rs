@@ -16,6 +16,13 @@
 // Webhook signature format:
 //   SHA512( order_id + status_code + gross_amount + server_key ) == signature_key
 //
+// Refunds: refund() calls the Core API refund endpoint for the order. The
+// notification reports `refund` / `partial_refund` as Refunded and
+// `chargeback` / `partial_chargeback` as Chargeback.
+//
+// Reconciliation: payment_status() calls the Core API status endpoint for the
+// order when its notification never arrived.
+//
 // Auto-disburse: NOT possible — Midtrans has no native disbursement/payout API.
 // All funds stay in the platform's Midtrans account.
 
@@ -23,12 +30,14 @@ use anyhow::{anyhow, bail, Result};
 use base64::{engine::general_purpose::STANDARD as B64, Engine};
 use serde_json::{json, Value};
 use sha2::{Digest, Sha512};
+use uuid::Uuid;
 
 use crate::plugins::payment::{
     env::{env_or, missing_env, required_env},
     models::{
         ConfirmPaymentRequest, CreateInvoiceRequest, Invoice, PaymentPluginCapability,
-        PaymentProviderConfig, PaymentResult, PaymentStatus,
+        PaymentProviderConfig, PaymentResult, PaymentStatus, PaymentStatusRequest, RefundNotice,
+        RefundRequest, RefundResult, RefundStatus,
     },
     traits::PaymentPlugin,
 };
@@ -81,6 +90,28 @@ impl MidtransPaymentPlugin {
         hasher.update(self.server_key.as_bytes());
         hex::encode(hasher.finalize())
     }
+
+    /// IDR amounts arrive as "28500.00"; keep the whole rupiah.
+    fn parse_idr(value: &str) -> i64 {
+        value.split('.').next().unwrap_or("0").parse().unwrap_or(0)
+    }
+
+    /// Maps `transaction_status` / `fraud_status` of a notification or a
+    /// status lookup.
+    fn transaction_status(txn_status: &str, fraud: &str) -> PaymentStatus {
+        match (txn_status, fraud) {
+            ("capture", "accept") | ("capture", "challenge") | ("settlement", _) => {
+                PaymentStatus::Paid
+            }
+            ("deny", _) | ("failure", _) => PaymentStatus::Failed,
+            ("cancel", _) => PaymentStatus::Cancelled,
+            ("expire", _) => PaymentStatus::Expired,
+            ("pending", _) => PaymentStatus::Pending,
+            ("refund", _) | ("partial_refund", _) => PaymentStatus::Refunded,
+            ("chargeback", _) | ("partial_chargeback", _) => PaymentStatus::Chargeback,
+            _ => PaymentStatus::Unknown,
+        }
+    }
 }
 
 impl Default for MidtransPaymentPlugin {
@@ -108,6 +139,9 @@ impl PaymentPlugin for MidtransPaymentPlugin {
             supports_redirect_checkout: true,
             supports_webhook_confirmation: true,
             supports_manual_confirmation: false,
+            supports_recurring: false,
+            supports_refunds: true,
+            supports_status_lookup: true,
             supported_currencies: vec!["IDR".into()],
             required_env: self.config.required_env.clone(),
             missing_env: self.config.missing_env.clone(),
@@ -208,6 +242,21 @@ impl PaymentPlugin for MidtransPaymentPlugin {
         })
     }
 
+    /// Midtrans notifications carry no event id: a transaction status (and,
+    /// for refunds, how many refunds there are) identifies one notification.
+    fn webhook_event_id(
+        &self,
+        payload: &Value,
+        _headers: &std::collections::HashMap<String, String>,
+    ) -> Option<String> {
+        let txn = payload["transaction_id"].as_str()?;
+        let status = payload["transaction_status"].as_str().unwrap_or("");
+        Some(match payload["refunds"].as_array() {
+            Some(refunds) => format!("{txn}:{status}:{}", refunds.len()),
+            None => format!("{txn}:{status}"),
+        })
+    }
+
     async fn confirm_payment(&self, request: ConfirmPaymentRequest) -> Result<PaymentResult> {
         if !self.config.configured {
             bail!(
@@ -233,25 +282,42 @@ impl PaymentPlugin for MidtransPaymentPlugin {
         let txn_status = payload["transaction_status"].as_str().unwrap_or("");
         let fraud = payload["fraud_status"].as_str().unwrap_or("accept");
 
-        let status = match (txn_status, fraud) {
-            ("capture", "accept") | ("capture", "challenge") | ("settlement", _) => {
-                PaymentStatus::Paid
-            }
-            ("deny", _) | ("failure", _) => PaymentStatus::Failed,
-            ("cancel", _) => PaymentStatus::Cancelled,
-            ("expire", _) => PaymentStatus::Expired,
-            ("pending", _) => PaymentStatus::Pending,
-            _ => PaymentStatus::Unknown,
+        let status = Self::transaction_status(txn_status, fraud);
+
+        // Each refund / chargeback is appended to `refunds`; the latest one is
+        // the event being notified.
+        let refund = if matches!(status, PaymentStatus::Refunded | PaymentStatus::Chargeback) {
+            let last = payload["refunds"]
+                .as_array()
+                .and_then(|r| r.last())
+                .cloned()
+                .unwrap_or_default();
+            let refund_id = last["refund_key"]
+                .as_str()
+                .map(String::from)
+                .or_else(|| {
+                    last["refund_chargeback_id"]
+                        .as_i64()
+                        .map(|id| id.to_string())
+                })
+                .unwrap_or_else(|| format!("{order_id}-{txn_status}"));
+            let amount = last["refund_amount"]
+                .as_str()
+                .or_else(|| payload["refund_amount"].as_str())
+                .unwrap_or("0");
+            Some(RefundNotice {
+                refund_id,
+                payment_ref: payload["transaction_id"].as_str().map(String::from),
+                amount_cents: Self::parse_idr(amount),
+                reason: last["reason"].as_str().map(String::from),
+            })
+        } else {
+            None
         };
 
         let transaction_id = payload["transaction_id"].as_str().map(String::from);
         // gross_amount from Midtrans is IDR as "28500.00" — trim the ".00"
-        let paid_amount = gross_amount
-            .split('.')
-            .next()
-            .unwrap_or("0")
-            .parse::<i64>()
-            .unwrap_or(0);
+        let paid_amount = Self::parse_idr(gross_amount);
 
         Ok(PaymentResult {
             provider: self.provider_key().into(),
@@ -261,6 +327,102 @@ impl PaymentPlugin for MidtransPaymentPlugin {
             paid_amount_cents: paid_amount,
             currency: "IDR".into(),
             raw: payload,
+            refund,
+        })
+    }
+
+    /// Refunds part or all of the order; the order id is our invoice uid.
+    async fn refund(&self, request: RefundRequest) -> Result<RefundResult> {
+        if !self.config.configured {
+            bail!(
+                "Midtrans plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+
+        let api_base = self.config.api_base_url.clone().unwrap_or_default();
+        let refund_key = format!("{}-{}", request.invoice_id, Uuid::new_v4().simple());
+        let body = json!({
+            "refund_key": refund_key,
+            "amount": request.amount_cents,
+            "reason": request.reason.clone().unwrap_or_default(),
+        });
+
+        let resp = reqwest::Client::new()
+            .post(format!("{api_base}/v2/{}/refund", request.invoice_id))
+            .header("Authorization", self.basic_auth_header())
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("midtrans: refund request failed: {e}"))?;
+        let result: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("midtrans: refund response parse error: {e}"))?;
+
+        // Midtrans answers HTTP 200 and puts the outcome in `status_code`.
+        let status = match result["status_code"].as_str() {
+            Some("200") => RefundStatus::Succeeded,
+            Some("201") => RefundStatus::Pending,
+            _ => {
+                let msg = result["status_message"].as_str().unwrap_or("unknown");
+                bail!("midtrans: refund rejected: {msg}");
+            }
+        };
+        Ok(RefundResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            refund_id: Some(refund_key),
+            status,
+            amount_cents: request.amount_cents,
+            currency: "IDR".into(),
+            raw: result,
+        })
+    }
+
+    /// Looks up the order by our invoice uid. Orders where the buyer never
+    /// chose a payment method are unknown to the Core API.
+    async fn payment_status(&self, request: PaymentStatusRequest) -> Result<PaymentResult> {
+        if !self.config.configured {
+            bail!(
+                "Midtrans plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+
+        let api_base = self.config.api_base_url.clone().unwrap_or_default();
+        let body: Value = reqwest::Client::new()
+            .get(format!("{api_base}/v2/{}/status", request.invoice_id))
+            .header("Authorization", self.basic_auth_header())
+            .send()
+            .await
+            .map_err(|e| anyhow!("midtrans: status request failed: {e}"))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("midtrans: status response parse error: {e}"))?;
+
+        // As with refunds, the outcome is in `status_code`, not the HTTP status.
+        let status = match body["status_code"].as_str() {
+            Some("404") => PaymentStatus::Unknown,
+            Some(code) if code.starts_with('2') || code == "407" => Self::transaction_status(
+                body["transaction_status"].as_str().unwrap_or(""),
+                body["fraud_status"].as_str().unwrap_or("accept"),
+            ),
+            _ => {
+                let msg = body["status_message"].as_str().unwrap_or("unknown");
+                bail!("midtrans: status lookup rejected: {msg}");
+            }
+        };
+
+        Ok(PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            transaction_id: body["transaction_id"].as_str().map(String::from),
+            status,
+            paid_amount_cents: Self::parse_idr(body["gross_amount"].as_str().unwrap_or("0")),
+            currency: "IDR".into(),
+            raw: body,
+            refund: None,
         })
     }
 }
@@ -0,0 +1,532 @@
+// src/plugins/payment/providers/mock.rs
+//
+// Offline mock provider for development and end-to-end tests.
+//
+// Flow:
+//   1. POST /api/pay/mock/start  → plugin.create_invoice() → returns a local checkout URL
+//   2. Buyer opens GET /api/pay/mock/checkout/:invoice_uid and picks an outcome
+//   3. complete_checkout() sends a signed webhook to POST /api/pay/mock/webhook
+//   4. confirm_payment() verifies the Mock-Signature HMAC-SHA256 like Stripe's
+//
+// Outcomes:
+//   paid        one `payment.succeeded` webhook
+//   failed      one `payment.failed` webhook
+//   underpaid   one `payment.underpaid` webhook for half the amount
+//   delayed     `payment.succeeded` after MOCK_WEBHOOK_DELAY_SECS
+//   duplicate   the same `payment.succeeded` event delivered twice
+//
+// Reconciliation: payment_status() answers from the checkouts completed in
+// this process, so a delayed webhook can also be picked up by the reconciler.
+//
+// Payouts: with method "mock" the destination picks the outcome — one
+// containing "fail" is always rejected, "flaky" is rejected on the first
+// attempt only, "processing" settles on the first status lookup,
+// "unreachable" times out with an unknown outcome, anything else is paid at
+// once.
+//
+// Env vars required:
+//   MOCK_PAYMENT_ENABLED      must be "true"; the provider does not exist otherwise
+//   MOCK_WEBHOOK_SECRET       HMAC key for webhook signatures
+//   MOCK_WEBHOOK_URL          optional, default {BASE_URL}/api/pay/mock/webhook
+//   MOCK_WEBHOOK_DELAY_SECS   optional, default 5
+//
+// Never enable it in production: anyone can complete a mock checkout.
+
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::plugins::payment::{
+    env::{env_or, missing_env, required_env},
+    models::{
+        ConfirmPaymentRequest, CreateInvoiceRequest, Invoice, PaymentPluginCapability,
+        PaymentProviderConfig, PaymentResult, PaymentStatus, PaymentStatusRequest, PayoutRequest,
+        PayoutResult, PayoutStatus,
+    },
+    traits::{PaymentPlugin, PayoutPlugin},
+};
+
+/// Header carrying `t=<unix>,v1=<hex hmac>` over `"<t>.<body>"`.
+pub const SIGNATURE_HEADER: &str = "mock-signature";
+
+/// Outcome of a mock checkout, chosen on the checkout page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MockOutcome {
+    Paid,
+    Failed,
+    Underpaid,
+    Delayed,
+    Duplicate,
+}
+
+impl MockOutcome {
+    pub const ALL: [MockOutcome; 5] = [
+        MockOutcome::Paid,
+        MockOutcome::Failed,
+        MockOutcome::Underpaid,
+        MockOutcome::Delayed,
+        MockOutcome::Duplicate,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|o| o.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MockOutcome::Paid => "paid",
+            MockOutcome::Failed => "failed",
+            MockOutcome::Underpaid => "underpaid",
+            MockOutcome::Delayed => "delayed",
+            MockOutcome::Duplicate => "duplicate",
+        }
+    }
+
+    fn event_type(self) -> &'static str {
+        match self {
+            MockOutcome::Failed => "payment.failed",
+            MockOutcome::Underpaid => "payment.underpaid",
+            _ => "payment.succeeded",
+        }
+    }
+}
+
+/// A checkout being completed: our invoice and what it charges.
+#[derive(Clone, Debug)]
+pub struct MockCheckout {
+    pub invoice_uid: String,
+    pub amount_cents: i64,
+    pub currency: String,
+}
+
+#[derive(Clone, Debug)]
+struct MockCharge {
+    payment_id: String,
+    status: PaymentStatus,
+    paid_amount_cents: i64,
+    currency: String,
+}
+
+/// Checkouts completed in this process, by invoice uid.
+static CHARGES: Lazy<Mutex<HashMap<String, MockCharge>>> = Lazy::new(Default::default);
+
+#[derive(Clone, Debug)]
+pub struct MockPaymentPlugin {
+    config: PaymentProviderConfig,
+    webhook_secret: String,
+    base_url: String,
+    webhook_url: String,
+    delay_secs: u64,
+}
+
+impl MockPaymentPlugin {
+    pub fn from_env() -> Self {
+        let base_url = env_or("BASE_URL", "http://localhost:8080")
+            .trim_end_matches('/')
+            .to_string();
+        let webhook_url = std::env::var("MOCK_WEBHOOK_URL")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| format!("{base_url}/api/pay/mock/webhook"));
+        let delay_secs = env_or("MOCK_WEBHOOK_DELAY_SECS", "5").parse().unwrap_or(5);
+        let required = ["MOCK_PAYMENT_ENABLED", "MOCK_WEBHOOK_SECRET"];
+        Self {
+            webhook_secret: std::env::var("MOCK_WEBHOOK_SECRET").unwrap_or_default(),
+            webhook_url,
+            delay_secs,
+            config: PaymentProviderConfig::new(
+                "mock",
+                "mock",
+                Some(base_url.clone()),
+                required_env(&required),
+                missing_env(&required),
+            ),
+            base_url,
+        }
+    }
+
+    /// The provider exists only when `MOCK_PAYMENT_ENABLED` is explicitly true.
+    pub fn enabled() -> bool {
+        matches!(
+            std::env::var("MOCK_PAYMENT_ENABLED")
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase()
+                .as_str(),
+            "1" | "true" | "yes"
+        )
+    }
+
+    /// `Mock-Signature` header value for `body` sent at `timestamp`.
+    pub fn sign(&self, body: &[u8], timestamp: i64) -> Result<String> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.webhook_secret.as_bytes())
+            .map_err(|_| anyhow!("mock: invalid webhook secret"))?;
+        mac.update(format!("{timestamp}.").as_bytes());
+        mac.update(body);
+        Ok(format!(
+            "t={timestamp},v1={}",
+            hex::encode(mac.finalize().into_bytes())
+        ))
+    }
+
+    fn verify_signature(&self, raw_payload: &[u8], sig_header: &str) -> Result<()> {
+        let ts = sig_header
+            .split(',')
+            .find_map(|s| s.strip_prefix("t="))
+            .and_then(|t| t.parse::<i64>().ok())
+            .ok_or_else(|| anyhow!("mock: missing timestamp in Mock-Signature"))?;
+        if self.sign(raw_payload, ts)? == sig_header {
+            Ok(())
+        } else {
+            bail!("mock: Mock-Signature verification failed")
+        }
+    }
+
+    /// Settles a checkout with `outcome` and delivers its webhook(s) the way
+    /// a real provider would: signed, over HTTP, to the webhook endpoint.
+    pub async fn complete_checkout(
+        &self,
+        checkout: MockCheckout,
+        outcome: MockOutcome,
+    ) -> Result<()> {
+        if !self.config.configured {
+            bail!("Mock plugin not configured: {:?}", self.config.missing_env);
+        }
+
+        let (status, paid_amount) = match outcome {
+            MockOutcome::Failed => (PaymentStatus::Failed, 0),
+            MockOutcome::Underpaid => (PaymentStatus::Underpaid, checkout.amount_cents / 2),
+            _ => (PaymentStatus::Paid, checkout.amount_cents),
+        };
+        let payment_id = format!("mockpay_{}", Uuid::new_v4().simple());
+        CHARGES.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            checkout.invoice_uid.clone(),
+            MockCharge {
+                payment_id: payment_id.clone(),
+                status,
+                paid_amount_cents: paid_amount,
+                currency: checkout.currency.clone(),
+            },
+        );
+
+        let event = json!({
+            "id": format!("evt_mock_{}", Uuid::new_v4().simple()),
+            "type": outcome.event_type(),
+            "created": chrono::Utc::now().timestamp(),
+            "data": {
+                "invoice_uid": checkout.invoice_uid,
+                "payment_id": payment_id,
+                "amount": checkout.amount_cents,
+                "paid_amount": paid_amount,
+                "currency": checkout.currency,
+            }
+        });
+        let body = serde_json::to_vec(&event)?;
+
+        match outcome {
+            MockOutcome::Delayed => {
+                let plugin = self.clone();
+                let delay = std::time::Duration::from_secs(self.delay_secs);
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    if let Err(e) = plugin.deliver(&body).await {
+                        tracing::warn!("mock: delayed webhook failed: {e}");
+                    }
+                });
+            }
+            MockOutcome::Duplicate => {
+                self.deliver(&body).await?;
+                self.deliver(&body).await?;
+            }
+            _ => self.deliver(&body).await?,
+        }
+        Ok(())
+    }
+
+    async fn deliver(&self, body: &[u8]) -> Result<()> {
+        let signature = self.sign(body, chrono::Utc::now().timestamp())?;
+        let resp = reqwest::Client::new()
+            .post(&self.webhook_url)
+            .header("content-type", "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|e| anyhow!("mock: webhook delivery failed: {e}"))?;
+        if !resp.status().is_success() {
+            bail!("mock: webhook endpoint answered {}", resp.status());
+        }
+        Ok(())
+    }
+}
+
+impl Default for MockPaymentPlugin {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+#[async_trait::async_trait]
+impl PaymentPlugin for MockPaymentPlugin {
+    fn provider_key(&self) -> &'static str {
+        "mock"
+    }
+    fn display_name(&self) -> &'static str {
+        "Mock (testing)"
+    }
+
+    fn capability(&self) -> PaymentPluginCapability {
+        PaymentPluginCapability {
+            provider: self.provider_key().into(),
+            display_name: self.display_name().into(),
+            configured: self.config.configured,
+            environment: self.config.environment.clone(),
+            api_base_url: self.config.api_base_url.clone(),
+            supports_redirect_checkout: true,
+            supports_webhook_confirmation: true,
+            supports_manual_confirmation: false,
+            supports_recurring: false,
+            supports_refunds: false,
+            supports_status_lookup: true,
+            supported_currencies: vec![],
+            required_env: self.config.required_env.clone(),
+            missing_env: self.config.missing_env.clone(),
+        }
+    }
+
+    async fn create_invoice(&self, request: CreateInvoiceRequest) -> Result<Invoice> {
+        if !self.config.configured {
+            bail!("Mock plugin not configured: {:?}", self.config.missing_env);
+        }
+
+        let invoice_uid = request
+            .metadata
+            .get("invoice_uid")
+            .cloned()
+            .unwrap_or_default();
+        let mut params: Vec<(&str, &str)> = Vec::new();
+        if let Some(url) = request.success_url.as_deref() {
+            params.push(("success_url", url));
+        }
+        if let Some(url) = request.cancel_url.as_deref() {
+            params.push(("cancel_url", url));
+        }
+        let payment_url = reqwest::Url::parse_with_params(
+            &format!("{}/api/pay/mock/checkout/{invoice_uid}", self.base_url),
+            &params,
+        )
+        .map_err(|e| anyhow!("mock: invalid checkout URL: {e}"))?;
+
+        Ok(Invoice {
+            provider: self.provider_key().into(),
+            invoice_id: invoice_uid.clone(),
+            payment_url: Some(payment_url.to_string()),
+            amount_cents: request.amount_cents,
+            currency: request.currency,
+            status: PaymentStatus::Pending,
+            raw: json!({ "session_id": format!("mock_{invoice_uid}") }),
+        })
+    }
+
+    fn webhook_event_id(
+        &self,
+        payload: &Value,
+        _headers: &HashMap<String, String>,
+    ) -> Option<String> {
+        payload["id"].as_str().map(String::from)
+    }
+
+    async fn confirm_payment(&self, request: ConfirmPaymentRequest) -> Result<PaymentResult> {
+        if !self.config.configured {
+            bail!("Mock plugin not configured: {:?}", self.config.missing_env);
+        }
+
+        let payload = request
+            .webhook_payload
+            .ok_or_else(|| anyhow!("mock: no webhook payload"))?;
+        let raw: Vec<u8> = payload
+            .get("__raw__")
+            .and_then(|v| v.as_str())
+            .map(|b64| B64.decode(b64).unwrap_or_default())
+            .unwrap_or_else(|| serde_json::to_vec(&payload).unwrap_or_default());
+        let sig = request
+            .signature_headers
+            .get(SIGNATURE_HEADER)
+            .ok_or_else(|| anyhow!("mock: missing Mock-Signature header"))?;
+        self.verify_signature(&raw, sig)?;
+
+        let event: Value = serde_json::from_slice(&raw)?;
+        let data = &event["data"];
+        let status = match event["type"].as_str() {
+            Some("payment.succeeded") => PaymentStatus::Paid,
+            Some("payment.failed") => PaymentStatus::Failed,
+            Some("payment.underpaid") => PaymentStatus::Underpaid,
+            _ => PaymentStatus::Unknown,
+        };
+
+        Ok(PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id: data["invoice_uid"].as_str().unwrap_or_default().into(),
+            transaction_id: data["payment_id"].as_str().map(String::from),
+            status,
+            paid_amount_cents: data["paid_amount"].as_i64().unwrap_or(0),
+            currency: data["currency"].as_str().unwrap_or("USD").to_uppercase(),
+            raw: event,
+            refund: None,
+        })
+    }
+
+    /// Checkouts not completed in this process are unknown.
+    async fn payment_status(&self, request: PaymentStatusRequest) -> Result<PaymentResult> {
+        let charge = CHARGES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&request.invoice_id)
+            .cloned();
+        let (transaction_id, status, paid_amount, currency) = match charge {
+            Some(c) => (
+                Some(c.payment_id),
+                c.status,
+                c.paid_amount_cents,
+                c.currency,
+            ),
+            None => (None, PaymentStatus::Unknown, 0, request.currency),
+        };
+        Ok(PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            transaction_id,
+            status,
+            paid_amount_cents: paid_amount,
+            currency,
+            raw: json!({}),
+            refund: None,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl PayoutPlugin for MockPaymentPlugin {
+    fn payout_method(&self) -> &'static str {
+        "mock"
+    }
+
+    fn payout_label(&self) -> &'static str {
+        "Mock payout (testing)"
+    }
+
+    fn payout_configured(&self) -> bool {
+        true
+    }
+
+    fn payout_currency(&self) -> String {
+        "USD".into()
+    }
+
+    fn validate_destination(&self, destination: &str) -> Result<()> {
+        if destination.trim().is_empty() {
+            bail!("destination is required");
+        }
+        Ok(())
+    }
+
+    fn destination_hint(&self) -> &'static str {
+        "paid | processing | flaky | fail | unreachable"
+    }
+
+    async fn send_payout(&self, request: PayoutRequest) -> Result<PayoutResult> {
+        let destination = request.destination.to_ascii_lowercase();
+        if destination.contains("unreachable") {
+            bail!("mock: payout request timed out");
+        }
+        if destination.contains("fail") || (destination.contains("flaky") && request.attempt <= 1) {
+            return Ok(PayoutResult {
+                provider_ref: None,
+                status: PayoutStatus::Failed,
+                failure_reason: Some(format!(
+                    "mock: payout rejected (attempt {})",
+                    request.attempt
+                )),
+                raw: json!({ "idempotency_key": request.idempotency_key }),
+            });
+        }
+        let status = if destination.contains("processing") {
+            PayoutStatus::Processing
+        } else {
+            PayoutStatus::Paid
+        };
+        Ok(PayoutResult {
+            provider_ref: Some(format!("mockpayout_{}", request.payout_id)),
+            status,
+            failure_reason: None,
+            raw: json!({ "idempotency_key": request.idempotency_key }),
+        })
+    }
+
+    async fn payout_status(&self, provider_ref: &str) -> Result<PayoutResult> {
+        Ok(PayoutResult {
+            provider_ref: Some(provider_ref.to_string()),
+            status: PayoutStatus::Paid,
+            failure_reason: None,
+            raw: json!({}),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin() -> MockPaymentPlugin {
+        let mut plugin = MockPaymentPlugin::from_env();
+        plugin.webhook_secret = "test-secret".into();
+        plugin.config.configured = true;
+        plugin
+    }
+
+    #[tokio::test]
+    async fn signed_webhooks_confirm_and_tampered_ones_do_not() {
+        let plugin = plugin();
+        let body = br#"{"id":"evt_mock_1","type":"payment.underpaid","data":{"invoice_uid":"inv_1","payment_id":"mockpay_1","paid_amount":99,"currency":"usd"}}"#;
+        let request = |body: &[u8], sig: String| ConfirmPaymentRequest {
+            provider: "mock".into(),
+            invoice_id: String::new(),
+            transaction_id: None,
+            webhook_payload: Some(json!({ "__raw__": B64.encode(body) })),
+            signature_headers: HashMap::from([(SIGNATURE_HEADER.to_string(), sig)]),
+        };
+
+        let sig = plugin.sign(body, 1_700_000_000).unwrap();
+        let result = plugin
+            .confirm_payment(request(body, sig.clone()))
+            .await
+            .unwrap();
+        assert_eq!(result.status, PaymentStatus::Underpaid);
+        assert_eq!(result.invoice_id, "inv_1");
+        assert_eq!(result.paid_amount_cents, 99);
+        assert_eq!(result.currency, "USD");
+
+        let tampered = String::from_utf8_lossy(body).replace("99", "199");
+        assert!(plugin
+            .confirm_payment(request(tampered.as_bytes(), sig))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn outcomes_parse_from_the_checkout_form() {
+        for outcome in MockOutcome::ALL {
+            assert_eq!(MockOutcome::parse(outcome.as_str()), Some(outcome));
+        }
+        assert_eq!(MockOutcome::parse(" Paid "), Some(MockOutcome::Paid));
+        assert_eq!(MockOutcome::parse("refunded"), None);
+    }
+}
@@ -1,5 +1,9 @@
+pub mod lightning;
+pub mod manual_bank;
 pub mod midtrans;
+pub mod mock;
 pub mod paypal;
+pub mod solana;
 pub mod stripe;
 pub mod x402;
 pub mod xendit;
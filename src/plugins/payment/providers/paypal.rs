@@ -8,6 +8,13 @@
 //   3. PayPal calls POST /api/pay/paypal/webhook (CHECKOUT.ORDER.APPROVED or PAYMENT.CAPTURE.COMPLETED)
 //   4. confirm_payment() verifies via PayPal's verify-webhook-signature REST API
 //
+// Refunds: refund() resolves the capture of the order and refunds it. The
+// webhook reports PAYMENT.CAPTURE.REFUNDED as Refunded and
+// CUSTOMER.DISPUTE.CREATED as Chargeback.
+//
+// Reconciliation: payment_status() reads the order saved as `provider_ref`
+// when its webhook never arrived.
+//
 // Env vars required:
 //   PAYPAL_CLIENT_ID       App Client ID (sandbox or live)
 //   PAYPAL_CLIENT_SECRET   App Client Secret
@@ -16,7 +23,9 @@
 //
 // Auto-disburse: NOT implemented.
 // All funds land in the platform's PayPal account.
-// Creator payouts can be added later using PayPal Payouts API if creators supply their PayPal email.
+// Scheduled creator payouts (method "paypal") go through the Payouts API to the
+// creator's PayPal email: one batch per attempt (sender_batch_id = idempotency
+// key), polled until the item settles. They need the client id and secret only.
 
 use anyhow::{anyhow, bail, Result};
 use serde_json::{json, Value};
@@ -25,9 +34,10 @@ use crate::plugins::payment::{
     env::{env_or, missing_env, required_env},
     models::{
         ConfirmPaymentRequest, CreateInvoiceRequest, Invoice, PaymentPluginCapability,
-        PaymentProviderConfig, PaymentResult, PaymentStatus,
+        PaymentProviderConfig, PaymentResult, PaymentStatus, PaymentStatusRequest, PayoutRequest,
+        PayoutResult, PayoutStatus, RefundNotice, RefundRequest, RefundResult, RefundStatus,
     },
-    traits::PaymentPlugin,
+    traits::{PaymentPlugin, PayoutPlugin},
 };
 
 #[derive(Clone, Debug)]
@@ -99,6 +109,39 @@ impl PaypalPaymentPlugin {
             _ => format!("{:.2}", amount_cents as f64 / 100.0),
         }
     }
+
+    /// Inverse of `format_amount`: PayPal decimal string to minor units.
+    fn parse_amount(value: &str, currency: &str) -> i64 {
+        let major = value.parse::<f64>().unwrap_or(0.0);
+        match currency.to_uppercase().as_str() {
+            "IDR" | "JPY" | "HUF" | "TWD" => major.round() as i64,
+            _ => (major * 100.0).round() as i64,
+        }
+    }
+
+    /// Capture id of an order; `payment_ref` may already be a capture id.
+    async fn capture_id(&self, token: &str, payment_ref: &str) -> Result<String> {
+        let resp = reqwest::Client::new()
+            .get(format!(
+                "{}/v2/checkout/orders/{payment_ref}",
+                self.api_base
+            ))
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(|e| anyhow!("paypal: order lookup failed: {e}"))?;
+        if !resp.status().is_success() {
+            return Ok(payment_ref.to_string());
+        }
+        let order: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("paypal: order parse error: {e}"))?;
+        order["purchase_units"][0]["payments"]["captures"][0]["id"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow!("paypal: order {payment_ref} has no capture"))
+    }
 }
 
 impl Default for PaypalPaymentPlugin {
@@ -126,6 +169,9 @@ impl PaymentPlugin for PaypalPaymentPlugin {
             supports_redirect_checkout: true,
             supports_webhook_confirmation: true,
             supports_manual_confirmation: false,
+            supports_recurring: false,
+            supports_refunds: true,
+            supports_status_lookup: true,
             supported_currencies: vec!["USD".into(), "EUR".into(), "IDR".into()],
             required_env: self.config.required_env.clone(),
             missing_env: self.config.missing_env.clone(),
@@ -215,6 +261,15 @@ impl PaymentPlugin for PaypalPaymentPlugin {
         })
     }
 
+    /// PayPal webhook ids (`WH-...`) are stable across redeliveries.
+    fn webhook_event_id(
+        &self,
+        payload: &Value,
+        _headers: &std::collections::HashMap<String, String>,
+    ) -> Option<String> {
+        payload["id"].as_str().map(String::from)
+    }
+
     /// Verifies the webhook via PayPal's `verify-webhook-signature` REST API.
     ///
     /// Required headers (lowercase):
@@ -274,17 +329,62 @@ impl PaymentPlugin for PaypalPaymentPlugin {
             "CHECKOUT.ORDER.APPROVED" | "PAYMENT.CAPTURE.COMPLETED" => PaymentStatus::Paid,
             "PAYMENT.CAPTURE.DENIED" | "PAYMENT.CAPTURE.DECLINED" => PaymentStatus::Failed,
             "CHECKOUT.ORDER.CANCELLED" => PaymentStatus::Cancelled,
+            "PAYMENT.CAPTURE.REFUNDED" => PaymentStatus::Refunded,
+            "CUSTOMER.DISPUTE.CREATED" => PaymentStatus::Chargeback,
             _ => PaymentStatus::Unknown,
         };
 
         // custom_id = our invoice_uid (set when creating the order)
+        let disputed = &resource["disputed_transactions"][0];
         let invoice_uid = resource["purchase_units"]
             .as_array()
             .and_then(|us| us.first())
             .and_then(|u| u["custom_id"].as_str())
-            .unwrap_or_else(|| resource["custom_id"].as_str().unwrap_or(""))
+            .or_else(|| resource["custom_id"].as_str())
+            .or_else(|| disputed["custom"].as_str())
+            .unwrap_or("")
             .to_string();
 
+        let refund = match status {
+            PaymentStatus::Refunded => {
+                let currency = resource["amount"]["currency_code"]
+                    .as_str()
+                    .unwrap_or("USD");
+                // The refund links "up" to the capture it returns.
+                let capture = resource["links"]
+                    .as_array()
+                    .and_then(|links| links.iter().find(|l| l["rel"].as_str() == Some("up")))
+                    .and_then(|l| l["href"].as_str())
+                    .and_then(|href| href.rsplit('/').next())
+                    .map(String::from);
+                Some(RefundNotice {
+                    refund_id: resource["id"].as_str().unwrap_or_default().to_string(),
+                    payment_ref: capture,
+                    amount_cents: Self::parse_amount(
+                        resource["amount"]["value"].as_str().unwrap_or("0"),
+                        currency,
+                    ),
+                    reason: resource["note_to_payer"].as_str().map(String::from),
+                })
+            }
+            PaymentStatus::Chargeback => {
+                let amount = &resource["dispute_amount"];
+                Some(RefundNotice {
+                    refund_id: resource["dispute_id"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    payment_ref: disputed["seller_transaction_id"].as_str().map(String::from),
+                    amount_cents: Self::parse_amount(
+                        amount["value"].as_str().unwrap_or("0"),
+                        amount["currency_code"].as_str().unwrap_or("USD"),
+                    ),
+                    reason: resource["reason"].as_str().map(String::from),
+                })
+            }
+            _ => None,
+        };
+
         let transaction_id = resource["id"].as_str().map(String::from);
         let paid_amount = resource["amount"]["value"]
             .as_str()
@@ -304,6 +404,281 @@ impl PaymentPlugin for PaypalPaymentPlugin {
             paid_amount_cents: paid_amount,
             currency,
             raw: payload,
+            refund,
+        })
+    }
+
+    async fn refund(&self, request: RefundRequest) -> Result<RefundResult> {
+        if !self.config.configured {
+            bail!(
+                "PayPal plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+
+        let token = self.access_token().await?;
+        let capture_id = self.capture_id(&token, &request.payment_ref).await?;
+        let mut body = json!({
+            "amount": {
+                "currency_code": request.currency.to_uppercase(),
+                "value": Self::format_amount(request.amount_cents, &request.currency),
+            },
+            "invoice_id": request.invoice_id,
+        });
+        if let Some(reason) = request.reason.as_deref().filter(|r| !r.is_empty()) {
+            body["note_to_payer"] = json!(reason);
+        }
+
+        let resp = reqwest::Client::new()
+            .post(format!(
+                "{}/v2/payments/captures/{capture_id}/refund",
+                self.api_base
+            ))
+            .bearer_auth(&token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("paypal: refund request failed: {e}"))?;
+        let http_status = resp.status();
+        let result: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("paypal: refund response parse error: {e}"))?;
+        if !http_status.is_success() {
+            bail!("paypal: API {http_status}: {result}");
+        }
+
+        let status = match result["status"].as_str() {
+            Some("COMPLETED") => RefundStatus::Succeeded,
+            Some("CANCELLED") | Some("FAILED") => RefundStatus::Failed,
+            _ => RefundStatus::Pending,
+        };
+        Ok(RefundResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            refund_id: result["id"].as_str().map(String::from),
+            status,
+            amount_cents: request.amount_cents,
+            currency: request.currency,
+            raw: result,
+        })
+    }
+
+    /// Reads the order saved as `payment_ref`. An approved order counts as
+    /// paid, as it does for the `CHECKOUT.ORDER.APPROVED` webhook.
+    async fn payment_status(&self, request: PaymentStatusRequest) -> Result<PaymentResult> {
+        if !self.config.configured {
+            bail!(
+                "PayPal plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+        let order_id = request
+            .payment_ref
+            .as_deref()
+            .filter(|r| !r.is_empty() && *r != request.invoice_id)
+            .ok_or_else(|| anyhow!("paypal: invoice has no order id"))?;
+
+        let token = self.access_token().await?;
+        let resp = reqwest::Client::new()
+            .get(format!("{}/v2/checkout/orders/{order_id}", self.api_base))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .map_err(|e| anyhow!("paypal: order lookup failed: {e}"))?;
+        let http_status = resp.status();
+        let order: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("paypal: order parse error: {e}"))?;
+
+        // Orders that were never approved disappear once they expire.
+        let status = if http_status == reqwest::StatusCode::NOT_FOUND {
+            PaymentStatus::Unknown
+        } else if !http_status.is_success() {
+            bail!("paypal: API {http_status}: {order}");
+        } else {
+            match order["status"].as_str() {
+                Some("COMPLETED") | Some("APPROVED") => PaymentStatus::Paid,
+                Some("VOIDED") => PaymentStatus::Cancelled,
+                _ => PaymentStatus::Pending,
+            }
+        };
+
+        let unit = &order["purchase_units"][0];
+        let currency = unit["amount"]["currency_code"]
+            .as_str()
+            .map(str::to_uppercase)
+            .unwrap_or(request.currency);
+        let transaction_id = unit["payments"]["captures"][0]["id"]
+            .as_str()
+            .unwrap_or(order_id)
+            .to_string();
+
+        Ok(PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            transaction_id: Some(transaction_id),
+            status,
+            paid_amount_cents: Self::parse_amount(
+                unit["amount"]["value"].as_str().unwrap_or("0"),
+                &currency,
+            ),
+            currency,
+            raw: order,
+            refund: None,
+        })
+    }
+}
+
+impl PaypalPaymentPlugin {
+    /// Status of the single item of a payout batch.
+    /// Whether a payouts API error reports an already used sender_batch_id.
+    fn duplicate_batch(error: &Value) -> bool {
+        error["details"].as_array().is_some_and(|details| {
+            details.iter().any(|d| {
+                d["issue"]
+                    .as_str()
+                    .is_some_and(|issue| issue.contains("DUPLICATE") || issue.contains("ALREADY"))
+            })
         })
     }
+
+    fn payout_item_result(batch: Value) -> PayoutResult {
+        let item = &batch["items"][0];
+        let item_status = item["transaction_status"].as_str();
+        let status = match item_status.or(batch["batch_header"]["batch_status"].as_str()) {
+            Some("SUCCESS") => PayoutStatus::Paid,
+            Some("FAILED" | "RETURNED" | "BLOCKED" | "REFUNDED" | "REVERSED" | "DENIED") => {
+                PayoutStatus::Failed
+            }
+            // PENDING, PROCESSING, ONHOLD, and UNCLAIMED (no PayPal account yet).
+            _ => PayoutStatus::Processing,
+        };
+        PayoutResult {
+            provider_ref: batch["batch_header"]["payout_batch_id"]
+                .as_str()
+                .map(String::from),
+            failure_reason: item["errors"]["name"]
+                .as_str()
+                .or(item_status.filter(|_| status == PayoutStatus::Failed))
+                .map(String::from),
+            status,
+            raw: batch,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PayoutPlugin for PaypalPaymentPlugin {
+    fn payout_method(&self) -> &'static str {
+        "paypal"
+    }
+
+    fn payout_label(&self) -> &'static str {
+        "PayPal"
+    }
+
+    fn payout_configured(&self) -> bool {
+        !self.client_id.is_empty() && !self.client_secret.is_empty()
+    }
+
+    fn payout_currency(&self) -> String {
+        "USD".into()
+    }
+
+    fn validate_destination(&self, destination: &str) -> Result<()> {
+        let email = destination.trim();
+        match email.split_once('@') {
+            Some((user, domain)) if !user.is_empty() && domain.contains('.') => Ok(()),
+            _ => bail!("PayPal payouts need the email address of a PayPal account"),
+        }
+    }
+
+    fn destination_hint(&self) -> &'static str {
+        "you@example.com"
+    }
+
+    async fn send_payout(&self, request: PayoutRequest) -> Result<PayoutResult> {
+        // Nothing was sent without a token, so the payout can go again.
+        let token = match self.access_token().await {
+            Ok(token) => token,
+            Err(e) => {
+                return Ok(PayoutResult {
+                    provider_ref: None,
+                    status: PayoutStatus::Failed,
+                    failure_reason: Some(e.to_string()),
+                    raw: Value::Null,
+                })
+            }
+        };
+        let body = json!({
+            "sender_batch_header": {
+                "sender_batch_id": request.idempotency_key,
+                "email_subject": "You have a payout",
+            },
+            "items": [{
+                "recipient_type": "EMAIL",
+                "receiver": request.destination.trim(),
+                "amount": {
+                    "value": Self::format_amount(request.amount, &request.currency),
+                    "currency": request.currency,
+                },
+                "note": request.description,
+                "sender_item_id": request.payout_id,
+            }]
+        });
+        let resp = reqwest::Client::new()
+            .post(format!("{}/v1/payments/payouts", self.api_base))
+            .bearer_auth(&token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("paypal: payout request failed: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("paypal: payout parse error: {e}"))?;
+        // A 4xx is a rejection, except a reused sender_batch_id: that batch
+        // was accepted before, so the outcome of this payout is unknown.
+        if http_status.is_client_error() && !Self::duplicate_batch(&body) {
+            let msg = body["message"].as_str().unwrap_or("unknown");
+            return Ok(PayoutResult {
+                provider_ref: None,
+                status: PayoutStatus::Failed,
+                failure_reason: Some(format!("paypal: payouts API {http_status}: {msg}")),
+                raw: body,
+            });
+        }
+        if !http_status.is_success() {
+            let msg = body["message"].as_str().unwrap_or("unknown");
+            bail!("paypal: payouts API {http_status}: {msg}");
+        }
+        // The create call only returns the batch header; items follow on lookup.
+        Ok(Self::payout_item_result(body))
+    }
+
+    async fn payout_status(&self, provider_ref: &str) -> Result<PayoutResult> {
+        let token = self.access_token().await?;
+        let resp = reqwest::Client::new()
+            .get(format!(
+                "{}/v1/payments/payouts/{provider_ref}",
+                self.api_base
+            ))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .map_err(|e| anyhow!("paypal: payout lookup failed: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("paypal: payout parse error: {e}"))?;
+        if !http_status.is_success() {
+            let msg = body["message"].as_str().unwrap_or("unknown");
+            bail!("paypal: payouts API {http_status}: {msg}");
+        }
+        Ok(Self::payout_item_result(body))
+    }
 }
@@ -0,0 +1,496 @@
+// src/plugins/payment/providers/solana.rs
+//
+// Solana USDC — SPL token transfers matched to invoices by memo.
+//
+// Flow:
+//   1. POST /api/pay/solana/start  → plugin.create_invoice() → returns a Solana
+//      Pay transfer request (`solana:<recipient>?amount=…&spl-token=<mint>&memo=<invoice_uid>`)
+//   2. Buyer's wallet sends the USDC with the invoice uid as its memo
+//   3. POST /api/pay/solana/confirm → confirm_payment() finds the transaction
+//      (by signature when given, else by memo) and verifies it
+//
+// Verification (verify_transfer): the transaction succeeded, carries a memo
+// instruction equal to the invoice uid, and raised the recipient's balance of
+// the USDC mint. The paid amount is that balance change, so transfer,
+// transferChecked and wallet-specific instruction layouts all count the same.
+//
+// Lookup: getSignaturesForAddress on the recipient's USDC token account,
+// keeping signatures whose memo names the invoice. Only the latest
+// SIGNATURE_SCAN_LIMIT transactions of that account are searched.
+//
+// Reconciliation: payment_status() runs the same memo lookup, so payments
+// whose confirm call never came are granted by the reconciler.
+//
+// Env vars required:
+//   SOLANA_RECIPIENT        merchant wallet (base58), owner of the token account
+//   SOLANA_TOKEN_ACCOUNT    merchant's USDC token account (base58)
+//   SOLANA_ENV              "devnet" (default) | "mainnet-beta"
+//   SOLANA_RPC_URL          optional, default the public endpoint of SOLANA_ENV
+//   SOLANA_USDC_MINT        optional, default Circle's USDC mint on SOLANA_ENV
+//   SOLANA_COMMITMENT       "finalized" (default) | "confirmed"
+//
+// Auto-disburse: NOT implemented.
+// All funds land in the platform's token account.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+
+use crate::plugins::payment::{
+    env::{env_or, missing_env, required_env},
+    models::{
+        ConfirmPaymentRequest, CreateInvoiceRequest, Invoice, PaymentPluginCapability,
+        PaymentProviderConfig, PaymentResult, PaymentStatus, PaymentStatusRequest,
+    },
+    traits::PaymentPlugin,
+};
+
+/// SPL Memo program, v2 and v1.
+const MEMO_PROGRAMS: [&str; 2] = [
+    "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+    "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo",
+];
+const USDC_DECIMALS: u32 = 6;
+/// Base units of USDC per US cent.
+const UNITS_PER_CENT: u128 = 10u128.pow(USDC_DECIMALS - 2);
+/// Most recent token account signatures searched for an invoice memo.
+const SIGNATURE_SCAN_LIMIT: u64 = 1000;
+
+#[derive(Clone, Debug)]
+pub struct SolanaPaymentPlugin {
+    config: PaymentProviderConfig,
+    rpc_url: String,
+    recipient: String,
+    token_account: String,
+    mint: String,
+    commitment: String,
+}
+
+impl SolanaPaymentPlugin {
+    pub fn from_env() -> Self {
+        let environment = env_or("SOLANA_ENV", "devnet");
+        let mainnet = environment == "mainnet-beta";
+        let var = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let rpc_url = var("SOLANA_RPC_URL").unwrap_or_else(|| {
+            if mainnet {
+                "https://api.mainnet-beta.solana.com".into()
+            } else {
+                "https://api.devnet.solana.com".into()
+            }
+        });
+        let mint = var("SOLANA_USDC_MINT").unwrap_or_else(|| {
+            if mainnet {
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".into()
+            } else {
+                "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU".into()
+            }
+        });
+        let commitment = match var("SOLANA_COMMITMENT").as_deref() {
+            Some("confirmed") => "confirmed",
+            _ => "finalized",
+        };
+        let recipient = var("SOLANA_RECIPIENT").unwrap_or_default();
+        let token_account = var("SOLANA_TOKEN_ACCOUNT").unwrap_or_default();
+
+        let required = ["SOLANA_RECIPIENT", "SOLANA_TOKEN_ACCOUNT"];
+        let mut missing = missing_env(&required);
+        for (key, value) in [
+            ("SOLANA_RECIPIENT", &recipient),
+            ("SOLANA_TOKEN_ACCOUNT", &token_account),
+            ("SOLANA_USDC_MINT", &mint),
+        ] {
+            if !value.is_empty() && !is_base58_key(value) {
+                missing.push(key.to_string());
+            }
+        }
+        Self {
+            config: PaymentProviderConfig::new(
+                "solana",
+                environment,
+                Some(rpc_url.clone()),
+                required_env(&required),
+                missing,
+            ),
+            rpc_url,
+            recipient,
+            token_account,
+            mint,
+            commitment: commitment.to_string(),
+        }
+    }
+
+    fn ensure_configured(&self) -> Result<()> {
+        if !self.config.configured {
+            bail!(
+                "Solana plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+        Ok(())
+    }
+
+    async fn rpc(&self, method: &str, params: Value) -> Result<Value> {
+        let body: Value = reqwest::Client::new()
+            .post(&self.rpc_url)
+            .json(&json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}))
+            .send()
+            .await
+            .map_err(|e| anyhow!("solana: RPC error: {e}"))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("solana: RPC response parse error: {e}"))?;
+        if let Some(error) = body.get("error") {
+            let msg = error["message"].as_str().unwrap_or("unknown");
+            bail!("solana: {method}: {msg}");
+        }
+        Ok(body["result"].clone())
+    }
+
+    async fn transaction(&self, signature: &str) -> Result<Value> {
+        self.rpc(
+            "getTransaction",
+            json!([signature, {
+                "encoding": "jsonParsed",
+                "commitment": self.commitment,
+                "maxSupportedTransactionVersion": 0,
+            }]),
+        )
+        .await
+    }
+
+    fn result(&self, invoice_id: String, signature: Option<String>, units: u128) -> PaymentResult {
+        let status = if signature.is_some() {
+            PaymentStatus::Paid
+        } else {
+            PaymentStatus::Pending
+        };
+        PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id: invoice_id.clone(),
+            transaction_id: signature.clone(),
+            status,
+            paid_amount_cents: i64::try_from(units / UNITS_PER_CENT).unwrap_or(i64::MAX),
+            currency: "USD".into(),
+            raw: json!({
+                "signature": signature,
+                "memo": invoice_id,
+                "amount_units": units.to_string(),
+                "mint": self.mint,
+                "recipient": self.recipient,
+            }),
+            refund: None,
+        }
+    }
+
+    /// Checks one transaction named by the buyer. One the node does not have
+    /// at the configured commitment yet is still pending.
+    async fn check_signature(&self, invoice_id: String, signature: &str) -> Result<PaymentResult> {
+        let tx = self.transaction(signature).await?;
+        if tx.is_null() {
+            return Ok(self.result(invoice_id, None, 0));
+        }
+        let units = verify_transfer(&tx, &invoice_id, &self.recipient, &self.mint)
+            .map_err(|e| anyhow!("solana: {e}"))?;
+        Ok(self.result(invoice_id, Some(signature.to_string()), units))
+    }
+
+    /// Searches the token account's recent transactions for this invoice's
+    /// memo and returns the largest verified payment, if any.
+    async fn find_payment(&self, invoice_id: String) -> Result<PaymentResult> {
+        let signatures = self
+            .rpc(
+                "getSignaturesForAddress",
+                json!([self.token_account, {
+                    "limit": SIGNATURE_SCAN_LIMIT,
+                    "commitment": self.commitment,
+                }]),
+            )
+            .await?;
+        let candidates = signatures
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|s| s["err"].is_null())
+            .filter(|s| s["memo"].as_str().is_some_and(|m| m.contains(&invoice_id)))
+            .filter_map(|s| s["signature"].as_str().map(String::from))
+            .collect::<Vec<_>>();
+
+        let mut best: Option<(String, u128)> = None;
+        for signature in candidates {
+            let tx = self.transaction(&signature).await?;
+            match verify_transfer(&tx, &invoice_id, &self.recipient, &self.mint) {
+                Ok(units) if best.as_ref().is_none_or(|(_, b)| units > *b) => {
+                    best = Some((signature, units))
+                }
+                Ok(_) => {}
+                Err(e) => tracing::debug!("solana: {signature} skipped for {invoice_id}: {e}"),
+            }
+        }
+        Ok(match best {
+            Some((signature, units)) => self.result(invoice_id, Some(signature), units),
+            None => self.result(invoice_id, None, 0),
+        })
+    }
+}
+
+impl Default for SolanaPaymentPlugin {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+#[async_trait::async_trait]
+impl PaymentPlugin for SolanaPaymentPlugin {
+    fn provider_key(&self) -> &'static str {
+        "solana"
+    }
+    fn display_name(&self) -> &'static str {
+        "Solana USDC"
+    }
+
+    fn capability(&self) -> PaymentPluginCapability {
+        PaymentPluginCapability {
+            provider: self.provider_key().into(),
+            display_name: self.display_name().into(),
+            configured: self.config.configured,
+            environment: self.config.environment.clone(),
+            api_base_url: self.config.api_base_url.clone(),
+            supports_redirect_checkout: false,
+            supports_webhook_confirmation: false,
+            supports_manual_confirmation: true,
+            supports_recurring: false,
+            supports_refunds: false,
+            supports_status_lookup: true,
+            // USDC is settled as US dollars.
+            supported_currencies: vec!["USD".into()],
+            required_env: self.config.required_env.clone(),
+            missing_env: self.config.missing_env.clone(),
+        }
+    }
+
+    async fn create_invoice(&self, request: CreateInvoiceRequest) -> Result<Invoice> {
+        self.ensure_configured()?;
+        if !request.currency.eq_ignore_ascii_case("USD") {
+            bail!("solana: USDC invoices must be in USD");
+        }
+        if request.amount_cents <= 0 {
+            bail!("solana: amount must be positive");
+        }
+        let invoice_uid = request
+            .metadata
+            .get("invoice_uid")
+            .cloned()
+            .ok_or_else(|| anyhow!("solana: missing invoice_uid"))?;
+
+        let title = request
+            .metadata
+            .get("video_title")
+            .or_else(|| request.metadata.get("bundle_title"))
+            .or_else(|| request.metadata.get("tip_title"))
+            .cloned()
+            .unwrap_or_else(|| request.video_id.clone());
+        let amount = format!(
+            "{}.{:02}",
+            request.amount_cents / 100,
+            request.amount_cents % 100
+        );
+        let payment_url = reqwest::Url::parse_with_params(
+            &format!("solana:{}", self.recipient),
+            &[
+                ("amount", amount.as_str()),
+                ("spl-token", self.mint.as_str()),
+                ("memo", invoice_uid.as_str()),
+                ("label", "PPV Stream"),
+                ("message", title.as_str()),
+            ],
+        )
+        .map_err(|e| anyhow!("solana: payment URL: {e}"))?
+        .to_string();
+        let units = request.amount_cents as u128 * UNITS_PER_CENT;
+
+        Ok(Invoice {
+            provider: self.provider_key().into(),
+            invoice_id: invoice_uid.clone(),
+            payment_url: Some(payment_url.clone()),
+            amount_cents: request.amount_cents,
+            currency: "USD".into(),
+            status: PaymentStatus::Pending,
+            raw: json!({
+                "solana_pay_url": payment_url,
+                "recipient": self.recipient,
+                "token_account": self.token_account,
+                "mint": self.mint,
+                "amount": amount,
+                "amount_units": units.to_string(),
+                "memo": invoice_uid,
+                "cluster": self.config.environment,
+            }),
+        })
+    }
+
+    /// Verifies `transaction_id` when the buyer names one, otherwise looks
+    /// the payment up by memo.
+    async fn confirm_payment(&self, request: ConfirmPaymentRequest) -> Result<PaymentResult> {
+        self.ensure_configured()?;
+        match request
+            .transaction_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            Some(signature) => {
+                if !is_base58(signature) {
+                    bail!("solana: invalid transaction signature");
+                }
+                self.check_signature(request.invoice_id, signature).await
+            }
+            None => self.find_payment(request.invoice_id).await,
+        }
+    }
+
+    async fn payment_status(&self, request: PaymentStatusRequest) -> Result<PaymentResult> {
+        self.ensure_configured()?;
+        self.find_payment(request.invoice_id).await
+    }
+}
+
+fn is_base58(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() && !matches!(b, b'0' | b'O' | b'I' | b'l'))
+}
+
+/// A base58 public key: 32 bytes encode to 32–44 characters.
+fn is_base58_key(value: &str) -> bool {
+    (32..=44).contains(&value.len()) && is_base58(value)
+}
+
+/// Checks a `getTransaction` (jsonParsed) result against an invoice and
+/// returns the USDC base units it credited to `recipient`.
+pub fn verify_transfer(
+    tx: &Value,
+    memo: &str,
+    recipient: &str,
+    mint: &str,
+) -> Result<u128, &'static str> {
+    if tx.is_null() {
+        return Err("transaction not found");
+    }
+    if !tx["meta"]["err"].is_null() {
+        return Err("transaction failed");
+    }
+    let memo_matches = tx["transaction"]["message"]["instructions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|ix| {
+            ix["programId"]
+                .as_str()
+                .is_some_and(|p| MEMO_PROGRAMS.contains(&p))
+        })
+        .any(|ix| ix["parsed"].as_str() == Some(memo));
+    if !memo_matches {
+        return Err("memo does not match the invoice");
+    }
+
+    let balance = |key: &str| -> u128 {
+        tx["meta"][key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|b| b["owner"].as_str() == Some(recipient) && b["mint"].as_str() == Some(mint))
+            .filter_map(|b| b["uiTokenAmount"]["amount"].as_str()?.parse::<u128>().ok())
+            .sum()
+    };
+    let (before, after) = (balance("preTokenBalances"), balance("postTokenBalances"));
+    if after <= before {
+        return Err("no USDC was paid to the recipient");
+    }
+    Ok(after - before)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECIPIENT: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+    const MINT: &str = "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU";
+
+    fn balance(index: u64, owner: &str, mint: &str, amount: &str) -> Value {
+        json!({
+            "accountIndex": index,
+            "owner": owner,
+            "mint": mint,
+            "uiTokenAmount": {"amount": amount, "decimals": 6},
+        })
+    }
+
+    fn transfer(memo: &str, paid: &str) -> Value {
+        json!({
+            "meta": {
+                "err": null,
+                "preTokenBalances": [
+                    balance(1, "Payer111111111111111111111111111111111111", MINT, "9000000"),
+                    balance(2, RECIPIENT, MINT, "1000000"),
+                ],
+                "postTokenBalances": [
+                    balance(1, "Payer111111111111111111111111111111111111", MINT, "7500000"),
+                    balance(2, RECIPIENT, MINT, paid),
+                ],
+            },
+            "transaction": {"message": {"instructions": [
+                {"program": "spl-token", "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                 "parsed": {"type": "transferChecked"}},
+                {"program": "spl-memo", "programId": MEMO_PROGRAMS[0], "parsed": memo},
+            ]}},
+        })
+    }
+
+    #[test]
+    fn a_transfer_with_the_invoice_memo_pays_its_balance_change() {
+        let tx = transfer("inv-1", "2500000");
+        assert_eq!(
+            verify_transfer(&tx, "inv-1", RECIPIENT, MINT),
+            Ok(1_500_000)
+        );
+        assert_eq!(1_500_000 / UNITS_PER_CENT, 150);
+
+        assert_eq!(
+            verify_transfer(&tx, "inv-2", RECIPIENT, MINT),
+            Err("memo does not match the invoice")
+        );
+        assert_eq!(
+            verify_transfer(
+                &tx,
+                "inv-1",
+                RECIPIENT,
+                "OtherMint1111111111111111111111111111111111"
+            ),
+            Err("no USDC was paid to the recipient")
+        );
+
+        let mut failed = tx.clone();
+        failed["meta"]["err"] = json!({"InstructionError": [0, "Custom"]});
+        assert_eq!(
+            verify_transfer(&failed, "inv-1", RECIPIENT, MINT),
+            Err("transaction failed")
+        );
+    }
+
+    #[test]
+    fn keys_and_signatures_are_base58() {
+        assert!(is_base58_key(RECIPIENT));
+        assert!(!is_base58_key("0xabc"));
+        assert!(!is_base58_key(
+            "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFI0"
+        ));
+        assert!(is_base58(
+            "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW"
+        ));
+    }
+}
@@ -8,14 +8,40 @@
 //   3. Stripe calls POST /api/pay/stripe/webhook  (checkout.session.completed)
 //   4. confirm_payment() verifies Stripe-Signature HMAC-SHA256
 //
+// Subscriptions: a checkout with metadata `recurring=true` saves the card for
+// off-session use; renewals call charge_recurring() with the first payment
+// intent id and confirm a new PaymentIntent against the same customer/card.
+//
+// Refunds: refund() creates a Refund against the payment intent. The webhook
+// reports `refund.created` / `refund.updated` (status succeeded) as Refunded
+// and `charge.dispute.created` as Chargeback, keyed by the payment intent.
+//
+// Reconciliation: payment_status() reads the Checkout Session saved as
+// `provider_ref` when its webhook never arrived.
+//
+// Connect (STRIPE_CONNECT_ENABLED=true): creators onboard an Express
+// connected account (create_connected_account + onboarding_link). A checkout
+// for a creator whose account can receive transfers is a destination charge:
+// `transfer_data[destination]` is the creator's account and
+// `application_fee_amount` (the platform share) is all the platform keeps.
+// The handler passes both in the request metadata (CONNECT_ACCOUNT_KEY,
+// APPLICATION_FEE_KEY); renewals of such a subscription are split the same
+// way, and refunds with `reverse_transfer` take the creator share back from
+// the connected account.
+//
 // Env vars required:
 //   STRIPE_SECRET_KEY       sk_test_... / sk_live_...
 //   STRIPE_WEBHOOK_SECRET   whsec_...  (from Stripe Dashboard → Webhooks)
 //   STRIPE_ENV              "test" (default) | "live"
+//   STRIPE_CONNECT_ENABLED  "true" to split charges with connected accounts
+//   STRIPE_API_BASE         default https://api.stripe.com; tests point it at
+//                           a fake server (tests/stripe_connect_integration.sh)
 //
-// Auto-disburse: NOT implemented.
-// All funds land in the platform's Stripe account.
-// Creator payouts need Stripe Connect (out of scope for v1).
+// Auto-disburse: only through Connect. Without it all funds land in the
+// platform's Stripe account.
+// Scheduled creator payouts (method "stripe") are Transfers from the platform
+// balance to the creator's connected account (acct_...). A created transfer is
+// already on the connected account, so it is reported as paid.
 
 use anyhow::{anyhow, bail, Result};
 use base64::{engine::general_purpose::STANDARD as B64, Engine};
@@ -27,16 +53,24 @@ use crate::plugins::payment::{
     env::{env_or, missing_env, required_env},
     models::{
         ConfirmPaymentRequest, CreateInvoiceRequest, Invoice, PaymentPluginCapability,
-        PaymentProviderConfig, PaymentResult, PaymentStatus,
+        PaymentProviderConfig, PaymentResult, PaymentStatus, PaymentStatusRequest, PayoutRequest,
+        PayoutResult, PayoutStatus, RecurringChargeRequest, RefundNotice, RefundRequest,
+        RefundResult, RefundStatus,
     },
-    traits::PaymentPlugin,
+    traits::{PaymentPlugin, PayoutPlugin},
 };
 
+/// Metadata key of the connected account a checkout is split with.
+pub const CONNECT_ACCOUNT_KEY: &str = "connect_account_id";
+/// Metadata key of the platform share of a split checkout, in minor units.
+pub const APPLICATION_FEE_KEY: &str = "application_fee_amount";
+
 #[derive(Clone, Debug)]
 pub struct StripePaymentPlugin {
     config: PaymentProviderConfig,
     secret_key: String,
     webhook_secret: String,
+    connect: bool,
 }
 
 impl StripePaymentPlugin {
@@ -44,20 +78,150 @@ impl StripePaymentPlugin {
         let environment = env_or("STRIPE_ENV", "test");
         let secret_key = std::env::var("STRIPE_SECRET_KEY").unwrap_or_default();
         let webhook_secret = std::env::var("STRIPE_WEBHOOK_SECRET").unwrap_or_default();
+        let connect = std::env::var("STRIPE_CONNECT_ENABLED")
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false);
+        let api_base = std::env::var("STRIPE_API_BASE")
+            .ok()
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "https://api.stripe.com".into());
         let required = ["STRIPE_SECRET_KEY", "STRIPE_WEBHOOK_SECRET"];
         Self {
             secret_key,
             webhook_secret,
+            connect,
             config: PaymentProviderConfig::new(
                 "stripe",
                 environment,
-                Some("https://api.stripe.com".into()),
+                Some(api_base),
                 required_env(&required),
                 missing_env(&required),
             ),
         }
     }
 
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}{path}",
+            self.config.api_base_url.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Sends a Stripe API request and returns its JSON body, failing on an
+    /// error status.
+    async fn call(&self, request: reqwest::RequestBuilder) -> Result<Value> {
+        let resp = request
+            .basic_auth(&self.secret_key, Some(""))
+            .send()
+            .await
+            .map_err(|e| anyhow!("stripe: HTTP error: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("stripe: response parse error: {e}"))?;
+        if !http_status.is_success() {
+            let msg = body["error"]["message"].as_str().unwrap_or("unknown");
+            bail!("stripe: API {http_status}: {msg}");
+        }
+        Ok(body)
+    }
+
+    /// Connect mode is on and the platform can call Stripe.
+    pub fn connect_enabled(&self) -> bool {
+        self.connect && !self.secret_key.is_empty()
+    }
+
+    /// Creates an Express connected account for a creator and returns its id.
+    pub async fn create_connected_account(&self, user_id: &str, email: &str) -> Result<String> {
+        if !self.connect_enabled() {
+            bail!("Stripe Connect is not enabled");
+        }
+        let mut params: Vec<(String, String)> = vec![
+            ("type".into(), "express".into()),
+            (
+                "capabilities[card_payments][requested]".into(),
+                "true".into(),
+            ),
+            ("capabilities[transfers][requested]".into(), "true".into()),
+            ("metadata[user_id]".into(), user_id.to_string()),
+        ];
+        if !email.is_empty() {
+            params.push(("email".into(), email.to_string()));
+        }
+        let body = self
+            .call(
+                reqwest::Client::new()
+                    .post(self.url("/v1/accounts"))
+                    .header("Idempotency-Key", format!("connect-account-{user_id}"))
+                    .form(&params),
+            )
+            .await?;
+        body["id"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow!("stripe: account response has no id"))
+    }
+
+    /// One-time onboarding URL for `account_id`. Stripe sends the creator to
+    /// `refresh_url` when the link has expired and to `return_url` when done.
+    pub async fn onboarding_link(
+        &self,
+        account_id: &str,
+        refresh_url: &str,
+        return_url: &str,
+    ) -> Result<String> {
+        let params = [
+            ("account", account_id),
+            ("refresh_url", refresh_url),
+            ("return_url", return_url),
+            ("type", "account_onboarding"),
+        ];
+        let body = self
+            .call(
+                reqwest::Client::new()
+                    .post(self.url("/v1/account_links"))
+                    .form(&params),
+            )
+            .await?;
+        body["url"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow!("stripe: account link response has no url"))
+    }
+
+    /// Whether `account_id` can receive destination charges: onboarding is
+    /// finished and its `transfers` capability is active.
+    pub async fn connected_account_ready(&self, account_id: &str) -> Result<bool> {
+        let body = self
+            .call(reqwest::Client::new().get(self.url(&format!("/v1/accounts/{account_id}"))))
+            .await?;
+        Ok(body["details_submitted"].as_bool() == Some(true)
+            && body["capabilities"]["transfers"].as_str() == Some("active"))
+    }
+
+    /// Connected account and application fee of a split charge of `amount`,
+    /// from the request metadata; None for a charge the platform keeps.
+    fn connect_split(
+        &self,
+        metadata: &std::collections::HashMap<String, String>,
+        amount: i64,
+    ) -> Result<Option<(String, i64)>> {
+        let Some(account) = metadata.get(CONNECT_ACCOUNT_KEY).filter(|a| !a.is_empty()) else {
+            return Ok(None);
+        };
+        if !self.connect_enabled() {
+            bail!("stripe: Connect is not enabled");
+        }
+        let fee = metadata
+            .get(APPLICATION_FEE_KEY)
+            .and_then(|f| f.parse::<i64>().ok())
+            .filter(|f| (0..=amount).contains(f))
+            .ok_or_else(|| anyhow!("stripe: split charge needs an application fee"))?;
+        Ok(Some((account.clone(), fee)))
+    }
+
     /// Verify `Stripe-Signature` header using HMAC-SHA256.
     /// `raw_payload` must be the exact bytes received from Stripe (not re-serialised JSON).
     fn verify_signature(&self, raw_payload: &[u8], sig_header: &str) -> Result<()> {
@@ -112,6 +276,9 @@ impl PaymentPlugin for StripePaymentPlugin {
             supports_redirect_checkout: true,
             supports_webhook_confirmation: true,
             supports_manual_confirmation: false,
+            supports_recurring: true,
+            supports_refunds: true,
+            supports_status_lookup: true,
             supported_currencies: vec!["USD".into(), "EUR".into(), "IDR".into()],
             required_env: self.config.required_env.clone(),
             missing_env: self.config.missing_env.clone(),
@@ -173,10 +340,28 @@ impl PaymentPlugin for StripePaymentPlugin {
                 params.push(("customer_email".into(), email.clone()));
             }
         }
+        if request.metadata.get("recurring").map(String::as_str) == Some("true") {
+            params.push((
+                "payment_intent_data[setup_future_usage]".into(),
+                "off_session".into(),
+            ));
+            params.push(("customer_creation".into(), "always".into()));
+        }
+        let split = self.connect_split(&request.metadata, request.amount_cents)?;
+        if let Some((account, fee)) = &split {
+            params.push((
+                "payment_intent_data[transfer_data][destination]".into(),
+                account.clone(),
+            ));
+            params.push((
+                "payment_intent_data[application_fee_amount]".into(),
+                fee.to_string(),
+            ));
+        }
 
         let client = reqwest::Client::new();
         let resp = client
-            .post("https://api.stripe.com/v1/checkout/sessions")
+            .post(self.url("/v1/checkout/sessions"))
             .basic_auth(&self.secret_key, Some(""))
             .form(&params)
             .send()
@@ -204,10 +389,23 @@ impl PaymentPlugin for StripePaymentPlugin {
             amount_cents: request.amount_cents,
             currency: request.currency,
             status: PaymentStatus::Pending,
-            raw: json!({ "session_id": session_id }),
+            raw: json!({
+                "session_id": session_id,
+                "connect_account_id": split.as_ref().map(|(account, _)| account),
+                "application_fee_amount": split.as_ref().map(|(_, fee)| fee),
+            }),
         })
     }
 
+    /// Stripe event ids (`evt_...`) are stable across redeliveries.
+    fn webhook_event_id(
+        &self,
+        payload: &Value,
+        _headers: &std::collections::HashMap<String, String>,
+    ) -> Option<String> {
+        payload["id"].as_str().map(String::from)
+    }
+
     /// Called by the webhook handler.
     ///
     /// The webhook handler stores the original raw bytes as base64 under `__raw__` inside
@@ -254,9 +452,25 @@ impl PaymentPlugin for StripePaymentPlugin {
             "payment_intent.succeeded" => PaymentStatus::Paid,
             "payment_intent.payment_failed" => PaymentStatus::Failed,
             "checkout.session.expired" => PaymentStatus::Expired,
+            "refund.created" | "refund.updated" if obj["status"].as_str() == Some("succeeded") => {
+                PaymentStatus::Refunded
+            }
+            "charge.dispute.created" => PaymentStatus::Chargeback,
             _ => PaymentStatus::Unknown,
         };
 
+        // Refund and dispute objects carry the payment intent, not our
+        // checkout metadata; the handler resolves the invoice from it.
+        let refund =
+            matches!(status, PaymentStatus::Refunded | PaymentStatus::Chargeback).then(|| {
+                RefundNotice {
+                    refund_id: obj["id"].as_str().unwrap_or_default().to_string(),
+                    payment_ref: obj["payment_intent"].as_str().map(String::from),
+                    amount_cents: obj["amount"].as_i64().unwrap_or(0),
+                    reason: obj["reason"].as_str().map(String::from),
+                }
+            });
+
         let invoice_uid = obj["metadata"]["invoice_uid"].as_str().unwrap_or("").into();
         let transaction_id = obj["payment_intent"].as_str().map(String::from);
         let paid_amount = obj["amount_total"].as_i64().unwrap_or(0);
@@ -270,6 +484,285 @@ impl PaymentPlugin for StripePaymentPlugin {
             paid_amount_cents: paid_amount,
             currency,
             raw: event,
+            refund,
+        })
+    }
+
+    /// Renews a subscription: looks up the customer and card of the first
+    /// PaymentIntent (`payment_ref`) and confirms a new off-session intent.
+    async fn charge_recurring(&self, request: RecurringChargeRequest) -> Result<PaymentResult> {
+        if !self.config.configured {
+            bail!(
+                "Stripe plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+
+        let client = reqwest::Client::new();
+        let original: Value = client
+            .get(self.url(&format!("/v1/payment_intents/{}", request.payment_ref)))
+            .basic_auth(&self.secret_key, Some(""))
+            .send()
+            .await
+            .map_err(|e| anyhow!("stripe: HTTP error: {e}"))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("stripe: response parse error: {e}"))?;
+
+        let customer = original["customer"]
+            .as_str()
+            .ok_or_else(|| anyhow!("stripe: original payment has no customer"))?;
+        let payment_method = original["payment_method"]
+            .as_str()
+            .ok_or_else(|| anyhow!("stripe: original payment has no saved payment method"))?;
+
+        let mut params: Vec<(String, String)> = vec![
+            ("amount".into(), request.amount_cents.to_string()),
+            ("currency".into(), request.currency.to_lowercase()),
+            ("customer".into(), customer.into()),
+            ("payment_method".into(), payment_method.into()),
+            ("off_session".into(), "true".into()),
+            ("confirm".into(), "true".into()),
+            (
+                "metadata[subscription_id]".into(),
+                request.subscription_id.clone(),
+            ),
+            ("metadata[user_id]".into(), request.user_id.clone()),
+        ];
+        for (key, value) in &request.metadata {
+            params.push((format!("metadata[{key}]"), value.clone()));
+        }
+        if let Some((account, fee)) = self.connect_split(&request.metadata, request.amount_cents)? {
+            params.push(("transfer_data[destination]".into(), account));
+            params.push(("application_fee_amount".into(), fee.to_string()));
+        }
+
+        let resp = client
+            .post(self.url("/v1/payment_intents"))
+            .basic_auth(&self.secret_key, Some(""))
+            .header("Idempotency-Key", &request.idempotency_key)
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| anyhow!("stripe: HTTP error: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("stripe: response parse error: {e}"))?;
+
+        let status = if !http_status.is_success() {
+            PaymentStatus::Failed
+        } else {
+            match body["status"].as_str() {
+                Some("succeeded") => PaymentStatus::Paid,
+                Some("processing") => PaymentStatus::Pending,
+                Some("canceled") => PaymentStatus::Cancelled,
+                _ => PaymentStatus::Failed,
+            }
+        };
+
+        Ok(PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.subscription_id,
+            transaction_id: body["id"]
+                .as_str()
+                .or_else(|| body["error"]["payment_intent"]["id"].as_str())
+                .map(String::from),
+            status,
+            paid_amount_cents: body["amount_received"].as_i64().unwrap_or(0),
+            currency: request.currency,
+            raw: body,
+            refund: None,
+        })
+    }
+
+    /// Refunds `amount_cents` of the payment intent saved as `payment_ref`.
+    async fn refund(&self, request: RefundRequest) -> Result<RefundResult> {
+        if !self.config.configured {
+            bail!(
+                "Stripe plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+
+        let mut params: Vec<(String, String)> = vec![
+            ("payment_intent".into(), request.payment_ref.clone()),
+            ("amount".into(), request.amount_cents.to_string()),
+            ("metadata[invoice_uid]".into(), request.invoice_id.clone()),
+        ];
+        if let Some(reason) = request.reason.as_deref().filter(|r| !r.is_empty()) {
+            params.push(("metadata[reason]".into(), reason.to_string()));
+        }
+        if request.reverse_transfer {
+            params.push(("reverse_transfer".into(), "true".into()));
+            params.push(("refund_application_fee".into(), "true".into()));
+        }
+
+        let resp = reqwest::Client::new()
+            .post(self.url("/v1/refunds"))
+            .basic_auth(&self.secret_key, Some(""))
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| anyhow!("stripe: HTTP error: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("stripe: response parse error: {e}"))?;
+        if !http_status.is_success() {
+            let msg = body["error"]["message"].as_str().unwrap_or("unknown");
+            bail!("stripe: API {http_status}: {msg}");
+        }
+
+        let status = match body["status"].as_str() {
+            Some("succeeded") => RefundStatus::Succeeded,
+            Some("failed") | Some("canceled") => RefundStatus::Failed,
+            _ => RefundStatus::Pending,
+        };
+        Ok(RefundResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            refund_id: body["id"].as_str().map(String::from),
+            status,
+            amount_cents: body["amount"].as_i64().unwrap_or(request.amount_cents),
+            currency: request.currency,
+            raw: body,
+        })
+    }
+
+    /// Reads the Checkout Session saved as `payment_ref`.
+    async fn payment_status(&self, request: PaymentStatusRequest) -> Result<PaymentResult> {
+        if !self.config.configured {
+            bail!(
+                "Stripe plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+        let session_id = request
+            .payment_ref
+            .as_deref()
+            .filter(|r| r.starts_with("cs_"))
+            .ok_or_else(|| anyhow!("stripe: invoice has no checkout session"))?;
+
+        let resp = reqwest::Client::new()
+            .get(self.url(&format!("/v1/checkout/sessions/{session_id}")))
+            .basic_auth(&self.secret_key, Some(""))
+            .send()
+            .await
+            .map_err(|e| anyhow!("stripe: HTTP error: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("stripe: response parse error: {e}"))?;
+
+        let status = if http_status == reqwest::StatusCode::NOT_FOUND {
+            PaymentStatus::Unknown
+        } else if !http_status.is_success() {
+            let msg = body["error"]["message"].as_str().unwrap_or("unknown");
+            bail!("stripe: API {http_status}: {msg}");
+        } else {
+            match (body["payment_status"].as_str(), body["status"].as_str()) {
+                (Some("paid" | "no_payment_required"), _) => PaymentStatus::Paid,
+                (_, Some("expired")) => PaymentStatus::Expired,
+                _ => PaymentStatus::Pending,
+            }
+        };
+
+        Ok(PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            transaction_id: body["payment_intent"].as_str().map(String::from),
+            status,
+            paid_amount_cents: body["amount_total"].as_i64().unwrap_or(0),
+            currency: body["currency"]
+                .as_str()
+                .map(str::to_uppercase)
+                .unwrap_or(request.currency),
+            raw: body,
+            refund: None,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl PayoutPlugin for StripePaymentPlugin {
+    fn payout_method(&self) -> &'static str {
+        "stripe"
+    }
+
+    fn payout_label(&self) -> &'static str {
+        "Stripe connected account"
+    }
+
+    fn payout_configured(&self) -> bool {
+        !self.secret_key.is_empty()
+    }
+
+    fn payout_currency(&self) -> String {
+        "USD".into()
+    }
+
+    fn validate_destination(&self, destination: &str) -> Result<()> {
+        let id = destination.trim();
+        if id.len() > 5
+            && id.starts_with("acct_")
+            && id[5..].chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            Ok(())
+        } else {
+            bail!("Stripe payouts need a connected account id (acct_...)")
+        }
+    }
+
+    fn destination_hint(&self) -> &'static str {
+        "acct_..."
+    }
+
+    async fn send_payout(&self, request: PayoutRequest) -> Result<PayoutResult> {
+        let params: Vec<(String, String)> = vec![
+            ("amount".into(), request.amount.to_string()),
+            ("currency".into(), request.currency.to_ascii_lowercase()),
+            ("destination".into(), request.destination.trim().to_string()),
+            ("transfer_group".into(), request.payout_id.clone()),
+            ("description".into(), request.description.clone()),
+            ("metadata[payout_id]".into(), request.payout_id.clone()),
+        ];
+        let resp = reqwest::Client::new()
+            .post(self.url("/v1/transfers"))
+            .basic_auth(&self.secret_key, Some(""))
+            .header("Idempotency-Key", &request.idempotency_key)
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| anyhow!("stripe: HTTP error: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("stripe: response parse error: {e}"))?;
+        // A 4xx other than an idempotency conflict is a rejection: no
+        // transfer was made. A 409 or 5xx leaves the outcome unknown.
+        if http_status.is_client_error() && http_status != reqwest::StatusCode::CONFLICT {
+            let msg = body["error"]["message"].as_str().unwrap_or("unknown");
+            return Ok(PayoutResult {
+                provider_ref: None,
+                status: PayoutStatus::Failed,
+                failure_reason: Some(format!("stripe: API {http_status}: {msg}")),
+                raw: body,
+            });
+        }
+        if !http_status.is_success() {
+            let msg = body["error"]["message"].as_str().unwrap_or("unknown");
+            bail!("stripe: API {http_status}: {msg}");
+        }
+        Ok(PayoutResult {
+            provider_ref: body["id"].as_str().map(String::from),
+            status: PayoutStatus::Paid,
+            failure_reason: None,
+            raw: body,
         })
     }
 }
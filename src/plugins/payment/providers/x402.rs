@@ -17,6 +17,7 @@ use crate::plugins::payment::{
     },
     traits::PaymentPlugin,
 };
+use crate::x402_chains::find_chain;
 
 #[derive(Clone, Debug)]
 pub struct X402PaymentPlugin {
@@ -30,11 +31,9 @@ impl X402PaymentPlugin {
     }
 
     pub fn from_env_with_pool(pool: Option<PgPool>) -> Self {
-        let required = [
-            "X402_CONTRACT_ADDRESS",
-            "X402_RPC_HTTP",
-            "X402_ADMIN_PRIVKEY",
-        ];
+        // Contracts and RPC endpoints are per chain (`x402_chains`); one admin
+        // key signs for all of them.
+        let required = ["X402_ADMIN_PRIVKEY"];
         let chain_id = env_or("X402_CHAIN_ID", "evm");
         let rpc_http = std::env::var("X402_RPC_HTTP").ok();
         let mut missing = missing_env(&required);
@@ -87,6 +86,9 @@ impl PaymentPlugin for X402PaymentPlugin {
             supports_redirect_checkout: false,
             supports_webhook_confirmation: false,
             supports_manual_confirmation: true,
+            supports_recurring: false,
+            supports_refunds: false,
+            supports_status_lookup: false,
             supported_currencies: vec!["USDC".into(), "MATIC".into(), "ETH".into()],
             required_env: self.config.required_env.clone(),
             missing_env: self.config.missing_env.clone(),
@@ -118,6 +120,12 @@ impl PaymentPlugin for X402PaymentPlugin {
         let payer_address = Address::from_str(&payer_address_string)
             .map_err(|_| anyhow!("invalid x402 payer_address"))?;
 
+        let chain = find_chain(pool, chain_id)
+            .await?
+            .ok_or_else(|| anyhow!("chain not supported"))?;
+        let contract_address =
+            Address::from_str(&chain.contract).map_err(|_| anyhow!("invalid x402 contract"))?;
+
         let video_metadata = sqlx::query(
             r#"
             SELECT v.price_cents, v.owner_id, u.wallet_account
@@ -218,7 +226,6 @@ impl PaymentPlugin for X402PaymentPlugin {
         .execute(pool)
         .await?;
 
-        let x402_contract = std::env::var("X402_CONTRACT_ADDRESS").unwrap_or_default();
         let token_address = token_address_string
             .as_deref()
             .and_then(|value| Address::from_str(value).ok())
@@ -245,6 +252,8 @@ impl PaymentPlugin for X402PaymentPlugin {
             AbiToken::FixedBytes(video_hash.as_bytes().to_vec()),
             AbiToken::Address(payer_address),
             AbiToken::Uint(U256::from(deadline)),
+            AbiToken::Address(contract_address),
+            AbiToken::Uint(U256::from(chain_id as u64)),
         ]);
 
         let message_hash = H256::from_slice(&keccak256(&encoded_payload));
@@ -273,7 +282,7 @@ impl PaymentPlugin for X402PaymentPlugin {
             "s": format!("{:#066x}", signature.s),
             "split_creator_bp": creator_basis_points,
             "split_admin_bp": 10000u16 - creator_basis_points,
-            "x402_contract": x402_contract,
+            "x402_contract": chain.contract,
             "creator_wallet": creator_wallet_string,
         });
 
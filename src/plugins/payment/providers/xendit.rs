@@ -14,7 +14,16 @@
 //   XENDIT_WEBHOOK_TOKEN    Callback verification token (set in Xendit dashboard)
 //   XENDIT_ENV              "test" (default) | "production"
 //
+// Refunds: refund() calls the Refund API for the Xendit invoice; the
+// `refund.succeeded` callback is reported as Refunded. Xendit sends no
+// chargeback callback for invoices, so disputes are handled from its dashboard.
+//
+// Reconciliation: payment_status() reads the Xendit invoice saved as
+// `provider_ref` when its callback never arrived.
+//
 // Auto-disburse: YES — Xendit Disbursement API supports sending funds to Indonesian bank accounts.
+// Scheduled creator payouts (method "xendit") use the same API through
+// PayoutPlugin, in IDR, keyed by payout id; they only need XENDIT_SECRET_KEY.
 //
 // Creator bank account format (stored in users.bank_account):
 //   "BCA 1234567890 a/n Nama Lengkap"
@@ -27,9 +36,10 @@ use crate::plugins::payment::{
     env::{env_or, missing_env, required_env},
     models::{
         ConfirmPaymentRequest, CreateInvoiceRequest, Invoice, PaymentPluginCapability,
-        PaymentProviderConfig, PaymentResult, PaymentStatus,
+        PaymentProviderConfig, PaymentResult, PaymentStatus, PaymentStatusRequest, PayoutRequest,
+        PayoutResult, PayoutStatus, RefundNotice, RefundRequest, RefundResult, RefundStatus,
     },
-    traits::PaymentPlugin,
+    traits::{PaymentPlugin, PayoutPlugin},
 };
 
 #[derive(Clone, Debug)]
@@ -61,6 +71,14 @@ impl XenditPaymentPlugin {
         }
     }
 
+    fn invoice_status(xendit_status: &str) -> PaymentStatus {
+        match xendit_status {
+            "PAID" | "SETTLED" => PaymentStatus::Paid,
+            "EXPIRED" => PaymentStatus::Expired,
+            _ => PaymentStatus::Pending,
+        }
+    }
+
     /// Parse creator's bank_account string into (bank_code, account_number, holder_name).
     ///
     /// Expected format: "BCA 1234567890 a/n Nama Lengkap"
@@ -102,20 +120,50 @@ impl XenditPaymentPlugin {
             .min(10000);
         let creator_amount = (amount_idr as f64 * (creator_split_bp as f64 / 10000.0)) as i64;
 
+        let (http_status, result) = self
+            .create_disbursement(
+                &format!("{invoice_uid}-creator"),
+                creator_amount,
+                (&bank_code, &account_number, &holder_name),
+                &format!("PPV creator payout for {invoice_uid}"),
+                None,
+            )
+            .await?;
+        if !http_status.is_success() {
+            let msg = result["message"].as_str().unwrap_or("unknown");
+            bail!("xendit: disbursement API {http_status}: {msg}");
+        }
+        Ok(result)
+    }
+
+    /// POST /disbursements. With an idempotency key Xendit answers a repeated
+    /// request with the disbursement it already created.
+    async fn create_disbursement(
+        &self,
+        external_id: &str,
+        amount_idr: i64,
+        (bank_code, account_number, holder_name): (&str, &str, &str),
+        description: &str,
+        idempotency_key: Option<&str>,
+    ) -> Result<(reqwest::StatusCode, Value)> {
         let body = json!({
-            "external_id":         format!("{invoice_uid}-creator"),
-            "amount":              creator_amount,
+            "external_id":         external_id,
+            "amount":              amount_idr,
             "bank_code":           bank_code,
             "account_holder_name": holder_name,
             "account_number":      account_number,
-            "description":         format!("PPV creator payout for {invoice_uid}")
+            "description":         description
         });
 
         let client = reqwest::Client::new();
-        let resp = client
+        let mut req = client
             .post(format!("{}/disbursements", self.api_base))
             .basic_auth(&self.secret_key, Some(""))
-            .json(&body)
+            .json(&body);
+        if let Some(key) = idempotency_key {
+            req = req.header("X-IDEMPOTENCY-KEY", key);
+        }
+        let resp = req
             .send()
             .await
             .map_err(|e| anyhow!("xendit: disbursement request failed: {e}"))?;
@@ -126,12 +174,21 @@ impl XenditPaymentPlugin {
             .await
             .map_err(|e| anyhow!("xendit: disbursement parse error: {e}"))?;
 
-        if !http_status.is_success() {
-            let msg = result["message"].as_str().unwrap_or("unknown");
-            bail!("xendit: disbursement API {http_status}: {msg}");
-        }
+        Ok((http_status, result))
+    }
 
-        Ok(result)
+    fn disbursement_result(body: Value) -> PayoutResult {
+        let status = match body["status"].as_str().unwrap_or("PENDING") {
+            "COMPLETED" => PayoutStatus::Paid,
+            "FAILED" => PayoutStatus::Failed,
+            _ => PayoutStatus::Processing,
+        };
+        PayoutResult {
+            provider_ref: body["id"].as_str().map(String::from),
+            failure_reason: body["failure_code"].as_str().map(String::from),
+            status,
+            raw: body,
+        }
     }
 }
 
@@ -160,6 +217,9 @@ impl PaymentPlugin for XenditPaymentPlugin {
             supports_redirect_checkout: true,
             supports_webhook_confirmation: true,
             supports_manual_confirmation: false,
+            supports_recurring: false,
+            supports_refunds: true,
+            supports_status_lookup: true,
             supported_currencies: vec!["IDR".into(), "PHP".into(), "USD".into()],
             required_env: self.config.required_env.clone(),
             missing_env: self.config.missing_env.clone(),
@@ -244,6 +304,26 @@ impl PaymentPlugin for XenditPaymentPlugin {
         })
     }
 
+    /// Xendit sends a `webhook-id` header; older callbacks are identified by
+    /// the object id and its status.
+    fn webhook_event_id(
+        &self,
+        payload: &Value,
+        headers: &std::collections::HashMap<String, String>,
+    ) -> Option<String> {
+        if let Some(id) = headers.get("webhook-id").filter(|id| !id.is_empty()) {
+            return Some(id.clone());
+        }
+        if let Some(event) = payload["event"].as_str() {
+            return Some(format!("{}:{event}", payload["data"]["id"].as_str()?));
+        }
+        Some(format!(
+            "{}:{}",
+            payload["id"].as_str()?,
+            payload["status"].as_str().unwrap_or("")
+        ))
+    }
+
     /// Verifies x-callback-token header, then parses `status` from the Xendit Invoice webhook.
     ///
     /// After this returns PaymentStatus::Paid, the webhook handler should call
@@ -269,12 +349,36 @@ impl PaymentPlugin for XenditPaymentPlugin {
             bail!("xendit: x-callback-token mismatch");
         }
 
-        let xendit_status = payload["status"].as_str().unwrap_or("PENDING");
-        let status = match xendit_status {
-            "PAID" | "SETTLED" => PaymentStatus::Paid,
-            "EXPIRED" => PaymentStatus::Expired,
-            _ => PaymentStatus::Pending,
-        };
+        // Refund callbacks wrap the refund in `data`; reference_id is our uid.
+        if let Some(event) = payload["event"]
+            .as_str()
+            .filter(|e| e.starts_with("refund."))
+        {
+            let data = &payload["data"];
+            let status = if event == "refund.succeeded" {
+                PaymentStatus::Refunded
+            } else {
+                PaymentStatus::Unknown
+            };
+            let amount = data["amount"].as_i64().unwrap_or(0);
+            return Ok(PaymentResult {
+                provider: self.provider_key().into(),
+                invoice_id: data["reference_id"].as_str().unwrap_or("").to_string(),
+                transaction_id: data["invoice_id"].as_str().map(String::from),
+                status,
+                paid_amount_cents: 0,
+                currency: data["currency"].as_str().unwrap_or("IDR").to_uppercase(),
+                refund: Some(RefundNotice {
+                    refund_id: data["id"].as_str().unwrap_or_default().to_string(),
+                    payment_ref: data["invoice_id"].as_str().map(String::from),
+                    amount_cents: amount,
+                    reason: data["reason"].as_str().map(String::from),
+                }),
+                raw: payload,
+            });
+        }
+
+        let status = Self::invoice_status(payload["status"].as_str().unwrap_or("PENDING"));
 
         let invoice_uid = payload["external_id"].as_str().unwrap_or("").to_string();
         let transaction_id = payload["id"].as_str().map(String::from);
@@ -292,6 +396,196 @@ impl PaymentPlugin for XenditPaymentPlugin {
             paid_amount_cents: paid_amount,
             currency,
             raw: payload,
+            refund: None,
         })
     }
+
+    /// Refunds the Xendit invoice saved as `payment_ref`.
+    async fn refund(&self, request: RefundRequest) -> Result<RefundResult> {
+        if !self.config.configured {
+            bail!(
+                "Xendit plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+
+        let body = json!({
+            "invoice_id":   request.payment_ref,
+            "reference_id": request.invoice_id,
+            "amount":       request.amount_cents,
+            "currency":     request.currency.to_uppercase(),
+            "reason":       "REQUESTED_BY_CUSTOMER",
+            "metadata":     { "note": request.reason.clone().unwrap_or_default() },
+        });
+
+        let resp = reqwest::Client::new()
+            .post(format!("{}/refunds", self.api_base))
+            .basic_auth(&self.secret_key, Some(""))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("xendit: refund request failed: {e}"))?;
+        let http_status = resp.status();
+        let result: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("xendit: refund parse error: {e}"))?;
+        if !http_status.is_success() {
+            let msg = result["message"].as_str().unwrap_or("unknown");
+            bail!("xendit: refund API {http_status}: {msg}");
+        }
+
+        let status = match result["status"].as_str() {
+            Some("SUCCEEDED") => RefundStatus::Succeeded,
+            Some("FAILED") | Some("CANCELLED") => RefundStatus::Failed,
+            _ => RefundStatus::Pending,
+        };
+        Ok(RefundResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            refund_id: result["id"].as_str().map(String::from),
+            status,
+            amount_cents: result["amount"].as_i64().unwrap_or(request.amount_cents),
+            currency: request.currency,
+            raw: result,
+        })
+    }
+
+    /// Reads the Xendit invoice saved as `payment_ref`.
+    async fn payment_status(&self, request: PaymentStatusRequest) -> Result<PaymentResult> {
+        if !self.config.configured {
+            bail!(
+                "Xendit plugin not configured: {:?}",
+                self.config.missing_env
+            );
+        }
+        let xendit_id = request
+            .payment_ref
+            .as_deref()
+            .filter(|r| !r.is_empty() && *r != request.invoice_id)
+            .ok_or_else(|| anyhow!("xendit: invoice has no Xendit invoice id"))?;
+
+        let resp = reqwest::Client::new()
+            .get(format!("{}/v2/invoices/{xendit_id}", self.api_base))
+            .basic_auth(&self.secret_key, Some(""))
+            .send()
+            .await
+            .map_err(|e| anyhow!("xendit: invoice lookup failed: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("xendit: invoice parse error: {e}"))?;
+
+        let status = if http_status == reqwest::StatusCode::NOT_FOUND {
+            PaymentStatus::Unknown
+        } else if !http_status.is_success() {
+            let msg = body["message"].as_str().unwrap_or("unknown");
+            bail!("xendit: API {http_status}: {msg}");
+        } else {
+            Self::invoice_status(body["status"].as_str().unwrap_or("PENDING"))
+        };
+
+        Ok(PaymentResult {
+            provider: self.provider_key().into(),
+            invoice_id: request.invoice_id,
+            transaction_id: body["id"].as_str().map(String::from),
+            status,
+            paid_amount_cents: body["paid_amount"]
+                .as_i64()
+                .or_else(|| body["amount"].as_i64())
+                .unwrap_or(0),
+            currency: body["currency"]
+                .as_str()
+                .map(str::to_uppercase)
+                .unwrap_or(request.currency),
+            raw: body,
+            refund: None,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl PayoutPlugin for XenditPaymentPlugin {
+    fn payout_method(&self) -> &'static str {
+        "xendit"
+    }
+
+    fn payout_label(&self) -> &'static str {
+        "Bank transfer (Xendit)"
+    }
+
+    fn payout_configured(&self) -> bool {
+        !self.secret_key.is_empty()
+    }
+
+    fn payout_currency(&self) -> String {
+        "IDR".into()
+    }
+
+    fn validate_destination(&self, destination: &str) -> Result<()> {
+        Self::parse_bank_account(destination)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("bank account must look like \"BCA 1234567890 a/n Name\""))
+    }
+
+    fn destination_hint(&self) -> &'static str {
+        "BCA 1234567890 a/n Name"
+    }
+
+    async fn send_payout(&self, request: PayoutRequest) -> Result<PayoutResult> {
+        let rejected = |reason: String, raw: Value| PayoutResult {
+            provider_ref: None,
+            status: PayoutStatus::Failed,
+            failure_reason: Some(reason),
+            raw,
+        };
+        let Some((bank_code, account_number, holder_name)) =
+            Self::parse_bank_account(&request.destination)
+        else {
+            return Ok(rejected(
+                format!("xendit: invalid bank_account: {}", request.destination),
+                Value::Null,
+            ));
+        };
+        let (http_status, body) = self
+            .create_disbursement(
+                &request.payout_id,
+                request.amount,
+                (&bank_code, &account_number, &holder_name),
+                &request.description,
+                Some(&request.idempotency_key),
+            )
+            .await?;
+        if http_status.is_success() {
+            return Ok(Self::disbursement_result(body));
+        }
+        let msg = body["message"].as_str().unwrap_or("unknown");
+        // A reused key means the disbursement was created before: unknown
+        // outcome, like a 5xx. Any other 4xx is a rejection.
+        if http_status.is_client_error() && body["error_code"] != "DUPLICATE_TRANSACTION_ERROR" {
+            let reason = format!("xendit: disbursement API {http_status}: {msg}");
+            return Ok(rejected(reason, body));
+        }
+        bail!("xendit: disbursement API {http_status}: {msg}");
+    }
+
+    async fn payout_status(&self, provider_ref: &str) -> Result<PayoutResult> {
+        let resp = reqwest::Client::new()
+            .get(format!("{}/disbursements/{provider_ref}", self.api_base))
+            .basic_auth(&self.secret_key, Some(""))
+            .send()
+            .await
+            .map_err(|e| anyhow!("xendit: disbursement lookup failed: {e}"))?;
+        let http_status = resp.status();
+        let body: Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("xendit: disbursement parse error: {e}"))?;
+        if !http_status.is_success() {
+            let msg = body["message"].as_str().unwrap_or("unknown");
+            bail!("xendit: disbursement API {http_status}: {msg}");
+        }
+        Ok(Self::disbursement_result(body))
+    }
 }
@@ -8,10 +8,12 @@ use sqlx::PgPool;
 
 use super::{
     providers::{
-        midtrans::MidtransPaymentPlugin, paypal::PaypalPaymentPlugin, stripe::StripePaymentPlugin,
-        x402::X402PaymentPlugin, xendit::XenditPaymentPlugin,
+        lightning::LightningPaymentPlugin, manual_bank::ManualBankPayoutPlugin,
+        midtrans::MidtransPaymentPlugin, mock::MockPaymentPlugin, paypal::PaypalPaymentPlugin,
+        solana::SolanaPaymentPlugin, stripe::StripePaymentPlugin, x402::X402PaymentPlugin,
+        xendit::XenditPaymentPlugin,
     },
-    traits::PaymentPlugin,
+    traits::{PaymentPlugin, PayoutPlugin},
 };
 use crate::payment_settings::{load_payment_settings, PaymentSettings};
 
@@ -36,7 +38,7 @@ impl PaymentPluginRegistry {
 
     pub fn from_env_with_pool(pool: Option<PgPool>) -> Self {
         let enabled = env::var("PAYMENT_PLUGINS")
-            .unwrap_or_else(|_| "x402,paypal,stripe,midtrans,xendit".to_string());
+            .unwrap_or_else(|_| "x402,paypal,stripe,midtrans,xendit,solana,lightning".to_string());
         let default_provider = env::var("PAYMENT_DEFAULT_PROVIDER").ok();
 
         let mut registry = Self::new();
@@ -49,9 +51,15 @@ impl PaymentPluginRegistry {
                 "stripe" => registry.register(Arc::new(StripePaymentPlugin::from_env())),
                 "midtrans" => registry.register(Arc::new(MidtransPaymentPlugin::from_env())),
                 "xendit" => registry.register(Arc::new(XenditPaymentPlugin::from_env())),
+                "solana" => registry.register(Arc::new(SolanaPaymentPlugin::from_env())),
+                "lightning" => registry.register(Arc::new(LightningPaymentPlugin::from_env())),
                 "x402" => registry.register(Arc::new(X402PaymentPlugin::from_env_with_pool(
                     pool.clone(),
                 ))),
+                // Listing it is not enough; it must be enabled explicitly.
+                "mock" if MockPaymentPlugin::enabled() => {
+                    registry.register(Arc::new(MockPaymentPlugin::from_env()))
+                }
                 "" => {}
                 _ => tracing::warn!("unknown payment plugin configured: {}", provider),
             }
@@ -69,13 +77,18 @@ impl PaymentPluginRegistry {
     pub fn capabilities_from_env_with_pool(
         pool: Option<PgPool>,
     ) -> Vec<super::models::PaymentPluginCapability> {
-        let plugins: Vec<Arc<dyn PaymentPlugin>> = vec![
+        let mut plugins: Vec<Arc<dyn PaymentPlugin>> = vec![
             Arc::new(PaypalPaymentPlugin::from_env()),
             Arc::new(StripePaymentPlugin::from_env()),
             Arc::new(MidtransPaymentPlugin::from_env()),
             Arc::new(XenditPaymentPlugin::from_env()),
             Arc::new(X402PaymentPlugin::from_env_with_pool(pool)),
+            Arc::new(SolanaPaymentPlugin::from_env()),
+            Arc::new(LightningPaymentPlugin::from_env()),
         ];
+        if MockPaymentPlugin::enabled() {
+            plugins.push(Arc::new(MockPaymentPlugin::from_env()));
+        }
 
         plugins
             .into_iter()
@@ -90,6 +103,11 @@ impl PaymentPluginRegistry {
         registry.register(Arc::new(MidtransPaymentPlugin::from_env()));
         registry.register(Arc::new(XenditPaymentPlugin::from_env()));
         registry.register(Arc::new(X402PaymentPlugin::from_env_with_pool(pool)));
+        registry.register(Arc::new(SolanaPaymentPlugin::from_env()));
+        registry.register(Arc::new(LightningPaymentPlugin::from_env()));
+        if MockPaymentPlugin::enabled() {
+            registry.register(Arc::new(MockPaymentPlugin::from_env()));
+        }
         registry.default_provider = registry.names().first().cloned();
         registry
     }
@@ -112,6 +130,16 @@ impl PaymentPluginRegistry {
         if settings.x402_enabled {
             registry.register(Arc::new(X402PaymentPlugin::from_env_with_pool(pool)));
         }
+        if settings.solana_enabled {
+            registry.register(Arc::new(SolanaPaymentPlugin::from_env()));
+        }
+        if settings.lightning_enabled {
+            registry.register(Arc::new(LightningPaymentPlugin::from_env()));
+        }
+        // No admin toggle: the mock provider follows MOCK_PAYMENT_ENABLED only.
+        if MockPaymentPlugin::enabled() {
+            registry.register(Arc::new(MockPaymentPlugin::from_env()));
+        }
 
         registry.default_provider = settings
             .default_provider
@@ -147,3 +175,28 @@ impl PaymentPluginRegistry {
         names
     }
 }
+
+/// Creator payout methods, in the order they are offered.
+pub const PAYOUT_METHODS: [&str; 4] = ["xendit", "paypal", "stripe", "manual_bank"];
+
+/// The payout plugin behind a `creator_payout_settings.method`. The mock
+/// method only exists while the mock payment provider is enabled.
+pub fn payout_plugin(method: &str) -> Option<Arc<dyn PayoutPlugin>> {
+    match method {
+        "xendit" => Some(Arc::new(XenditPaymentPlugin::from_env())),
+        "paypal" => Some(Arc::new(PaypalPaymentPlugin::from_env())),
+        "stripe" => Some(Arc::new(StripePaymentPlugin::from_env())),
+        "manual_bank" => Some(Arc::new(ManualBankPayoutPlugin::from_env())),
+        "mock" if MockPaymentPlugin::enabled() => Some(Arc::new(MockPaymentPlugin::from_env())),
+        _ => None,
+    }
+}
+
+/// Every payout plugin available in this process.
+pub fn payout_plugins() -> Vec<Arc<dyn PayoutPlugin>> {
+    PAYOUT_METHODS
+        .into_iter()
+        .chain(std::iter::once("mock"))
+        .filter_map(payout_plugin)
+        .collect()
+}
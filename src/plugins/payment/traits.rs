@@ -6,10 +6,13 @@
 // application can call the trait without depending on PayPal, Stripe, Midtrans,
 // Xendit, or x402 implementation details.
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
 
 use super::models::{
     ConfirmPaymentRequest, CreateInvoiceRequest, Invoice, PaymentPluginCapability, PaymentResult,
+    PaymentStatusRequest, PayoutRequest, PayoutResult, RecurringChargeRequest, RefundRequest,
+    RefundResult,
 };
 
 #[async_trait::async_trait]
@@ -23,4 +26,78 @@ pub trait PaymentPlugin: Send + Sync {
     async fn create_invoice(&self, request: CreateInvoiceRequest) -> Result<Invoice>;
 
     async fn confirm_payment(&self, request: ConfirmPaymentRequest) -> Result<PaymentResult>;
+
+    /// The provider's id of a webhook event, so redelivered events can be
+    /// dropped. Read from the unverified payload; `None` falls back to a hash
+    /// of the body.
+    fn webhook_event_id(
+        &self,
+        payload: &serde_json::Value,
+        headers: &HashMap<String, String>,
+    ) -> Option<String> {
+        let _ = (payload, headers);
+        None
+    }
+
+    /// Charges a saved payment method without the buyer present (subscription
+    /// renewals). Only called when `capability().supports_recurring` is true.
+    async fn charge_recurring(&self, request: RecurringChargeRequest) -> Result<PaymentResult> {
+        let _ = request;
+        bail!(
+            "{}: recurring charges are not supported",
+            self.provider_key()
+        )
+    }
+
+    /// Returns all or part of a paid charge to the buyer. Only called when
+    /// `capability().supports_refunds` is true.
+    async fn refund(&self, request: RefundRequest) -> Result<RefundResult> {
+        let _ = request;
+        bail!("{}: refunds are not supported", self.provider_key())
+    }
+
+    /// Asks the provider for the current status of a checkout, for invoices
+    /// whose webhook never arrived. `Unknown` means the provider has no such
+    /// payment. Only called when `capability().supports_status_lookup` is true.
+    async fn payment_status(&self, request: PaymentStatusRequest) -> Result<PaymentResult> {
+        let _ = request;
+        bail!("{}: status lookups are not supported", self.provider_key())
+    }
+}
+
+/// Sends creator earnings out of the platform account. Implemented by the
+/// providers that can transfer money to a creator, plus the manual bank
+/// export. See `handlers::payouts` for the engine that drives it.
+#[async_trait::async_trait]
+pub trait PayoutPlugin: Send + Sync {
+    /// Value of `creator_payout_settings.method`.
+    fn payout_method(&self) -> &'static str;
+
+    fn payout_label(&self) -> &'static str;
+
+    fn payout_configured(&self) -> bool;
+
+    /// Currency the transfer is made in; payouts are converted from USD.
+    fn payout_currency(&self) -> String;
+
+    /// Checks the creator's destination (bank account, email, account id)
+    /// when they save their payout settings.
+    fn validate_destination(&self, destination: &str) -> Result<()>;
+
+    /// Placeholder shown in the creator's payout form.
+    fn destination_hint(&self) -> &'static str;
+
+    /// `Failed` means the provider did not make the transfer and it can be
+    /// sent again under a new key. An error means the outcome is unknown (the
+    /// request may have reached the provider); it is not sent again blindly.
+    async fn send_payout(&self, request: PayoutRequest) -> Result<PayoutResult>;
+
+    /// Asks the provider about a transfer that was accepted as `Processing`.
+    async fn payout_status(&self, provider_ref: &str) -> Result<PayoutResult> {
+        let _ = provider_ref;
+        bail!(
+            "{}: payout status lookups are not supported",
+            self.payout_method()
+        )
+    }
 }
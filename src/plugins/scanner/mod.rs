@@ -0,0 +1,21 @@
+// src/plugins/scanner/mod.rs
+//
+// Content scanner plugin system — an optional malware/abuse scanner hook that
+// can veto an upload while it is still in quarantine (before it is renamed to
+// its final name and handed to the transcoder).
+//
+// Usage:
+//   let registry = ScannerRegistry::from_env();
+//   let scanner  = registry.plugin();   // Option<Arc<dyn ContentScanner>>
+//
+// Configuration:
+//   CONTENT_SCANNER=none     — no scanner (default)
+//   CONTENT_SCANNER=clamav   — clamd over TCP or a unix socket (CLAMAV_* vars)
+//   CONTENT_SCANNER=stub     — local signature stub, for development and tests
+
+pub mod providers;
+pub mod registry;
+pub mod traits;
+
+pub use registry::ScannerRegistry;
+pub use traits::{ContentScanner, ScanVerdict};
@@ -0,0 +1,174 @@
+// src/plugins/scanner/providers/clamav.rs
+//
+// ClamAvScanner — streams the quarantined file to clamd using the INSTREAM
+// command, so clamd does not need read access to the upload directory.
+//
+// Optional env vars:
+//   CLAMAV_SOCKET        unix socket path (e.g. /var/run/clamav/clamd.ctl); wins over CLAMAV_ADDR
+//   CLAMAV_ADDR          host:port of clamd's TCP listener (default: 127.0.0.1:3310)
+//   CLAMAV_TIMEOUT_SECS  whole-scan timeout in seconds (default: 120)
+//
+// clamd caps INSTREAM at StreamMaxLength (25 MB by default); raise it in
+// clamd.conf to at least MAX_UPLOAD_BYTES or every large upload is reported
+// as a scanner error.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::plugins::scanner::traits::{ContentScanner, ScanVerdict};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct ClamAvScanner {
+    socket_path: Option<String>,
+    addr: String,
+    timeout: Duration,
+}
+
+impl ClamAvScanner {
+    pub fn new(socket_path: Option<String>, addr: String, timeout: Duration) -> Self {
+        Self {
+            socket_path,
+            addr,
+            timeout,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let socket_path = std::env::var("CLAMAV_SOCKET")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+        let addr = std::env::var("CLAMAV_ADDR").unwrap_or_else(|_| "127.0.0.1:3310".into());
+        let timeout_secs = std::env::var("CLAMAV_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(120);
+        Self::new(socket_path, addr, Duration::from_secs(timeout_secs))
+    }
+
+    pub fn endpoint_display(&self) -> String {
+        match &self.socket_path {
+            Some(path) => format!("unix:{path}"),
+            None => format!("tcp:{}", self.addr),
+        }
+    }
+
+    async fn scan_inner(&self, path: &Path) -> Result<ScanVerdict> {
+        #[cfg(unix)]
+        if let Some(socket_path) = &self.socket_path {
+            let stream = tokio::net::UnixStream::connect(socket_path)
+                .await
+                .with_context(|| format!("connect clamd unix:{socket_path}"))?;
+            return instream(stream, path).await;
+        }
+
+        let stream = tokio::net::TcpStream::connect(&self.addr)
+            .await
+            .with_context(|| format!("connect clamd tcp:{}", self.addr))?;
+        instream(stream, path).await
+    }
+}
+
+/// Runs one `zINSTREAM` exchange: length-prefixed chunks, a zero-length
+/// terminator, then a single NUL-terminated reply line.
+async fn instream<S>(mut stream: S, path: &Path) -> Result<ScanVerdict>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("open {}", path.display()))?;
+
+    stream.write_all(b"zINSTREAM\0").await?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&(n as u32).to_be_bytes()).await?;
+        stream.write_all(&buf[..n]).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+    stream.flush().await?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await?;
+    parse_reply(&reply)
+}
+
+/// Parses clamd replies such as `stream: OK` and
+/// `stream: Eicar-Signature FOUND`.
+fn parse_reply(reply: &[u8]) -> Result<ScanVerdict> {
+    let text = String::from_utf8_lossy(reply);
+    let text = text.trim_end_matches(['\0', '\n']).trim();
+    let body = text.strip_prefix("stream:").unwrap_or(text).trim();
+
+    if body == "OK" {
+        return Ok(ScanVerdict::Clean);
+    }
+    if let Some(signature) = body.strip_suffix("FOUND") {
+        return Ok(ScanVerdict::Infected(signature.trim().to_string()));
+    }
+    if body.ends_with("ERROR") {
+        bail!("clamd error: {body}");
+    }
+    Err(anyhow!("unexpected clamd reply: {text}"))
+}
+
+#[async_trait]
+impl ContentScanner for ClamAvScanner {
+    fn scanner_name(&self) -> &'static str {
+        "clamav"
+    }
+
+    async fn scan_file(&self, path: &Path) -> Result<ScanVerdict> {
+        tokio::time::timeout(self.timeout, self.scan_inner(path))
+            .await
+            .map_err(|_| anyhow!("clamd scan timed out after {:?}", self.timeout))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_clamd_replies() {
+        assert_eq!(parse_reply(b"stream: OK\0").unwrap(), ScanVerdict::Clean);
+        assert_eq!(
+            parse_reply(b"stream: Eicar-Signature FOUND\0").unwrap(),
+            ScanVerdict::Infected("Eicar-Signature".into())
+        );
+        assert!(parse_reply(b"INSTREAM size limit exceeded. ERROR\0").is_err());
+    }
+
+    #[tokio::test]
+    async fn instream_frames_the_file() {
+        let dir = std::env::temp_dir().join(format!("clamav-test-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("sample.bin");
+        tokio::fs::write(&path, b"hello").await.unwrap();
+
+        let (client, mut server) = tokio::io::duplex(1024);
+        let fake_clamd = tokio::spawn(async move {
+            let mut received = vec![0u8; 10 + 4 + 5 + 4];
+            server.read_exact(&mut received).await.unwrap();
+            server.write_all(b"stream: OK\0").await.unwrap();
+            received
+        });
+
+        let verdict = instream(client, &path).await.unwrap();
+        let received = fake_clamd.await.unwrap();
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+
+        assert_eq!(verdict, ScanVerdict::Clean);
+        assert_eq!(&received[..10], b"zINSTREAM\0");
+        assert_eq!(&received[10..14], &5u32.to_be_bytes());
+        assert_eq!(&received[14..19], b"hello");
+        assert_eq!(&received[19..], &[0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,13 @@
+// src/plugins/scanner/providers/mod.rs
+//
+// Built-in content scanners:
+//   clamav — clamd INSTREAM protocol over TCP or a unix socket (CONTENT_SCANNER=clamav)
+//   stub   — in-process signature matcher for development and tests (CONTENT_SCANNER=stub)
+//
+// Adding a new scanner:
+//   1. Create providers/<name>.rs implementing ContentScanner
+//   2. Add `pub mod <name>;` here
+//   3. Handle the "<name>" match arm in ScannerRegistry::from_env()
+
+pub mod clamav;
+pub mod stub;
@@ -0,0 +1,56 @@
+// src/plugins/scanner/providers/stub.rs
+//
+// StubScanner — in-process stand-in for a real scanner. It flags files that
+// contain the EICAR anti-malware test string, which lets development setups
+// and tests exercise the quarantine veto path without running clamd.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::Path;
+
+use crate::plugins::scanner::traits::{ContentScanner, ScanVerdict};
+
+/// The standard EICAR test file body.
+pub const EICAR_TEST_STRING: &[u8] =
+    b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+pub struct StubScanner {
+    markers: Vec<(String, Vec<u8>)>,
+}
+
+impl StubScanner {
+    pub fn new() -> Self {
+        Self {
+            markers: vec![("Eicar-Test-Signature".into(), EICAR_TEST_STRING.to_vec())],
+        }
+    }
+}
+
+impl Default for StubScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ContentScanner for StubScanner {
+    fn scanner_name(&self) -> &'static str {
+        "stub"
+    }
+
+    async fn scan_file(&self, path: &Path) -> Result<ScanVerdict> {
+        let bytes = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("stub scan read {}", path.display()))?;
+        for (name, pattern) in &self.markers {
+            if !pattern.is_empty()
+                && bytes
+                    .windows(pattern.len())
+                    .any(|w| w == pattern.as_slice())
+            {
+                return Ok(ScanVerdict::Infected(name.clone()));
+            }
+        }
+        Ok(ScanVerdict::Clean)
+    }
+}
@@ -0,0 +1,62 @@
+// src/plugins/scanner/registry.rs
+//
+// ScannerRegistry reads CONTENT_SCANNER from the environment and constructs
+// the matching ContentScanner. Unlike storage there is no safe fallback
+// scanner, and a typo must not quietly turn the malware gate off, so an
+// unknown value stops startup.
+//
+// Env vars:
+//   CONTENT_SCANNER            "none" (default) | "clamav" | "stub"
+//   CONTENT_SCANNER_FAIL_OPEN  "true" to accept uploads when the scanner errors
+//                              (default: false — scanner errors reject the upload)
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+
+use crate::plugins::scanner::{
+    providers::{clamav::ClamAvScanner, stub::StubScanner},
+    traits::ContentScanner,
+};
+
+pub struct ScannerRegistry {
+    scanner: Option<Arc<dyn ContentScanner>>,
+    fail_open: bool,
+}
+
+impl ScannerRegistry {
+    pub fn from_env() -> Result<Self> {
+        let name = std::env::var("CONTENT_SCANNER")
+            .unwrap_or_else(|_| "none".into())
+            .to_lowercase();
+        let fail_open = std::env::var("CONTENT_SCANNER_FAIL_OPEN")
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false);
+
+        let scanner: Option<Arc<dyn ContentScanner>> = match name.as_str() {
+            "" | "none" | "off" => None,
+            "clamav" | "clamd" => {
+                let p = ClamAvScanner::from_env();
+                tracing::info!("content scanner: clamav ({})", p.endpoint_display());
+                Some(Arc::new(p))
+            }
+            "stub" => {
+                tracing::info!("content scanner: stub");
+                Some(Arc::new(StubScanner::new()))
+            }
+            other => bail!("unknown CONTENT_SCANNER={other} (expected none, clamav or stub)"),
+        };
+
+        Ok(Self { scanner, fail_open })
+    }
+
+    /// Return a clone of the shared scanner handle, if one is configured.
+    pub fn plugin(&self) -> Option<Arc<dyn ContentScanner>> {
+        self.scanner.clone()
+    }
+
+    /// Whether scanner errors should let the upload through.
+    pub fn fail_open(&self) -> bool {
+        self.fail_open
+    }
+}
@@ -0,0 +1,27 @@
+// src/plugins/scanner/traits.rs
+//
+// ContentScanner trait: abstraction over external malware scanners such as
+// ClamAV, plus a local stub for tests.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+
+/// Outcome of scanning one quarantined file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    /// The scanner found nothing.
+    Clean,
+    /// The scanner matched a signature; carries the signature name.
+    Infected(String),
+}
+
+#[async_trait]
+pub trait ContentScanner: Send + Sync {
+    /// Short identifier, e.g. "clamav", "stub".
+    fn scanner_name(&self) -> &'static str;
+
+    /// Scan the file at `path`. An `Err` means the scanner itself failed
+    /// (unreachable, size limit, protocol error) — not that the file is bad.
+    async fn scan_file(&self, path: &Path) -> Result<ScanVerdict>;
+}
@@ -0,0 +1,510 @@
+// src/quarantine.rs
+//
+// Upload quarantine stage.
+//
+// Every upload (multipart or bulk import) is written to a `.part` file first.
+// While it still has that name it is "in quarantine": nothing references it,
+// the transcoder has not seen it, and it is deleted if any check below fails.
+// Only after `Quarantine::inspect` succeeds is it renamed and registered, with
+// `processing_state = 'quarantined'` until the decode check has passed.
+//
+// Checks, cheapest first:
+// 1. Non-empty file whose leading bytes sniff as a video MIME type.
+// 2. Polyglot markers: HTML/script/PHP/PDF text in the head, a ZIP central
+//    directory or PDF trailer in the tail.
+// 3. For MP4/MOV, a walk of the top-level boxes: a box running past EOF means
+//    the file is truncated, bytes after the last box mean something was
+//    appended to it.
+// 4. The optional content scanner plugin (ClamAV, stub) — see plugins::scanner.
+// 5. FFprobe: the container must parse, contain a video stream, and match the
+//    claimed extension.
+// 6. A full FFmpeg decode into the null muxer; any decode error rejects.
+//
+// Steps 1-5 run inside the upload request (`inspect`). Step 6 can take as
+// long as the video itself, so the transcode worker runs it (`verify_decode`)
+// before anything else; a rejected video is marked `rejected` and its source
+// is deleted. Steps 5 and 6 can be disabled with QUARANTINE_DECODE_CHECK=false
+// for hosts without FFmpeg (the transcoder still needs it, so production
+// should not).
+
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::{
+    config::Config,
+    ffmpeg,
+    plugins::scanner::{ContentScanner, ScanVerdict, ScannerRegistry},
+};
+
+const HEAD_BYTES: usize = 8192;
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// Why a quarantined file was refused. `code` is stable and machine-readable;
+/// `reason` is the precise human explanation returned to the uploader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub code: &'static str,
+    pub reason: String,
+}
+
+impl Rejection {
+    fn new(code: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+
+    /// True when the file was not judged at all because the scanner failed.
+    pub fn is_scanner_failure(&self) -> bool {
+        self.code == "scanner_unavailable"
+    }
+
+    /// True when FFmpeg could not be started, so the decode was never tried.
+    pub fn is_decoder_failure(&self) -> bool {
+        self.code == "decoder_unavailable"
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.reason)
+    }
+}
+
+#[derive(Clone)]
+pub struct Quarantine {
+    decode_check: bool,
+    decode_timeout: Duration,
+    scanner: Option<Arc<dyn ContentScanner>>,
+    scanner_fail_open: bool,
+}
+
+impl Quarantine {
+    pub fn new(cfg: &Config, scanners: &ScannerRegistry) -> Self {
+        Self {
+            decode_check: cfg.quarantine_decode_check,
+            decode_timeout: Duration::from_secs(cfg.quarantine_decode_timeout_secs),
+            scanner: scanners.plugin(),
+            scanner_fail_open: scanners.fail_open(),
+        }
+    }
+
+    /// Runs the request-time quarantine checks against `path`, which the
+    /// uploader claims is a `.{extension}` file. The caller deletes the file
+    /// on rejection. The full decode is left to `verify_decode`.
+    pub async fn inspect(&self, path: &Path, extension: &str) -> Result<(), Rejection> {
+        let (head, tail, len) = read_head_and_tail(path)
+            .await
+            .map_err(|e| Rejection::new("io_error", format!("read quarantined file: {e}")))?;
+        if len == 0 {
+            return Err(Rejection::new("empty", "empty file"));
+        }
+
+        check_video_signature(&head).map_err(|reason| Rejection::new("not_video", reason))?;
+        check_embedded_markers(&head, &tail)?;
+
+        if is_isobmff_extension(extension) {
+            let owned: PathBuf = path.to_path_buf();
+            tokio::task::spawn_blocking(move || {
+                let mut file = std::fs::File::open(&owned)
+                    .map_err(|e| Rejection::new("io_error", format!("open: {e}")))?;
+                check_isobmff_layout(&mut file, len)
+            })
+            .await
+            .map_err(|e| Rejection::new("io_error", format!("layout check task: {e}")))??;
+        }
+
+        if let Some(scanner) = &self.scanner {
+            match scanner.scan_file(path).await {
+                Ok(ScanVerdict::Clean) => {}
+                Ok(ScanVerdict::Infected(signature)) => {
+                    return Err(Rejection::new(
+                        "infected",
+                        format!(
+                            "{} scanner flagged the file: {signature}",
+                            scanner.scanner_name()
+                        ),
+                    ));
+                }
+                Err(e) if self.scanner_fail_open => {
+                    tracing::warn!(
+                        scanner = scanner.scanner_name(),
+                        "content scanner failed, accepting upload (fail-open): {e}"
+                    );
+                }
+                Err(e) => {
+                    return Err(Rejection::new(
+                        "scanner_unavailable",
+                        format!("{} scanner failed: {e}", scanner.scanner_name()),
+                    ));
+                }
+            }
+        }
+
+        if self.decode_check {
+            let input = path.to_string_lossy().to_string();
+            let summary = ffmpeg::ffprobe_summary(&input)
+                .await
+                .map_err(|e| classify_ffmpeg_error("container could not be parsed", &e))?;
+            if summary.video_streams == 0 {
+                return Err(Rejection::new(
+                    "no_video_stream",
+                    format!("container {} holds no video stream", summary.format_name),
+                ));
+            }
+            check_container_matches_extension(&summary.format_name, extension)?;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes every frame of an already registered upload into the null
+    /// muxer. Called by the transcode worker, never inside a request.
+    pub async fn verify_decode(&self, path: &Path) -> Result<(), Rejection> {
+        if !self.decode_check {
+            return Ok(());
+        }
+        let input = path.to_string_lossy().to_string();
+        ffmpeg::ffmpeg_decode_check(&input, self.decode_timeout)
+            .await
+            .map_err(|e| classify_ffmpeg_error("full decode failed", &e))
+    }
+}
+
+/// Sniffs the leading bytes of a file and rejects anything that is not a
+/// recognised video container.
+pub fn check_video_signature(buffer: &[u8]) -> Result<(), String> {
+    let Some(kind) = infer::get(buffer) else {
+        return Err("unable to detect a supported video MIME type".to_string());
+    };
+    if !kind.mime_type().starts_with("video/") {
+        return Err(format!(
+            "uploaded content is not a video: {}",
+            kind.mime_type()
+        ));
+    }
+    Ok(())
+}
+
+async fn read_head_and_tail(path: &Path) -> std::io::Result<(Vec<u8>, Vec<u8>, u64)> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+
+    let mut head = vec![0u8; HEAD_BYTES.min(len as usize)];
+    file.read_exact(&mut head).await?;
+
+    let tail_len = TAIL_BYTES.min(len);
+    file.seek(SeekFrom::Start(len - tail_len)).await?;
+    let mut tail = vec![0u8; tail_len as usize];
+    file.read_exact(&mut tail).await?;
+
+    Ok((head, tail, len))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Rejects files that are also valid documents of another type: markup or
+/// scripts in the head, or an archive/PDF trailer appended to the tail.
+fn check_embedded_markers(head: &[u8], tail: &[u8]) -> Result<(), Rejection> {
+    let lowered = head.to_ascii_lowercase();
+    for (marker, label) in [
+        (&b"<html"[..], "HTML document"),
+        (b"<script", "script tag"),
+        (b"<?php", "PHP code"),
+        (b"%pdf-", "PDF header"),
+    ] {
+        if contains(&lowered, marker) {
+            return Err(Rejection::new(
+                "polyglot",
+                format!("{label} embedded in the first {} bytes", head.len()),
+            ));
+        }
+    }
+    if has_zip_end_record(tail) {
+        return Err(Rejection::new(
+            "polyglot",
+            "ZIP central directory found at the end of the file",
+        ));
+    }
+    if contains(tail, b"%%EOF") {
+        return Err(Rejection::new(
+            "polyglot",
+            "PDF trailer found at the end of the file",
+        ));
+    }
+    Ok(())
+}
+
+/// A ZIP end-of-central-directory record whose comment runs exactly to EOF,
+/// i.e. the file is also a readable ZIP archive.
+fn has_zip_end_record(tail: &[u8]) -> bool {
+    tail.windows(4)
+        .enumerate()
+        .filter(|(_, w)| *w == b"PK\x05\x06")
+        .any(|(pos, _)| {
+            let Some(comment) = tail.get(pos + 20..pos + 22) else {
+                return false;
+            };
+            let comment_len = u16::from_le_bytes([comment[0], comment[1]]) as usize;
+            pos + 22 + comment_len == tail.len()
+        })
+}
+
+fn is_isobmff_extension(extension: &str) -> bool {
+    matches!(extension, "mp4" | "m4v" | "mov" | "3gp")
+}
+
+/// Top-level box types seen in real MP4/MOV/fragmented-MP4 files. Anything
+/// else at the top level is treated as foreign data rather than a box.
+const TOP_LEVEL_BOXES: &[&[u8; 4]] = &[
+    b"ftyp", b"styp", b"moov", b"mdat", b"moof", b"mfra", b"sidx", b"ssix", b"free", b"skip",
+    b"wide", b"uuid", b"meta", b"pdin", b"emsg", b"prft", b"pnot", b"junk",
+];
+
+fn is_top_level_box(kind: &[u8]) -> bool {
+    TOP_LEVEL_BOXES.iter().any(|known| known.as_slice() == kind)
+}
+
+/// Walks the top-level ISO-BMFF boxes of an MP4/MOV file. The boxes must tile
+/// the file exactly: a box running past EOF means truncation, anything after
+/// the last box means foreign data was appended.
+fn check_isobmff_layout<R: Read + Seek>(reader: &mut R, len: u64) -> Result<(), Rejection> {
+    let io = |e: std::io::Error| Rejection::new("io_error", format!("read box header: {e}"));
+    let mut offset = 0u64;
+    let mut boxes = 0usize;
+
+    while offset < len {
+        let remaining = len - offset;
+        if remaining < 8 {
+            return Err(Rejection::new(
+                "polyglot",
+                format!(
+                    "{remaining} bytes of trailing data after the last MP4 box at offset {offset}"
+                ),
+            ));
+        }
+
+        reader.seek(SeekFrom::Start(offset)).map_err(io)?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).map_err(io)?;
+        let kind = &header[4..8];
+        if !is_top_level_box(kind) {
+            return Err(if boxes == 0 {
+                Rejection::new("corrupt", "file does not start with an MP4 box")
+            } else {
+                Rejection::new(
+                    "polyglot",
+                    format!("{remaining} bytes of non-MP4 data appended at offset {offset}"),
+                )
+            });
+        }
+        let name = String::from_utf8_lossy(kind).to_string();
+
+        let mut header_len = 8u64;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        if size == 1 {
+            if remaining < 16 {
+                return Err(Rejection::new(
+                    "truncated",
+                    format!("box '{name}' at offset {offset} is cut off inside its header"),
+                ));
+            }
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large).map_err(io)?;
+            size = u64::from_be_bytes(large);
+            header_len = 16;
+        } else if size == 0 {
+            size = remaining;
+        }
+
+        if size < header_len {
+            return Err(Rejection::new(
+                "corrupt",
+                format!("box '{name}' at offset {offset} has invalid size {size}"),
+            ));
+        }
+        if size > remaining {
+            return Err(Rejection::new(
+                "truncated",
+                format!(
+                    "box '{name}' at offset {offset} declares {size} bytes but only {remaining} remain"
+                ),
+            ));
+        }
+
+        offset += size;
+        boxes += 1;
+    }
+
+    Ok(())
+}
+
+/// FFprobe demuxer names that are acceptable for a claimed extension.
+/// Extensions not listed here are not cross-checked.
+fn expected_demuxers(extension: &str) -> Option<&'static [&'static str]> {
+    Some(match extension {
+        "mp4" | "m4v" | "mov" | "3gp" => &["mov", "mp4"],
+        "mkv" | "webm" => &["matroska", "webm"],
+        "avi" => &["avi"],
+        "flv" => &["flv"],
+        "ts" | "m2ts" => &["mpegts"],
+        "mpg" | "mpeg" => &["mpeg"],
+        "ogv" => &["ogg"],
+        "wmv" | "asf" => &["asf"],
+        _ => return None,
+    })
+}
+
+fn check_container_matches_extension(format_name: &str, extension: &str) -> Result<(), Rejection> {
+    let Some(expected) = expected_demuxers(extension) else {
+        return Ok(());
+    };
+    if format_name.split(',').any(|name| expected.contains(&name)) {
+        return Ok(());
+    }
+    Err(Rejection::new(
+        "container_mismatch",
+        format!("file claims to be .{extension} but FFprobe reads it as {format_name}"),
+    ))
+}
+
+/// Maps FFmpeg/FFprobe diagnostics onto `truncated` or `corrupt`.
+fn classify_ffmpeg_error(context: &str, err: &anyhow::Error) -> Rejection {
+    let message = err.to_string();
+    let lowered = message.to_ascii_lowercase();
+    let code = if lowered.starts_with("spawn ") {
+        "decoder_unavailable"
+    } else if lowered.contains("did not finish within") {
+        "decode_timeout"
+    } else if [
+        "moov atom not found",
+        "partial file",
+        "truncat",
+        "end of file",
+        "premature",
+    ]
+    .iter()
+    .any(|needle| lowered.contains(needle))
+    {
+        "truncated"
+    } else {
+        "corrupt"
+    };
+    Rejection::new(code, format!("{context}: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::scanner::providers::stub::{StubScanner, EICAR_TEST_STRING};
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn minimal_mp4(mdat: &[u8]) -> Vec<u8> {
+        let mut file = mp4_box(b"ftyp", b"isom\0\0\x02\0isomiso2mp41");
+        file.extend(mp4_box(b"mdat", mdat));
+        file
+    }
+
+    fn layout(bytes: &[u8]) -> Result<(), Rejection> {
+        check_isobmff_layout(&mut Cursor::new(bytes), bytes.len() as u64)
+    }
+
+    #[test]
+    fn well_formed_boxes_pass() {
+        assert_eq!(layout(&minimal_mp4(b"frames")), Ok(()));
+    }
+
+    #[test]
+    fn appended_archive_is_polyglot() {
+        let mut file = minimal_mp4(b"frames");
+        file.extend_from_slice(b"PK\x03\x04 appended zip payload");
+        assert_eq!(layout(&file).unwrap_err().code, "polyglot");
+
+        let mut tail = b"...PK\x05\x06".to_vec();
+        tail.extend_from_slice(&[0u8; 18]);
+        assert_eq!(
+            check_embedded_markers(b"", &tail).unwrap_err().code,
+            "polyglot"
+        );
+    }
+
+    #[test]
+    fn box_past_eof_is_truncated() {
+        let mut file = minimal_mp4(b"frames that were cut off");
+        file.truncate(file.len() - 10);
+        let rejection = layout(&file).unwrap_err();
+        assert_eq!(rejection.code, "truncated");
+        assert!(rejection.reason.contains("'mdat'"));
+    }
+
+    #[test]
+    fn markup_in_head_is_polyglot() {
+        let head = b"\0\0\0\x18ftypisom<HTML><body>";
+        assert_eq!(
+            check_embedded_markers(head, b"").unwrap_err().code,
+            "polyglot"
+        );
+    }
+
+    #[test]
+    fn container_must_match_extension() {
+        assert!(check_container_matches_extension("mov,mp4,m4a,3gp,3g2,mj2", "mp4").is_ok());
+        assert!(check_container_matches_extension("matroska,webm", "webm").is_ok());
+        assert_eq!(
+            check_container_matches_extension("matroska,webm", "mp4")
+                .unwrap_err()
+                .code,
+            "container_mismatch"
+        );
+    }
+
+    #[test]
+    fn ffmpeg_errors_are_classified() {
+        let truncated = anyhow::anyhow!("moov atom not found");
+        assert_eq!(classify_ffmpeg_error("probe", &truncated).code, "truncated");
+        let corrupt = anyhow::anyhow!("Invalid NAL unit size");
+        assert_eq!(classify_ffmpeg_error("decode", &corrupt).code, "corrupt");
+    }
+
+    #[tokio::test]
+    async fn scanner_hook_vetoes_upload() {
+        let dir = std::env::temp_dir().join(format!("quarantine-test-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let clean = dir.join("clean.mp4.part");
+        let infected = dir.join("infected.mp4.part");
+        tokio::fs::write(&clean, minimal_mp4(b"frames"))
+            .await
+            .unwrap();
+        tokio::fs::write(&infected, minimal_mp4(EICAR_TEST_STRING))
+            .await
+            .unwrap();
+
+        let quarantine = Quarantine {
+            decode_check: false,
+            decode_timeout: Duration::from_secs(1),
+            scanner: Some(Arc::new(StubScanner::new())),
+            scanner_fail_open: false,
+        };
+        let clean_result = quarantine.inspect(&clean, "mp4").await;
+        let infected_result = quarantine.inspect(&infected, "mp4").await;
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+
+        assert_eq!(clean_result, Ok(()));
+        assert_eq!(infected_result.unwrap_err().code, "infected");
+    }
+}
@@ -1,14 +1,39 @@
 // src/services/x402_watcher.rs
-// src/services/x402_watcher.rs
-use anyhow::Result;
+//
+// Watcher of X402Splitter `Paid` logs, one task per chain in `x402_chains`.
+//
+// Each pass scans `eth_getLogs` from the last scanned block (saved in
+// `x402_watcher_state`) up to the head, records every log in
+// `x402_chain_events`, and applies the ones with the chain's number of
+// confirmations whose block is still canonical. Logs emitted while the watcher
+// was down are picked up by the next scan.
+//
+// The last X402_REORG_DEPTH blocks are rescanned on every pass, and the blocks
+// of recorded logs in that window are compared with the canonical chain. A log
+// whose transaction is no longer included is marked `reorged`; if it had been
+// applied, the invoice goes back to `pending` and its entitlement, sale and
+// affiliate commission are rolled back. A transaction that is mined again
+// later is picked up and applied again.
+use anyhow::{bail, Result};
+use ethers::contract::parse_log;
 use ethers::prelude::*;
-use ethers::providers::{Provider, Ws};
+use ethers::providers::{Http, Provider, Ws};
 use hex;
 use sqlx::{PgPool, Row}; // Row diperlukan untuk row.get::<T,_>()
+use std::collections::HashMap;
 use std::time::Duration;
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use tokio_stream::StreamExt;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::entitlements::{self, Origin, Revoke, Source};
+use crate::x402_chains::{load_chains, X402Chain};
+
+/// Blocks per `eth_getLogs` request; public RPCs commonly cap the range.
+const LOG_CHUNK_BLOCKS: u64 = 2000;
+/// How often the chain list is reloaded for added, edited or removed chains.
+const CHAIN_RELOAD_SECS: u64 = 60;
 
 // Pakai JSON ABI agar parser stabil
 abigen!(
@@ -29,130 +54,574 @@ abigen!(
     }]"#
 );
 
-/// Jalankan watcher X402 dengan auto-reconnect.
-pub async fn run_watcher(pool: PgPool, wss_url: String, contract_addr: Address) -> Result<()> {
-    info!("🚀 X402 watcher initialized, WSS={}", wss_url);
+/// Keeps one watcher task per active x402 chain.
+///
+/// The chain list is reloaded every `CHAIN_RELOAD_SECS`: watchers of removed
+/// or edited chains are stopped, and new or edited chains get a fresh one.
+pub async fn run_watchers(pool: PgPool, cfg: Config) -> Result<()> {
+    let mut running: HashMap<i64, (X402Chain, JoinHandle<()>)> = HashMap::new();
+    loop {
+        match load_chains(&pool).await {
+            Ok(chains) => {
+                running.retain(|chain_id, (known, handle)| {
+                    let keep = chains.iter().any(|c| c == known);
+                    if !keep {
+                        info!("🛑 Stopping x402 watcher for chain {}", chain_id);
+                        handle.abort();
+                    }
+                    keep
+                });
+                for chain in chains {
+                    if running.contains_key(&chain.chain_id) {
+                        continue;
+                    }
+                    let handle =
+                        tokio::spawn(run_watcher(pool.clone(), cfg.clone(), chain.clone()));
+                    running.insert(chain.chain_id, (chain, handle));
+                }
+            }
+            Err(e) => error!("💥 Loading x402 chains failed: {e}"),
+        }
+        sleep(Duration::from_secs(CHAIN_RELOAD_SECS)).await;
+    }
+}
+
+/// Jalankan watcher X402 untuk satu chain dengan auto-reconnect.
+///
+/// The chain's watch endpoint may be a WebSocket (`ws://`, `wss://`) or HTTP
+/// endpoint.
+async fn run_watcher(pool: PgPool, cfg: Config, chain: X402Chain) {
+    info!(
+        "🚀 X402 watcher initialized for {} (chain {}), RPC={}",
+        chain.name,
+        chain.chain_id,
+        chain.watch_url()
+    );
 
     loop {
-        match watch_once(&pool, &wss_url, contract_addr).await {
+        match watch_once(&pool, &cfg, &chain).await {
             Ok(_) => info!("✅ Watcher stopped gracefully, restarting in 10s..."),
-            Err(e) => error!("💥 Watcher error: {e}, reconnecting in 10s..."),
+            Err(e) => error!(
+                "💥 Watcher error on chain {}: {e}, reconnecting in 10s...",
+                chain.chain_id
+            ),
         }
         sleep(Duration::from_secs(10)).await;
     }
 }
 
-async fn watch_once(pool: &PgPool, wss_url: &str, contract_addr: Address) -> Result<()> {
-    let ws = Ws::connect(wss_url).await?;
-    let provider = Provider::new(ws);
-    let client = std::sync::Arc::new(provider);
-    let contract = X402Splitter::new(contract_addr, client);
-
-    let event_builder = contract.event::<PaidFilter>();
-    let mut stream = event_builder.stream().await?;
-    info!("🎧 Listening for Paid(...) events on {}", contract_addr);
-
-    while let Some(event) = stream.next().await {
-        match event {
-            Ok(ev) => {
-                // Normalisasi hash: 0x + lowercase
-                let bytes: [u8; 32] = ev.invoice_uid; // H256 -> [u8;32]
-                let invoice_hash = format!("0x{}", hex::encode(bytes));
-                let payer = format!("{:?}", ev.payer); // atau ev.payer.encode_hex::<String>()
-                let video_id = ev.video_id.clone();
-
-                info!(
-                    "💰 Paid: hash={}, payer={}, video_id={}",
-                    invoice_hash, payer, video_id
-                );
-
-                if let Err(e) = handle_paid_event(pool, &invoice_hash, &payer, &video_id).await {
-                    error!("⚠️ handle_paid_event error: {}", e);
-                }
-            }
-            Err(e) => {
-                error!("❌ Event stream error: {}", e);
-                break; // trigger reconnect
+async fn watch_once(pool: &PgPool, cfg: &Config, chain: &X402Chain) -> Result<()> {
+    let rpc_url = chain.watch_url();
+    if rpc_url.starts_with("ws://") || rpc_url.starts_with("wss://") {
+        let provider = Provider::<Ws>::connect(rpc_url).await?;
+        follow(pool, cfg, &provider, chain).await
+    } else {
+        let provider = Provider::<Http>::try_from(rpc_url)?;
+        follow(pool, cfg, &provider, chain).await
+    }
+}
+
+/// Runs passes until an RPC or database error, which triggers a reconnect.
+async fn follow<M>(pool: &PgPool, cfg: &Config, provider: &M, chain: &X402Chain) -> Result<()>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    let rpc_chain_id = provider.get_chainid().await?.as_u64();
+    if rpc_chain_id != chain.chain_id as u64 {
+        bail!(
+            "RPC of chain {} reports chain id {}",
+            chain.chain_id,
+            rpc_chain_id
+        );
+    }
+    let watched = Watched {
+        chain_id: rpc_chain_id,
+        contract: &chain.contract,
+        address: chain.contract.parse()?,
+        confirmations: chain.confirmations,
+        reorg_depth: cfg.x402_reorg_depth.max(chain.confirmations),
+        start_block: chain.start_block,
+    };
+    info!(
+        "🎧 Scanning Paid(...) logs on {} (chain {}, {} confirmations)",
+        watched.contract, watched.chain_id, watched.confirmations
+    );
+
+    let mut tick = tokio::time::interval(Duration::from_secs(cfg.x402_watch_interval_secs));
+    loop {
+        tick.tick().await;
+        run_pass(pool, provider, &watched).await?;
+    }
+}
+
+struct Watched<'a> {
+    chain_id: u64,
+    /// Lowercase 0x-hex, as stored.
+    contract: &'a str,
+    address: Address,
+    confirmations: u64,
+    reorg_depth: u64,
+    start_block: Option<u64>,
+}
+
+/// One pass: reorg check, log scan, then the confirmed logs.
+async fn run_pass<M>(pool: &PgPool, provider: &M, w: &Watched<'_>) -> Result<()>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    let head = provider.get_block_number().await?.as_u64();
+    let window_start = head.saturating_sub(w.reorg_depth);
+
+    check_reorgs(pool, provider, w, window_start).await?;
+
+    let last_block: Option<i64> = sqlx::query_scalar(
+        "SELECT last_block FROM x402_watcher_state WHERE chain_id = $1 AND contract_address = $2",
+    )
+    .bind(w.chain_id as i64)
+    .bind(w.contract)
+    .fetch_optional(pool)
+    .await?;
+    let mut from = scan_start(
+        last_block.map(|b| b.max(0) as u64),
+        head,
+        w.reorg_depth,
+        w.start_block,
+    );
+
+    while from <= head {
+        let to = (from + LOG_CHUNK_BLOCKS - 1).min(head);
+        let filter = Filter::new()
+            .address(w.address)
+            .topic0(PaidFilter::signature())
+            .from_block(from)
+            .to_block(to);
+        for log in provider.get_logs(&filter).await? {
+            record_log(pool, w, log).await?;
+        }
+        sqlx::query(
+            r#"INSERT INTO x402_watcher_state (chain_id, contract_address, last_block)
+               VALUES ($1, $2, $3)
+               ON CONFLICT (chain_id, contract_address)
+               DO UPDATE SET last_block = EXCLUDED.last_block, updated_at = NOW()"#,
+        )
+        .bind(w.chain_id as i64)
+        .bind(w.contract)
+        .bind(to as i64)
+        .execute(pool)
+        .await?;
+        from = to + 1;
+    }
+
+    apply_confirmed(pool, provider, w, head).await
+}
+
+/// First block to scan: just after the saved progress, but never above the
+/// reorg window, so recent blocks are always looked at again.
+fn scan_start(
+    last_block: Option<u64>,
+    head: u64,
+    reorg_depth: u64,
+    start_block: Option<u64>,
+) -> u64 {
+    let window_start = head.saturating_sub(reorg_depth);
+    match last_block {
+        Some(last) => (last + 1).min(window_start),
+        None => start_block.unwrap_or(window_start),
+    }
+}
+
+/// Whether a log in `block` has `confirmations` blocks at `head` (its own
+/// block counts as the first).
+fn is_confirmed(block: u64, head: u64, confirmations: u64) -> bool {
+    head + 1 >= block + confirmations.max(1)
+}
+
+async fn record_log(pool: &PgPool, w: &Watched<'_>, log: Log) -> Result<()> {
+    if log.removed == Some(true) {
+        return Ok(());
+    }
+    let (Some(block_number), Some(block_hash), Some(tx_hash)) =
+        (log.block_number, log.block_hash, log.transaction_hash)
+    else {
+        return Ok(()); // pending log
+    };
+    let log_index = log.log_index.unwrap_or_default().as_u64();
+    let ev: PaidFilter = match parse_log(log) {
+        Ok(ev) => ev,
+        Err(e) => {
+            warn!("⚠️ Undecodable Paid log in tx {:?}: {}", tx_hash, e);
+            return Ok(());
+        }
+    };
+
+    // Normalisasi hash: 0x + lowercase
+    let invoice_hash = format!("0x{}", hex::encode(ev.invoice_uid));
+    sqlx::query(
+        r#"INSERT INTO x402_chain_events
+             (chain_id, contract_address, tx_hash, log_index, block_number, block_hash,
+              invoice_uid_hash, payer, amount_wei, video_id)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::NUMERIC, $10)
+           ON CONFLICT (chain_id, contract_address, tx_hash, invoice_uid_hash) DO UPDATE SET
+             log_index = EXCLUDED.log_index,
+             block_number = EXCLUDED.block_number,
+             block_hash = EXCLUDED.block_hash,
+             status = CASE WHEN x402_chain_events.status = 'reorged'
+                           THEN 'seen' ELSE x402_chain_events.status END,
+             reorged_at = CASE WHEN x402_chain_events.status = 'reorged'
+                               THEN NULL ELSE x402_chain_events.reorged_at END"#,
+    )
+    .bind(w.chain_id as i64)
+    .bind(w.contract)
+    .bind(format!("{:?}", tx_hash))
+    .bind(log_index as i64)
+    .bind(block_number.as_u64() as i64)
+    .bind(format!("{:?}", block_hash))
+    .bind(&invoice_hash)
+    .bind(format!("{:?}", ev.payer))
+    .bind(ev.amount_wei.to_string())
+    .bind(&ev.video_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn canonical_hash<M>(provider: &M, block: u64) -> Result<Option<String>>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    Ok(provider
+        .get_block(block)
+        .await?
+        .and_then(|b| b.hash)
+        .map(|h| format!("{:?}", h)))
+}
+
+/// Compares the blocks of recorded logs in the reorg window with the chain.
+async fn check_reorgs<M>(pool: &PgPool, provider: &M, w: &Watched<'_>, from: u64) -> Result<()>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    let rows = sqlx::query(
+        r#"SELECT id, tx_hash, block_number, block_hash, invoice_uid_hash
+           FROM x402_chain_events
+           WHERE chain_id = $1 AND contract_address = $2 AND status <> 'reorged'
+             AND block_number >= $3
+           ORDER BY block_number"#,
+    )
+    .bind(w.chain_id as i64)
+    .bind(w.contract)
+    .bind(from as i64)
+    .fetch_all(pool)
+    .await?;
+
+    for row in rows {
+        let id: i64 = row.get("id");
+        let block_number: i64 = row.get("block_number");
+        let block_hash: String = row.get("block_hash");
+        if canonical_hash(provider, block_number as u64)
+            .await?
+            .as_deref()
+            == Some(&block_hash)
+        {
+            continue;
+        }
+
+        // The block was replaced; the transaction may have been mined again.
+        let tx_hash: String = row.get("tx_hash");
+        let invoice_hash: String = row.get("invoice_uid_hash");
+        let receipt = provider
+            .get_transaction_receipt(tx_hash.parse::<H256>()?)
+            .await?;
+        let moved = receipt
+            .filter(|r| r.status == Some(U64::one()))
+            .and_then(|r| {
+                let (number, hash) = (r.block_number?, r.block_hash?);
+                r.logs
+                    .iter()
+                    .find(|l| {
+                        l.address == w.address
+                            && l.topics.first() == Some(&PaidFilter::signature())
+                            && l.topics.get(1).map(|t| format!("{:?}", t)).as_deref()
+                                == Some(invoice_hash.as_str())
+                    })
+                    .map(|l| (number, hash, l.log_index.unwrap_or_default()))
+            });
+
+        match moved {
+            Some((number, hash, log_index)) => {
+                sqlx::query(
+                    "UPDATE x402_chain_events SET block_number = $2, block_hash = $3, log_index = $4 \
+                     WHERE id = $1",
+                )
+                .bind(id)
+                .bind(number.as_u64() as i64)
+                .bind(format!("{:?}", hash))
+                .bind(log_index.as_u64() as i64)
+                .execute(pool)
+                .await?;
+                info!("↪️ Paid log of {} moved to block {}", tx_hash, number);
             }
+            None => roll_back(pool, id).await?,
         }
     }
+    Ok(())
+}
+
+/// Marks a log as reorged and undoes what applying it did.
+async fn roll_back(pool: &PgPool, event_id: i64) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let Some(ev) = sqlx::query(
+        "SELECT tx_hash, invoice_uid_hash, status FROM x402_chain_events \
+         WHERE id = $1 AND status <> 'reorged' FOR UPDATE",
+    )
+    .bind(event_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(());
+    };
+    let tx_hash: String = ev.get("tx_hash");
+    let invoice_hash: String = ev.get("invoice_uid_hash");
+    let status: String = ev.get("status");
+
+    sqlx::query(
+        "UPDATE x402_chain_events SET status = 'reorged', reorged_at = NOW() WHERE id = $1",
+    )
+    .bind(event_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // Only undo an invoice this transaction paid (x402_confirm may have
+    // recorded it before the watcher did).
+    let invoice_uid: Option<String> = sqlx::query_scalar(
+        r#"UPDATE x402_invoices
+           SET status = 'pending', paid_at = NULL, tx_hash = NULL, payer_address = NULL,
+               paid_amount_wei = 0
+           WHERE LOWER(invoice_uid_hash) = $1 AND LOWER(tx_hash) = $2
+             AND status IN ('paid', 'underpaid')
+           RETURNING invoice_uid"#,
+    )
+    .bind(&invoice_hash)
+    .bind(&tx_hash)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(uid) = invoice_uid.as_deref() {
+        // The sale did not happen after all: drop its sales record too.
+        sqlx::query(
+            "DELETE FROM purchases WHERE id IN \
+             (SELECT purchase_id FROM entitlements WHERE reference = $1 AND source = $2)",
+        )
+        .bind(uid)
+        .bind(Source::X402.as_str())
+        .execute(&mut *tx)
+        .await?;
+        let revoked = entitlements::revoke(&mut tx, Revoke::Reference(uid), "reorg").await?;
+        crate::handlers::refunds::reverse_commissions(&mut tx, uid, 1, 1)
+            .await
+            .map_err(anyhow::Error::msg)?;
+        warn!(
+            "⛓️ Paid log of {} reorged out: invoice {} back to pending ({} grant(s) revoked, was {})",
+            tx_hash, uid, revoked, status
+        );
+    } else {
+        warn!("⛓️ Paid log of {} reorged out (was {})", tx_hash, status);
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
 
+/// Applies recorded logs with enough confirmations, oldest first.
+async fn apply_confirmed<M>(pool: &PgPool, provider: &M, w: &Watched<'_>, head: u64) -> Result<()>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    let rows = sqlx::query(
+        r#"SELECT id, tx_hash, block_number, block_hash, invoice_uid_hash, payer,
+                  amount_wei::TEXT AS amount_wei, video_id
+           FROM x402_chain_events
+           WHERE chain_id = $1 AND contract_address = $2 AND status = 'seen'
+           ORDER BY block_number, log_index"#,
+    )
+    .bind(w.chain_id as i64)
+    .bind(w.contract)
+    .fetch_all(pool)
+    .await?;
+
+    for row in rows {
+        let block_number: i64 = row.get("block_number");
+        if !is_confirmed(block_number as u64, head, w.confirmations) {
+            break;
+        }
+        let block_hash: String = row.get("block_hash");
+        if canonical_hash(provider, block_number as u64)
+            .await?
+            .as_deref()
+            != Some(&block_hash)
+        {
+            continue; // the next reorg check settles it
+        }
+
+        let invoice_hash: String = row.get("invoice_uid_hash");
+        let payer: String = row.get("payer");
+        let video_id: String = row.get("video_id");
+        let amount_wei: String = row.get("amount_wei");
+        let tx_hash: String = row.get("tx_hash");
+        info!(
+            "💰 Paid: hash={}, payer={}, video_id={}, amount={}",
+            invoice_hash, payer, video_id, amount_wei
+        );
+
+        let event_id: i64 = row.get("id");
+        // One log that cannot be applied must not hold back the rest.
+        let outcome = match handle_paid_event(
+            pool,
+            &invoice_hash,
+            &payer,
+            &video_id,
+            &amount_wei,
+            &tx_hash,
+        )
+        .await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error!("💥 Applying Paid log of {} failed: {e}", tx_hash);
+                sqlx::query(
+                    "UPDATE x402_chain_events SET status = 'failed', last_error = $2, \
+                     applied_at = NOW() WHERE id = $1",
+                )
+                .bind(event_id)
+                .bind(e.to_string())
+                .execute(pool)
+                .await?;
+                continue;
+            }
+        };
+        sqlx::query("UPDATE x402_chain_events SET status = $2, applied_at = NOW() WHERE id = $1")
+            .bind(event_id)
+            .bind(outcome)
+            .execute(pool)
+            .await?;
+    }
     Ok(())
 }
 
+/// Applies one confirmed Paid log; returns the event status to record.
 async fn handle_paid_event(
     pool: &PgPool,
     invoice_hash: &str,
     payer: &str,
     video_id: &str,
-) -> Result<()> {
+    amount_wei: &str,
+    tx_hash: &str,
+) -> Result<&'static str> {
+    // The invoice turns paid in the same transaction as its grant, so a
+    // failed grant leaves it pending and the next pass grants it again.
+    let mut tx = pool.begin().await?;
     // gunakan sqlx::query (runtime-checked) agar build tidak perlu akses DB
     let rec = sqlx::query(
-        r#"SELECT id, user_id 
-           FROM x402_invoices 
-           WHERE LOWER(invoice_uid_hash) = $1 
-           LIMIT 1"#,
+        r#"SELECT id, user_id, invoice_uid, rental_hours, status, tx_hash,
+                  $2::NUMERIC >= COALESCE(required_amount_wei, 0) AS fully_paid
+           FROM x402_invoices
+           WHERE LOWER(invoice_uid_hash) = $1
+           LIMIT 1
+           FOR UPDATE"#,
     )
     .bind(invoice_hash)
-    .fetch_optional(pool)
+    .bind(amount_wei)
+    .fetch_optional(&mut *tx)
     .await?;
 
-    if let Some(row) = rec {
-        let inv_id: i64 = row.get("id");
-        let user_id: String = row.get("user_id");
+    let Some(row) = rec else {
+        error!("⚠️ No matching invoice for hash {}", invoice_hash);
+        return Ok("unmatched");
+    };
+    let inv_id: i64 = row.get("id");
+    let user_id: String = row.get("user_id");
+    let fully_paid: bool = row.get("fully_paid");
+    let status: String = row.get("status");
+    let known_tx: Option<String> = row.get("tx_hash");
 
-        // Update invoice -> paid
-        sqlx::query(
-            r#"UPDATE x402_invoices 
-               SET status='paid', paid_at=NOW(), payer_address=$1 
-               WHERE id=$2"#,
-        )
-        .bind(payer)
-        .bind(inv_id)
-        .execute(pool)
-        .await?;
+    // x402_confirm may have recorded this transaction already.
+    if status == "paid" {
+        info!("↩️ Invoice {} already paid", invoice_hash);
+        return Ok("applied");
+    }
+    if status == "underpaid" && known_tx.is_some_and(|known| known.eq_ignore_ascii_case(tx_hash)) {
+        return Ok("underpaid");
+    }
 
-        // Ambil username user
-        let uname =
-            sqlx::query_scalar::<_, Option<String>>(r#"SELECT username FROM users WHERE id=$1"#)
-                .bind(&user_id)
-                .fetch_one(pool)
-                .await?
-                .unwrap_or_default();
-
-        if !uname.is_empty() {
-            // purchases (idempotent)
-            sqlx::query(
-                r#"INSERT INTO purchases (user_id, video_id, created_at)
-                   VALUES ($1,$2,NOW())
-                   ON CONFLICT DO NOTHING"#,
-            )
-            .bind(&user_id)
-            .bind(video_id)
-            .execute(pool)
-            .await?;
+    // Same rule as x402_confirm: less than the required amount is only
+    // recorded, never granted.
+    sqlx::query(
+        r#"UPDATE x402_invoices
+           SET status=$3, paid_at=NOW(), payer_address=$1,
+               paid_amount_wei=$4::NUMERIC, tx_hash=$5
+           WHERE id=$2"#,
+    )
+    .bind(payer)
+    .bind(inv_id)
+    .bind(if fully_paid { "paid" } else { "underpaid" })
+    .bind(amount_wei)
+    .bind(tx_hash)
+    .execute(&mut *tx)
+    .await?;
+    if !fully_paid {
+        tx.commit().await?;
+        error!("⚠️ Underpaid invoice {}: {} wei", invoice_hash, amount_wei);
+        return Ok("underpaid");
+    }
 
-            // allowlist (idempotent)
-            sqlx::query(
-                r#"INSERT INTO allowlist (video_id, username)
-                   VALUES ($1,$2)
-                   ON CONFLICT (video_id, username) DO NOTHING"#,
-            )
-            .bind(video_id)
-            .bind(&uname)
-            .execute(pool)
-            .await?;
+    let invoice_uid: String = row.get("invoice_uid");
+    crate::handlers::coupons::confirm_redemption(&mut *tx, &invoice_uid).await?;
 
-            info!("✅ Access granted for {} on video {}", uname, video_id);
-        } else {
-            info!(
-                "ℹ️ Invoice matched but username not found (user_id={})",
-                user_id
-            );
-        }
+    let origin = Origin::new(Source::X402, Some(&invoice_uid));
+    let rental_hours: Option<i32> = row.try_get("rental_hours").ok().flatten();
+    if let Some(hours) = rental_hours {
+        // rentals expire: time-limited entitlement only
+        crate::handlers::rentals::record_rental(&mut tx, &user_id, video_id, hours, origin).await?;
     } else {
-        error!("⚠️ No matching invoice for hash {}", invoice_hash);
+        // idempotent: an existing live entitlement is kept
+        entitlements::grant_video(&mut tx, &user_id, video_id, origin).await?;
+    }
+    tx.commit().await?;
+    if rental_hours.is_some() {
+        info!("✅ Rental granted for {} on video {}", user_id, video_id);
+    } else {
+        info!("✅ Access granted for {} on video {}", user_id, video_id);
     }
 
-    Ok(())
+    Ok("applied")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scans_resume_but_always_cover_the_reorg_window() {
+        // first run: configured start block, else the reorg window
+        assert_eq!(scan_start(None, 1000, 64, Some(10)), 10);
+        assert_eq!(scan_start(None, 1000, 64, None), 936);
+        // behind: resume right after the saved block
+        assert_eq!(scan_start(Some(500), 1000, 64, None), 501);
+        // caught up: rescan the window
+        assert_eq!(scan_start(Some(1000), 1000, 64, None), 936);
+        assert_eq!(scan_start(Some(5), 10, 64, None), 0);
+    }
+
+    #[test]
+    fn confirmations_count_the_log_block() {
+        assert!(is_confirmed(100, 100, 1));
+        assert!(!is_confirmed(100, 101, 3));
+        assert!(is_confirmed(100, 102, 3));
+        assert!(is_confirmed(100, 100, 0));
+    }
 }
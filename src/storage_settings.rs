@@ -148,6 +148,8 @@ pub async fn load_storage_settings(pool: &PgPool) -> StoredStorageSettings {
     }
 }
 
+/// Regular files under `root`, recursively. Symbolic links are skipped, so a
+/// link cannot pull in files from outside `root`.
 pub async fn collect_local_files(root: &Path) -> Result<Vec<PathBuf>> {
     let mut files = Vec::new();
     if !root.exists() {
@@ -161,10 +163,10 @@ pub async fn collect_local_files(root: &Path) -> Result<Vec<PathBuf>> {
             .with_context(|| format!("read_dir {}", current.display()))?;
         while let Some(entry) = entries.next_entry().await? {
             let path = entry.path();
-            let meta = entry.metadata().await?;
-            if meta.is_dir() {
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
                 stack.push(path);
-            } else {
+            } else if file_type.is_file() {
                 files.push(path);
             }
         }
@@ -1,7 +1,12 @@
 // src/worker.rs
 // Background transcoding queue and FFmpeg job processor.
 
-use crate::{config::Config, ffmpeg::run_ffmpeg, plugins::storage::StoragePlugin};
+use crate::{
+    config::Config,
+    ffmpeg::{ffprobe_dimensions, ffprobe_duration, run_ffmpeg},
+    plugins::storage::StoragePlugin,
+    quarantine::Quarantine,
+};
 use anyhow::{anyhow, Context, Result};
 use sqlx::PgPool;
 use std::{
@@ -32,6 +37,7 @@ impl Worker {
         pool: PgPool,
         cfg: Config,
         storage: Arc<dyn StoragePlugin>,
+        quarantine: Quarantine,
         concurrency: usize,
     ) -> Self {
         let (tx, mut rx) = mpsc::channel::<TranscodeJob>(1024);
@@ -40,6 +46,7 @@ impl Worker {
         let _handle: JoinHandle<()> = tokio::spawn({
             let semaphore = semaphore.clone();
             let storage = storage.clone();
+            let quarantine = quarantine.clone();
             async move {
                 while let Some(job) = rx.recv().await {
                     let permit = match semaphore.clone().acquire_owned().await {
@@ -52,9 +59,10 @@ impl Worker {
                     let pool = pool.clone();
                     let cfg = cfg.clone();
                     let storage = storage.clone();
+                    let quarantine = quarantine.clone();
                     tokio::spawn(async move {
                         let _permit = permit;
-                        if let Err(e) = process_job(&pool, &cfg, storage, job).await {
+                        if let Err(e) = process_job(&pool, &cfg, storage, &quarantine, job).await {
                             error!("transcode job failed: {e}");
                         }
                     });
@@ -86,8 +94,54 @@ async fn process_job(
     pool: &PgPool,
     cfg: &Config,
     storage: Arc<dyn StoragePlugin>,
+    quarantine: &Quarantine,
     job: TranscodeJob,
 ) -> Result<()> {
+    // Last quarantine step: the full decode. The video stays 'quarantined'
+    // (and off remote storage) until it passes.
+    if let Err(rejection) = quarantine.verify_decode(Path::new(&job.input_path)).await {
+        if rejection.is_decoder_failure() {
+            if let Err(update_err) =
+                update_video_error(pool, &job.video_id, &rejection.to_string()).await
+            {
+                error!("failed to persist decoder error: {update_err}");
+            }
+            return Err(anyhow!("decode check for {}: {rejection}", job.video_id));
+        }
+        let _ = fs::remove_file(&job.input_path).await;
+        sqlx::query!(
+            "UPDATE videos SET processing_state='rejected', last_error=$2 WHERE id=$1",
+            job.video_id,
+            rejection.to_string()
+        )
+        .execute(pool)
+        .await
+        .with_context(|| format!("mark video {} as rejected", job.video_id))?;
+        warn!(
+            video_id = %job.video_id,
+            code = rejection.code,
+            "upload rejected in quarantine: {}",
+            rejection.reason
+        );
+        return Ok(());
+    }
+
+    // Push original to remote storage backend (fire-and-forget, non-fatal).
+    // No-op when STORAGE_BACKEND=local.
+    if !storage.is_local() {
+        let storage = storage.clone();
+        let input = PathBuf::from(&job.input_path);
+        if let Some(filename) = input.file_name().map(|n| n.to_string_lossy().into_owned()) {
+            let key = format!("uploads/{filename}");
+            tokio::spawn(async move {
+                match storage.put_file(&key, &input).await {
+                    Ok(_) => info!("storage: pushed original {key}"),
+                    Err(e) => warn!("storage: original push {key} non-fatal: {e}"),
+                }
+            });
+        }
+    }
+
     sqlx::query!(
         "UPDATE videos SET processing_state = 'processing', last_error = NULL WHERE id = $1",
         job.video_id
@@ -134,10 +188,24 @@ async fn process_job(
             let master_abs = Path::new(&job.out_dir).join(&master_name);
             let master_abs_owned = master_abs.to_string_lossy().into_owned();
 
+            // Source metadata for catalog filters (duration) and display.
+            let duration_sec = ffprobe_duration(&tmp_mp4)
+                .await
+                .map(|secs| secs.round() as i32);
+            let (width, height) = match ffprobe_dimensions(&tmp_mp4).await {
+                Some((w, h)) => (Some(w as i32), Some(h as i32)),
+                None => (None, None),
+            };
+
             if let Err(e) = sqlx::query!(
-                "UPDATE videos SET hls_ready = TRUE, hls_master = $2, processing_state='ready', last_error=NULL WHERE id=$1",
+                "UPDATE videos SET hls_ready = TRUE, hls_master = $2, processing_state='ready', last_error=NULL, \
+                 duration_sec = COALESCE($3, duration_sec), width = COALESCE($4, width), height = COALESCE($5, height) \
+                 WHERE id=$1",
                 job.video_id,
-                master_abs_owned.as_str()
+                master_abs_owned.as_str(),
+                duration_sec,
+                width,
+                height
             )
             .execute(pool)
             .await
@@ -0,0 +1,196 @@
+// src/x402_chains.rs
+//
+// EVM chains x402 payments are accepted on.
+//
+// Admins configure chains in `x402_chains`: the X402Splitter deployment, RPC
+// endpoints and confirmation depth of each. The tokens offered on a chain are
+// its active `pay_tokens` rows. The chain set by the X402_* environment
+// variables stays available as a default, so single-chain deployments keep
+// working, until a row with its chain id exists (an inactive row disables it).
+
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct X402Chain {
+    pub chain_id: i64,
+    pub name: String,
+    /// X402Splitter address, lowercase 0x-hex.
+    pub contract: String,
+    /// Receipts and block numbers for `/api/pay/x402/confirm`.
+    #[serde(skip)]
+    pub rpc_http: String,
+    /// Watcher endpoint; `rpc_http` when unset.
+    #[serde(skip)]
+    pub rpc_wss: Option<String>,
+    /// Blocks a payment needs, its own included, before access is granted.
+    pub confirmations: u64,
+    /// First block the watcher scans when it has no saved progress.
+    #[serde(skip)]
+    pub start_block: Option<u64>,
+}
+
+impl X402Chain {
+    /// The chain set by X402_CHAIN_ID, X402_CONTRACT_ADDRESS, X402_RPC_HTTP,
+    /// X402_RPC_WSS, X402_CONFIRMATIONS and X402_START_BLOCK, if any.
+    pub fn from_env() -> Option<Self> {
+        let var = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let chain_id = var("X402_CHAIN_ID")?
+            .parse::<i64>()
+            .ok()
+            .filter(|id| *id > 0)?;
+        let contract = var("X402_CONTRACT_ADDRESS")?.to_ascii_lowercase();
+        if !is_contract_address(&contract) {
+            return None;
+        }
+        Some(X402Chain {
+            chain_id,
+            name: format!("Chain {chain_id}"),
+            contract,
+            rpc_http: var("X402_RPC_HTTP").unwrap_or_default(),
+            rpc_wss: var("X402_RPC_WSS"),
+            confirmations: var("X402_CONFIRMATIONS")
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(3)
+                .max(1),
+            start_block: var("X402_START_BLOCK").and_then(|v| v.parse::<u64>().ok()),
+        })
+    }
+
+    /// Endpoint the watcher follows.
+    #[cfg_attr(not(feature = "x402-watcher"), allow(dead_code))]
+    pub fn watch_url(&self) -> &str {
+        self.rpc_wss.as_deref().unwrap_or(&self.rpc_http)
+    }
+}
+
+/// A non-zero 0x-prefixed 20-byte hex address.
+pub fn is_contract_address(value: &str) -> bool {
+    let Some(hex) = value.strip_prefix("0x") else {
+        return false;
+    };
+    hex.len() == 40 && hex.bytes().all(|b| b.is_ascii_hexdigit()) && hex.bytes().any(|b| b != b'0')
+}
+
+/// Active chains, ordered by chain id.
+pub async fn load_chains(pool: &PgPool) -> Result<Vec<X402Chain>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"SELECT c.chain_id, c.name, c.contract_address, c.rpc_http, c.rpc_wss,
+                  c.confirmations, c.start_block, c.is_active
+           FROM x402_chains c
+           ORDER BY c.chain_id"#,
+    )
+    .fetch_all(pool)
+    .await?;
+    let configured = rows
+        .iter()
+        .map(|r| {
+            let chain = X402Chain {
+                chain_id: r.try_get("chain_id")?,
+                name: r.try_get("name")?,
+                contract: r
+                    .try_get::<String, _>("contract_address")?
+                    .to_ascii_lowercase(),
+                rpc_http: r.try_get("rpc_http")?,
+                rpc_wss: r
+                    .try_get::<Option<String>, _>("rpc_wss")?
+                    .filter(|v| !v.trim().is_empty()),
+                confirmations: r.try_get::<i32, _>("confirmations")?.max(1) as u64,
+                start_block: r
+                    .try_get::<Option<i64>, _>("start_block")?
+                    .map(|b| b.max(0) as u64),
+            };
+            Ok((chain, r.try_get::<bool, _>("is_active")?))
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+    let mut env = X402Chain::from_env();
+    if let Some(chain) = env.as_mut() {
+        // Name it like its tokens, e.g. "Polygon".
+        if let Some(name) = sqlx::query_scalar::<_, String>(
+            "SELECT chain FROM pay_tokens WHERE chain_id = $1 ORDER BY id LIMIT 1",
+        )
+        .bind(chain.chain_id)
+        .fetch_optional(pool)
+        .await?
+        {
+            chain.name = name;
+        }
+    }
+    Ok(merge(configured, env))
+}
+
+/// The active chain with this id.
+pub async fn find_chain(pool: &PgPool, chain_id: i64) -> Result<Option<X402Chain>, sqlx::Error> {
+    Ok(load_chains(pool)
+        .await?
+        .into_iter()
+        .find(|c| c.chain_id == chain_id))
+}
+
+/// Active configured chains plus the environment chain when no row has its id.
+fn merge(configured: Vec<(X402Chain, bool)>, env: Option<X402Chain>) -> Vec<X402Chain> {
+    let env = env.filter(|e| !configured.iter().any(|(c, _)| c.chain_id == e.chain_id));
+    let mut chains: Vec<X402Chain> = configured
+        .into_iter()
+        .filter_map(|(chain, active)| active.then_some(chain))
+        .chain(env)
+        .collect();
+    chains.sort_by_key(|c| c.chain_id);
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(chain_id: i64, contract: &str) -> X402Chain {
+        X402Chain {
+            chain_id,
+            name: format!("Chain {chain_id}"),
+            contract: contract.into(),
+            rpc_http: "http://rpc".into(),
+            rpc_wss: None,
+            confirmations: 3,
+            start_block: None,
+        }
+    }
+
+    #[test]
+    fn rows_override_the_environment_chain() {
+        let env = chain(137, "0xenv");
+        let merged = merge(vec![(chain(8453, "0xbase"), true)], Some(env.clone()));
+        assert_eq!(
+            merged.iter().map(|c| c.chain_id).collect::<Vec<_>>(),
+            [137, 8453]
+        );
+
+        let merged = merge(vec![(chain(137, "0xrow"), true)], Some(env.clone()));
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].contract, "0xrow");
+
+        // An inactive row turns the chain off, environment included.
+        assert!(merge(vec![(chain(137, "0xrow"), false)], Some(env)).is_empty());
+    }
+
+    #[test]
+    fn contract_addresses_must_be_non_zero_hex() {
+        assert!(is_contract_address(
+            "0x5fbdb2315678afecb367f032d93f642f64180aa3"
+        ));
+        assert!(!is_contract_address(
+            "0x0000000000000000000000000000000000000000"
+        ));
+        assert!(!is_contract_address(
+            "5fbdb2315678afecb367f032d93f642f64180aa3"
+        ));
+        assert!(!is_contract_address(
+            "0x5fbdb2315678afecb367f032d93f642f64180aa"
+        ));
+    }
+}